@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::email::server_presets::{AuthType, ProviderType};
+use crate::email::server_presets::{AuthType, ProviderType, ServerConfig, TlsMode};
 
 /// Represents a connected email account
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,10 +13,28 @@ pub struct Account {
     pub imap_port: u16,
     pub smtp_host: String,
     pub smtp_port: u16,
+    /// IMAP connection security
+    #[serde(default)]
+    pub tls_mode: TlsMode,
+    /// SMTP connection security; chosen from the port when unset
+    #[serde(default)]
+    pub smtp_tls_mode: Option<TlsMode>,
     pub auth_type: String,
     pub is_active: bool,
     pub created_at: i64,
     pub last_synced_at: Option<i64>,
+    /// Background sync (IDLE, catch-up) is suspended for this account
+    #[serde(default)]
+    pub sync_paused: bool,
+    /// While paused, also refuse manual fetches from the server
+    #[serde(default)]
+    pub block_manual_fetch: bool,
+    /// Plain text signature appended to outgoing mail
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Signature used in the HTML part; the plain one is used when unset
+    #[serde(default)]
+    pub html_signature: Option<String>,
 }
 
 impl Account {
@@ -24,10 +42,7 @@ impl Account {
         email: String,
         display_name: String,
         provider: ProviderType,
-        imap_host: String,
-        imap_port: u16,
-        smtp_host: String,
-        smtp_port: u16,
+        server_config: ServerConfig,
         auth_type: AuthType,
     ) -> Self {
         Self {
@@ -35,10 +50,12 @@ impl Account {
             email,
             display_name,
             provider: provider.as_str().to_string(),
-            imap_host,
-            imap_port,
-            smtp_host,
-            smtp_port,
+            imap_host: server_config.imap_host,
+            imap_port: server_config.imap_port,
+            smtp_host: server_config.smtp_host,
+            smtp_port: server_config.smtp_port,
+            tls_mode: server_config.tls_mode,
+            smtp_tls_mode: server_config.smtp_tls_mode,
             auth_type: match auth_type {
                 AuthType::OAuth2 => "oauth2".to_string(),
                 AuthType::Password => "password".to_string(),
@@ -46,6 +63,10 @@ impl Account {
             is_active: true,
             created_at: chrono::Utc::now().timestamp(),
             last_synced_at: None,
+            sync_paused: false,
+            block_manual_fetch: false,
+            signature: None,
+            html_signature: None,
         }
     }
 
@@ -53,6 +74,22 @@ impl Account {
         ProviderType::from_str(&self.provider)
     }
 
+    pub fn server_config(&self) -> ServerConfig {
+        ServerConfig {
+            imap_host: self.imap_host.clone(),
+            imap_port: self.imap_port,
+            smtp_host: self.smtp_host.clone(),
+            smtp_port: self.smtp_port,
+            tls_mode: self.tls_mode,
+            smtp_tls_mode: self.smtp_tls_mode,
+        }
+    }
+
+    /// Mailbox identity used to detect the same account added twice
+    pub fn normalized_email(&self) -> String {
+        normalize_mailbox_address(&self.email)
+    }
+
     pub fn auth_type_enum(&self) -> AuthType {
         match self.auth_type.as_str() {
             "oauth2" => AuthType::OAuth2,
@@ -60,3 +97,21 @@ impl Account {
         }
     }
 }
+
+/// Normalize an address so that different spellings of the same mailbox compare equal.
+/// Addresses are case-insensitive in practice, and Gmail ignores dots and `+tags`
+/// in the local part.
+pub fn normalize_mailbox_address(address: &str) -> String {
+    let address = address.trim().to_lowercase();
+    let Some((local, domain)) = address.rsplit_once('@') else {
+        return address;
+    };
+
+    match domain {
+        "gmail.com" | "googlemail.com" => {
+            let local = local.split('+').next().unwrap_or(local).replace('.', "");
+            format!("{}@gmail.com", local)
+        }
+        _ => address,
+    }
+}
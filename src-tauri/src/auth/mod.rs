@@ -4,7 +4,7 @@ pub mod storage;
 
 pub use account::Account;
 pub use oauth::{
-    handle_oauth_callback, refresh_access_token, refresh_access_token_for_provider,
+    handle_oauth_callback, refresh_access_token, refresh_access_token_for_provider, revoke_token,
     start_oauth_flow, start_oauth_flow_for_provider,
 };
 pub use storage::{clear_tokens, get_tokens, has_valid_tokens, TokenData};
@@ -2,13 +2,15 @@ use anyhow::{Context, Result};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{Duration, Utc};
 use oauth2::{
-    basic::BasicClient, reqwest::async_http_client, AuthUrl, AuthorizationCode, ClientId,
-    ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
-    TokenResponse, TokenUrl,
+    basic::BasicClient, reqwest::async_http_client, AccessToken, AuthUrl, AuthorizationCode,
+    ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl,
+    RefreshToken, RevocationUrl, Scope, StandardRevocableToken, TokenResponse, TokenUrl,
 };
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Mutex;
+use std::time::Instant;
 use tokio::sync::oneshot;
 
 use super::storage::{store_account_tokens, store_tokens, TokenData};
@@ -17,6 +19,37 @@ use super::storage::{store_account_tokens, store_tokens, TokenData};
 
 const REDIRECT_URI: &str = "http://localhost:3000/callback";
 
+/// Authorization requests whose redirect hasn't arrived within this long are dropped
+const OAUTH_FLOW_TTL_SECS: u64 = 10 * 60;
+
+/// How the PKCE code challenge is derived from the verifier (RFC 7636)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PkceMethod {
+    /// Base64url SHA-256 of the verifier
+    S256,
+    /// The verifier itself, for providers that don't accept S256
+    Plain,
+}
+
+impl PkceMethod {
+    /// The method named in the `var` environment variable ("S256" or
+    /// "plain"), or S256 when it's unset or unrecognized
+    fn from_env(var: &str) -> Self {
+        std::env::var(var)
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or(Self::S256)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            v if v.eq_ignore_ascii_case("S256") => Some(Self::S256),
+            v if v.eq_ignore_ascii_case("plain") => Some(Self::Plain),
+            _ => None,
+        }
+    }
+}
+
 /// Provider-specific OAuth configuration
 #[derive(Debug, Clone)]
 pub struct OAuthProviderConfig {
@@ -25,6 +58,9 @@ pub struct OAuthProviderConfig {
     pub scopes: Vec<String>,
     pub client_id_env: &'static str,
     pub client_secret_env: &'static str,
+    pub pkce_method: PkceMethod,
+    /// RFC 7009 token revocation endpoint, if the provider documents one
+    pub revocation_url: Option<String>,
 }
 
 /// Get OAuth config for Google (Gmail IMAP access)
@@ -36,6 +72,8 @@ pub fn google_oauth_config() -> OAuthProviderConfig {
         scopes: vec!["https://mail.google.com/".to_string()],
         client_id_env: "GOOGLE_CLIENT_ID",
         client_secret_env: "GOOGLE_CLIENT_SECRET",
+        pkce_method: PkceMethod::from_env("GOOGLE_PKCE_METHOD"),
+        revocation_url: Some("https://oauth2.googleapis.com/revoke".to_string()),
     }
 }
 
@@ -51,6 +89,10 @@ pub fn microsoft_oauth_config() -> OAuthProviderConfig {
         ],
         client_id_env: "MICROSOFT_CLIENT_ID",
         client_secret_env: "MICROSOFT_CLIENT_SECRET",
+        pkce_method: PkceMethod::from_env("MICROSOFT_PKCE_METHOD"),
+        // The Microsoft identity platform has no token revocation endpoint;
+        // grants are revoked from the user's account page or via Graph
+        revocation_url: None,
     }
 }
 
@@ -96,26 +138,52 @@ fn get_client_secret() -> Option<String> {
 
 // ========== OAuth State ==========
 
+/// An authorization request waiting for its redirect, keyed by its `state`
+/// parameter until the callback arrives
 pub struct OAuthState {
     pub pkce_verifier: PkceCodeVerifier,
-    pub csrf_token: CsrfToken,
-    pub callback_receiver: Option<oneshot::Receiver<Result<String>>>,
     pub account_id: Option<String>,
     pub provider: String,
+    pub started_at: Instant,
 }
 
 lazy_static::lazy_static! {
-    static ref OAUTH_STATE: Mutex<Option<OAuthState>> = Mutex::new(None);
+    static ref OAUTH_STATES: Mutex<HashMap<String, OAuthState>> = Mutex::new(HashMap::new());
+    /// Query string of the next redirect received by the callback server
+    static ref CALLBACK_RECEIVER: Mutex<Option<oneshot::Receiver<Result<String>>>> =
+        Mutex::new(None);
+}
+
+/// Remember a pending authorization request, dropping ones that expired
+fn register_oauth_state(state: &str, pending: OAuthState) {
+    let mut states = OAUTH_STATES.lock().unwrap();
+    states.retain(|_, p| p.started_at.elapsed().as_secs() < OAUTH_FLOW_TTL_SECS);
+    states.insert(state.to_string(), pending);
+}
+
+/// Take the pending request named by the callback's `state` parameter. Fails
+/// if it is missing or unknown, e.g. a forged or replayed redirect.
+fn take_oauth_state(params: &HashMap<String, String>) -> Result<OAuthState> {
+    let state = params.get("state").context("No state in OAuth callback")?;
+    OAUTH_STATES
+        .lock()
+        .unwrap()
+        .remove(state)
+        .filter(|p| p.started_at.elapsed().as_secs() < OAUTH_FLOW_TTL_SECS)
+        .context("OAuth state mismatch: the callback doesn't match a pending sign-in")
 }
 
 // ========== PKCE ==========
 
-fn generate_pkce() -> (PkceCodeVerifier, PkceCodeChallenge) {
+fn generate_pkce(method: PkceMethod) -> (PkceCodeVerifier, PkceCodeChallenge) {
     let mut rng = rand::thread_rng();
     let random_bytes: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
     let code_verifier = URL_SAFE_NO_PAD.encode(random_bytes);
     let verifier = PkceCodeVerifier::new(code_verifier);
-    let code_challenge = PkceCodeChallenge::from_code_verifier_sha256(&verifier);
+    let code_challenge = match method {
+        PkceMethod::S256 => PkceCodeChallenge::from_code_verifier_sha256(&verifier),
+        PkceMethod::Plain => PkceCodeChallenge::from_code_verifier_plain(&verifier),
+    };
     (verifier, code_challenge)
 }
 
@@ -149,7 +217,7 @@ pub fn start_oauth_flow_for_provider(provider: &str, account_id: Option<&str>) -
     let config = get_provider_config(provider);
     let client = create_oauth_client_for_provider(&config)?;
 
-    let (pkce_verifier, pkce_challenge) = generate_pkce();
+    let (pkce_verifier, pkce_challenge) = generate_pkce(config.pkce_method);
 
     let mut auth_request = client
         .authorize_url(CsrfToken::new_random)
@@ -163,14 +231,16 @@ pub fn start_oauth_flow_for_provider(provider: &str, account_id: Option<&str>) -
 
     let (tx, rx) = oneshot::channel();
 
-    let mut state = OAUTH_STATE.lock().unwrap();
-    *state = Some(OAuthState {
-        pkce_verifier,
-        csrf_token,
-        callback_receiver: Some(rx),
-        account_id: account_id.map(|s| s.to_string()),
-        provider: provider.to_string(),
-    });
+    register_oauth_state(
+        csrf_token.secret(),
+        OAuthState {
+            pkce_verifier,
+            account_id: account_id.map(|s| s.to_string()),
+            provider: provider.to_string(),
+            started_at: Instant::now(),
+        },
+    );
+    *CALLBACK_RECEIVER.lock().unwrap() = Some(rx);
 
     start_callback_server(tx);
 
@@ -227,28 +297,29 @@ fn start_callback_server(tx: oneshot::Sender<Result<String>>) {
 
 /// Handle OAuth callback — exchanges code for tokens, stores them
 pub async fn handle_oauth_callback() -> Result<TokenData> {
-    let (pkce_verifier, callback_receiver, account_id, provider) = {
-        let mut state_lock = OAUTH_STATE.lock().unwrap();
-        let state = state_lock.take().context("No OAuth flow in progress")?;
-
-        (
-            state.pkce_verifier,
-            state.callback_receiver,
-            state.account_id,
-            state.provider,
-        )
-    };
+    let callback_receiver = CALLBACK_RECEIVER
+        .lock()
+        .unwrap()
+        .take()
+        .context("No OAuth flow in progress")?;
 
     let query_string = callback_receiver
-        .context("No callback receiver")?
         .await
         .context("Failed to receive callback")??;
 
-    let params: std::collections::HashMap<_, _> =
+    let params: HashMap<String, String> =
         url::form_urlencoded::parse(query_string.as_bytes())
             .into_owned()
             .collect();
 
+    // The verifier is only released to the redirect carrying our state
+    let OAuthState {
+        pkce_verifier,
+        account_id,
+        provider,
+        ..
+    } = take_oauth_state(&params)?;
+
     let code = params
         .get("code")
         .context("No authorization code in callback")?;
@@ -316,9 +387,15 @@ pub async fn refresh_access_token_for_provider(
                 .unwrap_or(3600),
         );
 
+    // Providers that rotate refresh tokens return a new one and revoke the old
     let token_data = TokenData {
         access_token: token_response.access_token().secret().clone(),
-        refresh_token: Some(refresh_token.to_string()),
+        refresh_token: Some(
+            token_response
+                .refresh_token()
+                .map(|t| t.secret().clone())
+                .unwrap_or_else(|| refresh_token.to_string()),
+        ),
         expires_at,
     };
 
@@ -330,3 +407,96 @@ pub async fn refresh_access_token_for_provider(
 
     Ok(token_data)
 }
+
+// ========== Token Revocation ==========
+
+/// Revoke a token at the provider so the grant doesn't outlive the account.
+/// Revoking a refresh token also invalidates the access tokens issued from it.
+/// Returns false without contacting the provider if it has no revocation endpoint.
+pub async fn revoke_token(provider: &str, token: &str, is_refresh_token: bool) -> Result<bool> {
+    let config = get_provider_config(provider);
+    let Some(revocation_url) = &config.revocation_url else {
+        return Ok(false);
+    };
+
+    let client = create_oauth_client_for_provider(&config)?.set_revocation_uri(
+        RevocationUrl::new(revocation_url.clone()).context("Failed to create revocation URL")?,
+    );
+    let token = if is_refresh_token {
+        StandardRevocableToken::RefreshToken(RefreshToken::new(token.to_string()))
+    } else {
+        StandardRevocableToken::AccessToken(AccessToken::new(token.to_string()))
+    };
+
+    client
+        .revoke_token(token)
+        .context("Failed to build revocation request")?
+        .request_async(async_http_client)
+        .await
+        .context("Failed to revoke token")?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(provider: &str) -> OAuthState {
+        OAuthState {
+            pkce_verifier: PkceCodeVerifier::new("verifier".to_string()),
+            account_id: Some("acct".to_string()),
+            provider: provider.to_string(),
+            started_at: Instant::now(),
+        }
+    }
+
+    fn callback(state: Option<&str>) -> HashMap<String, String> {
+        let mut params = HashMap::from([("code".to_string(), "abc".to_string())]);
+        if let Some(state) = state {
+            params.insert("state".to_string(), state.to_string());
+        }
+        params
+    }
+
+    #[test]
+    fn test_callback_state_must_match_a_pending_flow() {
+        register_oauth_state("state-a", pending("gmail"));
+        register_oauth_state("state-b", pending("microsoft"));
+
+        let err = take_oauth_state(&callback(Some("forged"))).err().unwrap();
+        assert!(err.to_string().contains("state mismatch"));
+        assert!(take_oauth_state(&callback(None)).is_err());
+
+        // Each verifier goes to the flow that requested it, and only once
+        let flow = take_oauth_state(&callback(Some("state-b"))).unwrap();
+        assert_eq!(flow.provider, "microsoft");
+        assert_eq!(flow.pkce_verifier.secret(), "verifier");
+        assert!(take_oauth_state(&callback(Some("state-b"))).is_err());
+        assert_eq!(
+            take_oauth_state(&callback(Some("state-a")))
+                .unwrap()
+                .provider,
+            "gmail"
+        );
+    }
+
+    #[test]
+    fn test_pkce_challenge_follows_provider_method() {
+        let (verifier, challenge) = generate_pkce(PkceMethod::S256);
+        assert_eq!(challenge.method().as_str(), "S256");
+        assert_ne!(challenge.as_str(), verifier.secret());
+
+        let (verifier, challenge) = generate_pkce(PkceMethod::Plain);
+        assert_eq!(challenge.method().as_str(), "plain");
+        assert_eq!(challenge.as_str(), verifier.secret());
+
+        assert_eq!(PkceMethod::parse("plain"), Some(PkceMethod::Plain));
+        assert_eq!(PkceMethod::parse(" s256 "), Some(PkceMethod::S256));
+        assert_eq!(PkceMethod::parse("S512"), None);
+        assert_eq!(
+            PkceMethod::from_env("INBOXED_TEST_UNSET_PKCE_METHOD"),
+            PkceMethod::S256
+        );
+    }
+}
@@ -3,7 +3,8 @@ use chrono::{DateTime, Utc};
 use keyring::Entry;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 const SERVICE_NAME: &str = "com.inboxed.app";
 const ACCESS_TOKEN_KEY: &str = "gmail_access_token";
@@ -13,6 +14,20 @@ const EXPIRY_KEY: &str = "gmail_token_expiry";
 // Dev mode: use file storage to avoid keychain prompts
 const USE_FILE_STORAGE: bool = cfg!(debug_assertions);
 
+/// Held across each token file write, and the whole read-modify-write of the
+/// per-account file, so concurrent updates (e.g. two accounts refreshing at
+/// once) don't drop each other
+static TOKEN_FILES_LOCK: Mutex<()> = Mutex::new(());
+
+/// Write `contents` to a temp file beside `path` and rename it into place, so
+/// readers never see a partially written file
+fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
 fn get_token_file_path() -> PathBuf {
     // Use a stable location in the user's home directory instead of temp
     if let Ok(home) = std::env::var("HOME") {
@@ -85,8 +100,8 @@ pub fn store_tokens(token_data: &TokenData) -> Result<()> {
             expires_at: Some(token_data.expires_at.to_rfc3339()),
         };
         let json = serde_json::to_string(&storage)?;
-        fs::write(get_token_file_path(), json)?;
-        Ok(())
+        let _guard = TOKEN_FILES_LOCK.lock().unwrap();
+        write_atomically(&get_token_file_path(), &json)
     } else {
         // Production: use keychain
         store_access_token(&token_data.access_token)?;
@@ -228,12 +243,12 @@ fn load_multi_account_storage() -> MultiAccountStorage {
 
 fn save_multi_account_storage(storage: &MultiAccountStorage) -> Result<()> {
     let json = serde_json::to_string(storage)?;
-    fs::write(get_multi_account_file_path(), json)?;
-    Ok(())
+    write_atomically(&get_multi_account_file_path(), &json)
 }
 
 /// Store tokens for a specific account
 pub fn store_account_tokens(account_id: &str, token_data: &TokenData) -> Result<()> {
+    let _guard = TOKEN_FILES_LOCK.lock().unwrap();
     let mut storage = load_multi_account_storage();
     let entry = storage
         .accounts
@@ -282,6 +297,7 @@ pub fn get_account_tokens(account_id: &str) -> Result<TokenData> {
 
 /// Store an app password for a specific account
 pub fn store_app_password(account_id: &str, password: &str) -> Result<()> {
+    let _guard = TOKEN_FILES_LOCK.lock().unwrap();
     let mut storage = load_multi_account_storage();
     let entry = storage
         .accounts
@@ -315,6 +331,7 @@ pub fn get_app_password(account_id: &str) -> Result<String> {
 
 /// Clear all tokens for a specific account
 pub fn clear_account_tokens(account_id: &str) -> Result<()> {
+    let _guard = TOKEN_FILES_LOCK.lock().unwrap();
     let mut storage = load_multi_account_storage();
     storage.accounts.remove(account_id);
     save_multi_account_storage(&storage)
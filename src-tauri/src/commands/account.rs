@@ -1,46 +1,237 @@
 use crate::auth::account::Account;
 use crate::db::EmailDatabase;
-use crate::email::imap_client::{ImapClient, ImapCredentials};
-use crate::email::server_presets::{get_server_preset, AuthType, ProviderType, ServerConfig};
+use crate::email::body_cache::{BodyCache, DEFAULT_BODY_CACHE_SIZE};
+use crate::email::connection_check::{self, ConnectionCheck};
+use crate::email::discovery::{self, ServerCandidate};
+use crate::email::idle::{IdleManager, NewMailEvent};
+use crate::email::imap_client::{ImapClient, ImapCredentials, UidValidityLookup};
+use crate::email::pool::{ConnectionPool, PooledClient, DEFAULT_POOL_SIZE};
+use crate::email::server_presets::{
+    detect_provider, get_server_preset, preset_for_email, AuthType, ProviderType, ServerConfig,
+    ServerPreset, TlsMode,
+};
+use crate::email::sort::MessageSort;
+use crate::email::special_folders::SpecialFolderMap;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use tauri::State;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+
+/// How long account removal waits for the provider to revoke the OAuth grant
+const TOKEN_REVOCATION_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Holds active IMAP clients for all connected accounts
 pub struct AccountManager {
-    pub clients: Mutex<HashMap<String, Arc<tokio::sync::Mutex<ImapClient>>>>,
+    /// IMAP connections per account
+    pool: ConnectionPool,
+    /// Unread UIDs per "account_id:folder", newest first.
+    /// Dropped whenever IDLE reports a change or the app changes flags itself.
+    unread_cache: Mutex<HashMap<String, Vec<u32>>>,
+    /// Special folders per account, resolved once after login
+    special_folders: Mutex<HashMap<String, SpecialFolderMap>>,
+    /// Held while an account's OAuth token is checked and refreshed, so
+    /// concurrent callers wait for one refresh instead of racing their own
+    token_refresh_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Recently opened messages, so reopening one doesn't fetch it again
+    body_cache: BodyCache,
+    /// Where pooled clients look up the UIDVALIDITY of folders they haven't
+    /// listed yet
+    database: Option<Arc<Mutex<Option<EmailDatabase>>>>,
 }
 
 impl AccountManager {
     pub fn new() -> Self {
+        Self::with_pool_size(DEFAULT_POOL_SIZE)
+    }
+
+    /// Keep up to `pool_size` IMAP connections open per account
+    pub fn with_pool_size(pool_size: usize) -> Self {
         Self {
-            clients: Mutex::new(HashMap::new()),
+            pool: ConnectionPool::new(pool_size),
+            unread_cache: Mutex::new(HashMap::new()),
+            special_folders: Mutex::new(HashMap::new()),
+            token_refresh_locks: Mutex::new(HashMap::new()),
+            body_cache: BodyCache::new(DEFAULT_BODY_CACHE_SIZE),
+            database: None,
         }
     }
 
-    pub fn get_client(
-        &self,
-        account_id: &str,
-    ) -> Option<Arc<tokio::sync::Mutex<ImapClient>>> {
-        let clients = self.clients.lock().unwrap();
-        clients.get(account_id).cloned()
+    /// Check UIDs against the UIDVALIDITY recorded in `database`, so ones
+    /// cached before a restart aren't trusted blindly
+    pub fn with_database(mut self, database: Arc<Mutex<Option<EmailDatabase>>>) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    pub fn body_cache(&self) -> &BodyCache {
+        &self.body_cache
+    }
+
+    /// The lock serializing OAuth token refreshes for an account
+    pub fn token_refresh_lock(&self, account_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.token_refresh_locks.lock().unwrap();
+        locks.entry(account_id.to_string()).or_default().clone()
+    }
+
+    /// Cached special folders, or None if they haven't been resolved yet
+    pub fn special_folders(&self, account_id: &str) -> Option<SpecialFolderMap> {
+        let maps = self.special_folders.lock().unwrap();
+        maps.get(account_id).cloned()
+    }
+
+    pub fn set_special_folders(&self, account_id: &str, map: SpecialFolderMap) {
+        let mut maps = self.special_folders.lock().unwrap();
+        maps.insert(account_id.to_string(), map);
+    }
+
+    /// Forget the resolved special folders so the next operation resolves them again
+    pub fn invalidate_special_folders(&self, account_id: &str) {
+        let mut maps = self.special_folders.lock().unwrap();
+        maps.remove(account_id);
+    }
+
+    pub fn cached_unread_uids(&self, account_id: &str, folder: &str) -> Option<Vec<u32>> {
+        let cache = self.unread_cache.lock().unwrap();
+        cache.get(&format!("{}:{}", account_id, folder)).cloned()
+    }
+
+    pub fn cache_unread_uids(&self, account_id: &str, folder: &str, uids: Vec<u32>) {
+        let mut cache = self.unread_cache.lock().unwrap();
+        cache.insert(format!("{}:{}", account_id, folder), uids);
+    }
+
+    /// Drop cached unread UIDs for a folder
+    pub fn invalidate_unread(&self, account_id: &str, folder: &str) {
+        let mut cache = self.unread_cache.lock().unwrap();
+        cache.remove(&format!("{}:{}", account_id, folder));
+    }
+
+    /// A connection for the account, waiting if all of its pooled connections
+    /// are in use. None if the account isn't connected.
+    pub async fn get_client(&self, account_id: &str) -> Option<PooledClient> {
+        self.pool.checkout(account_id).await
+    }
+
+    /// Start the account's connection pool with `client` as its first connection
+    pub fn add_client(&self, client: ImapClient) {
+        let client = match self.uid_validity_lookup(&client.account_id) {
+            Some(lookup) => client.with_uid_validity_lookup(lookup),
+            None => client,
+        };
+        self.pool.insert(client);
     }
 
-    pub fn add_client(&self, account_id: String, client: ImapClient) {
-        let mut clients = self.clients.lock().unwrap();
-        clients.insert(account_id, Arc::new(tokio::sync::Mutex::new(client)));
+    fn uid_validity_lookup(&self, account_id: &str) -> Option<UidValidityLookup> {
+        let database = self.database.clone()?;
+        let account_id = account_id.to_string();
+        Some(Arc::new(move |folder: &str| {
+            let db_lock = database.lock().unwrap();
+            db_lock
+                .as_ref()?
+                .known_uid_validity(&account_id, folder)
+                .unwrap_or_else(|e| {
+                    eprintln!("[IMAP] Failed to read UIDVALIDITY of {}: {}", folder, e);
+                    None
+                })
+        }))
     }
 
+    /// Task that logs out pooled connections left unused; spawn once at startup
+    pub fn close_idle_connections(&self) -> impl std::future::Future<Output = ()> {
+        self.pool.clone().close_idle_periodically()
+    }
+
+    /// Log out every pooled connection not in use right now, e.g. before the
+    /// system sleeps. Pools stay, so the next command connects again.
+    pub async fn close_unused_connections(&self) -> usize {
+        self.pool.close_idle(Duration::ZERO).await
+    }
+
+    /// Drop all of the account's pooled connections and cached state
     pub fn remove_client(&self, account_id: &str) {
-        let mut clients = self.clients.lock().unwrap();
-        clients.remove(account_id);
+        self.pool.remove(account_id);
+
+        let prefix = format!("{}:", account_id);
+        let mut cache = self.unread_cache.lock().unwrap();
+        cache.retain(|key, _| !key.starts_with(&prefix));
+        drop(cache);
+
+        self.invalidate_special_folders(account_id);
+        self.body_cache.remove_folder(account_id, None);
     }
 }
 
 type DbState = Arc<Mutex<Option<EmailDatabase>>>;
 
+/// Accounts that point at the same mailbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateAccountGroup {
+    pub email: String,
+    pub accounts: Vec<Account>,
+    /// The account we suggest keeping (OAuth over app password, then the oldest)
+    pub suggested_keep_id: String,
+}
+
+/// Account details with cache and sync state, for the account settings view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSummary {
+    pub account: Account,
+    pub paused: bool,
+    pub block_manual_fetch: bool,
+    pub cached_emails: i64,
+    pub unread_emails: i64,
+    pub monitored_folders: Vec<String>,
+}
+
+/// An account's outgoing mail signatures
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountSignature {
+    pub signature: Option<String>,
+    /// Used for HTML mail; the plain signature is escaped when unset
+    pub html_signature: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountMergeResult {
+    pub kept_account_id: String,
+    pub removed_account_id: String,
+    pub emails_moved: usize,
+    pub embeddings_moved: usize,
+}
+
+/// Likely server settings for an address, best first. Known providers get
+/// their preset; other domains are looked up via autoconfig and SRV records.
+#[tauri::command]
+pub async fn discover_server_config(email: String) -> Result<Vec<ServerCandidate>, String> {
+    if detect_provider(&email) == ProviderType::Custom {
+        super::settings::ensure_network_allowed("Server auto-discovery")?;
+    }
+    discovery::discover(&email)
+        .await
+        .map_err(|e| format!("Failed to discover server settings: {}", e))
+}
+
+/// The known provider for an address, with how it lets mail apps sign in
+/// (e.g. that iCloud needs an app-specific password). None for other domains.
+#[tauri::command]
+pub async fn get_provider_preset(email: String) -> Result<Option<ServerPreset>, String> {
+    Ok(preset_for_email(&email).cloned())
+}
+
+/// Try logging in to IMAP and SMTP with settings that aren't saved yet, so
+/// the setup wizard can report problems before adding the account
+#[tauri::command]
+pub async fn test_connection(
+    server_config: ServerConfig,
+    credentials: ImapCredentials,
+) -> Result<ConnectionCheck, String> {
+    Ok(connection_check::check_account(server_config, credentials).await)
+}
+
 /// Add a new email account (OAuth — tokens already obtained)
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn add_account(
     db: State<'_, DbState>,
     _account_manager: State<'_, AccountManager>,
@@ -52,6 +243,8 @@ pub async fn add_account(
     smtp_host: Option<String>,
     smtp_port: Option<u16>,
     auth_type: String,
+    tls_mode: Option<TlsMode>,
+    smtp_tls_mode: Option<TlsMode>,
 ) -> Result<Account, String> {
     let provider_type = ProviderType::from_str(&provider);
     let auth = if auth_type == "oauth2" {
@@ -67,28 +260,22 @@ pub async fn add_account(
             imap_port: imap_port.unwrap_or(preset.imap_port),
             smtp_host: smtp_host.unwrap_or(preset.smtp_host),
             smtp_port: smtp_port.unwrap_or(preset.smtp_port),
-            use_tls: preset.use_tls,
+            tls_mode: tls_mode.unwrap_or(preset.tls_mode),
+            smtp_tls_mode: smtp_tls_mode.or(preset.smtp_tls_mode),
         }
     } else {
+        let imap_port = imap_port.unwrap_or(993);
         ServerConfig {
             imap_host: imap_host.ok_or("IMAP host required for custom provider")?,
-            imap_port: imap_port.unwrap_or(993),
+            imap_port,
             smtp_host: smtp_host.ok_or("SMTP host required for custom provider")?,
             smtp_port: smtp_port.unwrap_or(465),
-            use_tls: true,
+            tls_mode: tls_mode.unwrap_or_else(|| TlsMode::for_imap_port(imap_port)),
+            smtp_tls_mode,
         }
     };
 
-    let account = Account::new(
-        email,
-        display_name,
-        provider_type,
-        server_config.imap_host,
-        server_config.imap_port,
-        server_config.smtp_host,
-        server_config.smtp_port,
-        auth,
-    );
+    let account = Account::new(email, display_name, provider_type, server_config, auth);
 
     // Store in database
     {
@@ -113,12 +300,19 @@ pub async fn remove_account(
     account_manager.remove_client(&account_id);
 
     // Remove from database
-    {
+    let account = {
         let db_lock = db.lock().unwrap();
         let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        let account = database.get_account(&account_id).map_err(|e| e.to_string())?;
         database
             .remove_account(&account_id)
             .map_err(|e| e.to_string())?;
+        account
+    };
+
+    // Revoke the OAuth grant at the provider; local data goes either way
+    if let Some(account) = account.filter(|a| a.auth_type == "oauth2") {
+        revoke_account_tokens(&account).await;
     }
 
     // Clear stored tokens for this account
@@ -127,6 +321,31 @@ pub async fn remove_account(
     Ok(())
 }
 
+/// Best-effort revocation of an OAuth account's stored tokens. Failures are
+/// logged and otherwise ignored so removing the account never depends on the
+/// provider being reachable.
+async fn revoke_account_tokens(account: &Account) {
+    let Ok(tokens) = crate::auth::storage::get_account_tokens(&account.id) else {
+        return;
+    };
+    let provider = super::email::oauth_provider_name(account.provider_type());
+    let (token, is_refresh_token) = match &tokens.refresh_token {
+        Some(refresh_token) => (refresh_token.as_str(), true),
+        None => (tokens.access_token.as_str(), false),
+    };
+
+    let revocation = crate::auth::revoke_token(provider, token, is_refresh_token);
+    match tokio::time::timeout(TOKEN_REVOCATION_TIMEOUT, revocation).await {
+        Ok(Ok(true)) => println!("[Account] Revoked OAuth grant for {}", account.email),
+        Ok(Ok(false)) => println!(
+            "[Account] {} has no token revocation endpoint; the grant for {} stays until revoked in the provider's account settings",
+            provider, account.email
+        ),
+        Ok(Err(e)) => eprintln!("[Account] Failed to revoke OAuth grant for {}: {:#}", account.email, e),
+        Err(_) => eprintln!("[Account] Timed out revoking OAuth grant for {}", account.email),
+    }
+}
+
 /// List all accounts
 #[tauri::command]
 pub async fn list_accounts(db: State<'_, DbState>) -> Result<Vec<Account>, String> {
@@ -186,13 +405,7 @@ pub async fn connect_account(
         }
     };
 
-    let server_config = ServerConfig {
-        imap_host: account.imap_host.clone(),
-        imap_port: account.imap_port,
-        smtp_host: account.smtp_host.clone(),
-        smtp_port: account.smtp_port,
-        use_tls: true,
-    };
+    let server_config = account.server_config();
 
     let client = ImapClient::new(
         account.id.clone(),
@@ -200,12 +413,350 @@ pub async fn connect_account(
         account.provider_type(),
         server_config,
         credentials,
-    );
+    )
+    .with_rate_limits(super::settings::imap_rate_limits(&account.provider_type()))
+    .with_trusted_authserv_ids(super::settings::trusted_authserv_ids(
+        &account.provider_type(),
+        &account.server_config().imap_host,
+    ));
 
     // Test connection
     client.reconnect().await.map_err(|e| format!("Connection failed: {}", e))?;
 
-    account_manager.add_client(account.id, client);
+    account_manager.add_client(client);
+
+    Ok(())
+}
+
+/// Find accounts that were added more than once for the same mailbox
+#[tauri::command]
+pub async fn find_duplicate_accounts(
+    db: State<'_, DbState>,
+) -> Result<Vec<DuplicateAccountGroup>, String> {
+    let accounts = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database.list_accounts().map_err(|e| e.to_string())?
+    };
+
+    let mut groups: HashMap<String, Vec<Account>> = HashMap::new();
+    for account in accounts {
+        groups
+            .entry(account.normalized_email())
+            .or_default()
+            .push(account);
+    }
+
+    let mut duplicates: Vec<DuplicateAccountGroup> = groups
+        .into_iter()
+        .filter(|(_, accounts)| accounts.len() > 1)
+        .map(|(email, accounts)| {
+            let suggested_keep_id = accounts
+                .iter()
+                .min_by_key(|a| (a.auth_type != "oauth2", a.created_at))
+                .map(|a| a.id.clone())
+                .unwrap_or_default();
+            DuplicateAccountGroup {
+                email,
+                accounts,
+                suggested_keep_id,
+            }
+        })
+        .collect();
+
+    duplicates.sort_by(|a, b| a.email.cmp(&b.email));
+    Ok(duplicates)
+}
+
+/// Merge a duplicate account into the one being kept.
+/// Cached emails and embeddings move to the kept account, except copies it
+/// already has, which are deleted; the duplicate's IDLE monitors, IMAP client
+/// and credentials are removed.
+#[tauri::command]
+pub async fn merge_duplicate_accounts(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    idle_manager: State<'_, IdleManager>,
+    keep_account_id: String,
+    duplicate_account_id: String,
+    confirm: bool,
+) -> Result<AccountMergeResult, String> {
+    if !confirm {
+        return Err("Merging accounts requires explicit confirmation".to_string());
+    }
+    if keep_account_id == duplicate_account_id {
+        return Err("Cannot merge an account into itself".to_string());
+    }
+
+    let (keep, duplicate) = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        let keep = database
+            .get_account(&keep_account_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("Account to keep not found")?;
+        let duplicate = database
+            .get_account(&duplicate_account_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("Duplicate account not found")?;
+        (keep, duplicate)
+    };
+
+    if keep.normalized_email() != duplicate.normalized_email() {
+        return Err(format!(
+            "{} and {} are not the same mailbox",
+            keep.email, duplicate.email
+        ));
+    }
+
+    // Stop everything still running for the duplicate before moving its data
+    idle_manager.stop_idle(&duplicate.id).await;
+    account_manager.remove_client(&duplicate.id);
+
+    let merged = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database
+            .merge_accounts(&keep.id, &duplicate.id)
+            .map_err(|e| format!("Failed to merge accounts: {}", e))?
+    };
+
+    let embeddings_moved = match super::rag::get_vector_db() {
+        Some(vector_db) => {
+            vector_db
+                .delete_embeddings(&merged.dropped)
+                .map_err(|e| format!("Failed to delete embeddings: {}", e))?;
+            vector_db
+                .rename_embeddings(&merged.renamed)
+                .map_err(|e| format!("Failed to move embeddings: {}", e))?
+        }
+        None => 0,
+    };
+
+    crate::auth::storage::clear_account_tokens(&duplicate.id).map_err(|e| e.to_string())?;
+
+    println!(
+        "[Account] Merged {} into {} ({} emails moved)",
+        duplicate.id,
+        keep.id,
+        merged.renamed.len()
+    );
+
+    Ok(AccountMergeResult {
+        kept_account_id: keep.id,
+        removed_account_id: duplicate.id,
+        emails_moved: merged.renamed.len(),
+        embeddings_moved,
+    })
+}
+
+fn load_account(db: &DbState, account_id: &str) -> Result<Account, String> {
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    database
+        .get_account(account_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Account not found: {}", account_id))
+}
+
+/// Get an account together with its cache and sync state (defaults to the active account)
+#[tauri::command]
+pub async fn get_account_summary(
+    db: State<'_, DbState>,
+    idle_manager: State<'_, IdleManager>,
+    account_id: Option<String>,
+) -> Result<AccountSummary, String> {
+    let (account, (cached_emails, unread_emails)) = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        let account = match account_id {
+            Some(id) => database.get_account(&id).map_err(|e| e.to_string())?,
+            None => database.get_active_account().map_err(|e| e.to_string())?,
+        }
+        .ok_or("Account not found")?;
+        let counts = database
+            .get_account_email_counts(&account.id)
+            .map_err(|e| e.to_string())?;
+        (account, counts)
+    };
+
+    let monitored_folders = idle_manager.monitored_folders(&account.id).await;
+
+    Ok(AccountSummary {
+        paused: account.sync_paused,
+        block_manual_fetch: account.block_manual_fetch,
+        account,
+        cached_emails,
+        unread_emails,
+        monitored_folders,
+    })
+}
 
+/// Pause background sync for an account. IDLE is stopped and the paused state
+/// survives restarts. With `block_manual_fetch`, manual fetches are refused too.
+#[tauri::command]
+pub async fn pause_account(
+    db: State<'_, DbState>,
+    idle_manager: State<'_, IdleManager>,
+    account_id: String,
+    block_manual_fetch: Option<bool>,
+) -> Result<(), String> {
+    {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database
+            .set_account_paused(&account_id, true, block_manual_fetch.unwrap_or(false))
+            .map_err(|e| e.to_string())?;
+    }
+
+    idle_manager.stop_idle(&account_id).await;
+    println!("[Account] Sync paused for {}", account_id);
     Ok(())
 }
+
+/// Resume sync for a paused account: restarts IDLE and runs a catch-up sync of the inbox.
+/// Returns the number of messages synced.
+#[tauri::command]
+pub async fn resume_account(
+    app: AppHandle,
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    idle_manager: State<'_, IdleManager>,
+    account_id: String,
+) -> Result<usize, String> {
+    {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database
+            .set_account_paused(&account_id, false, false)
+            .map_err(|e| e.to_string())?;
+    }
+
+    let account = load_account(&db, &account_id)?;
+    let folders = idle_manager.configured_folders(&account.id).await;
+
+    idle_manager
+        .start_idle(
+            app.clone(),
+            account.id.clone(),
+            account.email.clone(),
+            account.provider_type(),
+            account.server_config(),
+            account.auth_type.clone(),
+            &folders,
+        )
+        .await;
+
+    // Catch up on whatever arrived while the account was paused
+    let synced = {
+        let client = super::email::get_client_for_account(&account_manager, &account).await?;
+        super::email::sync_folder_to_cache(
+            &client,
+            &db,
+            "INBOX",
+            50,
+            MessageSort::Date,
+            None,
+            super::cache::prefetch_bodies(),
+        )
+        .await?
+    };
+
+    let _ = app.emit(
+        "email:new_mail",
+        NewMailEvent::new(&account.id, "INBOX", Default::default()),
+    );
+
+    println!(
+        "[Account] Sync resumed for {} ({} messages caught up)",
+        account.id,
+        synced.len()
+    );
+    Ok(synced.len())
+}
+
+/// Get an account's signatures (defaults to the active account)
+#[tauri::command]
+pub async fn get_signature(
+    db: State<'_, DbState>,
+    account_id: Option<String>,
+) -> Result<AccountSignature, String> {
+    let account = match account_id {
+        Some(id) => load_account(&db, &id)?,
+        None => {
+            let db_lock = db.lock().unwrap();
+            let database = db_lock.as_ref().ok_or("Database not initialized")?;
+            database
+                .get_active_account()
+                .map_err(|e| e.to_string())?
+                .ok_or("Account not found")?
+        }
+    };
+    Ok(AccountSignature {
+        signature: account.signature,
+        html_signature: account.html_signature,
+    })
+}
+
+/// Set the signature `send_email` and `save_draft` append for an account.
+/// Blank values clear it.
+#[tauri::command]
+pub async fn set_signature(
+    db: State<'_, DbState>,
+    account_id: String,
+    signature: Option<String>,
+    html_signature: Option<String>,
+) -> Result<(), String> {
+    let signature = signature.filter(|v| !v.trim().is_empty());
+    let html_signature = html_signature.filter(|v| !v.trim().is_empty());
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    database
+        .set_account_signature(&account_id, signature.as_deref(), html_signature.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Let an account send as another address (see `send_email`'s `from`).
+/// Returns false if it already could.
+#[tauri::command]
+pub async fn add_alias(
+    db: State<'_, DbState>,
+    account_id: String,
+    address: String,
+) -> Result<bool, String> {
+    let address = address.trim();
+    address
+        .parse::<lettre::Address>()
+        .map_err(|_| format!("Invalid address: {}", address))?;
+
+    let account = load_account(&db, &account_id)?;
+    if address.eq_ignore_ascii_case(&account.email) {
+        return Ok(false);
+    }
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    database
+        .add_alias(&account.id, address)
+        .map_err(|e| e.to_string())
+}
+
+/// Addresses an account may send as besides its own (defaults to the active account)
+#[tauri::command]
+pub async fn list_aliases(
+    db: State<'_, DbState>,
+    account_id: Option<String>,
+) -> Result<Vec<String>, String> {
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    let account_id = match account_id {
+        Some(id) => id,
+        None => {
+            database
+                .get_active_account()
+                .map_err(|e| e.to_string())?
+                .ok_or("Account not found")?
+                .id
+        }
+    };
+    database.get_aliases(&account_id).map_err(|e| e.to_string())
+}
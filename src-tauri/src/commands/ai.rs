@@ -1,16 +1,42 @@
+use super::account::AccountManager;
+use super::settings::ensure_network_allowed;
+use crate::db::EmailDatabase;
+use crate::email::quoting::strip_quoted_text;
 use crate::llm::{
-    get_available_models, ModelManager, ModelOption, ModelStatus, Summarizer, DEFAULT_MODEL_FILE,
+    get_available_models, EmailExplanation, ExplainLevel, ModelManager, ModelOption, ModelStatus,
+    ReplySuggestion, Summarizer, ThreadEntry, ThreadSummary, DEFAULT_MODEL_FILE,
     DEFAULT_MODEL_REPO,
 };
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
-use tauri::{AppHandle, Emitter};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, State};
+
+type DbState = Arc<Mutex<Option<EmailDatabase>>>;
+
+/// Related past emails offered to the model as background for explanations
+const EXPLAIN_CONTEXT_EMAILS: usize = 3;
+
+/// Newest messages of a folder searched for the conversation to summarize
+const THREAD_SEARCH_MESSAGES: u32 = 200;
+
+/// Similar past emails offered to the model as tone examples for replies
+const REPLY_CONTEXT_EMAILS: usize = 3;
+
+/// Newest messages of a conversation a reply is drafted from
+const REPLY_THREAD_MESSAGES: usize = 10;
+
+/// Most candidate replies drafted at once
+const MAX_REPLY_SUGGESTIONS: usize = 5;
 
 lazy_static::lazy_static! {
     pub static ref SUMMARIZER: Mutex<Option<Summarizer>> = Mutex::new(None);
     static ref MODEL_MANAGER: Mutex<Option<ModelManager>> = Mutex::new(None);
     static ref CURRENT_MODEL_ID: Mutex<Option<String>> = Mutex::new(None);
     static ref MODEL_LOADING: Mutex<bool> = Mutex::new(false);
+    /// Cancellation flags of in-flight `llm:token` streams, by stream ID
+    static ref LLM_STREAMS: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,6 +46,15 @@ pub struct EmailSummary {
     pub priority: String,
 }
 
+/// Payload of the `llm:token`, `llm:done` and `llm:error` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LlmStreamEvent {
+    Token { stream_id: String, token: String },
+    Done { stream_id: String, text: String, cancelled: bool },
+    Error { stream_id: String, message: String },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "status")]
 pub enum ModelStatusResponse {
@@ -111,6 +146,7 @@ pub async fn is_model_loading() -> Result<bool, String> {
 /// Download the default AI model from HuggingFace
 #[tauri::command]
 pub async fn download_model(app: AppHandle) -> Result<(), String> {
+    ensure_network_allowed("AI model download")?;
     ensure_model_manager()?;
 
     // Emit starting event
@@ -156,6 +192,7 @@ pub async fn download_model(app: AppHandle) -> Result<(), String> {
 /// Download a specific model by ID
 #[tauri::command]
 pub async fn download_model_by_id(app: AppHandle, model_id: String) -> Result<(), String> {
+    ensure_network_allowed("AI model download")?;
     ensure_model_manager()?;
 
     // Emit starting event
@@ -483,6 +520,82 @@ pub async fn summarize_email_stream(
     })
 }
 
+/// Run `generate` on a blocking thread, emitting each token as an `llm:token`
+/// event tagged with a fresh stream ID, then `llm:done` (or `llm:error`).
+/// Returns the stream ID immediately; `cancel_llm_stream` stops generation.
+pub(crate) fn start_llm_stream<G>(app: AppHandle, generate: G) -> String
+where
+    G: FnOnce(&AtomicBool, &mut dyn FnMut(&str)) -> Result<String, String> + Send + 'static,
+{
+    let stream_id = uuid::Uuid::new_v4().to_string();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    LLM_STREAMS
+        .lock()
+        .unwrap()
+        .insert(stream_id.clone(), cancelled.clone());
+
+    let id = stream_id.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut emit_token = |token: &str| {
+            let _ = app.emit(
+                "llm:token",
+                LlmStreamEvent::Token {
+                    stream_id: id.clone(),
+                    token: token.to_string(),
+                },
+            );
+        };
+        let result = generate(&cancelled, &mut emit_token);
+        LLM_STREAMS.lock().unwrap().remove(&id);
+
+        let _ = match result {
+            Ok(text) => app.emit(
+                "llm:done",
+                LlmStreamEvent::Done {
+                    stream_id: id,
+                    text,
+                    cancelled: cancelled.load(Ordering::Relaxed),
+                },
+            ),
+            Err(message) => app.emit("llm:error", LlmStreamEvent::Error { stream_id: id, message }),
+        };
+    });
+
+    stream_id
+}
+
+/// Stream a chat response as `llm:token` events. Returns the stream ID.
+#[tauri::command]
+pub async fn chat_stream(
+    app: AppHandle,
+    message: String,
+    email_context: Option<String>,
+) -> Result<String, String> {
+    Ok(start_llm_stream(app, move |cancel, on_token| {
+        let guard = SUMMARIZER.lock().unwrap();
+        let summarizer = guard
+            .as_ref()
+            .ok_or_else(|| "AI not initialized".to_string())?;
+
+        summarizer
+            .chat_stream(&message, email_context.as_deref(), cancel, on_token)
+            .map_err(|e| e.to_string())
+    }))
+}
+
+/// Stop a running LLM stream; the text generated so far is still delivered
+/// in `llm:done`. Returns false if no stream has that ID.
+#[tauri::command]
+pub async fn cancel_llm_stream(stream_id: String) -> Result<bool, String> {
+    match LLM_STREAMS.lock().unwrap().get(&stream_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
 /// Get quick insights about an email
 #[tauri::command]
 pub async fn get_email_insights(subject: String, body: String) -> Result<Vec<String>, String> {
@@ -505,6 +618,187 @@ pub async fn classify_priority(subject: String, from: String, body: String) -> R
         .map_err(|e| e.to_string())
 }
 
+/// Explain a dense email in plain language at the requested level
+/// ("brief", "detailed" or "eli5"), optionally grounded in related past emails.
+/// Quoted reply history is stripped so the explanation covers the latest message.
+#[tauri::command]
+pub async fn explain_email(
+    app: AppHandle,
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    level: Option<String>,
+    use_context: Option<bool>,
+) -> Result<EmailExplanation, String> {
+    let level = match level {
+        Some(value) => ExplainLevel::parse(&value)
+            .ok_or_else(|| format!("Unknown explanation level: {}", value))?,
+        None => ExplainLevel::default(),
+    };
+
+    let email = super::email::get_email(db, account_manager, email_id.clone()).await?;
+    let body = email
+        .body_plain
+        .as_deref()
+        .filter(|b| !b.trim().is_empty())
+        .or(email.body_html.as_deref())
+        .unwrap_or(&email.snippet);
+    let latest = strip_quoted_text(body);
+
+    let context = if use_context.unwrap_or(true) {
+        let query = format!("{} {}", email.subject, latest.chars().take(500).collect::<String>());
+        super::rag::related_email_context(&app, &email_id, &query, EXPLAIN_CONTEXT_EMAILS)
+    } else {
+        None
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let guard = SUMMARIZER.lock().unwrap();
+        let summarizer = guard
+            .as_ref()
+            .ok_or_else(|| "AI not initialized".to_string())?;
+
+        let (context_text, context_ids) = context.unzip();
+        let mut explanation = summarizer
+            .explain_email(
+                &email.subject,
+                &email.from,
+                &latest,
+                level,
+                context_text.as_deref(),
+            )
+            .map_err(|e| e.to_string())?;
+        explanation.context_email_ids = context_ids.unwrap_or_default();
+        Ok(explanation)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Summarize a conversation from `list_threads` in `folder` (INBOX by
+/// default): a short summary plus its action items and open questions
+#[tauri::command]
+pub async fn summarize_thread(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    thread_id: String,
+    folder: Option<String>,
+) -> Result<ThreadSummary, String> {
+    let threads = super::email::load_threads(
+        &db,
+        &account_manager,
+        folder.as_deref(),
+        THREAD_SEARCH_MESSAGES,
+    )
+    .await?;
+    let thread = threads
+        .into_iter()
+        .find(|thread| thread.thread_id == thread_id)
+        .ok_or_else(|| format!("Conversation not found: {}", thread_id))?;
+
+    let mut messages = Vec::with_capacity(thread.email_ids.len());
+    for email_id in &thread.email_ids {
+        let email = super::email::load_email(&db, &account_manager, email_id).await?;
+        let body = email
+            .body_plain
+            .filter(|b| !b.trim().is_empty())
+            .or(email.body_html)
+            .unwrap_or(email.snippet);
+        messages.push(ThreadEntry {
+            from: email.from,
+            date: email.date,
+            timestamp: email.date_timestamp,
+            body,
+        });
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let guard = SUMMARIZER.lock().unwrap();
+        let summarizer = guard
+            .as_ref()
+            .ok_or_else(|| "AI not initialized".to_string())?;
+        summarizer
+            .summarize_thread(&thread.subject, &messages)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Draft `count` (3 by default) short candidate replies to an email, each
+/// with an intent label, from its cached conversation and similar past
+/// emails. `temperature` (0.3 by default) trades consistency for variety.
+#[tauri::command]
+pub async fn suggest_replies(
+    app: AppHandle,
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    count: Option<usize>,
+    temperature: Option<f32>,
+) -> Result<Vec<ReplySuggestion>, String> {
+    let count = count.unwrap_or(3).clamp(1, MAX_REPLY_SUGGESTIONS);
+    let temperature = temperature.unwrap_or(0.3).clamp(0.0, 1.0);
+
+    let email = super::email::load_email(&db, &account_manager, &email_id).await?;
+    let mut thread = vec![email.clone()];
+    {
+        let db_lock = db.lock().unwrap();
+        if let Some(database) = db_lock.as_ref() {
+            // Newest first, so older messages are the ones left out
+            let member_ids = database
+                .get_thread_email_ids(&email.thread_id)
+                .unwrap_or_default();
+            thread.extend(
+                member_ids
+                    .iter()
+                    .filter(|id| **id != email_id)
+                    .filter_map(|id| database.get_email_by_id(id).ok().flatten())
+                    .take(REPLY_THREAD_MESSAGES - 1),
+            );
+        }
+    }
+    let messages: Vec<ThreadEntry> = thread
+        .into_iter()
+        .map(|message| ThreadEntry {
+            from: message.from,
+            date: message.date,
+            timestamp: message.date_timestamp,
+            body: message
+                .body_plain
+                .filter(|b| !b.trim().is_empty())
+                .or(message.body_html)
+                .unwrap_or(message.snippet),
+        })
+        .collect();
+
+    let latest: String = strip_quoted_text(&messages[0].body)
+        .chars()
+        .take(500)
+        .collect();
+    let query = format!("{} {}", email.subject, latest);
+    let context = super::rag::related_email_context(&app, &email_id, &query, REPLY_CONTEXT_EMAILS)
+        .map(|(text, _)| text);
+
+    tokio::task::spawn_blocking(move || {
+        let guard = SUMMARIZER.lock().unwrap();
+        let summarizer = guard
+            .as_ref()
+            .ok_or_else(|| "AI not initialized".to_string())?;
+        summarizer
+            .suggest_replies(
+                &email.subject,
+                &messages,
+                count,
+                context.as_deref(),
+                temperature,
+            )
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 /// Get model information (for the default/recommended model)
 #[tauri::command]
 pub async fn get_model_info() -> Result<ModelInfo, String> {
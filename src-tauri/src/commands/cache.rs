@@ -5,7 +5,10 @@ use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use tauri::State;
 
+use super::account::AccountManager;
 use crate::db::EmailDatabase;
+use crate::email::body_cache::DEFAULT_BODY_CACHE_SIZE;
+use crate::email::types::EmailListItem;
 
 type DbState = Arc<Mutex<Option<EmailDatabase>>>;
 
@@ -23,11 +26,44 @@ pub struct CacheSettings {
     pub cache_enabled: bool,
     pub auto_sync_on_start: bool,
     pub cache_media_assets: bool,
+    /// Cached emails not fetched again for this many days are pruned at startup
     pub max_cache_age_days: u32,
+    /// A cached folder page older than this is treated as a miss by `fetch_emails`
+    #[serde(default = "default_max_cache_age_secs")]
+    pub max_cache_age_secs: i64,
+    /// Opened messages kept in memory so reopening them skips the server; 0 turns it off
+    #[serde(default = "default_body_cache_size")]
+    pub body_cache_size: usize,
+    /// Download whole messages while syncing; otherwise only headers and a
+    /// snippet are cached and bodies are fetched when a message is opened
+    #[serde(default)]
+    pub prefetch_bodies: bool,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            cache_enabled: true,
+            auto_sync_on_start: false,
+            cache_media_assets: true,
+            max_cache_age_days: 30,
+            max_cache_age_secs: default_max_cache_age_secs(),
+            body_cache_size: default_body_cache_size(),
+            prefetch_bodies: false,
+        }
+    }
+}
+
+fn default_max_cache_age_secs() -> i64 {
+    5 * 60
+}
+
+fn default_body_cache_size() -> usize {
+    DEFAULT_BODY_CACHE_SIZE
 }
 
 /// Get the project data directory
-fn get_data_dir() -> Result<PathBuf, String> {
+pub(crate) fn get_data_dir() -> Result<PathBuf, String> {
     let project_dirs =
         ProjectDirs::from("com", "inboxed", "inboxed").ok_or("Failed to get project directory")?;
     Ok(project_dirs.data_dir().to_path_buf())
@@ -99,9 +135,8 @@ pub async fn get_storage_info(db: State<'_, DbState>) -> Result<StorageInfo, Str
     })
 }
 
-/// Get current cache settings
-#[tauri::command]
-pub async fn get_cache_settings() -> Result<CacheSettings, String> {
+/// Cache settings from disk, or the defaults if none were saved
+pub(crate) fn load_cache_settings() -> Result<CacheSettings, String> {
     let data_dir = get_data_dir()?;
     let settings_path = data_dir.join("cache_settings.json");
 
@@ -110,19 +145,31 @@ pub async fn get_cache_settings() -> Result<CacheSettings, String> {
             .map_err(|e| format!("Failed to read cache settings: {}", e))?;
         serde_json::from_str(&content).map_err(|e| format!("Failed to parse cache settings: {}", e))
     } else {
-        // Return default settings
-        Ok(CacheSettings {
-            cache_enabled: true,
-            auto_sync_on_start: false,
-            cache_media_assets: true,
-            max_cache_age_days: 30,
-        })
+        Ok(CacheSettings::default())
     }
 }
 
+/// Whether syncs should download message bodies up front
+pub(crate) fn prefetch_bodies() -> bool {
+    load_cache_settings().unwrap_or_default().prefetch_bodies
+}
+
+/// Get current cache settings
+#[tauri::command]
+pub async fn get_cache_settings() -> Result<CacheSettings, String> {
+    load_cache_settings()
+}
+
 /// Save cache settings
 #[tauri::command]
-pub async fn save_cache_settings(settings: CacheSettings) -> Result<(), String> {
+pub async fn save_cache_settings(
+    account_manager: State<'_, AccountManager>,
+    settings: CacheSettings,
+) -> Result<(), String> {
+    account_manager
+        .body_cache()
+        .set_capacity(settings.body_cache_size);
+
     let data_dir = get_data_dir()?;
     fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
 
@@ -133,15 +180,28 @@ pub async fn save_cache_settings(settings: CacheSettings) -> Result<(), String>
     fs::write(&settings_path, content).map_err(|e| format!("Failed to write cache settings: {}", e))
 }
 
-/// Clear the email database (keeps the schema)
+/// Clear the email database (keeps the schema) and the opened messages in memory
 #[tauri::command]
-pub async fn clear_email_cache(db: State<'_, DbState>) -> Result<(), String> {
+pub async fn clear_email_cache(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+) -> Result<(), String> {
+    account_manager.body_cache().clear();
     let db_lock = db.lock().unwrap();
     let database = db_lock.as_ref().ok_or("Database not initialized")?;
 
     database.clear_all_emails().map_err(|e| e.to_string())
 }
 
+/// Forget the opened messages kept in memory, so the next open fetches them
+/// again. Returns how many were dropped.
+#[tauri::command]
+pub async fn clear_message_cache(
+    account_manager: State<'_, AccountManager>,
+) -> Result<usize, String> {
+    Ok(account_manager.body_cache().clear())
+}
+
 /// Clear the media cache directory
 #[tauri::command]
 pub async fn clear_media_cache() -> Result<(), String> {
@@ -159,9 +219,12 @@ pub async fn clear_media_cache() -> Result<(), String> {
 
 /// Clear all caches (emails and media)
 #[tauri::command]
-pub async fn clear_all_caches(db: State<'_, DbState>) -> Result<(), String> {
+pub async fn clear_all_caches(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+) -> Result<(), String> {
     // Clear email cache
-    clear_email_cache(db).await?;
+    clear_email_cache(db, account_manager).await?;
 
     // Clear media cache
     clear_media_cache().await?;
@@ -237,6 +300,24 @@ pub async fn get_cached_emails_count(db: State<'_, DbState>) -> Result<i64, Stri
     database.get_email_count().map_err(|e| e.to_string())
 }
 
+/// Search cached emails without touching the network. `query` words must all
+/// match the subject, body or sender; "quoted" parts match as phrases.
+/// Snippets come back HTML-escaped with the matches in `<mark>` tags.
+#[tauri::command]
+pub async fn search_cached(
+    db: State<'_, DbState>,
+    query: String,
+    folder: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<EmailListItem>, String> {
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+
+    database
+        .search_cached(&query, folder.as_deref(), limit.unwrap_or(50))
+        .map_err(|e| e.to_string())
+}
+
 /// Check if any emails are cached
 #[tauri::command]
 pub async fn has_cached_emails(db: State<'_, DbState>) -> Result<bool, String> {
@@ -247,9 +328,12 @@ pub async fn has_cached_emails(db: State<'_, DbState>) -> Result<bool, String> {
 /// Clear all app data including database, cache, and settings
 /// This does NOT clear OAuth tokens - use sign_out for that
 #[tauri::command]
-pub async fn clear_all_app_data(db: State<'_, DbState>) -> Result<(), String> {
+pub async fn clear_all_app_data(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+) -> Result<(), String> {
     // Clear email cache and media cache
-    clear_all_caches(db).await?;
+    clear_all_caches(db, account_manager).await?;
 
     // Clear cache settings file
     let data_dir = get_data_dir()?;
@@ -6,11 +6,117 @@ use tokio::task;
 use chrono::Utc;
 
 use crate::db::{EmailDatabase, email_db::{EmailWithInsight, IndexingStatus, EmailInsight}};
+use crate::db::vector_db::EmailEmbedding;
+use crate::email::auth_results::AuthenticationResults;
+use crate::email::mailing_list::MailingList;
 use crate::email::types::Email;
+use crate::commands::account::AccountManager;
 use crate::commands::ai::SUMMARIZER;
+use crate::commands::email::{get_client_for_account, map_folder_name, parse_email_id};
+use crate::commands::rag::category_text;
+use crate::llm::rag::{calculate_text_hash, UNCERTAIN_CATEGORY};
+use serde::{Deserialize, Serialize};
 
 type DbState = Arc<Mutex<Option<EmailDatabase>>>;
 
+/// A mailing list the user receives, for the "your mailing lists" view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MailingListSummary {
+    /// Grouping key: List-Id, else List-Post address, else sender address
+    pub list_id: String,
+    /// Header metadata from the most recent message
+    pub list: MailingList,
+    pub message_count: i64,
+    /// Unix timestamp of the most recent message
+    pub last_received: i64,
+}
+
+/// Values the processing pipeline derives from a message
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DerivedFields {
+    pub thread_id: Option<String>,
+    pub snippet: Option<String>,
+    pub has_attachments: Option<bool>,
+    pub mailing_list_id: Option<String>,
+    pub category: Option<String>,
+    pub priority: Option<String>,
+    pub summary: Option<String>,
+    /// Hash of the text the stored embedding was computed from
+    pub embedding_text_hash: Option<String>,
+}
+
+impl DerivedFields {
+    fn collect(
+        email: Option<&Email>,
+        insight: Option<&EmailInsight>,
+        embedding: Option<&EmailEmbedding>,
+    ) -> Self {
+        Self {
+            thread_id: email.map(|e| e.thread_id.clone()),
+            snippet: email.map(|e| e.snippet.clone()),
+            has_attachments: email.map(|e| e.has_attachments),
+            mailing_list_id: email
+                .and_then(|e| e.mailing_list.as_ref())
+                .and_then(|list| list.id.clone()),
+            category: insight.and_then(|i| i.category.clone()),
+            priority: insight.map(|i| i.priority.clone()),
+            summary: insight.and_then(|i| i.summary.clone()),
+            embedding_text_hash: embedding.map(|e| e.text_hash.clone()),
+        }
+    }
+
+    /// Names of the fields that differ from `other`
+    fn changed_from(&self, other: &Self) -> Vec<String> {
+        let mut changed = Vec::new();
+        let mut check = |name: &str, differs: bool| {
+            if differs {
+                changed.push(name.to_string());
+            }
+        };
+        check("thread_id", self.thread_id != other.thread_id);
+        check("snippet", self.snippet != other.snippet);
+        check("has_attachments", self.has_attachments != other.has_attachments);
+        check("mailing_list_id", self.mailing_list_id != other.mailing_list_id);
+        check("category", self.category != other.category);
+        check("priority", self.priority != other.priority);
+        check("summary", self.summary != other.summary);
+        check("embedding_text_hash", self.embedding_text_hash != other.embedding_text_hash);
+        changed
+    }
+}
+
+/// Before/after comparison returned by `reprocess_email`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReprocessReport {
+    pub email_id: String,
+    pub before: DerivedFields,
+    pub after: DerivedFields,
+    /// Names of the derived fields whose value changed
+    pub changed: Vec<String>,
+    /// False when the RAG engine isn't initialized and the embedding was left alone
+    pub reembedded: bool,
+    pub embedding_changed: bool,
+    pub authentication_results: Vec<AuthenticationResults>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryCount {
+    pub category: String,
+    pub total: i64,
+    pub unread: i64,
+}
+
+/// Cached email counts per category for one folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryCounts {
+    pub folder: String,
+    /// Built-in categories first (always present), then custom ones, then "uncertain"
+    pub categories: Vec<CategoryCount>,
+    /// Emails that have not been classified yet
+    pub unclassified_total: i64,
+    pub unclassified_unread: i64,
+}
+
 #[tauri::command]
 pub async fn init_database() -> Result<(), String> {
     let project_dirs = ProjectDirs::from("com", "inboxed", "inboxed")
@@ -82,6 +188,115 @@ pub async fn get_emails_by_account_and_category(
     Ok(emails)
 }
 
+/// List the mailing lists seen on an account (defaults to the active account)
+#[tauri::command]
+pub async fn list_mailing_lists(
+    db: State<'_, DbState>,
+    account_id: Option<String>,
+) -> Result<Vec<MailingListSummary>, String> {
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+
+    let account_id = match account_id {
+        Some(id) => id,
+        None => database
+            .get_active_account()
+            .map_err(|e: anyhow::Error| e.to_string())?
+            .ok_or("No active account")?
+            .id,
+    };
+
+    let rows = database
+        .get_mailing_lists(&account_id)
+        .map_err(|e: anyhow::Error| e.to_string())?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(list_id, message_count, last_received, meta)| MailingListSummary {
+            list_id,
+            list: meta
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            message_count,
+            last_received,
+        })
+        .collect())
+}
+
+/// Count cached emails per stored category (with unread counts) for a folder
+#[tauri::command]
+pub async fn get_category_counts(
+    db: State<'_, DbState>,
+    folder: String,
+) -> Result<CategoryCounts, String> {
+    let rows = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        match database
+            .get_active_account()
+            .map_err(|e: anyhow::Error| e.to_string())?
+        {
+            Some(account) => {
+                // Resolve "trash" etc. the way the active account names them
+                let imap_folder = map_folder_name(&account.provider_type(), &folder);
+                database
+                    .get_category_counts(&account.id, &imap_folder)
+                    .map_err(|e: anyhow::Error| e.to_string())?
+            }
+            None => Vec::new(),
+        }
+    };
+
+    let mut categories: Vec<CategoryCount> = super::rag::category_names()
+        .into_iter()
+        .map(|name| CategoryCount {
+            category: name,
+            total: 0,
+            unread: 0,
+        })
+        .collect();
+    let mut uncertain = CategoryCount {
+        category: UNCERTAIN_CATEGORY.to_string(),
+        total: 0,
+        unread: 0,
+    };
+    let mut unclassified_total = 0;
+    let mut unclassified_unread = 0;
+
+    for (category, total, unread) in rows {
+        match category {
+            None => {
+                unclassified_total += total;
+                unclassified_unread += unread;
+            }
+            Some(name) if name == UNCERTAIN_CATEGORY => {
+                uncertain.total += total;
+                uncertain.unread += unread;
+            }
+            Some(name) => match categories.iter_mut().find(|c| c.category == name) {
+                Some(existing) => {
+                    existing.total += total;
+                    existing.unread += unread;
+                }
+                None => categories.push(CategoryCount {
+                    category: name,
+                    total,
+                    unread,
+                }),
+            },
+        }
+    }
+
+    categories.push(uncertain);
+
+    Ok(CategoryCounts {
+        folder: imap_folder,
+        categories,
+        unclassified_total,
+        unclassified_unread,
+    })
+}
+
 #[tauri::command]
 pub async fn search_smart_emails(
     db: State<'_, DbState>,
@@ -185,9 +400,25 @@ async fn index_emails_background<R: tauri::Runtime>(
     let total = emails.len() as i64;
     database.update_indexing_status(true, Some(total), Some(0), None)?;
 
+    // Categories already computed when the emails were fetched
+    let hashes: Vec<String> = emails
+        .iter()
+        .map(|email| calculate_text_hash(&category_text(email)))
+        .collect();
+    let known_categories = database.get_cached_categories(&hashes).unwrap_or_default();
+
     // Process each email (generate insights)
     for (idx, email) in emails.iter().enumerate() {
-        let insight = generate_email_insights(email).await;
+        // A category set by a filter rule wins over classification
+        let rule_category = if email.message_id.is_empty() {
+            None
+        } else {
+            database
+                .get_rule_category(&email.account_id, &email.message_id)
+                .unwrap_or_default()
+        };
+        let known_category = rule_category.or_else(|| known_categories.get(&hashes[idx]).cloned());
+        let insight = generate_email_insights(email, known_category).await;
 
         if let Err(e) = database.store_insights(&insight) {
             eprintln!("Failed to store insights for {}: {}", email.id, e);
@@ -206,10 +437,101 @@ async fn index_emails_background<R: tauri::Runtime>(
     database.update_indexing_status(false, None, None, None)?;
     let _ = app.emit("indexing:complete", ());
 
+    // New classifications change the per-category counts
+    if total > 0 {
+        let _ = app.emit("categories:updated", ());
+    }
+
     Ok(())
 }
 
-async fn generate_email_insights(email: &Email) -> EmailInsight {
+/// Run one email through the whole pipeline again: re-fetch (without marking it
+/// read), re-parse, re-store, re-classify and re-embed it. For fixing up single
+/// messages after a parser or model change without a full reindex.
+#[tauri::command]
+pub async fn reprocess_email(
+    app: tauri::AppHandle,
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<ReprocessReport, String> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| format!("Invalid email ID: {}", email_id))?;
+
+    let (account, cached, old_insight) = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        let account = database
+            .get_account(&account_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Account not found: {}", account_id))?;
+        let cached = database.get_email_by_id(&email_id).map_err(|e| e.to_string())?;
+        let insight = database.get_insight(&email_id).map_err(|e| e.to_string())?;
+        (account, cached, insight)
+    };
+
+    let stored_embedding = || {
+        super::rag::get_vector_db().and_then(|v| v.get_embedding(&email_id).ok().flatten())
+    };
+    let old_embedding = stored_embedding();
+    let before = DerivedFields::collect(cached.as_ref(), old_insight.as_ref(), old_embedding.as_ref());
+
+    let (email, authentication_results) = {
+        let client = get_client_for_account(&account_manager, &account).await?;
+        client
+            .peek_message(&folder, uid)
+            .await
+            .map_err(|e| format!("Failed to re-fetch message: {}", e))?
+    };
+
+    {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database.store_email(&email).map_err(|e| e.to_string())?;
+    }
+
+    let insight = generate_email_insights(&email, None).await;
+    {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database.store_insights(&insight).map_err(|e| e.to_string())?;
+    }
+
+    // Takes the RAG engine lock and runs the embedding model
+    let reembedded = {
+        let app = app.clone();
+        let email = email.clone();
+        let previous_thread_id = cached.as_ref().map(|c| c.thread_id.clone());
+        tokio::task::spawn_blocking(move || {
+            super::rag::reembed_email(&app, &email, previous_thread_id.as_ref())
+        })
+        .await
+        .map_err(|e| format!("Re-embedding task failed: {}", e))??
+    };
+    let new_embedding = stored_embedding();
+    let after = DerivedFields::collect(Some(&email), Some(&insight), new_embedding.as_ref());
+
+    let embedding_changed = old_embedding.map(|e| e.embedding) != new_embedding.map(|e| e.embedding);
+    let changed = after.changed_from(&before);
+    println!("[Reprocess] {}: changed {:?}", email_id, changed);
+
+    if before.category != after.category {
+        let _ = app.emit("categories:updated", ());
+    }
+
+    Ok(ReprocessReport {
+        email_id,
+        before,
+        after,
+        changed,
+        reembedded,
+        embedding_changed,
+        authentication_results,
+    })
+}
+
+/// `known_category` skips the embedding classification when the category is already cached
+async fn generate_email_insights(email: &Email, known_category: Option<String>) -> EmailInsight {
     let body = email.body_plain.as_deref()
         .or(email.body_html.as_deref())
         .unwrap_or("");
@@ -261,11 +583,15 @@ async fn generate_email_insights(email: &Email) -> EmailInsight {
     };
 
     // --- Embedding-based category classification (uses RAG engine) ---
-    let category = {
+    let category = if let Some(category) = known_category {
+        category
+    } else {
         let rag_guard = crate::commands::rag::RAG_ENGINE.lock().unwrap();
         if let Some(rag) = rag_guard.as_ref() {
             if rag.is_initialized() {
-                rag.classify_category(&email.subject, &email.from, body)
+                let min_margin = super::settings::category_min_margin();
+                rag.classify_category(&email.subject, &email.from, body, min_margin)
+                    .map(|category| category.category)
                     .unwrap_or_else(|_| "general".to_string())
             } else {
                 "general".to_string()
@@ -1,29 +1,108 @@
+use crate::auth::account::Account;
 use crate::auth::oauth::refresh_access_token_for_provider;
-use crate::auth::storage::{get_account_tokens, get_tokens, store_account_tokens, store_tokens};
+use crate::auth::storage::{get_account_tokens, get_tokens, store_tokens};
 use crate::commands::account::AccountManager;
 use crate::db::EmailDatabase;
+use crate::email::attachment_safety::{check_attachment, AttachmentSafety};
+use crate::email::attachments::{decode_transfer_encoding, load_attachments, AttachmentInput};
+use crate::email::calendar::{InviteMethod, InviteResponse, Participant};
+use crate::email::compose::{
+    build_reply_recipients, parse_address_list, sign_bodies, ReplyRecipients,
+};
+use crate::email::error::{EmailError, UidValidityChanged};
 use crate::email::idle::IdleManager;
-use crate::email::imap_client::{ImapClient, ImapCredentials};
+use crate::email::imap_client::{
+    build_draft, build_invite_reply, build_message, ImapClient, ImapCredentials, SNIPPET_LENGTH,
+};
+use crate::email::mailing_list::{one_click_unsubscribe, MailtoUnsubscribe};
+use crate::email::outbox::{Outbox, DEFAULT_UNDO_SEND_SECS};
+use crate::email::pool::PooledClient;
 use crate::email::provider::{EmailProvider, ImapFlag};
-use crate::email::server_presets::ServerConfig;
-use crate::email::types::{Email, EmailListItem};
-use chrono::Utc;
+use crate::email::remote_content::block_remote_content;
+use crate::email::server_presets::ProviderType;
+use crate::email::rules::Rule;
+use crate::email::search::SearchQuery;
+use crate::email::security::MessageSecurity;
+use crate::email::snooze::{Snooze, RESURFACE_FOLDER, SNOOZED_FOLDER};
+use crate::email::sort::{sort_items, MessageSort};
+use crate::email::special_folders::{
+    resolve_special_folder, special_folder_for_alias, SpecialFolderMap,
+};
+use crate::email::threading::{group_threads, ReplyHeaders, Thread};
+use crate::email::types::{
+    AttachmentContent, AttachmentDownload, AttachmentProgress, Email, EmailListItem, EmailPage,
+    Folder, FolderSyncEvent, FolderSyncSummary, OriginalMessage, SpecialFolder, SyncPhase,
+    SyncState,
+};
+use crate::email::utf7::decode_imap_utf7;
+use crate::email::unified::{
+    kway_merge, list_item_timestamp, MergeOrder, UnifiedInbox, UnifiedInboxOptions,
+};
+use crate::llm::rag::html_to_structured_text;
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::StreamExt;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Seek, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use tauri::State;
+use std::time::{Duration, SystemTime};
+use tauri::ipc::Channel;
+use tauri::{Emitter, State};
 
 type DbState = Arc<Mutex<Option<EmailDatabase>>>;
 
+lazy_static! {
+    /// Cancellation flags of running `sync_folder` requests, by request ID
+    static ref FOLDER_SYNCS: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
+}
+
+/// Default cap on the body returned by `get_original` (1 MiB)
+const MAX_ORIGINAL_BODY_BYTES: u32 = 1024 * 1024;
+
+/// Attachments are fetched in ranges of this size so a dropped connection
+/// only loses the chunk in flight
+const ATTACHMENT_CHUNK_BYTES: u64 = 256 * 1024;
+
+/// Consecutive failed chunk fetches before giving up (the temp file is kept)
+const MAX_CHUNK_ATTEMPTS: u32 = 4;
+
 /// Statistics for a single folder
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FolderStats {
     pub folder_name: String,
     pub unread_count: u32,
     pub total_count: u32,
+    /// False when the counts couldn't be fetched (counts are then zero, not real)
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// Outcome of a bulk flag change for one (account, folder) group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderFlagResult {
+    pub account_id: String,
+    pub folder: String,
+    pub count: usize,
+    pub ok: bool,
+    pub error: Option<EmailError>,
+}
+
+/// Outcome of marking a whole folder read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderReadResult {
+    /// IMAP name the requested folder resolved to
+    pub folder: String,
+    pub count: usize,
 }
 
 /// Parse a unified email ID "{account_id}:{folder}:{uid}" into parts
-fn parse_email_id(email_id: &str) -> Option<(String, String, u32)> {
+pub(crate) fn parse_email_id(email_id: &str) -> Option<(String, String, u32)> {
     let parts: Vec<&str> = email_id.splitn(3, ':').collect();
     if parts.len() == 3 {
         let uid = parts[2].parse::<u32>().ok()?;
@@ -33,31 +112,47 @@ fn parse_email_id(email_id: &str) -> Option<(String, String, u32)> {
     }
 }
 
+/// Name of the OAuth provider config used to refresh an account's tokens
+pub(crate) fn oauth_provider_name(provider: crate::email::server_presets::ProviderType) -> &'static str {
+    match provider {
+        crate::email::server_presets::ProviderType::Gmail => "gmail",
+        crate::email::server_presets::ProviderType::Outlook => "microsoft",
+        _ => "gmail",
+    }
+}
+
 /// Resolve OAuth2 credentials for an account, refreshing the token if expired.
-async fn resolve_oauth2_credentials(
+/// Refreshes are serialized per account: callers that arrive while one is in
+/// flight wait for it and reuse the new token, since some providers revoke a
+/// refresh token once it has been used.
+pub(crate) async fn resolve_oauth2_credentials(
+    account_manager: &AccountManager,
     account_id: &str,
     email: &str,
     provider: &str,
-) -> Result<ImapCredentials, String> {
+) -> Result<ImapCredentials, EmailError> {
+    let refresh_lock = account_manager.token_refresh_lock(account_id);
+    let _refreshing = refresh_lock.lock().await;
+
+    // Read after taking the lock so a refresh that just finished is seen
     let tokens = get_account_tokens(account_id)
         .or_else(|_| get_tokens())
-        .map_err(|e| format!("Not authenticated: {}", e))?;
+        .map_err(|e| EmailError::AuthExpired(format!("Not authenticated: {}", e)))?;
 
     // Check if token is expired (with 60s buffer to avoid edge-case failures)
     let buffer = chrono::Duration::seconds(60);
     if tokens.expires_at <= Utc::now() + buffer {
         eprintln!("[IMAP:{}] Token expired, refreshing...", account_id);
         if let Some(refresh_token) = &tokens.refresh_token {
+            // Persists the refreshed tokens for the account
             let new_tokens = refresh_access_token_for_provider(
                 refresh_token,
                 provider,
                 Some(account_id),
             )
             .await
-            .map_err(|e| format!("Token refresh failed: {}", e))?;
+            .map_err(|e| EmailError::AuthExpired(format!("Token refresh failed: {}", e)))?;
 
-            // Persist refreshed tokens
-            let _ = store_account_tokens(account_id, &new_tokens);
             let _ = store_tokens(&new_tokens);
 
             eprintln!("[IMAP:{}] Token refreshed successfully", account_id);
@@ -66,7 +161,9 @@ async fn resolve_oauth2_credentials(
                 access_token: new_tokens.access_token,
             });
         } else {
-            return Err("Token expired and no refresh token available. Please re-authenticate.".to_string());
+            return Err(EmailError::AuthExpired(
+                "Token expired and no refresh token available. Please re-authenticate.".to_string(),
+            ));
         }
     }
 
@@ -81,17 +178,26 @@ async fn resolve_oauth2_credentials(
 async fn get_active_client(
     db: &DbState,
     account_manager: &AccountManager,
-) -> Result<Arc<tokio::sync::Mutex<ImapClient>>, String> {
-    // Get active account from DB
-    let account = {
-        let db_lock = db.lock().unwrap();
-        let database = db_lock.as_ref().ok_or("Database not initialized")?;
-        database
-            .get_active_account()
-            .map_err(|e| e.to_string())?
-            .ok_or("No active account. Please add an account first.")?
-    };
+) -> Result<PooledClient, EmailError> {
+    let account = get_active_account(db)?;
+    get_client_for_account(account_manager, &account).await
+}
+
+/// Load the active account from the database
+fn get_active_account(db: &DbState) -> Result<Account, String> {
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    database
+        .get_active_account()
+        .map_err(EmailError::from)?
+        .ok_or_else(|| "No active account. Please add an account first.".to_string())
+}
 
+/// Check out a pooled ImapClient for a specific account, connecting it if needed.
+pub(crate) async fn get_client_for_account(
+    account_manager: &AccountManager,
+    account: &Account,
+) -> Result<PooledClient, EmailError> {
     // For OAuth2 accounts, check token expiry even if client is cached
     if account.auth_type == "oauth2" {
         let tokens = get_account_tokens(&account.id)
@@ -104,70 +210,451 @@ async fn get_active_client(
             .unwrap_or(true);
 
         if is_expired {
-            // Remove stale client so we recreate with fresh token
+            // Drop every pooled connection so none reconnects with the old token
             account_manager.remove_client(&account.id);
         }
     }
 
-    // Return cached client if it exists
-    if let Some(client) = account_manager.get_client(&account.id) {
+    // Reuse the account's pool if it exists
+    if let Some(client) = account_manager.get_client(&account.id).await {
         return Ok(client);
     }
 
     // Create a new client with fresh credentials
-    let provider_str = match account.provider_type() {
-        crate::email::server_presets::ProviderType::Gmail => "gmail",
-        crate::email::server_presets::ProviderType::Outlook => "microsoft",
-        _ => "gmail",
-    };
-
     let credentials = if account.auth_type == "oauth2" {
-        resolve_oauth2_credentials(&account.id, &account.email, provider_str).await?
+        let provider = oauth_provider_name(account.provider_type());
+        resolve_oauth2_credentials(account_manager, &account.id, &account.email, provider).await?
     } else {
         let password = crate::auth::storage::get_app_password(&account.id)
-            .map_err(|e| format!("No password for account: {}", e))?;
+            .map_err(|e| EmailError::AuthExpired(format!("No password for account: {}", e)))?;
         ImapCredentials::Password {
             user: account.email.clone(),
             password,
         }
     };
 
-    let server_config = ServerConfig {
-        imap_host: account.imap_host.clone(),
-        imap_port: account.imap_port,
-        smtp_host: account.smtp_host.clone(),
-        smtp_port: account.smtp_port,
-        use_tls: true,
-    };
-
     let client = ImapClient::new(
         account.id.clone(),
         account.email.clone(),
         account.provider_type(),
-        server_config,
+        account.server_config(),
         credentials,
-    );
+    )
+    .with_rate_limits(super::settings::imap_rate_limits(&account.provider_type()))
+    .with_trusted_authserv_ids(super::settings::trusted_authserv_ids(
+        &account.provider_type(),
+        &account.server_config().imap_host,
+    ));
 
-    account_manager.add_client(account.id.clone(), client);
+    account_manager.add_client(client);
 
-    account_manager
+    let client = account_manager
         .get_client(&account.id)
-        .ok_or_else(|| "Failed to store client".to_string())
+        .await
+        .ok_or_else(|| EmailError::Other("Failed to store client".to_string()))?;
+
+    // Resolve special folders once per login; later operations use the cached map
+    ensure_special_folders(account_manager, &client).await;
+
+    Ok(client)
+}
+
+/// The account's special folders, resolving them via LIST if not cached yet.
+/// A failed resolution isn't cached, so default names are used until it succeeds.
+pub(crate) async fn ensure_special_folders(
+    account_manager: &AccountManager,
+    client: &ImapClient,
+) -> SpecialFolderMap {
+    if let Some(map) = account_manager.special_folders(&client.account_id) {
+        return map;
+    }
+
+    match client.resolve_special_folders().await {
+        Ok(map) => {
+            if map.is_empty() {
+                println!(
+                    "[IMAP] {} has no SPECIAL-USE folders, using default names",
+                    client.email
+                );
+            }
+            account_manager.set_special_folders(&client.account_id, map.clone());
+            map
+        }
+        Err(e) => {
+            eprintln!("[IMAP] Failed to resolve special folders for {}: {}", client.email, e);
+            SpecialFolderMap::default()
+        }
+    }
+}
+
+/// Move a message into one of its account's special folders
+async fn move_to_special_folder(
+    account_manager: &AccountManager,
+    email_id: &str,
+    kind: SpecialFolder,
+) -> Result<(), EmailError> {
+    let (account_id, folder, uid) = parse_email_id(email_id)
+        .ok_or_else(|| EmailError::invalid_id(email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+
+    let folders = ensure_special_folders(account_manager, &client).await;
+    let moved = if kind == SpecialFolder::Archive
+        && client.provider == ProviderType::Gmail
+        && folder.eq_ignore_ascii_case("INBOX")
+    {
+        client.remove_inbox_label(&folder, uid).await
+    } else {
+        let target = folders.folder(&client.provider, kind);
+        client.move_message(&folder, uid, target).await
+    };
+    if let Err(e) = moved {
+        // The folder may have been renamed or removed; resolve again next time
+        account_manager.invalidate_special_folders(&account_id);
+        return Err(e.into());
+    }
+    account_manager.invalidate_unread(&account_id, &folder);
+    delete_moved_embedding(email_id);
+    Ok(())
+}
+
+/// The message gets a new ID in the target folder, so the old embedding
+/// would only turn up a dead search result
+fn delete_moved_embedding(email_id: &str) {
+    if let Some(vector_db) = super::rag::get_vector_db() {
+        if let Err(e) = vector_db.delete_embedding(email_id) {
+            eprintln!("[RAG] Failed to delete embedding for {}: {}", email_id, e);
+        }
+    }
+}
+
+/// Reject server sync for a paused account. Manual fetches are still allowed
+/// unless the account was paused with `block_manual_fetch`.
+pub(crate) fn ensure_sync_allowed(account: &Account, manual: bool) -> Result<(), String> {
+    if account.sync_paused && (!manual || account.block_manual_fetch) {
+        return Err(format!("Sync is paused for {}", account.email));
+    }
+    Ok(())
+}
+
+/// List a page of a folder in `sort` order and cache the messages: their
+/// full contents with `prefetch_bodies`, otherwise headers and a snippet.
+/// The first page without `before_uid`, otherwise the messages below that UID
+/// (reordered within the page).
+pub(crate) async fn sync_folder_to_cache(
+    client: &ImapClient,
+    db: &DbState,
+    folder: &str,
+    max_results: u32,
+    sort: MessageSort,
+    before_uid: Option<u32>,
+    prefetch_bodies: bool,
+) -> Result<Vec<EmailListItem>, String> {
+    let outcome = sync_folder_reporting(
+        client,
+        db,
+        folder,
+        max_results,
+        sort,
+        before_uid,
+        prefetch_bodies,
+        &SyncReporter::silent(),
+    )
+    .await?;
+    Ok(outcome.items)
+}
+
+/// What a folder sync fetched, for `sync_folder`'s summary
+struct FolderSyncOutcome {
+    items: Vec<EmailListItem>,
+    fetched: Vec<Email>,
+    failed: u32,
+    bytes: u64,
+    classified: usize,
+}
+
+/// Progress channel and cancellation flag of one `sync_folder` request
+pub(crate) struct SyncReporter {
+    request_id: String,
+    channel: Option<Channel<FolderSyncEvent>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SyncReporter {
+    /// Reports nothing and is never cancelled
+    fn silent() -> Self {
+        Self {
+            request_id: String::new(),
+            channel: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn progress(&self, phase: SyncPhase, current: u32, total: u32, bytes: u64) {
+        if let Some(channel) = &self.channel {
+            let _ = channel.send(FolderSyncEvent::Progress {
+                request_id: self.request_id.clone(),
+                phase,
+                current,
+                total,
+                bytes,
+            });
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn sync_folder_reporting(
+    client: &ImapClient,
+    db: &DbState,
+    folder: &str,
+    max_results: u32,
+    sort: MessageSort,
+    before_uid: Option<u32>,
+    prefetch_bodies: bool,
+    reporter: &SyncReporter,
+) -> Result<FolderSyncOutcome, String> {
+    reporter.progress(SyncPhase::Searching, 0, 0, 0);
+    let items = match before_uid {
+        None => client.list_messages_sorted(folder, max_results, 0, sort).await,
+        Some(before_uid) => client
+            .list_messages_before(folder, max_results, before_uid)
+            .await
+            .map(|mut items| {
+                sort_items(&mut items, sort);
+                items
+            }),
+    }
+    .map_err(EmailError::from)?;
+
+    let total = items.len() as u32;
+    let mut fetched = Vec::with_capacity(items.len());
+    let mut failed = 0;
+    let mut bytes = 0u64;
+    if prefetch_bodies {
+        for (index, item) in items.iter().enumerate() {
+            if reporter.is_cancelled() {
+                break;
+            }
+            if let Some((_, folder, uid)) = parse_email_id(&item.id) {
+                match client.get_message(&folder, uid).await {
+                    Ok(email) => {
+                        let db_lock = db.lock().unwrap();
+                        if let Some(database) = db_lock.as_ref() {
+                            let _ = database.store_email(&email);
+                        }
+                        bytes += email.size as u64;
+                        fetched.push(email);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to fetch message uid={}: {}", uid, e);
+                        failed += 1;
+                    }
+                }
+            }
+            reporter.progress(SyncPhase::Fetching, index as u32 + 1, total, bytes);
+        }
+    } else if !reporter.is_cancelled() {
+        // Bodies are fetched when a message is opened
+        let uids: Vec<u32> = items
+            .iter()
+            .filter_map(|item| parse_email_id(&item.id).map(|(_, _, uid)| uid))
+            .collect();
+        match client.fetch_headers(folder, &uids).await {
+            Ok(emails) => {
+                let db_lock = db.lock().unwrap();
+                if let Some(database) = db_lock.as_ref() {
+                    for email in &emails {
+                        let _ = database.store_email_headers(email);
+                    }
+                }
+                failed = total.saturating_sub(emails.len() as u32);
+                fetched = emails;
+            }
+            Err(e) => {
+                eprintln!("Failed to fetch headers in {}: {}", folder, e);
+                failed = total;
+            }
+        }
+        reporter.progress(SyncPhase::Fetching, total, total, bytes);
+    }
+
+    // Header-only copies have no body to classify by; they're left for later
+    let classified = if reporter.is_cancelled() || !prefetch_bodies {
+        0
+    } else {
+        crate::commands::rag::classify_batch(db, &fetched).await.classified
+    };
+
+    // Update known senders for the whole batch at once so first-time senders
+    // are flagged correctly even when several of their messages arrive together
+    let first_contacts = {
+        let db_lock = db.lock().unwrap();
+        match db_lock.as_ref() {
+            Some(database) => database
+                .record_senders(&client.email, &fetched)
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to update contacts: {}", e);
+                    Default::default()
+                }),
+            None => Default::default(),
+        }
+    };
+
+    let snippets: HashMap<&str, &str> = fetched
+        .iter()
+        .map(|email| (email.id.as_str(), email.snippet.as_str()))
+        .collect();
+    let items = items
+        .into_iter()
+        .map(|mut item| {
+            item.is_first_contact = first_contacts.contains(&item.id);
+            if let Some(snippet) = snippets.get(item.id.as_str()) {
+                item.snippet = snippet.to_string();
+            }
+            item
+        })
+        .collect();
+    Ok(FolderSyncOutcome {
+        items,
+        fetched,
+        failed,
+        bytes,
+        classified,
+    })
+}
+
+/// Bring a folder's cache up to date from where its last sync left off:
+/// changed flags are applied in place, messages that arrived since are
+/// downloaded and expunged ones dropped. Uses CONDSTORE when the server has it.
+/// Returns the downloaded emails, or None when there was nothing to resume
+/// from (first sync, or UIDVALIDITY changed) and the folder should be listed
+/// in full; the state to resume from is recorded either way. Without
+/// `prefetch_bodies` new messages are cached as headers and a snippet.
+pub(crate) async fn sync_folder_changes(
+    client: &ImapClient,
+    db: &DbState,
+    folder: &str,
+    prefetch_bodies: bool,
+) -> Result<Option<Vec<Email>>, EmailError> {
+    let account_id = client.account_id.as_str();
+    let (since, cached) = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        (
+            database.get_folder_sync_state(account_id, folder)?,
+            database.get_cached_flags(account_id, folder)?,
+        )
+    };
+
+    let mut delta = client.sync_changes(folder, since).await?;
+    let since = match since {
+        Some(since) if since.uid_validity == delta.state.uid_validity => since,
+        _ => {
+            let db_lock = db.lock().unwrap();
+            let database = db_lock.as_ref().ok_or("Database not initialized")?;
+            if since.is_some() {
+                // UIDs were reassigned; nothing cached for the folder is valid
+                let removed = database.clear_cached_folder(account_id, folder)?;
+                println!("[Sync] {} UIDVALIDITY changed, dropped {} cached", folder, removed);
+            }
+            database.set_folder_sync_state(account_id, folder, &delta.state)?;
+            return Ok(None);
+        }
+    };
+
+    let mut changed = Vec::new();
+    let mut new_uids = Vec::new();
+    for message in &delta.messages {
+        match cached.get(&message.uid) {
+            Some(flags) if flags != message => changed.push(*message),
+            Some(_) => {}
+            None if message.uid >= since.uid_next => new_uids.push(message.uid),
+            // Older than the last sync and never cached (beyond the listed pages)
+            None => {}
+        }
+    }
+    let on_server: HashSet<u32> = delta.uids.iter().copied().collect();
+    let expunged: Vec<u32> = cached
+        .keys()
+        .copied()
+        .filter(|uid| !on_server.contains(uid))
+        .collect();
+
+    let mut fetched = Vec::with_capacity(new_uids.len());
+    if prefetch_bodies {
+        for uid in new_uids {
+            match client.get_message(folder, uid).await {
+                Ok(email) => fetched.push(email),
+                Err(e) => {
+                    eprintln!("Failed to fetch message uid={}: {}", uid, e);
+                    // Pick it up again next time
+                    delta.state.uid_next = delta.state.uid_next.min(uid);
+                }
+            }
+        }
+    } else {
+        match client.fetch_headers(folder, &new_uids).await {
+            Ok(emails) => fetched = emails,
+            Err(e) => {
+                eprintln!("Failed to fetch headers in {}: {}", folder, e);
+                if let Some(&oldest) = new_uids.iter().min() {
+                    delta.state.uid_next = delta.state.uid_next.min(oldest);
+                }
+            }
+        }
+    }
+
+    {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database.update_cached_flags(account_id, folder, &changed)?;
+        database.remove_cached_uids(account_id, folder, &expunged)?;
+        for email in &fetched {
+            if prefetch_bodies {
+                database.store_email(email)?;
+            } else {
+                database.store_email_headers(email)?;
+            }
+        }
+        if let Err(e) = database.record_senders(&client.email, &fetched) {
+            eprintln!("Failed to update contacts: {}", e);
+        }
+        database.set_folder_sync_state(account_id, folder, &delta.state)?;
+    }
+
+    println!(
+        "[Sync] {} {}: {} flag changes, {} new, {} expunged ({})",
+        account_id,
+        folder,
+        changed.len(),
+        fetched.len(),
+        expunged.len(),
+        if delta.complete { "UID diff" } else { "CONDSTORE" }
+    );
+    Ok(Some(fetched))
 }
 
-/// Map frontend folder name (lowercase) to IMAP folder name (capitalized)
-fn map_folder_name(folder: &str) -> &str {
-    match folder.to_lowercase().as_str() {
-        "inbox" => "INBOX",
-        "sent" => "Sent",
-        "drafts" => "Drafts",
-        "trash" => "Trash",
-        "spam" => "Spam",
-        _ => folder, // Pass through unknown folders as-is
+/// Map frontend folder name (lowercase) to the provider's IMAP folder name
+/// (see `resolve_special_folder`). Other names pass through as UTF-8; a name
+/// still in modified UTF-7 is decoded so the client doesn't encode it a
+/// second time.
+pub(crate) fn map_folder_name<'a>(provider: &ProviderType, folder: &'a str) -> Cow<'a, str> {
+    match special_folder_for_alias(folder) {
+        Some(kind) => Cow::Borrowed(resolve_special_folder(provider, kind)),
+        None if folder.contains('&') => Cow::Owned(decode_imap_utf7(folder)),
+        None => Cow::Borrowed(folder),
     }
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn fetch_emails(
     db: State<'_, DbState>,
     account_manager: State<'_, AccountManager>,
@@ -175,209 +662,1947 @@ pub async fn fetch_emails(
     query: Option<String>,
     force_refresh: Option<bool>,
     folder: Option<String>,
-) -> Result<Vec<EmailListItem>, String> {
+    sort: Option<MessageSort>,
+    before_uid: Option<u32>,
+) -> Result<EmailPage, EmailError> {
     let should_refresh = force_refresh.unwrap_or(false);
-    let imap_folder = folder
-        .as_deref()
-        .map(map_folder_name)
-        .unwrap_or("INBOX");
+    let max_results = max_results.unwrap_or(50);
+    let account = get_active_account(&db)?;
+    let imap_folder: &str =
+        &map_folder_name(&account.provider_type(), folder.as_deref().unwrap_or("INBOX"));
+    // An explicit sort becomes the folder's remembered order
+    let sort = resolve_folder_sort(&db, &account.id, imap_folder, sort)?;
+
+    // A query searches the whole folder on the server, not just the cache
+    let search = SearchQuery::parse(query.as_deref().unwrap_or(""));
+    if !search.is_empty() {
+        ensure_sync_allowed(&account, true)?;
+
+        let client = get_client_for_account(&account_manager, &account).await?;
+        let mut uids = client
+            .search_messages(imap_folder, &search)
+            .await
+            .context("Search failed")?;
+        if let Some(before_uid) = before_uid {
+            uids.retain(|uid| *uid < before_uid);
+        }
+        uids.truncate(max_results as usize);
+
+        let mut items = client
+            .list_messages_by_uid(imap_folder, &uids)
+            .await
+            .map_err(EmailError::from)?;
+        attach_snippets(&client, imap_folder, &mut items).await;
+        sort_items(&mut items, sort);
+        return Ok(email_page(items));
+    }
 
-    // Try cache first if not forcing refresh
+    // Try cache first if not forcing refresh. A page not fetched from the
+    // server within max_cache_age_secs counts as a miss.
     if !should_refresh {
+        let max_age = super::cache::load_cache_settings()
+            .unwrap_or_default()
+            .max_cache_age_secs;
         let db_lock = db.lock().unwrap();
         if let Some(database) = db_lock.as_ref() {
-            if let Ok(cached_emails) =
-                database.get_cached_emails(imap_folder, max_results.unwrap_or(50) as i64)
-            {
-                if !cached_emails.is_empty() {
-                    return Ok(cached_emails);
+            let fresh = database
+                .oldest_cached_at(&account.id, imap_folder, max_results as i64, before_uid)
+                .ok()
+                .flatten()
+                .is_some_and(|cached_at| Utc::now().timestamp() - cached_at <= max_age);
+            if fresh {
+                if let Ok(cached_emails) = database.get_cached_emails(
+                    &account.id,
+                    imap_folder,
+                    max_results as i64,
+                    sort,
+                    before_uid,
+                ) {
+                    if !cached_emails.is_empty() {
+                        return Ok(email_page(cached_emails));
+                    }
                 }
             }
         }
     }
 
     // Fetch via IMAP client
-    let client_arc = get_active_client(&db, &account_manager).await?;
-    let client = client_arc.lock().await;
-    let items = client
-        .list_messages(imap_folder, max_results.unwrap_or(50), 0)
-        .await
-        .map_err(|e| e.to_string())?;
+    ensure_sync_allowed(&account, true)?;
 
-    // Cache the emails we fetched (fetch full for caching)
-    for item in &items {
-        if let Some((_, folder, uid)) = parse_email_id(&item.id) {
-            match client.get_message(&folder, uid).await {
-                Ok(email) => {
-                    let db_lock = db.lock().unwrap();
-                    if let Some(database) = db_lock.as_ref() {
-                        let _ = database.store_email(&email);
-                    }
+    let client = get_client_for_account(&account_manager, &account).await?;
+
+    let prefetch_bodies = super::cache::prefetch_bodies();
+    // Once a folder has been synced, refreshing its newest page only pulls changes
+    if before_uid.is_none() {
+        match sync_folder_changes(&client, &db, imap_folder, prefetch_bodies).await {
+            Ok(Some(_)) => {
+                let db_lock = db.lock().unwrap();
+                let database = db_lock.as_ref().ok_or("Database not initialized")?;
+                let items = database.get_cached_emails(
+                    &account.id,
+                    imap_folder,
+                    max_results as i64,
+                    sort,
+                    None,
+                )?;
+                if !items.is_empty() {
+                    let ids: Vec<String> = items.iter().map(|item| item.id.clone()).collect();
+                    database.touch_cached(&ids)?;
+                    return Ok(email_page(items));
                 }
-                Err(e) => eprintln!("Failed to fetch message uid={}: {}", uid, e),
             }
+            Ok(None) => {}
+            Err(e) => eprintln!("[Sync] Incremental sync of {} failed: {}", imap_folder, e),
         }
     }
 
-    Ok(items)
+    let items = sync_folder_to_cache(
+        &client,
+        &db,
+        imap_folder,
+        max_results,
+        sort,
+        before_uid,
+        prefetch_bodies,
+    )
+    .await?;
+    Ok(email_page(items))
 }
 
+/// Group the newest `max` messages of a folder into conversations, newest
+/// conversation first
 #[tauri::command]
-pub async fn get_email(
+pub async fn list_threads(
     db: State<'_, DbState>,
     account_manager: State<'_, AccountManager>,
-    email_id: String,
-) -> Result<Email, String> {
-    // Try IMAP path: parse the composite ID
-    if let Some((account_id, folder, uid)) = parse_email_id(&email_id) {
-        if let Some(client_arc) = account_manager.get_client(&account_id) {
-            let client = client_arc.lock().await;
-            return client
-                .get_message(&folder, uid)
-                .await
-                .map_err(|e| e.to_string());
-        }
-    }
+    folder: Option<String>,
+    max: Option<u32>,
+) -> Result<Vec<Thread>, EmailError> {
+    load_threads(&db, &account_manager, folder.as_deref(), max.unwrap_or(50)).await
+}
 
-    // Fallback: try database cache
+/// Conversations among the newest `max` messages of the active account's
+/// `folder` (INBOX by default)
+pub(crate) async fn load_threads(
+    db: &DbState,
+    account_manager: &AccountManager,
+    folder: Option<&str>,
+    max: u32,
+) -> Result<Vec<Thread>, EmailError> {
+    let account = get_active_account(db)?;
+    let imap_folder = map_folder_name(&account.provider_type(), folder.unwrap_or("INBOX"));
+    ensure_sync_allowed(&account, true)?;
+
+    let client = get_client_for_account(account_manager, &account).await?;
+    let mut messages = client
+        .list_thread_messages(&imap_folder, max)
+        .await
+        .map_err(EmailError::from)?;
+
+    // Envelopes carry no preview text; use the cached snippet where there is one
     {
         let db_lock = db.lock().unwrap();
         if let Some(database) = db_lock.as_ref() {
-            if let Ok(Some(email)) = database.get_email_by_id(&email_id) {
-                return Ok(email);
+            for message in &mut messages {
+                if let Ok(Some(cached)) = database.get_email_by_id(&message.id) {
+                    message.snippet = cached.snippet;
+                }
             }
         }
     }
 
-    Err(format!("Email not found: {}", email_id))
+    Ok(group_threads(messages))
 }
 
-#[tauri::command]
-pub async fn send_email(
-    db: State<'_, DbState>,
-    account_manager: State<'_, AccountManager>,
-    to: Vec<String>,
-    subject: String,
-    body: String,
-    cc: Option<Vec<String>>,
-    bcc: Option<Vec<String>>,
-) -> Result<String, String> {
-    // Send via IMAP/SMTP
-    let client_arc = get_active_client(&db, &account_manager).await?;
-    let client = client_arc.lock().await;
-    client
-        .send_email(
-            &client.email,
-            to,
-            cc.unwrap_or_default(),
-            bcc.unwrap_or_default(),
-            &subject,
-            &body,
-            "", // plain text version
-        )
-        .await
-        .map_err(|e| e.to_string())?;
-    Ok("sent".to_string())
+/// Wrap a listing with the cursor of the page after it: the lowest UID listed
+fn email_page(items: Vec<EmailListItem>) -> EmailPage {
+    let next_cursor = items
+        .iter()
+        .filter_map(|item| parse_email_id(&item.id).map(|(_, _, uid)| uid))
+        .min();
+    EmailPage { items, next_cursor }
 }
 
-#[tauri::command]
-pub async fn mark_email_read(
-    _db: State<'_, DbState>,
-    account_manager: State<'_, AccountManager>,
-    email_id: String,
-    read: bool,
-) -> Result<(), String> {
-    let (account_id, folder, uid) = parse_email_id(&email_id)
-        .ok_or_else(|| format!("Invalid email ID: {}", email_id))?;
-    let client_arc = account_manager
-        .get_client(&account_id)
-        .ok_or_else(|| format!("No client for account: {}", account_id))?;
-    let client = client_arc.lock().await;
-    client
-        .set_flags(&folder, uid, &[ImapFlag::Seen], read)
-        .await
-        .map_err(|e| e.to_string())
+/// Fill in the previews of list items fetched straight from `folder`. A failed
+/// fetch only costs the previews.
+async fn attach_snippets(client: &ImapClient, folder: &str, items: &mut [EmailListItem]) {
+    let uids: Vec<u32> = items
+        .iter()
+        .filter_map(|item| parse_email_id(&item.id).map(|(_, _, uid)| uid))
+        .collect();
+    let snippets = match client.fetch_snippets(folder, &uids, SNIPPET_LENGTH).await {
+        Ok(snippets) => snippets,
+        Err(e) => {
+            eprintln!("Failed to fetch snippets in {}: {}", folder, e);
+            return;
+        }
+    };
+    for item in items {
+        let snippet = parse_email_id(&item.id).and_then(|(_, _, uid)| snippets.get(&uid));
+        if let Some(snippet) = snippet {
+            item.snippet = snippet.clone();
+        }
+    }
 }
 
-#[tauri::command]
-pub async fn star_email(
-    _db: State<'_, DbState>,
-    account_manager: State<'_, AccountManager>,
-    email_id: String,
-    starred: bool,
-) -> Result<(), String> {
-    let (account_id, folder, uid) = parse_email_id(&email_id)
-        .ok_or_else(|| format!("Invalid email ID: {}", email_id))?;
-    let client_arc = account_manager
-        .get_client(&account_id)
-        .ok_or_else(|| format!("No client for account: {}", account_id))?;
-    let client = client_arc.lock().await;
-    client
-        .set_flags(&folder, uid, &[ImapFlag::Flagged], starred)
-        .await
-        .map_err(|e| e.to_string())
+/// Persist `requested` as the folder's sort order, or load the remembered one
+fn resolve_folder_sort(
+    db: &DbState,
+    account_id: &str,
+    folder: &str,
+    requested: Option<MessageSort>,
+) -> Result<MessageSort, String> {
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    match requested {
+        Some(sort) => {
+            database
+                .set_folder_sort(account_id, folder, sort)
+                .map_err(EmailError::from)?;
+            Ok(sort)
+        }
+        None => Ok(database
+            .get_folder_sort(account_id, folder)
+            .map_err(EmailError::from)?
+            .unwrap_or_default()),
+    }
 }
 
+/// The remembered sort order of a folder on the active account
 #[tauri::command]
-pub async fn trash_email(
-    _db: State<'_, DbState>,
-    account_manager: State<'_, AccountManager>,
-    email_id: String,
-) -> Result<(), String> {
-    let (account_id, folder, uid) = parse_email_id(&email_id)
-        .ok_or_else(|| format!("Invalid email ID: {}", email_id))?;
-    let client_arc = account_manager
-        .get_client(&account_id)
-        .ok_or_else(|| format!("No client for account: {}", account_id))?;
-    let client = client_arc.lock().await;
-    // Move to Trash folder
-    client
-        .move_message(&folder, uid, "Trash")
-        .await
-        .map_err(|e| e.to_string())
+pub async fn get_folder_sort(
+    db: State<'_, DbState>,
+    folder: String,
+) -> Result<MessageSort, EmailError> {
+    let account = get_active_account(&db)?;
+    let imap_folder = map_folder_name(&account.provider_type(), &folder);
+    resolve_folder_sort(&db, &account.id, &imap_folder, None).map_err(EmailError::from)
 }
 
+/// Remember a folder's sort order and return its cached messages in that
+/// order. Nothing is fetched from the server.
 #[tauri::command]
-pub async fn archive_email(
-    _db: State<'_, DbState>,
-    account_manager: State<'_, AccountManager>,
-    email_id: String,
-) -> Result<(), String> {
-    let (account_id, folder, uid) = parse_email_id(&email_id)
-        .ok_or_else(|| format!("Invalid email ID: {}", email_id))?;
-    let client_arc = account_manager
-        .get_client(&account_id)
-        .ok_or_else(|| format!("No client for account: {}", account_id))?;
-    let client = client_arc.lock().await;
-    // Move to Archive folder
-    client
-        .move_message(&folder, uid, "Archive")
-        .await
-        .map_err(|e| e.to_string())
+pub async fn set_folder_sort(
+    db: State<'_, DbState>,
+    folder: String,
+    sort: MessageSort,
+    max_results: Option<u32>,
+) -> Result<Vec<EmailListItem>, EmailError> {
+    let account = get_active_account(&db)?;
+    let imap_folder: &str = &map_folder_name(&account.provider_type(), &folder);
+    resolve_folder_sort(&db, &account.id, imap_folder, Some(sort))?;
+
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    database
+        .get_cached_emails(
+            &account.id,
+            imap_folder,
+            max_results.unwrap_or(50) as i64,
+            sort,
+            None,
+        )
+        .map_err(EmailError::from)
 }
 
+/// Sync the first page of a folder like `fetch_emails` with `force_refresh`,
+/// streaming progress over `on_progress` and finishing with a completion event.
+/// Cancel with `cancel_folder_sync(request_id)`; what was fetched stays cached.
 #[tauri::command]
-pub async fn start_idle_monitoring(
+pub async fn sync_folder(
     app: tauri::AppHandle,
     db: State<'_, DbState>,
-    idle_manager: State<'_, IdleManager>,
-) -> Result<(), String> {
-    let account = {
-        let db_lock = db.lock().unwrap();
-        let database = db_lock.as_ref().ok_or("Database not initialized")?;
-        database
-            .get_active_account()
-            .map_err(|e| e.to_string())?
-            .ok_or("No active account")?
+    account_manager: State<'_, AccountManager>,
+    request_id: String,
+    folder: Option<String>,
+    max_results: Option<u32>,
+    on_progress: Channel<FolderSyncEvent>,
+) -> Result<FolderSyncSummary, EmailError> {
+    let reporter = SyncReporter {
+        request_id: request_id.clone(),
+        channel: Some(on_progress),
+        cancelled: Arc::new(AtomicBool::new(false)),
     };
+    FOLDER_SYNCS
+        .lock()
+        .unwrap()
+        .insert(request_id.clone(), reporter.cancelled.clone());
 
-    let server_config = ServerConfig {
-        imap_host: account.imap_host.clone(),
-        imap_port: account.imap_port,
-        smtp_host: account.smtp_host.clone(),
-        smtp_port: account.smtp_port,
-        use_tls: true,
-    };
+    let result = run_folder_sync(&app, &db, &account_manager, folder, max_results, &reporter).await;
+    FOLDER_SYNCS.lock().unwrap().remove(&request_id);
+
+    let summary = result?;
+    if let Some(channel) = &reporter.channel {
+        let _ = channel.send(FolderSyncEvent::Completed {
+            request_id,
+            summary: summary.clone(),
+        });
+    }
+    Ok(summary)
+}
+
+/// Stop a running `sync_folder`. Returns false if no sync has that request ID.
+#[tauri::command]
+pub async fn cancel_folder_sync(request_id: String) -> Result<bool, EmailError> {
+    match FOLDER_SYNCS.lock().unwrap().get(&request_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+async fn run_folder_sync(
+    app: &tauri::AppHandle,
+    db: &DbState,
+    account_manager: &AccountManager,
+    folder: Option<String>,
+    max_results: Option<u32>,
+    reporter: &SyncReporter,
+) -> Result<FolderSyncSummary, String> {
+    let started = std::time::Instant::now();
+    let account = get_active_account(db)?;
+    let imap_folder: &str =
+        &map_folder_name(&account.provider_type(), folder.as_deref().unwrap_or("INBOX"));
+    let sort = resolve_folder_sort(db, &account.id, imap_folder, None)?;
+    let mut summary = FolderSyncSummary {
+        folder: imap_folder.to_string(),
+        ..Default::default()
+    };
+
+    ensure_sync_allowed(&account, true)?;
+    reporter.progress(SyncPhase::Connecting, 0, 1, 0);
+    let client = get_client_for_account(account_manager, &account).await?;
+    reporter.progress(SyncPhase::Connecting, 1, 1, 0);
+
+    if !reporter.is_cancelled() {
+        let outcome = sync_folder_reporting(
+            &client,
+            db,
+            imap_folder,
+            max_results.unwrap_or(50),
+            sort,
+            None,
+            super::cache::prefetch_bodies(),
+            reporter,
+        )
+        .await?;
+        drop(client);
+
+        summary.listed = outcome.items.len() as u32;
+        summary.fetched = outcome.fetched.len() as u32;
+        summary.failed = outcome.failed;
+        summary.bytes = outcome.bytes;
+        summary.classified = outcome.classified as u32;
+
+        // Header-only copies are embedded once their body has been fetched
+        let with_bodies: Vec<Email> = outcome
+            .fetched
+            .into_iter()
+            .filter(|email| email.body_html.is_some() || email.body_plain.is_some())
+            .collect();
+        let total = with_bodies.len() as u32;
+        for (index, email) in with_bodies.into_iter().enumerate() {
+            if reporter.is_cancelled() {
+                break;
+            }
+            let app = app.clone();
+            let embedded = tokio::task::spawn_blocking(move || {
+                crate::commands::rag::reembed_email(&app, &email, None)
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+            match embedded {
+                Ok(true) => summary.embedded += 1,
+                // RAG isn't initialized; nothing to embed with
+                Ok(false) => break,
+                Err(e) => eprintln!("[Sync] {}", e),
+            }
+            reporter.progress(SyncPhase::Embedding, index as u32 + 1, total, summary.bytes);
+        }
+    }
+
+    summary.cancelled = reporter.is_cancelled();
+    summary.elapsed_ms = started.elapsed().as_millis() as u64;
+    println!(
+        "[Sync] {} {}: {} fetched, {} failed, {} bytes, {} classified, {} embedded in {}ms",
+        imap_folder,
+        if summary.cancelled { "cancelled" } else { "done" },
+        summary.fetched,
+        summary.failed,
+        summary.bytes,
+        summary.classified,
+        summary.embedded,
+        summary.elapsed_ms
+    );
+    Ok(summary)
+}
+
+/// Merge the newest messages of a folder across all accounts.
+/// Accounts are fetched concurrently; any account that fails or misses the
+/// deadline is left out and reported in `incomplete_accounts`.
+#[tauri::command]
+pub async fn fetch_unified_inbox(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    folder: Option<String>,
+    max_results: Option<u32>,
+    options: Option<UnifiedInboxOptions>,
+) -> Result<UnifiedInbox, EmailError> {
+    let options = options.unwrap_or_default();
+    let max_results = max_results.unwrap_or(50);
+    let per_account = options
+        .per_account_limit
+        .unwrap_or(max_results)
+        .min(max_results);
+    let folder = folder.as_deref().unwrap_or("INBOX");
+    let deadline = std::time::Duration::from_millis(options.account_timeout_ms);
+
+    let accounts: Vec<Account> = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database
+            .list_accounts()
+            .map_err(EmailError::from)?
+            .into_iter()
+            .filter(|account| ensure_sync_allowed(account, true).is_ok())
+            .collect()
+    };
+
+    let manager = account_manager.inner();
+    let outcomes: Vec<(String, Result<Vec<EmailListItem>, EmailError>)> =
+        futures::stream::iter(accounts)
+            .map(|account| async move {
+                // Each account resolves the folder for its own provider
+                let imap_folder = map_folder_name(&account.provider_type(), folder);
+                let fetch = async {
+                    let client = get_client_for_account(manager, &account).await?;
+                    let mut items = client
+                        .list_messages(&imap_folder, per_account, 0)
+                        .await
+                        .map_err(EmailError::from)?;
+                    attach_snippets(&client, &imap_folder, &mut items).await;
+                    Ok::<_, EmailError>(items)
+                };
+                let result = match tokio::time::timeout(deadline, fetch).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        // The session was abandoned mid-command; reconnect next time
+                        manager.remove_client(&account.id);
+                        Err(EmailError::Network(format!(
+                            "timed out after {}ms",
+                            deadline.as_millis()
+                        )))
+                    }
+                };
+                (account.id, result)
+            })
+            .buffer_unordered(options.max_concurrency.max(1))
+            .collect()
+            .await;
+
+    let mut streams = Vec::new();
+    let mut incomplete_accounts = Vec::new();
+    for (account_id, result) in outcomes {
+        match result {
+            Ok(items) => streams.push(items),
+            Err(e) => {
+                eprintln!("[Unified] Dropping account {} from merge: {}", account_id, e);
+                incomplete_accounts.push(account_id);
+            }
+        }
+    }
+    incomplete_accounts.sort();
+
+    let emails = match options.order {
+        MergeOrder::Date => {
+            for stream in &mut streams {
+                stream.sort_by_key(|item| Reverse(list_item_timestamp(item)));
+            }
+            kway_merge(streams, list_item_timestamp, max_results as usize)
+        }
+        MergeOrder::Importance => {
+            let ids: Vec<String> = streams.iter().flatten().map(|i| i.id.clone()).collect();
+            let scores = {
+                let db_lock = db.lock().unwrap();
+                db_lock
+                    .as_ref()
+                    .and_then(|database| database.get_priority_scores(&ids).ok())
+                    .unwrap_or_default()
+            };
+            // Unscored mail ranks as medium priority; scores compared in thousandths
+            let key = |item: &EmailListItem| {
+                let score = scores.get(&item.id).copied().unwrap_or(0.5);
+                ((score * 1000.0) as i64, list_item_timestamp(item))
+            };
+            for stream in &mut streams {
+                stream.sort_by_key(|item| Reverse(key(item)));
+            }
+            kway_merge(streams, key, max_results as usize)
+        }
+    };
+
+    Ok(UnifiedInbox {
+        emails,
+        incomplete: !incomplete_accounts.is_empty(),
+        incomplete_accounts,
+    })
+}
+
+/// Get a message, live when its account is connected and from the cache
+/// otherwise. Remote images are blocked unless the sender is allowed to load
+/// them; `remote_content_blocked` tells the UI to offer "load images".
+#[tauri::command]
+pub async fn get_email(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<Email, EmailError> {
+    let mut email = load_email(&db, &account_manager, &email_id).await?;
+    // Local-only mode blocks remote content from every sender
+    let allowed = super::settings::ensure_network_allowed("Remote content loading").is_ok() && {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database
+            .is_remote_content_allowed(&email.from_email)
+            .map_err(EmailError::from)?
+    };
+    if !allowed {
+        if let Some(html) = &email.body_html {
+            let (html, blocked) = block_remote_content(html);
+            email.body_html = Some(html);
+            email.remote_content_blocked = blocked;
+        }
+    }
+    Ok(email)
+}
+
+/// Get a message with its remote images, for "load images" on a single
+/// message. The sender's other mail stays blocked.
+#[tauri::command]
+pub async fn load_remote_content(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<Email, EmailError> {
+    super::settings::ensure_network_allowed("Remote content loading")?;
+    load_email(&db, &account_manager, &email_id).await
+}
+
+/// A message's text with its structure kept (paragraphs, headings, list
+/// bullets, table rows), for reading aloud or a plain-text view. HTML is
+/// converted; plain-text bodies come back as they are.
+#[tauri::command]
+pub async fn get_readable_text(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<String, EmailError> {
+    let email = load_email(&db, &account_manager, &email_id).await?;
+    Ok(match (&email.body_html, &email.body_plain) {
+        (Some(html), _) => html_to_structured_text(html),
+        (None, Some(plain)) => plain.clone(),
+        (None, None) => String::new(),
+    })
+}
+
+/// Always load remote images in mail from `sender` ("Name <addr>" or a bare address)
+#[tauri::command]
+pub async fn allow_remote_content(
+    db: State<'_, DbState>,
+    sender: String,
+) -> Result<(), EmailError> {
+    super::settings::ensure_network_allowed("Remote content loading")?;
+    let address = parse_address_list(&sender)
+        .into_iter()
+        .next()
+        .ok_or_else(|| EmailError::Other(format!("Invalid sender address: {}", sender)))?
+        .address;
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    database
+        .allow_remote_content(&address)
+        .map_err(EmailError::from)
+}
+
+pub(crate) async fn load_email(
+    db: &DbState,
+    account_manager: &AccountManager,
+    email_id: &str,
+) -> Result<Email, EmailError> {
+    // Try IMAP path: parse the composite ID
+    if let Some((account_id, folder, uid)) = parse_email_id(email_id) {
+        if let Some(client) = account_manager.get_client(&account_id).await {
+            // Fetching the full body sets \Seen on the server
+            account_manager.invalidate_unread(&account_id, &folder);
+            let mut email = match cached_body(db, account_manager, email_id) {
+                Some(mut email) => {
+                    if let Err(e) = refresh_cached_flags(&client, &mut email).await {
+                        if e.is::<UidValidityChanged>() {
+                            account_manager
+                                .body_cache()
+                                .remove_folder(&account_id, Some(&folder));
+                        }
+                        return Err(e.into());
+                    }
+                    email
+                }
+                None => {
+                    let email = client
+                        .get_message(&folder, uid)
+                        .await
+                        .map_err(EmailError::from)?;
+                    let db_lock = db.lock().unwrap();
+                    if let Some(database) = db_lock.as_ref() {
+                        if let Err(e) = database.store_email(&email) {
+                            eprintln!("[Cache] Failed to cache {}: {}", email_id, e);
+                        }
+                    }
+                    email
+                }
+            };
+            account_manager.body_cache().insert(email.clone());
+
+            let db_lock = db.lock().unwrap();
+            if let Some(database) = db_lock.as_ref() {
+                email.is_first_contact = database.is_first_contact(email_id).unwrap_or(false);
+            }
+            return Ok(email);
+        }
+    }
+
+    // Fallback: try database cache
+    {
+        let db_lock = db.lock().unwrap();
+        if let Some(database) = db_lock.as_ref() {
+            if let Ok(Some(email)) = database.get_email_by_id(email_id) {
+                return Ok(email);
+            }
+        }
+    }
+
+    Err(EmailError::NotFound(format!("Email not found: {}", email_id)))
+}
+
+/// A previously fetched copy of the message: from memory, or else from the
+/// database if the row there has everything a live fetch would return. The
+/// body under a UID never changes, but the flags may be stale.
+fn cached_body(db: &DbState, account_manager: &AccountManager, email_id: &str) -> Option<Email> {
+    if let Some(email) = account_manager.body_cache().get(email_id) {
+        return Some(email);
+    }
+
+    let db_lock = db.lock().unwrap();
+    let email = db_lock.as_ref()?.get_email_by_id(email_id).ok()??;
+    // Inline parts and List-Unsubscribe-Post aren't cached, so messages
+    // needing them are fetched again
+    let complete = email.sync_state != SyncState::CachedHeadersOnly
+        && (email.body_html.is_some() || email.body_plain.is_some())
+        && !email
+            .body_html
+            .as_deref()
+            .is_some_and(|html| html.contains("cid:"))
+        && email.mailing_list.is_none();
+    complete.then_some(email)
+}
+
+/// Replace a cached message's flags with the server's, and mark it read the
+/// way fetching its body would have
+async fn refresh_cached_flags(client: &ImapClient, email: &mut Email) -> anyhow::Result<()> {
+    let flags = client.get_flags(&email.folder, email.uid).await?;
+    email.is_starred = flags.contains(&ImapFlag::Flagged);
+    if !flags.contains(&ImapFlag::Seen) {
+        client
+            .set_flags(&email.folder, email.uid, &[ImapFlag::Seen], true)
+            .await?;
+    }
+    email.is_read = true;
+    email.sync_state = SyncState::CachedFull;
+    Ok(())
+}
+
+/// IDs of the unread messages in a folder, newest first, without fetching
+/// envelopes or bodies. Served from cache until IDLE or a local flag change
+/// invalidates it.
+#[tauri::command]
+pub async fn get_unread_ids(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    folder: String,
+) -> Result<Vec<String>, EmailError> {
+    let account = get_active_account(&db)?;
+    let imap_folder: &str = &map_folder_name(&account.provider_type(), &folder);
+
+    let uids = match account_manager.cached_unread_uids(&account.id, imap_folder) {
+        Some(uids) => uids,
+        None => {
+            let client = get_client_for_account(&account_manager, &account).await?;
+            let uids = client
+                .search_unseen_uids(imap_folder)
+                .await
+                .map_err(EmailError::from)?;
+            account_manager.cache_unread_uids(&account.id, imap_folder, uids.clone());
+            uids
+        }
+    };
+
+    Ok(uids
+        .into_iter()
+        .map(|uid| format!("{}:{}:{}", account.id, imap_folder, uid))
+        .collect())
+}
+
+/// Get the raw headers and body of an email for a "Show original" view
+#[tauri::command]
+pub async fn get_original(
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    max_body_bytes: Option<u32>,
+) -> Result<OriginalMessage, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+    client
+        .get_original(
+            &folder,
+            uid,
+            max_body_bytes.unwrap_or(MAX_ORIGINAL_BODY_BYTES),
+        )
+        .await
+        .map_err(EmailError::from)
+}
+
+/// The full RFC822 source of a message as base64, e.g. for "view source" or
+/// to forward it as an .eml attachment. It's fetched with BODY.PEEK[], so the
+/// message isn't marked read. Use `save_raw_message` for very large messages.
+#[tauri::command]
+pub async fn get_raw_message(
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<String, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+    let raw = client
+        .get_raw_message(&folder, uid)
+        .await
+        .map_err(EmailError::from)?;
+    Ok(STANDARD.encode(raw))
+}
+
+/// Write a message's RFC822 source to `path` (an .eml file), fetched in
+/// ranges so a large message is never held in memory. Returns the bytes written.
+#[tauri::command]
+pub async fn save_raw_message(
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    path: String,
+) -> Result<u64, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+
+    let total = client
+        .get_raw_size(&folder, uid)
+        .await
+        .map_err(EmailError::from)? as u64;
+    let mut file =
+        std::fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut written = 0;
+    while written < total {
+        let length = (total - written).min(ATTACHMENT_CHUNK_BYTES) as u32;
+        let chunk = match client
+            .fetch_raw_range(&folder, uid, written as u32, length)
+            .await
+        {
+            Ok(chunk) if !chunk.is_empty() => chunk,
+            Ok(_) => break,
+            Err(e) => {
+                drop(file);
+                let _ = std::fs::remove_file(&path);
+                return Err(e.into());
+            }
+        };
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        written += chunk.len() as u64;
+    }
+
+    if written != total {
+        drop(file);
+        let _ = std::fs::remove_file(&path);
+        return Err(EmailError::Other(format!(
+            "Message size mismatch: received {} bytes, expected {}",
+            written, total
+        )));
+    }
+    Ok(written)
+}
+
+/// Detect S/MIME or PGP encryption and signatures on a message without
+/// marking it read. Falls back to the cached copy when the account is offline.
+#[tauri::command]
+pub async fn get_message_security(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<MessageSecurity, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+
+    if let Some(client) = account_manager.get_client(&account_id).await {
+        let (email, _) = client
+            .peek_message(&folder, uid)
+            .await
+            .map_err(EmailError::from)?;
+        return Ok(email.security);
+    }
+
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    database
+        .get_email_by_id(&email_id)
+        .map_err(EmailError::from)?
+        .map(|email| email.security)
+        .ok_or_else(|| EmailError::NotFound(format!("Email not found: {}", email_id)))
+}
+
+/// Directory downloaded attachments are saved under
+fn attachments_dir() -> Result<std::path::PathBuf, String> {
+    Ok(super::cache::get_data_dir()?.join("attachments"))
+}
+
+/// Make a string safe to use as a single path component
+fn sanitize_file_component(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').to_string();
+    if cleaned.is_empty() {
+        "attachment".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// Fetch one attachment's decoded bytes by IMAP part number (see `Email::attachments`)
+#[tauri::command]
+pub async fn get_attachment(
+    account_manager: State<'_, AccountManager>,
+    account_id: String,
+    folder: String,
+    uid: u32,
+    part_id: String,
+) -> Result<AttachmentContent, EmailError> {
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+    client
+        .get_attachment(&folder, uid, &part_id)
+        .await
+        .map_err(EmailError::from)
+}
+
+/// Fetch the decoded bytes of an inline part by the Content-ID a `cid:` URL
+/// names (see `Email::inline_parts`). Uses the active account unless
+/// `account_id` is given.
+#[tauri::command]
+pub async fn get_inline_part(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    folder: String,
+    uid: u32,
+    content_id: String,
+    account_id: Option<String>,
+) -> Result<AttachmentContent, EmailError> {
+    let client = match account_id {
+        Some(account_id) => account_manager
+            .get_client(&account_id)
+            .await
+            .ok_or_else(|| EmailError::no_client(&account_id))?,
+        None => get_active_client(&db, &account_manager).await?,
+    };
+    client
+        .get_inline_part(&folder, uid, &content_id)
+        .await
+        .map_err(EmailError::from)
+}
+
+/// Download an attachment in ranged chunks to a temp file, resuming from
+/// whatever an earlier interrupted download left behind. The encoded size is
+/// verified against BODYSTRUCTURE, then the part is decoded, hashed and run
+/// through the optional safety checker.
+#[tauri::command]
+pub async fn download_attachment(
+    app: tauri::AppHandle,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    attachment_index: u32,
+) -> Result<AttachmentDownload, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    // One connection for the whole download; other commands use the rest of the pool
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+
+    let part = client
+        .get_attachment_parts(&folder, uid)
+        .await
+        .map_err(EmailError::from)?
+        .into_iter()
+        .nth(attachment_index as usize)
+        .ok_or_else(|| format!("Attachment {} not found", attachment_index))?;
+
+    let dir = attachments_dir()?.join(sanitize_file_component(&email_id));
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(sanitize_file_component(&part.filename));
+    let temp_path = dir.join(format!("{}.part", sanitize_file_component(&part.filename)));
+
+    let total = part.octets as u64;
+    let mut received = std::fs::metadata(&temp_path).map(|m| m.len()).unwrap_or(0);
+    if received > total {
+        // Leftover from a different message/part; start over
+        received = 0;
+    }
+    let resumed_from = received;
+    if resumed_from > 0 {
+        println!(
+            "[Attachments] Resuming {} at {}/{} bytes",
+            part.filename, resumed_from, total
+        );
+    }
+
+    let mut temp_file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&temp_path)
+        .map_err(|e| format!("Failed to open temp file: {}", e))?;
+    temp_file.set_len(received).map_err(|e| e.to_string())?;
+    temp_file
+        .seek(std::io::SeekFrom::End(0))
+        .map_err(|e| e.to_string())?;
+
+    let mut failures = 0;
+    while received < total {
+        let length = (total - received).min(ATTACHMENT_CHUNK_BYTES) as u32;
+        let chunk = client
+            .fetch_part_range(&folder, uid, &part, received as u32, length)
+            .await;
+
+        match chunk {
+            Ok(data) if data.is_empty() => break,
+            Ok(data) => {
+                temp_file
+                    .write_all(&data)
+                    .map_err(|e| format!("Failed to write attachment: {}", e))?;
+                received += data.len() as u64;
+                failures = 0;
+                let _ = app.emit(
+                    "attachment:progress",
+                    AttachmentProgress {
+                        email_id: email_id.clone(),
+                        index: attachment_index,
+                        received,
+                        total,
+                    },
+                );
+            }
+            Err(e) => {
+                failures += 1;
+                if failures >= MAX_CHUNK_ATTEMPTS {
+                    return Err(EmailError::Network(format!(
+                        "Attachment download interrupted at {}/{} bytes: {}. Retry to resume.",
+                        received, total, e
+                    )));
+                }
+                eprintln!(
+                    "[Attachments] Chunk at {} failed (attempt {}): {}. Reconnecting...",
+                    received, failures, e
+                );
+                tokio::time::sleep(std::time::Duration::from_secs(2u64.pow(failures))).await;
+                if let Err(e) = client.reconnect().await {
+                    eprintln!("[Attachments] Reconnect failed: {}", e);
+                }
+            }
+        }
+    }
+    drop(temp_file);
+
+    if received != total {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(EmailError::Other(format!(
+            "Attachment size mismatch: received {} bytes, expected {}",
+            received, total
+        )));
+    }
+
+    let encoded = std::fs::read(&temp_path).map_err(|e| e.to_string())?;
+    let data = decode_transfer_encoding(&part.encoding, &encoded).map_err(EmailError::from)?;
+
+    let checker = super::settings::configured_safety_checker();
+    let report = check_attachment(&data, checker.as_deref());
+
+    std::fs::write(&path, &data).map_err(|e| format!("Failed to save attachment: {}", e))?;
+    let _ = std::fs::remove_file(&temp_path);
+
+    if report.safety == AttachmentSafety::Flagged {
+        println!(
+            "[Attachments] {} flagged by {} ({})",
+            part.filename,
+            report.checker.as_deref().unwrap_or("checker"),
+            report.sha256
+        );
+    }
+
+    Ok(AttachmentDownload {
+        email_id,
+        index: attachment_index,
+        filename: part.filename,
+        content_type: part.content_type,
+        size: data.len() as u64,
+        encoded_size: total,
+        resumed_from,
+        path: path.to_string_lossy().to_string(),
+        sha256: report.sha256,
+        safety: report.safety,
+    })
+}
+
+/// Open a downloaded attachment with the system handler. Flagged attachments
+/// are refused unless `allow_flagged` is set.
+#[tauri::command]
+pub async fn open_attachment(path: String, allow_flagged: Option<bool>) -> Result<(), EmailError> {
+    let root = attachments_dir()?
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+    let file = std::path::Path::new(&path)
+        .canonicalize()
+        .map_err(|e| format!("Attachment not found: {}", e))?;
+    if !file.starts_with(&root) {
+        return Err(EmailError::Other(
+            "Only downloaded attachments can be opened".to_string(),
+        ));
+    }
+
+    // Re-hash so a file swapped on disk can't bypass the check
+    let bytes = std::fs::read(&file).map_err(|e| e.to_string())?;
+    let checker = super::settings::configured_safety_checker();
+    let report = check_attachment(&bytes, checker.as_deref());
+    if report.safety == AttachmentSafety::Flagged && !allow_flagged.unwrap_or(false) {
+        return Err(EmailError::Other(format!(
+            "Attachment was flagged by {} (sha256 {}); confirm to open it anyway",
+            report.checker.as_deref().unwrap_or("safety checker"),
+            report.sha256
+        )));
+    }
+
+    tauri_plugin_opener::open_path(&file, None::<&str>)
+        .map_err(|e| EmailError::Other(e.to_string()))
+}
+
+/// Result of a queued send, emitted as "send-complete" once it has gone out or failed
+#[derive(Debug, Clone, Serialize)]
+pub struct SendComplete {
+    pub pending_id: String,
+    pub error: Option<EmailError>,
+    /// ID of the copy saved to the Sent folder, when the app filed one
+    pub sent_email_id: Option<String>,
+}
+
+/// The From address for a send: the account's own address, or `from` if it
+/// is one of the account's send-as aliases. `from` may carry a display name.
+fn sender_address(
+    db: &DbState,
+    account: &Account,
+    from: Option<&str>,
+) -> Result<String, EmailError> {
+    let Some(from) = from.map(str::trim).filter(|from| !from.is_empty()) else {
+        return Ok(account.email.clone());
+    };
+    let mailbox: lettre::message::Mailbox = from
+        .parse()
+        .map_err(|_| format!("Invalid from address: {}", from))?;
+    let address = mailbox.email.to_string();
+    if address.eq_ignore_ascii_case(&account.email) {
+        return Ok(from.to_string());
+    }
+
+    let allowed = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database.is_alias(&account.id, &address)?
+    };
+    if !allowed {
+        return Err(format!("{} is not a send-as alias of {}", address, account.email).into());
+    }
+    Ok(from.to_string())
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_email(
+    app: tauri::AppHandle,
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    outbox: State<'_, Outbox>,
+    to: Vec<String>,
+    subject: String,
+    body: String,
+    cc: Option<Vec<String>>,
+    bcc: Option<Vec<String>>,
+    html_body: Option<String>,
+    in_reply_to: Option<String>,
+    references: Option<Vec<String>>,
+    no_signature: Option<bool>,
+    attachments: Option<Vec<AttachmentInput>>,
+    from: Option<String>,
+) -> Result<String, EmailError> {
+    // Queue a send via SMTP and return its pending ID; it goes out after the
+    // undo-send delay unless `cancel_send` is called first. `body` is the
+    // plain-text part; with `html_body` the message is multipart/alternative
+    // (plain text generated if `body` is empty). `in_reply_to`/`references`
+    // come from the replied-to email's `message_id`/`references`; angle
+    // brackets are optional. The account signature is added unless
+    // `no_signature` is set. Attachments are read now, so a file changed
+    // during the undo window isn't picked up. `from` picks one of the
+    // account's send-as aliases; the account's own address when omitted.
+    let reply = ReplyHeaders::new(in_reply_to.as_deref(), &references.unwrap_or_default())
+        .map_err(EmailError::from)?;
+    let attachments = load_attachments(
+        &attachments.unwrap_or_default(),
+        super::settings::attachment_limit_bytes(),
+    )
+    .map_err(EmailError::from)?;
+    let account = get_active_account(&db)?;
+    let from = sender_address(&db, &account, from.as_deref())?;
+    let client = get_client_for_account(&account_manager, &account).await?;
+    let cc = cc.unwrap_or_default();
+    let bcc = bcc.unwrap_or_default();
+    let (body, html_body) = signed_bodies(
+        &account,
+        body,
+        html_body.unwrap_or_default(),
+        no_signature.unwrap_or(false),
+    );
+    // Report bad addresses now rather than after the delay
+    build_message(
+        &from,
+        &to,
+        &cc,
+        &bcc,
+        &subject,
+        &reply,
+        &html_body,
+        &body,
+        &attachments,
+    )
+    .map_err(EmailError::from)?;
+
+    // Providers that don't file SMTP mail in Sent get a copy appended
+    let sent_folder = if account.provider_type().saves_sent_mail() {
+        None
+    } else {
+        let folders = ensure_special_folders(&account_manager, &client).await;
+        let sent = folders.folder(&client.provider, SpecialFolder::Sent);
+        Some(sent.to_string())
+    };
+
+    // SMTP doesn't need the pooled IMAP connection, so don't hold it while waiting
+    let sender = client.new_connection();
+    drop(client);
+    let delay = Duration::from_secs(
+        super::settings::app_settings()
+            .undo_send_secs
+            .unwrap_or(DEFAULT_UNDO_SEND_SECS),
+    );
+    let send = async move {
+        let raw = sender
+            .send_email(
+                &from,
+                to,
+                cc,
+                bcc,
+                &subject,
+                &html_body,
+                &body,
+                &reply,
+                &attachments,
+            )
+            .await
+            .map_err(EmailError::from)?;
+        Ok::<_, EmailError>(match sent_folder {
+            Some(folder) => save_sent_copy(&sender, &folder, &raw).await,
+            None => None,
+        })
+    };
+    let pending_id = outbox.enqueue(delay, send, move |pending_id, result| {
+        let (sent_email_id, error) = match result {
+            Ok(sent_email_id) => (sent_email_id, None),
+            Err(e) => (None, Some(e)),
+        };
+        let event = SendComplete {
+            pending_id: pending_id.to_string(),
+            error,
+            sent_email_id,
+        };
+        if let Err(e) = app.emit("send-complete", event) {
+            eprintln!("[Outbox] Failed to emit send-complete: {}", e);
+        }
+    });
+    println!("[Outbox] Queued send {} ({}s undo window)", pending_id, delay.as_secs());
+    Ok(pending_id)
+}
+
+/// Store the sent bytes in `folder` and return the copy's email ID. The
+/// message is already out, so a failure here is only logged.
+async fn save_sent_copy(client: &ImapClient, folder: &str, raw: &[u8]) -> Option<String> {
+    match client.save_sent(folder, raw).await {
+        Ok(uid) => Some(format!("{}:{}:{}", client.account_id, folder, uid)),
+        Err(e) => {
+            eprintln!(
+                "[Outbox] Sent, but failed to save a copy to {}: {:#}",
+                folder, e
+            );
+            None
+        }
+    }
+}
+
+/// Answer a meeting invite: emails the organizer an iTIP REPLY carrying the
+/// account's participation status
+#[tauri::command]
+pub async fn respond_to_invite(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    response: InviteResponse,
+) -> Result<(), EmailError> {
+    let email = load_email(&db, &account_manager, &email_id).await?;
+    let invite = email
+        .invite
+        .as_ref()
+        .filter(|invite| invite.method == InviteMethod::Request)
+        .ok_or_else(|| EmailError::Other("This message is not a meeting invite".to_string()))?;
+    let organizer = invite
+        .organizer
+        .as_ref()
+        .ok_or_else(|| EmailError::Other("The invite has no organizer to reply to".to_string()))?;
+    let account = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database
+            .get_account(&email.account_id)
+            .map_err(EmailError::from)?
+            .ok_or_else(|| {
+                EmailError::NotFound(format!("Account not found: {}", email.account_id))
+            })?
+    };
+
+    // Answer as the attendee the organizer listed, so their CN is kept
+    let attendee = invite
+        .attendees
+        .iter()
+        .find(|attendee| attendee.email.eq_ignore_ascii_case(&account.email))
+        .cloned()
+        .unwrap_or_else(|| Participant {
+            email: account.email.clone(),
+            name: Some(account.display_name.clone()).filter(|name| !name.is_empty()),
+            status: None,
+        });
+    let who = attendee.name.as_deref().unwrap_or(&attendee.email);
+    let note = match response {
+        InviteResponse::Accept => format!("{} has accepted this invitation.", who),
+        InviteResponse::Decline => format!("{} has declined this invitation.", who),
+        InviteResponse::Tentative => format!("{} has tentatively accepted this invitation.", who),
+    };
+    let subject = format!("{}: {}", response.label(), invite.summary);
+    // Threading the reply under the invite is a nicety; odd IDs don't block it
+    let reply = ReplyHeaders::new(Some(&email.message_id), &email.references).unwrap_or_default();
+    let message = build_invite_reply(
+        &account.email,
+        &organizer.email,
+        &subject,
+        &reply,
+        &note,
+        &invite.reply(&attendee, response, Utc::now()),
+    )
+    .map_err(EmailError::from)?;
+
+    let client = get_client_for_account(&account_manager, &account).await?;
+    // SMTP doesn't need the pooled IMAP connection
+    let sender = client.new_connection();
+    drop(client);
+    sender
+        .send_with_retry(&message)
+        .await
+        .map_err(EmailError::from)?;
+    println!(
+        "[Calendar] Sent {} reply for {}",
+        response.label(),
+        invite.uid
+    );
+    Ok(())
+}
+
+/// Plain and HTML bodies with the account's signature added, before any
+/// quoted text. Signing is idempotent, so a saved draft isn't signed twice.
+fn signed_bodies(
+    account: &Account,
+    body: String,
+    html_body: String,
+    no_signature: bool,
+) -> (String, String) {
+    if no_signature {
+        return (body, html_body);
+    }
+    sign_bodies(
+        &body,
+        &html_body,
+        account.signature.as_deref(),
+        account.html_signature.as_deref(),
+    )
+}
+
+/// To/Cc for replying to `email`. The account's own address and any
+/// `aliases` are left out; see `build_reply_recipients`.
+#[tauri::command]
+pub async fn get_reply_recipients(
+    db: State<'_, DbState>,
+    email: Email,
+    reply_all: bool,
+    aliases: Option<Vec<String>>,
+) -> Result<ReplyRecipients, EmailError> {
+    let mut own_addresses = aliases.unwrap_or_default();
+    {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        if let Some(account) = database
+            .get_account(&email.account_id)
+            .map_err(EmailError::from)?
+        {
+            own_addresses.push(account.email);
+        }
+    }
+    Ok(build_reply_recipients(&email, reply_all, &own_addresses))
+}
+
+/// Stop a queued send before it goes out
+#[tauri::command]
+pub async fn cancel_send(outbox: State<'_, Outbox>, pending_id: String) -> Result<(), EmailError> {
+    if outbox.cancel(&pending_id) {
+        Ok(())
+    } else {
+        Err(EmailError::NotFound(format!(
+            "Send {} is no longer pending",
+            pending_id
+        )))
+    }
+}
+
+/// Save a composed message to the active account's Drafts folder and return
+/// its UID. Pass the previous `draft_uid` to replace that version.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn save_draft(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    to: Vec<String>,
+    subject: String,
+    body: String,
+    cc: Option<Vec<String>>,
+    bcc: Option<Vec<String>>,
+    html_body: Option<String>,
+    draft_uid: Option<u32>,
+    no_signature: Option<bool>,
+) -> Result<u32, EmailError> {
+    let account = get_active_account(&db)?;
+    let client = get_client_for_account(&account_manager, &account).await?;
+    let folders = ensure_special_folders(&account_manager, &client).await;
+    let drafts = folders.folder(&client.provider, SpecialFolder::Drafts);
+
+    let (body, html_body) = signed_bodies(
+        &account,
+        body,
+        html_body.unwrap_or_default(),
+        no_signature.unwrap_or(false),
+    );
+    let draft = build_draft(
+        &client.email,
+        &to,
+        &cc.unwrap_or_default(),
+        &bcc.unwrap_or_default(),
+        &subject,
+        &html_body,
+        &body,
+        SystemTime::now(),
+    )
+    .map_err(EmailError::from)?;
+    let uid = client
+        .save_draft(drafts, &draft, draft_uid)
+        .await
+        .map_err(EmailError::from)?;
+    println!("[Drafts] Saved draft {} in {}", uid, drafts);
+    Ok(uid)
+}
+
+#[tauri::command]
+pub async fn mark_email_read(
+    _db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    read: bool,
+) -> Result<(), EmailError> {
+    set_read_flag(&account_manager, &email_id, read).await
+}
+
+/// Add or remove \Seen on the server and drop the folder's cached unread UIDs
+async fn set_read_flag(
+    account_manager: &AccountManager,
+    email_id: &str,
+    read: bool,
+) -> Result<(), EmailError> {
+    let (account_id, folder, uid) = parse_email_id(email_id)
+        .ok_or_else(|| EmailError::invalid_id(email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+    client
+        .set_flags(&folder, uid, &[ImapFlag::Seen], read)
+        .await
+        .map_err(EmailError::from)?;
+    account_manager.invalidate_unread(&account_id, &folder);
+    Ok(())
+}
+
+/// Mark many messages read or unread with one UID STORE per folder.
+/// A failing folder is reported in its result and doesn't stop the others.
+#[tauri::command]
+pub async fn mark_emails_read(
+    account_manager: State<'_, AccountManager>,
+    email_ids: Vec<String>,
+    read: bool,
+) -> Result<Vec<FolderFlagResult>, EmailError> {
+    let groups = group_email_ids(&email_ids)?;
+    let mut results = Vec::with_capacity(groups.len());
+
+    for ((account_id, folder), uids) in groups {
+        let outcome = match account_manager.get_client(&account_id).await {
+            Some(client) => {
+                client
+                    .set_flags_bulk(&folder, &uids, &[ImapFlag::Seen], read)
+                    .await
+                    .map_err(EmailError::from)
+            }
+            None => Err(EmailError::no_client(&account_id)),
+        };
+        if let Err(e) = &outcome {
+            eprintln!(
+                "[Flags] Failed to update {} message(s) in {}: {}",
+                uids.len(),
+                folder,
+                e
+            );
+        }
+        account_manager.invalidate_unread(&account_id, &folder);
+
+        results.push(FolderFlagResult {
+            account_id,
+            folder,
+            count: uids.len(),
+            ok: outcome.is_ok(),
+            error: outcome.err(),
+        });
+    }
+
+    Ok(results)
+}
+
+/// Mark every unread message in a folder of the active account read.
+/// Returns the IMAP folder the name resolved to and how many messages changed,
+/// so the caller can tell which folder was actually affected.
+#[tauri::command]
+pub async fn mark_folder_read(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    folder: String,
+) -> Result<FolderReadResult, EmailError> {
+    if folder.trim().is_empty() {
+        return Err("Folder name is required".into());
+    }
+    let account = get_active_account(&db)?;
+    let imap_folder = map_folder_name(&account.provider_type(), &folder).into_owned();
+    let client = get_client_for_account(&account_manager, &account).await?;
+
+    let uids = client
+        .mark_folder_read(&imap_folder)
+        .await
+        .map_err(EmailError::from)?;
+    account_manager.invalidate_unread(&account.id, &imap_folder);
+
+    if !uids.is_empty() {
+        let db_lock = db.lock().unwrap();
+        if let Some(database) = db_lock.as_ref() {
+            if let Err(e) = database.mark_cached_read(&account.id, &imap_folder, &uids) {
+                eprintln!("[Flags] Failed to mark cached {} read: {}", imap_folder, e);
+            }
+        }
+    }
+
+    println!("[Flags] Marked {} message(s) read in {}", uids.len(), imap_folder);
+    Ok(FolderReadResult {
+        folder: imap_folder,
+        count: uids.len(),
+    })
+}
+
+/// Group email IDs by (account, folder), keeping each group's UIDs
+fn group_email_ids(
+    email_ids: &[String],
+) -> Result<BTreeMap<(String, String), Vec<u32>>, EmailError> {
+    let mut groups: BTreeMap<(String, String), Vec<u32>> = BTreeMap::new();
+    for email_id in email_ids {
+        let (account_id, folder, uid) = parse_email_id(email_id)
+            .ok_or_else(|| EmailError::invalid_id(email_id))?;
+        groups.entry((account_id, folder)).or_default().push(uid);
+    }
+    Ok(groups)
+}
+
+#[tauri::command]
+pub async fn star_email(
+    _db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    starred: bool,
+) -> Result<(), EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+    client
+        .set_flags(&folder, uid, &[ImapFlag::Flagged], starred)
+        .await
+        .map_err(EmailError::from)
+}
+
+/// Every flag and keyword on a message, for rendering labels
+#[tauri::command]
+pub async fn get_email_flags(
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<Vec<ImapFlag>, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+    client
+        .get_flags(&folder, uid)
+        .await
+        .map_err(EmailError::from)
+}
+
+/// Add or remove custom keywords (`$Important`, `Todo`, ...); Gmail labels on Gmail
+#[tauri::command]
+pub async fn set_email_keywords(
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    keywords: Vec<String>,
+    add: bool,
+) -> Result<(), EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let flags = keywords
+        .iter()
+        .map(|keyword| ImapFlag::keyword(keyword))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(EmailError::from)?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+    client
+        .set_flags(&folder, uid, &flags, add)
+        .await
+        .map_err(EmailError::from)
+}
+
+#[tauri::command]
+pub async fn trash_email(
+    _db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<(), EmailError> {
+    move_to_special_folder(&account_manager, &email_id, SpecialFolder::Trash).await
+}
+
+#[tauri::command]
+pub async fn archive_email(
+    _db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<(), EmailError> {
+    move_to_special_folder(&account_manager, &email_id, SpecialFolder::Archive).await
+}
+
+/// File a copy of a message into `dest_folder`, keeping the original.
+/// Returns the copy's UID when the server supports UIDPLUS.
+#[tauri::command]
+pub async fn copy_email(
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    dest_folder: String,
+) -> Result<Option<u32>, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+
+    let dest = map_folder_name(&client.provider, &dest_folder);
+    let copied = client.copy_message(&folder, uid, &dest).await?;
+    account_manager.invalidate_unread(&account_id, &dest);
+    Ok(copied)
+}
+
+/// Move a message into `dest_folder` (special folder aliases like "archive"
+/// are resolved). A missing folder is created when `create_if_missing` is
+/// set. Returns the message's new UID when the server supports UIDPLUS.
+#[tauri::command]
+pub async fn move_email(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    dest_folder: String,
+    create_if_missing: Option<bool>,
+    dest_account_id: Option<String>,
+) -> Result<Option<u32>, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    // MOVE and COPY only work within one IMAP connection
+    if dest_account_id.is_some_and(|dest| dest != account_id) {
+        return Err(EmailError::Other(
+            "Messages can't be moved between accounts".to_string(),
+        ));
+    }
+    let dest_folder = dest_folder.trim();
+    if dest_folder.is_empty() {
+        return Err(EmailError::Other(
+            "Destination folder is required".to_string(),
+        ));
+    }
+    if dest_folder.contains(['*', '%', '\r', '\n']) {
+        return Err(EmailError::Other(format!(
+            "Invalid folder name: {}",
+            dest_folder
+        )));
+    }
+
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+    let dest = map_folder_name(&client.provider, dest_folder);
+    if dest == folder {
+        return Err(EmailError::Other(format!(
+            "Message is already in {}",
+            folder
+        )));
+    }
+
+    if create_if_missing.unwrap_or(false) {
+        client.ensure_folder(&dest).await?;
+    } else if !client.folder_exists(&dest).await? {
+        return Err(EmailError::NotFound(format!("Folder {}", dest)));
+    }
+
+    let moved = client.move_message_to(&folder, uid, &dest).await?;
+    {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database.remove_cached_uids(&account_id, &folder, &[uid])?;
+    }
+    account_manager.invalidate_unread(&account_id, &folder);
+    account_manager.invalidate_unread(&account_id, &dest);
+    delete_moved_embedding(&email_id);
+    Ok(moved)
+}
+
+/// What `unsubscribe_email` did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum UnsubscribeOutcome {
+    /// The RFC 8058 one-click POST was accepted; nothing left to do
+    OneClick { url: String },
+    /// Send this message to unsubscribe (for the user to review and send)
+    Mailto(MailtoUnsubscribe),
+    /// The link has to be opened in a browser
+    Browser { url: String },
+}
+
+/// Unsubscribe from the list a message came from, using its
+/// List-Unsubscribe header: a one-click POST when the sender supports it,
+/// otherwise the mailto: message to send or the link to open
+#[tauri::command]
+pub async fn unsubscribe_email(
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<UnsubscribeOutcome, EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+
+    // Fetched live: the cache doesn't keep List-Unsubscribe-Post
+    let email = client.get_message(&folder, uid).await.map_err(EmailError::from)?;
+    let info = email.unsubscribe.ok_or_else(|| {
+        EmailError::NotFound(format!("No usable unsubscribe method for {}", email_id))
+    })?;
+
+    match (info.https, info.mailto) {
+        // In local-only mode the mailto: or browser fallback is offered instead
+        (Some(url), _)
+            if info.one_click
+                && super::settings::ensure_network_allowed("One-click unsubscribe").is_ok() =>
+        {
+            one_click_unsubscribe(&url).await.map_err(EmailError::from)?;
+            println!("[Unsubscribe] One-click unsubscribe sent for {}", email_id);
+            Ok(UnsubscribeOutcome::OneClick { url })
+        }
+        (_, Some(mailto)) => Ok(UnsubscribeOutcome::Mailto(mailto)),
+        (Some(url), None) => Ok(UnsubscribeOutcome::Browser { url }),
+        (None, None) => Err(EmailError::NotFound(format!(
+            "No usable unsubscribe method for {}",
+            email_id
+        ))),
+    }
+}
+
+/// Snooze a message until `until`: it moves to the Snoozed folder (created
+/// on first use) and comes back to the inbox when due. Snoozing a message
+/// that is already snoozed only changes its due time.
+#[tauri::command]
+pub async fn snooze_email(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+    until: DateTime<Utc>,
+) -> Result<Snooze, EmailError> {
+    if until <= Utc::now() {
+        return Err(EmailError::Other(
+            "Snooze time must be in the future".to_string(),
+        ));
+    }
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+
+    let message = client
+        .get_thread_message(&folder, uid)
+        .await
+        .map_err(EmailError::from)?;
+    if message.message_id.is_empty() {
+        return Err(EmailError::Other(
+            "Can't snooze a message without a Message-ID".to_string(),
+        ));
+    }
+    let snooze = Snooze {
+        account_id: account_id.clone(),
+        message_id: message.message_id,
+        subject: message.subject,
+        folder: folder.clone(),
+        until: until.timestamp(),
+    };
+
+    // Recorded first: a snooze whose message never reached the Snoozed
+    // folder is dropped when due, but a snoozed message with no record
+    // would never come back
+    {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database.store_snooze(&snooze)?;
+    }
+    if folder == SNOOZED_FOLDER {
+        return Ok(snooze);
+    }
+
+    let moved = match client.ensure_folder(SNOOZED_FOLDER).await {
+        Ok(()) => client.move_message(&folder, uid, SNOOZED_FOLDER).await,
+        Err(e) => Err(e),
+    };
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    if let Err(e) = moved {
+        database.remove_snooze(&account_id, &snooze.message_id)?;
+        return Err(e.into());
+    }
+    database.remove_cached_uids(&account_id, &folder, &[uid])?;
+    account_manager.invalidate_unread(&account_id, &folder);
+    println!("[Snooze] Snoozed {} until {}", email_id, until.to_rfc3339());
+    Ok(snooze)
+}
+
+/// Bring a snoozed message back to the inbox now instead of when it's due
+#[tauri::command]
+pub async fn unsnooze_email(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<(), EmailError> {
+    let (account_id, folder, uid) = parse_email_id(&email_id)
+        .ok_or_else(|| EmailError::invalid_id(&email_id))?;
+    let client = account_manager
+        .get_client(&account_id)
+        .await
+        .ok_or_else(|| EmailError::no_client(&account_id))?;
+
+    let message = client
+        .get_thread_message(&folder, uid)
+        .await
+        .map_err(EmailError::from)?;
+    if folder != RESURFACE_FOLDER {
+        client
+            .move_message(&folder, uid, RESURFACE_FOLDER)
+            .await
+            .map_err(EmailError::from)?;
+    }
+
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    database.remove_snooze(&account_id, &message.message_id)?;
+    database.remove_cached_uids(&account_id, &folder, &[uid])?;
+    account_manager.invalidate_unread(&account_id, RESURFACE_FOLDER);
+    Ok(())
+}
+
+/// The active account's snoozed messages, soonest due first
+#[tauri::command]
+pub async fn list_snoozed(db: State<'_, DbState>) -> Result<Vec<Snooze>, EmailError> {
+    let account = get_active_account(&db)?;
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    Ok(database.get_snoozes(&account.id)?)
+}
+
+/// Add a filter rule, run on new inbox mail after the existing rules
+#[tauri::command]
+pub async fn add_rule(db: State<'_, DbState>, rule: Rule) -> Result<Rule, EmailError> {
+    rule.validate()?;
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    let id = database.store_rule(&rule)?;
+    println!("[Rules] Added rule \"{}\" (#{})", rule.name, id);
+    Ok(Rule { id, ..rule })
+}
+
+/// All filter rules, in the order they are evaluated
+#[tauri::command]
+pub async fn list_rules(db: State<'_, DbState>) -> Result<Vec<Rule>, EmailError> {
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    Ok(database.get_rules(None)?)
+}
+
+#[tauri::command]
+pub async fn delete_rule(db: State<'_, DbState>, rule_id: i64) -> Result<(), EmailError> {
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    if !database.delete_rule(rule_id)? {
+        return Err(EmailError::NotFound(format!("No rule #{}", rule_id)));
+    }
+    Ok(())
+}
+
+/// Move a message to the account's junk folder
+#[tauri::command]
+pub async fn mark_as_spam(
+    account_manager: State<'_, AccountManager>,
+    email_id: String,
+) -> Result<(), EmailError> {
+    move_to_special_folder(&account_manager, &email_id, SpecialFolder::Spam).await
+}
+
+/// Special folders resolved for an account (None for roles the server
+/// doesn't advertise; those use the default folder names)
+#[tauri::command]
+pub async fn get_special_folders(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    account_id: String,
+) -> Result<SpecialFolderMap, EmailError> {
+    let account = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database
+            .get_account(&account_id)
+            .map_err(EmailError::from)?
+            .ok_or_else(|| format!("Account not found: {}", account_id))?
+    };
+
+    let client = get_client_for_account(&account_manager, &account).await?;
+    Ok(ensure_special_folders(&account_manager, &client).await)
+}
+
+/// Start IDLE on the active account's `folders` (the previous selection, or
+/// just INBOX, when omitted). Monitors already running on a listed folder are
+/// kept. Returns the folders now monitored.
+#[tauri::command]
+pub async fn start_idle_monitoring(
+    app: tauri::AppHandle,
+    db: State<'_, DbState>,
+    idle_manager: State<'_, IdleManager>,
+    folders: Option<Vec<String>>,
+) -> Result<Vec<String>, EmailError> {
+    let account = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database
+            .get_active_account()
+            .map_err(EmailError::from)?
+            .ok_or("No active account")?
+    };
+
+    if account.sync_paused {
+        println!("[IDLE] Account {} is paused, not starting IDLE", account.id);
+        return Ok(Vec::new());
+    }
+
+    let folders = match folders {
+        Some(folders) => folders
+            .iter()
+            .map(|folder| map_folder_name(&account.provider_type(), folder).into_owned())
+            .collect(),
+        None => idle_manager.configured_folders(&account.id).await,
+    };
 
     idle_manager
         .start_idle(
@@ -385,25 +2610,26 @@ pub async fn start_idle_monitoring(
             account.id.clone(),
             account.email.clone(),
             account.provider_type(),
-            server_config,
+            account.server_config(),
             account.auth_type.clone(),
+            &folders,
         )
         .await;
 
-    Ok(())
+    Ok(idle_manager.monitored_folders(&account.id).await)
 }
 
 #[tauri::command]
 pub async fn stop_idle_monitoring(
     db: State<'_, DbState>,
     idle_manager: State<'_, IdleManager>,
-) -> Result<(), String> {
+) -> Result<(), EmailError> {
     let account_id = {
         let db_lock = db.lock().unwrap();
         let database = db_lock.as_ref().ok_or("Database not initialized")?;
         database
             .get_active_account()
-            .map_err(|e| e.to_string())?
+            .map_err(EmailError::from)?
             .map(|a| a.id)
     };
 
@@ -414,40 +2640,519 @@ pub async fn stop_idle_monitoring(
     Ok(())
 }
 
+/// Stop IDLE on every account before the system sleeps, and log out pooled
+/// connections that aren't in use, since their sockets won't survive it.
+/// Returns the accounts `resume_idle_monitoring` will restart.
+#[tauri::command]
+pub async fn suspend_idle_monitoring(
+    account_manager: State<'_, AccountManager>,
+    idle_manager: State<'_, IdleManager>,
+) -> Result<Vec<String>, EmailError> {
+    let suspended = idle_manager.suspend_all().await;
+    let closed = account_manager.close_unused_connections().await;
+    println!("[IDLE] Closed {} pooled connection(s) for suspend", closed);
+    Ok(suspended)
+}
+
+/// Reconnect the monitors stopped by `suspend_idle_monitoring` once the system
+/// is awake, and sync what arrived meanwhile. Returns the accounts resumed.
+#[tauri::command]
+pub async fn resume_idle_monitoring(
+    app: tauri::AppHandle,
+    idle_manager: State<'_, IdleManager>,
+) -> Result<Vec<String>, EmailError> {
+    Ok(idle_manager.resume_all(app).await)
+}
+
+/// IDLE monitoring state for one account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdleStatus {
+    pub account_id: String,
+    pub email: String,
+    pub paused: bool,
+    pub monitored_folders: Vec<String>,
+}
+
+/// Report which folders are being monitored with IDLE, per account
+#[tauri::command]
+pub async fn get_idle_status(
+    db: State<'_, DbState>,
+    idle_manager: State<'_, IdleManager>,
+) -> Result<Vec<IdleStatus>, EmailError> {
+    let accounts = {
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().ok_or("Database not initialized")?;
+        database.list_accounts().map_err(EmailError::from)?
+    };
+
+    let mut statuses = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        statuses.push(IdleStatus {
+            monitored_folders: idle_manager.monitored_folders(&account.id).await,
+            account_id: account.id,
+            email: account.email,
+            paused: account.sync_paused,
+        });
+    }
+    Ok(statuses)
+}
+
+/// Folders of the active account as listed by the server
+#[tauri::command]
+pub async fn list_folders(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+) -> Result<Vec<Folder>, EmailError> {
+    let client = get_active_client(&db, &account_manager).await?;
+
+    client
+        .list_folders()
+        .await
+        .context("Failed to list folders")
+        .map_err(EmailError::from)
+}
+
+/// Create a folder in the active account, nested under `parent` if given.
+/// Returns its full name.
+#[tauri::command]
+pub async fn create_folder(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    name: String,
+    parent: Option<String>,
+) -> Result<String, EmailError> {
+    let client = get_active_client(&db, &account_manager).await?;
+    Ok(client.create_folder(&name, parent.as_deref()).await?)
+}
+
+/// Rename a folder of the active account; `new` is the full new name
+#[tauri::command]
+pub async fn rename_folder(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    old: String,
+    new: String,
+) -> Result<(), EmailError> {
+    let client = get_active_client(&db, &account_manager).await?;
+    let moved = client.rename_folder(&old, &new).await?;
+    forget_folders(&db, &account_manager, &client.account_id, &moved)
+}
+
+/// Delete a folder of the active account. INBOX and the other special
+/// folders are refused.
+#[tauri::command]
+pub async fn delete_folder(
+    db: State<'_, DbState>,
+    account_manager: State<'_, AccountManager>,
+    name: String,
+) -> Result<(), EmailError> {
+    let client = get_active_client(&db, &account_manager).await?;
+    client.delete_folder(&name).await?;
+    forget_folders(&db, &account_manager, &client.account_id, &[name])
+}
+
+/// Drop what's cached for folders that no longer exist under these names
+fn forget_folders(
+    db: &DbState,
+    account_manager: &AccountManager,
+    account_id: &str,
+    folders: &[String],
+) -> Result<(), EmailError> {
+    account_manager.invalidate_special_folders(account_id);
+    let db_lock = db.lock().unwrap();
+    let database = db_lock.as_ref().ok_or("Database not initialized")?;
+    for folder in folders {
+        account_manager.invalidate_unread(account_id, folder);
+        account_manager
+            .body_cache()
+            .remove_folder(account_id, Some(folder));
+        let removed = database.forget_folder(account_id, folder)?;
+        println!("[Folders] Forgot {} ({} cached emails)", folder, removed);
+    }
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn get_folder_stats(
     db: State<'_, DbState>,
     account_manager: State<'_, AccountManager>,
-) -> Result<Vec<FolderStats>, String> {
-    // Get active client
-    let client_arc = get_active_client(&db, &account_manager).await?;
-    let client = client_arc.lock().await;
+) -> Result<Vec<FolderStats>, EmailError> {
+    let account = get_active_account(&db)?;
+    let manager = account_manager.inner();
 
-    // List of folders to get stats for
-    let folders = ["INBOX", "Sent", "Drafts", "Trash", "Spam"];
-    let mut stats = Vec::new();
+    // Every folder the server lists, except \Noselect hierarchy placeholders
+    let folders: Vec<String> = get_client_for_account(manager, &account)
+        .await?
+        .list_folders()
+        .await
+        .context("Failed to list folders")?
+        .into_iter()
+        .filter(|folder| folder.selectable)
+        .map(|folder| folder.name)
+        .collect();
 
-    for folder in &folders {
-        match client.get_folder_stats(folder).await {
-            Ok((total_count, unread_count)) => {
-                stats.push(FolderStats {
-                    folder_name: folder.to_string(),
+    // One pooled connection per folder, so the round trips overlap; beyond
+    // the pool size, folders wait for a connection to come back
+    let lookups = folders.into_iter().map(|folder| {
+        let account = &account;
+        async move {
+            let counts = match get_client_for_account(manager, account).await {
+                Ok(client) => client.get_folder_stats(&folder).await.map_err(EmailError::from),
+                Err(e) => Err(e),
+            };
+            match counts {
+                Ok((total_count, unread_count)) => FolderStats {
+                    folder_name: folder,
                     unread_count,
                     total_count,
-                });
+                    ok: true,
+                    error: None,
+                },
+                Err(e) => {
+                    // Log error but continue with other folders
+                    eprintln!("Failed to get stats for folder {}: {}", folder, e);
+                    // Add zero counts for failed folders, marked as failed
+                    FolderStats {
+                        folder_name: folder,
+                        unread_count: 0,
+                        total_count: 0,
+                        ok: false,
+                        error: Some(e.to_string()),
+                    }
+                }
             }
-            Err(e) => {
-                // Log error but continue with other folders
-                eprintln!("Failed to get stats for folder {}: {}", folder, e);
-                // Add zero counts for failed folders
-                stats.push(FolderStats {
-                    folder_name: folder.to_string(),
-                    unread_count: 0,
-                    total_count: 0,
-                });
+        }
+    });
+
+    Ok(futures::future::join_all(lookups).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::mock_imap::{fetch_body_response, MockImap};
+
+    fn raw_message(from: &str, subject: &str, date: &str) -> String {
+        format!(
+            "From: {}\r\nTo: me@example.com\r\nSubject: {}\r\nDate: {}\r\n\
+             Message-ID: <{}@example.com>\r\n\r\nHello there\r\n",
+            from,
+            subject,
+            date,
+            subject.replace(' ', "-")
+        )
+    }
+
+    fn list_response(seq: u32, uid: u32, subject: &str) -> String {
+        format!(
+            "* {} FETCH (UID {} FLAGS () ENVELOPE (\"Mon, 2 Mar 2026 10:00:00 +0000\" \"{}\" \
+             ((\"Ana\" NIL \"ana\" \"example.com\")) NIL NIL NIL NIL NIL NIL NIL) RFC822.SIZE 120)\r\n",
+            seq, uid, subject
+        )
+    }
+
+    #[test]
+    fn test_group_email_ids_splits_mixed_folders() {
+        let ids: Vec<String> = ["a:INBOX:3", "b:INBOX:1", "a:Archive:9", "a:INBOX:4"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        let groups = group_email_ids(&ids).unwrap();
+
+        let key = |account: &str, folder: &str| (account.to_string(), folder.to_string());
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[&key("a", "INBOX")], [3, 4]);
+        assert_eq!(groups[&key("a", "Archive")], [9]);
+        assert_eq!(groups[&key("b", "INBOX")], [1]);
+
+        assert!(group_email_ids(&["bad".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_folder_caches_fetched_messages() {
+        let first = raw_message("ana@example.com", "First", "Mon, 2 Mar 2026 10:00:00 +0000");
+        let second = raw_message("ana@example.com", "Second", "Tue, 3 Mar 2026 10:00:00 +0000");
+        let mock = MockImap::new()
+            .on("SELECT", "* 2 EXISTS\r\n")
+            .on(
+                "FETCH 1:2",
+                &(list_response(1, 11, "First") + &list_response(2, 12, "Second")),
+            )
+            .on("UID FETCH 11 ", &fetch_body_response(1, 11, "", &first))
+            .on("UID FETCH 12 ", &fetch_body_response(2, 12, "\\Seen", &second));
+        let client = mock.client("acct");
+        let db: DbState = Arc::new(Mutex::new(Some(
+            EmailDatabase::new(std::path::PathBuf::from(":memory:")).unwrap(),
+        )));
+
+        let items = sync_folder_to_cache(&client, &db, "INBOX", 10, MessageSort::Date, None, true)
+            .await
+            .unwrap();
+
+        // Newest first, and only the earliest message from a new sender is flagged
+        let ids: Vec<_> = items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, vec!["acct:INBOX:12", "acct:INBOX:11"]);
+        assert!(!items[0].is_first_contact);
+        assert!(items[1].is_first_contact);
+
+        let db_lock = db.lock().unwrap();
+        let cached = db_lock
+            .as_ref()
+            .unwrap()
+            .get_email_by_id("acct:INBOX:12")
+            .unwrap()
+            .expect("message should be cached");
+        assert_eq!(cached.subject, "Second");
+        assert!(cached.is_read);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_sync_stops_before_fetching() {
+        let mock = MockImap::new()
+            .on("SELECT", "* 1 EXISTS\r\n")
+            .on("FETCH 1:1", &list_response(1, 11, "First"));
+        let client = mock.client("acct");
+        let db: DbState = Arc::new(Mutex::new(Some(
+            EmailDatabase::new(std::path::PathBuf::from(":memory:")).unwrap(),
+        )));
+        let reporter = SyncReporter::silent();
+        reporter.cancelled.store(true, Ordering::Relaxed);
+
+        let outcome =
+            sync_folder_reporting(&client, &db, "INBOX", 10, MessageSort::Date, None, true, &reporter)
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.items.len(), 1);
+        assert!(outcome.fetched.is_empty());
+        assert!(!mock.commands().iter().any(|c| c.starts_with("UID FETCH")));
+    }
+
+    #[tokio::test]
+    async fn test_sync_folder_caches_headers_and_snippets_only() {
+        let structure =
+            "BODYSTRUCTURE (\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 20 1)";
+        let header_response = |seq: u32, uid: u32, subject: &str| {
+            let raw = raw_message("ana@example.com", subject, "Mon, 2 Mar 2026 10:00:00 +0000");
+            let header = &raw[..raw.find("\r\n\r\n").unwrap() + 4];
+            format!(
+                "* {} FETCH (UID {} FLAGS () RFC822.SIZE 120 {} BODY[HEADER] {{{}}}\r\n{})\r\n",
+                seq,
+                uid,
+                structure,
+                header.len(),
+                header
+            )
+        };
+        let text_response = |seq: u32, uid: u32, text: &str| {
+            format!(
+                "* {} FETCH (UID {} BODY[1]<0> {{{}}}\r\n{})\r\n",
+                seq,
+                uid,
+                text.len(),
+                text
+            )
+        };
+        let mock = MockImap::new()
+            .on("SELECT", "* 2 EXISTS\r\n")
+            .on(
+                "FETCH 1:2",
+                &(list_response(1, 11, "First") + &list_response(2, 12, "Second")),
+            )
+            .on(
+                "UID FETCH 11:12 (UID FLAGS",
+                &(header_response(1, 11, "First") + &header_response(2, 12, "Second")),
+            )
+            .on(
+                "UID FETCH 11:12 (UID BODY.PEEK[1]<0.2048>)",
+                &(text_response(1, 11, "Minutes attached")
+                    + &text_response(2, 12, "See you\r\non Friday")),
+            );
+        let client = mock.client("acct");
+        let db: DbState = Arc::new(Mutex::new(Some(
+            EmailDatabase::new(std::path::PathBuf::from(":memory:")).unwrap(),
+        )));
+
+        let items = sync_folder_to_cache(&client, &db, "INBOX", 10, MessageSort::Date, None, false)
+            .await
+            .unwrap();
+
+        assert_eq!(items[0].snippet, "See you on Friday");
+        assert_eq!(items[1].snippet, "Minutes attached");
+        assert!(!mock.commands().iter().any(|c| c.contains("BODY[]")));
+
+        let db_lock = db.lock().unwrap();
+        let cached = db_lock
+            .as_ref()
+            .unwrap()
+            .get_email_by_id("acct:INBOX:12")
+            .unwrap()
+            .expect("headers should be cached");
+        assert_eq!(cached.subject, "Second");
+        assert_eq!(cached.body_plain, None);
+        assert_eq!(cached.sync_state, SyncState::CachedHeadersOnly);
+    }
+
+    #[tokio::test]
+    async fn test_sync_folder_changes_uses_condstore() {
+        let old = raw_message("ana@example.com", "Old", "Mon, 2 Mar 2026 10:00:00 +0000");
+        let new = raw_message("bo@example.com", "New", "Tue, 3 Mar 2026 10:00:00 +0000");
+        let mock = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1 CONDSTORE\r\n")
+            .on(
+                "SELECT",
+                "* 3 EXISTS\r\n* OK [UIDVALIDITY 7] UIDs valid\r\n\
+                 * OK [UIDNEXT 12] Predicted next UID\r\n\
+                 * OK [HIGHESTMODSEQ 200] Highest\r\n",
+            )
+            .on(
+                "UID FETCH 1:*",
+                "* 2 FETCH (UID 10 FLAGS (\\Seen) MODSEQ (150))\r\n\
+                 * 3 FETCH (UID 11 FLAGS () MODSEQ (199))\r\n",
+            )
+            .on("UID SEARCH ALL", "* SEARCH 9 10 11\r\n")
+            .on("UID FETCH 9 ", &fetch_body_response(1, 9, "", &old))
+            .on("UID FETCH 10 ", &fetch_body_response(2, 10, "", &old))
+            .on("UID FETCH 11 ", &fetch_body_response(3, 11, "", &new));
+        let client = mock.client("acct");
+        let db: DbState = Arc::new(Mutex::new(Some(
+            EmailDatabase::new(std::path::PathBuf::from(":memory:")).unwrap(),
+        )));
+        {
+            let cached = [
+                client.get_message("INBOX", 9).await.unwrap(),
+                client.get_message("INBOX", 10).await.unwrap(),
+            ];
+            let db_lock = db.lock().unwrap();
+            let database = db_lock.as_ref().unwrap();
+            for email in &cached {
+                database.store_email(email).unwrap();
+            }
+            let state = crate::email::types::FolderSyncState {
+                uid_validity: 7,
+                uid_next: 11,
+                highest_modseq: Some(100),
+            };
+            database.set_folder_sync_state("acct", "INBOX", &state).unwrap();
+        }
+
+        let fetched = sync_folder_changes(&client, &db, "INBOX", true).await.unwrap().unwrap();
+
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].uid, 11);
+        assert!(mock
+            .commands()
+            .iter()
+            .any(|c| c.starts_with("UID FETCH 1:*") && c.contains("CHANGEDSINCE 100")));
+
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().unwrap();
+        let flags = database.get_cached_flags("acct", "INBOX").unwrap();
+        assert!(flags[&10].is_read);
+        // CHANGEDSINCE only lists changed messages, but UID 9 is still in the folder
+        assert!(!flags[&9].is_read);
+        assert!(flags.contains_key(&11));
+        let state = database.get_folder_sync_state("acct", "INBOX").unwrap().unwrap();
+        assert_eq!(state.highest_modseq, Some(200));
+        assert_eq!(state.uid_next, 12);
+    }
+
+    #[tokio::test]
+    async fn test_condstore_sync_drops_messages_expunged_since_the_last_one() {
+        let raw = raw_message("ana@example.com", "Hi", "Mon, 2 Mar 2026 10:00:00 +0000");
+        let before = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1 CONDSTORE\r\n")
+            .on(
+                "SELECT",
+                "* 2 EXISTS\r\n* OK [UIDVALIDITY 7] UIDs valid\r\n\
+                 * OK [UIDNEXT 11] Predicted next UID\r\n\
+                 * OK [HIGHESTMODSEQ 200] Highest\r\n",
+            )
+            .on(
+                "UID FETCH 1:*",
+                "* 2 FETCH (UID 10 FLAGS (\\Seen) MODSEQ (150))\r\n",
+            )
+            .on("UID SEARCH ALL", "* SEARCH 9 10\r\n")
+            .on("UID FETCH 9 ", &fetch_body_response(1, 9, "", &raw))
+            .on("UID FETCH 10 ", &fetch_body_response(2, 10, "", &raw));
+        let client = before.client("acct");
+        let db: DbState = Arc::new(Mutex::new(Some(
+            EmailDatabase::new(std::path::PathBuf::from(":memory:")).unwrap(),
+        )));
+        {
+            let cached = [
+                client.get_message("INBOX", 9).await.unwrap(),
+                client.get_message("INBOX", 10).await.unwrap(),
+            ];
+            let db_lock = db.lock().unwrap();
+            let database = db_lock.as_ref().unwrap();
+            for email in &cached {
+                database.store_email(email).unwrap();
             }
+            let state = crate::email::types::FolderSyncState {
+                uid_validity: 7,
+                uid_next: 11,
+                highest_modseq: Some(100),
+            };
+            database
+                .set_folder_sync_state("acct", "INBOX", &state)
+                .unwrap();
+        }
+        sync_folder_changes(&client, &db, "INBOX", true)
+            .await
+            .unwrap()
+            .unwrap();
+        {
+            let db_lock = db.lock().unwrap();
+            let database = db_lock.as_ref().unwrap();
+            let flags = database.get_cached_flags("acct", "INBOX").unwrap();
+            assert!(flags[&10].is_read);
+            assert!(flags.contains_key(&9));
         }
+
+        // UID 9 is expunged elsewhere; CHANGEDSINCE has nothing to report
+        let after = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1 CONDSTORE\r\n")
+            .on(
+                "SELECT",
+                "* 1 EXISTS\r\n* OK [UIDVALIDITY 7] UIDs valid\r\n\
+                 * OK [UIDNEXT 11] Predicted next UID\r\n\
+                 * OK [HIGHESTMODSEQ 200] Highest\r\n",
+            )
+            .on("UID SEARCH ALL", "* SEARCH 10\r\n");
+        let client = after.client("acct");
+        sync_folder_changes(&client, &db, "INBOX", true)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(after.commands().iter().any(|c| c == "UID SEARCH ALL"));
+        let db_lock = db.lock().unwrap();
+        let database = db_lock.as_ref().unwrap();
+        let flags = database.get_cached_flags("acct", "INBOX").unwrap();
+        assert!(!flags.contains_key(&9));
+        assert!(flags[&10].is_read);
     }
 
-    Ok(stats)
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn test_mark_read_maps_to_seen_flag() {
+        let mock = MockImap::new();
+        let account_manager = AccountManager::new();
+        account_manager.add_client(mock.client("acct"));
+        account_manager.cache_unread_uids("acct", "INBOX", vec![7]);
+
+        set_read_flag(&account_manager, "acct:INBOX:7", true).await.unwrap();
+        set_read_flag(&account_manager, "acct:INBOX:7", false).await.unwrap();
+
+        let stores: Vec<_> = mock
+            .commands()
+            .into_iter()
+            .filter(|c| c.starts_with("UID STORE"))
+            .collect();
+        assert_eq!(
+            stores,
+            vec!["UID STORE 7 +FLAGS (\\Seen)", "UID STORE 7 -FLAGS (\\Seen)"]
+        );
+        assert!(account_manager.cached_unread_uids("acct", "INBOX").is_none());
+    }
+}
@@ -5,6 +5,7 @@ pub mod cache;
 pub mod db;
 pub mod email;
 pub mod rag;
+pub mod settings;
 
 pub use account::*;
 pub use ai::*;
@@ -13,3 +14,4 @@ pub use cache::*;
 pub use db::*;
 pub use email::*;
 pub use rag::*;
+pub use settings::*;
@@ -2,11 +2,17 @@
 //!
 //! Tauri commands for embedding generation, semantic search, and contextual AI chat.
 
-use crate::db::vector_db::{EmbeddingStatus, VectorDatabase};
+use super::settings::ensure_network_allowed;
+use crate::db::vector_db::{CategoryDefinition, EmbeddingStatus, SimilarEmail, VectorDatabase};
 use crate::llm::embeddings::{self, EmbeddingEngine, DEFAULT_EMBEDDING_MODEL};
-use crate::llm::rag::{calculate_text_hash, prepare_email_text, RagEngine};
+use crate::llm::rag::{
+    builtin_categories, calculate_text_hash, default_categories, prepare_email_chunks,
+    prepare_email_text, EmbeddingInput, RagEngine, RetrievedContext, CONTEXT_MAX_CHARS,
+    DEFAULT_EMBED_BATCH_SIZE, UNCERTAIN_CATEGORY,
+};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Emitter, Manager};
 
@@ -20,11 +26,25 @@ lazy_static! {
 pub struct SearchResult {
     pub email_id: String,
     pub similarity: f32,
+    /// Ranking score; differs from `similarity` when the results were reranked
+    /// or fused with keyword matches
+    pub score: f32,
     pub subject: Option<String>,
     pub from: Option<String>,
     pub snippet: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadSearchResult {
+    pub thread_id: String,
+    pub similarity: f32,
+    /// Number of embedded messages in the thread aggregate
+    pub message_count: i64,
+    pub latest_email_id: Option<String>,
+    pub subject: Option<String>,
+    pub snippet: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingProgress {
     pub total: i64,
@@ -32,6 +52,296 @@ pub struct EmbeddingProgress {
     pub current_email_id: Option<String>,
 }
 
+/// Shared handle to the vector database, if RAG has been initialized
+pub(crate) fn get_vector_db() -> Option<Arc<VectorDatabase>> {
+    VECTOR_DB.lock().unwrap().clone()
+}
+
+/// Names of the current classification categories, in display order.
+/// The built-ins until RAG is initialized.
+pub(crate) fn category_names() -> Vec<String> {
+    let configured = get_vector_db()
+        .and_then(|vector_db| vector_db.get_categories().ok())
+        .unwrap_or_default();
+    if configured.is_empty() {
+        return builtin_categories().into_iter().map(str::to_string).collect();
+    }
+    configured.into_iter().map(|category| category.name).collect()
+}
+
+fn open_email_db(app: &AppHandle) -> Result<crate::db::EmailDatabase, String> {
+    crate::db::EmailDatabase::new(
+        app.path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to get app data dir: {}", e))?
+            .join("emails.db"),
+    )
+    .map_err(|e| format!("Failed to open email database: {}", e))
+}
+
+/// Recompute the aggregate embeddings of the given threads.
+/// Returns how many threads ended up with an embedding.
+fn refresh_thread_embeddings<'a>(
+    email_db: &crate::db::EmailDatabase,
+    vector_db: &VectorDatabase,
+    thread_ids: impl IntoIterator<Item = &'a String>,
+) -> usize {
+    let mut updated = 0;
+    for thread_id in thread_ids {
+        let members = match email_db.get_thread_email_ids(thread_id) {
+            Ok(members) => members,
+            Err(e) => {
+                eprintln!("[RAG] Failed to load thread {}: {}", thread_id, e);
+                continue;
+            }
+        };
+        match vector_db.update_thread_embedding(thread_id, &members) {
+            Ok(true) => updated += 1,
+            Ok(false) => {}
+            Err(e) => eprintln!("[RAG] Failed to update thread embedding {}: {}", thread_id, e),
+        }
+    }
+    updated
+}
+
+/// Texts an email is embedded as: overlapping chunks when chunking is enabled,
+/// otherwise one text truncated like `prepare_email_text`. Returns them with
+/// the hash that detects changes.
+fn embedding_texts(subject: &str, from: &str, body: &str) -> (Vec<String>, String) {
+    let texts = if super::settings::embedding_chunking_enabled() {
+        prepare_email_chunks(subject, from, body)
+    } else {
+        vec![prepare_email_text(subject, from, body)]
+    };
+    // Same hash as before chunking existed for single-text emails
+    let text_hash = calculate_text_hash(&texts.concat());
+    (texts, text_hash)
+}
+
+/// Embedding inputs for the given cached emails, with each one's thread ID.
+/// Emails that can't be loaded are skipped.
+fn embedding_inputs(
+    email_db: &crate::db::EmailDatabase,
+    email_ids: &[String],
+) -> (Vec<EmbeddingInput>, HashMap<String, String>) {
+    let mut inputs = Vec::new();
+    let mut threads = HashMap::new();
+    for email_id in email_ids {
+        match email_db.get_email_by_id(email_id) {
+            Ok(Some(email)) => {
+                let body = email.body_plain.as_deref().unwrap_or("");
+                let (texts, text_hash) = embedding_texts(&email.subject, &email.from_email, body);
+                threads.insert(email_id.clone(), email.thread_id);
+                inputs.push(EmbeddingInput {
+                    email_id: email_id.clone(),
+                    texts,
+                    text_hash,
+                });
+            }
+            Ok(None) => {
+                eprintln!("[RAG] Email {} not found in DB, skipping", email_id);
+            }
+            Err(e) => {
+                eprintln!("[RAG] Failed to fetch email {}: {}", email_id, e);
+            }
+        }
+    }
+    (inputs, threads)
+}
+
+/// Text an email is classified by; also keys the category cache via its hash
+pub(crate) fn category_text(email: &crate::email::types::Email) -> String {
+    let body = email
+        .body_plain
+        .as_deref()
+        .or(email.body_html.as_deref())
+        .unwrap_or("");
+    prepare_email_text(&email.subject, &email.from, body)
+}
+
+/// Outcome of classifying a fetched batch
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ClassifyBatchReport {
+    /// Emails classified by this batch's embedding call
+    pub classified: usize,
+    /// Emails skipped because their text already had a stored category
+    pub reused: usize,
+}
+
+/// Classify freshly fetched emails with one batched embedding call and store
+/// the categories in bulk. Emails whose text hash already has a category are
+/// skipped. Does nothing when the RAG engine isn't initialized.
+pub(crate) async fn classify_batch(
+    db: &Arc<Mutex<Option<crate::db::EmailDatabase>>>,
+    emails: &[crate::email::types::Email],
+) -> ClassifyBatchReport {
+    let texts: Vec<(String, String, String)> = emails
+        .iter()
+        .map(|email| {
+            let text = category_text(email);
+            (email.id.clone(), calculate_text_hash(&text), text)
+        })
+        .collect();
+
+    let hashes: Vec<String> = texts.iter().map(|(_, hash, _)| hash.clone()).collect();
+    let known = {
+        let db_lock = db.lock().unwrap();
+        match db_lock.as_ref() {
+            Some(database) => database.get_cached_categories(&hashes).unwrap_or_else(|e| {
+                eprintln!("[Classify] Failed to read category cache: {}", e);
+                Default::default()
+            }),
+            None => return ClassifyBatchReport::default(),
+        }
+    };
+
+    // The same text can appear twice in a batch (e.g. a message in two folders)
+    let mut pending: Vec<(String, String, String)> = Vec::new();
+    let mut reused = 0;
+    for (email_id, hash, text) in texts {
+        if known.contains_key(&hash) || pending.iter().any(|(_, h, _)| *h == hash) {
+            reused += 1;
+        } else {
+            pending.push((email_id, hash, text));
+        }
+    }
+    if pending.is_empty() {
+        return ClassifyBatchReport {
+            classified: 0,
+            reused,
+        };
+    }
+
+    let batch: Vec<String> = pending.iter().map(|(_, _, text)| text.clone()).collect();
+    let min_margin = super::settings::category_min_margin();
+    let categories = tokio::task::spawn_blocking(move || {
+        let rag_guard = RAG_ENGINE.lock().unwrap();
+        match rag_guard.as_ref().filter(|r| r.is_initialized()) {
+            Some(rag) => {
+                let texts: Vec<&str> = batch.iter().map(String::as_str).collect();
+                rag.classify_texts(&texts, min_margin).map(Some)
+            }
+            None => Ok(None),
+        }
+    })
+    .await;
+
+    let categories = match categories {
+        Ok(Ok(Some(categories))) => categories,
+        Ok(Ok(None)) => return ClassifyBatchReport::default(),
+        Ok(Err(e)) => {
+            eprintln!("[Classify] Batch classification failed: {}", e);
+            return ClassifyBatchReport::default();
+        }
+        Err(e) => {
+            eprintln!("[Classify] Batch classification task failed: {}", e);
+            return ClassifyBatchReport::default();
+        }
+    };
+
+    let results: Vec<(String, String, String)> = pending
+        .into_iter()
+        .zip(categories)
+        .map(|((email_id, hash, _), category)| (email_id, hash, category.category))
+        .collect();
+
+    let db_lock = db.lock().unwrap();
+    if let Some(database) = db_lock.as_ref() {
+        if let Err(e) = database.store_categories(&results) {
+            eprintln!("[Classify] Failed to store categories: {}", e);
+            return ClassifyBatchReport::default();
+        }
+    }
+
+    let report = ClassifyBatchReport {
+        classified: results.len(),
+        reused,
+    };
+    println!(
+        "[Classify] Classified {} emails in one batch ({} already categorized)",
+        report.classified, report.reused
+    );
+    report
+}
+
+/// Re-embed one email even if its text hash is unchanged, then refresh its
+/// thread (and the thread it used to belong to). Returns false when RAG isn't initialized.
+pub(crate) fn reembed_email(
+    app: &AppHandle,
+    email: &crate::email::types::Email,
+    previous_thread_id: Option<&String>,
+) -> Result<bool, String> {
+    let rag_guard = RAG_ENGINE.lock().unwrap();
+    let Some(rag) = rag_guard.as_ref().filter(|r| r.is_initialized()) else {
+        return Ok(false);
+    };
+
+    let body = email.body_plain.as_deref().unwrap_or("");
+    let (texts, text_hash) = embedding_texts(&email.subject, &email.from, body);
+    rag.store_email_embedding(&email.id, &texts, &text_hash)
+        .map_err(|e| format!("Failed to embed email: {}", e))?;
+
+    if let Some(vector_db) = rag.vector_db() {
+        let email_db = open_email_db(app)?;
+        let moved_from = previous_thread_id.filter(|id| **id != email.thread_id);
+        let threads = std::iter::once(&email.thread_id).chain(moved_from);
+        refresh_thread_embeddings(&email_db, &vector_db, threads);
+    }
+    Ok(true)
+}
+
+/// Related emails below this similarity are too loosely connected to use as background
+const MIN_CONTEXT_SIMILARITY: f32 = 0.5;
+
+/// Snippets of past emails related to `email_id`, for grounding an LLM prompt.
+/// Returns the context text and the IDs it was built from, or None when RAG
+/// isn't ready or nothing relevant was found.
+pub(crate) fn related_email_context(
+    app: &AppHandle,
+    email_id: &str,
+    query: &str,
+    limit: usize,
+) -> Option<(String, Vec<String>)> {
+    let similar = {
+        let rag_guard = RAG_ENGINE.lock().unwrap();
+        let rag = rag_guard.as_ref().filter(|r| r.is_initialized())?;
+        match rag.search_similar(query, limit, Some(email_id)) {
+            Ok(similar) => similar,
+            Err(e) => {
+                eprintln!("[RAG] Context search failed: {}", e);
+                return None;
+            }
+        }
+    };
+
+    let email_db = open_email_db(app).ok()?;
+    let mut ids = Vec::new();
+    let mut lines = Vec::new();
+    for s in similar.into_iter().filter(|s| s.similarity >= MIN_CONTEXT_SIMILARITY) {
+        let Ok(Some(email)) = email_db.get_email_by_id(&s.email_id) else {
+            continue;
+        };
+        let snippet: String = email
+            .body_plain
+            .as_deref()
+            .unwrap_or(&email.snippet)
+            .chars()
+            .take(300)
+            .collect();
+        lines.push(format!(
+            "Email {}: From: {} | Date: {} | Subject: {} | {}",
+            lines.len() + 1,
+            email.from,
+            email.date,
+            email.subject,
+            snippet
+        ));
+        ids.push(s.email_id);
+    }
+
+    (!ids.is_empty()).then(|| (lines.join("\n"), ids))
+}
+
 /// Initialize the RAG system (embedding engine + vector database)
 #[tauri::command]
 pub async fn init_rag(app: AppHandle) -> Result<bool, String> {
@@ -62,6 +372,8 @@ pub async fn init_rag(app: AppHandle) -> Result<bool, String> {
         VectorDatabase::new(db_path)
             .map_err(|e| format!("Failed to create vector database: {}", e))?,
     );
+    vector_db.set_search_probes(super::settings::vector_search_probes());
+    vector_db.set_quantization(super::settings::embedding_quantization_enabled());
 
     // Store vector db
     {
@@ -70,6 +382,9 @@ pub async fn init_rag(app: AppHandle) -> Result<bool, String> {
     }
 
     // Download embedding model (async, with direct HTTP fallback)
+    if !embeddings::is_model_downloaded(None) {
+        ensure_network_allowed("Embedding model download")?;
+    }
     let (config_path, tokenizer_path, weights_path) =
         embeddings::download_embedding_model(None)
             .await
@@ -86,6 +401,7 @@ pub async fn init_rag(app: AppHandle) -> Result<bool, String> {
     ) {
         Ok(engine) => {
             let engine = Arc::new(engine);
+            vector_db.set_model(engine.model_id());
             {
                 let mut engine_guard = EMBEDDING_ENGINE.lock().unwrap();
                 *engine_guard = Some(engine.clone());
@@ -145,6 +461,7 @@ pub fn get_embedding_status() -> Result<EmbeddingStatus, String> {
 /// Embed a single email
 #[tauri::command]
 pub fn embed_email(
+    app: AppHandle,
     email_id: String,
     subject: String,
     from: String,
@@ -153,8 +470,7 @@ pub fn embed_email(
     let rag_guard = RAG_ENGINE.lock().unwrap();
     let rag = rag_guard.as_ref().ok_or("RAG engine not initialized")?;
 
-    let text = prepare_email_text(&subject, &from, &body);
-    let text_hash = calculate_text_hash(&text);
+    let (texts, text_hash) = embedding_texts(&subject, &from, &body);
 
     // Check if already embedded with same hash
     if let Some(vector_db) = rag.vector_db() {
@@ -166,13 +482,100 @@ pub fn embed_email(
         }
     }
 
-    rag.store_email_embedding(&email_id, &text, &text_hash)
-        .map_err(|e| format!("Failed to embed email: {}", e))
+    rag.store_email_embedding(&email_id, &texts, &text_hash)
+        .map_err(|e| format!("Failed to embed email: {}", e))?;
+
+    // Fold the new message into its thread's embedding
+    if let Some(vector_db) = rag.vector_db() {
+        let email_db = open_email_db(&app)?;
+        if let Ok(Some(email)) = email_db.get_email_by_id(&email_id) {
+            refresh_thread_embeddings(&email_db, &vector_db, [&email.thread_id]);
+        }
+    }
+
+    Ok(())
 }
 
-/// Embed all unembedded emails (batch operation)
+/// The classification categories, in display order
 #[tauri::command]
-pub async fn embed_all_emails(app: AppHandle) -> Result<i64, String> {
+pub fn list_categories() -> Result<Vec<CategoryDefinition>, String> {
+    let vector_db = get_vector_db().ok_or("Vector database not initialized")?;
+    vector_db
+        .seed_categories(default_categories())
+        .and_then(|_| vector_db.get_categories())
+        .map_err(|e| format!("Failed to load categories: {}", e))
+}
+
+/// Add a category, or change the description of an existing one
+#[tauri::command]
+pub fn set_category(
+    app: AppHandle,
+    name: String,
+    description: String,
+) -> Result<Vec<CategoryDefinition>, String> {
+    let name = name.trim();
+    if name.is_empty() || description.trim().is_empty() {
+        return Err("Category name and description are required".to_string());
+    }
+    if name == UNCERTAIN_CATEGORY {
+        return Err(format!("\"{}\" is reserved", UNCERTAIN_CATEGORY));
+    }
+
+    let vector_db = get_vector_db().ok_or("Vector database not initialized")?;
+    vector_db
+        .seed_categories(default_categories())
+        .and_then(|_| vector_db.set_category(name, description.trim()))
+        .map_err(|e| format!("Failed to save category: {}", e))?;
+    categories_changed(&app)?;
+    list_categories()
+}
+
+/// Remove a category (built-in ones included); the last one can't be removed
+#[tauri::command]
+pub fn remove_category(app: AppHandle, name: String) -> Result<Vec<CategoryDefinition>, String> {
+    let vector_db = get_vector_db().ok_or("Vector database not initialized")?;
+    let removed = vector_db
+        .seed_categories(default_categories())
+        .and_then(|_| vector_db.remove_category(&name))
+        .map_err(|e| format!("Failed to remove category: {}", e))?;
+    if !removed {
+        return Err(format!("No category named {}", name));
+    }
+    categories_changed(&app)?;
+    list_categories()
+}
+
+/// Go back to the built-in categories
+#[tauri::command]
+pub fn reset_categories(app: AppHandle) -> Result<Vec<CategoryDefinition>, String> {
+    let vector_db = get_vector_db().ok_or("Vector database not initialized")?;
+    vector_db
+        .reset_categories(default_categories())
+        .map_err(|e| format!("Failed to reset categories: {}", e))?;
+    categories_changed(&app)?;
+    list_categories()
+}
+
+/// Re-embed the category set and drop classifications made against the old one
+fn categories_changed(app: &AppHandle) -> Result<(), String> {
+    {
+        let mut rag_guard = RAG_ENGINE.lock().unwrap();
+        if let Some(rag) = rag_guard.as_mut().filter(|r| r.is_initialized()) {
+            rag.init_category_embeddings()
+                .map_err(|e| format!("Failed to embed categories: {}", e))?;
+        }
+    }
+    open_email_db(app)?
+        .clear_category_cache()
+        .map_err(|e| format!("Failed to clear category cache: {}", e))?;
+    let _ = app.emit("categories:updated", ());
+    Ok(())
+}
+
+/// Embed all unembedded emails, `batch_size` emails at a time with up to
+/// `batch_size` texts per model call (32 when unset)
+#[tauri::command]
+pub async fn embed_all_emails(app: AppHandle, batch_size: Option<usize>) -> Result<i64, String> {
     // Get email database to fetch emails
     let email_db = crate::db::EmailDatabase::new(
         app.path()
@@ -194,6 +597,16 @@ pub async fn embed_all_emails(app: AppHandle) -> Result<i64, String> {
             .ok_or("Embedding engine not initialized")?
     };
 
+    // Drop embeddings of emails no longer cached (expunged, moved or pruned)
+    let cached_ids = email_db
+        .get_all_email_ids(i64::MAX)
+        .map_err(|e| format!("Failed to get email IDs: {}", e))?;
+    match vector_db.reconcile_embeddings(&cached_ids.into_iter().collect()) {
+        Ok(0) => {}
+        Ok(pruned) => eprintln!("[RAG] Pruned embeddings of {} removed emails", pruned),
+        Err(e) => eprintln!("[RAG] Failed to prune orphaned embeddings: {}", e),
+    }
+
     // Get all email IDs from the email database, then filter out already-embedded ones
     let all_email_ids = email_db
         .get_all_email_ids(1000)
@@ -232,63 +645,52 @@ pub async fn embed_all_emails(app: AppHandle) -> Result<i64, String> {
         )
         .map_err(|e| format!("Failed to update status: {}", e))?;
 
+    let batch_size = batch_size.unwrap_or(DEFAULT_EMBED_BATCH_SIZE).max(1);
+    let mut rag = RagEngine::new();
+    rag.init(embedding_engine, vector_db.clone());
+
     let mut embedded_count = 0i64;
+    let mut touched_threads = std::collections::HashSet::new();
 
-    for email_id in unembedded_ids {
-        // Get email content
-        match email_db.get_email_by_id(&email_id) {
-            Ok(Some(email)) => {
-                let body = email.body_plain.as_deref().unwrap_or("");
-                let text = prepare_email_text(&email.subject, &email.from_email, body);
-                let text_hash = calculate_text_hash(&text);
-
-                // Generate embedding
-                match embedding_engine.embed(&text) {
-                    Ok(embedding) => {
-                        let email_embedding = crate::db::vector_db::EmailEmbedding {
-                            email_id: email_id.clone(),
-                            embedding,
-                            embedding_model: embedding_engine.model_id().to_string(),
-                            text_hash,
-                            created_at: chrono::Utc::now().timestamp(),
-                        };
-
-                        if vector_db.store_embedding(&email_embedding).is_ok() {
-                            embedded_count += 1;
-
-                            // Emit progress event
-                            let _ = app.emit(
-                                "embedding:progress",
-                                EmbeddingProgress {
-                                    total,
-                                    embedded: embedded_count,
-                                    current_email_id: Some(email_id),
-                                },
-                            );
-
-                            // Update status periodically
-                            if embedded_count % 10 == 0 {
-                                let _ = vector_db.update_embedding_status(
-                                    true,
-                                    Some(total),
-                                    Some(embedded_count),
-                                    None,
-                                    None,
-                                );
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("[RAG] Failed to embed email {}: {}", email_id, e);
-                    }
-                }
-            }
-            Ok(None) => {
-                eprintln!("[RAG] Email {} not found in DB, skipping", email_id);
-            }
+    for batch_ids in unembedded_ids.chunks(batch_size) {
+        let (inputs, mut threads) = embedding_inputs(&email_db, batch_ids);
+
+        // Generate and store the whole batch at once
+        let embedded = match rag.store_email_embeddings_batch(&inputs, batch_size) {
+            Ok(embedded) => embedded,
             Err(e) => {
-                eprintln!("[RAG] Failed to fetch email {}: {}", email_id, e);
+                eprintln!("[RAG] Failed to embed batch of {} emails: {}", inputs.len(), e);
+                continue;
             }
+        };
+        embedded_count += embedded.len() as i64;
+        touched_threads.extend(embedded.iter().filter_map(|id| threads.remove(id)));
+
+        // Emit progress event
+        let _ = app.emit(
+            "embedding:progress",
+            EmbeddingProgress {
+                total,
+                embedded: embedded_count,
+                current_email_id: embedded.last().cloned(),
+            },
+        );
+        let _ = vector_db.update_embedding_status(
+            true,
+            Some(total),
+            Some(embedded_count),
+            None,
+            None,
+        );
+    }
+
+    let threads_updated = refresh_thread_embeddings(&email_db, &vector_db, &touched_threads);
+    eprintln!("[RAG] Updated {} thread embeddings", threads_updated);
+
+    // Re-cluster the search index around everything just embedded
+    if embedded_count > 0 {
+        if let Err(e) = vector_db.rebuild_index() {
+            eprintln!("[RAG] Failed to rebuild search index: {}", e);
         }
     }
 
@@ -305,22 +707,109 @@ pub async fn embed_all_emails(app: AppHandle) -> Result<i64, String> {
     Ok(embedded_count)
 }
 
-/// Semantic search for emails
+/// Number of emails embedded by another model than the loaded one
+#[tauri::command]
+pub fn needs_reindex() -> Result<i64, String> {
+    let vector_db = get_vector_db().ok_or("Vector database not initialized")?;
+    vector_db
+        .needs_reindex()
+        .map_err(|e| format!("Failed to count stale embeddings: {}", e))
+}
+
+/// Re-embed every email embedded by another model than the loaded one,
+/// `batch_size` emails at a time (32 when unset). Emits `reindex:progress`
+/// after each batch and `reindex:complete` at the end.
+#[tauri::command]
+pub async fn reindex_all(app: AppHandle, batch_size: Option<usize>) -> Result<i64, String> {
+    let email_db = open_email_db(&app)?;
+    let vector_db = get_vector_db().ok_or("Vector database not initialized")?;
+    let embedding_engine = EMBEDDING_ENGINE
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or("Embedding engine not initialized")?;
+
+    let stale_ids = vector_db
+        .get_stale_email_ids()
+        .map_err(|e| format!("Failed to get stale embeddings: {}", e))?;
+    if stale_ids.is_empty() {
+        return Ok(0);
+    }
+    let total = stale_ids.len() as i64;
+    eprintln!(
+        "[RAG] Re-embedding {} emails with {}",
+        total,
+        embedding_engine.model_id()
+    );
+
+    vector_db
+        .update_embedding_status(
+            true,
+            Some(total),
+            Some(0),
+            Some(embedding_engine.model_id()),
+            None,
+        )
+        .map_err(|e| format!("Failed to update status: {}", e))?;
+
+    let batch_size = batch_size.unwrap_or(DEFAULT_EMBED_BATCH_SIZE).max(1);
+    let mut rag = RagEngine::new();
+    rag.init(embedding_engine, vector_db.clone());
+
+    let mut reindexed = 0i64;
+    let mut touched_threads = std::collections::HashSet::new();
+
+    for batch_ids in stale_ids.chunks(batch_size) {
+        let (inputs, mut threads) = embedding_inputs(&email_db, batch_ids);
+        let embedded = match rag.store_email_embeddings_batch(&inputs, batch_size) {
+            Ok(embedded) => embedded,
+            Err(e) => {
+                eprintln!(
+                    "[RAG] Failed to re-embed batch of {} emails: {}",
+                    inputs.len(),
+                    e
+                );
+                continue;
+            }
+        };
+        reindexed += embedded.len() as i64;
+        touched_threads.extend(embedded.iter().filter_map(|id| threads.remove(id)));
+
+        let _ = app.emit(
+            "reindex:progress",
+            EmbeddingProgress {
+                total,
+                embedded: reindexed,
+                current_email_id: embedded.last().cloned(),
+            },
+        );
+        let _ = vector_db.update_embedding_status(true, Some(total), Some(reindexed), None, None);
+    }
+
+    refresh_thread_embeddings(&email_db, &vector_db, &touched_threads);
+    if let Err(e) = vector_db.rebuild_index() {
+        eprintln!("[RAG] Failed to rebuild search index: {}", e);
+    }
+
+    vector_db
+        .update_embedding_status(false, Some(total), Some(reindexed), None, None)
+        .map_err(|e| format!("Failed to update status: {}", e))?;
+    eprintln!("[RAG] Re-embedded {}/{} emails", reindexed, total);
+    let _ = app.emit("reindex:complete", reindexed);
+
+    Ok(reindexed)
+}
+
+/// Semantic search for emails. With `rerank`, extra vector matches are
+/// fetched and reordered by how well their subject and body match the query.
 #[tauri::command]
 pub fn search_emails_semantic(
     app: AppHandle,
     query: String,
     limit: usize,
+    rerank: Option<bool>,
 ) -> Result<Vec<SearchResult>, String> {
-    // Step 1: Lock RAG_ENGINE, perform search, drop lock
-    let similar = {
-        let rag_guard = RAG_ENGINE.lock().unwrap();
-        let rag = rag_guard.as_ref().ok_or("RAG engine not initialized")?;
-        rag.search_similar(&query, limit, None)
-            .map_err(|e| format!("Failed to search: {}", e))?
-    };
-
-    // Step 2: Open EmailDatabase to enrich results with metadata
+    // Open EmailDatabase to rerank and enrich results with metadata
     let email_db = crate::db::EmailDatabase::new(
         app.path()
             .app_data_dir()
@@ -329,7 +818,58 @@ pub fn search_emails_semantic(
     )
     .map_err(|e| format!("Failed to open email database: {}", e))?;
 
-    let results: Vec<SearchResult> = similar
+    // Lock RAG_ENGINE, perform search, drop lock
+    let similar = {
+        let rag_guard = RAG_ENGINE.lock().unwrap();
+        let rag = rag_guard.as_ref().ok_or("RAG engine not initialized")?;
+        if rerank.unwrap_or(false) {
+            rag.search_reranked(&query, limit, None, |email_id| {
+                let email = email_db.get_email_by_id(email_id).ok()??;
+                let body = email.body_plain.or(email.body_html).unwrap_or(email.snippet);
+                Some((email.subject, body))
+            })
+        } else {
+            rag.search_similar(&query, limit, None)
+        }
+        .map_err(|e| format!("Failed to search: {}", e))?
+    };
+
+    Ok(to_search_results(&email_db, similar))
+}
+
+/// Hybrid search: BM25 keyword matches over subject and body fused with
+/// vector similarity, so exact terms like order numbers are found even when
+/// their embedding isn't close to the query's
+#[tauri::command]
+pub fn search_emails_hybrid(
+    app: AppHandle,
+    query: String,
+    limit: usize,
+) -> Result<Vec<SearchResult>, String> {
+    let email_db = open_email_db(&app)?;
+
+    let similar = {
+        let rag_guard = RAG_ENGINE.lock().unwrap();
+        let rag = rag_guard.as_ref().ok_or("RAG engine not initialized")?;
+        rag.hybrid_search(&query, limit, |query, candidates| {
+            Ok(email_db
+                .keyword_search(query, candidates)?
+                .into_iter()
+                .map(|(email_id, _)| email_id)
+                .collect())
+        })
+        .map_err(|e| format!("Failed to search: {}", e))?
+    };
+
+    Ok(to_search_results(&email_db, similar))
+}
+
+/// Attach each match's subject, sender and snippet from the email cache
+fn to_search_results(
+    email_db: &crate::db::EmailDatabase,
+    similar: Vec<crate::db::vector_db::SimilarEmail>,
+) -> Vec<SearchResult> {
+    similar
         .into_iter()
         .map(|s| {
             let (subject, from, snippet) =
@@ -345,14 +885,13 @@ pub fn search_emails_semantic(
             SearchResult {
                 email_id: s.email_id,
                 similarity: s.similarity,
+                score: s.score,
                 subject,
                 from,
                 snippet,
             }
         })
-        .collect();
-
-    Ok(results)
+        .collect()
 }
 
 /// Find emails similar to a given email
@@ -379,6 +918,7 @@ pub fn find_similar_emails(email_id: String, limit: usize) -> Result<Vec<SearchR
         .map(|s| SearchResult {
             email_id: s.email_id,
             similarity: s.similarity,
+            score: s.score,
             subject: None,
             from: None,
             snippet: None,
@@ -388,6 +928,112 @@ pub fn find_similar_emails(email_id: String, limit: usize) -> Result<Vec<SearchR
     Ok(results)
 }
 
+/// The emails most similar to `email_id`, for a related conversations panel.
+/// An email that isn't embedded yet (or whose text changed) is embedded first.
+#[tauri::command]
+pub fn related_emails(
+    app: AppHandle,
+    email_id: String,
+    top_k: usize,
+) -> Result<Vec<RetrievedContext>, String> {
+    let email_db = open_email_db(&app)?;
+    let email = email_db
+        .get_email_by_id(&email_id)
+        .map_err(|e| format!("Failed to load email: {}", e))?
+        .ok_or("Email not found")?;
+
+    let similar = {
+        let rag_guard = RAG_ENGINE.lock().unwrap();
+        let rag = rag_guard.as_ref().ok_or("RAG engine not initialized")?;
+        let vector_db = rag.vector_db().ok_or("Vector database not initialized")?;
+
+        let body = email.body_plain.as_deref().unwrap_or("");
+        let (texts, text_hash) = embedding_texts(&email.subject, &email.from_email, body);
+        if !vector_db
+            .has_embedding(&email_id, &text_hash)
+            .map_err(|e| format!("Failed to check embedding: {}", e))?
+        {
+            rag.store_email_embedding(&email_id, &texts, &text_hash)
+                .map_err(|e| format!("Failed to embed email: {}", e))?;
+            refresh_thread_embeddings(&email_db, &vector_db, [&email.thread_id]);
+        }
+
+        let embedding = vector_db
+            .get_embedding(&email_id)
+            .map_err(|e| format!("Failed to get embedding: {}", e))?
+            .ok_or("Email not embedded")?;
+        vector_db
+            .search_similar(&embedding.embedding, top_k, Some(&email_id))
+            .map_err(|e| format!("Failed to search: {}", e))?
+    };
+
+    Ok(to_contexts(&email_db, similar))
+}
+
+/// Compute (or recompute) the aggregate embedding of every cached thread
+#[tauri::command]
+pub async fn compute_thread_embeddings(app: AppHandle) -> Result<usize, String> {
+    let vector_db = get_vector_db().ok_or("Vector database not initialized")?;
+    let email_db = open_email_db(&app)?;
+
+    let thread_ids = email_db
+        .get_all_thread_ids()
+        .map_err(|e| format!("Failed to get thread IDs: {}", e))?;
+    let updated = refresh_thread_embeddings(&email_db, &vector_db, &thread_ids);
+
+    eprintln!(
+        "[RAG] Thread embeddings: {}/{} threads have embedded members",
+        updated,
+        thread_ids.len()
+    );
+    Ok(updated)
+}
+
+/// Semantic search at thread granularity
+#[tauri::command]
+pub fn search_similar_threads(
+    app: AppHandle,
+    query: String,
+    top_k: usize,
+) -> Result<Vec<ThreadSearchResult>, String> {
+    let similar = {
+        let rag_guard = RAG_ENGINE.lock().unwrap();
+        let rag = rag_guard.as_ref().ok_or("RAG engine not initialized")?;
+        let vector_db = rag.vector_db().ok_or("Vector database not initialized")?;
+        let query_embedding = rag
+            .embed_text(&query)
+            .map_err(|e| format!("Failed to embed query: {}", e))?;
+        vector_db
+            .search_similar_threads(&query_embedding, top_k)
+            .map_err(|e| format!("Failed to search: {}", e))?
+    };
+
+    let email_db = open_email_db(&app)?;
+
+    let results = similar
+        .into_iter()
+        .map(|t| {
+            let latest = email_db
+                .get_thread_email_ids(&t.thread_id)
+                .ok()
+                .and_then(|ids| ids.into_iter().next());
+            let email = latest
+                .as_deref()
+                .and_then(|id| email_db.get_email_by_id(id).ok().flatten());
+            ThreadSearchResult {
+                thread_id: t.thread_id,
+                similarity: t.similarity,
+                message_count: t.member_count,
+                latest_email_id: latest,
+                subject: email.as_ref().map(|e| e.subject.clone()),
+                snippet: email.map(|e| e.snippet),
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
 /// Get count of embedded emails
 #[tauri::command]
 pub fn get_embedded_count() -> Result<i64, String> {
@@ -408,37 +1054,35 @@ pub fn clear_embeddings() -> Result<(), String> {
         .map_err(|e| format!("Failed to clear embeddings: {}", e))
 }
 
-/// Chat with RAG context
-#[tauri::command]
-pub fn chat_with_context(
-    app: AppHandle,
-    query: String,
+/// Semantic search for `query`, resolved to the stored emails it matched
+fn retrieve_contexts(
+    app: &AppHandle,
+    query: &str,
     limit: usize,
-) -> Result<String, String> {
-    use crate::llm::rag::RetrievedContext;
-
-    // Step 1: Lock RAG_ENGINE → semantic search → drop lock
+) -> Result<Vec<RetrievedContext>, String> {
+    // Lock RAG_ENGINE → semantic search → drop lock
     let similar = {
         let rag_guard = RAG_ENGINE.lock().unwrap();
         let rag = rag_guard.as_ref().ok_or("RAG engine not initialized")?;
-        rag.search_similar(&query, limit, None)
+        rag.search_similar(query, limit, None)
             .map_err(|e| format!("Failed to search: {}", e))?
     };
 
     if similar.is_empty() {
-        return Ok(format!("No relevant emails found for: {}", query));
+        return Ok(Vec::new());
     }
 
-    // Step 2: Open EmailDatabase → fetch metadata → build RetrievedContext list
-    let email_db = crate::db::EmailDatabase::new(
-        app.path()
-            .app_data_dir()
-            .map_err(|e| format!("Failed to get app data dir: {}", e))?
-            .join("emails.db"),
-    )
-    .map_err(|e| format!("Failed to open email database: {}", e))?;
+    // Open EmailDatabase → fetch metadata → build RetrievedContext list
+    let email_db = open_email_db(app)?;
+    Ok(to_contexts(&email_db, similar))
+}
 
-    let contexts: Vec<RetrievedContext> = similar
+/// Resolve search hits to the stored emails; hits no longer cached are dropped
+fn to_contexts(
+    email_db: &crate::db::EmailDatabase,
+    similar: Vec<SimilarEmail>,
+) -> Vec<RetrievedContext> {
+    similar
         .into_iter()
         .filter_map(|s| {
             if let Ok(Some(email)) = email_db.get_email_by_id(&s.email_id) {
@@ -455,12 +1099,24 @@ pub fn chat_with_context(
                     from: email.from,
                     snippet,
                     similarity: s.similarity,
+                    folder: email.folder,
+                    date: email.date,
                 })
             } else {
                 None
             }
         })
-        .collect();
+        .collect()
+}
+
+/// Chat with RAG context
+#[tauri::command]
+pub fn chat_with_context(
+    app: AppHandle,
+    query: String,
+    limit: usize,
+) -> Result<String, String> {
+    let contexts = retrieve_contexts(&app, &query, limit)?;
 
     if contexts.is_empty() {
         return Ok(format!("No relevant emails found for: {}", query));
@@ -486,7 +1142,7 @@ pub fn chat_with_context(
     let summarizer_guard = crate::commands::ai::SUMMARIZER.lock().unwrap();
     if let Some(summarizer) = summarizer_guard.as_ref() {
         if summarizer.is_model_loaded() {
-            match summarizer.chat(&query, Some(&context_str)) {
+            match summarizer.answer_with_context(&query, &context_str) {
                 Ok(response) => return Ok(response),
                 Err(e) => {
                     let err_msg = e.to_string();
@@ -509,3 +1165,44 @@ pub fn chat_with_context(
         context_str
     ))
 }
+
+/// Stream a RAG answer as `llm:token` events (see `cancel_llm_stream`).
+/// Retrieval happens up front; returns the stream ID.
+#[tauri::command]
+pub async fn chat_with_context_stream(
+    app: AppHandle,
+    query: String,
+    limit: usize,
+) -> Result<String, String> {
+    // Embedding the query runs the model, so not on an async worker
+    let contexts = {
+        let app = app.clone();
+        let query = query.clone();
+        tokio::task::spawn_blocking(move || retrieve_contexts(&app, &query, limit))
+            .await
+            .map_err(|e| format!("Retrieval task failed: {}", e))??
+    };
+
+    // The RAG engine is only needed for the context; search and indexing
+    // shouldn't wait for the whole answer
+    let context = if contexts.is_empty() {
+        None
+    } else {
+        let rag_guard = RAG_ENGINE.lock().unwrap();
+        let rag = rag_guard.as_ref().ok_or("RAG engine not initialized")?;
+        Some(rag.build_context(&contexts, CONTEXT_MAX_CHARS))
+    };
+
+    Ok(super::ai::start_llm_stream(app, move |cancel, on_token| {
+        let summarizer_guard = super::ai::SUMMARIZER.lock().unwrap();
+        let summarizer = summarizer_guard.as_ref().ok_or("AI not initialized")?;
+
+        match &context {
+            Some(context) => {
+                summarizer.answer_with_context_stream(&query, context, cancel, on_token)
+            }
+            None => summarizer.chat_stream(&query, None, cancel, on_token),
+        }
+        .map_err(|e| e.to_string())
+    }))
+}
@@ -0,0 +1,437 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use super::cache::get_data_dir;
+use crate::db::EmailDatabase;
+use crate::email::attachment_safety::{BlocklistChecker, SafetyChecker};
+use crate::email::attachments::DEFAULT_ATTACHMENT_LIMIT_MB;
+use crate::email::auth_results::provider_authserv_ids;
+use crate::email::idle::{IdleManager, DEFAULT_POLL_INTERVAL_SECS};
+use crate::email::rate_limit::RateLimits;
+use crate::email::server_presets::ProviderType;
+use crate::db::vector_index::DEFAULT_SEARCH_PROBES;
+use crate::llm::rag::DEFAULT_MIN_CATEGORY_MARGIN;
+
+type DbState = Arc<Mutex<Option<EmailDatabase>>>;
+
+const APP_SETTINGS_FILE: &str = "app_settings.json";
+
+/// Application-wide settings persisted alongside the cache settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppSettings {
+    /// When enabled nothing leaves the device except traffic to the user's own
+    /// mail servers: model downloads, remote content and any other third-party
+    /// network access are refused.
+    #[serde(default)]
+    pub local_only: bool,
+    /// Optional file of SHA-256 hashes that flags matching attachments.
+    /// Attachment hash lookups are disabled when unset.
+    #[serde(default)]
+    pub attachment_blocklist_path: Option<String>,
+    /// IMAP connections kept open per account (3 when unset). Read at startup.
+    #[serde(default)]
+    pub imap_pool_size: Option<usize>,
+    /// Seconds a sent message waits before going out, so it can be undone
+    /// (10 when unset, 0 sends right away)
+    #[serde(default)]
+    pub undo_send_secs: Option<u64>,
+    /// Embed long emails as overlapping chunks instead of only their first
+    /// 1000 characters. Applies to emails embedded from now on.
+    #[serde(default)]
+    pub embedding_chunking: bool,
+    /// Keep quoted reply history in the text embedded and summarized instead
+    /// of only the newest part of each message
+    #[serde(default)]
+    pub keep_quoted_text: bool,
+    /// Lead the best category needs over the runner-up before an email is
+    /// labelled with it; closer calls are stored as "uncertain" (0.02 when unset)
+    #[serde(default)]
+    pub category_min_margin: Option<f32>,
+    /// Index lists scanned per semantic search over large mailboxes; more
+    /// finds more of the exact matches but is slower (8 when unset)
+    #[serde(default)]
+    pub vector_search_probes: Option<usize>,
+    /// Store embeddings as int8 instead of f32, a quarter of the disk space
+    /// for a barely noticeable loss of search accuracy
+    #[serde(default)]
+    pub quantize_embeddings: bool,
+    /// Seconds between new-mail checks on servers without IDLE, where the
+    /// folder is polled instead (60 when unset)
+    #[serde(default)]
+    pub poll_interval_secs: Option<u64>,
+    /// Combined size of a sent message's attachments, in MB (18 when unset)
+    #[serde(default)]
+    pub attachment_limit_mb: Option<u64>,
+    /// IMAP commands per second allowed per account; the provider's
+    /// default when unset. Read when an account connects.
+    #[serde(default)]
+    pub imap_requests_per_sec: Option<f64>,
+    /// IMAP commands that may go out back to back before the rate applies
+    #[serde(default)]
+    pub imap_burst: Option<u32>,
+    /// Fetches in flight at once per account
+    #[serde(default)]
+    pub imap_max_concurrent_fetches: Option<usize>,
+    /// Servers whose Authentication-Results are trusted besides the
+    /// provider's own, e.g. "mx.example.org" or "*.example.org"
+    #[serde(default)]
+    pub trusted_authserv_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemHealth {
+    pub local_only: bool,
+    pub database_ready: bool,
+    pub account_count: usize,
+    pub ai_model_loaded: bool,
+    pub rag_ready: bool,
+    pub embedding_model_downloaded: bool,
+    pub idle_monitors: Vec<String>,
+    /// Features currently refused because of local-only mode
+    pub disabled_features: Vec<String>,
+}
+
+lazy_static::lazy_static! {
+    static ref APP_SETTINGS: Mutex<Option<AppSettings>> = Mutex::new(None);
+}
+
+/// Features that need network access beyond the user's mail server
+const REMOTE_FEATURES: &[&str] = &[
+    "AI model download",
+    "Embedding model download",
+    "One-click unsubscribe",
+    "Remote content loading",
+    "Server auto-discovery",
+];
+
+fn load_app_settings() -> AppSettings {
+    let path = match get_data_dir() {
+        Ok(dir) => dir.join(APP_SETTINGS_FILE),
+        Err(_) => return AppSettings::default(),
+    };
+
+    fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Current app settings (loaded from disk on first access)
+pub fn app_settings() -> AppSettings {
+    let mut guard = APP_SETTINGS.lock().unwrap();
+    guard.get_or_insert_with(load_app_settings).clone()
+}
+
+fn save_app_settings(settings: &AppSettings) -> Result<(), String> {
+    let data_dir = get_data_dir()?;
+    fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize app settings: {}", e))?;
+    fs::write(data_dir.join(APP_SETTINGS_FILE), content)
+        .map_err(|e| format!("Failed to write app settings: {}", e))?;
+
+    let mut guard = APP_SETTINGS.lock().unwrap();
+    *guard = Some(settings.clone());
+    Ok(())
+}
+
+/// Whether local-only mode is enabled
+pub fn is_local_only() -> bool {
+    app_settings().local_only
+}
+
+/// Reject a feature that needs network access beyond the user's mail server
+pub fn ensure_network_allowed(feature: &str) -> Result<(), String> {
+    app_settings().ensure_network_allowed(feature)
+}
+
+impl AppSettings {
+    fn ensure_network_allowed(&self, feature: &str) -> Result<(), String> {
+        if self.local_only {
+            Err(format!("{} is disabled in local-only mode", feature))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Whether long emails are embedded in chunks
+pub fn embedding_chunking_enabled() -> bool {
+    app_settings().embedding_chunking
+}
+
+/// Whether quoted reply history is embedded and summarized
+pub fn quoted_text_kept() -> bool {
+    app_settings().keep_quoted_text
+}
+
+/// Minimum classification margin to use
+pub fn category_min_margin() -> f32 {
+    app_settings()
+        .category_min_margin
+        .unwrap_or(DEFAULT_MIN_CATEGORY_MARGIN)
+}
+
+/// Whether embeddings are stored quantized
+pub fn embedding_quantization_enabled() -> bool {
+    app_settings().quantize_embeddings
+}
+
+/// Index lists to scan per semantic search
+pub fn vector_search_probes() -> usize {
+    app_settings()
+        .vector_search_probes
+        .unwrap_or(DEFAULT_SEARCH_PROBES)
+}
+
+/// How often folders are polled on servers without IDLE
+pub fn poll_interval() -> std::time::Duration {
+    std::time::Duration::from_secs(
+        app_settings()
+            .poll_interval_secs
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
+    )
+}
+
+/// The provider's IMAP rate limits with any overrides from the settings
+pub fn imap_rate_limits(provider: &ProviderType) -> RateLimits {
+    let settings = app_settings();
+    RateLimits::for_provider(provider).with_overrides(
+        settings.imap_requests_per_sec,
+        settings.imap_burst,
+        settings.imap_max_concurrent_fetches,
+    )
+}
+
+/// Authserv-ids whose Authentication-Results are shown for mail received
+/// through `imap_host`: the provider's and any added in the settings
+pub fn trusted_authserv_ids(provider: &ProviderType, imap_host: &str) -> Vec<String> {
+    let mut ids = provider_authserv_ids(provider, imap_host);
+    ids.extend(app_settings().trusted_authserv_ids);
+    ids
+}
+
+/// Largest combined attachment size `send_email` accepts, in bytes
+pub fn attachment_limit_bytes() -> u64 {
+    app_settings()
+        .attachment_limit_mb
+        .unwrap_or(DEFAULT_ATTACHMENT_LIMIT_MB)
+        .saturating_mul(1024 * 1024)
+}
+
+/// The configured attachment safety checker, if any
+pub fn configured_safety_checker() -> Option<Box<dyn SafetyChecker>> {
+    let path = app_settings().attachment_blocklist_path?;
+    match BlocklistChecker::from_file(std::path::Path::new(&path)) {
+        Ok(checker) => Some(Box::new(checker)),
+        Err(e) => {
+            eprintln!("[Settings] Attachment blocklist unavailable: {}", e);
+            None
+        }
+    }
+}
+
+/// Get the current app settings
+#[tauri::command]
+pub async fn get_app_settings() -> Result<AppSettings, String> {
+    Ok(app_settings())
+}
+
+/// Enable or disable local-only mode
+#[tauri::command]
+pub async fn set_local_only(enabled: bool) -> Result<AppSettings, String> {
+    let mut settings = app_settings();
+    settings.local_only = enabled;
+    save_app_settings(&settings)?;
+
+    println!("[Settings] Local-only mode {}", if enabled { "enabled" } else { "disabled" });
+    Ok(settings)
+}
+
+/// Set the undo-send window in seconds (None restores the default)
+#[tauri::command]
+pub async fn set_undo_send_delay(seconds: Option<u64>) -> Result<AppSettings, String> {
+    let mut settings = app_settings();
+    settings.undo_send_secs = seconds;
+    save_app_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Enable or disable chunked embeddings for long emails
+#[tauri::command]
+pub async fn set_embedding_chunking(enabled: bool) -> Result<AppSettings, String> {
+    let mut settings = app_settings();
+    settings.embedding_chunking = enabled;
+    save_app_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Keep or strip quoted reply history when embedding and summarizing emails.
+/// Applies to emails embedded from now on.
+#[tauri::command]
+pub async fn set_keep_quoted_text(enabled: bool) -> Result<AppSettings, String> {
+    let mut settings = app_settings();
+    settings.keep_quoted_text = enabled;
+    save_app_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Enable or disable int8 embedding storage, converting the stored
+/// embeddings to the chosen format
+#[tauri::command]
+pub async fn set_embedding_quantization(enabled: bool) -> Result<AppSettings, String> {
+    let mut settings = app_settings();
+    settings.quantize_embeddings = enabled;
+    save_app_settings(&settings)?;
+
+    if let Some(vector_db) = super::rag::get_vector_db() {
+        vector_db.set_quantization(enabled);
+        let converted = tokio::task::spawn_blocking(move || {
+            vector_db.requantize_embeddings()
+        })
+        .await
+        .map_err(|e| format!("Requantization task failed: {}", e))?
+        .map_err(|e| format!("Failed to convert embeddings: {}", e))?;
+        println!("[Settings] Converted {} stored embeddings", converted);
+    }
+    Ok(settings)
+}
+
+/// Set the classification margin below which emails are "uncertain"
+/// (None restores the default)
+#[tauri::command]
+pub async fn set_category_threshold(min_margin: Option<f32>) -> Result<AppSettings, String> {
+    if let Some(margin) = min_margin {
+        if !(0.0..=1.0).contains(&margin) {
+            return Err(format!("Category threshold must be between 0 and 1, got {}", margin));
+        }
+    }
+
+    let mut settings = app_settings();
+    settings.category_min_margin = min_margin;
+    save_app_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Set how many index lists semantic search scans (None restores the default)
+#[tauri::command]
+pub async fn set_vector_search_probes(probes: Option<usize>) -> Result<AppSettings, String> {
+    if probes == Some(0) {
+        return Err("Search probes must be at least 1".to_string());
+    }
+
+    let mut settings = app_settings();
+    settings.vector_search_probes = probes;
+    save_app_settings(&settings)?;
+
+    if let Some(vector_db) = super::rag::get_vector_db() {
+        vector_db.set_search_probes(vector_search_probes());
+    }
+    Ok(settings)
+}
+
+/// Set the polling interval for servers without IDLE (None restores the default)
+#[tauri::command]
+pub async fn set_poll_interval(seconds: Option<u64>) -> Result<AppSettings, String> {
+    if seconds == Some(0) {
+        return Err("Poll interval must be at least 1 second".to_string());
+    }
+
+    let mut settings = app_settings();
+    settings.poll_interval_secs = seconds;
+    save_app_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Set the attachment size limit in MB (None restores the default)
+#[tauri::command]
+pub async fn set_attachment_limit(megabytes: Option<u64>) -> Result<AppSettings, String> {
+    if megabytes == Some(0) {
+        return Err("Attachment limit must be at least 1 MB".to_string());
+    }
+
+    let mut settings = app_settings();
+    settings.attachment_limit_mb = megabytes;
+    save_app_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Configure (or clear) the local attachment hash blocklist
+#[tauri::command]
+pub async fn set_attachment_blocklist(path: Option<String>) -> Result<AppSettings, String> {
+    if let Some(ref p) = path {
+        BlocklistChecker::from_file(std::path::Path::new(p)).map_err(|e| e.to_string())?;
+    }
+
+    let mut settings = app_settings();
+    settings.attachment_blocklist_path = path;
+    save_app_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Report the state of the app's subsystems
+#[tauri::command]
+pub async fn system_health(
+    db: State<'_, DbState>,
+    idle_manager: State<'_, IdleManager>,
+) -> Result<SystemHealth, String> {
+    let local_only = is_local_only();
+
+    let (database_ready, account_count) = {
+        let db_lock = db.lock().unwrap();
+        match db_lock.as_ref() {
+            Some(database) => (
+                true,
+                database.list_accounts().map(|a| a.len()).unwrap_or(0),
+            ),
+            None => (false, 0),
+        }
+    };
+
+    let ai_model_loaded = {
+        let guard = super::ai::SUMMARIZER.lock().unwrap();
+        guard.as_ref().map(|s| s.is_model_loaded()).unwrap_or(false)
+    };
+
+    let disabled_features = if local_only {
+        REMOTE_FEATURES.iter().map(|f| f.to_string()).collect()
+    } else {
+        Vec::new()
+    };
+
+    Ok(SystemHealth {
+        local_only,
+        database_ready,
+        account_count,
+        ai_model_loaded,
+        rag_ready: super::rag::is_rag_ready(),
+        embedding_model_downloaded: crate::llm::embeddings::is_model_downloaded(None),
+        idle_monitors: idle_manager.active_monitors().await,
+        disabled_features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_only_refuses_remote_features() {
+        let settings = AppSettings::default();
+        for feature in REMOTE_FEATURES {
+            assert!(settings.ensure_network_allowed(feature).is_ok());
+        }
+
+        // As saved by set_local_only
+        let settings: AppSettings = serde_json::from_str(r#"{"local_only": true}"#).unwrap();
+        for feature in REMOTE_FEATURES {
+            assert_eq!(
+                settings.ensure_network_allowed(feature),
+                Err(format!("{} is disabled in local-only mode", feature))
+            );
+        }
+    }
+}
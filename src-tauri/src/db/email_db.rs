@@ -2,12 +2,19 @@ use anyhow::{Context, Result as AnyhowResult};
 use chrono::Utc;
 use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use super::schema::create_tables;
-use crate::auth::account::Account;
-use crate::email::types::Email;
+use crate::auth::account::{normalize_mailbox_address, Account};
+use crate::email::imap_client::{compact_uid_set, parse_uid_set};
+use crate::email::mailing_list::{MailingList, UnsubscribeInfo};
+use crate::email::rules::Rule;
+use crate::email::server_presets::TlsMode;
+use crate::email::snooze::Snooze;
+use crate::email::sort::{sort_items, MessageSort};
+use crate::email::types::{Email, FolderSyncState, IdleFolderState, MessageFlags, SyncState};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailInsight {
@@ -53,10 +60,37 @@ pub struct IndexingStatus {
     pub error_message: Option<String>,
 }
 
+/// (list_id, message_count, last_received, latest list_meta JSON)
+pub type MailingListRow = (String, i64, i64, Option<String>);
+
+/// What `merge_accounts` did with the duplicate account's emails
+#[derive(Debug, Default)]
+pub struct MergedEmails {
+    /// (old_id, new_id) of emails moved to the kept account
+    pub renamed: Vec<(String, String)>,
+    /// Emails deleted because the kept account already had them
+    pub dropped: Vec<String>,
+}
+
 pub struct EmailDatabase {
     conn: Arc<Mutex<Connection>>,
 }
 
+/// Subquery for the UIDVALIDITY a folder was last recorded under by a sync
+/// or IDLE run (NULL if neither has run), given SQL for the account and folder
+fn known_uid_validity_sql(account_id: &str, folder: &str) -> String {
+    format!(
+        "(SELECT uid_validity FROM (
+             SELECT uid_validity, synced_at AS seen_at FROM folder_sync_state
+             WHERE account_id = {0} AND folder = {1}
+             UNION ALL
+             SELECT uid_validity, updated_at FROM idle_state
+             WHERE account_id = {0} AND folder = {1}
+         ) ORDER BY seen_at DESC LIMIT 1)",
+        account_id, folder
+    )
+}
+
 impl EmailDatabase {
     pub fn new(db_path: PathBuf) -> AnyhowResult<Self> {
         let conn = Connection::open(db_path).context("Failed to open database")?;
@@ -70,15 +104,94 @@ impl EmailDatabase {
 
     // Store or update an email
     pub fn store_email(&self, email: &Email) -> AnyhowResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        // REPLACE deletes the old row without firing delete triggers, so its
+        // index entry has to go first
+        tx.execute(
+            "INSERT INTO emails_fts(emails_fts, rowid, subject, body_plain, from_name, from_email)
+             SELECT 'delete', rowid, subject, body_plain, from_name, from_email
+             FROM emails WHERE id = ?1",
+            params![&email.id],
+        )?;
+        Self::write_email(&tx, email, "INSERT OR REPLACE INTO emails", "")?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Cache a header-only copy from `ImapClient::fetch_headers`. A row that
+    /// already holds the body keeps it; only its flags and timestamps change.
+    pub fn store_email_headers(&self, email: &Email) -> AnyhowResult<()> {
         let conn = self.conn.lock().unwrap();
+        // A row cached under another UIDVALIDITY is a different message
+        conn.execute(
+            &format!(
+                "DELETE FROM emails WHERE id = ?1 AND uid_validity NOT IN (0, {})",
+                known_uid_validity_sql("?2", "?3")
+            ),
+            params![&email.id, &email.account_id, &email.folder],
+        )?;
+        Self::write_email(
+            &conn,
+            email,
+            "INSERT INTO emails",
+            "ON CONFLICT(id) DO UPDATE SET
+             is_read = excluded.is_read, is_starred = excluded.is_starred,
+             labels = excluded.labels, updated_at = excluded.updated_at,
+             cached_at = excluded.cached_at",
+        )
+    }
+
+    /// `insert` with all columns of `email`, then `on_conflict`
+    fn write_email(
+        conn: &Connection,
+        email: &Email,
+        insert: &str,
+        on_conflict: &str,
+    ) -> AnyhowResult<()> {
         let now = Utc::now().timestamp();
 
+        // Lists without a List-Id/List-Post (e.g. newsletters with only
+        // List-Unsubscribe) are grouped by sender
+        let list_id = email.mailing_list.as_ref().map(|list| {
+            list.id
+                .clone()
+                .unwrap_or_else(|| email.from_email.to_lowercase())
+        });
+        let list_meta = email
+            .mailing_list
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let security = serde_json::to_string(&email.security)?;
+        let attachments = serde_json::to_string(&email.attachments)?;
+        let references = serde_json::to_string(&email.references)?;
+        let invite = email
+            .invite
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let authentication = email
+            .authentication
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+
         conn.execute(
-            "INSERT OR REPLACE INTO emails
-            (id, thread_id, subject, from_name, from_email, to_emails, date, snippet,
-             body_html, body_plain, is_read, is_starred, has_attachments, labels,
-             created_at, updated_at, account_id, uid, folder, message_id)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+            &format!(
+                "{}
+                (id, thread_id, subject, from_name, from_email, to_emails, date, snippet,
+                 body_html, body_plain, is_read, is_starred, has_attachments, labels,
+                 created_at, updated_at, account_id, uid, folder, message_id, list_id, list_meta,
+                 security, size, attachments, in_reply_to, reference_ids, cached_at,
+                 cc_emails, reply_to, invite, authentication, uid_validity)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32,
+                        COALESCE({}, 0))
+                {}",
+                insert,
+                known_uid_validity_sql("?17", "?19"),
+                on_conflict
+            ),
             params![
                 &email.id,
                 &email.thread_id,
@@ -100,12 +213,136 @@ impl EmailDatabase {
                 email.uid as i64,
                 &email.folder,
                 &email.message_id,
+                list_id,
+                list_meta,
+                security,
+                email.size as i64,
+                attachments,
+                &email.in_reply_to,
+                references,
+                now,
+                serde_json::to_string(&email.cc)?,
+                serde_json::to_string(&email.reply_to)?,
+                invite,
+                authentication,
             ],
         )?;
 
         Ok(())
     }
 
+    /// Record the senders of a batch of incoming emails in the contacts table and
+    /// return the IDs of the emails that are the first ever received from their
+    /// sender. The batch is applied oldest-first in a single transaction, so two
+    /// messages from a new sender in the same sync flag only the earlier one.
+    pub fn record_senders(
+        &self,
+        own_address: &str,
+        emails: &[Email],
+    ) -> AnyhowResult<HashSet<String>> {
+        let own_address = normalize_mailbox_address(own_address);
+        let mut incoming: Vec<(&Email, String)> = emails
+            .iter()
+            .map(|e| (e, normalize_mailbox_address(&e.from_email)))
+            .filter(|(_, address)| !address.is_empty() && *address != own_address)
+            .collect();
+        incoming.sort_by_key(|(e, _)| e.date_timestamp);
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        for (email, address) in &incoming {
+            tx.execute(
+                "INSERT INTO contacts
+                 (account_id, email, display_name, first_email_id, first_seen_at, last_seen_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+                 ON CONFLICT(account_id, email) DO UPDATE SET
+                    first_email_id = CASE WHEN excluded.first_seen_at < contacts.first_seen_at
+                                          THEN excluded.first_email_id
+                                          ELSE contacts.first_email_id END,
+                    first_seen_at = MIN(contacts.first_seen_at, excluded.first_seen_at),
+                    last_seen_at = MAX(contacts.last_seen_at, excluded.last_seen_at),
+                    display_name = CASE WHEN excluded.display_name != ''
+                                        THEN excluded.display_name
+                                        ELSE contacts.display_name END",
+                params![
+                    &email.account_id,
+                    address,
+                    &email.from,
+                    &email.id,
+                    email.date_timestamp,
+                ],
+            )?;
+        }
+
+        let mut first_contacts = HashSet::new();
+        for (email, address) in &incoming {
+            let first_email_id: String = tx.query_row(
+                "SELECT first_email_id FROM contacts WHERE account_id = ?1 AND email = ?2",
+                params![&email.account_id, address],
+                |row| row.get(0),
+            )?;
+            if first_email_id == email.id {
+                first_contacts.insert(email.id.clone());
+            }
+        }
+
+        tx.commit()?;
+        Ok(first_contacts)
+    }
+
+    /// Mailing lists seen on an account, most recently active first
+    pub fn get_mailing_lists(&self, account_id: &str) -> AnyhowResult<Vec<MailingListRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT e.list_id, COUNT(*), MAX(e.date),
+                    (SELECT l.list_meta FROM emails l
+                     WHERE l.account_id = e.account_id AND l.list_id = e.list_id
+                     ORDER BY l.date DESC LIMIT 1)
+             FROM emails e
+             WHERE e.account_id = ?1 AND e.list_id IS NOT NULL
+             GROUP BY e.list_id
+             ORDER BY MAX(e.date) DESC",
+        )?;
+
+        let lists = stmt
+            .query_map(params![account_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(lists)
+    }
+
+    /// AI priority scores for the given emails (emails without insights are omitted)
+    pub fn get_priority_scores(&self, email_ids: &[String]) -> AnyhowResult<HashMap<String, f64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT priority_score FROM email_insights WHERE email_id = ?1")?;
+
+        let mut scores = HashMap::new();
+        for email_id in email_ids {
+            if let Some(score) = stmt
+                .query_row(params![email_id], |row| row.get::<_, f64>(0))
+                .optional()?
+            {
+                scores.insert(email_id.clone(), score);
+            }
+        }
+        Ok(scores)
+    }
+
+    /// Whether an email is the first one received from its sender
+    pub fn is_first_contact(&self, email_id: &str) -> AnyhowResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let first: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM contacts WHERE first_email_id = ?1)",
+            params![email_id],
+            |row| row.get(0),
+        )?;
+        Ok(first)
+    }
+
     // Store AI insights for an email
     pub fn store_insights(&self, insight: &EmailInsight) -> AnyhowResult<()> {
         let conn = self.conn.lock().unwrap();
@@ -134,6 +371,38 @@ impl EmailDatabase {
         Ok(())
     }
 
+    /// Stored AI insights for an email, if it has been indexed
+    pub fn get_insight(&self, email_id: &str) -> AnyhowResult<Option<EmailInsight>> {
+        let conn = self.conn.lock().unwrap();
+
+        let insight = conn
+            .query_row(
+                "SELECT email_id, summary, priority, priority_score, category, insights,
+                        action_items, has_deadline, has_meeting, has_financial, sentiment, indexed_at
+                 FROM email_insights WHERE email_id = ?1",
+                params![email_id],
+                |row| {
+                    Ok(EmailInsight {
+                        email_id: row.get(0)?,
+                        summary: row.get(1)?,
+                        priority: row.get(2)?,
+                        priority_score: row.get(3)?,
+                        category: row.get(4)?,
+                        insights: row.get(5)?,
+                        action_items: row.get(6)?,
+                        has_deadline: row.get::<_, i32>(7)? != 0,
+                        has_meeting: row.get::<_, i32>(8)? != 0,
+                        has_financial: row.get::<_, i32>(9)? != 0,
+                        sentiment: row.get(10)?,
+                        indexed_at: row.get(11)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(insight)
+    }
+
     // Get emails sorted by priority
     pub fn get_emails_by_priority(
         &self,
@@ -314,6 +583,101 @@ impl EmailDatabase {
         Ok(emails)
     }
 
+    /// Full-text search over subjects and plain bodies, best BM25 match first.
+    /// Returns (email_id, score) where a higher score is a better match and
+    /// subject hits count double.
+    pub fn keyword_search(&self, query: &str, limit: usize) -> AnyhowResult<Vec<(String, f64)>> {
+        let Some(fts_query) = fts_match_query(query) else {
+            return Ok(Vec::new());
+        };
+        let conn = self.conn.lock().unwrap();
+
+        // bm25() is lower for better matches
+        let mut stmt = conn.prepare(
+            "SELECT e.id, -bm25(emails_fts, 2.0, 1.0) AS score
+             FROM emails_fts
+             JOIN emails e ON e.rowid = emails_fts.rowid
+             WHERE emails_fts MATCH ?1
+             ORDER BY bm25(emails_fts, 2.0, 1.0)
+             LIMIT ?2",
+        )?;
+
+        let matches = stmt
+            .query_map(params![fts_query, limit as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(matches)
+    }
+
+    /// Offline search of cached emails, best BM25 match first, optionally in
+    /// one folder. Every word must match; "quoted" parts match as phrases.
+    /// Each item's snippet is HTML-escaped with matches wrapped in `<mark>`.
+    pub fn search_cached(
+        &self,
+        query: &str,
+        folder: Option<&str>,
+        limit: i64,
+    ) -> AnyhowResult<Vec<crate::email::types::EmailListItem>> {
+        let Some(fts_query) = fts_search_query(query) else {
+            return Ok(Vec::new());
+        };
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+
+        // Highlights are marked with control characters and turned into
+        // <mark> after escaping the text around them
+        let mut stmt = conn.prepare(
+            "SELECT e.id, e.thread_id, e.subject, e.from_name, e.from_email, e.date,
+                    snippet(emails_fts, 1, char(2), char(3), '…', 16), e.snippet,
+                    e.is_read, e.is_starred, e.has_attachments,
+                    EXISTS(SELECT 1 FROM contacts c WHERE c.first_email_id = e.id),
+                    e.body_html IS NOT NULL OR e.body_plain IS NOT NULL, e.updated_at, e.size
+             FROM emails_fts
+             JOIN emails e ON e.rowid = emails_fts.rowid
+             WHERE emails_fts MATCH ?1 AND (?2 IS NULL OR e.folder = ?2)
+             ORDER BY bm25(emails_fts, 2.0, 1.0, 1.5, 1.5)
+             LIMIT ?3",
+        )?;
+
+        let emails = stmt
+            .query_map(params![fts_query, folder, limit], |row| {
+                let date_timestamp: i64 = row.get(5)?;
+                let highlighted: Option<String> = row.get(6)?;
+                let snippet = match highlighted.filter(|s| !s.is_empty()) {
+                    Some(highlighted) => highlight_snippet(&highlighted),
+                    // Only the subject or sender matched, or there's no body
+                    None => highlight_snippet(&row.get::<_, String>(7)?),
+                };
+
+                Ok(crate::email::types::EmailListItem {
+                    id: row.get(0)?,
+                    thread_id: row.get(1)?,
+                    subject: row.get(2)?,
+                    from: row.get(3)?,
+                    from_email: row.get(4)?,
+                    date: chrono::DateTime::from_timestamp(date_timestamp, 0)
+                        .map(|dt| dt.format("%a, %d %b %Y %H:%M:%S %z").to_string())
+                        .unwrap_or_default(),
+                    snippet,
+                    is_read: row.get::<_, i32>(8)? != 0,
+                    is_starred: row.get::<_, i32>(9)? != 0,
+                    has_attachments: row.get::<_, i32>(10)? != 0,
+                    is_first_contact: row.get::<_, i32>(11)? != 0,
+                    sync_state: SyncState::from_cache(
+                        row.get::<_, i32>(12)? != 0,
+                        row.get(13)?,
+                        now,
+                    ),
+                    size: row.get::<_, i64>(14)? as u32,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(emails)
+    }
+
     // Update indexing status
     pub fn update_indexing_status(
         &self,
@@ -395,6 +759,57 @@ impl EmailDatabase {
         Ok(ids)
     }
 
+    /// Distinct thread IDs of cached emails
+    pub fn get_all_thread_ids(&self) -> AnyhowResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare("SELECT DISTINCT thread_id FROM emails")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(ids)
+    }
+
+    /// IDs of the emails in a thread, newest first
+    pub fn get_thread_email_ids(&self, thread_id: &str) -> AnyhowResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt =
+            conn.prepare("SELECT id FROM emails WHERE thread_id = ?1 ORDER BY date DESC")?;
+        let ids = stmt
+            .query_map(params![thread_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(ids)
+    }
+
+    /// Per-category (category, total, unread) counts of an account's cached emails in a
+    /// folder. Emails without a stored classification are reported under a NULL category.
+    pub fn get_category_counts(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> AnyhowResult<Vec<(Option<String>, i64, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT i.category, COUNT(*),
+                    COALESCE(SUM(CASE WHEN e.is_read = 0 THEN 1 ELSE 0 END), 0)
+             FROM emails e
+             LEFT JOIN email_insights i ON e.id = i.email_id
+             WHERE e.folder = ?1 AND e.account_id = ?2
+             GROUP BY i.category",
+        )?;
+
+        let counts = stmt
+            .query_map(params![folder, account_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(counts)
+    }
+
     // Get total count of emails
     pub fn get_email_count(&self) -> AnyhowResult<i64> {
         let conn = self.conn.lock().unwrap();
@@ -435,19 +850,39 @@ impl EmailDatabase {
         email_id: &str,
     ) -> AnyhowResult<Option<crate::email::types::Email>> {
         let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
 
-        let mut stmt = conn.prepare(
+        // Rows cached under an older UIDVALIDITY name some other message now
+        let mut stmt = conn.prepare(&format!(
             "SELECT id, thread_id, subject, from_name, from_email, to_emails,
                     date, snippet, body_html, body_plain, is_read, is_starred,
-                    has_attachments, labels, account_id, uid, folder, message_id
-             FROM emails WHERE id = ?1",
-        )?;
+                    has_attachments, labels, account_id, uid, folder, message_id,
+                    EXISTS(SELECT 1 FROM contacts c WHERE c.first_email_id = emails.id),
+                    list_meta, updated_at, security, size, attachments, in_reply_to, reference_ids,
+                    cc_emails, reply_to, invite, authentication
+             FROM emails WHERE id = ?1 AND uid_validity IN (0, {})",
+            known_uid_validity_sql("emails.account_id", "emails.folder")
+        ))?;
 
         let email = stmt
             .query_row([email_id], |row| {
                 let to_emails_json: String = row.get(5)?;
                 let labels_json: String = row.get(13)?;
                 let date_timestamp: i64 = row.get(6)?;
+                let body_html: Option<String> = row.get(8)?;
+                let body_plain: Option<String> = row.get(9)?;
+                let sync_state = SyncState::from_cache(
+                    body_html.is_some() || body_plain.is_some(),
+                    row.get(20)?,
+                    now,
+                );
+                let mailing_list: Option<MailingList> = row
+                    .get::<_, Option<String>>(19)?
+                    .and_then(|json| serde_json::from_str(&json).ok());
+                // List-Unsubscribe-Post isn't cached, so one-click needs a live fetch
+                let unsubscribe = mailing_list
+                    .as_ref()
+                    .and_then(|list| UnsubscribeInfo::from_urls(&list.unsubscribe, false));
 
                 Ok(crate::email::types::Email {
                     id: row.get(0)?,
@@ -456,13 +891,21 @@ impl EmailDatabase {
                     from: row.get(3)?,
                     from_email: row.get(4)?,
                     to: serde_json::from_str(&to_emails_json).unwrap_or_default(),
+                    cc: row
+                        .get::<_, Option<String>>(26)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    reply_to: row
+                        .get::<_, Option<String>>(27)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
                     date: chrono::DateTime::from_timestamp(date_timestamp, 0)
                         .map(|dt| dt.format("%a, %d %b %Y %H:%M:%S %z").to_string())
                         .unwrap_or_default(),
                     date_timestamp,
                     snippet: row.get(7)?,
-                    body_html: row.get(8)?,
-                    body_plain: row.get(9)?,
+                    body_html,
+                    body_plain,
                     is_read: row.get::<_, i32>(10)? != 0,
                     is_starred: row.get::<_, i32>(11)? != 0,
                     has_attachments: row.get::<_, i32>(12)? != 0,
@@ -471,6 +914,33 @@ impl EmailDatabase {
                     uid: row.get::<_, i64>(15).unwrap_or(0) as u32,
                     folder: row.get::<_, String>(16).unwrap_or_else(|_| "INBOX".to_string()),
                     message_id: row.get::<_, String>(17).unwrap_or_default(),
+                    is_first_contact: row.get::<_, i32>(18)? != 0,
+                    mailing_list,
+                    unsubscribe,
+                    sync_state,
+                    security: row
+                        .get::<_, Option<String>>(21)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    size: row.get::<_, i64>(22)? as u32,
+                    attachments: row
+                        .get::<_, Option<String>>(23)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    // Not cached; fetched with the message
+                    inline_parts: Vec::new(),
+                    remote_content_blocked: false,
+                    in_reply_to: row.get(24)?,
+                    references: row
+                        .get::<_, Option<String>>(25)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    invite: row
+                        .get::<_, Option<String>>(28)?
+                        .and_then(|json| serde_json::from_str(&json).ok()),
+                    authentication: row
+                        .get::<_, Option<String>>(29)?
+                        .and_then(|json| serde_json::from_str(&json).ok()),
                 })
             })
             .optional()?;
@@ -486,8 +956,10 @@ impl EmailDatabase {
         conn.execute(
             "INSERT OR REPLACE INTO accounts
             (id, email, display_name, provider, imap_host, imap_port, smtp_host, smtp_port,
-             auth_type, is_active, created_at, last_synced_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+             auth_type, is_active, created_at, last_synced_at, sync_paused, block_manual_fetch,
+             tls_mode, smtp_tls_mode, signature, html_signature)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+                    ?18)",
             params![
                 &account.id,
                 &account.email,
@@ -501,6 +973,12 @@ impl EmailDatabase {
                 account.is_active as i32,
                 account.created_at,
                 account.last_synced_at,
+                account.sync_paused as i32,
+                account.block_manual_fetch as i32,
+                account.tls_mode.as_str(),
+                account.smtp_tls_mode.as_ref().map(TlsMode::as_str),
+                &account.signature,
+                &account.html_signature,
             ],
         )?;
         Ok(())
@@ -524,6 +1002,18 @@ impl EmailDatabase {
             "DELETE FROM emails WHERE account_id = ?1",
             params![account_id],
         )?;
+        conn.execute(
+            "DELETE FROM contacts WHERE account_id = ?1",
+            params![account_id],
+        )?;
+        conn.execute(
+            "DELETE FROM send_aliases WHERE account_id = ?1",
+            params![account_id],
+        )?;
+        conn.execute(
+            "DELETE FROM idle_state WHERE account_id = ?1",
+            params![account_id],
+        )?;
         // Delete account
         conn.execute("DELETE FROM accounts WHERE id = ?1", params![account_id])?;
         Ok(())
@@ -534,27 +1024,13 @@ impl EmailDatabase {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT id, email, display_name, provider, imap_host, imap_port, smtp_host, smtp_port,
-                    auth_type, is_active, created_at, last_synced_at
+                    auth_type, is_active, created_at, last_synced_at, sync_paused, block_manual_fetch,
+                    tls_mode, smtp_tls_mode, signature, html_signature
              FROM accounts ORDER BY created_at ASC",
         )?;
 
         let accounts = stmt
-            .query_map([], |row| {
-                Ok(Account {
-                    id: row.get(0)?,
-                    email: row.get(1)?,
-                    display_name: row.get(2)?,
-                    provider: row.get(3)?,
-                    imap_host: row.get(4)?,
-                    imap_port: row.get::<_, i32>(5)? as u16,
-                    smtp_host: row.get(6)?,
-                    smtp_port: row.get::<_, i32>(7)? as u16,
-                    auth_type: row.get(8)?,
-                    is_active: row.get::<_, i32>(9)? != 0,
-                    created_at: row.get(10)?,
-                    last_synced_at: row.get(11)?,
-                })
-            })?
+            .query_map([], account_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(accounts)
@@ -565,27 +1041,13 @@ impl EmailDatabase {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT id, email, display_name, provider, imap_host, imap_port, smtp_host, smtp_port,
-                    auth_type, is_active, created_at, last_synced_at
+                    auth_type, is_active, created_at, last_synced_at, sync_paused, block_manual_fetch,
+                    tls_mode, smtp_tls_mode, signature, html_signature
              FROM accounts WHERE id = ?1",
         )?;
 
         let account = stmt
-            .query_row([account_id], |row| {
-                Ok(Account {
-                    id: row.get(0)?,
-                    email: row.get(1)?,
-                    display_name: row.get(2)?,
-                    provider: row.get(3)?,
-                    imap_host: row.get(4)?,
-                    imap_port: row.get::<_, i32>(5)? as u16,
-                    smtp_host: row.get(6)?,
-                    smtp_port: row.get::<_, i32>(7)? as u16,
-                    auth_type: row.get(8)?,
-                    is_active: row.get::<_, i32>(9)? != 0,
-                    created_at: row.get(10)?,
-                    last_synced_at: row.get(11)?,
-                })
-            })
+            .query_row([account_id], account_from_row)
             .optional()?;
 
         Ok(account)
@@ -602,37 +1064,204 @@ impl EmailDatabase {
         Ok(())
     }
 
+    /// Pause or resume background sync for an account
+    pub fn set_account_paused(
+        &self,
+        account_id: &str,
+        paused: bool,
+        block_manual_fetch: bool,
+    ) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE accounts SET sync_paused = ?2, block_manual_fetch = ?3 WHERE id = ?1",
+            params![account_id, paused as i32, block_manual_fetch as i32],
+        )?;
+        if updated == 0 {
+            anyhow::bail!("Account not found: {}", account_id);
+        }
+        Ok(())
+    }
+
+    /// Replace an account's signatures; None clears one
+    pub fn set_account_signature(
+        &self,
+        account_id: &str,
+        signature: Option<&str>,
+        html_signature: Option<&str>,
+    ) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE accounts SET signature = ?2, html_signature = ?3 WHERE id = ?1",
+            params![account_id, signature, html_signature],
+        )?;
+        if updated == 0 {
+            anyhow::bail!("Account not found: {}", account_id);
+        }
+        Ok(())
+    }
+
+    /// Cached (total, unread) email counts for an account
+    pub fn get_account_email_counts(&self, account_id: &str) -> AnyhowResult<(i64, i64)> {
+        let conn = self.conn.lock().unwrap();
+        let counts = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(CASE WHEN is_read = 0 THEN 1 ELSE 0 END), 0)
+             FROM emails WHERE account_id = ?1",
+            params![account_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(counts)
+    }
+
     /// Get the active account
     pub fn get_active_account(&self) -> AnyhowResult<Option<Account>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT id, email, display_name, provider, imap_host, imap_port, smtp_host, smtp_port,
-                    auth_type, is_active, created_at, last_synced_at
+                    auth_type, is_active, created_at, last_synced_at, sync_paused, block_manual_fetch,
+                    tls_mode, smtp_tls_mode, signature, html_signature
              FROM accounts WHERE is_active = 1 LIMIT 1",
         )?;
 
         let account = stmt
-            .query_row([], |row| {
-                Ok(Account {
-                    id: row.get(0)?,
-                    email: row.get(1)?,
-                    display_name: row.get(2)?,
-                    provider: row.get(3)?,
-                    imap_host: row.get(4)?,
-                    imap_port: row.get::<_, i32>(5)? as u16,
-                    smtp_host: row.get(6)?,
-                    smtp_port: row.get::<_, i32>(7)? as u16,
-                    auth_type: row.get(8)?,
-                    is_active: row.get::<_, i32>(9)? != 0,
-                    created_at: row.get(10)?,
-                    last_synced_at: row.get(11)?,
-                })
-            })
+            .query_row([], account_from_row)
             .optional()?;
 
         Ok(account)
     }
 
+    /// Move all cached data of `duplicate_id` onto `keep_id` and delete the duplicate account.
+    /// Emails are re-keyed to the kept account's ID scheme; when the kept account already
+    /// has the same message cached, the duplicate's copy is dropped. Settings the kept
+    /// account has (signature, folder sorts, ...) win over the duplicate's.
+    pub fn merge_accounts(&self, keep_id: &str, duplicate_id: &str) -> AnyhowResult<MergedEmails> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let duplicate_emails: Vec<String> = {
+            let mut stmt = tx.prepare("SELECT id FROM emails WHERE account_id = ?1")?;
+            let ids = stmt
+                .query_map(params![duplicate_id], |row| row.get(0))?
+                .collect::<Result<Vec<String>, _>>()?;
+            ids
+        };
+
+        let mut merged = MergedEmails::default();
+        for old_id in duplicate_emails {
+            let suffix = old_id
+                .strip_prefix(&format!("{}:", duplicate_id))
+                .unwrap_or(&old_id);
+            let new_id = format!("{}:{}", keep_id, suffix);
+
+            let exists: bool = tx.query_row(
+                "SELECT count(*) > 0 FROM emails WHERE id = ?1",
+                params![&new_id],
+                |row| row.get(0),
+            )?;
+
+            if exists {
+                tx.execute("DELETE FROM email_insights WHERE email_id = ?1", params![&old_id])?;
+                tx.execute("DELETE FROM email_embeddings WHERE email_id = ?1", params![&old_id])?;
+                tx.execute("DELETE FROM emails WHERE id = ?1", params![&old_id])?;
+                merged.dropped.push(old_id);
+                continue;
+            }
+
+            tx.execute(
+                "UPDATE emails SET id = ?2, account_id = ?3 WHERE id = ?1",
+                params![&old_id, &new_id, keep_id],
+            )?;
+            tx.execute(
+                "UPDATE email_insights SET email_id = ?2 WHERE email_id = ?1",
+                params![&old_id, &new_id],
+            )?;
+            tx.execute(
+                "UPDATE email_embeddings SET email_id = ?2 WHERE email_id = ?1",
+                params![&old_id, &new_id],
+            )?;
+            tx.execute(
+                "UPDATE contacts SET first_email_id = ?2 WHERE first_email_id = ?1",
+                params![&old_id, &new_id],
+            )?;
+            merged.renamed.push((old_id, new_id));
+        }
+
+        // Senders known to both accounts keep the surviving account's history
+        tx.execute(
+            "UPDATE OR IGNORE contacts SET account_id = ?1 WHERE account_id = ?2",
+            params![keep_id, duplicate_id],
+        )?;
+        tx.execute(
+            "DELETE FROM contacts WHERE account_id = ?1",
+            params![duplicate_id],
+        )?;
+        tx.execute(
+            "UPDATE OR IGNORE send_aliases SET account_id = ?1 WHERE account_id = ?2",
+            params![keep_id, duplicate_id],
+        )?;
+        tx.execute(
+            "DELETE FROM send_aliases WHERE account_id = ?1",
+            params![duplicate_id],
+        )?;
+        for table in ["folder_sort", "snoozes", "rule_categories"] {
+            tx.execute(
+                &format!(
+                    "UPDATE OR IGNORE {} SET account_id = ?1 WHERE account_id = ?2",
+                    table
+                ),
+                params![keep_id, duplicate_id],
+            )?;
+        }
+        // Rows left over clashed with the kept account's; sync state is
+        // rebuilt by the kept account's next sync
+        for table in [
+            "folder_sort",
+            "snoozes",
+            "rule_categories",
+            "folder_sync_state",
+            "idle_state",
+        ] {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE account_id = ?1", table),
+                params![duplicate_id],
+            )?;
+        }
+        tx.execute(
+            "UPDATE rules SET account_id = ?1 WHERE account_id = ?2",
+            params![keep_id, duplicate_id],
+        )?;
+        tx.execute(
+            "UPDATE accounts SET
+             signature = COALESCE(signature, (SELECT signature FROM accounts WHERE id = ?2)),
+             html_signature = COALESCE(html_signature, (SELECT html_signature FROM accounts WHERE id = ?2))
+             WHERE id = ?1",
+            params![keep_id, duplicate_id],
+        )?;
+
+        // Keep the active-account pointer on the surviving account
+        let duplicate_was_active: bool = tx
+            .query_row(
+                "SELECT is_active FROM accounts WHERE id = ?1",
+                params![duplicate_id],
+                |row| row.get::<_, i32>(0),
+            )
+            .optional()?
+            .map(|v| v != 0)
+            .unwrap_or(false);
+
+        if duplicate_was_active {
+            tx.execute("UPDATE accounts SET is_active = 0", [])?;
+            tx.execute(
+                "UPDATE accounts SET is_active = 1 WHERE id = ?1",
+                params![keep_id],
+            )?;
+        }
+
+        tx.execute("DELETE FROM accounts WHERE id = ?1", params![duplicate_id])?;
+        tx.commit()?;
+
+        Ok(merged)
+    }
+
     // Get important emails by account (HIGH priority or starred)
     pub fn get_important_emails_by_account(
         &self,
@@ -728,11 +1357,16 @@ impl EmailDatabase {
     /// Get emails that haven't been indexed yet (no entry in email_insights)
     pub fn get_unindexed_emails(&self, limit: i64) -> AnyhowResult<Vec<crate::email::types::Email>> {
         let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
 
         let mut stmt = conn.prepare(
             "SELECT e.id, e.thread_id, e.subject, e.from_name, e.from_email, e.to_emails,
                     e.date, e.snippet, e.body_html, e.body_plain, e.is_read, e.is_starred,
-                    e.has_attachments, e.labels, e.account_id, e.uid, e.folder, e.message_id
+                    e.has_attachments, e.labels, e.account_id, e.uid, e.folder, e.message_id,
+                    EXISTS(SELECT 1 FROM contacts c WHERE c.first_email_id = e.id),
+                    e.list_meta, e.updated_at, e.security, e.size, e.attachments,
+                    e.in_reply_to, e.reference_ids, e.cc_emails, e.reply_to, e.invite,
+                    e.authentication
              FROM emails e
              LEFT JOIN email_insights i ON e.id = i.email_id
              WHERE i.email_id IS NULL
@@ -745,6 +1379,20 @@ impl EmailDatabase {
                 let to_emails_json: String = row.get(5)?;
                 let labels_json: String = row.get(13)?;
                 let date_timestamp: i64 = row.get(6)?;
+                let body_html: Option<String> = row.get(8)?;
+                let body_plain: Option<String> = row.get(9)?;
+                let sync_state = SyncState::from_cache(
+                    body_html.is_some() || body_plain.is_some(),
+                    row.get(20)?,
+                    now,
+                );
+                let mailing_list: Option<MailingList> = row
+                    .get::<_, Option<String>>(19)?
+                    .and_then(|json| serde_json::from_str(&json).ok());
+                // List-Unsubscribe-Post isn't cached, so one-click needs a live fetch
+                let unsubscribe = mailing_list
+                    .as_ref()
+                    .and_then(|list| UnsubscribeInfo::from_urls(&list.unsubscribe, false));
 
                 Ok(crate::email::types::Email {
                     id: row.get(0)?,
@@ -753,13 +1401,21 @@ impl EmailDatabase {
                     from: row.get(3)?,
                     from_email: row.get(4)?,
                     to: serde_json::from_str(&to_emails_json).unwrap_or_default(),
+                    cc: row
+                        .get::<_, Option<String>>(26)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    reply_to: row
+                        .get::<_, Option<String>>(27)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
                     date: chrono::DateTime::from_timestamp(date_timestamp, 0)
                         .map(|dt| dt.format("%a, %d %b %Y %H:%M:%S %z").to_string())
                         .unwrap_or_default(),
                     date_timestamp,
                     snippet: row.get(7)?,
-                    body_html: row.get(8)?,
-                    body_plain: row.get(9)?,
+                    body_html,
+                    body_plain,
                     is_read: row.get::<_, i32>(10)? != 0,
                     is_starred: row.get::<_, i32>(11)? != 0,
                     has_attachments: row.get::<_, i32>(12)? != 0,
@@ -768,6 +1424,32 @@ impl EmailDatabase {
                     uid: row.get::<_, i64>(15).unwrap_or(0) as u32,
                     folder: row.get::<_, String>(16).unwrap_or_else(|_| "INBOX".to_string()),
                     message_id: row.get::<_, String>(17).unwrap_or_default(),
+                    is_first_contact: row.get::<_, i32>(18)? != 0,
+                    mailing_list,
+                    unsubscribe,
+                    sync_state,
+                    security: row
+                        .get::<_, Option<String>>(21)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    size: row.get::<_, i64>(22)? as u32,
+                    attachments: row
+                        .get::<_, Option<String>>(23)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    inline_parts: Vec::new(),
+                    remote_content_blocked: false,
+                    in_reply_to: row.get(24)?,
+                    references: row
+                        .get::<_, Option<String>>(25)?
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    invite: row
+                        .get::<_, Option<String>>(28)?
+                        .and_then(|json| serde_json::from_str(&json).ok()),
+                    authentication: row
+                        .get::<_, Option<String>>(29)?
+                        .and_then(|json| serde_json::from_str(&json).ok()),
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -789,24 +1471,39 @@ impl EmailDatabase {
         Ok(())
     }
 
-    // Get all cached emails as EmailListItem for a specific folder
+    // Get cached emails of an account's folder as EmailListItem, newest (highest
+    // UID) first, like the server listing. `before_uid` pages the same way as
+    // `ImapClient::list_messages_before`. Without a cursor, non-date orders sort
+    // the folder's cached rows in memory, so switching sort only reorders what
+    // is already known; with one, they reorder just that page.
     pub fn get_cached_emails(
         &self,
+        account_id: &str,
         folder: &str,
         limit: i64,
+        sort: MessageSort,
+        before_uid: Option<u32>,
     ) -> AnyhowResult<Vec<crate::email::types::EmailListItem>> {
         let conn = self.conn.lock().unwrap();
+        let now = Utc::now().timestamp();
+        let whole_folder = sort != MessageSort::Date && before_uid.is_none();
+        // -1 is "no limit" in SQLite
+        let sql_limit = if whole_folder { -1 } else { limit };
 
-        let mut stmt = conn.prepare(
+        let mut stmt = conn.prepare(&format!(
             "SELECT id, thread_id, subject, from_name, from_email, date, snippet,
-                    is_read, is_starred, has_attachments
+                    is_read, is_starred, has_attachments,
+                    EXISTS(SELECT 1 FROM contacts c WHERE c.first_email_id = emails.id),
+                    body_html IS NOT NULL OR body_plain IS NOT NULL, updated_at, size
              FROM emails 
-             WHERE folder = ?1
-             ORDER BY date DESC LIMIT ?2",
-        )?;
-
-        let emails = stmt
-            .query_map(params![folder, limit], |row| {
+             WHERE account_id = ?1 AND folder = ?2 AND (?4 IS NULL OR uid < ?4)
+               AND uid_validity IN (0, {})
+             ORDER BY uid DESC, date DESC LIMIT ?3",
+            known_uid_validity_sql("?1", "?2")
+        ))?;
+
+        let mut emails = stmt
+            .query_map(params![account_id, folder, sql_limit, before_uid], |row| {
                 let date_timestamp: i64 = row.get(5)?;
 
                 Ok(crate::email::types::EmailListItem {
@@ -822,10 +1519,1335 @@ impl EmailDatabase {
                     is_read: row.get::<_, i32>(7)? != 0,
                     is_starred: row.get::<_, i32>(8)? != 0,
                     has_attachments: row.get::<_, i32>(9)? != 0,
+                    is_first_contact: row.get::<_, i32>(10)? != 0,
+                    sync_state: SyncState::from_cache(
+                        row.get::<_, i32>(11)? != 0,
+                        row.get(12)?,
+                        now,
+                    ),
+                    size: row.get::<_, i64>(13)? as u32,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
 
+        if sort != MessageSort::Date {
+            sort_items(&mut emails, sort);
+            emails.truncate(limit.max(0) as usize);
+        }
+
         Ok(emails)
     }
+
+    /// When the page `get_cached_emails` would list (by UID) was least recently
+    /// fetched from the server: the oldest `cached_at` among its rows. None
+    /// when nothing is cached.
+    pub fn oldest_cached_at(
+        &self,
+        account_id: &str,
+        folder: &str,
+        limit: i64,
+        before_uid: Option<u32>,
+    ) -> AnyhowResult<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let oldest = conn.query_row(
+            "SELECT MIN(cached_at) FROM (
+                 SELECT cached_at FROM emails
+                 WHERE account_id = ?1 AND folder = ?2 AND (?4 IS NULL OR uid < ?4)
+                 ORDER BY uid DESC LIMIT ?3
+             )",
+            params![account_id, folder, limit, before_uid],
+            |row| row.get(0),
+        )?;
+        Ok(oldest)
+    }
+
+    /// Delete cached emails (and their insights) last fetched before the
+    /// `older_than` timestamp. Returns the number of emails removed.
+    pub fn prune_cache(&self, older_than: i64) -> AnyhowResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM email_insights
+             WHERE email_id IN (SELECT id FROM emails WHERE cached_at < ?1)",
+            [older_than],
+        )?;
+        let removed = tx.execute("DELETE FROM emails WHERE cached_at < ?1", [older_than])?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    // ========== Incremental Sync ==========
+
+    pub fn get_folder_sync_state(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> AnyhowResult<Option<FolderSyncState>> {
+        let conn = self.conn.lock().unwrap();
+        let state = conn
+            .query_row(
+                "SELECT uid_validity, uid_next, highest_modseq FROM folder_sync_state
+                 WHERE account_id = ?1 AND folder = ?2",
+                params![account_id, folder],
+                |row| {
+                    Ok(FolderSyncState {
+                        uid_validity: row.get::<_, i64>(0)? as u32,
+                        uid_next: row.get::<_, i64>(1)? as u32,
+                        // SQLite integers are signed; MODSEQ values fit in 63 bits
+                        highest_modseq: row.get::<_, Option<i64>>(2)?.map(|m| m as u64),
+                    })
+                },
+            )
+            .optional()?;
+        Ok(state)
+    }
+
+    pub fn set_folder_sync_state(
+        &self,
+        account_id: &str,
+        folder: &str,
+        state: &FolderSyncState,
+    ) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO folder_sync_state
+             (account_id, folder, uid_validity, uid_next, highest_modseq, synced_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                account_id,
+                folder,
+                state.uid_validity as i64,
+                state.uid_next as i64,
+                state.highest_modseq.map(|m| m as i64),
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// What the folder's IDLE monitor saw last, if it has run before
+    pub fn get_idle_state(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> AnyhowResult<Option<IdleFolderState>> {
+        let conn = self.conn.lock().unwrap();
+        let state = conn
+            .query_row(
+                "SELECT uid_validity, uid_next, uids FROM idle_state
+                 WHERE account_id = ?1 AND folder = ?2",
+                params![account_id, folder],
+                |row| {
+                    Ok(IdleFolderState {
+                        uid_validity: row.get::<_, i64>(0)? as u32,
+                        uid_next: row.get::<_, i64>(1)? as u32,
+                        uids: parse_uid_set(&row.get::<_, String>(2)?),
+                    })
+                },
+            )
+            .optional()?;
+        Ok(state)
+    }
+
+    pub fn set_idle_state(
+        &self,
+        account_id: &str,
+        folder: &str,
+        state: &IdleFolderState,
+    ) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO idle_state
+             (account_id, folder, uid_validity, uid_next, uids, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                account_id,
+                folder,
+                state.uid_validity as i64,
+                state.uid_next as i64,
+                compact_uid_set(&state.uids),
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The UIDVALIDITY the folder's cached UIDs were recorded under, from
+    /// whichever of the last sync and the last IDLE run is newer
+    pub fn known_uid_validity(&self, account_id: &str, folder: &str) -> AnyhowResult<Option<u32>> {
+        let conn = self.conn.lock().unwrap();
+        let uid_validity = conn.query_row(
+            &format!("SELECT {}", known_uid_validity_sql("?1", "?2")),
+            params![account_id, folder],
+            |row| row.get::<_, Option<i64>>(0),
+        )?;
+        Ok(uid_validity.map(|v| v as u32))
+    }
+
+    /// Read/starred state of every cached email in a folder, keyed by UID
+    pub fn get_cached_flags(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> AnyhowResult<HashMap<u32, MessageFlags>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT uid, is_read, is_starred FROM emails WHERE account_id = ?1 AND folder = ?2",
+        )?;
+        let flags = stmt
+            .query_map(params![account_id, folder], |row| {
+                let uid = row.get::<_, i64>(0)? as u32;
+                Ok((
+                    uid,
+                    MessageFlags {
+                        uid,
+                        is_read: row.get::<_, i32>(1)? != 0,
+                        is_starred: row.get::<_, i32>(2)? != 0,
+                    },
+                ))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(flags)
+    }
+
+    /// Apply flags reported by the server to cached emails in one transaction
+    pub fn update_cached_flags(
+        &self,
+        account_id: &str,
+        folder: &str,
+        flags: &[MessageFlags],
+    ) -> AnyhowResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for message in flags {
+            tx.execute(
+                "UPDATE emails SET is_read = ?4, is_starred = ?5
+                 WHERE account_id = ?1 AND folder = ?2 AND uid = ?3",
+                params![
+                    account_id,
+                    folder,
+                    message.uid as i64,
+                    message.is_read as i32,
+                    message.is_starred as i32
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Mark cached emails of a folder read by UID
+    pub fn mark_cached_read(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> AnyhowResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut updated = 0;
+        for uid in uids {
+            updated += tx.execute(
+                "UPDATE emails SET is_read = 1
+                 WHERE account_id = ?1 AND folder = ?2 AND uid = ?3 AND is_read = 0",
+                params![account_id, folder, *uid as i64],
+            )?;
+        }
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    /// Drop cached emails of a folder (with their insights) by UID
+    pub fn remove_cached_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> AnyhowResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut removed = 0;
+        for uid in uids {
+            tx.execute(
+                "DELETE FROM email_insights WHERE email_id IN (
+                     SELECT id FROM emails WHERE account_id = ?1 AND folder = ?2 AND uid = ?3)",
+                params![account_id, folder, *uid as i64],
+            )?;
+            removed += tx.execute(
+                "DELETE FROM emails WHERE account_id = ?1 AND folder = ?2 AND uid = ?3",
+                params![account_id, folder, *uid as i64],
+            )?;
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Drop every cached email of a folder, e.g. after its UIDVALIDITY changed
+    pub fn clear_cached_folder(&self, account_id: &str, folder: &str) -> AnyhowResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM email_insights WHERE email_id IN (
+                 SELECT id FROM emails WHERE account_id = ?1 AND folder = ?2)",
+            params![account_id, folder],
+        )?;
+        let removed = tx.execute(
+            "DELETE FROM emails WHERE account_id = ?1 AND folder = ?2",
+            params![account_id, folder],
+        )?;
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    /// Forget everything stored for a folder that was renamed or deleted:
+    /// its cached emails, sync and IDLE state, and sort order
+    pub fn forget_folder(&self, account_id: &str, folder: &str) -> AnyhowResult<usize> {
+        let removed = self.clear_cached_folder(account_id, folder)?;
+        let conn = self.conn.lock().unwrap();
+        for table in ["folder_sync_state", "idle_state", "folder_sort"] {
+            conn.execute(
+                &format!(
+                    "DELETE FROM {} WHERE account_id = ?1 AND folder = ?2",
+                    table
+                ),
+                params![account_id, folder],
+            )?;
+        }
+        Ok(removed)
+    }
+
+    /// Mark cached emails as just confirmed against the server
+    pub fn touch_cached(&self, email_ids: &[String]) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("UPDATE emails SET cached_at = ?2 WHERE id = ?1")?;
+        let now = Utc::now().timestamp();
+        for email_id in email_ids {
+            stmt.execute(params![email_id, now])?;
+        }
+        Ok(())
+    }
+
+    // ========== Folder Sort Preferences ==========
+
+    /// Sort order the user picked for a folder, if any
+    pub fn get_folder_sort(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> AnyhowResult<Option<MessageSort>> {
+        let conn = self.conn.lock().unwrap();
+        let sort: Option<String> = conn
+            .query_row(
+                "SELECT sort FROM folder_sort WHERE account_id = ?1 AND folder = ?2",
+                params![account_id, folder],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(sort.as_deref().and_then(MessageSort::parse))
+    }
+
+    /// Remember the sort order for a folder
+    pub fn set_folder_sort(
+        &self,
+        account_id: &str,
+        folder: &str,
+        sort: MessageSort,
+    ) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO folder_sort (account_id, folder, sort, updated_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![account_id, folder, sort.as_str(), Utc::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    // ========== Category Cache ==========
+
+    /// Stored categories for the given text hashes; unknown hashes are absent
+    pub fn get_cached_categories(
+        &self,
+        text_hashes: &[String],
+    ) -> AnyhowResult<HashMap<String, String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT category FROM category_cache WHERE text_hash = ?1")?;
+
+        let mut categories = HashMap::new();
+        for hash in text_hashes {
+            let category: Option<String> = stmt
+                .query_row(params![hash], |row| row.get(0))
+                .optional()?;
+            if let Some(category) = category {
+                categories.insert(hash.clone(), category);
+            }
+        }
+        Ok(categories)
+    }
+
+    /// Forget every cached classification, e.g. after the category set changed
+    pub fn clear_category_cache(&self) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM category_cache", [])?;
+        Ok(())
+    }
+
+    // ========== Snooze ==========
+
+    /// Record a snooze, replacing any earlier one for the same message
+    pub fn store_snooze(&self, snooze: &Snooze) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO snoozes
+             (account_id, message_id, subject, folder, snoozed_until, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                snooze.account_id,
+                snooze.message_id,
+                snooze.subject,
+                snooze.folder,
+                snooze.until,
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether there was a snooze to remove
+    pub fn remove_snooze(&self, account_id: &str, message_id: &str) -> AnyhowResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM snoozes WHERE account_id = ?1 AND message_id = ?2",
+            params![account_id, message_id],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Snoozes due at or before `now`, across all accounts, oldest first
+    pub fn get_due_snoozes(&self, now: i64) -> AnyhowResult<Vec<Snooze>> {
+        self.query_snoozes(
+            "SELECT account_id, message_id, subject, folder, snoozed_until FROM snoozes
+             WHERE snoozed_until <= ?1 ORDER BY snoozed_until",
+            params![now],
+        )
+    }
+
+    /// An account's pending snoozes, soonest first
+    pub fn get_snoozes(&self, account_id: &str) -> AnyhowResult<Vec<Snooze>> {
+        self.query_snoozes(
+            "SELECT account_id, message_id, subject, folder, snoozed_until FROM snoozes
+             WHERE account_id = ?1 ORDER BY snoozed_until",
+            params![account_id],
+        )
+    }
+
+    fn query_snoozes(&self, sql: &str, params: impl rusqlite::Params) -> AnyhowResult<Vec<Snooze>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(sql)?;
+        let snoozes = stmt
+            .query_map(params, |row| {
+                Ok(Snooze {
+                    account_id: row.get(0)?,
+                    message_id: row.get(1)?,
+                    subject: row.get(2)?,
+                    folder: row.get(3)?,
+                    until: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(snoozes)
+    }
+
+    // ========== Rules ==========
+
+    /// Store a new rule after the existing ones and return its ID
+    pub fn store_rule(&self, rule: &Rule) -> AnyhowResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO rules
+             (account_id, name, conditions, actions, stop_processing, position, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, (SELECT COALESCE(MAX(position), 0) + 1 FROM rules), ?6)",
+            params![
+                rule.account_id,
+                rule.name.trim(),
+                serde_json::to_string(&rule.conditions)?,
+                serde_json::to_string(&rule.actions)?,
+                rule.stop_processing as i32,
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Rules in evaluation order. With an account, only the rules that apply
+    /// to it (its own and the ones for all accounts).
+    pub fn get_rules(&self, account_id: Option<&str>) -> AnyhowResult<Vec<Rule>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, account_id, name, conditions, actions, stop_processing FROM rules
+             WHERE ?1 IS NULL OR account_id IS NULL OR account_id = ?1
+             ORDER BY position",
+        )?;
+        let rows = stmt
+            .query_map(params![account_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, i32>(5)? != 0,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut rules = Vec::with_capacity(rows.len());
+        for (id, account_id, name, conditions, actions, stop_processing) in rows {
+            // A rule saved by a newer version may not parse; skip it rather
+            // than disabling every rule
+            match (
+                serde_json::from_str(&conditions),
+                serde_json::from_str(&actions),
+            ) {
+                (Ok(conditions), Ok(actions)) => rules.push(Rule {
+                    id,
+                    name,
+                    account_id,
+                    conditions,
+                    actions,
+                    stop_processing,
+                }),
+                _ => eprintln!("[Rules] Skipping unreadable rule #{} ({})", id, name),
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Returns whether the rule existed
+    pub fn delete_rule(&self, rule_id: i64) -> AnyhowResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute("DELETE FROM rules WHERE id = ?1", params![rule_id])?;
+        Ok(removed > 0)
+    }
+
+    /// Record a rule's category for a message, updating any insights already stored
+    pub fn set_rule_category(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        category: &str,
+    ) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO rule_categories (account_id, message_id, category)
+             VALUES (?1, ?2, ?3)",
+            params![account_id, message_id, category],
+        )?;
+        conn.execute(
+            "UPDATE email_insights SET category = ?3 WHERE email_id IN (
+                 SELECT id FROM emails WHERE account_id = ?1 AND message_id = ?2)",
+            params![account_id, message_id, category],
+        )?;
+        Ok(())
+    }
+
+    /// The category a rule gave a message, if any
+    pub fn get_rule_category(
+        &self,
+        account_id: &str,
+        message_id: &str,
+    ) -> AnyhowResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let category = conn
+            .query_row(
+                "SELECT category FROM rule_categories WHERE account_id = ?1 AND message_id = ?2",
+                params![account_id, message_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(category)
+    }
+
+    // ========== Send-as Aliases ==========
+
+    /// Allow an account to send as `address`. Returns false if it already could.
+    pub fn add_alias(&self, account_id: &str, address: &str) -> AnyhowResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO send_aliases (account_id, address, created_at)
+             VALUES (?1, ?2, ?3)",
+            params![
+                account_id,
+                address.trim().to_lowercase(),
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(added > 0)
+    }
+
+    /// An account's aliases in the order they were added
+    pub fn get_aliases(&self, account_id: &str) -> AnyhowResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT address FROM send_aliases WHERE account_id = ?1
+             ORDER BY created_at, rowid",
+        )?;
+        let aliases = stmt
+            .query_map(params![account_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(aliases)
+    }
+
+    pub fn is_alias(&self, account_id: &str, address: &str) -> AnyhowResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let allowed = conn.query_row(
+            "SELECT count(*) > 0 FROM send_aliases WHERE account_id = ?1 AND address = ?2",
+            params![account_id, address.trim().to_lowercase()],
+            |row| row.get(0),
+        )?;
+        Ok(allowed)
+    }
+
+    // ========== Remote Content ==========
+
+    /// Load remote images in mail from `sender` from now on
+    pub fn allow_remote_content(&self, sender: &str) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO remote_content_senders (sender, allowed_at) VALUES (?1, ?2)",
+            params![sender.trim().to_lowercase(), Utc::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    pub fn is_remote_content_allowed(&self, sender: &str) -> AnyhowResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let allowed = conn.query_row(
+            "SELECT count(*) > 0 FROM remote_content_senders WHERE sender = ?1",
+            params![sender.trim().to_lowercase()],
+            |row| row.get(0),
+        )?;
+        Ok(allowed)
+    }
+
+    /// Store (email_id, text_hash, category) results in one transaction.
+    /// Emails that already have insights get their category updated; the rest
+    /// pick it up from the cache when they are indexed.
+    pub fn store_categories(&self, results: &[(String, String, String)]) -> AnyhowResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = Utc::now().timestamp();
+
+        for (email_id, text_hash, category) in results {
+            tx.execute(
+                "INSERT OR REPLACE INTO category_cache (text_hash, category, created_at)
+                 VALUES (?1, ?2, ?3)",
+                params![text_hash, category, now],
+            )?;
+            tx.execute(
+                "UPDATE email_insights SET category = ?1 WHERE email_id = ?2",
+                params![category, email_id],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn account_from_row(row: &rusqlite::Row<'_>) -> Result<Account> {
+    Ok(Account {
+        id: row.get(0)?,
+        email: row.get(1)?,
+        display_name: row.get(2)?,
+        provider: row.get(3)?,
+        imap_host: row.get(4)?,
+        imap_port: row.get::<_, i32>(5)? as u16,
+        smtp_host: row.get(6)?,
+        smtp_port: row.get::<_, i32>(7)? as u16,
+        auth_type: row.get(8)?,
+        is_active: row.get::<_, i32>(9)? != 0,
+        created_at: row.get(10)?,
+        last_synced_at: row.get(11)?,
+        sync_paused: row.get::<_, i32>(12)? != 0,
+        block_manual_fetch: row.get::<_, i32>(13)? != 0,
+        tls_mode: TlsMode::from_str(&row.get::<_, String>(14)?).unwrap_or_default(),
+        smtp_tls_mode: row
+            .get::<_, Option<String>>(15)?
+            .and_then(|mode| TlsMode::from_str(&mode)),
+        signature: row.get(16)?,
+        html_signature: row.get(17)?,
+    })
+}
+
+/// FTS5 query matching any of the words in `query`. Each word is quoted so
+/// punctuation in order numbers or addresses isn't read as query syntax.
+fn fts_match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"", term))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" OR "))
+}
+
+/// FTS5 query requiring every word of `query`, with "double-quoted" parts
+/// matched as phrases. Words are re-quoted, so FTS operators (AND, NEAR,
+/// `*`, `:`) and punctuation in the input are plain text.
+fn fts_search_query(query: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    // Odd-numbered pieces were inside quotes; an unclosed quote runs to the end
+    for (i, piece) in query.split('"').enumerate() {
+        let words: Vec<&str> = piece
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        if i % 2 == 1 {
+            if !words.is_empty() {
+                terms.push(format!("\"{}\"", words.join(" ")));
+            }
+        } else {
+            terms.extend(words.iter().map(|word| format!("\"{}\"", word)));
+        }
+    }
+    (!terms.is_empty()).then(|| terms.join(" AND "))
+}
+
+/// Escape a snippet for HTML and turn the \x02/\x03 match markers from
+/// `snippet()` into `<mark>` tags
+fn highlight_snippet(snippet: &str) -> String {
+    snippet
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\u{2}', "<mark>")
+        .replace('\u{3}', "</mark>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::types::CACHE_STALE_AFTER_SECS;
+
+    fn email(id: &str, from_email: &str, date_timestamp: i64) -> Email {
+        Email {
+            id: id.to_string(),
+            thread_id: id.to_string(),
+            subject: String::new(),
+            from: String::new(),
+            from_email: from_email.to_string(),
+            to: Vec::new(),
+            cc: Vec::new(),
+            reply_to: Vec::new(),
+            date: String::new(),
+            date_timestamp,
+            snippet: String::new(),
+            body_html: None,
+            body_plain: None,
+            labels: Vec::new(),
+            is_read: false,
+            is_starred: false,
+            has_attachments: false,
+            account_id: "acct".to_string(),
+            uid: 0,
+            folder: "INBOX".to_string(),
+            message_id: String::new(),
+            is_first_contact: false,
+            mailing_list: None,
+            unsubscribe: None,
+            sync_state: SyncState::LiveFetched,
+            security: Default::default(),
+            size: 0,
+            attachments: Vec::new(),
+            inline_parts: Vec::new(),
+            remote_content_blocked: false,
+            in_reply_to: None,
+            references: Vec::new(),
+            invite: None,
+            authentication: None,
+        }
+    }
+
+    #[test]
+    fn test_contacts_are_backfilled_from_cached_mail() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        db.store_email(&email("acct:INBOX:1", "Old@Example.com", 100))
+            .unwrap();
+        db.store_email(&email("acct:INBOX:2", "old@example.com", 200))
+            .unwrap();
+        // A database from before senders were tracked
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute("DROP TABLE contacts", []).unwrap();
+            create_tables(&conn).unwrap();
+        }
+
+        let first = db
+            .record_senders(
+                "me@example.com",
+                &[
+                    email("acct:INBOX:3", "old@example.com", 300),
+                    email("acct:INBOX:4", "new@example.com", 400),
+                ],
+            )
+            .unwrap();
+        assert_eq!(first, HashSet::from(["acct:INBOX:4".to_string()]));
+    }
+
+    #[test]
+    fn test_record_senders_flags_earliest_message_per_sender() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+
+        // Newest first, as the server lists them; two from the same new sender
+        let batch = vec![
+            email("acct:INBOX:3", "New@Example.com", 300),
+            email("acct:INBOX:2", "new@example.com", 200),
+            email("acct:INBOX:1", "me@example.com", 100),
+        ];
+        let first = db.record_senders("me@example.com", &batch).unwrap();
+        assert_eq!(first, HashSet::from(["acct:INBOX:2".to_string()]));
+
+        // A later sync of a newer message from the same sender is not a first contact
+        let later = db
+            .record_senders("me@example.com", &[email("acct:INBOX:4", "new@example.com", 400)])
+            .unwrap();
+        assert!(later.is_empty());
+        assert!(db.is_first_contact("acct:INBOX:2").unwrap());
+    }
+
+    #[test]
+    fn test_cached_sync_state_reflects_stored_body_and_age() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+
+        let mut full = email("acct:INBOX:1", "a@example.com", 100);
+        full.body_plain = Some("Hello".to_string());
+        db.store_email(&full).unwrap();
+        db.store_email(&email("acct:INBOX:2", "b@example.com", 200)).unwrap();
+        db.store_email(&email("acct:INBOX:3", "c@example.com", 300)).unwrap();
+        db.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE emails SET updated_at = updated_at - ?1 WHERE id = 'acct:INBOX:3'",
+                [CACHE_STALE_AFTER_SECS + 1],
+            )
+            .unwrap();
+
+        let states: HashMap<String, SyncState> = db
+            .get_cached_emails("acct", "INBOX", 10, MessageSort::Date, None)
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.id, item.sync_state))
+            .collect();
+        assert_eq!(states["acct:INBOX:1"], SyncState::CachedFull);
+        assert_eq!(states["acct:INBOX:2"], SyncState::CachedHeadersOnly);
+        assert_eq!(states["acct:INBOX:3"], SyncState::Stale);
+
+        let cached = db.get_email_by_id("acct:INBOX:1").unwrap().unwrap();
+        assert_eq!(cached.sync_state, SyncState::CachedFull);
+    }
+
+    #[test]
+    fn test_cache_age_and_prune() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        assert_eq!(db.oldest_cached_at("acct", "INBOX", 10, None).unwrap(), None);
+
+        for uid in 1..=3 {
+            let mut cached = email(&format!("acct:INBOX:{}", uid), "a@example.com", 100);
+            cached.uid = uid;
+            db.store_email(&cached).unwrap();
+        }
+        db.conn
+            .lock()
+            .unwrap()
+            .execute("UPDATE emails SET cached_at = 1000 WHERE uid = 1", [])
+            .unwrap();
+
+        // The newest two were just fetched; the page reaching UID 1 is old
+        let now = Utc::now().timestamp();
+        assert!(db.oldest_cached_at("acct", "INBOX", 2, None).unwrap().unwrap() >= now - 5);
+        assert_eq!(db.oldest_cached_at("acct", "INBOX", 10, None).unwrap(), Some(1000));
+        assert_eq!(db.oldest_cached_at("acct", "INBOX", 10, Some(2)).unwrap(), Some(1000));
+
+        assert_eq!(db.prune_cache(now - 60).unwrap(), 1);
+        assert!(db.get_email_by_id("acct:INBOX:1").unwrap().is_none());
+        assert_eq!(db.get_email_count().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_category_counts_are_per_account() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let categorized = |id: &str, account_id: &str, category: &str| {
+            db.store_email(&Email {
+                account_id: account_id.to_string(),
+                ..email(id, "a@example.com", 100)
+            })
+            .unwrap();
+            db.store_insights(&EmailInsight {
+                email_id: id.to_string(),
+                summary: None,
+                priority: "MEDIUM".to_string(),
+                priority_score: 0.5,
+                category: Some(category.to_string()),
+                insights: None,
+                action_items: None,
+                has_deadline: false,
+                has_meeting: false,
+                has_financial: false,
+                sentiment: None,
+                indexed_at: 0,
+            })
+            .unwrap();
+        };
+        categorized("work:INBOX:1", "work", "promotions");
+        categorized("work:INBOX:2", "work", "promotions");
+        categorized("home:INBOX:1", "home", "promotions");
+        categorized("home:INBOX:2", "home", "social");
+
+        let counts = |account_id| {
+            let mut counts = db.get_category_counts(account_id, "INBOX").unwrap();
+            counts.sort();
+            counts
+        };
+        assert_eq!(counts("work"), [(Some("promotions".to_string()), 2, 2)]);
+        assert_eq!(
+            counts("home"),
+            [
+                (Some("promotions".to_string()), 1, 1),
+                (Some("social".to_string()), 1, 1)
+            ]
+        );
+        assert!(counts("other").is_empty());
+    }
+
+    #[test]
+    fn test_store_categories_in_bulk() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        db.store_email(&email("acct:INBOX:1", "a@example.com", 100)).unwrap();
+        db.store_email(&email("acct:INBOX:2", "b@example.com", 200)).unwrap();
+        db.store_insights(&EmailInsight {
+            email_id: "acct:INBOX:1".to_string(),
+            summary: None,
+            priority: "MEDIUM".to_string(),
+            priority_score: 0.5,
+            category: Some("general".to_string()),
+            insights: None,
+            action_items: None,
+            has_deadline: false,
+            has_meeting: false,
+            has_financial: false,
+            sentiment: None,
+            indexed_at: 0,
+        })
+        .unwrap();
+
+        db.store_categories(&[
+            ("acct:INBOX:1".to_string(), "h1".to_string(), "promotions".to_string()),
+            ("acct:INBOX:2".to_string(), "h2".to_string(), "newsletters".to_string()),
+        ])
+        .unwrap();
+
+        let cached = db
+            .get_cached_categories(&["h1".to_string(), "h2".to_string(), "h3".to_string()])
+            .unwrap();
+        assert_eq!(cached.len(), 2);
+        assert_eq!(cached["h2"], "newsletters");
+
+        // Existing insights are updated, but no insight row is created, so the
+        // second email still gets indexed
+        let insight = db.get_insight("acct:INBOX:1").unwrap().unwrap();
+        assert_eq!(insight.category.as_deref(), Some("promotions"));
+        assert!(db.get_insight("acct:INBOX:2").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_cached_pages_follow_uid_cursor() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        for uid in [3, 5, 7] {
+            let mut cached = email(&format!("acct:INBOX:{}", uid), "a@example.com", 100);
+            cached.uid = uid;
+            db.store_email(&cached).unwrap();
+        }
+        let page = |before_uid| {
+            db.get_cached_emails("acct", "INBOX", 2, MessageSort::Date, before_uid)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(page(None), ["acct:INBOX:7", "acct:INBOX:5"]);
+        assert_eq!(page(Some(5)), ["acct:INBOX:3"]);
+        assert!(page(Some(3)).is_empty());
+    }
+
+    #[test]
+    fn test_keyword_search_tracks_stored_and_removed_emails() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let mut order = email("acct:INBOX:1", "shop@example.com", 100);
+        order.subject = "Your order #ORD-48213 has shipped".to_string();
+        let mut other = email("acct:INBOX:2", "a@example.com", 200);
+        other.subject = "Lunch".to_string();
+        other.body_plain = Some("Re: order ORD-48213? No idea".to_string());
+        db.store_email(&order).unwrap();
+        db.store_email(&other).unwrap();
+
+        // Punctuation isn't query syntax; the subject match ranks first
+        let ids = |query| {
+            db.keyword_search(query, 10)
+                .unwrap()
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("#ORD-48213"), ["acct:INBOX:1", "acct:INBOX:2"]);
+
+        // Re-storing replaces the indexed text
+        order.subject = "Delivered".to_string();
+        db.store_email(&order).unwrap();
+        assert_eq!(ids("ORD-48213"), ["acct:INBOX:2"]);
+
+        db.clear_cached_folder("acct", "INBOX").unwrap();
+        assert!(ids("ORD-48213").is_empty());
+        assert!(ids("  ").is_empty());
+    }
+
+    #[test]
+    fn test_search_cached_phrases_senders_and_highlights() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let mut invoice = email("acct:INBOX:1", "billing@acme.com", 100);
+        invoice.subject = "Invoice".to_string();
+        invoice.from = "Acme Billing".to_string();
+        invoice.body_plain = Some("Your <b>quarterly report</b> is attached".to_string());
+        let mut memo = email("acct:Archive:2", "ana@example.com", 200);
+        memo.subject = "Notes".to_string();
+        memo.folder = "Archive".to_string();
+        memo.body_plain = Some("The report for this quarterly review".to_string());
+        db.store_email(&invoice).unwrap();
+        db.store_email(&memo).unwrap();
+
+        let ids = |query, folder| {
+            db.search_cached(query, folder, 10)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("report quarterly", None).len(), 2);
+        assert_eq!(ids("\"quarterly report\"", None), ["acct:INBOX:1"]);
+        assert_eq!(ids("report", Some("Archive")), ["acct:Archive:2"]);
+        // Sender name and address are searchable
+        assert_eq!(ids("acme billing", None), ["acct:INBOX:1"]);
+        // FTS syntax in the input is matched as text, not parsed
+        assert_eq!(ids("report NEAR(\"x\" AND", None), Vec::<String>::new());
+        assert!(ids("\"  *", None).is_empty());
+
+        let results = db.search_cached("\"quarterly report\"", None, 10).unwrap();
+        assert_eq!(
+            results[0].snippet,
+            "Your &lt;b&gt;<mark>quarterly report</mark>&lt;/b&gt; is attached"
+        );
+    }
+
+    #[test]
+    fn test_header_resync_keeps_cached_email_searchable() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let mut full = email("acct:INBOX:1", "billing@acme.com", 100);
+        full.subject = "Invoice".to_string();
+        full.body_plain = Some("Your quarterly report is attached".to_string());
+        db.store_email(&full).unwrap();
+
+        let mut headers = full.clone();
+        headers.body_plain = None;
+        headers.is_read = true;
+        db.store_email_headers(&headers).unwrap();
+        db.store_email_headers(&headers).unwrap();
+
+        let ids = |query| {
+            db.search_cached(query, None, 10)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("quarterly"), ["acct:INBOX:1"]);
+        assert_eq!(ids("invoice"), ["acct:INBOX:1"]);
+
+        // A full re-fetch replaces the entry instead of duplicating it
+        full.body_plain = Some("Updated totals".to_string());
+        db.store_email(&full).unwrap();
+        assert!(ids("quarterly").is_empty());
+        assert_eq!(ids("totals"), ["acct:INBOX:1"]);
+    }
+
+    #[test]
+    fn test_due_snoozes() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let snooze = |message_id: &str, until: i64| Snooze {
+            account_id: "acct".to_string(),
+            message_id: message_id.to_string(),
+            subject: String::new(),
+            folder: "INBOX".to_string(),
+            until,
+        };
+        db.store_snooze(&snooze("later@x", 500)).unwrap();
+        db.store_snooze(&snooze("soon@x", 100)).unwrap();
+        // Snoozing again moves the due time
+        db.store_snooze(&snooze("soon@x", 200)).unwrap();
+
+        let due = |now| -> Vec<String> {
+            db.get_due_snoozes(now)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.message_id)
+                .collect()
+        };
+        assert!(due(150).is_empty());
+        assert_eq!(due(200), ["soon@x"]);
+        assert_eq!(due(1000), ["soon@x", "later@x"]);
+
+        assert!(db.remove_snooze("acct", "soon@x").unwrap());
+        assert!(!db.remove_snooze("acct", "soon@x").unwrap());
+        assert_eq!(db.get_snoozes("acct").unwrap(), [snooze("later@x", 500)]);
+    }
+
+    #[test]
+    fn test_remote_content_allowlist_ignores_case() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        assert!(!db.is_remote_content_allowed("news@shop.example").unwrap());
+
+        db.allow_remote_content(" News@Shop.example").unwrap();
+        db.allow_remote_content("news@shop.example").unwrap();
+        assert!(db.is_remote_content_allowed("NEWS@shop.example").unwrap());
+        assert!(!db.is_remote_content_allowed("other@shop.example").unwrap());
+    }
+
+    #[test]
+    fn test_merge_accounts_moves_cache_and_settings() {
+        use crate::email::server_presets::{AuthType, ProviderType, ServerConfig};
+
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let account = |id: &str, address: &str| Account {
+            id: id.to_string(),
+            ..Account::new(
+                address.to_string(),
+                "Ana".to_string(),
+                ProviderType::Custom,
+                ServerConfig {
+                    imap_host: "imap.example.com".to_string(),
+                    imap_port: 993,
+                    smtp_host: "smtp.example.com".to_string(),
+                    smtp_port: 465,
+                    tls_mode: TlsMode::Implicit,
+                    smtp_tls_mode: None,
+                },
+                AuthType::Password,
+            )
+        };
+        db.store_account(&account("keep", "ana@example.com"))
+            .unwrap();
+        db.store_account(&account("dup", "Ana@Example.com"))
+            .unwrap();
+        db.set_active_account("dup").unwrap();
+        db.set_account_signature("dup", Some("-- Ana"), None)
+            .unwrap();
+
+        let cached = |id: &str, account_id: &str| Email {
+            account_id: account_id.to_string(),
+            ..email(id, "bob@example.com", 100)
+        };
+        db.store_email(&cached("keep:INBOX:1", "keep")).unwrap();
+        db.store_email(&cached("dup:INBOX:1", "dup")).unwrap();
+        db.store_email(&cached("dup:INBOX:2", "dup")).unwrap();
+        db.set_folder_sort("keep", "INBOX", MessageSort::Sender)
+            .unwrap();
+        db.set_folder_sort("dup", "INBOX", MessageSort::Subject)
+            .unwrap();
+        db.set_folder_sort("dup", "Archive", MessageSort::Size)
+            .unwrap();
+        db.add_alias("dup", "sales@example.com").unwrap();
+        db.store_rule(&Rule {
+            id: 0,
+            name: "files".to_string(),
+            account_id: Some("dup".to_string()),
+            conditions: Vec::new(),
+            actions: Vec::new(),
+            stop_processing: true,
+        })
+        .unwrap();
+
+        let merged = db.merge_accounts("keep", "dup").unwrap();
+        assert_eq!(
+            merged.renamed,
+            [("dup:INBOX:2".to_string(), "keep:INBOX:2".to_string())]
+        );
+        assert_eq!(merged.dropped, ["dup:INBOX:1"]);
+        assert!(db.get_email_by_id("keep:INBOX:2").unwrap().is_some());
+        assert!(db.get_email_by_id("dup:INBOX:1").unwrap().is_none());
+
+        // The duplicate was active, so the kept account takes over
+        assert!(db.get_account("dup").unwrap().is_none());
+        let keep = db.get_active_account().unwrap().unwrap();
+        assert_eq!(keep.id, "keep");
+        assert_eq!(keep.signature.as_deref(), Some("-- Ana"));
+
+        let sort = |folder| db.get_folder_sort("keep", folder).unwrap();
+        assert_eq!(sort("INBOX"), Some(MessageSort::Sender));
+        assert_eq!(sort("Archive"), Some(MessageSort::Size));
+        assert_eq!(db.get_aliases("keep").unwrap(), ["sales@example.com"]);
+        assert_eq!(
+            db.get_rules(Some("keep")).unwrap()[0].account_id.as_deref(),
+            Some("keep")
+        );
+        assert!(db.get_folder_sort("dup", "Archive").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_send_aliases_are_per_account() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        assert!(db.add_alias("acct", " Sales@Example.com").unwrap());
+        assert!(!db.add_alias("acct", "sales@example.com").unwrap());
+        assert!(db.add_alias("acct", "support@example.com").unwrap());
+
+        assert_eq!(
+            db.get_aliases("acct").unwrap(),
+            ["sales@example.com", "support@example.com"]
+        );
+        assert!(db.is_alias("acct", "SALES@example.com").unwrap());
+        assert!(!db.is_alias("other", "sales@example.com").unwrap());
+
+        db.remove_account("acct").unwrap();
+        assert!(db.get_aliases("acct").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_idle_state_round_trips_uids() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        assert_eq!(db.get_idle_state("acct", "INBOX").unwrap(), None);
+
+        let mut state = IdleFolderState {
+            uid_validity: 7,
+            uid_next: 31,
+            uids: vec![3, 4, 5, 9, 20, 21, 30],
+        };
+        db.set_idle_state("acct", "INBOX", &state).unwrap();
+        let stored = db.get_idle_state("acct", "INBOX").unwrap();
+        assert_eq!(stored.as_ref(), Some(&state));
+
+        state.apply(&crate::email::types::FolderChanges {
+            new_uids: vec![31, 32],
+            expunged_uids: vec![4, 20],
+        });
+        assert_eq!(state.uids, [3, 5, 9, 21, 30, 31, 32]);
+        assert_eq!(state.uid_next, 33);
+
+        db.remove_account("acct").unwrap();
+        assert_eq!(db.get_idle_state("acct", "INBOX").unwrap(), None);
+    }
+
+    #[test]
+    fn test_known_uid_validity_prefers_the_newest_record() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        assert_eq!(db.known_uid_validity("acct", "INBOX").unwrap(), None);
+
+        let state = FolderSyncState {
+            uid_validity: 7,
+            uid_next: 10,
+            highest_modseq: None,
+        };
+        db.set_folder_sync_state("acct", "INBOX", &state).unwrap();
+        assert_eq!(db.known_uid_validity("acct", "INBOX").unwrap(), Some(7));
+
+        let idle = IdleFolderState {
+            uid_validity: 8,
+            uid_next: 3,
+            uids: vec![1, 2],
+        };
+        db.set_idle_state("acct", "INBOX", &idle).unwrap();
+        db.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE folder_sync_state SET synced_at = synced_at - 60",
+                [],
+            )
+            .unwrap();
+        assert_eq!(db.known_uid_validity("acct", "INBOX").unwrap(), Some(8));
+        assert_eq!(db.known_uid_validity("other", "INBOX").unwrap(), None);
+    }
+
+    #[test]
+    fn test_cached_emails_are_keyed_by_uid_validity() {
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let mut state = FolderSyncState {
+            uid_validity: 7,
+            uid_next: 10,
+            highest_modseq: None,
+        };
+        db.set_folder_sync_state("acct", "INBOX", &state).unwrap();
+        let mut cached = email("acct:INBOX:3", "a@example.com", 1);
+        cached.uid = 3;
+        cached.body_plain = Some("old body".to_string());
+        db.store_email(&cached).unwrap();
+        assert!(db.get_email_by_id("acct:INBOX:3").unwrap().is_some());
+
+        // Renumbered: UID 3 is another message now, whatever is cached for it
+        state.uid_validity = 8;
+        db.set_folder_sync_state("acct", "INBOX", &state).unwrap();
+        assert!(db.get_email_by_id("acct:INBOX:3").unwrap().is_none());
+        let listed = db
+            .get_cached_emails("acct", "INBOX", 50, MessageSort::Date, None)
+            .unwrap();
+        assert!(listed.is_empty());
+
+        // Caching the new message's headers doesn't inherit the old body
+        let mut headers = email("acct:INBOX:3", "b@example.com", 2);
+        headers.uid = 3;
+        db.store_email_headers(&headers).unwrap();
+        let stored = db.get_email_by_id("acct:INBOX:3").unwrap().unwrap();
+        assert_eq!(stored.from_email, "b@example.com");
+        assert_eq!(stored.body_plain, None);
+    }
+
+    #[test]
+    fn test_rules_keep_order_and_account_scope() {
+        use crate::email::rules::{RuleAction, RuleCondition};
+
+        let db = EmailDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let rule = |name: &str, account_id: Option<&str>| Rule {
+            id: 0,
+            name: name.to_string(),
+            account_id: account_id.map(str::to_string),
+            conditions: vec![RuleCondition::HasAttachment],
+            actions: vec![RuleAction::Categorize {
+                category: "Files".to_string(),
+            }],
+            stop_processing: true,
+        };
+        let first = db.store_rule(&rule("all", None)).unwrap();
+        db.store_rule(&rule("other", Some("other"))).unwrap();
+        db.store_rule(&rule("mine", Some("acct"))).unwrap();
+
+        let names = |rules: Vec<Rule>| rules.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        assert_eq!(names(db.get_rules(Some("acct")).unwrap()), ["all", "mine"]);
+        assert_eq!(names(db.get_rules(None).unwrap()), ["all", "other", "mine"]);
+        assert_eq!(
+            db.get_rules(None).unwrap()[0],
+            Rule {
+                id: first,
+                ..rule("all", None)
+            }
+        );
+
+        assert!(db.delete_rule(first).unwrap());
+        assert!(!db.delete_rule(first).unwrap());
+        assert_eq!(names(db.get_rules(Some("acct")).unwrap()), ["mine"]);
+
+        db.set_rule_category("acct", "m@x", "Files").unwrap();
+        assert_eq!(
+            db.get_rule_category("acct", "m@x").unwrap().as_deref(),
+            Some("Files")
+        );
+        assert_eq!(db.get_rule_category("other", "m@x").unwrap(), None);
+    }
 }
@@ -1,6 +1,7 @@
 pub mod email_db;
 pub mod schema;
 pub mod vector_db;
+pub mod vector_index;
 
 pub use email_db::EmailDatabase;
 pub use vector_db::VectorDatabase;
@@ -1,4 +1,6 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{params, Connection, Result};
+
+use crate::auth::account::normalize_mailbox_address;
 
 pub fn create_tables(conn: &Connection) -> Result<()> {
     // Check if we need to migrate the date column from TEXT to INTEGER
@@ -18,7 +20,13 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             auth_type TEXT NOT NULL,
             is_active INTEGER NOT NULL DEFAULT 1,
             created_at INTEGER NOT NULL,
-            last_synced_at INTEGER
+            last_synced_at INTEGER,
+            sync_paused INTEGER NOT NULL DEFAULT 0,
+            block_manual_fetch INTEGER NOT NULL DEFAULT 0,
+            tls_mode TEXT NOT NULL DEFAULT 'implicit',
+            smtp_tls_mode TEXT,
+            signature TEXT,
+            html_signature TEXT
         )",
         [],
     )?;
@@ -45,7 +53,20 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             account_id TEXT NOT NULL DEFAULT 'legacy',
             uid INTEGER NOT NULL DEFAULT 0,
             folder TEXT NOT NULL DEFAULT 'INBOX',
-            message_id TEXT NOT NULL DEFAULT ''
+            message_id TEXT NOT NULL DEFAULT '',
+            list_id TEXT,
+            list_meta TEXT,
+            security TEXT,
+            size INTEGER NOT NULL DEFAULT 0,
+            attachments TEXT,
+            in_reply_to TEXT,
+            reference_ids TEXT,
+            cached_at INTEGER NOT NULL DEFAULT 0,
+            cc_emails TEXT,
+            reply_to TEXT,
+            invite TEXT,
+            authentication TEXT,
+            uid_validity INTEGER NOT NULL DEFAULT 0
         )",
         [],
     )?;
@@ -110,6 +131,142 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Contacts table - senders seen per account, used to flag first-time senders
+    let contacts_existed: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type='table' AND name='contacts'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS contacts (
+            account_id TEXT NOT NULL,
+            email TEXT NOT NULL,
+            display_name TEXT NOT NULL DEFAULT '',
+            first_email_id TEXT NOT NULL,
+            first_seen_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            PRIMARY KEY (account_id, email)
+        )",
+        [],
+    )?;
+
+    // Message list sort order chosen per folder
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS folder_sort (
+            account_id TEXT NOT NULL,
+            folder TEXT NOT NULL,
+            sort TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (account_id, folder)
+        )",
+        [],
+    )?;
+
+    // Where incremental sync of each folder left off (UIDVALIDITY, UIDNEXT and
+    // the CONDSTORE HIGHESTMODSEQ, if the server has it)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS folder_sync_state (
+            account_id TEXT NOT NULL,
+            folder TEXT NOT NULL,
+            uid_validity INTEGER NOT NULL,
+            uid_next INTEGER NOT NULL,
+            highest_modseq INTEGER,
+            synced_at INTEGER NOT NULL,
+            PRIMARY KEY (account_id, folder)
+        )",
+        [],
+    )?;
+
+    // What each folder's IDLE monitor last saw (UIDs as a compact UID set),
+    // so mail arriving or expunged while it reconnects isn't missed
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS idle_state (
+            account_id TEXT NOT NULL,
+            folder TEXT NOT NULL,
+            uid_validity INTEGER NOT NULL,
+            uid_next INTEGER NOT NULL,
+            uids TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (account_id, folder)
+        )",
+        [],
+    )?;
+
+    // Embedding-based categories keyed by the hash of the classified text,
+    // so a batch classification is never repeated for unchanged content
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS category_cache (
+            text_hash TEXT PRIMARY KEY,
+            category TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Messages waiting in the Snoozed folder, matched by Message-ID when due
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS snoozes (
+            account_id TEXT NOT NULL,
+            message_id TEXT NOT NULL,
+            subject TEXT NOT NULL DEFAULT '',
+            folder TEXT NOT NULL,
+            snoozed_until INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (account_id, message_id)
+        )",
+        [],
+    )?;
+
+    // Senders whose mail may load remote images; addresses are lowercased
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS remote_content_senders (
+            sender TEXT PRIMARY KEY,
+            allowed_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Filter rules run on new inbox mail, in position order. Conditions and
+    // actions are JSON arrays of RuleCondition/RuleAction.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id TEXT,
+            name TEXT NOT NULL,
+            conditions TEXT NOT NULL,
+            actions TEXT NOT NULL,
+            stop_processing INTEGER NOT NULL DEFAULT 1,
+            position INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Categories set by rules, keyed by Message-ID so they survive a rule
+    // moving the message; indexing uses them instead of classifying
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rule_categories (
+            account_id TEXT NOT NULL,
+            message_id TEXT NOT NULL,
+            category TEXT NOT NULL,
+            PRIMARY KEY (account_id, message_id)
+        )",
+        [],
+    )?;
+
+    // Extra addresses an account may send as; addresses are lowercased
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS send_aliases (
+            account_id TEXT NOT NULL,
+            address TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (account_id, address)
+        )",
+        [],
+    )?;
+
     // Initialize indexing status if not exists
     conn.execute("INSERT OR IGNORE INTO indexing_status (id) VALUES (1)", [])?;
 
@@ -118,6 +275,23 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
 
     // Run IMAP migration to add new columns to existing tables
     migrate_add_imap_columns(conn)?;
+    migrate_add_account_sync_columns(conn)?;
+    migrate_add_tls_mode_columns(conn)?;
+    migrate_add_signature_columns(conn)?;
+    migrate_add_mailing_list_columns(conn)?;
+    migrate_add_security_column(conn)?;
+    migrate_add_size_column(conn)?;
+    migrate_add_attachments_column(conn)?;
+    migrate_add_threading_columns(conn)?;
+    migrate_add_cached_at_column(conn)?;
+    migrate_add_reply_recipient_columns(conn)?;
+    migrate_add_invite_column(conn)?;
+    migrate_add_authentication_column(conn)?;
+    migrate_add_uid_validity_column(conn)?;
+    if !contacts_existed {
+        backfill_contacts(conn)?;
+    }
+    create_fts_index(conn)?;
 
     // Create indexes for performance
     conn.execute(
@@ -130,6 +304,11 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_contacts_first_email ON contacts(first_email_id)",
+        [],
+    )?;
+
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_insights_priority ON email_insights(priority_score DESC)",
         [],
@@ -155,23 +334,40 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_emails_cached_at ON emails(cached_at)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_snoozes_until ON snoozes(snoozed_until)",
+        [],
+    )?;
+
     Ok(())
 }
 
 /// Create only vector/embedding-related tables (for use by VectorDatabase).
 /// This avoids creating an empty `emails` table in the vector DB file.
 pub fn create_vector_tables(conn: &Connection) -> Result<()> {
-    // Email embeddings table - stores vector embeddings for RAG
+    migrate_add_embedding_chunk_index(conn)?;
+
+    // Email embeddings table - stores vector embeddings for RAG.
+    // One row per email, or one per chunk when long emails are chunked.
     conn.execute(
         "CREATE TABLE IF NOT EXISTS email_embeddings (
-            email_id TEXT PRIMARY KEY,
+            email_id TEXT NOT NULL,
+            chunk_index INTEGER NOT NULL DEFAULT 0,
             embedding BLOB NOT NULL,
             embedding_model TEXT NOT NULL,
             text_hash TEXT NOT NULL,
-            created_at INTEGER NOT NULL
+            created_at INTEGER NOT NULL,
+            quantization_scale REAL,
+            PRIMARY KEY (email_id, chunk_index)
         )",
         [],
     )?;
+    migrate_add_embedding_quantization_scale(conn)?;
 
     // Embedding status table - track embedding progress
     conn.execute(
@@ -190,6 +386,31 @@ pub fn create_vector_tables(conn: &Connection) -> Result<()> {
     // Initialize embedding status if not exists
     conn.execute("INSERT OR IGNORE INTO embedding_status (id) VALUES (1)", [])?;
 
+    // Thread embeddings - aggregate (mean) of member email embeddings, keyed by thread root
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS thread_embeddings (
+            thread_id TEXT PRIMARY KEY,
+            embedding BLOB NOT NULL,
+            embedding_model TEXT NOT NULL,
+            member_count INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Classification categories and their reference embeddings, in display order.
+    // Seeded with the built-in categories the first time classification runs.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS categories (
+            name TEXT PRIMARY KEY,
+            description TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            embedding BLOB,
+            embedding_model TEXT
+        )",
+        [],
+    )?;
+
     // Create index for performance
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_embeddings_model ON email_embeddings(embedding_model)",
@@ -199,6 +420,112 @@ pub fn create_vector_tables(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+/// Full-text (FTS5) index over email subjects, plain bodies and senders for
+/// keyword search. It reads its content from `emails` by rowid and is kept in
+/// sync by triggers; whenever it is (re)created it is filled from existing rows.
+fn create_fts_index(conn: &Connection) -> Result<()> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type='table' AND name='emails_fts'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+    let has_sender: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails_fts') WHERE name = 'from_name'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if exists && !has_sender {
+        // Indexes from before sender search; recreated below with the new columns
+        conn.execute_batch(
+            "DROP TRIGGER IF EXISTS emails_fts_insert;
+             DROP TRIGGER IF EXISTS emails_fts_delete;
+             DROP TRIGGER IF EXISTS emails_fts_update;
+             DROP TABLE emails_fts;",
+        )?;
+    }
+
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS emails_fts USING fts5(
+            subject, body_plain, from_name, from_email, content='emails', content_rowid='rowid'
+         );
+         CREATE TRIGGER IF NOT EXISTS emails_fts_insert AFTER INSERT ON emails BEGIN
+            INSERT INTO emails_fts(rowid, subject, body_plain, from_name, from_email)
+            VALUES (new.rowid, new.subject, new.body_plain, new.from_name, new.from_email);
+         END;
+         CREATE TRIGGER IF NOT EXISTS emails_fts_delete AFTER DELETE ON emails BEGIN
+            INSERT INTO emails_fts(emails_fts, rowid, subject, body_plain, from_name, from_email)
+            VALUES ('delete', old.rowid, old.subject, old.body_plain, old.from_name, old.from_email);
+         END;
+         CREATE TRIGGER IF NOT EXISTS emails_fts_update
+         AFTER UPDATE OF subject, body_plain, from_name, from_email ON emails BEGIN
+            INSERT INTO emails_fts(emails_fts, rowid, subject, body_plain, from_name, from_email)
+            VALUES ('delete', old.rowid, old.subject, old.body_plain, old.from_name, old.from_email);
+            INSERT INTO emails_fts(rowid, subject, body_plain, from_name, from_email)
+            VALUES (new.rowid, new.subject, new.body_plain, new.from_name, new.from_email);
+         END;
+         -- Also fired by upserts that keep the row; EmailDatabase::store_email
+         -- clears the replaced entry itself
+         DROP TRIGGER IF EXISTS emails_fts_replace;",
+    )?;
+
+    if !has_sender {
+        conn.execute("INSERT INTO emails_fts(emails_fts) VALUES ('rebuild')", [])?;
+    }
+
+    Ok(())
+}
+
+/// Fill a newly created contacts table from the mail already cached, so
+/// senders known before the upgrade aren't flagged as first contacts
+fn backfill_contacts(conn: &Connection) -> Result<()> {
+    let cached = {
+        let mut stmt = conn.prepare(
+            "SELECT e.account_id, e.from_email, e.from_name, e.id, e.date, COALESCE(a.email, '')
+             FROM emails e LEFT JOIN accounts a ON a.id = e.account_id
+             ORDER BY e.date ASC",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        rows
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    for (account_id, from_email, from_name, email_id, date, own_address) in cached {
+        let address = normalize_mailbox_address(&from_email);
+        if address.is_empty() || address == normalize_mailbox_address(&own_address) {
+            continue;
+        }
+        // Oldest first, so the first message inserted stays the first contact
+        tx.execute(
+            "INSERT INTO contacts
+             (account_id, email, display_name, first_email_id, first_seen_at, last_seen_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+             ON CONFLICT(account_id, email) DO UPDATE SET
+                last_seen_at = excluded.last_seen_at,
+                display_name = CASE WHEN excluded.display_name != ''
+                                    THEN excluded.display_name
+                                    ELSE contacts.display_name END",
+            params![account_id, address, from_name, email_id, date],
+        )?;
+    }
+    tx.commit()
+}
+
 /// Add IMAP-specific columns to existing tables if they don't exist yet
 fn migrate_add_imap_columns(conn: &Connection) -> Result<()> {
     // Check if account_id column exists on emails table
@@ -243,6 +570,328 @@ fn migrate_add_imap_columns(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+/// Add the sync pause columns to an existing accounts table
+fn migrate_add_account_sync_columns(conn: &Connection) -> Result<()> {
+    let has_sync_paused: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('accounts') WHERE name = 'sync_paused'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_sync_paused {
+        conn.execute(
+            "ALTER TABLE accounts ADD COLUMN sync_paused INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+        conn.execute(
+            "ALTER TABLE accounts ADD COLUMN block_manual_fetch INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Add TLS mode columns to the accounts table; existing accounts used implicit TLS
+/// for IMAP and the port-based choice for SMTP (NULL)
+fn migrate_add_tls_mode_columns(conn: &Connection) -> Result<()> {
+    let has_tls_mode: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('accounts') WHERE name = 'tls_mode'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_tls_mode {
+        conn.execute(
+            "ALTER TABLE accounts ADD COLUMN tls_mode TEXT NOT NULL DEFAULT 'implicit'",
+            [],
+        )?;
+        conn.execute("ALTER TABLE accounts ADD COLUMN smtp_tls_mode TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add the per-account signature columns
+fn migrate_add_signature_columns(conn: &Connection) -> Result<()> {
+    let has_signature: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('accounts') WHERE name = 'signature'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_signature {
+        conn.execute("ALTER TABLE accounts ADD COLUMN signature TEXT", [])?;
+        conn.execute("ALTER TABLE accounts ADD COLUMN html_signature TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add mailing-list columns to the emails table if they don't exist yet
+fn migrate_add_mailing_list_columns(conn: &Connection) -> Result<()> {
+    let has_list_id: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'list_id'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_list_id {
+        conn.execute("ALTER TABLE emails ADD COLUMN list_id TEXT", [])?;
+        conn.execute("ALTER TABLE emails ADD COLUMN list_meta TEXT", [])?;
+    }
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_emails_list_id ON emails(account_id, list_id)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Add the S/MIME/PGP detection column to the emails table if it doesn't exist yet
+fn migrate_add_security_column(conn: &Connection) -> Result<()> {
+    let has_security: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'security'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_security {
+        conn.execute("ALTER TABLE emails ADD COLUMN security TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add the attachment list column (JSON) to the emails table if it doesn't exist yet
+fn migrate_add_attachments_column(conn: &Connection) -> Result<()> {
+    let has_attachments: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'attachments'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_attachments {
+        conn.execute("ALTER TABLE emails ADD COLUMN attachments TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add the reply threading columns (In-Reply-To, References as JSON) if they don't exist yet
+fn migrate_add_threading_columns(conn: &Connection) -> Result<()> {
+    let has_threading: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'in_reply_to'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_threading {
+        conn.execute("ALTER TABLE emails ADD COLUMN in_reply_to TEXT", [])?;
+        conn.execute("ALTER TABLE emails ADD COLUMN reference_ids TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add the message size column (used for sorting by size) if it doesn't exist yet
+fn migrate_add_size_column(conn: &Connection) -> Result<()> {
+    let has_size: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'size'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_size {
+        conn.execute(
+            "ALTER TABLE emails ADD COLUMN size INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Add the column recording when a row was last fetched from the server. Existing
+/// rows take their last write time.
+fn migrate_add_cached_at_column(conn: &Connection) -> Result<()> {
+    let has_cached_at: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'cached_at'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_cached_at {
+        conn.execute(
+            "ALTER TABLE emails ADD COLUMN cached_at INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+        conn.execute("UPDATE emails SET cached_at = updated_at", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add the Cc and Reply-To columns (JSON address lists) used to build replies
+fn migrate_add_reply_recipient_columns(conn: &Connection) -> Result<()> {
+    let has_cc: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'cc_emails'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_cc {
+        conn.execute("ALTER TABLE emails ADD COLUMN cc_emails TEXT", [])?;
+        conn.execute("ALTER TABLE emails ADD COLUMN reply_to TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add the invite column: the message's calendar invite as JSON
+fn migrate_add_invite_column(conn: &Connection) -> Result<()> {
+    let has_invite: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'invite'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_invite {
+        conn.execute("ALTER TABLE emails ADD COLUMN invite TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add the authentication column: the SPF/DKIM/DMARC summary as JSON
+fn migrate_add_authentication_column(conn: &Connection) -> Result<()> {
+    let has_authentication: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'authentication'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_authentication {
+        conn.execute("ALTER TABLE emails ADD COLUMN authentication TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+/// Add the uid_validity column: the folder's UIDVALIDITY when the row was
+/// cached (0 if unknown), so rows from before a renumbering aren't served
+fn migrate_add_uid_validity_column(conn: &Connection) -> Result<()> {
+    let has_uid_validity: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('emails') WHERE name = 'uid_validity'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_uid_validity {
+        conn.execute(
+            "ALTER TABLE emails ADD COLUMN uid_validity INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Re-key the vector DB's email_embeddings by (email_id, chunk_index) so an
+/// email can have several chunk embeddings. Existing rows become chunk 0.
+fn migrate_add_embedding_chunk_index(conn: &Connection) -> Result<()> {
+    let table_exists: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type='table' AND name='email_embeddings'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+    let has_chunk_index: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('email_embeddings') WHERE name = 'chunk_index'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !table_exists || has_chunk_index {
+        return Ok(());
+    }
+
+    eprintln!("Migrating email_embeddings table: adding chunk_index...");
+
+    conn.execute_batch(
+        "BEGIN TRANSACTION;
+         CREATE TABLE email_embeddings_new (
+            email_id TEXT NOT NULL,
+            chunk_index INTEGER NOT NULL DEFAULT 0,
+            embedding BLOB NOT NULL,
+            embedding_model TEXT NOT NULL,
+            text_hash TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (email_id, chunk_index)
+         );
+         INSERT INTO email_embeddings_new
+            (email_id, chunk_index, embedding, embedding_model, text_hash, created_at)
+            SELECT email_id, 0, embedding, embedding_model, text_hash, created_at
+            FROM email_embeddings;
+         DROP TABLE email_embeddings;
+         ALTER TABLE email_embeddings_new RENAME TO email_embeddings;
+         COMMIT;",
+    )?;
+
+    Ok(())
+}
+
+/// Add the scale of int8-quantized embeddings. Existing rows keep full
+/// precision (NULL scale).
+fn migrate_add_embedding_quantization_scale(conn: &Connection) -> Result<()> {
+    let has_scale: bool = conn
+        .query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info('email_embeddings') WHERE name = 'quantization_scale'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(false);
+
+    if !has_scale {
+        conn.execute(
+            "ALTER TABLE email_embeddings ADD COLUMN quantization_scale REAL",
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Migrates the date column from TEXT to INTEGER if needed
 fn migrate_date_column_if_needed(conn: &Connection) -> Result<()> {
     let table_exists: bool = conn
@@ -5,21 +5,35 @@
 use anyhow::{Context, Result as AnyhowResult};
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use super::schema::create_vector_tables;
+use super::vector_index::{IvfIndex, ANN_MIN_EMBEDDINGS, DEFAULT_SEARCH_PROBES};
 
 /// Embedding dimensions (all-MiniLM-L6-v2 produces 384-dim vectors)
 pub const EMBEDDING_DIMENSIONS: usize = 384;
 
+/// Columns read by `embedding_from_row`, in order
+const EMBEDDING_COLUMNS: &str =
+    "email_id, chunk_index, embedding, embedding_model, text_hash, created_at, quantization_scale";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailEmbedding {
     pub email_id: String,
+    /// Position of the chunk this embedding covers; 0 when the email is embedded whole
+    #[serde(default)]
+    pub chunk_index: i64,
     pub embedding: Vec<f32>,
     pub embedding_model: String,
     pub text_hash: String,
     pub created_at: i64,
+    /// Scale of the int8 encoding the embedding is stored with; None for full
+    /// precision. Chosen by the database when storing.
+    #[serde(default)]
+    pub quantization_scale: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,11 +49,55 @@ pub struct EmbeddingStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimilarEmail {
     pub email_id: String,
+    /// Cosine similarity to the query embedding
+    pub similarity: f32,
+    /// Final ranking score; the similarity unless the results were reranked
+    /// or fused with keyword matches
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadEmbedding {
+    pub thread_id: String,
+    pub embedding: Vec<f32>,
+    pub embedding_model: String,
+    /// Number of embedded emails averaged into this vector
+    pub member_count: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarThread {
+    pub thread_id: String,
     pub similarity: f32,
+    pub member_count: i64,
+}
+
+/// A classification category. The embedding of its description is cached
+/// until the description or the embedding model changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryDefinition {
+    pub name: String,
+    pub description: String,
+    #[serde(skip)]
+    pub embedding: Option<Vec<f32>>,
+    #[serde(skip)]
+    pub embedding_model: Option<String>,
 }
 
 pub struct VectorDatabase {
     conn: Arc<Mutex<Connection>>,
+    /// Approximate index for large collections, built on the first search
+    /// that needs it and kept up to date as embeddings are stored
+    index: Mutex<Option<IvfIndex>>,
+    /// Index lists scanned per search; higher trades speed for recall
+    search_probes: AtomicUsize,
+    /// Store new embeddings as int8 instead of f32
+    quantize: AtomicBool,
+    /// Model of the loaded embedding engine. Searches only read its rows,
+    /// since vectors from different models can't be compared; every row
+    /// counts until it's set.
+    model: Mutex<Option<String>>,
 }
 
 impl VectorDatabase {
@@ -51,48 +109,169 @@ impl VectorDatabase {
 
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            index: Mutex::new(None),
+            search_probes: AtomicUsize::new(DEFAULT_SEARCH_PROBES),
+            quantize: AtomicBool::new(false),
+            model: Mutex::new(None),
         })
     }
 
-    /// Store an embedding for an email
-    pub fn store_embedding(&self, embedding: &EmailEmbedding) -> AnyhowResult<()> {
-        let conn = self.conn.lock().unwrap();
+    /// Set the model queries are embedded with. Rows from other models are
+    /// left out of searches until `reindex_all` re-embeds them.
+    pub fn set_model(&self, model: &str) {
+        let mut current = self.model.lock().unwrap();
+        if current.as_deref() != Some(model) {
+            *current = Some(model.to_string());
+            // The index may hold the other model's vectors
+            *self.index.lock().unwrap() = None;
+        }
+    }
 
-        // Serialize embedding to bytes
-        let embedding_bytes = embedding_to_bytes(&embedding.embedding)?;
+    fn current_model(&self) -> Option<String> {
+        self.model.lock().unwrap().clone()
+    }
 
-        conn.execute(
-            "INSERT OR REPLACE INTO email_embeddings (email_id, embedding, embedding_model, text_hash, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![
-                embedding.email_id,
-                embedding_bytes,
-                embedding.embedding_model,
-                embedding.text_hash,
-                embedding.created_at,
-            ],
-        )?;
+    /// Store embeddings from now on as int8 (a quarter of the size) or f32.
+    /// Existing rows keep their format until `requantize_embeddings` runs.
+    pub fn set_quantization(&self, enabled: bool) {
+        self.quantize.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Re-encode every stored email embedding in the current format.
+    /// Returns how many rows were converted.
+    pub fn requantize_embeddings(&self) -> AnyhowResult<usize> {
+        let quantize = self.quantize.load(Ordering::Relaxed);
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let rows = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {} FROM email_embeddings WHERE (quantization_scale IS NULL) = ?1",
+                EMBEDDING_COLUMNS
+            ))?;
+            let rows = stmt
+                .query_map(params![quantize], embedding_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        for row in &rows {
+            let (bytes, scale) = encode_embedding(&row.embedding, quantize);
+            tx.execute(
+                "UPDATE email_embeddings SET embedding = ?3, quantization_scale = ?4
+                 WHERE email_id = ?1 AND chunk_index = ?2",
+                params![row.email_id, row.chunk_index, bytes, scale],
+            )?;
+        }
+        tx.commit()?;
+
+        if !rows.is_empty() {
+            // Drop the space freed by shrinking the rows
+            if let Err(e) = conn.execute_batch("VACUUM") {
+                eprintln!("[VectorDB] Failed to vacuum after requantizing: {}", e);
+            }
+        }
+        Ok(rows.len())
+    }
+
+    /// Set how many index lists a search scans (at least 1)
+    pub fn set_search_probes(&self, probes: usize) {
+        self.search_probes.store(probes.max(1), Ordering::Relaxed);
+    }
+
+    /// Rebuild the search index from the stored embeddings, e.g. after a bulk
+    /// import. Below `ANN_MIN_EMBEDDINGS` the index is dropped instead, since
+    /// searches scan everything anyway. Returns whether an index was built.
+    pub fn rebuild_index(&self) -> AnyhowResult<bool> {
+        let mut index = self.index.lock().unwrap();
+        *index = self.build_index()?;
+        Ok(index.is_some())
+    }
+
+    fn build_index(&self) -> AnyhowResult<Option<IvfIndex>> {
+        let embeddings = self.get_all_embeddings()?;
+        if embeddings.len() < ANN_MIN_EMBEDDINGS {
+            return Ok(None);
+        }
+        let index = IvfIndex::build(
+            embeddings
+                .into_iter()
+                .map(|e| (e.email_id, e.embedding))
+                .collect(),
+        );
+        println!("[VectorDB] Built search index over {} embeddings", index.len());
+        Ok(Some(index))
+    }
+
+    /// Store chunk embeddings in one transaction. Every email in `chunks`
+    /// loses all of its previous embeddings first.
+    pub fn store_embeddings(&self, chunks: &[EmailEmbedding]) -> AnyhowResult<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        // A shorter re-embedding mustn't leave stale chunks behind
+        let email_ids: std::collections::HashSet<&str> =
+            chunks.iter().map(|c| c.email_id.as_str()).collect();
+        for email_id in email_ids {
+            tx.execute(
+                "DELETE FROM email_embeddings WHERE email_id = ?1",
+                params![email_id],
+            )?;
+        }
+        let quantize = self.quantize.load(Ordering::Relaxed);
+        let mut stored = Vec::with_capacity(chunks.len());
+        for embedding in chunks {
+            let (bytes, scale) = encode_embedding(&embedding.embedding, quantize);
+            tx.execute(
+                "INSERT OR REPLACE INTO email_embeddings
+                 (email_id, chunk_index, embedding, embedding_model, text_hash, created_at,
+                  quantization_scale)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    embedding.email_id,
+                    embedding.chunk_index,
+                    bytes,
+                    embedding.embedding_model,
+                    embedding.text_hash,
+                    embedding.created_at,
+                    scale,
+                ],
+            )?;
+            stored.push(decode_embedding(&bytes, scale)?);
+        }
+        tx.commit()?;
+        drop(conn);
+
+        // Index what a rebuild would read back, i.e. the quantized values
+        if let Some(index) = self.index.lock().unwrap().as_mut() {
+            let mut by_email: HashMap<&str, Vec<Vec<f32>>> = HashMap::new();
+            for (chunk, vector) in chunks.iter().zip(stored) {
+                by_email
+                    .entry(chunk.email_id.as_str())
+                    .or_default()
+                    .push(vector);
+            }
+            for (email_id, vectors) in by_email {
+                index.insert(email_id, vectors);
+            }
+        }
 
         Ok(())
     }
 
-    /// Get embedding for a specific email
+    /// Get embedding for a specific email (its first chunk when chunked)
     pub fn get_embedding(&self, email_id: &str) -> AnyhowResult<Option<EmailEmbedding>> {
         let conn = self.conn.lock().unwrap();
 
         let result = conn.query_row(
-            "SELECT email_id, embedding, embedding_model, text_hash, created_at FROM email_embeddings WHERE email_id = ?1",
+            &format!(
+                "SELECT {} FROM email_embeddings WHERE email_id = ?1 ORDER BY chunk_index LIMIT 1",
+                EMBEDDING_COLUMNS
+            ),
             params![email_id],
-            |row| {
-                let embedding_bytes: Vec<u8> = row.get(1)?;
-                Ok(EmailEmbedding {
-                    email_id: row.get(0)?,
-                    embedding: bytes_to_embedding(&embedding_bytes).unwrap_or_default(),
-                    embedding_model: row.get(2)?,
-                    text_hash: row.get(3)?,
-                    created_at: row.get(4)?,
-                })
-            },
+            embedding_from_row,
         );
 
         match result {
@@ -102,51 +281,82 @@ impl VectorDatabase {
         }
     }
 
-    /// Get all embeddings (for similarity search)
+    /// Get all embeddings of the current model, every chunk included (for
+    /// similarity search)
     pub fn get_all_embeddings(&self) -> AnyhowResult<Vec<EmailEmbedding>> {
+        let model = self.current_model();
         let conn = self.conn.lock().unwrap();
 
-        let mut stmt = conn.prepare(
-            "SELECT email_id, embedding, embedding_model, text_hash, created_at FROM email_embeddings",
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM email_embeddings WHERE ?1 IS NULL OR embedding_model = ?1",
+            EMBEDDING_COLUMNS
+        ))?;
 
         let embeddings = stmt
-            .query_map([], |row| {
-                let embedding_bytes: Vec<u8> = row.get(1)?;
-                Ok(EmailEmbedding {
-                    email_id: row.get(0)?,
-                    embedding: bytes_to_embedding(&embedding_bytes).unwrap_or_default(),
-                    embedding_model: row.get(2)?,
-                    text_hash: row.get(3)?,
-                    created_at: row.get(4)?,
-                })
-            })?
+            .query_map(params![model], embedding_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(embeddings)
     }
 
-    /// Find similar emails using cosine similarity
+    /// Find similar emails among the current model's embeddings using cosine
+    /// similarity. A chunked email scores as its best-matching chunk and appears once. Large collections are
+    /// searched through the approximate index, small ones exhaustively.
     pub fn search_similar(
         &self,
         query_embedding: &[f32],
         top_k: usize,
         exclude_email_id: Option<&str>,
+    ) -> AnyhowResult<Vec<SimilarEmail>> {
+        {
+            let mut index = self.index.lock().unwrap();
+            let stale = index.as_ref().map(|i| i.needs_rebuild()).unwrap_or(true);
+            if stale && self.get_embedded_chunk_count()? >= ANN_MIN_EMBEDDINGS {
+                *index = self.build_index()?;
+            }
+            if let Some(index) = index.as_ref().filter(|i| i.len() >= ANN_MIN_EMBEDDINGS) {
+                let probes = self.search_probes.load(Ordering::Relaxed);
+                return Ok(index
+                    .search(query_embedding, top_k, probes, exclude_email_id)
+                    .into_iter()
+                    .map(|(email_id, similarity)| SimilarEmail {
+                        email_id,
+                        similarity,
+                        score: similarity,
+                    })
+                    .collect());
+            }
+        }
+
+        self.search_similar_exhaustive(query_embedding, top_k, exclude_email_id)
+    }
+
+    /// Score every embedding of the current model against the query
+    fn search_similar_exhaustive(
+        &self,
+        query_embedding: &[f32],
+        top_k: usize,
+        exclude_email_id: Option<&str>,
     ) -> AnyhowResult<Vec<SimilarEmail>> {
         let embeddings = self.get_all_embeddings()?;
 
-        let mut similarities: Vec<SimilarEmail> = embeddings
-            .iter()
-            .filter(|e| {
-                if let Some(exclude_id) = exclude_email_id {
-                    e.email_id != exclude_id
-                } else {
-                    true
-                }
-            })
-            .map(|e| SimilarEmail {
-                email_id: e.email_id.clone(),
-                similarity: cosine_similarity(query_embedding, &e.embedding),
+        let mut best: HashMap<String, f32> = HashMap::new();
+        for e in embeddings {
+            if exclude_email_id == Some(e.email_id.as_str()) {
+                continue;
+            }
+            let similarity = cosine_similarity(query_embedding, &e.embedding);
+            best.entry(e.email_id)
+                .and_modify(|best| *best = best.max(similarity))
+                .or_insert(similarity);
+        }
+
+        let mut similarities: Vec<SimilarEmail> = best
+            .into_iter()
+            .map(|(email_id, similarity)| SimilarEmail {
+                email_id,
+                similarity,
+                score: similarity,
             })
             .collect();
 
@@ -159,13 +369,16 @@ impl VectorDatabase {
         Ok(similarities)
     }
 
-    /// Check if an email has an embedding with the given text hash
+    /// Check if an email has an embedding with the given text hash from the
+    /// current model. Unchanged text still needs embedding after a model switch.
     pub fn has_embedding(&self, email_id: &str, text_hash: &str) -> AnyhowResult<bool> {
+        let model = self.current_model();
         let conn = self.conn.lock().unwrap();
 
         let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM email_embeddings WHERE email_id = ?1 AND text_hash = ?2",
-            params![email_id, text_hash],
+            "SELECT COUNT(*) FROM email_embeddings
+             WHERE email_id = ?1 AND text_hash = ?2 AND (?3 IS NULL OR embedding_model = ?3)",
+            params![email_id, text_hash, model],
             |row| row.get(0),
         )?;
 
@@ -176,18 +389,60 @@ impl VectorDatabase {
     pub fn get_embedded_count(&self) -> AnyhowResult<i64> {
         let conn = self.conn.lock().unwrap();
 
-        let count: i64 = conn.query_row("SELECT COUNT(*) FROM email_embeddings", [], |row| {
-            row.get(0)
-        })?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(DISTINCT email_id) FROM email_embeddings",
+            [],
+            |row| row.get(0),
+        )?;
+
+        Ok(count)
+    }
+
+    fn get_embedded_chunk_count(&self) -> AnyhowResult<usize> {
+        let model = self.current_model();
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM email_embeddings WHERE ?1 IS NULL OR embedding_model = ?1",
+            params![model],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
 
+    /// Number of emails embedded by a model other than the current one
+    pub fn needs_reindex(&self) -> AnyhowResult<i64> {
+        let Some(model) = self.current_model() else {
+            return Ok(0);
+        };
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(DISTINCT email_id) FROM email_embeddings WHERE embedding_model != ?1",
+            params![model],
+            |row| row.get(0),
+        )?;
         Ok(count)
     }
 
+    /// IDs of the emails `needs_reindex` counts
+    pub fn get_stale_email_ids(&self) -> AnyhowResult<Vec<String>> {
+        let Some(model) = self.current_model() else {
+            return Ok(Vec::new());
+        };
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT email_id FROM email_embeddings WHERE embedding_model != ?1",
+        )?;
+        let ids = stmt
+            .query_map(params![model], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(ids)
+    }
+
     /// Get all email IDs that already have embeddings
     pub fn get_embedded_email_ids(&self) -> AnyhowResult<std::collections::HashSet<String>> {
         let conn = self.conn.lock().unwrap();
 
-        let mut stmt = conn.prepare("SELECT email_id FROM email_embeddings")?;
+        let mut stmt = conn.prepare("SELECT DISTINCT email_id FROM email_embeddings")?;
         let ids = stmt
             .query_map([], |row| row.get(0))?
             .collect::<Result<std::collections::HashSet<String>, _>>()?;
@@ -268,12 +523,276 @@ impl VectorDatabase {
         Ok(status)
     }
 
-    /// Delete embedding for an email
+    /// Delete all embeddings (every chunk) of an email
     pub fn delete_embedding(&self, email_id: &str) -> AnyhowResult<()> {
+        self.delete_embeddings(&[email_id.to_string()])?;
+        Ok(())
+    }
+
+    /// Delete the embeddings of several emails in one transaction.
+    /// Returns how many of the emails had any.
+    pub fn delete_embeddings(&self, email_ids: &[String]) -> AnyhowResult<usize> {
+        if email_ids.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut deleted = 0;
+        for email_id in email_ids {
+            if tx.execute(
+                "DELETE FROM email_embeddings WHERE email_id = ?1",
+                params![email_id],
+            )? > 0
+            {
+                deleted += 1;
+            }
+        }
+        tx.commit()?;
+        drop(conn);
+
+        if let Some(index) = self.index.lock().unwrap().as_mut() {
+            for email_id in email_ids {
+                index.remove(email_id);
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Delete the embeddings of every email not in `existing_ids`, e.g. ones
+    /// expunged or moved since they were embedded. Returns how many emails
+    /// lost their embeddings.
+    pub fn reconcile_embeddings(
+        &self,
+        existing_ids: &std::collections::HashSet<String>,
+    ) -> AnyhowResult<usize> {
+        let orphans: Vec<String> = self
+            .get_embedded_email_ids()?
+            .into_iter()
+            .filter(|id| !existing_ids.contains(id))
+            .collect();
+        self.delete_embeddings(&orphans)
+    }
+
+    /// Re-key embeddings after emails were moved to a new ID (e.g. account merge).
+    /// Embeddings already stored under the new ID win over the old ones.
+    /// Returns how many emails had their embeddings moved.
+    pub fn rename_embeddings(&self, renames: &[(String, String)]) -> AnyhowResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut renamed = 0;
+        for (old_id, new_id) in renames {
+            // All chunks move or none do, so two emails' chunks never mix
+            let moved = tx.execute(
+                "UPDATE email_embeddings SET email_id = ?2 WHERE email_id = ?1
+                 AND NOT EXISTS (SELECT 1 FROM email_embeddings WHERE email_id = ?2)",
+                params![old_id, new_id],
+            )?;
+            if moved > 0 {
+                renamed += 1;
+            }
+            tx.execute(
+                "DELETE FROM email_embeddings WHERE email_id = ?1",
+                params![old_id],
+            )?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        // Rare enough that rebuilding on the next search is simplest
+        *self.index.lock().unwrap() = None;
+        Ok(renamed)
+    }
+
+    // ========== Thread Embeddings ==========
+
+    /// Recompute a thread's aggregate embedding from its members' embeddings.
+    /// Removes the thread embedding when none of the members are embedded yet.
+    /// Returns whether an embedding was stored.
+    pub fn update_thread_embedding(
+        &self,
+        thread_id: &str,
+        member_email_ids: &[String],
+    ) -> AnyhowResult<bool> {
+        let current_model = self.current_model();
+        let mut vectors = Vec::new();
+        let mut model = None;
+        for email_id in member_email_ids {
+            let embedding = self.get_embedding(email_id)?.filter(|e| {
+                current_model.is_none() || current_model.as_ref() == Some(&e.embedding_model)
+            });
+            if let Some(embedding) = embedding {
+                model.get_or_insert(embedding.embedding_model);
+                vectors.push(embedding.embedding);
+            }
+        }
+
         let conn = self.conn.lock().unwrap();
+        let (Some(embedding), Some(model)) = (mean_embedding(&vectors), model) else {
+            conn.execute(
+                "DELETE FROM thread_embeddings WHERE thread_id = ?1",
+                params![thread_id],
+            )?;
+            return Ok(false);
+        };
+
         conn.execute(
-            "DELETE FROM email_embeddings WHERE email_id = ?1",
-            params![email_id],
+            "INSERT OR REPLACE INTO thread_embeddings
+             (thread_id, embedding, embedding_model, member_count, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                thread_id,
+                embedding_to_bytes(&embedding)?,
+                model,
+                vectors.len() as i64,
+                chrono::Utc::now().timestamp(),
+            ],
+        )?;
+        Ok(true)
+    }
+
+    /// Get all thread embeddings (for similarity search)
+    pub fn get_all_thread_embeddings(&self) -> AnyhowResult<Vec<ThreadEmbedding>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT thread_id, embedding, embedding_model, member_count, updated_at
+             FROM thread_embeddings",
+        )?;
+
+        let embeddings = stmt
+            .query_map([], |row| {
+                let embedding_bytes: Vec<u8> = row.get(1)?;
+                Ok(ThreadEmbedding {
+                    thread_id: row.get(0)?,
+                    embedding: bytes_to_embedding(&embedding_bytes).unwrap_or_default(),
+                    embedding_model: row.get(2)?,
+                    member_count: row.get(3)?,
+                    updated_at: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(embeddings)
+    }
+
+    /// Find similar threads using cosine similarity against the thread aggregates
+    pub fn search_similar_threads(
+        &self,
+        query_embedding: &[f32],
+        top_k: usize,
+    ) -> AnyhowResult<Vec<SimilarThread>> {
+        let model = self.current_model();
+        let mut similarities: Vec<SimilarThread> = self
+            .get_all_thread_embeddings()?
+            .into_iter()
+            .filter(|t| model.is_none() || model.as_ref() == Some(&t.embedding_model))
+            .map(|t| SimilarThread {
+                similarity: cosine_similarity(query_embedding, &t.embedding),
+                thread_id: t.thread_id,
+                member_count: t.member_count,
+            })
+            .collect();
+
+        similarities.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap());
+        similarities.truncate(top_k);
+
+        Ok(similarities)
+    }
+
+    // ========== Categories ==========
+
+    /// The category set in display order
+    pub fn get_categories(&self) -> AnyhowResult<Vec<CategoryDefinition>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT name, description, embedding, embedding_model FROM categories ORDER BY position",
+        )?;
+        let categories = stmt
+            .query_map([], |row| {
+                let embedding_bytes: Option<Vec<u8>> = row.get(2)?;
+                Ok(CategoryDefinition {
+                    name: row.get(0)?,
+                    description: row.get(1)?,
+                    embedding: embedding_bytes.and_then(|bytes| bytes_to_embedding(&bytes).ok()),
+                    embedding_model: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(categories)
+    }
+
+    /// Fill an empty category set with `defaults`. Returns whether it was empty.
+    pub fn seed_categories(&self, defaults: &[(&str, &str)]) -> AnyhowResult<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM categories", [], |row| row.get(0))?;
+        if count > 0 {
+            return Ok(false);
+        }
+
+        let tx = conn.transaction()?;
+        insert_categories(&tx, defaults)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Replace the whole category set with `defaults`
+    pub fn reset_categories(&self, defaults: &[(&str, &str)]) -> AnyhowResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM categories", [])?;
+        insert_categories(&tx, defaults)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Add a category at the end, or change an existing one's description
+    /// (which drops its cached embedding)
+    pub fn set_category(&self, name: &str, description: &str) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO categories (name, description, position)
+             VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM categories))
+             ON CONFLICT(name) DO UPDATE SET
+                embedding = CASE WHEN description = excluded.description THEN embedding END,
+                embedding_model =
+                    CASE WHEN description = excluded.description THEN embedding_model END,
+                description = excluded.description",
+            params![name, description],
+        )?;
+        Ok(())
+    }
+
+    /// Remove a category. False if it doesn't exist; refused for the last one,
+    /// since classification needs at least one category.
+    pub fn remove_category(&self, name: &str) -> AnyhowResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM categories", [], |row| row.get(0))?;
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM categories WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        )?;
+        if exists && count <= 1 {
+            anyhow::bail!("Can't remove the last category");
+        }
+
+        let removed = conn.execute("DELETE FROM categories WHERE name = ?1", params![name])?;
+        Ok(removed > 0)
+    }
+
+    /// Cache the embedding of a category's description
+    pub fn store_category_embedding(
+        &self,
+        name: &str,
+        embedding: &[f32],
+        embedding_model: &str,
+    ) -> AnyhowResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE categories SET embedding = ?2, embedding_model = ?3 WHERE name = ?1",
+            params![name, embedding_to_bytes(embedding)?, embedding_model],
         )?;
         Ok(())
     }
@@ -282,14 +801,64 @@ impl VectorDatabase {
     pub fn clear_all_embeddings(&self) -> AnyhowResult<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute("DELETE FROM email_embeddings", [])?;
+        conn.execute("DELETE FROM thread_embeddings", [])?;
         conn.execute(
             "UPDATE embedding_status SET embedded_emails = 0, is_embedding = 0 WHERE id = 1",
             [],
         )?;
+        drop(conn);
+
+        *self.index.lock().unwrap() = None;
         Ok(())
     }
 }
 
+fn insert_categories(tx: &rusqlite::Transaction, categories: &[(&str, &str)]) -> AnyhowResult<()> {
+    for (position, (name, description)) in categories.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO categories (name, description, position) VALUES (?1, ?2, ?3)",
+            params![name, description, position as i64],
+        )?;
+    }
+    Ok(())
+}
+
+fn embedding_from_row(row: &rusqlite::Row) -> rusqlite::Result<EmailEmbedding> {
+    let embedding_bytes: Vec<u8> = row.get(2)?;
+    let quantization_scale: Option<f32> = row.get(6)?;
+    Ok(EmailEmbedding {
+        email_id: row.get(0)?,
+        chunk_index: row.get(1)?,
+        embedding: decode_embedding(&embedding_bytes, quantization_scale).unwrap_or_default(),
+        embedding_model: row.get(3)?,
+        text_hash: row.get(4)?,
+        created_at: row.get(5)?,
+        quantization_scale,
+    })
+}
+
+/// Encode an email embedding for storage: as f32, or as one signed byte per
+/// dimension scaled so the largest magnitude maps to 127
+fn encode_embedding(embedding: &[f32], quantize: bool) -> (Vec<u8>, Option<f32>) {
+    if !quantize {
+        return (embedding_to_bytes(embedding).unwrap_or_default(), None);
+    }
+    let max = embedding.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+    let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+    let bytes = embedding
+        .iter()
+        .map(|v| (v / scale).round().clamp(-127.0, 127.0) as i8 as u8)
+        .collect();
+    (bytes, Some(scale))
+}
+
+fn decode_embedding(bytes: &[u8], quantization_scale: Option<f32>) -> AnyhowResult<Vec<f32>> {
+    match quantization_scale {
+        Some(scale) => Ok(bytes.iter().map(|&b| f32::from(b as i8) * scale).collect()),
+        None => bytes_to_embedding(bytes),
+    }
+}
+
 /// Convert f32 vector to bytes for storage
 fn embedding_to_bytes(embedding: &[f32]) -> AnyhowResult<Vec<u8>> {
     let mut bytes = Vec::with_capacity(embedding.len() * 4);
@@ -313,6 +882,25 @@ fn bytes_to_embedding(bytes: &[u8]) -> AnyhowResult<Vec<f32>> {
     Ok(embedding)
 }
 
+/// Average a set of embeddings and L2-normalize the result
+fn mean_embedding(vectors: &[Vec<f32>]) -> Option<Vec<f32>> {
+    let dims = vectors.first()?.len();
+    let mut mean = vec![0.0f32; dims];
+    let mut count = 0;
+    for vector in vectors.iter().filter(|v| v.len() == dims) {
+        for (m, v) in mean.iter_mut().zip(vector) {
+            *m += v;
+        }
+        count += 1;
+    }
+
+    let norm = mean.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if count == 0 || norm == 0.0 {
+        return None;
+    }
+    Some(mean.into_iter().map(|v| v / norm).collect())
+}
+
 /// Calculate cosine similarity between two vectors
 fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     if a.len() != b.len() || a.is_empty() {
@@ -352,6 +940,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_quantized_embeddings_take_a_quarter_of_the_space() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let embeddings: Vec<EmailEmbedding> = (0..1000)
+            .map(|i| EmailEmbedding {
+                email_id: format!("email-{}", i),
+                chunk_index: 0,
+                embedding: (0..EMBEDDING_DIMENSIONS)
+                    .map(|_| rng.gen_range(-1.0..1.0))
+                    .collect(),
+                embedding_model: "test".to_string(),
+                text_hash: "hash".to_string(),
+                created_at: 0,
+                quantization_scale: None,
+            })
+            .collect();
+        let stored_bytes = |db: &VectorDatabase| -> i64 {
+            db.conn
+                .lock()
+                .unwrap()
+                .query_row("SELECT SUM(LENGTH(embedding)) FROM email_embeddings", [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+
+        let full = VectorDatabase::new(PathBuf::from(":memory:")).unwrap();
+        full.store_embeddings(&embeddings).unwrap();
+        let quantized = VectorDatabase::new(PathBuf::from(":memory:")).unwrap();
+        quantized.set_quantization(true);
+        quantized.store_embeddings(&embeddings).unwrap();
+
+        // 1000 x 384 dimensions: 1,536,000 bytes as f32, 384,000 as int8
+        assert_eq!(stored_bytes(&full), 1000 * 384 * 4);
+        assert_eq!(stored_bytes(&quantized), 1000 * 384);
+
+        // Rankings barely move
+        let ranked = |db: &VectorDatabase, query: &[f32]| -> Vec<String> {
+            let results = db.search_similar(query, 10, None).unwrap();
+            results.into_iter().map(|r| r.email_id).collect()
+        };
+        let mut overlap = 0;
+        for query in embeddings.iter().step_by(100) {
+            let exact = ranked(&full, &query.embedding);
+            let approx = ranked(&quantized, &query.embedding);
+            assert_eq!(exact[0], approx[0]);
+            overlap += approx.iter().filter(|id| exact.contains(id)).count();
+        }
+        assert!(overlap >= 90, "top-10 overlap {}/100", overlap);
+
+        // Full-precision rows still load and can be converted in place
+        full.set_quantization(true);
+        assert_eq!(full.requantize_embeddings().unwrap(), 1000);
+        assert_eq!(stored_bytes(&full), 1000 * 384);
+        let row = full.get_embedding("email-0").unwrap().unwrap();
+        assert!(row.quantization_scale.is_some());
+        assert!(cosine_similarity(&row.embedding, &embeddings[0].embedding) > 0.999);
+    }
+
     #[test]
     fn test_cosine_similarity() {
         let a = vec![1.0, 0.0, 0.0];
@@ -364,4 +1014,135 @@ mod tests {
         let d = vec![-1.0, 0.0, 0.0];
         assert!((cosine_similarity(&a, &d) + 1.0).abs() < 1e-6);
     }
+
+    #[test]
+    fn test_chunked_email_matches_once_by_best_chunk() {
+        let db = VectorDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let chunk = |email_id: &str, chunk_index: i64, embedding: Vec<f32>| EmailEmbedding {
+            email_id: email_id.to_string(),
+            chunk_index,
+            embedding,
+            embedding_model: "test".to_string(),
+            text_hash: "hash".to_string(),
+            created_at: 0,
+            quantization_scale: None,
+        };
+
+        db.store_embeddings(&[
+            chunk("long", 0, vec![0.0, 1.0]),
+            chunk("long", 1, vec![1.0, 0.1]),
+            chunk("long", 2, vec![0.5, 0.5]),
+        ])
+        .unwrap();
+        db.store_embeddings(&[chunk("short", 0, vec![1.0, 0.5])]).unwrap();
+        assert_eq!(db.get_embedded_count().unwrap(), 2);
+
+        let results = db.search_similar(&[1.0, 0.0], 5, None).unwrap();
+        let ids: Vec<_> = results.iter().map(|r| r.email_id.as_str()).collect();
+        assert_eq!(ids, ["long", "short"]);
+        assert!(results[0].similarity > 0.99);
+
+        // Re-embedding whole drops the old chunks
+        db.store_embeddings(&[chunk("long", 0, vec![0.0, 1.0])]).unwrap();
+        assert_eq!(db.get_all_embeddings().unwrap().len(), 2);
+        assert_eq!(db.search_similar(&[1.0, 0.0], 5, None).unwrap()[0].email_id, "short");
+    }
+
+    #[test]
+    fn test_model_switch_hides_and_reindexes_old_embeddings() {
+        let db = VectorDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let embedding = |email_id: &str, model: &str| EmailEmbedding {
+            email_id: email_id.to_string(),
+            chunk_index: 0,
+            embedding: vec![1.0, 0.0],
+            embedding_model: model.to_string(),
+            text_hash: "hash".to_string(),
+            created_at: 0,
+            quantization_scale: None,
+        };
+        db.store_embeddings(&[embedding("a", "old"), embedding("b", "old")])
+            .unwrap();
+        db.set_model("old");
+        assert!(db.has_embedding("a", "hash").unwrap());
+        assert_eq!(db.needs_reindex().unwrap(), 0);
+
+        db.set_model("new");
+        assert_eq!(db.needs_reindex().unwrap(), 2);
+        assert!(!db.has_embedding("a", "hash").unwrap());
+        assert!(db.search_similar(&[1.0, 0.0], 5, None).unwrap().is_empty());
+
+        db.store_embeddings(&[embedding("a", "new")]).unwrap();
+        assert!(db.has_embedding("a", "hash").unwrap());
+        assert_eq!(db.get_stale_email_ids().unwrap(), ["b"]);
+        let results = db.search_similar(&[1.0, 0.0], 5, None).unwrap();
+        let ids: Vec<_> = results.iter().map(|r| r.email_id.as_str()).collect();
+        assert_eq!(ids, ["a"]);
+    }
+
+    #[test]
+    fn test_reconcile_prunes_embeddings_of_removed_emails() {
+        let db = VectorDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let chunks: Vec<EmailEmbedding> = ["kept", "trashed", "expunged"]
+            .iter()
+            .flat_map(|id| {
+                (0..2).map(move |chunk_index| EmailEmbedding {
+                    email_id: id.to_string(),
+                    chunk_index,
+                    embedding: vec![1.0, chunk_index as f32],
+                    embedding_model: "test".to_string(),
+                    text_hash: "hash".to_string(),
+                    created_at: 0,
+                    quantization_scale: None,
+                })
+            })
+            .collect();
+        db.store_embeddings(&chunks).unwrap();
+
+        db.delete_embedding("trashed").unwrap();
+        let existing = ["kept".to_string(), "never-embedded".to_string()].into();
+        assert_eq!(db.reconcile_embeddings(&existing).unwrap(), 1);
+
+        assert_eq!(db.get_all_embeddings().unwrap().len(), 2);
+        let results = db.search_similar(&[1.0, 0.0], 5, None).unwrap();
+        let ids: Vec<_> = results.iter().map(|r| r.email_id.as_str()).collect();
+        assert_eq!(ids, ["kept"]);
+    }
+
+    #[test]
+    fn test_category_set_edits_keep_order_and_invalidate_embeddings() {
+        let db = VectorDatabase::new(PathBuf::from(":memory:")).unwrap();
+        let defaults = [("general", "Conversation"), ("promotions", "Sales")];
+        assert!(db.seed_categories(&defaults).unwrap());
+
+        db.store_category_embedding("general", &[1.0, 0.0], "model").unwrap();
+        db.store_category_embedding("promotions", &[0.0, 1.0], "model").unwrap();
+        db.set_category("finance", "Invoices, bank statements").unwrap();
+        db.set_category("promotions", "Coupons and sales").unwrap();
+        db.set_category("general", "Conversation").unwrap();
+        assert!(db.get_categories().unwrap()[0].embedding.is_some());
+        assert!(db.remove_category("general").unwrap());
+        assert!(!db.remove_category("travel").unwrap());
+
+        let categories = db.get_categories().unwrap();
+        let names: Vec<_> = categories.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["promotions", "finance"]);
+        // A new description needs a new embedding
+        assert!(categories[0].embedding.is_none());
+        // Already configured, so the defaults don't come back
+        assert!(!db.seed_categories(&defaults).unwrap());
+
+        assert!(db.remove_category("finance").unwrap());
+        assert!(db.remove_category("promotions").is_err());
+    }
+
+    #[test]
+    fn test_mean_embedding() {
+        let mean = mean_embedding(&[vec![1.0, 0.0], vec![0.0, 1.0]]).unwrap();
+        let expected = 1.0 / 2.0f32.sqrt();
+        assert!((mean[0] - expected).abs() < 1e-6);
+        assert!((mean[1] - expected).abs() < 1e-6);
+
+        assert!(mean_embedding(&[]).is_none());
+        assert!(mean_embedding(&[vec![1.0, 0.0], vec![-1.0, 0.0]]).is_none());
+    }
 }
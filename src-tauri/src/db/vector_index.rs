@@ -0,0 +1,290 @@
+//! Approximate nearest-neighbour index over email embeddings (IVF).
+//!
+//! Embeddings are grouped into lists around k-means centroids. A search only
+//! scans the lists whose centroids are closest to the query, so its cost grows
+//! with the number of probed lists instead of the number of embeddings. More
+//! probes find more of the true neighbours at the price of speed.
+
+use std::collections::HashMap;
+
+/// Below this many embeddings a brute-force scan is fast enough and exact
+pub const ANN_MIN_EMBEDDINGS: usize = 5000;
+
+/// Lists scanned per search unless configured otherwise
+pub const DEFAULT_SEARCH_PROBES: usize = 8;
+
+/// k-means refinement rounds when (re)building
+const KMEANS_ITERATIONS: usize = 8;
+
+struct Entry {
+    email_id: String,
+    /// Unit length, so a dot product is the cosine similarity
+    vector: Vec<f32>,
+}
+
+pub struct IvfIndex {
+    centroids: Vec<Vec<f32>>,
+    /// Entry positions per centroid
+    lists: Vec<Vec<usize>>,
+    /// None once removed; compacted away by the next rebuild
+    entries: Vec<Option<Entry>>,
+    by_email: HashMap<String, Vec<usize>>,
+    live: usize,
+    /// Live entries when the centroids were last computed
+    built_with: usize,
+}
+
+impl IvfIndex {
+    /// Cluster `embeddings` (email ID, vector) into about sqrt(n) lists
+    pub fn build(embeddings: Vec<(String, Vec<f32>)>) -> Self {
+        let entries: Vec<Entry> = embeddings
+            .into_iter()
+            .filter_map(|(email_id, vector)| {
+                normalize(vector).map(|vector| Entry { email_id, vector })
+            })
+            .collect();
+        let centroids = kmeans(&entries, (entries.len() as f64).sqrt().ceil() as usize);
+
+        let mut index = Self {
+            lists: vec![Vec::new(); centroids.len()],
+            centroids,
+            entries: Vec::with_capacity(entries.len()),
+            by_email: HashMap::new(),
+            live: 0,
+            built_with: entries.len(),
+        };
+        for entry in entries {
+            index.push(entry);
+        }
+        index
+    }
+
+    /// Number of embeddings in the index
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    /// Whether enough has changed since the last build that the lists no
+    /// longer reflect the data well
+    pub fn needs_rebuild(&self) -> bool {
+        let dead = self.entries.len() - self.live;
+        dead > self.live || self.live > self.built_with.max(1) * 2
+    }
+
+    /// Add an email's embeddings (one per chunk), replacing any it had
+    pub fn insert(&mut self, email_id: &str, vectors: Vec<Vec<f32>>) {
+        self.remove(email_id);
+        for vector in vectors.into_iter().filter_map(normalize) {
+            self.push(Entry {
+                email_id: email_id.to_string(),
+                vector,
+            });
+        }
+    }
+
+    /// Drop all of an email's embeddings. They stay behind as tombstones in
+    /// their lists, which searches skip, until the next rebuild.
+    pub fn remove(&mut self, email_id: &str) {
+        for position in self.by_email.remove(email_id).unwrap_or_default() {
+            if self.entries[position].take().is_some() {
+                self.live -= 1;
+            }
+        }
+    }
+
+    /// The best `top_k` emails for `query` among the `probes` nearest lists,
+    /// as (email ID, similarity), best first. A chunked email counts once.
+    pub fn search(
+        &self,
+        query: &[f32],
+        top_k: usize,
+        probes: usize,
+        exclude_email_id: Option<&str>,
+    ) -> Vec<(String, f32)> {
+        let Some(query) = normalize(query.to_vec()) else {
+            return Vec::new();
+        };
+
+        let mut nearest_lists: Vec<(usize, f32)> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(list, centroid)| (list, dot(&query, centroid)))
+            .collect();
+        nearest_lists.sort_by(|a, b| b.1.total_cmp(&a.1));
+        nearest_lists.truncate(probes.max(1));
+
+        let mut best: HashMap<&str, f32> = HashMap::new();
+        for (list, _) in nearest_lists {
+            for &position in &self.lists[list] {
+                let Some(entry) = &self.entries[position] else {
+                    continue;
+                };
+                if exclude_email_id == Some(entry.email_id.as_str()) {
+                    continue;
+                }
+                let similarity = dot(&query, &entry.vector);
+                best.entry(&entry.email_id)
+                    .and_modify(|best| *best = best.max(similarity))
+                    .or_insert(similarity);
+            }
+        }
+
+        let mut results: Vec<(String, f32)> = best
+            .into_iter()
+            .map(|(email_id, similarity)| (email_id.to_string(), similarity))
+            .collect();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1));
+        results.truncate(top_k);
+        results
+    }
+
+    fn push(&mut self, entry: Entry) {
+        let position = self.entries.len();
+        if let Some(list) = nearest(&self.centroids, &entry.vector) {
+            self.lists[list].push(position);
+        } else {
+            // No centroids yet (built empty): the first entry founds a list
+            self.centroids.push(entry.vector.clone());
+            self.lists.push(vec![position]);
+        }
+        self.by_email
+            .entry(entry.email_id.clone())
+            .or_default()
+            .push(position);
+        self.entries.push(Some(entry));
+        self.live += 1;
+    }
+}
+
+/// `k` centroids for `entries`, seeded with evenly spaced entries so builds
+/// are deterministic
+fn kmeans(entries: &[Entry], k: usize) -> Vec<Vec<f32>> {
+    let k = k.min(entries.len());
+    if k == 0 {
+        return Vec::new();
+    }
+    let step = entries.len() / k;
+    let mut centroids: Vec<Vec<f32>> = (0..k).map(|i| entries[i * step].vector.clone()).collect();
+
+    for _ in 0..KMEANS_ITERATIONS {
+        let dims = centroids[0].len();
+        let mut sums = vec![vec![0.0f32; dims]; k];
+        for entry in entries {
+            if let Some(list) = nearest(&centroids, &entry.vector) {
+                for (sum, value) in sums[list].iter_mut().zip(&entry.vector) {
+                    *sum += value;
+                }
+            }
+        }
+        // An empty cluster keeps its old centroid
+        for (centroid, sum) in centroids.iter_mut().zip(sums) {
+            if let Some(mean) = normalize(sum) {
+                *centroid = mean;
+            }
+        }
+    }
+    centroids
+}
+
+fn nearest(centroids: &[Vec<f32>], vector: &[f32]) -> Option<usize> {
+    centroids
+        .iter()
+        .map(|centroid| dot(centroid, vector))
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(list, _)| list)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalize(mut vector: Vec<f32>) -> Option<Vec<f32>> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if vector.is_empty() || norm == 0.0 {
+        return None;
+    }
+    vector.iter_mut().for_each(|v| *v /= norm);
+    Some(vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn brute_force(data: &[(String, Vec<f32>)], query: &[f32], top_k: usize) -> Vec<String> {
+        let query = normalize(query.to_vec()).unwrap();
+        let mut scored: Vec<(String, f32)> = data
+            .iter()
+            .map(|(id, v)| (id.clone(), dot(&query, &normalize(v.clone()).unwrap())))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(top_k).map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn test_ivf_search_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        // Clustered data, like embeddings of emails on a handful of topics
+        let topics: Vec<Vec<f32>> = (0..20)
+            .map(|_| (0..32).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect();
+        let data: Vec<(String, Vec<f32>)> = (0..3000)
+            .map(|i| {
+                let topic = &topics[i % topics.len()];
+                let vector = topic.iter().map(|t| t + rng.gen_range(-0.4..0.4)).collect();
+                (format!("email-{}", i), vector)
+            })
+            .collect();
+        let index = IvfIndex::build(data.clone());
+        assert_eq!(index.len(), 3000);
+
+        let mut found = 0;
+        let mut total = 0;
+        for q in 0..20 {
+            let query = &data[q * 97].1;
+            let exact = brute_force(&data, query, 10);
+
+            // Probing every list is exact
+            let all: Vec<String> = index
+                .search(query, 10, usize::MAX, None)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            assert_eq!(all, exact);
+
+            let approx = index.search(query, 10, DEFAULT_SEARCH_PROBES, None);
+            found += approx.iter().filter(|(id, _)| exact.contains(id)).count();
+            total += exact.len();
+        }
+        assert!(found * 10 >= total * 9, "recall {}/{}", found, total);
+    }
+
+    #[test]
+    fn test_ivf_insert_replaces_and_remove_drops_email() {
+        let mut index = IvfIndex::build(vec![
+            ("a".to_string(), vec![1.0, 0.0]),
+            ("b".to_string(), vec![0.0, 1.0]),
+        ]);
+        index.insert("c", vec![vec![0.9, 0.1], vec![0.1, 0.9]]);
+        index.insert("a", vec![vec![0.0, 1.0]]);
+        assert_eq!(index.len(), 4);
+
+        let results = index.search(&[1.0, 0.0], 3, usize::MAX, Some("b"));
+        assert_eq!(results[0].0, "c");
+        assert_eq!(results.len(), 2);
+
+        index.remove("c");
+        assert_eq!(index.len(), 2);
+        assert!(index
+            .search(&[1.0, 0.0], 3, usize::MAX, None)
+            .iter()
+            .all(|(id, _)| id != "c"));
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Verdict for a downloaded attachment
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum AttachmentSafety {
+    /// A configured checker knows the hash and considers it clean
+    Safe,
+    /// No checker configured, or the checker has no opinion
+    Unknown,
+    /// A checker flagged the hash; opening requires an explicit override
+    Flagged,
+}
+
+/// Hash and verdict computed for an attachment's bytes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyReport {
+    /// Lowercase hex SHA-256 of the attachment bytes
+    pub sha256: String,
+    pub safety: AttachmentSafety,
+    /// Name of the checker that produced the verdict, if any ran
+    pub checker: Option<String>,
+}
+
+/// Extension point for hash lookups (local blocklist, reputation service, ...).
+/// Implementations receive only the hash, never the attachment contents.
+pub trait SafetyChecker: Send + Sync {
+    fn name(&self) -> &str;
+    fn check(&self, sha256: &str) -> AttachmentSafety;
+}
+
+/// Flags hashes listed in a local blocklist file (one hex SHA-256 per line, `#` comments)
+pub struct BlocklistChecker {
+    hashes: HashSet<String>,
+}
+
+impl BlocklistChecker {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read blocklist {}", path.display()))?;
+        Ok(Self::from_hashes(content.lines()))
+    }
+
+    pub fn from_hashes<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let hashes = lines
+            .into_iter()
+            .map(|line| line.split('#').next().unwrap_or("").trim().to_lowercase())
+            .filter(|hash| !hash.is_empty())
+            .collect();
+        Self { hashes }
+    }
+}
+
+impl SafetyChecker for BlocklistChecker {
+    fn name(&self) -> &str {
+        "local-blocklist"
+    }
+
+    fn check(&self, sha256: &str) -> AttachmentSafety {
+        if self.hashes.contains(&sha256.to_lowercase()) {
+            AttachmentSafety::Flagged
+        } else {
+            AttachmentSafety::Unknown
+        }
+    }
+}
+
+/// Compute the lowercase hex SHA-256 of some bytes
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash attachment bytes locally and run the optional checker over the hash
+pub fn check_attachment(bytes: &[u8], checker: Option<&dyn SafetyChecker>) -> SafetyReport {
+    let sha256 = sha256_hex(bytes);
+    match checker {
+        Some(checker) => SafetyReport {
+            safety: checker.check(&sha256),
+            checker: Some(checker.name().to_string()),
+            sha256,
+        },
+        None => SafetyReport {
+            sha256,
+            safety: AttachmentSafety::Unknown,
+            checker: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocklist_flags_known_hash() {
+        let bytes = b"not really malware";
+        let hash = sha256_hex(bytes);
+        let checker =
+            BlocklistChecker::from_hashes(vec!["# local list", hash.to_uppercase().as_str()]);
+
+        let report = check_attachment(bytes, Some(&checker));
+        assert_eq!(report.safety, AttachmentSafety::Flagged);
+        assert_eq!(report.checker.as_deref(), Some("local-blocklist"));
+
+        let clean = check_attachment(b"other", Some(&checker));
+        assert_eq!(clean.safety, AttachmentSafety::Unknown);
+        assert_eq!(check_attachment(bytes, None).checker, None);
+    }
+}
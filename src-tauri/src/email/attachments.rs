@@ -0,0 +1,655 @@
+use anyhow::{Context, Result};
+use async_imap::imap_proto::types::{
+    BodyContentCommon, BodyParams, BodyStructure, ContentEncoding,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use mail_parser::parsers::MessageStream;
+use mail_parser::{HeaderValue, MessageParser, MimeHeaders};
+use serde::{Deserialize, Serialize};
+
+/// Inline parts up to this many decoded bytes are embedded as data URLs;
+/// larger ones are fetched with `get_inline_part`
+pub const INLINE_DATA_URL_LIMIT: usize = 256 * 1024;
+
+/// Attachment listed on an `Email`, enough to show it and fetch it with `get_attachment`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AttachmentMeta {
+    /// IMAP part number, e.g. "2" or "1.3"
+    pub part_id: String,
+    pub filename: String,
+    pub content_type: String,
+    /// Encoded size declared by the server
+    pub size: u32,
+}
+
+/// Part referenced from the HTML body as `cid:<content_id>`, e.g. an embedded logo
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InlinePartMeta {
+    /// Content-ID without angle brackets
+    pub content_id: String,
+    /// IMAP part number, e.g. "1.2"
+    pub part_id: String,
+    pub content_type: String,
+    /// Encoded size declared by the server
+    pub size: u32,
+    /// `data:` URL with the decoded content, for parts under `INLINE_DATA_URL_LIMIT`
+    pub data_url: Option<String>,
+}
+
+/// An attachment or inline part located in a message's BODYSTRUCTURE
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AttachmentPart {
+    /// IMAP section path, e.g. [2] or [1, 3]
+    pub section: Vec<u32>,
+    pub filename: String,
+    pub content_type: String,
+    /// Content-Transfer-Encoding, lowercased ("base64", "quoted-printable", "7bit", ...)
+    pub encoding: String,
+    /// Size of the encoded part as declared by the server
+    pub octets: u32,
+    /// Content-ID without angle brackets
+    pub content_id: Option<String>,
+    /// Shown in the body via `cid:` rather than in the attachment list
+    pub inline: bool,
+}
+
+impl AttachmentPart {
+    /// Section path in IMAP syntax ("1.3")
+    pub fn section_spec(&self) -> String {
+        section_spec(&self.section)
+    }
+
+    pub fn meta(&self) -> AttachmentMeta {
+        AttachmentMeta {
+            part_id: self.section_spec(),
+            filename: self.filename.clone(),
+            content_type: self.content_type.clone(),
+            size: self.octets,
+        }
+    }
+
+    pub fn inline_meta(&self) -> InlinePartMeta {
+        InlinePartMeta {
+            content_id: self.content_id.clone().unwrap_or_default(),
+            part_id: self.section_spec(),
+            content_type: self.content_type.clone(),
+            size: self.octets,
+            data_url: None,
+        }
+    }
+}
+
+/// The body part a list snippet is read from, located in BODYSTRUCTURE
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewPart {
+    /// IMAP section path, e.g. [1] or [1, 1]
+    pub section: Vec<u32>,
+    /// text/html rather than text/plain
+    pub html: bool,
+    /// Content-Transfer-Encoding, lowercased
+    pub encoding: String,
+    pub charset: Option<String>,
+}
+
+impl PreviewPart {
+    pub fn section_spec(&self) -> String {
+        section_spec(&self.section)
+    }
+}
+
+fn section_spec(section: &[u32]) -> String {
+    section
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Text of the start of a body part, decoded as far as the cut-off data
+/// allows. HTML is reduced to its text.
+pub fn preview_text(part: &PreviewPart, data: &[u8]) -> String {
+    let mut data = data.to_vec();
+    match part.encoding.as_str() {
+        "base64" => {
+            // Drop the incomplete 4-character group at the cut
+            data.retain(|b| !b.is_ascii_whitespace());
+            data.truncate(data.len() / 4 * 4);
+        }
+        "quoted-printable" => {
+            // An escape or soft line break the cut went through
+            if let Some(i) = data.iter().rev().take(2).position(|&b| b == b'=') {
+                data.truncate(data.len() - 1 - i);
+            }
+        }
+        _ => {}
+    }
+
+    let mut raw = format!(
+        "Content-Type: text/{}; charset=\"{}\"\r\nContent-Transfer-Encoding: {}\r\n\r\n",
+        if part.html { "html" } else { "plain" },
+        part.charset.as_deref().unwrap_or("utf-8"),
+        part.encoding
+    )
+    .into_bytes();
+    raw.extend_from_slice(&data);
+    MessageParser::default()
+        .parse(&raw)
+        .and_then(|message| message.body_text(0).map(|text| text.into_owned()))
+        .unwrap_or_default()
+}
+
+/// Collect the attachment parts of a message, in BODYSTRUCTURE order.
+/// Non-text leaves and attached messages always count; text parts only
+/// when marked as attachments or given a filename. Inline parts are left out.
+pub fn collect_attachment_parts(structure: &BodyStructure) -> Vec<AttachmentPart> {
+    collect_mime_parts(structure)
+        .into_iter()
+        .filter(|part| !part.inline)
+        .collect()
+}
+
+/// Collect the parts the HTML body shows via `cid:` URLs: those with a
+/// Content-ID that aren't explicitly `Content-Disposition: attachment`
+pub fn collect_inline_parts(structure: &BodyStructure) -> Vec<AttachmentPart> {
+    collect_mime_parts(structure)
+        .into_iter()
+        .filter(|part| part.inline)
+        .collect()
+}
+
+/// Attachment and inline parts together, in BODYSTRUCTURE order
+pub fn collect_mime_parts(structure: &BodyStructure) -> Vec<AttachmentPart> {
+    let mut parts = Vec::new();
+    walk_leaves(structure, &mut |leaf, section| {
+        push_leaf(leaf, section, &mut parts)
+    });
+    parts
+}
+
+/// The part a list snippet is read from: the first text/plain body part, or
+/// else the first text/html one. Text attachments don't count.
+pub fn find_preview_part(structure: &BodyStructure) -> Option<PreviewPart> {
+    let mut plain = None;
+    let mut html = None;
+    walk_leaves(structure, &mut |leaf, section| {
+        let BodyStructure::Text { common, other, .. } = leaf else {
+            return;
+        };
+        if disposition_is_attachment(common) || part_filename(common).is_some() {
+            return;
+        }
+        let slot = match common.ty.subtype.to_ascii_lowercase().as_str() {
+            "plain" => &mut plain,
+            "html" => &mut html,
+            _ => return,
+        };
+        if slot.is_none() {
+            *slot = Some(PreviewPart {
+                html: common.ty.subtype.eq_ignore_ascii_case("html"),
+                section,
+                encoding: encoding_name(&other.transfer_encoding),
+                charset: param_value(&common.ty.params, "charset"),
+            });
+        }
+    });
+    plain.or(html)
+}
+
+/// Call `visit` with every part that isn't a multipart, and its section path
+fn walk_leaves(structure: &BodyStructure, visit: &mut impl FnMut(&BodyStructure, Vec<u32>)) {
+    match structure {
+        // A single-part message is section 1
+        BodyStructure::Multipart { bodies, .. } => walk_multipart(bodies, &[], visit),
+        leaf => visit(leaf, vec![1]),
+    }
+}
+
+fn walk_multipart(
+    bodies: &[BodyStructure],
+    prefix: &[u32],
+    visit: &mut impl FnMut(&BodyStructure, Vec<u32>),
+) {
+    for (i, body) in bodies.iter().enumerate() {
+        let mut section = prefix.to_vec();
+        section.push(i as u32 + 1);
+        match body {
+            BodyStructure::Multipart { bodies, .. } => walk_multipart(bodies, &section, visit),
+            leaf => visit(leaf, section),
+        }
+    }
+}
+
+fn push_leaf(body: &BodyStructure, section: Vec<u32>, parts: &mut Vec<AttachmentPart>) {
+    let (common, other, is_attachment) = match body {
+        BodyStructure::Basic { common, other, .. } => (common, other, true),
+        BodyStructure::Message { common, other, .. } => (common, other, true),
+        BodyStructure::Text { common, other, .. } => {
+            let marked = disposition_is_attachment(common) || part_filename(common).is_some();
+            (common, other, marked)
+        }
+        BodyStructure::Multipart { .. } => return,
+    };
+    if !is_attachment {
+        return;
+    }
+
+    let content_type = format!("{}/{}", common.ty.ty, common.ty.subtype).to_lowercase();
+    let filename = part_filename(common).unwrap_or_else(|| {
+        if content_type == "message/rfc822" {
+            "message.eml".to_string()
+        } else {
+            format!("attachment-{}", parts.len())
+        }
+    });
+    let encoding = encoding_name(&other.transfer_encoding);
+
+    let content_id = other
+        .id
+        .as_ref()
+        .map(|id| strip_angle_brackets(id))
+        .filter(|id| !id.is_empty());
+    let inline = content_id.is_some() && !disposition_is_attachment(common);
+
+    parts.push(AttachmentPart {
+        section,
+        filename,
+        content_type,
+        encoding,
+        octets: other.octets,
+        content_id,
+        inline,
+    });
+}
+
+/// Content-Transfer-Encoding, lowercased
+fn encoding_name(encoding: &ContentEncoding) -> String {
+    match encoding {
+        ContentEncoding::SevenBit => "7bit".to_string(),
+        ContentEncoding::EightBit => "8bit".to_string(),
+        ContentEncoding::Binary => "binary".to_string(),
+        ContentEncoding::Base64 => "base64".to_string(),
+        ContentEncoding::QuotedPrintable => "quoted-printable".to_string(),
+        ContentEncoding::Other(other) => other.to_lowercase(),
+    }
+}
+
+/// Content-ID as used in `cid:` URLs
+pub fn strip_angle_brackets(id: &str) -> String {
+    id.trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string()
+}
+
+/// Fill in `data_url` for the small inline parts, decoding them from the
+/// full message source
+pub fn embed_inline_parts(raw: &[u8], parts: &mut [InlinePartMeta]) {
+    if parts.is_empty() {
+        return;
+    }
+    let Some(message) = MessageParser::default().parse(raw) else {
+        return;
+    };
+    for part in message.parts.iter() {
+        let Some(content_id) = part.content_id().map(strip_angle_brackets) else {
+            continue;
+        };
+        let data = part.contents();
+        if data.len() > INLINE_DATA_URL_LIMIT {
+            continue;
+        }
+        if let Some(meta) = parts.iter_mut().find(|m| m.content_id == content_id) {
+            meta.data_url = Some(format!(
+                "data:{};base64,{}",
+                meta.content_type,
+                STANDARD.encode(data)
+            ));
+        }
+    }
+}
+
+fn disposition_is_attachment(common: &BodyContentCommon) -> bool {
+    common
+        .disposition
+        .as_ref()
+        .map(|d| d.ty.eq_ignore_ascii_case("attachment"))
+        .unwrap_or(false)
+}
+
+fn param_value(params: &BodyParams, key: &str) -> Option<String> {
+    params
+        .as_ref()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.to_string())
+}
+
+/// Filename from Content-Disposition, falling back to the Content-Type name parameter
+fn part_filename(common: &BodyContentCommon) -> Option<String> {
+    let disposition = common.disposition.as_ref();
+    disposition
+        .and_then(|d| param_value(&d.params, "filename"))
+        .or_else(|| {
+            // RFC 2231 extended value: charset'language'percent-encoded
+            let value = param_value(&disposition?.params, "filename*")?;
+            let encoded = value.rsplit('\'').next().unwrap_or(&value);
+            Some(
+                urlencoding::decode(encoded)
+                    .map(|s| s.into_owned())
+                    .unwrap_or_else(|_| encoded.to_string()),
+            )
+        })
+        .or_else(|| param_value(&common.ty.params, "name"))
+        .map(|name| decode_encoded_words(&name))
+        .filter(|name| !name.trim().is_empty())
+}
+
+/// Decode RFC 2047 encoded words ("=?UTF-8?B?...?="), which many clients use
+/// for non-ASCII filenames instead of RFC 2231
+fn decode_encoded_words(value: &str) -> String {
+    if !value.contains("=?") {
+        return value.to_string();
+    }
+    let line = format!("{}\r\n", value);
+    match MessageStream::new(line.as_bytes()).parse_unstructured() {
+        HeaderValue::Text(text) => text.trim().to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Undo the Content-Transfer-Encoding of a downloaded part
+pub fn decode_transfer_encoding(encoding: &str, data: &[u8]) -> Result<Vec<u8>> {
+    match encoding {
+        "base64" => {
+            let compact: Vec<u8> = data
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            STANDARD
+                .decode(compact)
+                .context("Invalid base64 attachment data")
+        }
+        "quoted-printable" => {
+            mail_parser::decoders::quoted_printable::quoted_printable_decode(data)
+                .context("Invalid quoted-printable attachment data")
+        }
+        _ => Ok(data.to_vec()),
+    }
+}
+
+/// Default cap on the combined size of a message's attachments. Base64 adds
+/// a third, which keeps the message under the 25 MB most providers accept.
+pub const DEFAULT_ATTACHMENT_LIMIT_MB: u64 = 18;
+
+/// An attachment for `send_email`: a file on disk (`path`) or base64 `data`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AttachmentInput {
+    /// Name shown to recipients; the file name of `path` when empty
+    #[serde(default)]
+    pub filename: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Base64 content, used when there's no `path`
+    #[serde(default)]
+    pub data: Option<String>,
+    /// Guessed from the filename's extension when unset
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+/// A loaded attachment, ready to be added to an outgoing message
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutgoingAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl AttachmentInput {
+    pub fn load(&self) -> Result<OutgoingAttachment> {
+        let (data, path_name) = match (&self.path, &self.data) {
+            (Some(path), _) => {
+                let path = std::path::Path::new(path);
+                let data = std::fs::read(path)
+                    .with_context(|| format!("Failed to read attachment {}", path.display()))?;
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned());
+                (data, name)
+            }
+            (None, Some(data)) => {
+                let data = decode_transfer_encoding("base64", data.as_bytes())
+                    .with_context(|| format!("Invalid data for attachment {}", self.filename))?;
+                (data, None)
+            }
+            (None, None) => anyhow::bail!("Attachment {} has no path or data", self.filename),
+        };
+
+        let filename = match self.filename.trim() {
+            "" => path_name.context("Attachment needs a filename")?,
+            name => name.to_string(),
+        };
+        let content_type = self
+            .content_type
+            .clone()
+            .filter(|content_type| !content_type.trim().is_empty())
+            .unwrap_or_else(|| content_type_for_filename(&filename).to_string());
+        Ok(OutgoingAttachment {
+            filename,
+            content_type,
+            data,
+        })
+    }
+}
+
+/// Load every attachment, failing with the total size when together they
+/// are over `limit` bytes (rather than having the SMTP server reject the message)
+pub fn load_attachments(inputs: &[AttachmentInput], limit: u64) -> Result<Vec<OutgoingAttachment>> {
+    let attachments = inputs
+        .iter()
+        .map(AttachmentInput::load)
+        .collect::<Result<Vec<_>>>()?;
+    let total: u64 = attachments.iter().map(|a| a.data.len() as u64).sum();
+    if total > limit {
+        anyhow::bail!(
+            "Attachments total {}, over the limit of {}",
+            format_size(total),
+            format_size(limit)
+        );
+    }
+    Ok(attachments)
+}
+
+fn format_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= MB {
+        format!("{:.1} MB", bytes as f64 / MB)
+    } else if bytes >= 1024 {
+        format!("{} KB", bytes.div_ceil(1024))
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+/// MIME type for common attachment extensions; application/octet-stream otherwise
+pub fn content_type_for_filename(filename: &str) -> &'static str {
+    let extension = match filename.rsplit_once('.') {
+        Some((_, extension)) => extension.to_ascii_lowercase(),
+        None => return "application/octet-stream",
+    };
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "ics" => "text/calendar",
+        "txt" | "log" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "md" => "text/markdown",
+        "eml" => "message/rfc822",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "heic" => "image/heic",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_imap::imap_proto::{parser::parse_response, AttributeValue, Response};
+
+    fn parts_from_fetch(line: &[u8]) -> Vec<AttachmentPart> {
+        let (_, response) = parse_response(line).unwrap();
+        let Response::Fetch(_, attrs) = response else {
+            panic!("expected FETCH response");
+        };
+        attrs
+            .iter()
+            .find_map(|attr| match attr {
+                AttributeValue::BodyStructure(bs) => Some(collect_attachment_parts(bs)),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_collect_attachment_parts() {
+        let line = b"* 1 FETCH (BODYSTRUCTURE ((\"text\" \"plain\" (\"charset\" \"utf-8\") NIL NIL \"7bit\" 12 1 NIL NIL NIL NIL)\
+                     (\"application\" \"pdf\" (\"name\" \"report.pdf\") NIL NIL \"base64\" 4096 NIL (\"attachment\" (\"filename\" \"Q3 report.pdf\")) NIL NIL) \
+                     \"mixed\" (\"boundary\" \"b1\") NIL NIL NIL))\r\n";
+        let parts = parts_from_fetch(line);
+
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].section, vec![2]);
+        assert_eq!(parts[0].section_spec(), "2");
+        assert_eq!(parts[0].filename, "Q3 report.pdf");
+        assert_eq!(parts[0].content_type, "application/pdf");
+        assert_eq!(parts[0].encoding, "base64");
+        assert_eq!(parts[0].octets, 4096);
+    }
+
+    #[test]
+    fn test_nested_parts_and_encoded_filenames() {
+        // multipart/mixed( multipart/alternative(text, html), image, message/rfc822 )
+        let line = b"* 1 FETCH (BODYSTRUCTURE (((\"text\" \"plain\" (\"charset\" \"utf-8\") NIL NIL \"7bit\" 12 1 NIL NIL NIL NIL)\
+                     (\"text\" \"html\" (\"charset\" \"utf-8\") NIL NIL \"7bit\" 30 1 NIL NIL NIL NIL) \"alternative\" (\"boundary\" \"b2\") NIL NIL NIL)\
+                     (\"image\" \"png\" (\"name\" \"=?UTF-8?B?w7xiZXJzaWNodC5wbmc=?=\") NIL NIL \"base64\" 2048 NIL NIL NIL NIL)\
+                     (\"text\" \"csv\" NIL NIL NIL \"quoted-printable\" 100 4 NIL (\"attachment\" (\"filename\" \"=?ISO-8859-1?Q?K=F6ln.csv?=\")) NIL NIL) \
+                     \"mixed\" (\"boundary\" \"b1\") NIL NIL NIL))\r\n";
+        let parts = parts_from_fetch(line);
+
+        let metas: Vec<AttachmentMeta> = parts.iter().map(AttachmentPart::meta).collect();
+        assert_eq!(metas.len(), 2);
+        assert_eq!(metas[0].part_id, "2");
+        assert_eq!(metas[0].filename, "übersicht.png");
+        assert_eq!(metas[0].size, 2048);
+        assert_eq!(metas[1].part_id, "3");
+        assert_eq!(metas[1].filename, "Köln.csv");
+        assert_eq!(metas[1].content_type, "text/csv");
+    }
+
+    #[test]
+    fn test_inline_parts_are_not_attachments() {
+        // multipart/related( html, image with Content-ID ), plus a real attachment with one
+        let line = b"* 1 FETCH (BODYSTRUCTURE (((\"text\" \"html\" (\"charset\" \"utf-8\") NIL NIL \"7bit\" 30 1 NIL NIL NIL NIL)\
+                     (\"image\" \"png\" (\"name\" \"logo.png\") \"<logo@acme>\" NIL \"base64\" 512 NIL (\"inline\" NIL) NIL NIL) \"related\" (\"boundary\" \"b2\") NIL NIL NIL)\
+                     (\"image\" \"jpeg\" NIL \"<photo@acme>\" NIL \"base64\" 9000 NIL (\"attachment\" (\"filename\" \"photo.jpg\")) NIL NIL) \
+                     \"mixed\" (\"boundary\" \"b1\") NIL NIL NIL))\r\n";
+        let (_, response) = parse_response(line).unwrap();
+        let Response::Fetch(_, attrs) = response else {
+            panic!("expected FETCH response");
+        };
+        let structure = attrs
+            .iter()
+            .find_map(|attr| match attr {
+                AttributeValue::BodyStructure(bs) => Some(bs),
+                _ => None,
+            })
+            .unwrap();
+
+        let attachments = collect_attachment_parts(structure);
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].filename, "photo.jpg");
+
+        let inline = collect_inline_parts(structure);
+        assert_eq!(inline.len(), 1);
+        let mut metas = vec![inline[0].inline_meta()];
+        assert_eq!(metas[0].content_id, "logo@acme");
+        assert_eq!(metas[0].part_id, "1.2");
+
+        let raw = b"Content-Type: multipart/related; boundary=\"b2\"\r\n\r\n\
+                    --b2\r\nContent-Type: text/html\r\n\r\n<img src=\"cid:logo@acme\">\r\n\
+                    --b2\r\nContent-Type: image/png\r\nContent-ID: <logo@acme>\r\n\
+                    Content-Transfer-Encoding: base64\r\n\r\niVBORw==\r\n--b2--\r\n";
+        embed_inline_parts(raw, &mut metas);
+        assert_eq!(
+            metas[0].data_url.as_deref(),
+            Some("data:image/png;base64,iVBORw==")
+        );
+    }
+
+    #[test]
+    fn test_decode_base64_with_line_breaks() {
+        let decoded = decode_transfer_encoding("base64", b"aGVsbG8g\r\nd29ybGQ=\r\n").unwrap();
+        assert_eq!(decoded, b"hello world");
+    }
+
+    #[test]
+    fn test_load_attachments_infers_type_and_enforces_limit() {
+        let dir = std::env::temp_dir().join(format!("attach-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Q3 Report.PDF");
+        std::fs::write(&path, b"%PDF-1.4").unwrap();
+
+        let inputs = vec![
+            AttachmentInput {
+                filename: String::new(),
+                path: Some(path.to_string_lossy().into_owned()),
+                data: None,
+                content_type: None,
+            },
+            AttachmentInput {
+                filename: "notes".to_string(),
+                path: None,
+                data: Some("aGVsbG8=".to_string()),
+                content_type: Some("text/plain".to_string()),
+            },
+        ];
+        let loaded = load_attachments(&inputs, 1024).unwrap();
+        assert_eq!(loaded[0].filename, "Q3 Report.PDF");
+        assert_eq!(loaded[0].content_type, "application/pdf");
+        assert_eq!(loaded[0].data, b"%PDF-1.4");
+        assert_eq!(loaded[1].content_type, "text/plain");
+        assert_eq!(loaded[1].data, b"hello");
+
+        let err = load_attachments(&inputs, 10).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Attachments total 13 bytes, over the limit of 10 bytes"
+        );
+        assert_eq!(
+            content_type_for_filename("archive"),
+            "application/octet-stream"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
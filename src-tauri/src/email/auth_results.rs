@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+
+use super::headers::{header_values, RawHeader};
+use super::server_presets::ProviderType;
+
+/// One method result from an Authentication-Results header (RFC 8601),
+/// e.g. `dkim=pass header.d=example.com`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuthMethodResult {
+    /// Authentication method ("spf", "dkim", "dmarc", "arc", ...)
+    pub method: String,
+    /// Result keyword ("pass", "fail", "softfail", "neutral", "none", ...)
+    pub result: String,
+    pub reason: Option<String>,
+    /// Remaining `ptype.property=value` pairs, e.g. ("header.d", "example.com")
+    pub properties: Vec<(String, String)>,
+}
+
+/// A parsed Authentication-Results header
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuthenticationResults {
+    /// The server that performed the checks
+    pub authserv_id: String,
+    pub results: Vec<AuthMethodResult>,
+}
+
+/// An SPF, DKIM or DMARC result collapsed for display
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthStatus {
+    Pass,
+    /// Including softfail and permerror
+    Fail,
+    /// Not checked, or no definite answer (neutral, temperror)
+    #[default]
+    None,
+}
+
+impl AuthStatus {
+    fn from_result(result: &str) -> Self {
+        match result {
+            "pass" => Self::Pass,
+            "fail" | "softfail" | "hardfail" | "permerror" | "policy" => Self::Fail,
+            _ => Self::None,
+        }
+    }
+}
+
+/// One check and the domain it was made for: the envelope sender's for
+/// SPF, the signing domain (`d=`) for DKIM, the From domain for DMARC
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AuthCheck {
+    pub status: AuthStatus,
+    pub domain: Option<String>,
+}
+
+/// SPF, DKIM and DMARC verdicts the receiving server recorded for a message
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AuthenticationSummary {
+    pub authserv_id: String,
+    pub spf: AuthCheck,
+    pub dkim: AuthCheck,
+    pub dmarc: AuthCheck,
+}
+
+impl AuthenticationSummary {
+    /// Summarize the topmost Authentication-Results header whose authserv-id
+    /// is one of `trusted`, or None without one. Anyone can add the header,
+    /// so those from other servers (possibly the sender) are ignored; each
+    /// server prepends its own, so a trusted one on top is the latest check.
+    pub fn from_headers(headers: &[RawHeader], trusted: &[String]) -> Option<Self> {
+        extract_authentication_results(headers)
+            .iter()
+            .find(|results| {
+                trusted
+                    .iter()
+                    .any(|id| authserv_id_matches(&results.authserv_id, id))
+            })
+            .map(Self::from_results)
+    }
+
+    pub fn from_results(results: &AuthenticationResults) -> Self {
+        Self {
+            authserv_id: results.authserv_id.clone(),
+            spf: auth_check(results, "spf", &["smtp.mailfrom", "smtp.helo"]),
+            dkim: auth_check(results, "dkim", &["header.d", "header.i"]),
+            dmarc: auth_check(results, "dmarc", &["header.from"]),
+        }
+    }
+}
+
+/// Authserv-ids the receiving servers of `provider` sign their
+/// Authentication-Results with; `*.` matches any subdomain. Custom servers
+/// are trusted under the domain of their IMAP host.
+pub fn provider_authserv_ids(provider: &ProviderType, imap_host: &str) -> Vec<String> {
+    match provider {
+        ProviderType::Gmail => vec!["mx.google.com".to_string()],
+        ProviderType::Outlook => vec!["*.outlook.com".to_string()],
+        ProviderType::Yahoo => vec!["*.yahoo.com".to_string()],
+        ProviderType::Custom => {
+            // imap.example.org -> example.org
+            let host = imap_host.trim_end_matches('.').to_lowercase();
+            let domain = match host.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => parent.to_string(),
+                _ => host,
+            };
+            vec![format!("*.{}", domain), domain]
+        }
+    }
+}
+
+fn authserv_id_matches(authserv_id: &str, trusted: &str) -> bool {
+    let authserv_id = authserv_id.to_lowercase();
+    let trusted = trusted.trim().to_lowercase();
+    match trusted.strip_prefix("*.") {
+        Some(domain) => authserv_id
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+        None => authserv_id == trusted,
+    }
+}
+
+/// The `method` result, preferring a passing one when there are several
+/// (one per DKIM signature), with the domain from the first of `properties`
+fn auth_check(results: &AuthenticationResults, method: &str, properties: &[&str]) -> AuthCheck {
+    let mut matching = results.results.iter().filter(|r| r.method == method);
+    let Some(first) = matching.clone().next() else {
+        return AuthCheck::default();
+    };
+    let chosen = matching.find(|r| r.result == "pass").unwrap_or(first);
+
+    let domain = properties.iter().find_map(|name| {
+        chosen
+            .properties
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.rsplit('@').next().unwrap_or(value).to_lowercase())
+            .filter(|domain| !domain.is_empty())
+    });
+    AuthCheck {
+        status: AuthStatus::from_result(&chosen.result),
+        domain,
+    }
+}
+
+/// Parse every Authentication-Results header of a message, in header order
+pub fn extract_authentication_results(headers: &[RawHeader]) -> Vec<AuthenticationResults> {
+    header_values(headers, "Authentication-Results")
+        .into_iter()
+        .filter_map(parse_authentication_results)
+        .collect()
+}
+
+/// Parse a single Authentication-Results header value
+pub fn parse_authentication_results(value: &str) -> Option<AuthenticationResults> {
+    let cleaned = strip_comments(value);
+    let mut segments = split_outside_quotes(&cleaned, ';').into_iter();
+
+    // First segment is the authserv-id, optionally followed by a version number
+    let authserv_id = segments
+        .next()?
+        .split_whitespace()
+        .next()?
+        .to_string();
+
+    let mut results = Vec::new();
+    for segment in segments {
+        let mut tokens = split_outside_quotes(segment.trim(), ' ')
+            .into_iter()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty());
+
+        let Some(first) = tokens.next() else {
+            continue;
+        };
+        let Some((method, result)) = first.split_once('=') else {
+            // "none" means no checks were performed
+            continue;
+        };
+
+        let method = method.split('/').next().unwrap_or(method);
+        let mut entry = AuthMethodResult {
+            method: method.to_lowercase(),
+            result: result.to_lowercase(),
+            reason: None,
+            properties: Vec::new(),
+        };
+
+        for token in tokens {
+            if let Some((key, val)) = token.split_once('=') {
+                let val = val.trim_matches('"').to_string();
+                if key.eq_ignore_ascii_case("reason") {
+                    entry.reason = Some(val);
+                } else {
+                    entry.properties.push((key.to_lowercase(), val));
+                }
+            }
+        }
+
+        results.push(entry);
+    }
+
+    Some(AuthenticationResults {
+        authserv_id,
+        results,
+    })
+}
+
+/// Remove RFC 5322 comments (parenthesised, possibly nested) outside quoted strings
+fn strip_comments(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut depth = 0usize;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        if escaped {
+            if depth == 0 {
+                out.push(c);
+            }
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => {
+                escaped = true;
+                if depth == 0 {
+                    out.push(c);
+                }
+            }
+            '"' if depth == 0 => {
+                in_quotes = !in_quotes;
+                out.push(c);
+            }
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes && depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    out.push(' ');
+                }
+            }
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+
+    out
+}
+
+fn split_outside_quotes(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == separator && !in_quotes {
+            parts.push(&value[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gmail_style_header() {
+        let value = "mx.google.com; dkim=pass header.i=@example.com header.s=s1 (comment); \
+                     spf=pass (google.com: domain of a@example.com designates 1.2.3.4) \
+                     smtp.mailfrom=a@example.com; dmarc=pass (p=NONE) header.from=example.com";
+        let parsed = parse_authentication_results(value).unwrap();
+
+        assert_eq!(parsed.authserv_id, "mx.google.com");
+        assert_eq!(parsed.results.len(), 3);
+        assert_eq!(parsed.results[0].method, "dkim");
+        assert_eq!(parsed.results[0].result, "pass");
+        assert_eq!(
+            parsed.results[1].properties,
+            vec![("smtp.mailfrom".to_string(), "a@example.com".to_string())]
+        );
+        assert_eq!(parsed.results[2].method, "dmarc");
+    }
+
+    #[test]
+    fn test_parse_none_and_reason() {
+        let none = parse_authentication_results("example.org 1; none").unwrap();
+        assert_eq!(none.authserv_id, "example.org");
+        assert!(none.results.is_empty());
+
+        let failed =
+            parse_authentication_results("example.org; spf=fail reason=\"no; match\"").unwrap();
+        assert_eq!(failed.results[0].result, "fail");
+        assert_eq!(failed.results[0].reason.as_deref(), Some("no; match"));
+    }
+
+    #[test]
+    fn test_summary_trusts_the_topmost_header() {
+        let header = |value: &str| RawHeader {
+            name: "Authentication-Results".to_string(),
+            value: value.to_string(),
+        };
+        let trusted = provider_authserv_ids(&ProviderType::Custom, "imap.example.net");
+        let headers = [
+            header(
+                "mx.example.net; spf=softfail smtp.mailfrom=bounce@Mailer.example; \
+                 dkim=fail header.d=other.example; dkim=pass header.i=@bank.example; \
+                 dmarc=fail header.from=bank.example",
+            ),
+            // Added before delivery, e.g. by the sender
+            header("bank.example; spf=pass; dkim=pass; dmarc=pass"),
+        ];
+        let summary = AuthenticationSummary::from_headers(&headers, &trusted).unwrap();
+
+        assert_eq!(summary.authserv_id, "mx.example.net");
+        assert_eq!(summary.spf.status, AuthStatus::Fail);
+        assert_eq!(summary.spf.domain.as_deref(), Some("mailer.example"));
+        assert_eq!(summary.dkim.status, AuthStatus::Pass);
+        assert_eq!(summary.dkim.domain.as_deref(), Some("bank.example"));
+        assert_eq!(summary.dmarc.status, AuthStatus::Fail);
+
+        let unchecked =
+            AuthenticationSummary::from_headers(&[header("mx.example.net; none")], &trusted);
+        assert_eq!(unchecked.unwrap().dmarc, AuthCheck::default());
+        assert_eq!(AuthenticationSummary::from_headers(&[], &trusted), None);
+    }
+
+    #[test]
+    fn test_summary_ignores_untrusted_servers() {
+        let header = |value: &str| RawHeader {
+            name: "Authentication-Results".to_string(),
+            value: value.to_string(),
+        };
+        let gmail = provider_authserv_ids(&ProviderType::Gmail, "imap.gmail.com");
+        // Only the sender's forged header: nothing to show
+        let forged = [header("mx.google.com.evil.example; spf=pass; dmarc=pass")];
+        assert_eq!(AuthenticationSummary::from_headers(&forged, &gmail), None);
+
+        // Headers from other servers are skipped
+        let headers = [
+            header("bank.example; spf=pass; dkim=pass; dmarc=pass"),
+            header("MX.Google.com; spf=fail smtp.mailfrom=bank.example"),
+        ];
+        let summary = AuthenticationSummary::from_headers(&headers, &gmail).unwrap();
+        assert_eq!(summary.spf.status, AuthStatus::Fail);
+
+        let outlook = &provider_authserv_ids(&ProviderType::Outlook, "outlook.office365.com")[0];
+        assert!(authserv_id_matches("spf.protection.outlook.com", outlook));
+        assert!(!authserv_id_matches("outlook.com", outlook));
+        assert!(!authserv_id_matches("evil-outlook.com", outlook));
+    }
+}
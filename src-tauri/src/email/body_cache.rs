@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use super::types::Email;
+
+/// Messages kept in memory when the cache settings don't say otherwise
+pub const DEFAULT_BODY_CACHE_SIZE: usize = 200;
+
+/// Fully fetched messages by composite ID, so reopening one doesn't fetch
+/// the body again. The least recently opened message is evicted first.
+/// Flags in the cached copies may be stale; callers refresh them.
+#[derive(Debug)]
+pub struct BodyCache {
+    inner: Mutex<Lru>,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    capacity: usize,
+    /// Bumped on every access; orders the entries by recency
+    tick: u64,
+    entries: HashMap<String, (Email, u64)>,
+    recency: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn touch(&mut self, id: &str) -> Option<&mut Email> {
+        self.tick += 1;
+        let (email, used) = self.entries.get_mut(id)?;
+        self.recency.remove(used);
+        self.recency.insert(self.tick, id.to_string());
+        *used = self.tick;
+        Some(email)
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Email> {
+        let (email, used) = self.entries.remove(id)?;
+        self.recency.remove(&used);
+        Some(email)
+    }
+
+    fn evict_to_capacity(&mut self) {
+        while self.entries.len() > self.capacity {
+            let Some((_, id)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&id);
+        }
+    }
+}
+
+impl BodyCache {
+    /// Hold up to `capacity` messages; 0 turns the cache off
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Lru {
+                capacity,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn get(&self, email_id: &str) -> Option<Email> {
+        let mut lru = self.inner.lock().unwrap();
+        lru.touch(email_id).cloned()
+    }
+
+    pub fn insert(&self, email: Email) {
+        let mut lru = self.inner.lock().unwrap();
+        if lru.capacity == 0 {
+            return;
+        }
+        let id = email.id.clone();
+        lru.remove(&id);
+        lru.tick += 1;
+        let tick = lru.tick;
+        lru.recency.insert(tick, id.clone());
+        lru.entries.insert(id, (email, tick));
+        lru.evict_to_capacity();
+    }
+
+    pub fn remove(&self, email_id: &str) {
+        self.inner.lock().unwrap().remove(email_id);
+    }
+
+    /// Drop the account's messages, or only those in `folder`
+    pub fn remove_folder(&self, account_id: &str, folder: Option<&str>) {
+        let mut lru = self.inner.lock().unwrap();
+        let doomed: Vec<String> = lru
+            .entries
+            .values()
+            .filter(|(email, _)| {
+                email.account_id == account_id
+                    && (folder.is_none() || folder == Some(email.folder.as_str()))
+            })
+            .map(|(email, _)| email.id.clone())
+            .collect();
+        for id in doomed {
+            lru.remove(&id);
+        }
+    }
+
+    /// Change the capacity, evicting the oldest messages if it shrank
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut lru = self.inner.lock().unwrap();
+        lru.capacity = capacity;
+        lru.evict_to_capacity();
+    }
+
+    /// Empty the cache and return how many messages it held
+    pub fn clear(&self) -> usize {
+        let mut lru = self.inner.lock().unwrap();
+        let count = lru.entries.len();
+        lru.entries.clear();
+        lru.recency.clear();
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(id: &str, folder: &str) -> Email {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "thread_id": id,
+            "subject": "",
+            "from": "",
+            "from_email": "",
+            "to": [],
+            "date": "",
+            "date_timestamp": 0,
+            "snippet": "",
+            "body_html": null,
+            "body_plain": "body",
+            "labels": [],
+            "is_read": true,
+            "is_starred": false,
+            "has_attachments": false,
+            "account_id": "acct",
+            "uid": 1,
+            "folder": folder,
+            "message_id": "",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_evicts_least_recently_opened() {
+        let cache = BodyCache::new(2);
+        cache.insert(email("acct:INBOX:1", "INBOX"));
+        cache.insert(email("acct:INBOX:2", "INBOX"));
+        // Opening 1 again makes 2 the oldest
+        assert!(cache.get("acct:INBOX:1").is_some());
+        cache.insert(email("acct:Work:3", "Work"));
+
+        assert!(cache.get("acct:INBOX:2").is_none());
+        assert!(cache.get("acct:INBOX:1").is_some());
+        assert!(cache.get("acct:Work:3").is_some());
+
+        cache.remove_folder("acct", Some("Work"));
+        assert!(cache.get("acct:Work:3").is_none());
+        cache.set_capacity(0);
+        cache.insert(email("acct:INBOX:4", "INBOX"));
+        assert_eq!(cache.clear(), 0);
+    }
+}
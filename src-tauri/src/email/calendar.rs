@@ -0,0 +1,772 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc, Weekday};
+use mail_parser::{Message, MessagePart, MimeHeaders};
+use serde::{Deserialize, Serialize};
+
+/// Sent as PRODID in the calendars we generate
+const PRODID: &str = "-//Inboxed//Inboxed Mail//EN";
+
+/// iTIP method of a calendar part (RFC 5546)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InviteMethod {
+    Request,
+    Cancel,
+    Reply,
+    /// No METHOD: an event shared for information, not an invite
+    Publish,
+    Other,
+}
+
+/// How the user answers an invite
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InviteResponse {
+    Accept,
+    Decline,
+    Tentative,
+}
+
+impl InviteResponse {
+    fn partstat(self) -> &'static str {
+        match self {
+            Self::Accept => "ACCEPTED",
+            Self::Decline => "DECLINED",
+            Self::Tentative => "TENTATIVE",
+        }
+    }
+
+    /// Subject prefix of the reply, as calendar clients word it
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Accept => "Accepted",
+            Self::Decline => "Declined",
+            Self::Tentative => "Tentative",
+        }
+    }
+}
+
+/// An organizer or attendee
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Participant {
+    pub email: String,
+    pub name: Option<String>,
+    /// PARTSTAT as sent, e.g. "NEEDS-ACTION" or "ACCEPTED"
+    pub status: Option<String>,
+}
+
+/// The basic fields of an RRULE
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Recurrence {
+    /// DAILY, WEEKLY, MONTHLY or YEARLY
+    pub freq: String,
+    pub interval: u32,
+    pub count: Option<u32>,
+    /// Unix seconds of the last occurrence allowed
+    pub until: Option<i64>,
+    /// BYDAY entries such as "MO" or "-1FR"
+    pub by_day: Vec<String>,
+    /// The rule as sent, for anything not broken out above
+    pub rule: String,
+}
+
+/// A meeting invite (or cancellation) found in a text/calendar part
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Invite {
+    pub method: InviteMethod,
+    pub uid: String,
+    pub sequence: u32,
+    pub summary: String,
+    pub organizer: Option<Participant>,
+    #[serde(default)]
+    pub attendees: Vec<Participant>,
+    /// Unix seconds; all-day events start at midnight UTC
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    #[serde(default)]
+    pub all_day: bool,
+    /// TZID the start time was given in
+    pub timezone: Option<String>,
+    pub location: Option<String>,
+    pub recurrence: Option<Recurrence>,
+    /// RECURRENCE-ID line when the invite is for one occurrence of a series;
+    /// echoed in replies
+    pub recurrence_id: Option<String>,
+}
+
+impl Invite {
+    /// The first calendar part of a parsed message that holds an event
+    pub fn from_message(message: &Message<'_>) -> Option<Self> {
+        message
+            .parts
+            .iter()
+            .filter(|part| is_calendar_part(part))
+            .find_map(|part| parse_invite(&String::from_utf8_lossy(part.contents())))
+    }
+
+    /// The iTIP REPLY telling the organizer how `attendee` answered
+    pub fn reply(
+        &self,
+        attendee: &Participant,
+        response: InviteResponse,
+        now: DateTime<Utc>,
+    ) -> String {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            format!("PRODID:{}", PRODID),
+            "VERSION:2.0".to_string(),
+            "METHOD:REPLY".to_string(),
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", self.uid),
+        ];
+        lines.extend(self.recurrence_id.clone());
+        lines.push(format!("SEQUENCE:{}", self.sequence));
+        lines.push(format!("DTSTAMP:{}", format_utc(now.timestamp())));
+        for (name, time) in [("DTSTART", self.start), ("DTEND", self.end)] {
+            match time {
+                Some(time) if self.all_day => lines.push(format!(
+                    "{};VALUE=DATE:{}",
+                    name,
+                    DateTime::from_timestamp(time, 0)
+                        .unwrap_or_default()
+                        .format("%Y%m%d")
+                )),
+                Some(time) => lines.push(format!("{}:{}", name, format_utc(time))),
+                None => {}
+            }
+        }
+        lines.push(format!("SUMMARY:{}", escape_text(&self.summary)));
+        if let Some(organizer) = &self.organizer {
+            lines.push(participant_line("ORGANIZER", organizer, None));
+        }
+        lines.push(participant_line(
+            "ATTENDEE",
+            attendee,
+            Some(response.partstat()),
+        ));
+        lines.push("END:VEVENT".to_string());
+        lines.push("END:VCALENDAR".to_string());
+
+        lines.into_iter().map(|line| fold(&line)).collect()
+    }
+}
+
+/// text/calendar, or the application/ics some clients attach instead
+fn is_calendar_part(part: &MessagePart<'_>) -> bool {
+    part.content_type().is_some_and(|content_type| {
+        let subtype = content_type.subtype().unwrap_or("");
+        (content_type.ctype().eq_ignore_ascii_case("text")
+            && subtype.eq_ignore_ascii_case("calendar"))
+            || (content_type.ctype().eq_ignore_ascii_case("application")
+                && subtype.eq_ignore_ascii_case("ics"))
+    })
+}
+
+/// One unfolded content line: `NAME;PARAM=value:value`
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        // The value starts at the first ':' outside a quoted parameter
+        let mut quoted = false;
+        let (colon, _) = line.char_indices().find(|&(_, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == ':' && !quoted
+        })?;
+        let mut head = split_unquoted(&line[..colon], ';').into_iter();
+        let name = head.next()?.trim().to_ascii_uppercase();
+        let params = head
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                Some((
+                    key.trim().to_ascii_uppercase(),
+                    value.trim_matches('"').to_string(),
+                ))
+            })
+            .collect();
+        Some(Self {
+            name,
+            params,
+            value: line[colon + 1..].to_string(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Back to a content line, as it was sent
+    fn to_line(&self) -> String {
+        let mut line = self.name.clone();
+        for (key, value) in &self.params {
+            if value.contains([':', ';', ',']) {
+                line.push_str(&format!(";{}=\"{}\"", key, value));
+            } else {
+                line.push_str(&format!(";{}={}", key, value));
+            }
+        }
+        line.push(':');
+        line.push_str(&self.value);
+        line
+    }
+}
+
+/// A STANDARD or DAYLIGHT block of a VTIMEZONE
+#[derive(Debug)]
+struct Observance {
+    start: NaiveDateTime,
+    offset_from: i32,
+    offset_to: i32,
+    rule: Option<YearlyRule>,
+}
+
+/// The yearly onset of an observance, e.g. the last Sunday in October
+#[derive(Debug)]
+struct YearlyRule {
+    month: u32,
+    /// Which weekday of the month; negative counts from the end
+    nth: i32,
+    weekday: Weekday,
+    until: Option<NaiveDateTime>,
+}
+
+impl Observance {
+    fn from_properties(properties: &[Property]) -> Option<Self> {
+        let get = |name: &str| properties.iter().find(|p| p.name == name);
+        let start = parse_local(&get("DTSTART")?.value)?;
+        let offset_to = parse_offset(&get("TZOFFSETTO")?.value)?;
+        let offset_from = get("TZOFFSETFROM")
+            .and_then(|p| parse_offset(&p.value))
+            .unwrap_or(offset_to);
+        let rule = get("RRULE").and_then(|p| YearlyRule::parse(&p.value));
+        Some(Self {
+            start,
+            offset_from,
+            offset_to,
+            rule,
+        })
+    }
+
+    /// When this observance last took effect at or before `local`
+    fn last_onset(&self, local: NaiveDateTime) -> Option<NaiveDateTime> {
+        let Some(rule) = &self.rule else {
+            return (self.start <= local).then_some(self.start);
+        };
+        [local.year(), local.year() - 1]
+            .into_iter()
+            .filter_map(|year| rule.onset(year, self.start.time()))
+            .filter(|onset| {
+                *onset >= self.start
+                    && *onset <= local
+                    && rule.until.is_none_or(|until| *onset <= until)
+            })
+            .max()
+    }
+}
+
+impl YearlyRule {
+    fn parse(rrule: &str) -> Option<Self> {
+        let parts = rule_parts(rrule);
+        if parts.get("FREQ").map(String::as_str) != Some("YEARLY") {
+            return None;
+        }
+        let month = parts.get("BYMONTH")?.parse().ok()?;
+        let by_day = parts.get("BYDAY")?;
+        let (nth, weekday) = by_day.split_at_checked(by_day.len().checked_sub(2)?)?;
+        Some(Self {
+            month,
+            nth: nth.trim_start_matches('+').parse().ok()?,
+            weekday: parse_weekday(weekday)?,
+            until: parts.get("UNTIL").and_then(|until| parse_local(until)),
+        })
+    }
+
+    fn onset(&self, year: i32, time: NaiveTime) -> Option<NaiveDateTime> {
+        let weekday = self.weekday.num_days_from_monday();
+        let date = if self.nth > 0 {
+            let first = NaiveDate::from_ymd_opt(year, self.month, 1)?;
+            let skip = (7 + weekday - first.weekday().num_days_from_monday()) % 7;
+            first + TimeDelta::days((skip + 7 * (self.nth as u32 - 1)) as i64)
+        } else {
+            let next_month = match self.month {
+                12 => NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+                month => NaiveDate::from_ymd_opt(year, month + 1, 1)?,
+            };
+            let last = next_month.pred_opt()?;
+            let back = (7 + last.weekday().num_days_from_monday() - weekday) % 7;
+            last - TimeDelta::days((back + 7 * (self.nth.unsigned_abs() - 1)) as i64)
+        };
+        (date.month() == self.month).then(|| date.and_time(time))
+    }
+}
+
+/// Parse the first VEVENT of an iCalendar object. When a series comes with
+/// overridden occurrences, the series itself is picked.
+pub fn parse_invite(ics: &str) -> Option<Invite> {
+    let mut method = None;
+    let mut stack: Vec<String> = Vec::new();
+    let mut events: Vec<Vec<Property>> = Vec::new();
+    let mut zones: HashMap<String, Vec<Observance>> = HashMap::new();
+    let mut zone: (Option<String>, Vec<Observance>) = (None, Vec::new());
+    let mut observance: Vec<Property> = Vec::new();
+
+    for line in unfold(ics) {
+        let Some(property) = Property::parse(&line) else {
+            continue;
+        };
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.trim().to_ascii_uppercase();
+                if component == "VEVENT" && stack.len() == 1 {
+                    events.push(Vec::new());
+                }
+                stack.push(component);
+                continue;
+            }
+            "END" => {
+                match stack.pop().as_deref() {
+                    Some("STANDARD" | "DAYLIGHT") => {
+                        zone.1.extend(Observance::from_properties(&observance));
+                        observance.clear();
+                    }
+                    Some("VTIMEZONE") => {
+                        let (tzid, observances) = std::mem::take(&mut zone);
+                        if let Some(tzid) = tzid {
+                            zones.insert(tzid, observances);
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let path: Vec<&str> = stack.iter().map(String::as_str).collect();
+        match path.as_slice() {
+            ["VCALENDAR"] if property.name == "METHOD" => {
+                method = Some(property.value.trim().to_ascii_uppercase());
+            }
+            ["VCALENDAR", "VEVENT"] => {
+                if let Some(event) = events.last_mut() {
+                    event.push(property);
+                }
+            }
+            ["VCALENDAR", "VTIMEZONE"] if property.name == "TZID" => {
+                zone.0 = Some(property.value.trim().to_string());
+            }
+            ["VCALENDAR", "VTIMEZONE", _] => observance.push(property),
+            _ => {}
+        }
+    }
+
+    let event = events
+        .iter()
+        .find(|event| !event.iter().any(|p| p.name == "RECURRENCE-ID"))
+        .or(events.first())?;
+    let get = |name: &str| event.iter().find(|p| p.name == name);
+
+    let method = match method.as_deref() {
+        Some("REQUEST") => InviteMethod::Request,
+        Some("CANCEL") => InviteMethod::Cancel,
+        Some("REPLY") => InviteMethod::Reply,
+        Some("PUBLISH") | None => InviteMethod::Publish,
+        Some(_) => InviteMethod::Other,
+    };
+    let dtstart = get("DTSTART");
+    let start = dtstart.and_then(|p| resolve_time(p, &zones));
+    let all_day = start.is_some_and(|(_, all_day)| all_day);
+    let start = start.map(|(time, _)| time);
+    let end = match (get("DTEND"), get("DURATION"), start) {
+        (Some(dtend), _, _) => resolve_time(dtend, &zones).map(|(time, _)| time),
+        (None, Some(duration), Some(start)) => parse_duration(&duration.value).map(|d| start + d),
+        // An all-day event without an end lasts the day
+        (None, None, Some(start)) if all_day => Some(start + 24 * 60 * 60),
+        _ => None,
+    };
+
+    Some(Invite {
+        method,
+        uid: get("UID")?.value.trim().to_string(),
+        sequence: get("SEQUENCE")
+            .and_then(|p| p.value.trim().parse().ok())
+            .unwrap_or(0),
+        summary: get("SUMMARY")
+            .map(|p| unescape_text(&p.value))
+            .unwrap_or_default(),
+        organizer: get("ORGANIZER").map(participant),
+        attendees: event
+            .iter()
+            .filter(|p| p.name == "ATTENDEE")
+            .map(participant)
+            .collect(),
+        start,
+        end,
+        all_day,
+        timezone: dtstart.and_then(|p| p.param("TZID")).map(str::to_string),
+        location: get("LOCATION")
+            .map(|p| unescape_text(&p.value))
+            .filter(|location| !location.is_empty()),
+        recurrence: get("RRULE").map(|p| recurrence(&p.value)),
+        recurrence_id: get("RECURRENCE-ID").map(Property::to_line),
+    })
+}
+
+/// Join continuation lines (starting with a space or tab) onto the line before
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Fold a content line at 75 octets and end it with CRLF
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&text[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n' | 'N')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some(escaped @ ('\\' | ',' | ';'))) => {
+                out.push(escaped);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn participant(property: &Property) -> Participant {
+    let value = property.value.trim();
+    let email = match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &value[7..],
+        _ => value,
+    };
+    Participant {
+        email: email.to_string(),
+        name: property
+            .param("CN")
+            .filter(|name| !name.is_empty())
+            .map(str::to_string),
+        status: property.param("PARTSTAT").map(str::to_ascii_uppercase),
+    }
+}
+
+fn participant_line(name: &str, participant: &Participant, partstat: Option<&str>) -> String {
+    let mut line = name.to_string();
+    if let Some(partstat) = partstat {
+        line.push_str(&format!(";PARTSTAT={}", partstat));
+    }
+    if let Some(cn) = &participant.name {
+        line.push_str(&format!(";CN=\"{}\"", cn.replace('"', "")));
+    }
+    line.push_str(&format!(":mailto:{}", participant.email));
+    line
+}
+
+fn recurrence(rrule: &str) -> Recurrence {
+    let parts = rule_parts(rrule);
+    Recurrence {
+        freq: parts.get("FREQ").cloned().unwrap_or_default(),
+        interval: parts
+            .get("INTERVAL")
+            .and_then(|interval| interval.parse().ok())
+            .unwrap_or(1),
+        count: parts.get("COUNT").and_then(|count| count.parse().ok()),
+        until: parts
+            .get("UNTIL")
+            .and_then(|until| parse_local(until))
+            .map(|until| until.and_utc().timestamp()),
+        by_day: parts
+            .get("BYDAY")
+            .map(|days| days.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+        rule: rrule.trim().to_string(),
+    }
+}
+
+/// `FREQ=YEARLY;BYMONTH=10` as a map with upper-cased keys
+fn rule_parts(rrule: &str) -> HashMap<String, String> {
+    rrule
+        .trim()
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .map(|(key, value)| (key.to_ascii_uppercase(), value.to_ascii_uppercase()))
+        .collect()
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    Some(match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// A DATE or DATE-TIME value without regard to its zone; dates are midnight
+fn parse_local(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim().trim_end_matches(['Z', 'z']);
+    match value.len() {
+        8 => NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0),
+        _ => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok(),
+    }
+}
+
+/// `+0100` or `-0530` in seconds east of UTC
+fn parse_offset(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let (sign, digits) = match value.split_at_checked(1)? {
+        ("-", digits) => (-1, digits),
+        ("+", digits) => (1, digits),
+        _ => return None,
+    };
+    let hours: i32 = digits.get(..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..4)?.parse().ok()?;
+    let seconds: i32 = digits.get(4..6).and_then(|s| s.parse().ok()).unwrap_or(0);
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// `PT1H30M`, `P1D`, `-PT15M` in seconds
+fn parse_duration(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.trim_start_matches('+')),
+    };
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        let unit = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'T' => continue,
+            'W' => 7 * 24 * 60 * 60,
+            'D' => 24 * 60 * 60,
+            'H' => 60 * 60,
+            'M' => 60,
+            'S' => 1,
+            _ => return None,
+        };
+        seconds += number.parse::<i64>().ok()? * unit;
+        number.clear();
+    }
+    Some(sign * seconds)
+}
+
+/// Unix seconds of a DTSTART/DTEND, and whether it was a bare date. Local
+/// times are resolved with the message's VTIMEZONE for their TZID; floating
+/// times and zones the message doesn't define are taken as UTC.
+fn resolve_time(
+    property: &Property,
+    zones: &HashMap<String, Vec<Observance>>,
+) -> Option<(i64, bool)> {
+    let value = property.value.trim();
+    let local = parse_local(value)?;
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return Some((local.and_utc().timestamp(), true));
+    }
+    if value.ends_with(['Z', 'z']) {
+        return Some((local.and_utc().timestamp(), false));
+    }
+    let offset = property
+        .param("TZID")
+        .and_then(|tzid| zones.get(tzid))
+        .map(|observances| utc_offset(observances, local))
+        .unwrap_or(0);
+    Some((local.and_utc().timestamp() - offset as i64, false))
+}
+
+/// The offset in effect at `local`: that of the observance that most
+/// recently began. Before any of them, the earliest one's TZOFFSETFROM.
+fn utc_offset(observances: &[Observance], local: NaiveDateTime) -> i32 {
+    observances
+        .iter()
+        .filter_map(|o| o.last_onset(local).map(|onset| (onset, o.offset_to)))
+        .max_by_key(|(onset, _)| *onset)
+        .map(|(_, offset)| offset)
+        .or_else(|| {
+            observances
+                .iter()
+                .min_by_key(|o| o.start)
+                .map(|o| o.offset_from)
+        })
+        .unwrap_or(0)
+}
+
+fn format_utc(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVITE: &str = "BEGIN:VCALENDAR\r\n\
+        PRODID:-//Google Inc//Google Calendar 70.9054//EN\r\n\
+        VERSION:2.0\r\n\
+        METHOD:REQUEST\r\n\
+        BEGIN:VTIMEZONE\r\n\
+        TZID:Europe/Berlin\r\n\
+        BEGIN:DAYLIGHT\r\n\
+        TZOFFSETFROM:+0100\r\n\
+        TZOFFSETTO:+0200\r\n\
+        DTSTART:19700329T020000\r\n\
+        RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r\n\
+        END:DAYLIGHT\r\n\
+        BEGIN:STANDARD\r\n\
+        TZOFFSETFROM:+0200\r\n\
+        TZOFFSETTO:+0100\r\n\
+        DTSTART:19701025T030000\r\n\
+        RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r\n\
+        END:STANDARD\r\n\
+        END:VTIMEZONE\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART;TZID=Europe/Berlin:20260310T100000\r\n\
+        DTEND;TZID=Europe/Berlin:20260310T103000\r\n\
+        RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,TH;UNTIL=20260630T000000Z\r\n\
+        UID:abc123@google.com\r\n\
+        SEQUENCE:2\r\n\
+        ORGANIZER;CN=Alice Example:mailto:alice@example.com\r\n\
+        ATTENDEE;CUTYPE=INDIVIDUAL;ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;CN=\"Bob\r\n \
+        , the builder\":mailto:bob@example.com\r\n\
+        SUMMARY:Design review\\, round 2\r\n\
+        LOCATION:Room 4\\; 2nd floor\r\n\
+        BEGIN:VALARM\r\n\
+        ACTION:DISPLAY\r\n\
+        SUMMARY:Reminder\r\n\
+        END:VALARM\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART;TZID=Europe/Berlin:20260407T110000\r\n\
+        RECURRENCE-ID;TZID=Europe/Berlin:20260407T100000\r\n\
+        UID:abc123@google.com\r\n\
+        SUMMARY:Design review (moved)\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn test_parses_invite_and_builds_reply() {
+        let invite = parse_invite(INVITE).unwrap();
+
+        assert_eq!(invite.method, InviteMethod::Request);
+        assert_eq!(invite.uid, "abc123@google.com");
+        assert_eq!(invite.sequence, 2);
+        // The series, not the moved occurrence
+        assert_eq!(invite.summary, "Design review, round 2");
+        assert_eq!(invite.location.as_deref(), Some("Room 4; 2nd floor"));
+        assert_eq!(invite.recurrence_id, None);
+        // 10:00 in Berlin before DST starts (March 29th) is 09:00 UTC
+        assert_eq!(invite.start, Some(1773133200));
+        assert_eq!(invite.end, Some(1773133200 + 30 * 60));
+        assert!(!invite.all_day);
+        assert_eq!(invite.timezone.as_deref(), Some("Europe/Berlin"));
+        let organizer = invite.organizer.as_ref().unwrap();
+        assert_eq!(organizer.email, "alice@example.com");
+        assert_eq!(organizer.name.as_deref(), Some("Alice Example"));
+        assert_eq!(
+            invite.attendees,
+            vec![Participant {
+                email: "bob@example.com".to_string(),
+                name: Some("Bob, the builder".to_string()),
+                status: Some("NEEDS-ACTION".to_string()),
+            }]
+        );
+        let recurrence = invite.recurrence.as_ref().unwrap();
+        assert_eq!(recurrence.freq, "WEEKLY");
+        assert_eq!(recurrence.interval, 2);
+        assert_eq!(recurrence.by_day, ["TU", "TH"]);
+        assert_eq!(recurrence.until, Some(1782777600));
+
+        // Summer time applies from the last Sunday in March
+        let summer = INVITE.replace("20260310T10", "20260331T10");
+        assert_eq!(parse_invite(&summer).unwrap().start, Some(1774944000));
+
+        let now = DateTime::from_timestamp(1772000000, 0).unwrap();
+        let reply = invite.reply(&invite.attendees[0], InviteResponse::Accept, now);
+        let parsed = parse_invite(&reply).unwrap();
+        assert_eq!(parsed.method, InviteMethod::Reply);
+        assert_eq!(parsed.uid, invite.uid);
+        assert_eq!(parsed.sequence, 2);
+        assert_eq!(parsed.start, invite.start);
+        assert_eq!(parsed.summary, invite.summary);
+        assert_eq!(parsed.organizer, invite.organizer);
+        assert_eq!(parsed.attendees[0].status.as_deref(), Some("ACCEPTED"));
+        assert_eq!(
+            parsed.attendees[0].name.as_deref(),
+            Some("Bob, the builder")
+        );
+        assert!(reply.contains("DTSTAMP:20260225T061320Z\r\n"));
+        assert!(reply.lines().all(|line| line.len() <= 75));
+    }
+}
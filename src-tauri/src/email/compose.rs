@@ -0,0 +1,413 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::quoting::{html_quote_start, plain_quote_start};
+use super::types::{Email, EmailAddress};
+
+/// Standard "dash dash space" line that introduces a signature
+const SIGNATURE_DELIMITER: &str = "-- \n";
+
+/// To/Cc of a reply, formatted for `send_email`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReplyRecipients {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+}
+
+/// Recipients for a reply to `original`. The reply goes to Reply-To when the
+/// original has one, otherwise to From; reply-all copies the original To and
+/// Cc. The user's own addresses (`self_addresses`, e.g. the account address
+/// and its aliases) are left out, and each address appears once.
+/// Replying to a message the user sent goes to its original recipients.
+pub fn build_reply_recipients(
+    original: &Email,
+    reply_all: bool,
+    self_addresses: &[String],
+) -> ReplyRecipients {
+    let own: HashSet<String> = self_addresses
+        .iter()
+        .flat_map(|value| parse_address_list(value))
+        .map(|addr| addr.address.to_lowercase())
+        .collect();
+    let mut seen = own.clone();
+    let mut take = |addresses: Vec<EmailAddress>| -> Vec<String> {
+        addresses
+            .into_iter()
+            .filter(|addr| seen.insert(addr.address.to_lowercase()))
+            .map(|addr| format_address(&addr))
+            .collect()
+    };
+
+    let mut sender = if original.reply_to.is_empty() {
+        parse_address_list(&original.from)
+    } else {
+        parse_list(&original.reply_to)
+    };
+    if sender.is_empty() && original.from_email.contains('@') {
+        sender.push(EmailAddress {
+            name: None,
+            address: original.from_email.clone(),
+        });
+    }
+    let sent_by_self = sender
+        .iter()
+        .all(|addr| own.contains(&addr.address.to_lowercase()));
+
+    let original_to = parse_list(&original.to);
+    let mut to = take(sender);
+    let mut cc = Vec::new();
+    if to.is_empty() && sent_by_self {
+        to = take(original_to);
+    } else if reply_all {
+        cc = take(original_to);
+    }
+    if reply_all {
+        cc.extend(take(parse_list(&original.cc)));
+    }
+
+    ReplyRecipients { to, cc }
+}
+
+fn parse_list(values: &[String]) -> Vec<EmailAddress> {
+    values
+        .iter()
+        .flat_map(|value| parse_address_list(value))
+        .collect()
+}
+
+/// Parse an RFC 5322 address list: `Name <addr>`, bare addresses, quoted
+/// names containing commas, `addr (Comment)` and groups like
+/// `Team: a@example.com, b@example.com;`. Entries without an address are
+/// dropped.
+pub fn parse_address_list(value: &str) -> Vec<EmailAddress> {
+    let mut addresses = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut comment_depth = 0usize;
+    let mut in_angle = false;
+
+    for c in value.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes || comment_depth > 0 => escaped = true,
+            '"' if comment_depth == 0 => in_quotes = !in_quotes,
+            '(' if !in_quotes => comment_depth += 1,
+            ')' if !in_quotes && comment_depth > 0 => comment_depth -= 1,
+            '<' if !in_quotes && comment_depth == 0 => in_angle = true,
+            '>' if !in_quotes && comment_depth == 0 => in_angle = false,
+            ':' if !in_quotes && comment_depth == 0 && !in_angle => {
+                // A group's display name; its members follow
+                current.clear();
+                continue;
+            }
+            ',' | ';' if !in_quotes && comment_depth == 0 && !in_angle => {
+                addresses.extend(parse_mailbox(&current));
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        // Quotes and escapes are kept for parse_mailbox
+        current.push(c);
+    }
+    addresses.extend(parse_mailbox(&current));
+    addresses
+}
+
+/// Parse one mailbox: `"Name" <addr>`, `Name <addr>` or `addr (Name)`
+fn parse_mailbox(value: &str) -> Option<EmailAddress> {
+    let (text, comment) = strip_comments(value);
+    let text = text.trim();
+
+    let (name, address) = match (text.rfind('<'), text.rfind('>')) {
+        (Some(open), Some(close)) if open < close => {
+            let name = unquote(text[..open].trim());
+            (name, text[open + 1..close].trim().to_string())
+        }
+        _ => (String::new(), text.to_string()),
+    };
+    if !address.contains('@') || address.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let name = if name.is_empty() { comment } else { name };
+    Some(EmailAddress {
+        name: Some(name).filter(|n| !n.is_empty() && !n.eq_ignore_ascii_case(&address)),
+        address,
+    })
+}
+
+/// Split `(comments)` outside quotes from the rest of a mailbox
+fn strip_comments(value: &str) -> (String, String) {
+    let mut text = String::new();
+    let mut comment = String::new();
+    let mut depth = 0usize;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        let target = if depth > 0 { &mut comment } else { &mut text };
+        if escaped {
+            target.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes || depth > 0 => {
+                escaped = true;
+                // Quoted names keep their escapes for unquote
+                if depth == 0 {
+                    text.push(c);
+                }
+            }
+            '"' if depth == 0 => {
+                in_quotes = !in_quotes;
+                text.push(c);
+            }
+            '(' if !in_quotes => {
+                if depth > 0 {
+                    comment.push(c);
+                }
+                depth += 1;
+            }
+            ')' if !in_quotes && depth > 0 => {
+                depth -= 1;
+                if depth > 0 {
+                    comment.push(c);
+                }
+            }
+            _ if depth > 0 => comment.push(c),
+            _ => text.push(c),
+        }
+    }
+    (text, comment.trim().to_string())
+}
+
+/// Display name without its surrounding quotes and backslash escapes
+fn unquote(name: &str) -> String {
+    let Some(quoted) = name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) else {
+        return name.to_string();
+    };
+    let mut unescaped = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped.trim().to_string()
+}
+
+/// `Name <addr>`, quoting the name when it contains characters that would
+/// otherwise split or end it
+fn format_address(addr: &EmailAddress) -> String {
+    match &addr.name {
+        Some(name) if name.contains(|c| ",;:<>@\"()[]\\".contains(c)) => format!(
+            "\"{}\" <{}>",
+            name.replace('\\', "\\\\").replace('"', "\\\""),
+            addr.address
+        ),
+        Some(name) => format!("{} <{}>", name, addr.address),
+        None => addr.address.clone(),
+    }
+}
+
+/// Plain and HTML bodies of an outgoing message with the account signature
+/// added. The plain part is left alone when only HTML was written (it's
+/// generated from the HTML later); the HTML part falls back to the plain
+/// signature when there is no HTML one.
+pub fn sign_bodies(
+    body: &str,
+    html_body: &str,
+    signature: Option<&str>,
+    html_signature: Option<&str>,
+) -> (String, String) {
+    let signature = signature.filter(|sig| !sig.trim().is_empty());
+    let html_signature = html_signature
+        .filter(|sig| !sig.trim().is_empty())
+        .map(str::to_string)
+        .or_else(|| signature.map(plain_to_html));
+
+    let body = match signature {
+        Some(sig) if !body.is_empty() || html_body.is_empty() => append_signature(body, sig),
+        _ => body.to_string(),
+    };
+    let html_body = match html_signature {
+        Some(sig) if !html_body.is_empty() => append_html_signature(html_body, &sig),
+        _ => html_body.to_string(),
+    };
+    (body, html_body)
+}
+
+/// Add `signature` to a plain text body after the `-- ` delimiter. In a
+/// reply it goes above the quoted text rather than at the very bottom.
+/// A body that already carries the signature (e.g. a reopened draft) is
+/// returned unchanged.
+pub fn append_signature(body: &str, signature: &str) -> String {
+    let signature = signature
+        .strip_prefix(SIGNATURE_DELIMITER)
+        .unwrap_or(signature);
+    let block = format!("{}{}", SIGNATURE_DELIMITER, signature.trim_matches('\n'));
+    let (reply, quoted) = body.split_at(plain_quote_start(body).unwrap_or(body.len()));
+    if reply.contains(&block) {
+        return body.to_string();
+    }
+
+    let reply = reply.trim_end();
+    let mut signed = String::new();
+    if !reply.is_empty() {
+        signed.push_str(reply);
+        signed.push_str("\n\n");
+    }
+    signed.push_str(&block);
+    if !quoted.is_empty() {
+        signed.push_str("\n\n");
+        signed.push_str(quoted);
+    }
+    signed
+}
+
+/// Add an HTML signature inside the body: before the quoted block of a
+/// reply, otherwise just before `</body>`
+pub fn append_html_signature(html: &str, signature: &str) -> String {
+    let block = format!("<div class=\"signature\">-- <br>{}</div>", signature.trim());
+    if html.contains(&block) {
+        return html.to_string();
+    }
+    let at = html_quote_start(html)
+        .or_else(|| html.to_ascii_lowercase().rfind("</body>"))
+        .unwrap_or(html.len());
+    format!("{}{}{}", &html[..at], block, &html[at..])
+}
+
+/// Escape a plain text signature for HTML, keeping its line breaks
+fn plain_to_html(signature: &str) -> String {
+    signature
+        .trim_matches('\n')
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(from: &str, reply_to: &[&str], to: &[&str], cc: &[&str]) -> Email {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        let mut email: Email = serde_json::from_value(serde_json::json!({
+            "id": "acct:INBOX:1", "thread_id": "t", "subject": "Plans", "from": from,
+            "from_email": "", "to": [], "date": "", "date_timestamp": 0, "snippet": "",
+            "body_html": null, "body_plain": null, "labels": [], "is_read": false,
+            "is_starred": false, "has_attachments": false, "account_id": "acct", "uid": 1,
+            "folder": "INBOX", "message_id": "m@example.com"
+        }))
+        .unwrap();
+        email.reply_to = strings(reply_to);
+        email.to = strings(to);
+        email.cc = strings(cc);
+        email
+    }
+
+    #[test]
+    fn test_parse_groups_comments_and_quoted_names() {
+        let parsed = parse_address_list(
+            "\"Doe, Jane\" <jane@example.com>, Team: a@example.com, bob@example.com (Bob);, \
+             undisclosed-recipients:;, \"J \\\"JJ\\\" Smith\" <jj@example.com>",
+        );
+        let addresses: Vec<_> = parsed.iter().map(|a| a.address.as_str()).collect();
+        assert_eq!(
+            addresses,
+            [
+                "jane@example.com",
+                "a@example.com",
+                "bob@example.com",
+                "jj@example.com"
+            ]
+        );
+        assert_eq!(parsed[0].name.as_deref(), Some("Doe, Jane"));
+        assert_eq!(parsed[2].name.as_deref(), Some("Bob"));
+        assert_eq!(parsed[3].name.as_deref(), Some("J \"JJ\" Smith"));
+        assert_eq!(
+            format_address(&parsed[0]),
+            "\"Doe, Jane\" <jane@example.com>"
+        );
+    }
+
+    #[test]
+    fn test_reply_all_prefers_reply_to_and_drops_own_addresses() {
+        let original = email(
+            "Alice <alice@example.com>",
+            &["List <list@example.com>"],
+            &[
+                "Me <ME@example.com>",
+                "carol@example.com",
+                "list@example.com",
+            ],
+            &[
+                "alias@example.com",
+                "Carol <carol@example.com>",
+                "dave@example.com",
+            ],
+        );
+        let own = [
+            "me@example.com".to_string(),
+            "alias@example.com".to_string(),
+        ];
+
+        let reply = build_reply_recipients(&original, false, &own);
+        assert_eq!(reply.to, ["List <list@example.com>"]);
+        assert!(reply.cc.is_empty());
+
+        let reply_all = build_reply_recipients(&original, true, &own);
+        assert_eq!(reply_all.to, ["List <list@example.com>"]);
+        assert_eq!(reply_all.cc, ["carol@example.com", "dave@example.com"]);
+    }
+
+    #[test]
+    fn test_reply_to_own_message_goes_to_original_recipients() {
+        let original = email(
+            "Me <me@example.com>",
+            &[],
+            &["bob@example.com"],
+            &["eve@example.com"],
+        );
+        let own = ["me@example.com".to_string()];
+
+        let reply = build_reply_recipients(&original, true, &own);
+        assert_eq!(reply.to, ["bob@example.com"]);
+        assert_eq!(reply.cc, ["eve@example.com"]);
+    }
+
+    #[test]
+    fn test_signature_goes_above_quoted_text() {
+        let reply = "Sounds good.\n\nOn Mon, Ana wrote:\n> Ship it?";
+        let signed = append_signature(reply, "Sam\nAcme");
+        assert_eq!(
+            signed,
+            "Sounds good.\n\n-- \nSam\nAcme\n\nOn Mon, Ana wrote:\n> Ship it?"
+        );
+        // Signing again (a saved draft being sent) doesn't add a second copy
+        assert_eq!(append_signature(&signed, "Sam\nAcme"), signed);
+
+        let (body, html) = sign_bodies(
+            "",
+            "<html><body><p>Yes</p><blockquote>old</blockquote></body></html>",
+            Some("Sam <sam@acme.com>"),
+            None,
+        );
+        assert_eq!(body, "");
+        assert_eq!(
+            html,
+            "<html><body><p>Yes</p><div class=\"signature\">-- <br>Sam &lt;sam@acme.com&gt;</div>\
+             <blockquote>old</blockquote></body></html>"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::error::LoginRejected;
+use super::imap_client::{ImapClient, ImapCredentials};
+use super::server_presets::{detect_provider, ServerConfig};
+use super::smtp::{SmtpSendError, SmtpSendOptions};
+use super::transport::{is_timeout, ImapTimeouts};
+
+/// Longest each step of a check may take, so a dead host fails fast
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why a connection check failed. Serializes as
+/// `{ "code": "bad_credentials", "message": "..." }`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub enum ConnectionProblem {
+    /// The server refused the login
+    BadCredentials(String),
+    /// The TLS certificate wasn't trusted (self-signed, expired, wrong host)
+    Certificate(String),
+    /// DNS lookup failed or the connection was refused or reset
+    HostUnreachable(String),
+    /// No answer within the check's time limit
+    Timeout(String),
+    Other(String),
+}
+
+/// Result of checking one protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolCheck {
+    pub ok: bool,
+    /// Advertised capabilities; empty for SMTP, whose EHLO keywords lettre
+    /// doesn't expose
+    pub capabilities: Vec<String>,
+    pub error: Option<ConnectionProblem>,
+}
+
+impl ProtocolCheck {
+    fn passed(capabilities: Vec<String>) -> Self {
+        Self {
+            ok: true,
+            capabilities,
+            error: None,
+        }
+    }
+
+    fn failed(error: ConnectionProblem) -> Self {
+        Self {
+            ok: false,
+            capabilities: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionCheck {
+    pub imap: ProtocolCheck,
+    pub smtp: ProtocolCheck,
+}
+
+/// Connect and authenticate to both servers without saving anything,
+/// checking IMAP and SMTP at the same time
+pub async fn check_account(
+    server_config: ServerConfig,
+    credentials: ImapCredentials,
+) -> ConnectionCheck {
+    let mut client = ImapClient::new(
+        "connection-check".to_string(),
+        credentials.user().to_string(),
+        detect_provider(credentials.user()),
+        server_config,
+        credentials,
+    )
+    .with_timeouts(ImapTimeouts {
+        connect: CHECK_TIMEOUT,
+        read: CHECK_TIMEOUT,
+        write: CHECK_TIMEOUT,
+    });
+    client.smtp_options = SmtpSendOptions {
+        connect_timeout: CHECK_TIMEOUT,
+        auth_timeout: CHECK_TIMEOUT,
+        ..SmtpSendOptions::default()
+    };
+
+    let (imap, smtp) = futures::join!(check_imap(&client), check_smtp(&client));
+    client.disconnect().await;
+    ConnectionCheck { imap, smtp }
+}
+
+/// Log in, then CAPABILITY and NOOP
+pub async fn check_imap(client: &ImapClient) -> ProtocolCheck {
+    match client.check_connection().await {
+        Ok(capabilities) => ProtocolCheck::passed(capabilities),
+        Err(e) => ProtocolCheck::failed(classify_imap(&e)),
+    }
+}
+
+pub async fn check_smtp(client: &ImapClient) -> ProtocolCheck {
+    match client.check_smtp().await {
+        Ok(()) => ProtocolCheck::passed(Vec::new()),
+        Err(e) => ProtocolCheck::failed(classify_smtp(&e)),
+    }
+}
+
+fn classify_imap(err: &anyhow::Error) -> ConnectionProblem {
+    use async_imap::error::Error;
+
+    let message = format!("{:#}", err);
+    if is_timeout(err) {
+        return ConnectionProblem::Timeout(message);
+    }
+    if message.to_lowercase().contains("certificate") {
+        return ConnectionProblem::Certificate(message);
+    }
+    for cause in err.chain() {
+        if let Some(LoginRejected(Error::No(_) | Error::Bad(_))) =
+            cause.downcast_ref::<LoginRejected>()
+        {
+            return ConnectionProblem::BadCredentials(message);
+        }
+        if cause.is::<std::io::Error>() {
+            return ConnectionProblem::HostUnreachable(message);
+        }
+    }
+    ConnectionProblem::Other(message)
+}
+
+fn classify_smtp(err: &SmtpSendError) -> ConnectionProblem {
+    let message = err.to_string();
+    let lower = err.message.to_lowercase();
+    match err.code {
+        // 530/534/535: authentication required or rejected
+        Some(530) | Some(534) | Some(535) => ConnectionProblem::BadCredentials(message),
+        _ if lower.contains("certificate") => ConnectionProblem::Certificate(message),
+        _ if lower.contains("timed out") => ConnectionProblem::Timeout(message),
+        None if !err.permanent => ConnectionProblem::HostUnreachable(message),
+        _ => ConnectionProblem::Other(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::mock_imap::MockImap;
+
+    #[tokio::test]
+    async fn test_imap_check_reports_capabilities_and_rejected_logins() {
+        let mock = MockImap::new().on("CAPABILITY", "* CAPABILITY IMAP4rev1 IDLE AUTH=PLAIN\r\n");
+        let check = check_imap(&mock.client("acct")).await;
+        assert!(check.ok);
+        assert_eq!(check.capabilities, ["AUTH=PLAIN", "IDLE", "IMAP4rev1"]);
+        assert!(mock.commands().contains(&"NOOP".to_string()));
+
+        let mock = MockImap::new().on_no("LOGIN", "[AUTHENTICATIONFAILED] Invalid credentials");
+        let check = check_imap(&mock.client("acct")).await;
+        assert!(!check.ok);
+        assert!(matches!(
+            check.error,
+            Some(ConnectionProblem::BadCredentials(_))
+        ));
+    }
+}
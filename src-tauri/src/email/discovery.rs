@@ -0,0 +1,425 @@
+//! Server settings discovery for addresses without a preset.
+//!
+//! Sources, most trusted first: the domain's own autoconfig file, Mozilla's
+//! ISPDB, DNS SRV records (RFC 6186, looked up over DNS-over-HTTPS) and finally
+//! a guess from the usual `imap.`/`smtp.` host names.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::server_presets::{preset_for_email, AuthType, ServerConfig, TlsMode};
+
+/// Per-request timeout; a slow source shouldn't hold up the others
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const ISPDB_URL: &str = "https://autoconfig.thunderbird.net/v1.1";
+const DOH_URL: &str = "https://dns.google/resolve";
+
+/// A possible configuration for an address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerCandidate {
+    pub config: ServerConfig,
+    pub auth_type: AuthType,
+    /// "preset", "autoconfig", "ispdb", "srv" or "guess"
+    pub source: String,
+    /// 0.0-1.0; candidates are returned highest first
+    pub confidence: f32,
+}
+
+impl ServerCandidate {
+    fn new(config: ServerConfig, auth_type: AuthType, source: &str, confidence: f32) -> Self {
+        Self {
+            config,
+            auth_type,
+            source: source.to_string(),
+            confidence,
+        }
+    }
+
+    fn same_servers(&self, other: &ServerCandidate) -> bool {
+        let (a, b) = (&self.config, &other.config);
+        a.imap_host.eq_ignore_ascii_case(&b.imap_host)
+            && a.imap_port == b.imap_port
+            && a.smtp_host.eq_ignore_ascii_case(&b.smtp_host)
+            && a.smtp_port == b.smtp_port
+    }
+}
+
+/// Candidate configurations for `email`, best first. A known provider gets its
+/// preset only; otherwise every source is tried and sources that fail or have
+/// nothing for the domain are skipped.
+pub async fn discover(email: &str) -> Result<Vec<ServerCandidate>> {
+    let domain = email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.trim().to_lowercase())
+        .filter(|domain| !domain.is_empty())
+        .context("Not an email address")?;
+
+    if let Some(preset) = preset_for_email(email) {
+        return Ok(vec![ServerCandidate::new(
+            preset.server_config(),
+            preset.auth_type(),
+            "preset",
+            1.0,
+        )]);
+    }
+
+    let http = reqwest::Client::builder()
+        .timeout(LOOKUP_TIMEOUT)
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let own_url = format!(
+        "https://autoconfig.{}/mail/config-v1.1.xml?emailaddress={}",
+        domain,
+        urlencoding::encode(email)
+    );
+    let ispdb_url = format!("{}/{}", ISPDB_URL, domain);
+    let (own, ispdb, srv) = futures::join!(
+        fetch_text(&http, &own_url),
+        fetch_text(&http, &ispdb_url),
+        lookup_srv_config(&http, &domain),
+    );
+
+    let mut candidates = Vec::new();
+    for (source, confidence, xml) in [("autoconfig", 0.9, own), ("ispdb", 0.85, ispdb)] {
+        match xml {
+            Ok(xml) => candidates.extend(
+                parse_autoconfig(&xml, email)
+                    .into_iter()
+                    .map(|(config, auth)| ServerCandidate::new(config, auth, source, confidence)),
+            ),
+            Err(e) => println!("[Discovery] No {} for {}: {:#}", source, domain, e),
+        }
+    }
+    match srv {
+        Ok(Some(config)) => {
+            candidates.push(ServerCandidate::new(config, AuthType::Password, "srv", 0.7))
+        }
+        Ok(None) => {}
+        Err(e) => println!("[Discovery] SRV lookup failed for {}: {:#}", domain, e),
+    }
+    candidates.push(ServerCandidate::new(
+        guess_config(&domain),
+        AuthType::Password,
+        "guess",
+        0.2,
+    ));
+
+    Ok(rank(candidates))
+}
+
+/// Sort best first, keeping only the best-ranked copy of each server pair
+fn rank(mut candidates: Vec<ServerCandidate>) -> Vec<ServerCandidate> {
+    // Stable, so candidates from the same source keep the file's order
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    let mut ranked: Vec<ServerCandidate> = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        if !ranked.iter().any(|kept| kept.same_servers(&candidate)) {
+            ranked.push(candidate);
+        }
+    }
+    ranked
+}
+
+async fn fetch_text(http: &reqwest::Client, url: &str) -> Result<String> {
+    let response = http.get(url).send().await?.error_for_status()?;
+    Ok(response.text().await?)
+}
+
+fn guess_config(domain: &str) -> ServerConfig {
+    ServerConfig {
+        imap_host: format!("imap.{}", domain),
+        imap_port: 993,
+        smtp_host: format!("smtp.{}", domain),
+        smtp_port: 465,
+        tls_mode: TlsMode::Implicit,
+        smtp_tls_mode: Some(TlsMode::Implicit),
+    }
+}
+
+/// IMAP + SMTP servers from an autoconfig document (the format shared by
+/// domain autoconfig files and the ISPDB). One candidate per IMAP server, each
+/// paired with the first SMTP server; POP3 entries are ignored.
+pub fn parse_autoconfig(xml: &str, email: &str) -> Vec<(ServerConfig, AuthType)> {
+    let (local_part, domain) = email.rsplit_once('@').unwrap_or((email, ""));
+    let expand = |value: &str| {
+        value
+            .replace("%EMAILADDRESS%", email)
+            .replace("%EMAILLOCALPART%", local_part)
+            .replace("%EMAILDOMAIN%", domain)
+    };
+
+    let Some(smtp) = elements(xml, "outgoingServer")
+        .into_iter()
+        .filter(|server| server.attributes.contains("\"smtp\""))
+        .find_map(|server| parse_server(server.body))
+    else {
+        return Vec::new();
+    };
+
+    elements(xml, "incomingServer")
+        .into_iter()
+        .filter(|server| server.attributes.contains("\"imap\""))
+        .filter_map(|server| parse_server(server.body))
+        .map(|imap| {
+            let config = ServerConfig {
+                imap_host: expand(&imap.hostname),
+                imap_port: imap.port,
+                smtp_host: expand(&smtp.hostname),
+                smtp_port: smtp.port,
+                tls_mode: imap.tls_mode,
+                smtp_tls_mode: Some(smtp.tls_mode),
+            };
+            (config, imap.auth_type)
+        })
+        .collect()
+}
+
+struct AutoconfigServer {
+    hostname: String,
+    port: u16,
+    tls_mode: TlsMode,
+    auth_type: AuthType,
+}
+
+fn parse_server(body: &str) -> Option<AutoconfigServer> {
+    let text = |name: &str| {
+        elements(body, name)
+            .first()
+            .map(|e| e.body.trim().to_string())
+    };
+
+    let tls_mode = match text("socketType")?.to_ascii_uppercase().as_str() {
+        "SSL" | "TLS" => TlsMode::Implicit,
+        "STARTTLS" => TlsMode::StartTls,
+        _ => TlsMode::None,
+    };
+    // Any listed OAuth2 method wins; the app supports it wherever offered
+    let auth_type = if elements(body, "authentication")
+        .iter()
+        .any(|e| e.body.trim().eq_ignore_ascii_case("OAuth2"))
+    {
+        AuthType::OAuth2
+    } else {
+        AuthType::Password
+    };
+
+    Some(AutoconfigServer {
+        hostname: text("hostname")?,
+        port: text("port")?.parse().ok()?,
+        tls_mode,
+        auth_type,
+    })
+}
+
+struct Element<'a> {
+    attributes: &'a str,
+    body: &'a str,
+}
+
+/// Every `<name ...>body</name>` in `xml`, not nested in one another. Enough
+/// for the flat autoconfig format; this is not a general XML parser.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<Element<'a>> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut found = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find(&open) {
+        let after_name = &rest[start + open.len()..];
+        // Skip longer names sharing the prefix (<port> vs <portRange>)
+        if !after_name.starts_with(|c: char| c == '>' || c.is_whitespace()) {
+            rest = after_name;
+            continue;
+        }
+        let Some(tag_end) = after_name.find('>') else {
+            break;
+        };
+        let Some(body_len) = after_name[tag_end + 1..].find(&close) else {
+            break;
+        };
+        let body_start = tag_end + 1;
+        found.push(Element {
+            attributes: &after_name[..tag_end],
+            body: &after_name[body_start..body_start + body_len],
+        });
+        rest = &after_name[body_start + body_len + close.len()..];
+    }
+    found
+}
+
+/// Servers from RFC 6186 SRV records; None unless both an IMAP and a
+/// submission service are published
+async fn lookup_srv_config(http: &reqwest::Client, domain: &str) -> Result<Option<ServerConfig>> {
+    let (imaps, imap, submissions, submission) = futures::join!(
+        lookup_srv(http, "_imaps._tcp", domain),
+        lookup_srv(http, "_imap._tcp", domain),
+        lookup_srv(http, "_submissions._tcp", domain),
+        lookup_srv(http, "_submission._tcp", domain),
+    );
+
+    // Implicit TLS is preferred over STARTTLS when both are published
+    let imap = match imaps? {
+        Some(target) => Some((target, TlsMode::Implicit)),
+        None => imap?.map(|target| (target, TlsMode::StartTls)),
+    };
+    let smtp = match submissions? {
+        Some(target) => Some((target, TlsMode::Implicit)),
+        None => submission?.map(|target| (target, TlsMode::StartTls)),
+    };
+
+    Ok(imap
+        .zip(smtp)
+        .map(|((imap, imap_tls), (smtp, smtp_tls))| ServerConfig {
+            imap_host: imap.host,
+            imap_port: imap.port,
+            smtp_host: smtp.host,
+            smtp_port: smtp.port,
+            tls_mode: imap_tls,
+            smtp_tls_mode: Some(smtp_tls),
+        }))
+}
+
+#[derive(Debug, PartialEq)]
+struct SrvTarget {
+    host: String,
+    port: u16,
+}
+
+async fn lookup_srv(
+    http: &reqwest::Client,
+    service: &str,
+    domain: &str,
+) -> Result<Option<SrvTarget>> {
+    let name = format!("{}.{}", service, domain);
+    let answer: serde_json::Value = http
+        .get(DOH_URL)
+        .query(&[("name", name.as_str()), ("type", "SRV")])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(parse_srv_answer(&answer))
+}
+
+/// Best target from a DNS-over-HTTPS JSON answer: lowest priority, then
+/// highest weight. A "." target means the service is deliberately not offered.
+fn parse_srv_answer(answer: &serde_json::Value) -> Option<SrvTarget> {
+    let records = answer.get("Answer")?.as_array()?;
+    let mut best: Option<(u16, u16, SrvTarget)> = None;
+
+    for record in records {
+        // 33 = SRV; answers can also carry the CNAMEs followed on the way
+        if record.get("type").and_then(|t| t.as_u64()) != Some(33) {
+            continue;
+        }
+        let Some(data) = record.get("data").and_then(|d| d.as_str()) else {
+            continue;
+        };
+        let fields: Vec<&str> = data.split_whitespace().collect();
+        let [priority, weight, port, target] = fields[..] else {
+            continue;
+        };
+        let (Ok(priority), Ok(weight), Ok(port)) = (priority.parse(), weight.parse(), port.parse())
+        else {
+            continue;
+        };
+        let host = target.trim_end_matches('.');
+        if host.is_empty() || port == 0 {
+            return None;
+        }
+
+        let better = match &best {
+            Some((best_priority, best_weight, _)) => {
+                (priority, std::cmp::Reverse(weight))
+                    < (*best_priority, std::cmp::Reverse(*best_weight))
+            }
+            None => true,
+        };
+        if better {
+            let target = SrvTarget {
+                host: host.to_string(),
+                port,
+            };
+            best = Some((priority, weight, target));
+        }
+    }
+    best.map(|(_, _, target)| target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_autoconfig_and_srv() {
+        let xml = r#"<?xml version="1.0"?>
+<clientConfig version="1.1">
+  <emailProvider id="example.org">
+    <incomingServer type="pop3">
+      <hostname>pop.example.org</hostname><port>995</port><socketType>SSL</socketType>
+    </incomingServer>
+    <incomingServer type="imap">
+      <hostname>mail.%EMAILDOMAIN%</hostname>
+      <port>143</port>
+      <socketType>STARTTLS</socketType>
+      <authentication>password-cleartext</authentication>
+      <authentication>OAuth2</authentication>
+    </incomingServer>
+    <outgoingServer type="smtp">
+      <hostname>smtp.example.org</hostname>
+      <port>587</port>
+      <socketType>STARTTLS</socketType>
+    </outgoingServer>
+  </emailProvider>
+</clientConfig>"#;
+        let found = parse_autoconfig(xml, "ana@example.org");
+        assert_eq!(found.len(), 1);
+        let (config, auth) = &found[0];
+        assert_eq!(config.imap_host, "mail.example.org");
+        assert_eq!(config.imap_port, 143);
+        assert_eq!(config.tls_mode, TlsMode::StartTls);
+        assert_eq!(
+            (config.smtp_host.as_str(), config.smtp_port),
+            ("smtp.example.org", 587)
+        );
+        assert_eq!(*auth, AuthType::OAuth2);
+        assert!(parse_autoconfig("<html>not found</html>", "ana@example.org").is_empty());
+
+        let answer = serde_json::json!({
+            "Status": 0,
+            "Answer": [
+                {"type": 33, "data": "10 5 993 backup.example.org."},
+                {"type": 33, "data": "0 1 993 imap.example.org."},
+                {"type": 33, "data": "0 9 993 imap2.example.org."}
+            ]
+        });
+        assert_eq!(
+            parse_srv_answer(&answer),
+            Some(SrvTarget {
+                host: "imap2.example.org".to_string(),
+                port: 993
+            })
+        );
+        let disabled = serde_json::json!({"Answer": [{"type": 33, "data": "0 0 0 ."}]});
+        assert_eq!(parse_srv_answer(&disabled), None);
+        assert_eq!(parse_srv_answer(&serde_json::json!({"Status": 3})), None);
+
+        // The guess never outranks a real answer for the same servers
+        let guess = ServerCandidate::new(
+            guess_config("example.org"),
+            AuthType::Password,
+            "guess",
+            0.2,
+        );
+        let mut srv = guess.clone();
+        srv.source = "srv".to_string();
+        srv.confidence = 0.7;
+        let ranked = rank(vec![guess, srv]);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].source, "srv");
+    }
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::smtp::SmtpSendError;
+
+/// Error returned by the email commands. Serializes as
+/// `{ "code": "auth_expired", "message": "..." }` so the frontend can branch on
+/// `code` instead of matching message text.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub enum EmailError {
+    /// Credentials were rejected or the OAuth token can't be refreshed; re-authenticate
+    AuthExpired(String),
+    /// The account, folder, message or attachment doesn't exist
+    NotFound(String),
+    /// Connection failed, dropped or timed out; retrying may help
+    Network(String),
+    /// The server answered NO/BAD (or an SMTP 5xx) for another reason
+    ServerRejected(String),
+    /// A malformed "{account}:{folder}:{uid}" email ID
+    InvalidId(String),
+    /// The folder's UIDVALIDITY changed, so the ID may name another message
+    /// now; list the folder again
+    UidValidityChanged(String),
+    /// Anything else (local database, file system, ...)
+    Other(String),
+}
+
+impl EmailError {
+    pub fn invalid_id(email_id: &str) -> Self {
+        EmailError::InvalidId(format!("Invalid email ID: {}", email_id))
+    }
+
+    pub fn no_client(account_id: &str) -> Self {
+        EmailError::NotFound(format!("No client for account: {}", account_id))
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            EmailError::AuthExpired(message)
+            | EmailError::NotFound(message)
+            | EmailError::Network(message)
+            | EmailError::ServerRejected(message)
+            | EmailError::InvalidId(message)
+            | EmailError::UidValidityChanged(message)
+            | EmailError::Other(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for EmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for EmailError {}
+
+/// IMAP login/AUTHENTICATE failure. A NO or BAD here means the credentials
+/// were refused rather than the command.
+#[derive(Debug)]
+pub struct LoginRejected(pub async_imap::error::Error);
+
+impl fmt::Display for LoginRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for LoginRejected {}
+
+/// A folder's UIDVALIDITY differs from the one its UIDs were handed out
+/// under, or that one isn't known
+#[derive(Debug)]
+pub struct UidValidityChanged {
+    pub folder: String,
+    pub expected: Option<u32>,
+    pub found: u32,
+}
+
+impl fmt::Display for UidValidityChanged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expected {
+            Some(expected) => write!(
+                f,
+                "{} was renumbered on the server (UIDVALIDITY {} -> {}); refresh the folder",
+                self.folder, expected, self.found
+            ),
+            None => write!(
+                f,
+                "{} has no recorded UIDVALIDITY to check its UIDs against; refresh the folder",
+                self.folder
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UidValidityChanged {}
+
+impl From<anyhow::Error> for EmailError {
+    fn from(err: anyhow::Error) -> Self {
+        let message = format!("{:#}", err);
+        for cause in err.chain() {
+            if let Some(EmailError::AuthExpired(_)) = cause.downcast_ref::<EmailError>() {
+                return EmailError::AuthExpired(message);
+            }
+            if cause.is::<UidValidityChanged>() {
+                return EmailError::UidValidityChanged(message);
+            }
+            if let Some(LoginRejected(imap)) = cause.downcast_ref::<LoginRejected>() {
+                return match imap {
+                    async_imap::error::Error::No(_) | async_imap::error::Error::Bad(_) => {
+                        EmailError::AuthExpired(message)
+                    }
+                    _ => classify_imap(imap, message),
+                };
+            }
+            if let Some(imap) = cause.downcast_ref::<async_imap::error::Error>() {
+                return classify_imap(imap, message);
+            }
+            if let Some(smtp) = cause.downcast_ref::<SmtpSendError>() {
+                return match smtp.code {
+                    // 530/534/535: authentication required or rejected
+                    Some(530) | Some(534) | Some(535) => EmailError::AuthExpired(message),
+                    _ if smtp.permanent => EmailError::ServerRejected(message),
+                    // Not a network error: retrying could send the message twice
+                    _ if smtp.delivery_unknown => EmailError::Other(message),
+                    _ => EmailError::Network(message),
+                };
+            }
+            if cause.is::<std::io::Error>() || cause.is::<tokio::time::error::Elapsed>() {
+                return EmailError::Network(message);
+            }
+        }
+        EmailError::Other(message)
+    }
+}
+
+impl From<String> for EmailError {
+    fn from(message: String) -> Self {
+        EmailError::Other(message)
+    }
+}
+
+impl From<&str> for EmailError {
+    fn from(message: &str) -> Self {
+        EmailError::Other(message.to_string())
+    }
+}
+
+/// Lets String-returning helpers keep using `?` on these errors
+impl From<EmailError> for String {
+    fn from(err: EmailError) -> Self {
+        err.to_string()
+    }
+}
+
+fn classify_imap(err: &async_imap::error::Error, message: String) -> EmailError {
+    use async_imap::error::Error;
+
+    match err {
+        Error::No(text) | Error::Bad(text) => classify_rejection(text, message),
+        Error::Io(_) | Error::ConnectionLost => EmailError::Network(message),
+        _ => EmailError::Other(message),
+    }
+}
+
+/// Sort a NO/BAD by its response code (RFC 5530). imap-proto only parses the
+/// RFC 3501 codes, so the others are still in the response text.
+fn classify_rejection(text: &str, message: String) -> EmailError {
+    let text = text.to_ascii_uppercase();
+    let has = |code: &str| text.contains(code);
+
+    if has("[AUTHENTICATIONFAILED]") || has("[AUTHORIZATIONFAILED]") || has("[EXPIRED]") {
+        EmailError::AuthExpired(message)
+    } else if has("[NONEXISTENT]") || has("TRYCREATE") {
+        EmailError::NotFound(message)
+    } else if has("[UNAVAILABLE]") {
+        EmailError::Network(message)
+    } else {
+        EmailError::ServerRejected(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_imap_responses_map_to_codes() {
+        let no = |text: &str| async_imap::error::Error::No(text.to_string());
+
+        let err = Err::<(), _>(no(
+            "code: None, info: Some(\"[NONEXISTENT] Unknown folder\")",
+        ))
+        .context("Failed to select folder")
+        .unwrap_err();
+        let err = EmailError::from(err);
+        assert!(matches!(err, EmailError::NotFound(_)));
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({
+                "code": "not_found",
+                "message": "Failed to select folder: no response: code: None, info: Some(\"[NONEXISTENT] Unknown folder\")"
+            })
+        );
+
+        let login = anyhow::Error::new(LoginRejected(no(
+            "code: None, info: Some(\"Invalid credentials\")",
+        )));
+        assert!(matches!(
+            EmailError::from(login),
+            EmailError::AuthExpired(_)
+        ));
+
+        let quota = anyhow::Error::new(no("code: None, info: Some(\"[OVERQUOTA] Mailbox full\")"));
+        assert!(matches!(
+            EmailError::from(quota),
+            EmailError::ServerRejected(_)
+        ));
+
+        let lost = anyhow::Error::new(async_imap::error::Error::ConnectionLost);
+        assert!(matches!(EmailError::from(lost), EmailError::Network(_)));
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// A single header field exactly as it appeared in the message
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RawHeader {
+    pub name: String,
+    /// Unfolded value (continuation lines joined with a single space)
+    pub value: String,
+}
+
+/// Split a raw RFC 5322 header block into fields, preserving their original order.
+/// Folded continuation lines are joined onto the field they belong to.
+pub fn split_raw_headers(raw: &[u8]) -> Vec<RawHeader> {
+    let text = String::from_utf8_lossy(raw);
+    let mut headers: Vec<RawHeader> = Vec::new();
+
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() {
+            // Blank line ends the header block
+            if !headers.is_empty() {
+                break;
+            }
+            continue;
+        }
+
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = headers.last_mut() {
+                if !last.value.is_empty() {
+                    last.value.push(' ');
+                }
+                last.value.push_str(line.trim());
+            }
+            continue;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push(RawHeader {
+                name: name.trim().to_string(),
+                value: value.trim().to_string(),
+            });
+        }
+    }
+
+    headers
+}
+
+/// All values of a header (case-insensitive name match), in message order
+pub fn header_values<'a>(headers: &'a [RawHeader], name: &str) -> Vec<&'a str> {
+    headers
+        .iter()
+        .filter(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+        .collect()
+}
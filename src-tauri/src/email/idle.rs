@@ -1,37 +1,126 @@
+use crate::auth::account::Account;
 use crate::auth::storage::{get_account_tokens, get_app_password};
-use crate::email::imap_client::{ImapClient, ImapCredentials};
+use crate::commands::account::AccountManager;
+use crate::commands::cache::prefetch_bodies;
+use crate::commands::email::{get_client_for_account, sync_folder_changes};
+use crate::commands::settings::{imap_rate_limits, poll_interval, trusted_authserv_ids};
+use crate::db::EmailDatabase;
+use crate::email::imap_client::{diff_uids, ImapClient, ImapCredentials};
+use crate::email::rules::apply_rules;
 use crate::email::server_presets::{ProviderType, ServerConfig};
+use crate::email::transport::is_timeout;
+use crate::email::types::{FolderChanges, IdleFolderState};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::{watch, Mutex};
 use tokio::time::{sleep, Duration};
 
-/// Event payload emitted when new mail arrives
+/// Event payload emitted when a monitored folder changes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewMailEvent {
     pub account_id: String,
     pub folder: String,
+    /// UIDs of the messages that arrived, ascending. Empty when the wakeup only
+    /// removed or re-flagged messages, or when the changes aren't known (after
+    /// a catch-up sync) and the folder should be re-fetched.
+    #[serde(default)]
+    pub new_uids: Vec<u32>,
+    pub count: u32,
+    /// UIDs of the messages removed from the folder, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expunged_uids: Option<Vec<u32>>,
+}
+
+impl NewMailEvent {
+    pub fn new(account_id: &str, folder: &str, changes: FolderChanges) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            folder: folder.to_string(),
+            count: changes.new_uids.len() as u32,
+            new_uids: changes.new_uids,
+            expunged_uids: (!changes.expunged_uids.is_empty()).then_some(changes.expunged_uids),
+        }
+    }
 }
 
 /// Manages IMAP IDLE connections for all accounts
 pub struct IdleManager {
     /// Per-account-folder shutdown senders (key: "account_id:folder")
     shutdown_senders: Arc<Mutex<HashMap<String, watch::Sender<bool>>>>,
+    /// Folders last requested per account, kept across stop_idle so a resume
+    /// watches the same set
+    configured_folders: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    /// Accounts whose monitors `suspend_all` stopped, for `resume_all`
+    suspended_accounts: Arc<Mutex<Vec<String>>>,
+    /// Bumped by every suspend and resume, so a pending resume can tell it
+    /// was superseded
+    lifecycle: Arc<AtomicU64>,
+}
+
+/// First reconnect delay after a failure; doubled per consecutive failure
+const BACKOFF_BASE: Duration = Duration::from_secs(5);
+/// Upper bound for the reconnect delay
+const BACKOFF_MAX: Duration = Duration::from_secs(5 * 60);
+
+/// Reconnect backoff for one folder's IDLE loop. Each folder runs its own loop,
+/// so a flaky folder never delays the others.
+#[derive(Debug, Default)]
+struct ReconnectBackoff {
+    failures: u32,
 }
 
-/// List of folders to monitor for each account
-const MONITORED_FOLDERS: &[&str] = &["INBOX", "Sent", "Drafts", "Trash", "Spam"];
+impl ReconnectBackoff {
+    /// Delay before the next attempt, randomized so folders don't retry in lockstep
+    fn next_delay(&mut self) -> Duration {
+        let delay = backoff_delay(self.failures, rand::thread_rng().gen());
+        self.failures = self.failures.saturating_add(1);
+        delay
+    }
+
+    /// Back to the base delay after a successful IDLE cycle
+    fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Exponential delay for the given number of consecutive failures, with
+/// "equal jitter": half is fixed and half scaled by `jitter` (0.0..1.0)
+fn backoff_delay(failures: u32, jitter: f64) -> Duration {
+    let exponential = BACKOFF_BASE
+        .checked_mul(2u32.saturating_pow(failures))
+        .map_or(BACKOFF_MAX, |delay| delay.min(BACKOFF_MAX));
+    let half = exponential / 2;
+    half + half.mul_f64(jitter.clamp(0.0, 1.0))
+}
+
+/// How long the system has to stay awake before `resume_all` reconnects, so
+/// a lid opened and closed again doesn't open connections only to drop them
+const RESUME_DEBOUNCE: Duration = Duration::from_secs(3);
+
+/// Seconds between checks when the server lacks IDLE and folders are polled
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 60;
+
+/// Folders watched when an account hasn't chosen any. Each folder holds its
+/// own connection, and servers like Gmail cap simultaneous connections.
+pub const DEFAULT_MONITORED_FOLDERS: &[&str] = &["INBOX"];
 
 impl IdleManager {
     pub fn new() -> Self {
         Self {
             shutdown_senders: Arc::new(Mutex::new(HashMap::new())),
+            configured_folders: Arc::new(Mutex::new(HashMap::new())),
+            suspended_accounts: Arc::new(Mutex::new(Vec::new())),
+            lifecycle: Arc::new(AtomicU64::new(0)),
         }
     }
 
-    /// Start IDLE monitoring for an account (all folders)
+    /// Monitor exactly `folders` for an account. Monitors for folders no longer
+    /// listed are stopped, new ones started, and unchanged ones left running.
+    #[allow(clippy::too_many_arguments)]
     pub async fn start_idle<R: tauri::Runtime>(
         &self,
         app: AppHandle<R>,
@@ -40,12 +129,32 @@ impl IdleManager {
         provider: ProviderType,
         server_config: ServerConfig,
         auth_type: String,
+        folders: &[String],
     ) {
-        // Stop existing IDLE connections for this account
-        self.stop_idle(&account_id).await;
+        self.configured_folders
+            .lock()
+            .await
+            .insert(account_id.clone(), folders.to_vec());
+
+        let current = self.monitored_folders(&account_id).await;
+        let (removed, added) = reconcile_folders(&current, folders);
+
+        {
+            let mut senders = self.shutdown_senders.lock().await;
+            for folder in &removed {
+                if let Some(tx) = senders.remove(&format!("{}:{}", account_id, folder)) {
+                    let _ = tx.send(true);
+                }
+            }
+        }
+        if !removed.is_empty() || !added.is_empty() {
+            println!(
+                "[IDLE:{}] Monitoring {:?} (stopped {:?}, started {:?})",
+                account_id, folders, removed, added
+            );
+        }
 
-        // Start IDLE monitoring for each folder
-        for folder in MONITORED_FOLDERS {
+        for folder in &added {
             self.start_folder_idle(
                 app.clone(),
                 account_id.clone(),
@@ -114,6 +223,39 @@ impl IdleManager {
         }
     }
 
+    /// Folders with a running IDLE monitor for an account, sorted
+    pub async fn monitored_folders(&self, account_id: &str) -> Vec<String> {
+        let prefix = format!("{}:", account_id);
+        self.active_monitors()
+            .await
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&prefix).map(str::to_string))
+            .collect()
+    }
+
+    /// Folders an account asked to monitor, or the defaults if it never did
+    pub async fn configured_folders(&self, account_id: &str) -> Vec<String> {
+        self.configured_folders
+            .lock()
+            .await
+            .get(account_id)
+            .cloned()
+            .unwrap_or_else(|| {
+                DEFAULT_MONITORED_FOLDERS
+                    .iter()
+                    .map(|f| f.to_string())
+                    .collect()
+            })
+    }
+
+    /// Keys ("account_id:folder") of all currently running IDLE monitors
+    pub async fn active_monitors(&self) -> Vec<String> {
+        let senders = self.shutdown_senders.lock().await;
+        let mut keys: Vec<String> = senders.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
     /// Stop all IDLE monitors
     pub async fn stop_all(&self) {
         let mut senders = self.shutdown_senders.lock().await;
@@ -121,6 +263,341 @@ impl IdleManager {
             let _ = tx.send(true);
         }
     }
+
+    /// Stop every monitor before the system sleeps, remembering the accounts
+    /// that had any so `resume_all` restarts them. Cancels a pending resume.
+    /// Returns the accounts suspended so far.
+    pub async fn suspend_all(&self) -> Vec<String> {
+        self.lifecycle.fetch_add(1, Ordering::SeqCst);
+
+        let accounts: Vec<String> = self
+            .configured_folders
+            .lock()
+            .await
+            .keys()
+            .cloned()
+            .collect();
+        let mut suspended = self.suspended_accounts.lock().await;
+        for account_id in accounts {
+            let running = !self.monitored_folders(&account_id).await.is_empty();
+            if running && !suspended.contains(&account_id) {
+                suspended.push(account_id);
+            }
+        }
+        self.stop_all().await;
+
+        println!("[IDLE] Suspended monitors for {:?}", *suspended);
+        suspended.clone()
+    }
+
+    /// Restart the monitors `suspend_all` stopped once the system has stayed
+    /// awake for RESUME_DEBOUNCE, and catch up on mail that arrived while it
+    /// slept. Returns the accounts resumed; none when a later suspend or
+    /// resume superseded this call.
+    pub async fn resume_all<R: tauri::Runtime>(&self, app: AppHandle<R>) -> Vec<String> {
+        let generation = self.lifecycle.fetch_add(1, Ordering::SeqCst) + 1;
+        sleep(RESUME_DEBOUNCE).await;
+
+        let Some(db) = app.try_state::<Arc<StdMutex<Option<EmailDatabase>>>>() else {
+            return Vec::new();
+        };
+        let mut resumed = Vec::new();
+        while self.lifecycle.load(Ordering::SeqCst) == generation {
+            let Some(account_id) = self.suspended_accounts.lock().await.pop() else {
+                break;
+            };
+            let account = {
+                let db_lock = db.lock().unwrap();
+                db_lock
+                    .as_ref()
+                    .and_then(|database| database.get_account(&account_id).ok().flatten())
+            };
+            // Removed or paused while the system slept
+            let Some(account) = account.filter(|account| !account.sync_paused) else {
+                continue;
+            };
+
+            let folders = self.configured_folders(&account.id).await;
+            self.start_idle(
+                app.clone(),
+                account.id.clone(),
+                account.email.clone(),
+                account.provider_type(),
+                account.server_config(),
+                account.auth_type.clone(),
+                &folders,
+            )
+            .await;
+            catch_up(&app, &db, &account, &folders).await;
+            resumed.push(account.id);
+        }
+
+        if !resumed.is_empty() {
+            println!("[IDLE] Resumed monitors for {:?}", resumed);
+        }
+        resumed
+    }
+}
+
+/// Folders to stop and to start to get from `current` to `desired`
+fn reconcile_folders(current: &[String], desired: &[String]) -> (Vec<String>, Vec<String>) {
+    let removed = current
+        .iter()
+        .filter(|folder| !desired.contains(folder))
+        .cloned()
+        .collect();
+    let mut added: Vec<String> = Vec::new();
+    for folder in desired {
+        if !current.contains(folder) && !added.contains(folder) {
+            added.push(folder.clone());
+        }
+    }
+    (removed, added)
+}
+
+fn invalidate_unread_cache<R: tauri::Runtime>(app: &AppHandle<R>, account_id: &str, folder: &str) {
+    if let Some(account_manager) = app.try_state::<AccountManager>() {
+        account_manager.invalidate_unread(account_id, folder);
+    }
+}
+
+/// Apply the account's filter rules to newly arrived messages and return the
+/// UIDs moved out of `folder`. Failures are logged; the mail is still reported.
+async fn run_rules<R: tauri::Runtime>(
+    app: &AppHandle<R>,
+    client: &ImapClient,
+    folder: &str,
+    uids: &[u32],
+) -> Vec<u32> {
+    let Some(db) = app.try_state::<Arc<StdMutex<Option<EmailDatabase>>>>() else {
+        return Vec::new();
+    };
+    match apply_rules(client, &db, folder, uids).await {
+        Ok(moved) => moved,
+        Err(e) => {
+            eprintln!(
+                "[IDLE:{}:{}] Failed to apply rules: {}",
+                client.account_id, folder, e
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Sync a resumed account's monitored folders and report what arrived while
+/// the system slept. New INBOX mail goes through the rules first, as it would
+/// have over IDLE.
+async fn catch_up<R: tauri::Runtime>(
+    app: &AppHandle<R>,
+    db: &Arc<StdMutex<Option<EmailDatabase>>>,
+    account: &Account,
+    folders: &[String],
+) {
+    let Some(account_manager) = app.try_state::<AccountManager>() else {
+        return;
+    };
+    let client = match get_client_for_account(&account_manager, account).await {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("[IDLE:{}] Catch-up after resume failed: {}", account.id, e);
+            return;
+        }
+    };
+
+    for folder in folders {
+        let changes = match sync_folder_changes(&client, db, folder, prefetch_bodies()).await {
+            Ok(Some(fetched)) if fetched.is_empty() => continue,
+            Ok(Some(fetched)) => {
+                let mut new_uids: Vec<u32> = fetched.iter().map(|email| email.uid).collect();
+                new_uids.sort_unstable();
+                if folder.eq_ignore_ascii_case("INBOX") {
+                    let moved = run_rules(app, &client, folder, &new_uids).await;
+                    uncache(db, &account.id, folder, &moved);
+                    new_uids.retain(|uid| !moved.contains(uid));
+                }
+                FolderChanges {
+                    new_uids,
+                    ..Default::default()
+                }
+            }
+            // Nothing to resume from; the folder has to be listed again
+            Ok(None) => FolderChanges::default(),
+            Err(e) => {
+                eprintln!(
+                    "[IDLE:{}:{}] Catch-up after resume failed: {}",
+                    account.id, folder, e
+                );
+                continue;
+            }
+        };
+        invalidate_unread_cache(app, &account.id, folder);
+        let _ = app.emit(
+            "email:new_mail",
+            NewMailEvent::new(&account.id, folder, changes),
+        );
+    }
+}
+
+/// Drop messages no longer in `folder` (moved by a rule or expunged) from its cache
+fn uncache(
+    db: &Arc<StdMutex<Option<EmailDatabase>>>,
+    account_id: &str,
+    folder: &str,
+    uids: &[u32],
+) {
+    if uids.is_empty() {
+        return;
+    }
+    let db_lock = db.lock().unwrap();
+    let Some(database) = db_lock.as_ref() else {
+        return;
+    };
+    if let Err(e) = database.remove_cached_uids(account_id, folder, uids) {
+        eprintln!(
+            "[IDLE:{}:{}] Failed to uncache removed mail: {}",
+            account_id, folder, e
+        );
+    }
+}
+
+/// What changed in a folder between two IDLE states. None when UIDVALIDITY
+/// changed, so the UIDs can't be compared and the folder must be re-listed.
+fn missed_changes(previous: &IdleFolderState, current: &IdleFolderState) -> Option<FolderChanges> {
+    if previous.uid_validity != current.uid_validity {
+        return None;
+    }
+    let known = previous.uids.iter().copied().collect();
+    let now = current.uids.iter().copied().collect();
+    Some(diff_uids(&known, &now, Some(previous.uid_next)))
+}
+
+/// Compare a folder with what its monitor saw before the last disconnect
+/// (or app exit) and report mail that arrived or was expunged meanwhile.
+/// After a UIDVALIDITY change the folder's cache is dropped and a re-fetch
+/// requested. Returns the folder's current state, which is also saved.
+async fn report_missed_changes<R: tauri::Runtime>(
+    app: &AppHandle<R>,
+    client: &ImapClient,
+    folder: &str,
+) -> Option<IdleFolderState> {
+    let account_id = client.account_id.as_str();
+    let db = app.try_state::<Arc<StdMutex<Option<EmailDatabase>>>>()?;
+    let mut current = match client.idle_folder_state(folder).await {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!(
+                "[IDLE:{}:{}] Failed to read folder state: {}",
+                account_id, folder, e
+            );
+            return None;
+        }
+    };
+    let previous = {
+        let db_lock = db.lock().unwrap();
+        db_lock
+            .as_ref()
+            .and_then(|database| database.get_idle_state(account_id, folder).ok().flatten())
+    };
+
+    let changes = match previous.map(|previous| missed_changes(&previous, &current)) {
+        // First run for this folder: nothing to compare with
+        None => None,
+        Some(None) => {
+            let db_lock = db.lock().unwrap();
+            if let Some(database) = db_lock.as_ref() {
+                // UIDs were reassigned; nothing cached for the folder is valid
+                match database.clear_cached_folder(account_id, folder) {
+                    Ok(removed) => println!(
+                        "[IDLE:{}:{}] UIDVALIDITY changed, dropped {} cached",
+                        account_id, folder, removed
+                    ),
+                    Err(e) => eprintln!(
+                        "[IDLE:{}:{}] Failed to drop stale cache: {}",
+                        account_id, folder, e
+                    ),
+                }
+            }
+            Some(FolderChanges::default())
+        }
+        Some(Some(changes)) if changes.new_uids.is_empty() && changes.expunged_uids.is_empty() => {
+            None
+        }
+        Some(Some(mut changes)) => {
+            if folder.eq_ignore_ascii_case("INBOX") && !changes.new_uids.is_empty() {
+                let moved = run_rules(app, client, folder, &changes.new_uids).await;
+                changes.new_uids.retain(|uid| !moved.contains(uid));
+                current.uids.retain(|uid| !moved.contains(uid));
+                uncache(&db, account_id, folder, &moved);
+            }
+            uncache(&db, account_id, folder, &changes.expunged_uids);
+            println!(
+                "[IDLE:{}:{}] While disconnected: {} new, {} expunged",
+                account_id,
+                folder,
+                changes.new_uids.len(),
+                changes.expunged_uids.len()
+            );
+            Some(changes)
+        }
+    };
+
+    save_idle_state(&db, account_id, folder, &current);
+    if let Some(changes) = changes {
+        invalidate_unread_cache(app, account_id, folder);
+        let _ = app.emit(
+            "email:new_mail",
+            NewMailEvent::new(account_id, folder, changes),
+        );
+    }
+    Some(current)
+}
+
+fn save_idle_state(
+    db: &Arc<StdMutex<Option<EmailDatabase>>>,
+    account_id: &str,
+    folder: &str,
+    state: &IdleFolderState,
+) {
+    let db_lock = db.lock().unwrap();
+    let Some(database) = db_lock.as_ref() else {
+        return;
+    };
+    if let Err(e) = database.set_idle_state(account_id, folder, state) {
+        eprintln!(
+            "[IDLE:{}:{}] Failed to save IDLE state: {}",
+            account_id, folder, e
+        );
+    }
+}
+
+/// OAuth credentials for an IDLE connection. An expired token is refreshed
+/// under the account manager's per-account lock, so IDLE reconnects don't
+/// race refreshes started by commands.
+async fn oauth_credentials<R: tauri::Runtime>(
+    app: &AppHandle<R>,
+    account_id: &str,
+    email: &str,
+    provider: &ProviderType,
+) -> Result<ImapCredentials, String> {
+    match app.try_state::<AccountManager>() {
+        Some(account_manager) => {
+            let provider = crate::commands::email::oauth_provider_name(provider.clone());
+            crate::commands::email::resolve_oauth2_credentials(
+                &account_manager,
+                account_id,
+                email,
+                provider,
+            )
+            .await
+            .map_err(|e| e.to_string())
+        }
+        None => get_account_tokens(account_id)
+            .map(|tokens| ImapCredentials::OAuth2 {
+                user: email.to_string(),
+                access_token: tokens.access_token,
+            })
+            .map_err(|e| e.to_string()),
+    }
 }
 
 /// The IDLE loop for a single folder in an account
@@ -136,7 +613,7 @@ async fn idle_loop<R: tauri::Runtime>(
 ) {
     // RFC 2177: IDLE should be re-issued every 29 minutes max
     let idle_timeout_secs = 29 * 60;
-    let retry_delay = Duration::from_secs(30);
+    let mut backoff = ReconnectBackoff::default();
 
     loop {
         // Check shutdown
@@ -147,17 +624,18 @@ async fn idle_loop<R: tauri::Runtime>(
 
         // Build credentials
         let credentials = if auth_type == "oauth2" {
-            match get_account_tokens(&account_id) {
-                Ok(tokens) => ImapCredentials::OAuth2 {
-                    user: email.clone(),
-                    access_token: tokens.access_token,
-                },
+            match oauth_credentials(&app, &account_id, &email, &provider).await {
+                Ok(credentials) => credentials,
                 Err(e) => {
+                    let delay = backoff.next_delay();
                     eprintln!(
-                        "[IDLE:{}:{}] Failed to get OAuth tokens: {}. Retrying...",
-                        account_id, folder, e
+                        "[IDLE:{}:{}] Failed to get OAuth tokens: {}. Retrying in {}s...",
+                        account_id,
+                        folder,
+                        e,
+                        delay.as_secs()
                     );
-                    sleep(retry_delay).await;
+                    sleep(delay).await;
                     continue;
                 }
             }
@@ -168,11 +646,15 @@ async fn idle_loop<R: tauri::Runtime>(
                     password,
                 },
                 Err(e) => {
+                    let delay = backoff.next_delay();
                     eprintln!(
-                        "[IDLE:{}:{}] Failed to get password: {}. Retrying...",
-                        account_id, folder, e
+                        "[IDLE:{}:{}] Failed to get password: {}. Retrying in {}s...",
+                        account_id,
+                        folder,
+                        e,
+                        delay.as_secs()
                     );
-                    sleep(retry_delay).await;
+                    sleep(delay).await;
                     continue;
                 }
             }
@@ -184,49 +666,322 @@ async fn idle_loop<R: tauri::Runtime>(
             provider.clone(),
             server_config.clone(),
             credentials,
-        );
+        )
+        .with_rate_limits(imap_rate_limits(&provider))
+        .with_trusted_authserv_ids(trusted_authserv_ids(&provider, &server_config.imap_host));
 
         // Connect
         match client.reconnect().await {
-            Ok(()) => {
+            Ok(()) if client.supports_idle() => {
                 println!("[IDLE:{}:{}] Connected, starting IDLE", account_id, folder);
             }
+            Ok(()) => {
+                println!(
+                    "[IDLE:{}:{}] Connected; server lacks IDLE, polling every {}s",
+                    account_id,
+                    folder,
+                    poll_interval().as_secs()
+                );
+            }
             Err(e) => {
+                let delay = backoff.next_delay();
                 eprintln!(
-                    "[IDLE:{}:{}] Connection failed: {}. Retrying in 30s...",
-                    account_id, folder, e
+                    "[IDLE:{}:{}] Connection failed: {}. Retrying in {}s...",
+                    account_id,
+                    folder,
+                    e,
+                    delay.as_secs()
                 );
-                sleep(retry_delay).await;
+                sleep(delay).await;
                 continue;
             }
         }
 
+        // Catch up on what happened since the last connection
+        let mut seen = report_missed_changes(&app, &client, &folder).await;
+
         // IDLE loop (re-issue every 29 min)
-        match client.idle_wait(&folder, idle_timeout_secs).await {
-            Ok(true) => {
-                // New mail detected
-                println!("[IDLE:{}:{}] New mail detected", account_id, folder);
+        let waited = if client.supports_idle() {
+            client
+                .idle_wait(&folder, idle_timeout_secs, &mut shutdown_rx)
+                .await
+        } else {
+            client
+                .poll_wait(
+                    &folder,
+                    poll_interval(),
+                    idle_timeout_secs,
+                    &mut shutdown_rx,
+                )
+                .await
+        };
+        match waited {
+            Ok(Some(mut changes)) => {
+                backoff.reset();
+                if folder.eq_ignore_ascii_case("INBOX") && !changes.new_uids.is_empty() {
+                    // Messages a rule moved away never show up as new here
+                    let moved = run_rules(&app, &client, &folder, &changes.new_uids).await;
+                    changes.new_uids.retain(|uid| !moved.contains(uid));
+                }
+                println!(
+                    "[IDLE:{}:{}] Folder changed: {} new, {} expunged",
+                    account_id,
+                    folder,
+                    changes.new_uids.len(),
+                    changes.expunged_uids.len()
+                );
+                if let (Some(seen), Some(db)) = (
+                    seen.as_mut(),
+                    app.try_state::<Arc<StdMutex<Option<EmailDatabase>>>>(),
+                ) {
+                    // So the next connection doesn't report these again
+                    seen.apply(&changes);
+                    save_idle_state(&db, &account_id, &folder, seen);
+                }
+                invalidate_unread_cache(&app, &account_id, &folder);
                 let _ = app.emit(
                     "email:new_mail",
-                    NewMailEvent {
-                        account_id: account_id.clone(),
-                        folder: folder.clone(),
-                    },
+                    NewMailEvent::new(&account_id, &folder, changes),
                 );
             }
-            Ok(false) => {
+            // Stopped: IDLE was ended and the session logged out
+            Ok(None) if *shutdown_rx.borrow() => continue,
+            Ok(None) => {
+                backoff.reset();
                 // Timeout — re-issue IDLE
                 println!("[IDLE:{}:{}] IDLE timeout, re-issuing", account_id, folder);
             }
-            Err(e) => {
+            Err(e) if is_timeout(&e) => {
+                // Most likely a half-open connection: reconnect straight away.
+                // If the network is really down, connecting backs off.
                 eprintln!(
-                    "[IDLE:{}:{}] IDLE error: {}. Reconnecting in 30s...",
+                    "[IDLE:{}:{}] Connection timed out: {}. Reconnecting...",
                     account_id, folder, e
                 );
-                sleep(retry_delay).await;
+                invalidate_unread_cache(&app, &account_id, &folder);
+            }
+            Err(e) => {
+                let delay = backoff.next_delay();
+                eprintln!(
+                    "[IDLE:{}:{}] IDLE error: {}. Reconnecting in {}s...",
+                    account_id,
+                    folder,
+                    e,
+                    delay.as_secs()
+                );
+                // Changes may be missed while disconnected
+                invalidate_unread_cache(&app, &account_id, &folder);
+                sleep(delay).await;
             }
         }
     }
 
     println!("[IDLE:{}:{}] IDLE loop exited", account_id, folder);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::mock_imap::MockImap;
+
+    #[test]
+    fn test_reconcile_keeps_unchanged_folders() {
+        let folders = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        let (removed, added) = reconcile_folders(
+            &folders(&["INBOX", "Sent", "Spam"]),
+            &folders(&["INBOX", "Archive", "Archive", "Sent"]),
+        );
+        assert_eq!(removed, folders(&["Spam"]));
+        assert_eq!(added, folders(&["Archive"]));
+
+        let (removed, added) = reconcile_folders(&folders(&["INBOX"]), &folders(&["INBOX"]));
+        assert!(removed.is_empty() && added.is_empty());
+    }
+
+    #[test]
+    fn test_backoff_doubles_with_jitter_and_caps() {
+        assert_eq!(backoff_delay(0, 1.0), Duration::from_secs(5));
+        assert_eq!(backoff_delay(0, 0.0), Duration::from_millis(2500));
+        assert_eq!(backoff_delay(2, 1.0), Duration::from_secs(20));
+        assert_eq!(backoff_delay(10, 1.0), BACKOFF_MAX);
+        assert_eq!(backoff_delay(u32::MAX, 0.0), BACKOFF_MAX / 2);
+
+        let mut backoff = ReconnectBackoff::default();
+        for _ in 0..3 {
+            backoff.next_delay();
+        }
+        assert!(backoff.next_delay() >= Duration::from_secs(20));
+        backoff.reset();
+        assert!(backoff.next_delay() <= BACKOFF_BASE);
+    }
+
+    #[tokio::test]
+    async fn test_idle_wait_reports_new_mail() {
+        let mock = MockImap::new()
+            .on("SELECT", "* 2 EXISTS\r\n* OK [UIDNEXT 12] Predicted next UID\r\n")
+            .on("UID SEARCH UID 1:11", "* SEARCH 7 10\r\n")
+            .on("UID SEARCH ALL", "* SEARCH 10 12 13\r\n")
+            .on("EXAMINE", "* 3 EXISTS\r\n")
+            .on("STATUS", "* STATUS INBOX (MESSAGES 3 UNSEEN 0)\r\n")
+            .on_idle("* 1 EXPUNGE\r\n* 3 EXISTS\r\n");
+        let client = mock.client("acct");
+        client.reconnect().await.unwrap();
+
+        let (_shutdown_tx, mut shutdown_rx) = watch::channel(false);
+        let changes = client
+            .idle_wait("INBOX", 5, &mut shutdown_rx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(changes.new_uids, vec![12, 13]);
+        assert_eq!(changes.expunged_uids, vec![7]);
+
+        let event = serde_json::to_value(NewMailEvent::new("acct", "INBOX", changes)).unwrap();
+        assert_eq!(event["count"], 2);
+        assert_eq!(event["expunged_uids"], serde_json::json!([7]));
+        // IDLE was ended cleanly and the session handed back for reuse
+        assert!(mock.commands().iter().any(|c| c == "DONE"));
+        assert_eq!(client.get_folder_stats("INBOX").await.unwrap().0, 3);
+    }
+
+    #[tokio::test]
+    async fn test_idle_wait_times_out_without_changes() {
+        let mock = MockImap::new();
+        let client = mock.client("acct");
+        client.reconnect().await.unwrap();
+
+        let (_shutdown_tx, mut shutdown_rx) = watch::channel(false);
+        let changes = client
+            .idle_wait("INBOX", 1, &mut shutdown_rx)
+            .await
+            .unwrap();
+        assert_eq!(changes, None);
+    }
+
+    #[tokio::test]
+    async fn test_poll_wait_without_idle_capability() {
+        let mock = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1\r\n")
+            .on(
+                "SELECT",
+                "* 2 EXISTS\r\n* OK [UIDNEXT 12] Predicted next UID\r\n",
+            )
+            .on("UID SEARCH UID 1:11", "* SEARCH 7 10\r\n")
+            .on("UID SEARCH ALL", "* SEARCH 10 12\r\n")
+            .on("NOOP", "* 1 EXPUNGE\r\n* 2 EXISTS\r\n");
+        let client = mock.client("acct");
+        client.reconnect().await.unwrap();
+        assert!(!client.supports_idle());
+
+        let (_shutdown_tx, mut shutdown_rx) = watch::channel(false);
+        let changes = client
+            .poll_wait("INBOX", Duration::from_millis(10), 5, &mut shutdown_rx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(changes.new_uids, vec![12]);
+        assert_eq!(changes.expunged_uids, vec![7]);
+        assert!(!mock.commands().iter().any(|c| c == "IDLE"));
+
+        // NOOPs that report nothing new poll until the timeout
+        let quiet = MockImap::new().on("SELECT", "* 2 EXISTS\r\n");
+        let client = quiet.client("acct");
+        let changes = client
+            .poll_wait("INBOX", Duration::from_millis(10), 1, &mut shutdown_rx)
+            .await
+            .unwrap();
+        assert_eq!(changes, None);
+        assert!(quiet.commands().iter().filter(|c| *c == "NOOP").count() > 1);
+
+        let idle = MockImap::new().on("CAPABILITY", "* CAPABILITY IMAP4rev1 IDLE\r\n");
+        let client = idle.client("acct");
+        client.reconnect().await.unwrap();
+        assert!(client.supports_idle());
+    }
+
+    #[tokio::test]
+    async fn test_idle_wait_sends_done_on_shutdown() {
+        let mock = MockImap::new();
+        let client = mock.client("acct");
+        client.reconnect().await.unwrap();
+
+        let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            let _ = shutdown_tx.send(true);
+        });
+        // Returns on the signal, well before the IDLE timeout
+        let stopped = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.idle_wait("INBOX", 60, &mut shutdown_rx),
+        )
+        .await
+        .expect("idle_wait ignored the shutdown signal");
+        assert_eq!(stopped.unwrap(), None);
+
+        let commands = mock.commands();
+        let done = commands.iter().position(|c| c == "DONE").unwrap();
+        let logout = commands.iter().position(|c| c == "LOGOUT").unwrap();
+        assert!(done < logout);
+    }
+
+    #[tokio::test]
+    async fn test_missed_changes_while_disconnected() {
+        let mock = MockImap::new()
+            .on(
+                "EXAMINE",
+                "* 3 EXISTS\r\n* OK [UIDVALIDITY 5] UIDs valid\r\n* OK [UIDNEXT 14] Predicted next UID\r\n",
+            )
+            .on("UID SEARCH ALL", "* SEARCH 13 10 12\r\n");
+        let client = mock.client("acct");
+        client.reconnect().await.unwrap();
+
+        let current = client.idle_folder_state("INBOX").await.unwrap();
+        assert_eq!(current.uids, [10, 12, 13]);
+        assert_eq!((current.uid_validity, current.uid_next), (5, 14));
+
+        // 7 was expunged and 12, 13 arrived while the monitor was away
+        let previous = IdleFolderState {
+            uid_validity: 5,
+            uid_next: 12,
+            uids: vec![7, 10],
+        };
+        let changes = missed_changes(&previous, &current).unwrap();
+        assert_eq!(changes.new_uids, [12, 13]);
+        assert_eq!(changes.expunged_uids, [7]);
+
+        let reassigned = IdleFolderState {
+            uid_validity: 4,
+            ..previous
+        };
+        assert_eq!(missed_changes(&reassigned, &current), None);
+    }
+
+    #[tokio::test]
+    async fn test_suspend_all_stops_monitors_and_remembers_accounts() {
+        let manager = IdleManager::new();
+        let (tx, mut rx) = watch::channel(false);
+        manager
+            .shutdown_senders
+            .lock()
+            .await
+            .insert("acct:INBOX".to_string(), tx);
+        for account_id in ["acct", "idle-less"] {
+            manager
+                .configured_folders
+                .lock()
+                .await
+                .insert(account_id.to_string(), vec!["INBOX".to_string()]);
+        }
+        let before = manager.lifecycle.load(Ordering::SeqCst);
+
+        assert_eq!(manager.suspend_all().await, ["acct"]);
+        assert!(*rx.borrow_and_update());
+        assert!(manager.active_monitors().await.is_empty());
+        // A second suspend keeps the accounts the first one stopped
+        assert_eq!(manager.suspend_all().await, ["acct"]);
+        // Both supersede any resume still waiting out its debounce
+        assert_eq!(manager.lifecycle.load(Ordering::SeqCst), before + 2);
+    }
+}
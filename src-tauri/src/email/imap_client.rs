@@ -1,28 +1,75 @@
 use anyhow::{Context, Result};
 use async_imap::extensions::idle::IdleResponse;
-use async_imap::types::{Fetch, Flag};
-use async_native_tls::TlsConnector;
+use async_imap::imap_proto::{
+    Address, AttributeValue, Envelope, MailboxDatum, NameAttribute, Response, ResponseCode,
+    SectionPath, Status, UidSetMember,
+};
+use async_imap::types::{Capability, Fetch, Flag, Name};
 use futures::StreamExt;
-use lettre::message::{header::ContentType, Mailbox, MultiPart, SinglePart};
+use chrono::{DateTime, Utc};
+use lettre::message::header::{self, ContentType};
+use lettre::message::{Attachment, Body, Mailbox, MessageBuilder, MultiPart, SinglePart};
 use lettre::transport::smtp::authentication::{Credentials, Mechanism};
-use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::{address::Envelope, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 use mail_parser::MessageParser;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
-use tokio_util::compat::TokioAsyncReadCompatExt;
+use std::time::SystemTime;
+use tokio::sync::{watch, Mutex};
 
+use super::attachments::{
+    collect_attachment_parts, collect_inline_parts, collect_mime_parts, decode_transfer_encoding,
+    embed_inline_parts, find_preview_part, preview_text, strip_angle_brackets, AttachmentPart,
+    OutgoingAttachment, PreviewPart,
+};
 use super::provider::{EmailProvider, ImapFlag};
-use super::server_presets::{AuthType, ProviderType, ServerConfig};
-use super::types::{Email, EmailListItem, Folder, SpecialFolder};
+use super::rate_limit::{RateLimiter, RateLimits};
+use super::rules::RuleMessage;
+use super::server_presets::{AuthType, OAuthMechanism, ProviderType, ServerConfig, TlsMode};
+use super::smtp::{retry_backoff, SmtpPhase, SmtpSendError, SmtpSendOptions};
+use super::sort::{sort_items, MessageSort};
+use super::auth_results::{
+    extract_authentication_results, provider_authserv_ids, AuthenticationResults,
+    AuthenticationSummary,
+};
+use super::calendar::Invite;
+use super::error::{LoginRejected, UidValidityChanged};
+use super::headers::split_raw_headers;
+use super::mailing_list::{MailingList, UnsubscribeInfo};
+use super::special_folders::{special_use_name, SpecialFolderMap};
+use super::transport::{
+    ImapStream, ImapTimeouts, ImapTransport, StreamWatchdog, TimeoutStream, TlsTransport,
+};
+use super::search::{quote, SearchQuery};
+use super::security::MessageSecurity;
+use super::types::{
+    AttachmentContent, Email, EmailListItem, Folder, FolderChanges, FolderDelta, FolderSyncState,
+    IdleFolderState, MessageFlags, OriginalMessage, SpecialFolder, SyncState,
+};
+use super::threading::{bracket_message_id, message_ids_in, ReplyHeaders, ThreadMessage};
+use super::utf7::{decode_imap_utf7, encode_imap_utf7};
+use crate::llm::rag::strip_html;
 
 /// Type alias for the TLS stream using tokio compat
-type ImapTlsStream = async_native_tls::TlsStream<tokio_util::compat::Compat<TcpStream>>;
-type ImapSession = async_imap::Session<ImapTlsStream>;
+type ImapSession = async_imap::Session<Box<dyn ImapStream>>;
 
-/// Credentials for connecting to IMAP/SMTP
-#[derive(Debug, Clone)]
+/// The UIDVALIDITY a folder's UIDs were last recorded under (by a sync or
+/// IDLE run), so checks survive a restart
+pub type UidValidityLookup = Arc<dyn Fn(&str) -> Option<u32> + Send + Sync>;
+
+/// Characters of text in a list preview
+pub const SNIPPET_LENGTH: usize = 200;
+
+/// Bytes of the text part fetched for a preview without the whole body
+const SNIPPET_FETCH_BYTES: u32 = 2048;
+
+/// Credentials for connecting to IMAP/SMTP. Deserializes from
+/// `{ "type": "oauth2" | "password", "user": ..., ... }`; never serialized.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ImapCredentials {
+    #[serde(rename = "oauth2")]
     OAuth2 {
         user: String,
         access_token: String,
@@ -48,8 +95,43 @@ pub struct ImapClient {
     pub email: String,
     pub provider: ProviderType,
     pub server_config: ServerConfig,
+    /// Timeouts and retry policy used by `send_email`
+    pub smtp_options: SmtpSendOptions,
+    /// Connect/read/write timeouts for IMAP sessions opened after this is set
+    pub timeouts: ImapTimeouts,
+    /// Servers whose Authentication-Results headers are believed
+    pub trusted_authserv_ids: Vec<String>,
     credentials: ImapCredentials,
+    /// Paces list/fetch/flag commands; shared with `new_connection` clients
+    rate_limiter: Arc<RateLimiter>,
+    transport: Arc<dyn ImapTransport>,
     session: Arc<Mutex<Option<ImapSession>>>,
+    /// Tracks whether the current session's stream has timed out
+    watchdog: Arc<StreamWatchdog>,
+    /// Whether the current session accepted `ENABLE UTF8=ACCEPT` (RFC 6855),
+    /// so mailbox names go over the wire as UTF-8 instead of modified UTF-7
+    utf8_enabled: Arc<AtomicBool>,
+    /// Whether the server advertises SORT (RFC 5256)
+    sort_supported: Arc<AtomicBool>,
+    /// Whether the server advertises CONDSTORE (RFC 7162)
+    condstore_supported: Arc<AtomicBool>,
+    /// Whether the server advertises UIDPLUS (RFC 4315), so single messages
+    /// can be expunged with UID EXPUNGE
+    uidplus_supported: Arc<AtomicBool>,
+    /// Whether the server advertises Gmail's X-GM-EXT-1, so keywords are
+    /// stored as labels
+    gmail_labels: Arc<AtomicBool>,
+    /// Whether the server advertises IDLE (RFC 2177); without it new mail is
+    /// found by polling
+    idle_supported: Arc<AtomicBool>,
+    /// Mechanism tried first for OAuth2 logins; updated to whichever the
+    /// server last accepted
+    oauth_mechanism: Arc<std::sync::Mutex<OAuthMechanism>>,
+    /// UIDVALIDITY of each folder when its messages were last listed. UIDs
+    /// handed out before a change name different messages now.
+    uid_validity: Arc<std::sync::Mutex<HashMap<String, u32>>>,
+    /// Where to find a folder's UIDVALIDITY before it's listed this session
+    uid_validity_lookup: Option<UidValidityLookup>,
 }
 
 impl ImapClient {
@@ -59,63 +141,321 @@ impl ImapClient {
         provider: ProviderType,
         server_config: ServerConfig,
         credentials: ImapCredentials,
+    ) -> Self {
+        let transport = Arc::new(TlsTransport {
+            host: server_config.imap_host.clone(),
+            port: server_config.imap_port,
+            mode: server_config.tls_mode,
+        });
+        Self::with_transport(account_id, email, provider, server_config, credentials, transport)
+    }
+
+    /// Create a client whose IMAP connections are opened by `transport`
+    /// (the test suite plugs in an in-memory server here)
+    pub fn with_transport(
+        account_id: String,
+        email: String,
+        provider: ProviderType,
+        server_config: ServerConfig,
+        credentials: ImapCredentials,
+        transport: Arc<dyn ImapTransport>,
     ) -> Self {
         Self {
             account_id,
             email,
+            trusted_authserv_ids: provider_authserv_ids(&provider, &server_config.imap_host),
             provider,
             server_config,
+            smtp_options: SmtpSendOptions::default(),
+            timeouts: ImapTimeouts::default(),
+            rate_limiter: Arc::new(RateLimiter::new(RateLimits::for_provider(&provider))),
             credentials,
+            transport,
             session: Arc::new(Mutex::new(None)),
+            watchdog: Arc::new(StreamWatchdog::default()),
+            utf8_enabled: Arc::new(AtomicBool::new(false)),
+            sort_supported: Arc::new(AtomicBool::new(false)),
+            condstore_supported: Arc::new(AtomicBool::new(false)),
+            uidplus_supported: Arc::new(AtomicBool::new(false)),
+            gmail_labels: Arc::new(AtomicBool::new(false)),
+            idle_supported: Arc::new(AtomicBool::new(false)),
+            oauth_mechanism: Arc::new(std::sync::Mutex::new(OAuthMechanism::default())),
+            uid_validity: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            uid_validity_lookup: None,
         }
     }
 
+    /// Another client for the same account and credentials, with its own
+    /// session (not opened until first used)
+    pub fn new_connection(&self) -> Self {
+        let mut client = Self::with_transport(
+            self.account_id.clone(),
+            self.email.clone(),
+            self.provider.clone(),
+            self.server_config.clone(),
+            self.credentials.clone(),
+            self.transport.clone(),
+        );
+        client.smtp_options = self.smtp_options.clone();
+        client.timeouts = self.timeouts;
+        client.trusted_authserv_ids = self.trusted_authserv_ids.clone();
+        client.rate_limiter = self.rate_limiter.clone();
+        client.oauth_mechanism = self.oauth_mechanism.clone();
+        client.uid_validity = self.uid_validity.clone();
+        client.uid_validity_lookup = self.uid_validity_lookup.clone();
+        client
+    }
+
+    /// Use `timeouts` instead of the defaults
+    pub fn with_timeouts(mut self, timeouts: ImapTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Use `limits` instead of the provider's defaults
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(limits));
+        self
+    }
+
+    /// Believe Authentication-Results from `ids` instead of only the provider's
+    pub fn with_trusted_authserv_ids(mut self, ids: Vec<String>) -> Self {
+        self.trusted_authserv_ids = ids;
+        self
+    }
+
+    pub fn rate_limits(&self) -> RateLimits {
+        self.rate_limiter.limits()
+    }
+
+    /// Try `mechanism` first for OAuth2 logins instead of XOAUTH2
+    pub fn with_oauth_mechanism(self, mechanism: OAuthMechanism) -> Self {
+        *self.oauth_mechanism.lock().unwrap() = mechanism;
+        self
+    }
+
+    /// Check UIDs in folders not listed yet this session against the
+    /// UIDVALIDITY `lookup` recorded; folders it has nothing for are refused
+    pub fn with_uid_validity_lookup(mut self, lookup: UidValidityLookup) -> Self {
+        self.uid_validity_lookup = Some(lookup);
+        self
+    }
+
     pub fn update_credentials(&mut self, credentials: ImapCredentials) {
         self.credentials = credentials;
     }
 
-    /// Connect to IMAP server and authenticate
+    /// Connect to IMAP server and authenticate, giving up after the connect
+    /// timeout
     async fn connect(&self) -> Result<ImapSession> {
-        let tls = TlsConnector::new();
-        let tcp = TcpStream::connect((
-            self.server_config.imap_host.as_str(),
-            self.server_config.imap_port,
-        ))
-        .await
-        .context("Failed to connect to IMAP server")?;
-
-        // Convert tokio TcpStream to futures_io compatible stream
-        let tcp_compat = tcp.compat();
-
-        let tls_stream = tls
-            .connect(&self.server_config.imap_host, tcp_compat)
+        self.watchdog.reset();
+        tokio::time::timeout(self.timeouts.connect, self.open_session())
             .await
-            .context("TLS handshake failed")?;
+            .context(format!(
+                "Timed out connecting to {} after {}s",
+                self.server_config.imap_host,
+                self.timeouts.connect.as_secs()
+            ))?
+    }
 
-        let client = async_imap::Client::new(tls_stream);
+    async fn open_session(&self) -> Result<ImapSession> {
+        let stream = self.transport.open().await?;
+        let stream: Box<dyn ImapStream> = Box::new(TimeoutStream::new(
+            stream,
+            self.timeouts,
+            self.watchdog.clone(),
+        ));
+        let client = async_imap::Client::new(stream);
 
         let session = match &self.credentials {
             ImapCredentials::OAuth2 { user, access_token } => {
-                let auth_string = format!(
-                    "user={}\x01auth=Bearer {}\x01\x01",
-                    user, access_token
-                );
-                client
-                    .authenticate("XOAUTH2", XOAuth2Authenticator(auth_string))
-                    .await
-                    .map_err(|(e, _)| anyhow::anyhow!("XOAUTH2 authentication failed: {}", e))?
+                self.authenticate_oauth(client, user, access_token).await?
             }
             ImapCredentials::Password { user, password } => client
                 .login(user, password)
                 .await
-                .map_err(|(e, _)| anyhow::anyhow!("IMAP login failed: {}", e))?,
+                .map_err(|(e, _)| anyhow::Error::new(LoginRejected(e)))
+                .context("IMAP login failed")?,
         };
 
+        let mut session = session;
+        let (utf8_accept, sort, condstore, uidplus, gmail, idle) =
+            match session.capabilities().await {
+                Ok(caps) => (
+                    caps.has_str("UTF8=ACCEPT"),
+                    caps.has_str("SORT"),
+                    caps.has_str("CONDSTORE"),
+                    caps.has_str("UIDPLUS"),
+                    caps.has_str("X-GM-EXT-1"),
+                    caps.has_str("IDLE"),
+                ),
+                Err(e) => {
+                    eprintln!("[IMAP] CAPABILITY failed: {}", e);
+                    // Unknown: try IDLE as before rather than polling
+                    (false, false, false, false, false, true)
+                }
+            };
+        let utf8 = utf8_accept && Self::enable_utf8(&mut session).await;
+        self.utf8_enabled.store(utf8, Ordering::Relaxed);
+        self.sort_supported.store(sort, Ordering::Relaxed);
+        self.condstore_supported.store(condstore, Ordering::Relaxed);
+        self.uidplus_supported.store(uidplus, Ordering::Relaxed);
+        self.gmail_labels.store(gmail, Ordering::Relaxed);
+        self.idle_supported.store(idle, Ordering::Relaxed);
+
         Ok(session)
     }
 
+    /// AUTHENTICATE with the preferred OAuth mechanism. If the server rejects
+    /// it, the other one is tried once on the same connection before giving up.
+    async fn authenticate_oauth(
+        &self,
+        mut client: async_imap::Client<Box<dyn ImapStream>>,
+        user: &str,
+        access_token: &str,
+    ) -> Result<ImapSession> {
+        let mut mechanism = *self.oauth_mechanism.lock().unwrap();
+        let mut retried = false;
+        loop {
+            let authenticator = OAuthAuthenticator {
+                response: mechanism.initial_response(
+                    user,
+                    access_token,
+                    &self.server_config.imap_host,
+                    self.server_config.imap_port,
+                ),
+                error_ack: mechanism.error_ack(),
+                sent: false,
+            };
+            match client.authenticate(mechanism.as_str(), authenticator).await {
+                Ok(session) => {
+                    println!(
+                        "[IMAP:{}] Authenticated with {}",
+                        self.account_id,
+                        mechanism.as_str()
+                    );
+                    *self.oauth_mechanism.lock().unwrap() = mechanism;
+                    return Ok(session);
+                }
+                Err((
+                    e @ (async_imap::error::Error::No(_) | async_imap::error::Error::Bad(_)),
+                    returned,
+                )) if !retried => {
+                    eprintln!(
+                        "[IMAP:{}] {} rejected ({}), retrying with {}",
+                        self.account_id,
+                        mechanism.as_str(),
+                        e,
+                        mechanism.other().as_str()
+                    );
+                    client = returned;
+                    mechanism = mechanism.other();
+                    retried = true;
+                }
+                Err((e, _)) => {
+                    return Err(anyhow::Error::new(LoginRejected(e)))
+                        .context(format!("{} authentication failed", mechanism.as_str()));
+                }
+            }
+        }
+    }
+
+    /// Log in if needed, then run CAPABILITY and NOOP. Returns the advertised
+    /// capabilities, sorted.
+    pub async fn check_connection(&self) -> Result<Vec<String>> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mut capabilities: Vec<String> = session
+            .capabilities()
+            .await
+            .context("CAPABILITY failed")?
+            .iter()
+            .map(|capability| match capability {
+                Capability::Imap4rev1 => "IMAP4rev1".to_string(),
+                Capability::Auth(mechanism) => format!("AUTH={}", mechanism),
+                Capability::Atom(atom) => atom.to_string(),
+            })
+            .collect();
+        capabilities.sort();
+        session.noop().await.context("NOOP failed")?;
+        Ok(capabilities)
+    }
+
+    /// Issue `ENABLE UTF8=ACCEPT` (the server must advertise it)
+    async fn enable_utf8(session: &mut ImapSession) -> bool {
+        match session.run_command_and_check_ok("ENABLE UTF8=ACCEPT").await {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("[IMAP] ENABLE UTF8=ACCEPT failed: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Remember the UIDVALIDITY a folder's messages were listed under
+    fn note_uid_validity(&self, folder: &str, uid_validity: Option<u32>) {
+        if let Some(uid_validity) = uid_validity {
+            let mut known = self.uid_validity.lock().unwrap();
+            known.insert(folder.to_string(), uid_validity);
+        }
+    }
+
+    /// Fail if `folder`'s UIDVALIDITY changed since its messages were listed,
+    /// so a UID from that listing is never applied to another message. The
+    /// folder has to be listed again before its UIDs are accepted. Folders not
+    /// listed this session are checked against the recorded validity; without
+    /// a lookup the first SELECT is trusted.
+    fn check_uid_validity(&self, folder: &str, uid_validity: Option<u32>) -> Result<()> {
+        let Some(found) = uid_validity else {
+            return Ok(());
+        };
+        let known = self.uid_validity.lock().unwrap().get(folder).copied();
+        let expected = match (known, &self.uid_validity_lookup) {
+            (Some(expected), _) => Some(expected),
+            (None, Some(lookup)) => lookup(folder),
+            (None, None) => Some(found),
+        };
+        match expected {
+            Some(expected) if expected == found => {
+                let mut known = self.uid_validity.lock().unwrap();
+                known.entry(folder.to_string()).or_insert(found);
+                Ok(())
+            }
+            expected => Err(UidValidityChanged {
+                folder: folder.to_string(),
+                expected,
+                found,
+            }
+            .into()),
+        }
+    }
+
+    /// Mailbox name as sent to the server
+    fn wire_name(&self, folder: &str) -> String {
+        if self.utf8_enabled.load(Ordering::Relaxed) {
+            folder.to_string()
+        } else {
+            encode_imap_utf7(folder)
+        }
+    }
+
+    /// Mailbox name from a server response, as UTF-8
+    fn decode_name(&self, name: &str) -> String {
+        if self.utf8_enabled.load(Ordering::Relaxed) {
+            name.to_string()
+        } else {
+            decode_imap_utf7(name)
+        }
+    }
+
     async fn get_session(&self) -> Result<tokio::sync::MutexGuard<'_, Option<ImapSession>>> {
         let mut guard = self.session.lock().await;
+        if guard.is_some() && self.watchdog.expired() {
+            // A command timed out mid-response; the session can't be reused
+            eprintln!("[IMAP] Session for {} timed out, reconnecting", self.account_id);
+            guard.take();
+        }
         if guard.is_none() {
             let session = self.connect().await?;
             *guard = Some(session);
@@ -133,6 +473,13 @@ impl ImapClient {
         Ok(())
     }
 
+    /// Log out; the next command opens a new session
+    pub async fn disconnect(&self) {
+        if let Some(mut session) = self.session.lock().await.take() {
+            let _ = session.logout().await;
+        }
+    }
+
     /// Parse a raw email message into our Email type
     pub fn parse_raw_email(
         &self,
@@ -169,21 +516,9 @@ impl ImapClient {
             .unwrap_or("")
             .to_string();
 
-        let to: Vec<String> = parsed
-            .to()
-            .map(|addrs| {
-                addrs
-                    .iter()
-                    .map(|addr| {
-                        if let Some(name) = addr.name() {
-                            format!("{} <{}>", name, addr.address().unwrap_or(""))
-                        } else {
-                            addr.address().unwrap_or("").to_string()
-                        }
-                    })
-                    .collect()
-            })
-            .unwrap_or_default();
+        let to = address_strings(parsed.to());
+        let cc = address_strings(parsed.cc());
+        let reply_to = address_strings(parsed.reply_to());
 
         let date = parsed
             .date()
@@ -197,22 +532,48 @@ impl ImapClient {
 
         let body_html = parsed.body_html(0).map(|s| s.to_string());
         let body_plain = parsed.body_text(0).map(|s| s.to_string());
+        let security = MessageSecurity::from_message(&parsed);
+        let invite = Invite::from_message(&parsed);
+        let authentication = AuthenticationSummary::from_headers(
+            &split_raw_headers(raw),
+            &self.trusted_authserv_ids,
+        );
 
-        let snippet = body_plain
-            .as_deref()
-            .unwrap_or("")
-            .chars()
-            .take(200)
-            .collect::<String>()
-            .replace('\n', " ")
-            .replace('\r', "");
+        // Ciphertext makes no sense as a preview
+        let snippet = if security.encrypted {
+            String::new()
+        } else {
+            make_snippet(body_plain.as_deref().unwrap_or(""), SNIPPET_LENGTH)
+        };
 
         let is_read = flags.iter().any(|f| matches!(f, Flag::Seen));
         let is_starred = flags.iter().any(|f| matches!(f, Flag::Flagged));
         let has_attachments = parsed.attachment_count() > 0;
 
         let message_id = parsed.message_id().unwrap_or("").to_string();
+        let in_reply_to = parsed
+            .in_reply_to()
+            .as_text()
+            .filter(|id| !id.is_empty())
+            .map(str::to_string);
+        let references = parsed
+            .references()
+            .as_text_list()
+            .unwrap_or_default()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
         let thread_id = self.compute_thread_id(&parsed);
+        let mailing_list = MailingList::from_headers(
+            parsed.header_raw("List-Id"),
+            parsed.header_raw("List-Post"),
+            parsed.header_raw("List-Archive"),
+            parsed.header_raw("List-Unsubscribe"),
+        );
+        let unsubscribe = UnsubscribeInfo::from_headers(
+            parsed.header_raw("List-Unsubscribe"),
+            parsed.header_raw("List-Unsubscribe-Post"),
+        );
         let id = format!("{}:{}:{}", self.account_id, folder, uid);
 
         let mut labels = Vec::new();
@@ -233,6 +594,8 @@ impl ImapClient {
             from,
             from_email,
             to,
+            cc,
+            reply_to,
             date,
             date_timestamp,
             snippet,
@@ -246,6 +609,19 @@ impl ImapClient {
             uid,
             folder: folder.to_string(),
             message_id,
+            is_first_contact: false,
+            mailing_list,
+            unsubscribe,
+            sync_state: SyncState::LiveFetched,
+            security,
+            size: raw.len() as u32,
+            attachments: Vec::new(),
+            inline_parts: Vec::new(),
+            remote_content_blocked: false,
+            in_reply_to,
+            references,
+            invite,
+            authentication,
         })
     }
 
@@ -291,19 +667,31 @@ impl ImapClient {
             is_read: email.is_read,
             is_starred: email.is_starred,
             has_attachments: email.has_attachments,
+            is_first_contact: email.is_first_contact,
+            sync_state: email.sync_state,
+            size: email.size,
         }
     }
 
     async fn build_smtp_transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
-        let builder = if self.server_config.smtp_port == 465 {
-            AsyncSmtpTransport::<Tokio1Executor>::relay(&self.server_config.smtp_host)?
-                .port(self.server_config.smtp_port)
-        } else {
-            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.server_config.smtp_host)?
-                .port(self.server_config.smtp_port)
-        };
+        // Per-command timeout; whole phases are additionally bounded in `try_send_once`
+        let command_timeout = self
+            .smtp_options
+            .connect_timeout
+            .max(self.smtp_options.auth_timeout);
+
+        let host = &self.server_config.smtp_host;
+        // starttls_relay refuses to continue if the server doesn't offer STARTTLS
+        let builder = match self.server_config.smtp_tls() {
+            TlsMode::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            TlsMode::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            TlsMode::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        }
+        .port(self.server_config.smtp_port)
+        .timeout(Some(command_timeout));
 
         let transport = match &self.credentials {
+            // lettre only implements XOAUTH2, so SMTP has no OAUTHBEARER fallback
             ImapCredentials::OAuth2 { user, access_token } => builder
                 .credentials(Credentials::new(user.clone(), access_token.clone()))
                 .authentication(vec![Mechanism::Xoauth2])
@@ -316,194 +704,2338 @@ impl ImapClient {
         Ok(transport)
     }
 
-    pub async fn idle_wait(&self, folder: &str, timeout_secs: u64) -> Result<bool> {
+    /// Submit a message over SMTP, retrying transient failures (4xx replies, failures
+    /// while connecting or authenticating) with exponential backoff. Permanent failures
+    /// fail immediately, and so does a timeout or dropped connection mid-send, since the
+    /// server may have accepted the message. Returns the bytes sent; every attempt sends
+    /// the same ones.
+    pub async fn send_with_retry(&self, message: &Message) -> Result<Vec<u8>> {
+        let options = &self.smtp_options;
+        let envelope = message.envelope();
+        let raw = message.formatted();
+        let mut attempt = 1;
+
+        loop {
+            match self.try_send_once(envelope, &raw).await {
+                Ok(()) => return Ok(raw),
+                Err(e) if e.is_transient() && attempt < options.max_attempts => {
+                    let delay = retry_backoff(options, attempt);
+                    eprintln!(
+                        "[SMTP:{}] Attempt {} failed: {}. Retrying in {}s...",
+                        self.account_id,
+                        attempt,
+                        e,
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn try_send_once(
+        &self,
+        envelope: &Envelope,
+        raw: &[u8],
+    ) -> std::result::Result<(), SmtpSendError> {
+        let transport = self.connect_smtp().await?;
+
+        let data_timeout = self.smtp_options.data_timeout;
+        match tokio::time::timeout(data_timeout, transport.send_raw(envelope, raw)).await {
+            Err(_) => Err(SmtpSendError::timeout(SmtpPhase::Data, data_timeout)),
+            Ok(Err(e)) => Err(SmtpSendError::from_lettre(SmtpPhase::Data, &e)),
+            Ok(Ok(_)) => Ok(()),
+        }
+    }
+
+    /// Connect to the SMTP server and authenticate without sending anything
+    pub async fn check_smtp(&self) -> std::result::Result<(), SmtpSendError> {
+        self.connect_smtp().await.map(|_| ())
+    }
+
+    /// Connect + authenticate; the pooled connection is reused by a later send
+    async fn connect_smtp(
+        &self,
+    ) -> std::result::Result<AsyncSmtpTransport<Tokio1Executor>, SmtpSendError> {
+        let transport = self
+            .build_smtp_transport()
+            .await
+            .map_err(|e| SmtpSendError::new(SmtpPhase::Connect, None, true, e.to_string()))?;
+
+        let setup_timeout = self.smtp_options.connect_timeout + self.smtp_options.auth_timeout;
+        match tokio::time::timeout(setup_timeout, transport.test_connection()).await {
+            Err(_) => return Err(SmtpSendError::timeout(SmtpPhase::Connect, setup_timeout)),
+            Ok(Err(e)) => return Err(SmtpSendError::from_lettre(SmtpPhase::Connect, &e)),
+            Ok(Ok(false)) => {
+                return Err(SmtpSendError::new(
+                    SmtpPhase::Connect,
+                    None,
+                    false,
+                    "server closed the connection".to_string(),
+                ))
+            }
+            Ok(Ok(true)) => {}
+        }
+        Ok(transport)
+    }
+
+    /// IDLE on a folder until the server reports a change or `timeout_secs`
+    /// pass. On a change, returns the UIDs added since SELECT (at or above its
+    /// UIDNEXT) and the ones expunged meanwhile; None on timeout.
+    ///
+    /// When `shutdown` turns true, IDLE is ended with DONE and the session
+    /// logged out, so the server isn't left with a half-finished command.
+    pub async fn idle_wait(
+        &self,
+        folder: &str,
+        timeout_secs: u64,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> Result<Option<FolderChanges>> {
         let mut guard = self.session.lock().await;
-        let session = guard.take().context("No IMAP session")?;
+        let mut session = guard.take().context("No IMAP session")?;
 
         // Select folder first, then start IDLE
-        let mut session = session;
-        session
-            .select(folder)
+        let mailbox = session
+            .select(self.wire_name(folder))
             .await
             .context("Failed to select folder")?;
+        let uid_next = mailbox.uid_next;
+        let known_uids = Self::known_uids(&mut session, uid_next).await?;
+
+        // Reads legitimately wait the whole IDLE; anything longer is a dead
+        // connection
+        let idle_timeout = std::time::Duration::from_secs(timeout_secs);
+        self.watchdog
+            .set_idle_read_timeout(Some(idle_timeout + self.timeouts.read));
 
         let mut idle = session.idle();
         idle.init().await.context("Failed to init IDLE")?;
 
-        let (idle_wait, _stop) =
-            idle.wait_with_timeout(std::time::Duration::from_secs(timeout_secs));
-        let result = idle_wait.await.context("IDLE wait failed")?;
+        let result = {
+            let (idle_wait, stop) = idle.wait_with_timeout(idle_timeout);
+            tokio::pin!(idle_wait);
+            let finished = tokio::select! {
+                result = &mut idle_wait => Some(result),
+                _ = shutdown_requested(shutdown) => None,
+            };
+            match finished {
+                Some(result) => result,
+                None => {
+                    // Dropping the stop source interrupts the wait, so DONE
+                    // goes out below rather than the socket closing mid-IDLE
+                    drop(stop);
+                    idle_wait.await
+                }
+            }
+        }
+        .context("IDLE wait failed")?;
+        self.watchdog.set_idle_read_timeout(None);
 
-        let new_mail = match result {
+        let changed = match result {
             IdleResponse::NewData(_) => true,
             IdleResponse::Timeout => false,
             IdleResponse::ManualInterrupt => false,
         };
 
         // Get session back from idle handle
-        let session = idle.done().await.context("Failed to finish IDLE")?;
+        let mut session = idle.done().await.context("Failed to finish IDLE")?;
+        if *shutdown.borrow() {
+            let _ = session.logout().await;
+            return Ok(None);
+        }
+
+        let changes = if changed {
+            let current_uids = session
+                .uid_search("ALL")
+                .await
+                .context("Failed to list UIDs after IDLE")?;
+            Some(diff_uids(&known_uids, &current_uids, uid_next))
+        } else {
+            None
+        };
         *guard = Some(session);
 
-        Ok(new_mail)
+        Ok(changes)
     }
 
-    /// Get folder statistics (total and unseen message counts)
-    pub async fn get_folder_stats(&self, folder: &str) -> Result<(u32, u32)> {
+    /// Stand-in for `idle_wait` on servers without IDLE: NOOP the folder
+    /// every `interval` until EXISTS or UIDNEXT change, `timeout_secs` pass
+    /// or `shutdown` is set. Returns the same changes as `idle_wait`.
+    pub async fn poll_wait(
+        &self,
+        folder: &str,
+        interval: std::time::Duration,
+        timeout_secs: u64,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> Result<Option<FolderChanges>> {
         let mut guard = self.get_session().await?;
         let session = guard.as_mut().context("No IMAP session")?;
 
-        // Use EXAMINE to check folder without marking messages as read
         let mailbox = session
-            .examine(folder)
+            .select(self.wire_name(folder))
             .await
-            .context(format!("Failed to examine folder: {}", folder))?;
-
-        let total = mailbox.exists;
-        let unseen = mailbox.unseen.unwrap_or(0);
+            .context("Failed to select folder")?;
+        let uid_next = mailbox.uid_next;
+        let known_uids = Self::known_uids(session, uid_next).await?;
 
-        Ok((total, unseen))
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = shutdown_requested(shutdown) => {
+                    let _ = session.logout().await;
+                    guard.take();
+                    return Ok(None);
+                }
+            }
+            if Self::noop_changed(session, mailbox.exists, uid_next).await? {
+                let current_uids = session
+                    .uid_search("ALL")
+                    .await
+                    .context("Failed to list UIDs after polling")?;
+                return Ok(Some(diff_uids(&known_uids, &current_uids, uid_next)));
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
     }
 
-    /// Parse a FETCH response into an EmailListItem
-    fn parse_fetch_to_list_item(&self, uid: u32, folder: &str, fetch: &Fetch) -> EmailListItem {
-        let flags: Vec<Flag<'_>> = fetch.flags().collect();
-        let is_read = flags.iter().any(|f| matches!(f, Flag::Seen));
-        let is_starred = flags.iter().any(|f| matches!(f, Flag::Flagged));
+    /// UIDVALIDITY, UIDNEXT and every UID of a folder, for an IDLE monitor
+    /// to compare against after reconnecting. The folder is EXAMINEd, so
+    /// nothing is marked as seen.
+    pub async fn idle_folder_state(&self, folder: &str) -> Result<IdleFolderState> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
 
-        let (subject, from, from_email, date) = if let Some(envelope) = fetch.envelope() {
-            let subject = envelope
-                .subject
-                .as_ref()
-                .and_then(|s| std::str::from_utf8(s).ok())
-                .unwrap_or("(No Subject)")
-                .to_string();
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        let mut uids: Vec<u32> = session
+            .uid_search("ALL")
+            .await
+            .context("Failed to list UIDs")?
+            .into_iter()
+            .collect();
+        uids.sort_unstable();
 
-            let (from, from_email) = envelope
-                .from
-                .as_ref()
-                .and_then(|addrs| addrs.first())
-                .map(|addr| {
-                    let name = addr
-                        .name
-                        .as_ref()
-                        .and_then(|n| std::str::from_utf8(n).ok())
-                        .unwrap_or("");
-                    let mailbox = addr
-                        .mailbox
-                        .as_ref()
-                        .and_then(|m| std::str::from_utf8(m).ok())
-                        .unwrap_or("");
-                    let host = addr
-                        .host
-                        .as_ref()
-                        .and_then(|h| std::str::from_utf8(h).ok())
-                        .unwrap_or("");
-                    let email = format!("{}@{}", mailbox, host);
-                    if name.is_empty() {
-                        (email.clone(), email)
-                    } else {
-                        (format!("{} <{}>", name, email), email)
-                    }
-                })
-                .unwrap_or_else(|| ("Unknown".to_string(), String::new()));
+        Ok(IdleFolderState {
+            uid_validity: mailbox.uid_validity.unwrap_or(0),
+            uid_next: mailbox
+                .uid_next
+                .unwrap_or_else(|| uids.last().map_or(1, |uid| uid + 1)),
+            uids,
+        })
+    }
 
-            let date = envelope
-                .date
-                .as_ref()
-                .and_then(|d| std::str::from_utf8(d).ok())
-                .unwrap_or("")
-                .to_string();
+    /// Whether the server advertised IDLE (RFC 2177) on the last connect
+    pub fn supports_idle(&self) -> bool {
+        self.idle_supported.load(Ordering::Relaxed)
+    }
 
-            (subject, from, from_email, date)
-        } else {
-            (
-                "(No Subject)".to_string(),
-                "Unknown".to_string(),
-                String::new(),
-                String::new(),
-            )
+    /// UIDs in the selected folder below `uid_next`, to tell later which
+    /// ones an EXPUNGE removed
+    async fn known_uids(session: &mut ImapSession, uid_next: Option<u32>) -> Result<HashSet<u32>> {
+        let query = match uid_next {
+            Some(next) if next > 1 => format!("UID 1:{}", next - 1),
+            Some(_) => return Ok(HashSet::new()),
+            None => "ALL".to_string(),
         };
+        session
+            .uid_search(query)
+            .await
+            .context("Failed to list UIDs before waiting for changes")
+    }
 
-        let id = format!("{}:{}:{}", self.account_id, folder, uid);
+    /// Send NOOP and report whether its untagged responses show the selected
+    /// folder changed since it had `exists` messages and `uid_next`
+    async fn noop_changed(
+        session: &mut ImapSession,
+        exists: u32,
+        uid_next: Option<u32>,
+    ) -> Result<bool> {
+        let id = session
+            .run_command("NOOP")
+            .await
+            .context("Failed to send NOOP")?;
 
-        EmailListItem {
-            id,
-            thread_id: String::new(),
-            subject,
-            from,
-            from_email,
-            date,
-            snippet: String::new(),
-            is_read,
-            is_starred,
-            has_attachments: false,
+        let mut changed = false;
+        while let Some(response) = session.read_response().await {
+            let response = response.context("Failed to read NOOP response")?;
+            match response.parsed() {
+                Response::MailboxData(MailboxDatum::Exists(count)) => changed |= *count != exists,
+                Response::Expunge(_) => changed = true,
+                Response::Data {
+                    code: Some(ResponseCode::UidNext(next)),
+                    ..
+                } => changed |= Some(*next) != uid_next,
+                Response::Done {
+                    tag,
+                    status,
+                    information,
+                    ..
+                } if *tag == id => {
+                    if *status != Status::Ok {
+                        anyhow::bail!("NOOP failed: {}", information.as_deref().unwrap_or(""));
+                    }
+                    return Ok(changed);
+                }
+                _ => {}
+            }
         }
+        anyhow::bail!("Connection closed during NOOP")
     }
 
-    fn detect_special_folder(
-        &self,
-        name: &str,
-        _attributes: &[async_imap::types::NameAttribute<'_>],
-    ) -> Option<SpecialFolder> {
-        // Name-based detection (works across all IMAP servers)
-        let lower = name.to_lowercase();
-        if lower == "inbox" {
-            Some(SpecialFolder::Inbox)
-        } else if lower.contains("sent") {
-            Some(SpecialFolder::Sent)
-        } else if lower.contains("trash") || lower.contains("deleted") {
-            Some(SpecialFolder::Trash)
-        } else if lower.contains("draft") {
-            Some(SpecialFolder::Drafts)
-        } else if lower.contains("spam") || lower.contains("junk") {
-            Some(SpecialFolder::Spam)
-        } else if lower.contains("archive") || lower.contains("all mail") {
-            Some(SpecialFolder::Archive)
-        } else {
-            None
-        }
+    /// Get folder statistics (total and unseen message counts).
+    /// Uses STATUS, so the selected folder (and anything IDLEing on it) is left alone.
+    pub async fn get_folder_stats(&self, folder: &str) -> Result<(u32, u32)> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .status(self.wire_name(folder), "(MESSAGES UNSEEN)")
+            .await
+            .context(format!("Failed to get status of folder: {}", folder))?;
+
+        let total = mailbox.exists;
+        let unseen = mailbox.unseen.unwrap_or(0);
+
+        Ok((total, unseen))
     }
-}
 
-/// XOAUTH2 authenticator for async-imap
-struct XOAuth2Authenticator(String);
+    /// UIDs of unseen messages in a folder, newest (highest UID) first.
+    /// Only runs UID SEARCH, so no envelopes or bodies are downloaded.
+    pub async fn search_unseen_uids(&self, folder: &str) -> Result<Vec<u32>> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
 
-impl async_imap::Authenticator for XOAuth2Authenticator {
-    type Response = String;
+        session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
 
-    fn process(&mut self, _data: &[u8]) -> Self::Response {
-        self.0.clone()
+        let uids = session
+            .uid_search("UNSEEN")
+            .await
+            .context("Failed to search for unseen messages")?;
+
+        let mut uids: Vec<u32> = uids.into_iter().collect();
+        uids.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(uids)
     }
-}
 
-#[async_trait::async_trait]
-impl EmailProvider for ImapClient {
-    async fn list_messages(
-        &self,
-        folder: &str,
-        max_results: u32,
-        offset: u32,
-    ) -> Result<Vec<EmailListItem>> {
+    /// Set \Seen on every unseen message in a folder with one UID STORE and
+    /// return the UIDs it changed. An empty folder is a no-op.
+    pub async fn mark_folder_read(&self, folder: &str) -> Result<Vec<u32>> {
         let mut guard = self.get_session().await?;
         let session = guard.as_mut().context("No IMAP session")?;
 
         let mailbox = session
-            .select(folder)
+            .select(self.wire_name(folder))
             .await
-            .context("Failed to select folder")?;
-
-        let total = mailbox.exists;
-        if total == 0 {
-            return Ok(vec![]);
+            .context(format!("Failed to select folder: {}", folder))?;
+        if mailbox.exists == 0 {
+            return Ok(Vec::new());
         }
 
-        let end = total.saturating_sub(offset);
-        if end == 0 {
-            return Ok(vec![]);
+        let mut uids: Vec<u32> = session
+            .uid_search("UNSEEN")
+            .await
+            .context("Failed to search for unseen messages")?
+            .into_iter()
+            .collect();
+        if uids.is_empty() {
+            return Ok(uids);
         }
-        let start = end.saturating_sub(max_results).max(1);
+        uids.sort_unstable();
 
-        let range = format!("{}:{}", start, end);
+        // .SILENT so a large folder doesn't echo a FETCH per message
+        let error = "Failed to mark folder read";
+        let updates: Vec<_> = session
+            .uid_store(compact_uid_set(&uids), "+FLAGS.SILENT (\\Seen)")
+            .await
+            .context(error)?
+            .collect::<Vec<_>>()
+            .await;
+        for update in updates {
+            update.context(error)?;
+        }
+        Ok(uids)
+    }
+
+    /// UIDs matching a search in a folder, newest (highest UID) first
+    pub async fn search_messages(&self, folder: &str, criteria: &SearchQuery) -> Result<Vec<u32>> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+
+        // UTF8=ACCEPT already makes strings UTF-8; otherwise the charset must be named
+        let mut query = criteria.to_imap_criteria();
+        if !criteria.is_ascii() && !self.utf8_enabled.load(Ordering::Relaxed) {
+            query = format!("CHARSET UTF-8 {}", query);
+        }
+
+        let uids = session
+            .uid_search(query)
+            .await
+            .context("Failed to search messages")?;
+
+        let mut uids: Vec<u32> = uids.into_iter().collect();
+        uids.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(uids)
+    }
+
+    /// Flags changed in a folder since `since`. With CONDSTORE only messages
+    /// whose MODSEQ is above the saved HIGHESTMODSEQ are fetched, plus the
+    /// folder's UIDs to find expunged messages by; otherwise (or without a
+    /// usable `since`) the flags of every message are.
+    pub async fn sync_changes(
+        &self,
+        folder: &str,
+        since: Option<FolderSyncState>,
+    ) -> Result<FolderDelta> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let condstore = self.condstore_supported.load(Ordering::Relaxed);
+        let mailbox = if condstore {
+            session.select_condstore(self.wire_name(folder)).await
+        } else {
+            session.select(self.wire_name(folder)).await
+        }
+        .context(format!("Failed to select folder: {}", folder))?;
+        self.note_uid_validity(folder, mailbox.uid_validity);
+
+        let state = FolderSyncState {
+            uid_validity: mailbox.uid_validity.unwrap_or(0),
+            uid_next: mailbox.uid_next.unwrap_or(0),
+            highest_modseq: mailbox.highest_modseq,
+        };
+        let since_modseq = since
+            .filter(|since| condstore && since.uid_validity == state.uid_validity)
+            .and_then(|since| since.highest_modseq);
+
+        if mailbox.exists == 0 {
+            return Ok(FolderDelta {
+                state,
+                messages: Vec::new(),
+                uids: Vec::new(),
+                complete: true,
+            });
+        }
+
+        let mut messages = Vec::new();
+        if since_modseq.is_none() || since_modseq != state.highest_modseq {
+            let query = match since_modseq {
+                Some(modseq) => format!("(UID FLAGS) (CHANGEDSINCE {})", modseq),
+                None => "(UID FLAGS)".to_string(),
+            };
+            let fetches = session
+                .uid_fetch("1:*", query)
+                .await
+                .context("Failed to fetch flag changes")?
+                .collect::<Vec<_>>()
+                .await;
+
+            messages.reserve(fetches.len());
+            for fetch in fetches {
+                let fetch = fetch.context("Failed to fetch flag changes")?;
+                let Some(uid) = fetch.uid else { continue };
+                let flags: Vec<Flag<'_>> = fetch.flags().collect();
+                messages.push(MessageFlags {
+                    uid,
+                    is_read: flags.iter().any(|f| matches!(f, Flag::Seen)),
+                    is_starred: flags.iter().any(|f| matches!(f, Flag::Flagged)),
+                });
+            }
+        }
+
+        // CHANGEDSINCE never reports expunged messages (that takes QRESYNC),
+        // so list every UID still in the folder
+        let uids = match since_modseq {
+            Some(_) => {
+                let mut uids: Vec<u32> = session
+                    .uid_search("ALL")
+                    .await
+                    .context("Failed to list UIDs")?
+                    .into_iter()
+                    .collect();
+                uids.sort_unstable();
+                uids
+            }
+            None => messages.iter().map(|m| m.uid).collect(),
+        };
+
+        Ok(FolderDelta {
+            state,
+            messages,
+            uids,
+            complete: since_modseq.is_none(),
+        })
+    }
+
+    /// Envelopes for specific UIDs in a folder, in the order given
+    pub async fn list_messages_by_uid(
+        &self,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<Vec<EmailListItem>> {
+        if uids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+
+        self.fetch_list_items(session, folder, uids).await
+    }
+
+    /// Threading data for the newest `max_results` messages in `folder`: the
+    /// envelope, References and, on Gmail, X-GM-THRID
+    pub async fn list_thread_messages(
+        &self,
+        folder: &str,
+        max_results: u32,
+    ) -> Result<Vec<ThreadMessage>> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        if mailbox.exists == 0 || max_results == 0 {
+            return Ok(vec![]);
+        }
+        let start = (mailbox.exists + 1).saturating_sub(max_results).max(1);
+        let command = format!("FETCH {}:{} {}", start, mailbox.exists, self.thread_items());
+        self.fetch_thread_messages(session, folder, command).await
+    }
+
+    /// Envelope and threading data of one message, without marking it read
+    pub async fn get_thread_message(&self, folder: &str, uid: u32) -> Result<ThreadMessage> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+        let command = format!("UID FETCH {} {}", uid, self.thread_items());
+        self.fetch_thread_messages(session, folder, command)
+            .await?
+            .into_iter()
+            .next()
+            .context("Message not found")
+    }
+
+    /// Envelope and attachment presence of `uids` in `folder`, for matching
+    /// filter rules. Doesn't mark anything read.
+    pub async fn get_rule_messages(&self, folder: &str, uids: &[u32]) -> Result<Vec<RuleMessage>> {
+        if uids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        let fetches: Vec<_> = session
+            .uid_fetch(compact_uid_set(uids), "(UID ENVELOPE BODYSTRUCTURE)")
+            .await
+            .context("Failed to fetch messages")?
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut messages = Vec::new();
+        for fetch in fetches {
+            let fetch = fetch.context("Failed to fetch messages")?;
+            let (Some(uid), Some(envelope)) = (fetch.uid, fetch.envelope()) else {
+                continue;
+            };
+            let (subject, from, _, _) = envelope_summary(envelope);
+            let message_id = envelope
+                .message_id
+                .as_ref()
+                .and_then(|id| {
+                    message_ids_in(&String::from_utf8_lossy(id))
+                        .into_iter()
+                        .next()
+                })
+                .unwrap_or_default();
+            let mut to = envelope_addresses(&envelope.to);
+            to.extend(envelope_addresses(&envelope.cc));
+            messages.push(RuleMessage {
+                uid,
+                message_id,
+                from,
+                to,
+                subject,
+                has_attachment: fetch
+                    .bodystructure()
+                    .is_some_and(|structure| !collect_attachment_parts(structure).is_empty()),
+            });
+        }
+        Ok(messages)
+    }
+
+    /// FETCH items for `parse_thread_message`
+    fn thread_items(&self) -> &'static str {
+        if self.uses_gmail_labels() {
+            "(UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] X-GM-THRID)"
+        } else {
+            "(UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)])"
+        }
+    }
+
+    /// Run a FETCH of `thread_items` and parse the results. Fetch has no
+    /// accessor for X-GM-THRID, so the responses are read directly.
+    async fn fetch_thread_messages(
+        &self,
+        session: &mut ImapSession,
+        folder: &str,
+        command: String,
+    ) -> Result<Vec<ThreadMessage>> {
+        let id = session
+            .run_command(command)
+            .await
+            .context("Failed to fetch messages")?;
+
+        let mut messages = Vec::new();
+        while let Some(response) = session.read_response().await {
+            let response = response.context("Failed to read FETCH response")?;
+            match response.parsed() {
+                Response::Fetch(_, attributes) => {
+                    messages.extend(self.parse_thread_message(folder, attributes));
+                }
+                Response::Done {
+                    tag,
+                    status,
+                    information,
+                    ..
+                } if *tag == id => {
+                    if *status != Status::Ok {
+                        anyhow::bail!("FETCH failed: {}", information.as_deref().unwrap_or(""));
+                    }
+                    return Ok(messages);
+                }
+                _ => {}
+            }
+        }
+        anyhow::bail!("Connection closed during FETCH")
+    }
+
+    fn parse_thread_message(
+        &self,
+        folder: &str,
+        attributes: &[AttributeValue<'_>],
+    ) -> Option<ThreadMessage> {
+        let first_id = |value: &Option<std::borrow::Cow<'_, [u8]>>| {
+            value.as_ref().and_then(|v| {
+                message_ids_in(&String::from_utf8_lossy(v))
+                    .into_iter()
+                    .next()
+            })
+        };
+
+        let mut message = ThreadMessage::default();
+        let mut uid = None;
+        for attribute in attributes {
+            match attribute {
+                AttributeValue::Uid(value) => uid = Some(*value),
+                AttributeValue::Flags(flags) => {
+                    message.is_read = flags.iter().any(|f| f.eq_ignore_ascii_case("\\Seen"));
+                }
+                AttributeValue::Envelope(envelope) => {
+                    let (subject, from, from_email, date) = envelope_summary(envelope);
+                    message.subject = subject;
+                    message.from = from;
+                    message.from_email = from_email;
+                    message.timestamp = DateTime::parse_from_rfc2822(date.trim())
+                        .map(|dt| dt.timestamp())
+                        .unwrap_or(0);
+                    message.date = date;
+                    message.message_id = first_id(&envelope.message_id).unwrap_or_default();
+                    message.in_reply_to = first_id(&envelope.in_reply_to);
+                }
+                AttributeValue::BodySection {
+                    data: Some(header), ..
+                } => {
+                    // "References: <a> <b>", possibly folded
+                    let header = String::from_utf8_lossy(header);
+                    if let Some((_, value)) = header.split_once(':') {
+                        message.references = message_ids_in(value);
+                    }
+                }
+                AttributeValue::GmailThrId(thread_id) => {
+                    message.gmail_thread_id = Some(*thread_id);
+                }
+                _ => {}
+            }
+        }
+
+        message.id = format!("{}:{}:{}", self.account_id, folder, uid?);
+        Some(message)
+    }
+
+    /// Set or remove flags on many messages in one folder with a single UID STORE.
+    /// On Gmail, keywords are set as labels with a second STORE of X-GM-LABELS.
+    pub async fn set_flags_bulk(
+        &self,
+        folder: &str,
+        uids: &[u32],
+        flags: &[ImapFlag],
+        add: bool,
+    ) -> Result<()> {
+        if uids.is_empty() || flags.is_empty() {
+            return Ok(());
+        }
+        for flag in flags {
+            if let ImapFlag::Keyword(keyword) = flag {
+                ImapFlag::keyword(keyword)?;
+            }
+        }
+        self.rate_limiter
+            .run(|| self.store_flags(folder, uids, flags, add))
+            .await
+    }
+
+    async fn store_flags(
+        &self,
+        folder: &str,
+        uids: &[u32],
+        flags: &[ImapFlag],
+        add: bool,
+    ) -> Result<()> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .select(self.wire_name(folder))
+            .await
+            .context("Failed to select folder")?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let (labels, flags): (Vec<&ImapFlag>, Vec<&ImapFlag>) = if self.uses_gmail_labels() {
+            flags
+                .iter()
+                .partition(|flag| matches!(flag, ImapFlag::Keyword(_)))
+        } else {
+            (Vec::new(), flags.iter().collect())
+        };
+
+        let uid_set = compact_uid_set(uids);
+        for (item, values) in [("FLAGS", flags), ("X-GM-LABELS", labels)] {
+            if values.is_empty() {
+                continue;
+            }
+            let value_str = values
+                .iter()
+                .map(|f| f.to_imap_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let (query, error) = if add {
+                (format!("+{} ({})", item, value_str), "Failed to add flags")
+            } else {
+                (format!("-{} ({})", item, value_str), "Failed to remove flags")
+            };
+
+            // Drain the responses so the change is confirmed before returning
+            let updates: Vec<_> = session
+                .uid_store(&uid_set, query)
+                .await
+                .context(error)?
+                .collect::<Vec<_>>()
+                .await;
+            for update in updates {
+                update.context(error)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether keywords map onto Gmail labels for this session
+    fn uses_gmail_labels(&self) -> bool {
+        self.gmail_labels.load(Ordering::Relaxed)
+    }
+
+    /// Archive a Gmail message by dropping its \Inbox label. It stays in
+    /// All Mail with its other labels, as archiving does in the Gmail UI.
+    pub async fn remove_inbox_label(&self, folder: &str, uid: u32) -> Result<()> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .select(self.wire_name(folder))
+            .await
+            .context("Failed to select folder")?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let error = "Failed to remove \\Inbox label";
+        let updates: Vec<_> = session
+            .uid_store(uid.to_string(), "-X-GM-LABELS (\\Inbox)")
+            .await
+            .context(error)?
+            .collect::<Vec<_>>()
+            .await;
+        for update in updates {
+            update.context(error)?;
+        }
+        Ok(())
+    }
+
+    /// COPY a message into `dest`, keeping the original. Returns the copy's
+    /// UID when the server reports it with COPYUID (RFC 4315 UIDPLUS).
+    /// Copying into the source folder is rejected rather than duplicating
+    /// the message, since Gmail treats that as a no-op.
+    pub async fn copy_message(&self, folder: &str, uid: u32, dest: &str) -> Result<Option<u32>> {
+        if folder == dest {
+            anyhow::bail!("Message is already in {}", folder);
+        }
+
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .select(self.wire_name(folder))
+            .await
+            .context("Failed to select source folder")?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        // uid_copy drops the response code, so read the tagged OK directly
+        let command = format!("UID COPY {} {}", uid, quote(&self.wire_name(dest)));
+        Self::run_copy(session, command)
+            .await?
+            .map_err(|e| anyhow::anyhow!("Failed to copy message to {}: {}", dest, e))
+    }
+
+    /// MOVE a message into `dest` and return its new UID when the server
+    /// reports it with COPYUID. Without the MOVE extension this falls back
+    /// to COPY + STORE \Deleted + EXPUNGE, like `move_message`.
+    pub async fn move_message_to(&self, folder: &str, uid: u32, dest: &str) -> Result<Option<u32>> {
+        if folder == dest {
+            anyhow::bail!("Message is already in {}", folder);
+        }
+
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .select(self.wire_name(folder))
+            .await
+            .context("Failed to select source folder")?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        // MOVE sends COPYUID untagged before the EXPUNGE (RFC 6851)
+        let target = quote(&self.wire_name(dest));
+        if let Ok(moved) = Self::run_copy(session, format!("UID MOVE {} {}", uid, target)).await? {
+            return Ok(moved);
+        }
+
+        let copied = Self::run_copy(session, format!("UID COPY {} {}", uid, target))
+            .await?
+            .map_err(|e| anyhow::anyhow!("Failed to copy message to {}: {}", dest, e))?;
+        let updates: Vec<_> = session
+            .uid_store(uid.to_string(), "+FLAGS (\\Deleted)")
+            .await
+            .context("Failed to mark as deleted")?
+            .collect::<Vec<_>>()
+            .await;
+        for update in updates {
+            update.context("Failed to mark as deleted")?;
+        }
+        let expunged: Vec<_> = session
+            .expunge()
+            .await
+            .context("Failed to expunge")?
+            .collect::<Vec<_>>()
+            .await;
+        for result in expunged {
+            result.context("Failed to expunge")?;
+        }
+        Ok(copied)
+    }
+
+    /// Run a UID COPY or MOVE and return the destination UID from COPYUID,
+    /// which MOVE sends untagged and COPY on the tagged OK. A NO or BAD
+    /// comes back as the inner error so the caller can fall back.
+    async fn run_copy(
+        session: &mut ImapSession,
+        command: String,
+    ) -> Result<std::result::Result<Option<u32>, String>> {
+        let id = session
+            .run_command(command)
+            .await
+            .context("Failed to send COPY")?;
+
+        let mut copied = None;
+        while let Some(response) = session.read_response().await {
+            let response = response.context("Failed to read COPY response")?;
+            match response.parsed() {
+                Response::Data {
+                    status: Status::Ok,
+                    code: Some(code),
+                    ..
+                } => copied = copied.or_else(|| copied_uid(code)),
+                Response::Done {
+                    tag,
+                    status,
+                    code,
+                    information,
+                } if *tag == id => {
+                    if *status != Status::Ok {
+                        return Ok(Err(information.as_deref().unwrap_or("").to_string()));
+                    }
+                    return Ok(Ok(copied.or_else(|| code.as_ref().and_then(copied_uid))));
+                }
+                _ => {}
+            }
+        }
+        anyhow::bail!("Connection closed during COPY")
+    }
+
+    /// Whether `folder` exists on the server
+    pub async fn folder_exists(&self, folder: &str) -> Result<bool> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+        Self::list_exists(session, &self.wire_name(folder)).await
+    }
+
+    async fn list_exists(session: &mut ImapSession, wire: &str) -> Result<bool> {
+        Ok(Self::find_mailbox(session, wire).await?.is_some())
+    }
+
+    /// The LIST entry of the mailbox named exactly `wire`. A LIST pattern
+    /// can't escape its wildcards, so a `*` in the name is sent as `%` and
+    /// whatever else they match is filtered out by comparing names.
+    async fn find_mailbox(session: &mut ImapSession, wire: &str) -> Result<Option<Name>> {
+        let pattern = wire.replace('*', "%");
+        let names: Vec<_> = session
+            .list(Some(""), Some(&quote(&pattern)))
+            .await
+            .context("Failed to list folders")?
+            .collect::<Vec<_>>()
+            .await;
+        Ok(names
+            .into_iter()
+            .filter_map(|name| name.ok())
+            .find(|name| same_mailbox(name.name(), wire)))
+    }
+
+    /// CREATE `folder` (and subscribe to it) unless it already exists
+    pub async fn ensure_folder(&self, folder: &str) -> Result<()> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let wire = self.wire_name(folder);
+        if Self::list_exists(session, &wire).await? {
+            return Ok(());
+        }
+
+        session
+            .create(&wire)
+            .await
+            .context(format!("Failed to create folder: {}", folder))?;
+        if let Err(e) = session.subscribe(&wire).await {
+            eprintln!("[IMAP] Failed to subscribe to {}: {}", folder, e);
+        }
+        Ok(())
+    }
+
+    /// The server's hierarchy delimiter, from `LIST "" ""`; None for a flat
+    /// namespace
+    async fn hierarchy_delimiter(session: &mut ImapSession) -> Result<Option<String>> {
+        let names: Vec<_> = session
+            .list(Some(""), None)
+            .await
+            .context("Failed to list folders")?
+            .collect::<Vec<_>>()
+            .await;
+        Ok(names
+            .iter()
+            .filter_map(|name| name.as_ref().ok())
+            .find_map(|name| name.delimiter().map(str::to_string))
+            .filter(|delimiter| !delimiter.is_empty()))
+    }
+
+    /// Fail if `folder` is INBOX or another special folder, which the app
+    /// relies on and which can't be `action` (e.g. "deleted")
+    async fn ensure_not_special(
+        &self,
+        session: &mut ImapSession,
+        folder: &str,
+        action: &str,
+    ) -> Result<()> {
+        let name = Self::find_mailbox(session, &self.wire_name(folder))
+            .await?
+            .context(format!("Folder not found: {}", folder))?;
+        if self
+            .detect_special_folder(folder, name.attributes())
+            .is_some()
+        {
+            anyhow::bail!("{} is a special folder and can't be {}", folder, action);
+        }
+        Ok(())
+    }
+
+    /// CREATE folder `name` under `parent` (top level when None) and subscribe
+    /// to it. Returns its full name.
+    pub async fn create_folder(&self, name: &str, parent: Option<&str>) -> Result<String> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let delimiter = Self::hierarchy_delimiter(session).await?;
+        let folder = nested_folder_name(parent, name, delimiter.as_deref())?;
+        let wire = self.wire_name(&folder);
+        if Self::list_exists(session, &wire).await? {
+            anyhow::bail!("Folder {} already exists", folder);
+        }
+
+        session
+            .create(&wire)
+            .await
+            .context(format!("Failed to create folder: {}", folder))?;
+        if let Err(e) = session.subscribe(&wire).await {
+            eprintln!("[IMAP] Failed to subscribe to {}: {}", folder, e);
+        }
+        Ok(folder)
+    }
+
+    /// RENAME `folder` to the full name `new_name`. Its subfolders move with
+    /// it; returns the old names of everything that moved.
+    pub async fn rename_folder(&self, folder: &str, new_name: &str) -> Result<Vec<String>> {
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            anyhow::bail!("Folder name is required");
+        }
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        self.ensure_not_special(session, folder, "renamed").await?;
+        let wire = self.wire_name(folder);
+        let new_wire = self.wire_name(new_name);
+        if Self::list_exists(session, &new_wire).await? {
+            anyhow::bail!("Folder {} already exists", new_name);
+        }
+
+        let mut moved = vec![folder.to_string()];
+        if let Some(delimiter) = Self::hierarchy_delimiter(session).await? {
+            let prefix = format!("{}{}", wire, delimiter);
+            let children: Vec<_> = session
+                .list(
+                    Some(""),
+                    Some(&quote(&format!("{}*", prefix.replace('*', "%")))),
+                )
+                .await
+                .context("Failed to list folders")?
+                .collect::<Vec<_>>()
+                .await;
+            moved.extend(
+                children
+                    .iter()
+                    .filter_map(|name| name.as_ref().ok())
+                    .filter(|name| name.name().starts_with(&prefix))
+                    .map(|name| self.decode_name(name.name())),
+            );
+        }
+
+        session
+            .rename(&wire, &new_wire)
+            .await
+            .context(format!("Failed to rename folder: {}", folder))?;
+        // Not every server carries the subscription over
+        let _ = session.unsubscribe(&wire).await;
+        if let Err(e) = session.subscribe(&new_wire).await {
+            eprintln!("[IMAP] Failed to subscribe to {}: {}", new_name, e);
+        }
+        Ok(moved)
+    }
+
+    /// Unsubscribe from and DELETE `folder`
+    pub async fn delete_folder(&self, folder: &str) -> Result<()> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        self.ensure_not_special(session, folder, "deleted").await?;
+        let wire = self.wire_name(folder);
+        let _ = session.unsubscribe(&wire).await;
+        session
+            .delete(&wire)
+            .await
+            .context(format!("Failed to delete folder: {}", folder))?;
+        Ok(())
+    }
+
+    /// UID of the message with this Message-ID (with or without angle
+    /// brackets) in `folder`, if it's there
+    pub async fn find_message_id(&self, folder: &str, message_id: &str) -> Result<Option<u32>> {
+        let message_id = bracket_message_id(message_id)?;
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.note_uid_validity(folder, mailbox.uid_validity);
+        let uids = session
+            .uid_search(format!("HEADER Message-ID {}", quote(&message_id)))
+            .await
+            .context("Failed to search messages")?;
+        Ok(uids.into_iter().max())
+    }
+
+    /// APPEND a draft to `folder` with \Draft set and return its UID. With
+    /// `replace_uid` the previous version is flagged \Deleted once the new one
+    /// is stored, and expunged on its own when the server has UIDPLUS. A plain
+    /// EXPUNGE would also remove messages other clients only flagged.
+    pub async fn save_draft(
+        &self,
+        folder: &str,
+        draft: &Message,
+        replace_uid: Option<u32>,
+    ) -> Result<u32> {
+        let saved_at: DateTime<Utc> = draft
+            .headers()
+            .get::<header::Date>()
+            .map(SystemTime::from)
+            .context("Draft has no Date header")?
+            .into();
+        let message_id = draft
+            .headers()
+            .get_raw("Message-ID")
+            .context("Draft has no Message-ID")?
+            .to_string();
+
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+        let target = self.wire_name(folder);
+
+        let uid = append_message(
+            session,
+            &target,
+            "(\\Seen \\Draft)",
+            saved_at,
+            &draft.formatted(),
+            &message_id,
+        )
+        .await
+        .context(format!("Failed to save draft to {}", folder))?;
+
+        if let Some(old_uid) = replace_uid.filter(|old| *old != uid) {
+            let updates: Vec<_> = session
+                .uid_store(old_uid.to_string(), "+FLAGS (\\Deleted)")
+                .await
+                .context("Failed to mark previous draft as deleted")?
+                .collect::<Vec<_>>()
+                .await;
+            for update in updates {
+                update.context("Failed to mark previous draft as deleted")?;
+            }
+            if self.uidplus_supported.load(Ordering::Relaxed) {
+                let expunged: Vec<_> = session
+                    .uid_expunge(old_uid.to_string())
+                    .await
+                    .context("Failed to expunge previous draft")?
+                    .collect::<Vec<_>>()
+                    .await;
+                for seq in expunged {
+                    seq.context("Failed to expunge previous draft")?;
+                }
+            }
+        }
+
+        Ok(uid)
+    }
+
+    /// APPEND a message that was just sent to `folder` with \Seen set and
+    /// return its UID. `raw` is stored unchanged, so the copy is byte for byte
+    /// what the SMTP server received.
+    pub async fn save_sent(&self, folder: &str, raw: &[u8]) -> Result<u32> {
+        let parsed = MessageParser::default()
+            .parse(raw)
+            .context("Failed to parse sent message")?;
+        let message_id = bracket_message_id(
+            parsed
+                .message_id()
+                .context("Sent message has no Message-ID")?,
+        )?;
+        let sent_at = parsed
+            .date()
+            .and_then(|date| DateTime::from_timestamp(date.to_timestamp(), 0))
+            .unwrap_or_else(Utc::now);
+
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+        let target = self.wire_name(folder);
+
+        append_message(session, &target, "(\\Seen)", sent_at, raw, &message_id)
+            .await
+            .context(format!("Failed to save sent message to {}", folder))
+    }
+
+    /// Resolve the account's special folders from the SPECIAL-USE attributes of LIST
+    pub async fn resolve_special_folders(&self) -> Result<SpecialFolderMap> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let names: Vec<_> = session
+            .list(Some(""), Some("*"))
+            .await
+            .context("Failed to list folders")?
+            .collect::<Vec<_>>()
+            .await;
+
+        let names: Vec<_> = names
+            .iter()
+            .filter_map(|n| n.as_ref().ok())
+            .map(|n| (self.decode_name(n.name()), n.attributes()))
+            .collect();
+        Ok(SpecialFolderMap::from_list(
+            names.iter().map(|(name, attributes)| (name.as_str(), *attributes)),
+        ))
+    }
+
+    /// Fetch the unparsed header block and body of a message for a "Show original" view.
+    /// Uses BODY.PEEK so the message is not marked as read, and only downloads the first
+    /// `max_body_bytes` of the body.
+    pub async fn get_original(
+        &self,
+        folder: &str,
+        uid: u32,
+        max_body_bytes: u32,
+    ) -> Result<OriginalMessage> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let query = format!(
+            "(RFC822.SIZE BODY.PEEK[HEADER] BODY.PEEK[TEXT]<0.{}>)",
+            max_body_bytes
+        );
+        let fetches: Vec<_> = session
+            .uid_fetch(uid.to_string(), query)
+            .await
+            .context("Failed to fetch message source")?
+            .collect::<Vec<_>>()
+            .await;
+
+        let fetch = fetches
+            .into_iter()
+            .next()
+            .context("Message not found")?
+            .context("Failed to fetch message source")?;
+
+        let header_bytes = fetch.header().unwrap_or_default();
+        let body_bytes = fetch.text().unwrap_or_default();
+        let total_size = fetch
+            .size
+            .unwrap_or((header_bytes.len() + body_bytes.len()) as u32);
+
+        let headers = split_raw_headers(header_bytes);
+        let authentication_results = extract_authentication_results(&headers);
+
+        Ok(OriginalMessage {
+            email_id: format!("{}:{}:{}", self.account_id, folder, uid),
+            raw_headers: String::from_utf8_lossy(header_bytes).to_string(),
+            headers,
+            raw_body: String::from_utf8_lossy(body_bytes).to_string(),
+            body_truncated: ((header_bytes.len() + body_bytes.len()) as u32) < total_size,
+            total_size,
+            authentication_results,
+        })
+    }
+
+    /// Header-only copies of `uids` for the cache: what the headers and
+    /// BODYSTRUCTURE say, and a snippet from the first `SNIPPET_FETCH_BYTES`
+    /// of the text part. Bodies are left for `get_message`; nothing is marked read.
+    pub async fn fetch_headers(&self, folder: &str, uids: &[u32]) -> Result<Vec<Email>> {
+        self.rate_limiter
+            .run(|| self.fetch_header_copies(folder, uids))
+            .await
+    }
+
+    async fn fetch_header_copies(&self, folder: &str, uids: &[u32]) -> Result<Vec<Email>> {
+        if uids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let fetches: Vec<_> = session
+            .uid_fetch(
+                compact_uid_set(uids),
+                "(UID FLAGS RFC822.SIZE BODYSTRUCTURE BODY.PEEK[HEADER])",
+            )
+            .await
+            .context("Failed to fetch headers")?
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut emails = HashMap::new();
+        let mut preview_parts: HashMap<u32, PreviewPart> = HashMap::new();
+        for fetch in fetches.iter().filter_map(|fetch| fetch.as_ref().ok()) {
+            let Some(uid) = fetch.uid else {
+                continue;
+            };
+            let flags: Vec<Flag<'_>> = fetch.flags().collect();
+            let header = fetch.header().unwrap_or_default();
+            let mut email = match self.parse_raw_email(uid, folder, header, &flags) {
+                Ok(email) => email,
+                Err(e) => {
+                    eprintln!("Failed to parse headers of uid={}: {}", uid, e);
+                    continue;
+                }
+            };
+            email.body_html = None;
+            email.body_plain = None;
+            email.snippet = String::new();
+            email.size = fetch.size.unwrap_or(0);
+            email.sync_state = SyncState::CachedHeadersOnly;
+            if let Some(structure) = fetch.bodystructure() {
+                email.attachments = collect_attachment_parts(structure)
+                    .iter()
+                    .map(AttachmentPart::meta)
+                    .collect();
+                email.has_attachments = !email.attachments.is_empty();
+                if let Some(part) = find_preview_part(structure) {
+                    preview_parts.insert(uid, part);
+                }
+            }
+            emails.insert(uid, email);
+        }
+
+        for (uid, text) in Self::fetch_preview_texts(session, &preview_parts).await? {
+            if let Some(email) = emails.get_mut(&uid) {
+                email.snippet = make_snippet(&text, SNIPPET_LENGTH);
+            }
+        }
+
+        Ok(uids.iter().filter_map(|uid| emails.remove(uid)).collect())
+    }
+
+    /// Previews of `uids` of at most `length` characters, read from the first
+    /// `SNIPPET_FETCH_BYTES` of each message's plain text part, or its HTML
+    /// part reduced to text. Messages without a text part are left out.
+    /// Nothing is marked read.
+    pub async fn fetch_snippets(
+        &self,
+        folder: &str,
+        uids: &[u32],
+        length: usize,
+    ) -> Result<HashMap<u32, String>> {
+        self.rate_limiter
+            .run(|| self.fetch_snippet_texts(folder, uids, length))
+            .await
+    }
+
+    async fn fetch_snippet_texts(
+        &self,
+        folder: &str,
+        uids: &[u32],
+        length: usize,
+    ) -> Result<HashMap<u32, String>> {
+        if uids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let fetches: Vec<_> = session
+            .uid_fetch(compact_uid_set(uids), "(UID BODYSTRUCTURE)")
+            .await
+            .context("Failed to fetch message structure")?
+            .collect::<Vec<_>>()
+            .await;
+        let preview_parts: HashMap<u32, PreviewPart> = fetches
+            .iter()
+            .filter_map(|fetch| fetch.as_ref().ok())
+            .filter_map(|fetch| Some((fetch.uid?, find_preview_part(fetch.bodystructure()?)?)))
+            .collect();
+
+        let texts = Self::fetch_preview_texts(session, &preview_parts).await?;
+        Ok(texts
+            .into_iter()
+            .map(|(uid, text)| (uid, make_snippet(&text, length)))
+            .collect())
+    }
+
+    /// Partially fetch each message's preview part in the selected folder,
+    /// without marking it read, and decode what arrived
+    async fn fetch_preview_texts(
+        session: &mut ImapSession,
+        preview_parts: &HashMap<u32, PreviewPart>,
+    ) -> Result<HashMap<u32, String>> {
+        // One partial fetch per section; most messages share "1" or "1.1"
+        let mut by_section: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        for (uid, part) in preview_parts {
+            by_section
+                .entry(part.section_spec())
+                .or_default()
+                .push(*uid);
+        }
+
+        let mut texts = HashMap::new();
+        for (section, section_uids) in by_section {
+            let query = format!("(UID BODY.PEEK[{}]<0.{}>)", section, SNIPPET_FETCH_BYTES);
+            let fetches: Vec<_> = session
+                .uid_fetch(compact_uid_set(&section_uids), query)
+                .await
+                .context("Failed to fetch snippets")?
+                .collect::<Vec<_>>()
+                .await;
+            for fetch in fetches.iter().filter_map(|fetch| fetch.as_ref().ok()) {
+                let Some((uid, part)) = fetch
+                    .uid
+                    .and_then(|uid| Some((uid, preview_parts.get(&uid)?)))
+                else {
+                    continue;
+                };
+                if let Some(data) = fetch.section(&SectionPath::Part(part.section.clone(), None)) {
+                    texts.insert(uid, preview_text(part, data));
+                }
+            }
+        }
+        Ok(texts)
+    }
+
+    /// Download and parse a message without marking it read, together with
+    /// its Authentication-Results verdicts
+    pub async fn peek_message(
+        &self,
+        folder: &str,
+        uid: u32,
+    ) -> Result<(Email, Vec<AuthenticationResults>)> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let fetches: Vec<_> = session
+            .uid_fetch(uid.to_string(), "(FLAGS BODY.PEEK[])")
+            .await
+            .context("Failed to fetch message")?
+            .collect::<Vec<_>>()
+            .await;
+
+        let fetch = fetches
+            .into_iter()
+            .next()
+            .context("Message not found")?
+            .context("Failed to fetch message")?;
+
+        let raw = fetch.body().context("No message body")?;
+        let flags: Vec<Flag<'_>> = fetch.flags().collect();
+
+        let email = self.parse_raw_email(uid, folder, raw, &flags)?;
+        // The header parser stops at the blank line ending the header block
+        let authentication_results = extract_authentication_results(&split_raw_headers(raw));
+        Ok((email, authentication_results))
+    }
+
+    /// The full RFC822 source of a message. Fetched with BODY.PEEK[] from a
+    /// read-only EXAMINE, so viewing the source never sets \Seen.
+    pub async fn get_raw_message(&self, folder: &str, uid: u32) -> Result<Vec<u8>> {
+        let fetch = self.fetch_source(folder, uid, "BODY.PEEK[]").await?;
+        Ok(fetch.body().context("No message body")?.to_vec())
+    }
+
+    /// Size of the message source in bytes (RFC822.SIZE)
+    pub async fn get_raw_size(&self, folder: &str, uid: u32) -> Result<u32> {
+        let fetch = self.fetch_source(folder, uid, "RFC822.SIZE").await?;
+        fetch.size.context("Server didn't report the message size")
+    }
+
+    /// `length` bytes of the message source starting at `offset`, for
+    /// copying a large message without holding all of it in memory. Returns
+    /// fewer bytes (or none) at the end of the message.
+    pub async fn fetch_raw_range(
+        &self,
+        folder: &str,
+        uid: u32,
+        offset: u32,
+        length: u32,
+    ) -> Result<Vec<u8>> {
+        let fetch = self
+            .fetch_source(folder, uid, &format!("BODY.PEEK[]<{}.{}>", offset, length))
+            .await?;
+        Ok(fetch.body().map(|data| data.to_vec()).unwrap_or_default())
+    }
+
+    /// FETCH `items` for one message of a folder opened read-only
+    async fn fetch_source(&self, folder: &str, uid: u32, items: &str) -> Result<Fetch> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let fetches: Vec<_> = session
+            .uid_fetch(uid.to_string(), items)
+            .await
+            .context("Failed to fetch message source")?
+            .collect::<Vec<_>>()
+            .await;
+
+        fetches
+            .into_iter()
+            .next()
+            .context("Message not found")?
+            .context("Failed to fetch message source")
+    }
+
+    /// Locate the attachment parts of a message from its BODYSTRUCTURE
+    pub async fn get_attachment_parts(&self, folder: &str, uid: u32) -> Result<Vec<AttachmentPart>> {
+        Ok(self
+            .get_mime_parts(folder, uid)
+            .await?
+            .into_iter()
+            .filter(|part| !part.inline)
+            .collect())
+    }
+
+    /// Attachment and inline parts from the message's BODYSTRUCTURE
+    async fn get_mime_parts(&self, folder: &str, uid: u32) -> Result<Vec<AttachmentPart>> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let fetches: Vec<_> = session
+            .uid_fetch(uid.to_string(), "BODYSTRUCTURE")
+            .await
+            .context("Failed to fetch body structure")?
+            .collect::<Vec<_>>()
+            .await;
+
+        let fetch = fetches
+            .into_iter()
+            .next()
+            .context("Message not found")?
+            .context("Failed to fetch body structure")?;
+        let structure = fetch.bodystructure().context("No body structure")?;
+
+        Ok(collect_mime_parts(structure))
+    }
+
+    /// Fetch one attachment by IMAP part number ("2", "1.3") and undo its
+    /// transfer encoding. Doesn't mark the message read.
+    pub async fn get_attachment(
+        &self,
+        folder: &str,
+        uid: u32,
+        part_id: &str,
+    ) -> Result<AttachmentContent> {
+        let part = self
+            .get_attachment_parts(folder, uid)
+            .await?
+            .into_iter()
+            .find(|part| part.section_spec() == part_id)
+            .with_context(|| format!("No attachment at part {}", part_id))?;
+        self.fetch_part_content(folder, uid, part).await
+    }
+
+    /// Fetch an inline part by the Content-ID its `cid:` URL names (angle
+    /// brackets optional). Doesn't mark the message read.
+    pub async fn get_inline_part(
+        &self,
+        folder: &str,
+        uid: u32,
+        content_id: &str,
+    ) -> Result<AttachmentContent> {
+        let content_id = strip_angle_brackets(content_id);
+        let part = self
+            .get_mime_parts(folder, uid)
+            .await?
+            .into_iter()
+            .find(|part| part.inline && part.content_id.as_deref() == Some(content_id.as_str()))
+            .with_context(|| format!("No inline part with Content-ID {}", content_id))?;
+        self.fetch_part_content(folder, uid, part).await
+    }
+
+    async fn fetch_part_content(
+        &self,
+        folder: &str,
+        uid: u32,
+        part: AttachmentPart,
+    ) -> Result<AttachmentContent> {
+        let part_id = part.section_spec();
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let fetches: Vec<_> = session
+            .uid_fetch(uid.to_string(), format!("BODY.PEEK[{}]", part_id))
+            .await
+            .context("Failed to fetch attachment")?
+            .collect::<Vec<_>>()
+            .await;
+
+        let fetch = fetches
+            .into_iter()
+            .next()
+            .context("Message not found")?
+            .context("Failed to fetch attachment")?;
+        let encoded = fetch
+            .section(&SectionPath::Part(part.section.clone(), None))
+            .context("Attachment part missing from response")?;
+
+        Ok(AttachmentContent {
+            part_id,
+            data: decode_transfer_encoding(&part.encoding, encoded)?,
+            filename: part.filename,
+            content_type: part.content_type,
+        })
+    }
+
+    /// Fetch `length` bytes of an attachment's encoded content starting at `offset`.
+    /// Returns fewer bytes (or none) once the end of the part is reached.
+    pub async fn fetch_part_range(
+        &self,
+        folder: &str,
+        uid: u32,
+        part: &AttachmentPart,
+        offset: u32,
+        length: u32,
+    ) -> Result<Vec<u8>> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .examine(self.wire_name(folder))
+            .await
+            .context(format!("Failed to examine folder: {}", folder))?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        let fetches: Vec<_> = session
+            .uid_fetch(
+                uid.to_string(),
+                format!("BODY.PEEK[{}]<{}.{}>", part.section_spec(), offset, length),
+            )
+            .await
+            .context("Failed to fetch attachment data")?
+            .collect::<Vec<_>>()
+            .await;
+
+        let fetch = fetches
+            .into_iter()
+            .next()
+            .context("Message not found")?
+            .context("Failed to fetch attachment data")?;
+
+        Ok(fetch
+            .section(&SectionPath::Part(part.section.clone(), None))
+            .map(|data| data.to_vec())
+            .unwrap_or_default())
+    }
+
+    /// List a page of a folder in the given order. Uses server-side SORT when
+    /// advertised; otherwise the newest `max_results` messages are reordered locally.
+    /// Only envelopes are fetched, never bodies.
+    pub async fn list_messages_sorted(
+        &self,
+        folder: &str,
+        max_results: u32,
+        offset: u32,
+        sort: MessageSort,
+    ) -> Result<Vec<EmailListItem>> {
+        if sort == MessageSort::Date {
+            return self.list_messages(folder, max_results, offset).await;
+        }
+
+        // Capabilities are known once connected
+        let mut guard = self.get_session().await?;
+        if !self.sort_supported.load(Ordering::Relaxed) {
+            drop(guard);
+            let mut items = self.list_messages(folder, max_results, offset).await?;
+            sort_items(&mut items, sort);
+            return Ok(items);
+        }
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .select(self.wire_name(folder))
+            .await
+            .context("Failed to select folder")?;
+        self.note_uid_validity(folder, mailbox.uid_validity);
+
+        let uids = Self::uid_sort(session, sort.sort_criteria()).await?;
+        let page: Vec<u32> = uids
+            .into_iter()
+            .skip(offset as usize)
+            .take(max_results as usize)
+            .collect();
+        self.fetch_list_items(session, folder, &page).await
+    }
+
+    /// List up to `max_results` messages with a UID strictly below `before_uid`,
+    /// newest (highest UID) first. An empty result is the end of the folder.
+    pub async fn list_messages_before(
+        &self,
+        folder: &str,
+        max_results: u32,
+        before_uid: u32,
+    ) -> Result<Vec<EmailListItem>> {
+        // "1:0" would mean 0:1 and return UID 1 again
+        if before_uid <= 1 || max_results == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .select(self.wire_name(folder))
+            .await
+            .context("Failed to select folder")?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
+
+        // An explicit upper bound; "n:*" would wrap around to the newest message
+        let uids = session
+            .uid_search(format!("UID 1:{}", before_uid - 1))
+            .await
+            .context("Failed to search messages")?;
+
+        let mut uids: Vec<u32> = uids.into_iter().filter(|uid| *uid < before_uid).collect();
+        uids.sort_unstable_by(|a, b| b.cmp(a));
+        uids.truncate(max_results as usize);
+        self.fetch_list_items(session, folder, &uids).await
+    }
+
+    /// Fetch envelopes for `uids` in the selected folder and return the items in that order
+    async fn fetch_list_items(
+        &self,
+        session: &mut ImapSession,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<Vec<EmailListItem>> {
+        if uids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let uid_set = uids
+            .iter()
+            .map(|uid| uid.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let fetches: Vec<_> = session
+            .uid_fetch(
+                uid_set,
+                "(UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (DATE FROM SUBJECT)] RFC822.SIZE)",
+            )
+            .await
+            .context("Failed to fetch messages")?
+            .collect::<Vec<_>>()
+            .await;
+
+        // FETCH answers in mailbox order; put the items back in the requested order
+        let mut by_uid: HashMap<u32, EmailListItem> = fetches
+            .iter()
+            .filter_map(|fetch| fetch.as_ref().ok())
+            .filter_map(|fetch| {
+                fetch
+                    .uid
+                    .map(|uid| (uid, self.parse_fetch_to_list_item(uid, folder, fetch)))
+            })
+            .collect();
+        Ok(uids.iter().filter_map(|uid| by_uid.remove(uid)).collect())
+    }
+
+    /// Run `UID SORT` and return the UIDs in the server's order
+    async fn uid_sort(session: &mut ImapSession, criteria: &str) -> Result<Vec<u32>> {
+        let id = session
+            .run_command(format!("UID SORT ({}) UTF-8 ALL", criteria))
+            .await
+            .context("Failed to send SORT")?;
+
+        let mut uids = Vec::new();
+        while let Some(response) = session.read_response().await {
+            let response = response.context("Failed to read SORT response")?;
+            match response.parsed() {
+                Response::MailboxData(MailboxDatum::Sort(ids)) => uids.extend(ids),
+                Response::Done {
+                    tag,
+                    status,
+                    information,
+                    ..
+                } if *tag == id => {
+                    if *status != Status::Ok {
+                        anyhow::bail!("SORT failed: {}", information.as_deref().unwrap_or(""));
+                    }
+                    return Ok(uids);
+                }
+                _ => {}
+            }
+        }
+        anyhow::bail!("Connection closed during SORT")
+    }
+
+    /// Parse a FETCH response into an EmailListItem
+    fn parse_fetch_to_list_item(&self, uid: u32, folder: &str, fetch: &Fetch) -> EmailListItem {
+        let flags: Vec<Flag<'_>> = fetch.flags().collect();
+        let is_read = flags.iter().any(|f| matches!(f, Flag::Seen));
+        let is_starred = flags.iter().any(|f| matches!(f, Flag::Flagged));
+
+        let (subject, from, from_email, date) = match fetch.envelope() {
+            Some(envelope) => envelope_summary(envelope),
+            None => (
+                "(No Subject)".to_string(),
+                "Unknown".to_string(),
+                String::new(),
+                String::new(),
+            ),
+        };
+
+        let id = format!("{}:{}:{}", self.account_id, folder, uid);
+
+        EmailListItem {
+            id,
+            thread_id: String::new(),
+            subject,
+            from,
+            from_email,
+            date,
+            snippet: String::new(),
+            is_read,
+            is_starred,
+            has_attachments: false,
+            is_first_contact: false,
+            sync_state: SyncState::LiveFetched,
+            size: fetch.size.unwrap_or(0),
+        }
+    }
+
+    fn detect_special_folder(
+        &self,
+        name: &str,
+        attributes: &[NameAttribute<'_>],
+    ) -> Option<SpecialFolder> {
+        // SPECIAL-USE attributes (RFC 6154) win when the server sends them
+        for attribute in attributes {
+            match attribute {
+                NameAttribute::Sent => return Some(SpecialFolder::Sent),
+                NameAttribute::Drafts => return Some(SpecialFolder::Drafts),
+                NameAttribute::Trash => return Some(SpecialFolder::Trash),
+                NameAttribute::Junk => return Some(SpecialFolder::Spam),
+                NameAttribute::Archive | NameAttribute::All => return Some(SpecialFolder::Archive),
+                NameAttribute::Flagged => return Some(SpecialFolder::Starred),
+                _ => {}
+            }
+        }
+
+        // Name-based detection (works across all IMAP servers)
+        let lower = name.to_lowercase();
+        if lower == "inbox" {
+            Some(SpecialFolder::Inbox)
+        } else if lower.contains("sent") {
+            Some(SpecialFolder::Sent)
+        } else if lower.contains("trash") || lower.contains("deleted") {
+            Some(SpecialFolder::Trash)
+        } else if lower.contains("draft") {
+            Some(SpecialFolder::Drafts)
+        } else if lower.contains("spam") || lower.contains("junk") {
+            Some(SpecialFolder::Spam)
+        } else if lower.contains("archive") || lower.contains("all mail") {
+            Some(SpecialFolder::Archive)
+        } else {
+            None
+        }
+    }
+}
+
+/// Full name of folder `name` nested under `parent` with the server's
+/// hierarchy `delimiter`
+pub fn nested_folder_name(
+    parent: Option<&str>,
+    name: &str,
+    delimiter: Option<&str>,
+) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("Folder name is required");
+    }
+    if let Some(delimiter) = delimiter.filter(|d| name.contains(d)) {
+        anyhow::bail!("Folder names can't contain \"{}\"", delimiter);
+    }
+    match (parent.filter(|p| !p.is_empty()), delimiter) {
+        (None, _) => Ok(name.to_string()),
+        (Some(parent), Some(delimiter)) => Ok(format!("{}{}{}", parent, delimiter, name)),
+        (Some(_), None) => anyhow::bail!("This server doesn't support nested folders"),
+    }
+}
+
+/// The list preview of a message: the start of its text on one line, with
+/// runs of whitespace (common in text reduced from HTML) collapsed
+fn make_snippet(text: &str, length: usize) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(length)
+        .collect()
+}
+
+/// Whether two wire mailbox names are the same mailbox; INBOX is case-insensitive
+fn same_mailbox(a: &str, b: &str) -> bool {
+    a == b || (a.eq_ignore_ascii_case("INBOX") && b.eq_ignore_ascii_case("INBOX"))
+}
+
+/// Sorted UID set with consecutive runs collapsed, e.g. `12,15,20:25`
+pub fn compact_uid_set(uids: &[u32]) -> String {
+    let mut sorted = uids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for uid in sorted {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == uid => *end = uid,
+            _ => ranges.push((uid, uid)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}:{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Inverse of `compact_uid_set`; malformed members are skipped
+pub fn parse_uid_set(set: &str) -> Vec<u32> {
+    let mut uids = Vec::new();
+    for member in set.split(',') {
+        let (start, end) = member.split_once(':').unwrap_or((member, member));
+        if let (Ok(start), Ok(end)) = (start.trim().parse::<u32>(), end.trim().parse::<u32>()) {
+            uids.extend(start.min(end)..=start.max(end));
+        }
+    }
+    uids
+}
+
+/// Subject, `Name <address>`, sender address and date of an ENVELOPE
+fn envelope_summary(envelope: &Envelope<'_>) -> (String, String, String, String) {
+    let subject = envelope
+        .subject
+        .as_ref()
+        .and_then(|s| std::str::from_utf8(s).ok())
+        .unwrap_or("(No Subject)")
+        .to_string();
+
+    let (from, from_email) = envelope
+        .from
+        .as_ref()
+        .and_then(|addrs| addrs.first())
+        .map(|addr| {
+            let name = addr
+                .name
+                .as_ref()
+                .and_then(|n| std::str::from_utf8(n).ok())
+                .unwrap_or("");
+            let mailbox = addr
+                .mailbox
+                .as_ref()
+                .and_then(|m| std::str::from_utf8(m).ok())
+                .unwrap_or("");
+            let host = addr
+                .host
+                .as_ref()
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .unwrap_or("");
+            let email = format!("{}@{}", mailbox, host);
+            if name.is_empty() {
+                (email.clone(), email)
+            } else {
+                (format!("{} <{}>", name, email), email)
+            }
+        })
+        .unwrap_or_else(|| ("Unknown".to_string(), String::new()));
+
+    let date = envelope
+        .date
+        .as_ref()
+        .and_then(|d| std::str::from_utf8(d).ok())
+        .unwrap_or("")
+        .to_string();
+
+    (subject, from, from_email, date)
+}
+
+/// Addresses of an ENVELOPE address list as "Name <address>" (or the bare
+/// address); group markers are skipped
+fn envelope_addresses(addresses: &Option<Vec<Address<'_>>>) -> Vec<String> {
+    let text = |value: &Option<std::borrow::Cow<'_, [u8]>>| {
+        value
+            .as_ref()
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+            .unwrap_or_default()
+    };
+    addresses
+        .iter()
+        .flatten()
+        .filter(|addr| addr.host.is_some())
+        .map(|addr| {
+            let address = format!("{}@{}", text(&addr.mailbox), text(&addr.host));
+            match text(&addr.name) {
+                name if name.is_empty() => address,
+                name => format!("{} <{}>", name, address),
+            }
+        })
+        .collect()
+}
+
+/// Addresses of a header as "Name <address>" (or the bare address), with
+/// group members flattened into the list
+fn address_strings(addresses: Option<&mail_parser::Address<'_>>) -> Vec<String> {
+    addresses
+        .map(|addrs| {
+            addrs
+                .iter()
+                .map(|addr| {
+                    if let Some(name) = addr.name() {
+                        format!("{} <{}>", name, addr.address().unwrap_or(""))
+                    } else {
+                        addr.address().unwrap_or("").to_string()
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Resolves once `shutdown` is set. A dropped sender never signals, so the
+/// caller waits out its IDLE instead.
+async fn shutdown_requested(shutdown: &mut watch::Receiver<bool>) {
+    if shutdown.wait_for(|stop| *stop).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Destination UID of a single-message COPYUID response code
+fn copied_uid(code: &ResponseCode<'_>) -> Option<u32> {
+    match code {
+        ResponseCode::CopyUid(_, _, dest) => match dest.as_slice() {
+            [UidSetMember::Uid(uid)] => Some(*uid),
+            [UidSetMember::UidRange(range)] => Some(*range.start()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Compare the UIDs of a folder before and after IDLE. Without UIDNEXT, any
+/// UID not seen before counts as new.
+pub(crate) fn diff_uids(
+    known: &HashSet<u32>,
+    current: &HashSet<u32>,
+    uid_next: Option<u32>,
+) -> FolderChanges {
+    let mut new_uids: Vec<u32> = current
+        .iter()
+        .copied()
+        .filter(|uid| match uid_next {
+            Some(next) => *uid >= next,
+            None => !known.contains(uid),
+        })
+        .collect();
+    let mut expunged_uids: Vec<u32> = known.difference(current).copied().collect();
+    new_uids.sort_unstable();
+    expunged_uids.sort_unstable();
+    FolderChanges {
+        new_uids,
+        expunged_uids,
+    }
+}
+
+/// APPEND `raw` to the `target` mailbox and return the UID it was stored
+/// under. INTERNALDATE is set to `date`, so the list and the Date header agree.
+async fn append_message(
+    session: &mut ImapSession,
+    target: &str,
+    flags: &str,
+    date: DateTime<Utc>,
+    raw: &[u8],
+    message_id: &str,
+) -> Result<u32> {
+    let internal_date = date.format("\"%e-%b-%Y %H:%M:%S %z\"").to_string();
+    session
+        .append(target, Some(flags), Some(&internal_date), raw)
+        .await
+        .context("Failed to append message")?;
+
+    // async-imap drops the APPENDUID code, so find the copy by Message-ID
+    session
+        .select(target)
+        .await
+        .context("Failed to select folder")?;
+    session
+        .uid_search(format!("HEADER Message-ID {}", quote(message_id)))
+        .await
+        .context("Failed to find appended message")?
+        .into_iter()
+        .max()
+        .context("Appended message not found")
+}
+
+/// Build an outgoing message. With an HTML body it is multipart/alternative,
+/// and a missing plain-text part is generated from the HTML. lettre picks a
+/// random boundary per multipart and quoted-printable/base64 encodes any part
+/// with lines too long for 7bit, which soft-wraps them at 76 characters.
+#[allow(clippy::too_many_arguments)]
+pub fn build_message(
+    from: &str,
+    to: &[String],
+    cc: &[String],
+    bcc: &[String],
+    subject: &str,
+    reply: &ReplyHeaders,
+    body_html: &str,
+    body_plain: &str,
+    attachments: &[OutgoingAttachment],
+) -> Result<Message> {
+    let builder = message_builder(from, to, cc, bcc, subject, reply)?;
+    with_body(builder, body_html, body_plain, attachments)
+}
+
+/// An iTIP reply to `organizer`: a short note and the calendar as
+/// alternatives, so calendar clients update the event and others show the note
+pub fn build_invite_reply(
+    from: &str,
+    organizer: &str,
+    subject: &str,
+    reply: &ReplyHeaders,
+    body_plain: &str,
+    ics: &str,
+) -> Result<Message> {
+    let builder = message_builder(from, &[organizer.to_string()], &[], &[], subject, reply)?;
+    let calendar = ContentType::parse("text/calendar; charset=utf-8; method=REPLY")
+        .context("Invalid calendar content type")?;
+    Ok(builder.multipart(
+        MultiPart::alternative()
+            .singlepart(
+                SinglePart::builder()
+                    .header(ContentType::TEXT_PLAIN)
+                    .body(body_plain.to_string()),
+            )
+            .singlepart(SinglePart::builder().header(calendar).body(ics.to_string())),
+    )?)
+}
+
+/// A draft for the Drafts folder: same MIME layout as a sent message, but Bcc
+/// is kept and the Date header is the save time
+#[allow(clippy::too_many_arguments)]
+pub fn build_draft(
+    from: &str,
+    to: &[String],
+    cc: &[String],
+    bcc: &[String],
+    subject: &str,
+    body_html: &str,
+    body_plain: &str,
+    saved_at: SystemTime,
+) -> Result<Message> {
+    let builder = message_builder(from, to, cc, bcc, subject, &ReplyHeaders::default())?
+        .keep_bcc()
+        .date(saved_at)
+        .message_id(None);
+    with_body(builder, body_html, body_plain, &[])
+}
+
+fn message_builder(
+    from: &str,
+    to: &[String],
+    cc: &[String],
+    bcc: &[String],
+    subject: &str,
+    reply: &ReplyHeaders,
+) -> Result<MessageBuilder> {
+    let from_mailbox: Mailbox = from.parse().context("Invalid from address")?;
+
+    let mut builder = Message::builder().from(from_mailbox).subject(subject);
+
+    for addr in to {
+        let mbox: Mailbox = addr.parse().context("Invalid to address")?;
+        builder = builder.to(mbox);
+    }
+    for addr in cc {
+        let mbox: Mailbox = addr.parse().context("Invalid cc address")?;
+        builder = builder.cc(mbox);
+    }
+    for addr in bcc {
+        let mbox: Mailbox = addr.parse().context("Invalid bcc address")?;
+        builder = builder.bcc(mbox);
+    }
+    if let Some(in_reply_to) = &reply.in_reply_to {
+        builder = builder.in_reply_to(in_reply_to.clone());
+    }
+    if let Some(references) = reply.references_header() {
+        builder = builder.references(references);
+    }
+    Ok(builder)
+}
+
+/// The body parts, wrapped in multipart/mixed with the attachments if any
+fn with_body(
+    builder: MessageBuilder,
+    body_html: &str,
+    body_plain: &str,
+    attachments: &[OutgoingAttachment],
+) -> Result<Message> {
+    let plain_part = |text: String| {
+        SinglePart::builder()
+            .header(ContentType::TEXT_PLAIN)
+            .body(text)
+    };
+    let alternative = (!body_html.is_empty()).then(|| {
+        let body_plain = if body_plain.is_empty() {
+            strip_html(body_html)
+        } else {
+            body_plain.to_string()
+        };
+        MultiPart::alternative()
+            .singlepart(plain_part(body_plain))
+            .singlepart(
+                SinglePart::builder()
+                    .header(ContentType::TEXT_HTML)
+                    .body(body_html.to_string()),
+            )
+    });
+
+    if attachments.is_empty() {
+        let email = match alternative {
+            Some(alternative) => builder.multipart(alternative)?,
+            None => builder.singlepart(plain_part(body_plain.to_string()))?,
+        };
+        return Ok(email);
+    }
+
+    let mut mixed = match alternative {
+        Some(alternative) => MultiPart::mixed().multipart(alternative),
+        None => MultiPart::mixed().singlepart(plain_part(body_plain.to_string())),
+    };
+    for attachment in attachments {
+        mixed = mixed.singlepart(attachment_part(attachment)?);
+    }
+    Ok(builder.multipart(mixed)?)
+}
+
+/// An attachment as a base64 part with `Content-Disposition: attachment`
+fn attachment_part(attachment: &OutgoingAttachment) -> Result<SinglePart> {
+    let content_type = ContentType::parse(&attachment.content_type)
+        .or_else(|_| ContentType::parse("application/octet-stream"))
+        .context("Invalid attachment content type")?;
+    let body = Body::new_with_encoding(
+        attachment.data.clone(),
+        header::ContentTransferEncoding::Base64,
+    )
+    .map_err(|_| anyhow::anyhow!("Failed to encode attachment {}", attachment.filename))?;
+    Ok(Attachment::new(attachment.filename.clone()).body(body, content_type))
+}
+
+/// XOAUTH2/OAUTHBEARER authenticator for async-imap. Any challenge after the
+/// initial response is the server's error report, answered with `error_ack`.
+struct OAuthAuthenticator {
+    response: String,
+    error_ack: &'static str,
+    sent: bool,
+}
+
+impl async_imap::Authenticator for OAuthAuthenticator {
+    type Response = String;
+
+    fn process(&mut self, _data: &[u8]) -> Self::Response {
+        if std::mem::replace(&mut self.sent, true) {
+            self.error_ack.to_string()
+        } else {
+            self.response.clone()
+        }
+    }
+}
+
+impl ImapClient {
+    /// `list_messages` without the rate limiter
+    async fn fetch_list(
+        &self,
+        folder: &str,
+        max_results: u32,
+        offset: u32,
+    ) -> Result<Vec<EmailListItem>> {
+        let mut guard = self.get_session().await?;
+        let session = guard.as_mut().context("No IMAP session")?;
+
+        let mailbox = session
+            .select(self.wire_name(folder))
+            .await
+            .context("Failed to select folder")?;
+        self.note_uid_validity(folder, mailbox.uid_validity);
+
+        let total = mailbox.exists;
+        if total == 0 {
+            return Ok(vec![]);
+        }
+
+        let end = total.saturating_sub(offset);
+        if end == 0 {
+            return Ok(vec![]);
+        }
+        let start = end.saturating_sub(max_results).max(1);
+
+        let range = format!("{}:{}", start, end);
         let fetches: Vec<_> = session
             .fetch(
                 range,
@@ -529,18 +3061,20 @@ impl EmailProvider for ImapClient {
         Ok(items)
     }
 
-    async fn get_message(&self, folder: &str, uid: u32) -> Result<Email> {
+    /// `get_message` without the rate limiter
+    async fn fetch_message(&self, folder: &str, uid: u32) -> Result<Email> {
         let mut guard = self.get_session().await?;
         let session = guard.as_mut().context("No IMAP session")?;
 
-        session
-            .select(folder)
+        let mailbox = session
+            .select(self.wire_name(folder))
             .await
             .context("Failed to select folder")?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
 
         let uid_str = uid.to_string();
         let fetches: Vec<_> = session
-            .uid_fetch(&uid_str, "(FLAGS BODY[])")
+            .uid_fetch(&uid_str, "(FLAGS BODYSTRUCTURE BODY[])")
             .await
             .context("Failed to fetch message")?
             .collect::<Vec<_>>()
@@ -555,7 +3089,39 @@ impl EmailProvider for ImapClient {
         let raw = fetch.body().context("No message body")?;
         let flags: Vec<Flag<'_>> = fetch.flags().collect();
 
-        self.parse_raw_email(uid, folder, raw, &flags)
+        let mut email = self.parse_raw_email(uid, folder, raw, &flags)?;
+        if let Some(structure) = fetch.bodystructure() {
+            email.attachments = collect_attachment_parts(structure)
+                .iter()
+                .map(AttachmentPart::meta)
+                .collect();
+            email.inline_parts = collect_inline_parts(structure)
+                .iter()
+                .map(AttachmentPart::inline_meta)
+                .collect();
+            embed_inline_parts(raw, &mut email.inline_parts);
+        }
+        Ok(email)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailProvider for ImapClient {
+    async fn list_messages(
+        &self,
+        folder: &str,
+        max_results: u32,
+        offset: u32,
+    ) -> Result<Vec<EmailListItem>> {
+        self.rate_limiter
+            .run(|| self.fetch_list(folder, max_results, offset))
+            .await
+    }
+
+    async fn get_message(&self, folder: &str, uid: u32) -> Result<Email> {
+        self.rate_limiter
+            .run(|| self.fetch_message(folder, uid))
+            .await
     }
 
     async fn send_email(
@@ -567,116 +3133,107 @@ impl EmailProvider for ImapClient {
         subject: &str,
         body_html: &str,
         body_plain: &str,
-    ) -> Result<()> {
-        let from_mailbox: Mailbox = from.parse().context("Invalid from address")?;
-
-        let mut builder = Message::builder().from(from_mailbox).subject(subject);
-
-        for addr in &to {
-            let mbox: Mailbox = addr.parse().context("Invalid to address")?;
-            builder = builder.to(mbox);
-        }
-        for addr in &cc {
-            let mbox: Mailbox = addr.parse().context("Invalid cc address")?;
-            builder = builder.cc(mbox);
-        }
-        for addr in &bcc {
-            let mbox: Mailbox = addr.parse().context("Invalid bcc address")?;
-            builder = builder.bcc(mbox);
-        }
-
-        let email = if !body_html.is_empty() && !body_plain.is_empty() {
-            builder.multipart(
-                MultiPart::alternative()
-                    .singlepart(
-                        SinglePart::builder()
-                            .header(ContentType::TEXT_PLAIN)
-                            .body(body_plain.to_string()),
-                    )
-                    .singlepart(
-                        SinglePart::builder()
-                            .header(ContentType::TEXT_HTML)
-                            .body(body_html.to_string()),
-                    ),
-            )?
-        } else if !body_html.is_empty() {
-            builder.singlepart(
-                SinglePart::builder()
-                    .header(ContentType::TEXT_HTML)
-                    .body(body_html.to_string()),
-            )?
-        } else {
-            builder.singlepart(
-                SinglePart::builder()
-                    .header(ContentType::TEXT_PLAIN)
-                    .body(body_plain.to_string()),
-            )?
-        };
-
-        let transport = self.build_smtp_transport().await?;
-        transport
-            .send(email)
-            .await
-            .context("Failed to send email via SMTP")?;
+        reply: &ReplyHeaders,
+        attachments: &[OutgoingAttachment],
+    ) -> Result<Vec<u8>> {
+        let email = build_message(
+            from,
+            &to,
+            &cc,
+            &bcc,
+            subject,
+            reply,
+            body_html,
+            body_plain,
+            attachments,
+        )?;
+        self.send_with_retry(&email).await
+    }
 
-        Ok(())
+    async fn set_flags(&self, folder: &str, uid: u32, flags: &[ImapFlag], add: bool) -> Result<()> {
+        self.set_flags_bulk(folder, &[uid], flags, add).await
     }
 
-    async fn set_flags(
-        &self,
-        folder: &str,
-        uid: u32,
-        flags: &[ImapFlag],
-        add: bool,
-    ) -> Result<()> {
+    async fn get_flags(&self, folder: &str, uid: u32) -> Result<Vec<ImapFlag>> {
         let mut guard = self.get_session().await?;
         let session = guard.as_mut().context("No IMAP session")?;
 
-        session
-            .select(folder)
+        let mailbox = session
+            .select(self.wire_name(folder))
             .await
             .context("Failed to select folder")?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
 
-        let flag_str = flags
-            .iter()
-            .map(|f| f.to_imap_str())
-            .collect::<Vec<_>>()
-            .join(" ");
-
-        let uid_str = uid.to_string();
-        if add {
-            session
-                .uid_store(&uid_str, format!("+FLAGS ({})", flag_str))
-                .await
-                .context("Failed to add flags")?;
+        // Fetch::flags() can't see X-GM-LABELS, so read the responses directly
+        let items = if self.uses_gmail_labels() {
+            "(UID FLAGS X-GM-LABELS)"
         } else {
-            session
-                .uid_store(&uid_str, format!("-FLAGS ({})", flag_str))
-                .await
-                .context("Failed to remove flags")?;
-        }
+            "(UID FLAGS)"
+        };
+        let id = session
+            .run_command(format!("UID FETCH {} {}", uid, items))
+            .await
+            .context("Failed to fetch flags")?;
 
-        Ok(())
+        let mut flags = None;
+        while let Some(response) = session.read_response().await {
+            let response = response.context("Failed to read flags")?;
+            match response.parsed() {
+                Response::Fetch(_, attributes)
+                    if attributes
+                        .iter()
+                        .any(|a| matches!(a, AttributeValue::Uid(u) if *u == uid)) =>
+                {
+                    let found = flags.get_or_insert_with(Vec::new);
+                    for attribute in attributes {
+                        match attribute {
+                            AttributeValue::Flags(values) => found.extend(
+                                values.iter().filter_map(|f| ImapFlag::from_imap_str(f)),
+                            ),
+                            AttributeValue::GmailLabels(labels) => found.extend(
+                                labels.iter().map(|l| ImapFlag::Keyword(l.to_string())),
+                            ),
+                            _ => {}
+                        }
+                    }
+                }
+                Response::Done {
+                    tag,
+                    status,
+                    information,
+                    ..
+                } if *tag == id => {
+                    if *status != Status::Ok {
+                        anyhow::bail!("FETCH failed: {}", information.as_deref().unwrap_or(""));
+                    }
+                    return flags.context("Message not found");
+                }
+                _ => {}
+            }
+        }
+        anyhow::bail!("Connection closed during FETCH")
     }
 
     async fn move_message(&self, from_folder: &str, uid: u32, to_folder: &str) -> Result<()> {
         let mut guard = self.get_session().await?;
         let session = guard.as_mut().context("No IMAP session")?;
 
-        session
-            .select(from_folder)
+        let mailbox = session
+            .select(self.wire_name(from_folder))
             .await
             .context("Failed to select source folder")?;
+        self.check_uid_validity(from_folder, mailbox.uid_validity)?;
 
         let uid_str = uid.to_string();
+        let target = self.wire_name(to_folder);
 
         // Try MOVE extension first (RFC 6851)
-        match session.uid_mv(&uid_str, to_folder).await {
+        match session.uid_mv(&uid_str, &target).await {
             Ok(_) => Ok(()),
             Err(_) => {
                 // Fallback: COPY + STORE \Deleted + EXPUNGE
                 session
-                    .uid_copy(&uid_str, to_folder)
+                    .uid_copy(&uid_str, &target)
                     .await
                     .context("Failed to copy message")?;
                 session
@@ -696,10 +3253,11 @@ impl EmailProvider for ImapClient {
         let mut guard = self.get_session().await?;
         let session = guard.as_mut().context("No IMAP session")?;
 
-        session
-            .select(folder)
+        let mailbox = session
+            .select(self.wire_name(folder))
             .await
             .context("Failed to select folder")?;
+        self.check_uid_validity(folder, mailbox.uid_validity)?;
 
         let uid_str = uid.to_string();
         session
@@ -729,23 +3287,851 @@ impl EmailProvider for ImapClient {
                 Ok(n) => n,
                 Err(_) => continue,
             };
-            let full_name = name.name().to_string();
-            let display_name = full_name
-                .rsplit('/')
-                .next()
-                .unwrap_or(&full_name)
-                .to_string();
+            let full_name = self.decode_name(name.name());
+            let delimiter = name.delimiter().map(|s| s.to_string());
+            let display_name = match delimiter.as_deref() {
+                Some(delimiter) if !delimiter.is_empty() => full_name
+                    .rsplit(delimiter)
+                    .next()
+                    .unwrap_or(&full_name)
+                    .to_string(),
+                _ => full_name.clone(),
+            };
 
+            // Falls back to name heuristics when the server has no SPECIAL-USE
             let special = self.detect_special_folder(&full_name, name.attributes());
+            let special_use = name
+                .attributes()
+                .iter()
+                .filter_map(special_use_name)
+                .map(str::to_string)
+                .collect();
+            let selectable = !name
+                .attributes()
+                .iter()
+                .any(|attribute| matches!(attribute, NameAttribute::NoSelect));
 
             folders.push(Folder {
                 name: full_name,
+                raw_name: name.name().to_string(),
                 display_name,
                 special,
-                delimiter: name.delimiter().map(|s| s.to_string()),
+                delimiter,
+                special_use,
+                selectable,
             });
         }
 
         Ok(folders)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::mock_imap::MockImap;
+    use crate::email::threading::group_threads;
+
+    #[tokio::test]
+    async fn test_non_ascii_folder_uses_modified_utf7_without_utf8_accept() {
+        let mock = MockImap::new().on("LIST", "* LIST () \"/\" \"Entw&APw-rfe\"\r\n");
+        let client = mock.client("acct");
+
+        let folders = client.list_folders().await.unwrap();
+        assert_eq!(folders[0].name, "Entwürfe");
+
+        client.list_messages(&folders[0].name, 10, 0).await.unwrap();
+        assert!(!mock.commands().iter().any(|c| c.starts_with("ENABLE")));
+        assert!(mock.commands().contains(&"SELECT \"Entw&APw-rfe\"".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_non_ascii_folder_is_raw_utf8_with_utf8_accept() {
+        let mock = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1 ENABLE UTF8=ACCEPT\r\n")
+            .on("ENABLE", "* ENABLED UTF8=ACCEPT\r\n")
+            // imap-proto only parses 8-bit mailbox names sent as literals
+            .on("LIST", "* LIST () \"/\" {9}\r\nEntwürfe\r\n");
+        let client = mock.client("acct");
+
+        let folders = client.list_folders().await.unwrap();
+        assert_eq!(folders[0].name, "Entwürfe");
+
+        client.list_messages(&folders[0].name, 10, 0).await.unwrap();
+        assert!(mock.commands().contains(&"ENABLE UTF8=ACCEPT".to_string()));
+        assert!(mock.commands().contains(&"SELECT \"Entwürfe\"".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_list_folders_reads_special_use_or_falls_back_to_names() {
+        let mock = MockImap::new().on(
+            "LIST",
+            "* LIST (\\HasNoChildren) \".\" INBOX\r\n\
+             * LIST (\\Noselect \\HasChildren) \".\" \"[Gmail]\"\r\n\
+             * LIST (\\HasNoChildren \\Sent) \".\" \"[Gmail].Gesendet\"\r\n\
+             * LIST (\\HasNoChildren) \".\" \"Junk E-mail\"\r\n\
+             * LIST (\\HasNoChildren) \".\" \"Projects.Entw&APw-rfe\"\r\n",
+        );
+        let client = mock.client("acct");
+
+        let folders = client.list_folders().await.unwrap();
+        assert_eq!(folders.len(), 5);
+        assert!(!folders[1].selectable);
+
+        let sent = &folders[2];
+        assert_eq!(sent.special, Some(SpecialFolder::Sent));
+        assert_eq!(sent.special_use, vec!["\\Sent".to_string()]);
+        assert_eq!(sent.display_name, "Gesendet");
+
+        // No SPECIAL-USE attribute, so the name decides
+        assert_eq!(folders[3].special, Some(SpecialFolder::Spam));
+        assert!(folders[3].special_use.is_empty());
+
+        let drafts = &folders[4];
+        assert_eq!(drafts.raw_name, "Projects.Entw&APw-rfe");
+        assert_eq!(drafts.name, "Projects.Entwürfe");
+        assert_eq!(drafts.display_name, "Entwürfe");
+        assert_eq!(drafts.delimiter.as_deref(), Some("."));
+    }
+
+    #[tokio::test]
+    async fn test_sorted_listing_uses_server_sort_order() {
+        let envelope = |seq: u32, uid: u32, from: &str| {
+            format!(
+                "* {} FETCH (UID {} FLAGS () ENVELOPE (\"Mon, 2 Mar 2026 10:00:00 +0000\" \"Hi\" \
+                 ((NIL NIL \"{}\" \"example.com\")) NIL NIL NIL NIL NIL NIL NIL) RFC822.SIZE 120)\r\n",
+                seq, uid, from
+            )
+        };
+        let mock = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1 SORT\r\n")
+            .on("SELECT", "* 3 EXISTS\r\n")
+            .on("UID SORT", "* SORT 12 10\r\n")
+            // Mailbox order, not SORT order
+            .on(
+                "UID FETCH 12,10",
+                &(envelope(1, 10, "zed") + &envelope(3, 12, "amy")),
+            );
+        let client = mock.client("acct");
+
+        let items = client
+            .list_messages_sorted("INBOX", 2, 0, MessageSort::Sender)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["acct:INBOX:12", "acct:INBOX:10"]);
+        assert!(mock
+            .commands()
+            .contains(&"UID SORT (FROM) UTF-8 ALL".to_string()));
+        // Only envelopes are fetched when the order changes
+        assert!(!mock.commands().iter().any(|c| c.contains("BODY[]")));
+    }
+
+    #[tokio::test]
+    async fn test_list_before_uid_pages_down_without_wrapping() {
+        let envelope = |seq: u32, uid: u32| {
+            format!(
+                "* {} FETCH (UID {} FLAGS () ENVELOPE (\"Mon, 2 Mar 2026 10:00:00 +0000\" \"Hi\" \
+                 ((NIL NIL \"ana\" \"example.com\")) NIL NIL NIL NIL NIL NIL NIL) RFC822.SIZE 120)\r\n",
+                seq, uid
+            )
+        };
+        let mock = MockImap::new()
+            .on("SELECT", "* 3 EXISTS\r\n")
+            .on("UID SEARCH", "* SEARCH 3 5 7\r\n")
+            .on("UID FETCH 7,5", &(envelope(2, 5) + &envelope(3, 7)));
+        let client = mock.client("acct");
+
+        let items = client.list_messages_before("INBOX", 2, 9).await.unwrap();
+        let ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["acct:INBOX:7", "acct:INBOX:5"]);
+        assert!(mock.commands().contains(&"UID SEARCH UID 1:8".to_string()));
+
+        // Below the oldest message there is nothing left, not the newest page again
+        assert!(client.list_messages_before("INBOX", 2, 1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_snippets_reads_first_text_part() {
+        let plain =
+            "(\"TEXT\" \"PLAIN\" (\"CHARSET\" \"utf-8\") NIL NIL \"QUOTED-PRINTABLE\" 40 2)";
+        let html = "(\"TEXT\" \"HTML\" (\"CHARSET\" \"utf-8\") NIL NIL \"7BIT\" 60 1)";
+        let image = "(\"IMAGE\" \"PNG\" (\"NAME\" \"a.png\") NIL NIL \"BASE64\" 100)";
+        let structures = format!(
+            "* 1 FETCH (UID 3 BODYSTRUCTURE ({} {} \"ALTERNATIVE\"))\r\n\
+             * 2 FETCH (UID 4 BODYSTRUCTURE (({} \"RELATED\") {} \"MIXED\"))\r\n\
+             * 3 FETCH (UID 5 BODYSTRUCTURE {})\r\n",
+            plain, html, html, image, image
+        );
+        let section = |uid: u32, section: &str, data: &str| {
+            format!(
+                "* 1 FETCH (UID {} BODY[{}]<0> {{{}}}\r\n{})\r\n",
+                uid,
+                section,
+                data.len(),
+                data
+            )
+        };
+        let mock = MockImap::new()
+            .on("UID FETCH 3:5 (UID BODYSTRUCTURE)", &structures)
+            // Cut off in the middle of an escape
+            .on(
+                "UID FETCH 3 (UID BODY.PEEK[1]<0.2048>)",
+                &section(3, "1", "Lunch =\r\nat noon=3F ="),
+            )
+            .on(
+                "UID FETCH 4 (UID BODY.PEEK[1.1]<0.2048>)",
+                &section(4, "1.1", "<p>Quarterly   report</p><p>is <b>rea"),
+            );
+        let client = mock.client("acct");
+
+        let snippets = client
+            .fetch_snippets("INBOX", &[3, 4, 5], 200)
+            .await
+            .unwrap();
+
+        assert_eq!(snippets[&3], "Lunch at noon?");
+        assert!(snippets[&4].starts_with("Quarterly report is"));
+        assert!(!snippets[&4].contains('<'));
+        // Nothing to preview in an image
+        assert!(!snippets.contains_key(&5));
+        assert!(!mock.commands().iter().any(|c| c.contains("BODY[")));
+    }
+
+    #[tokio::test]
+    async fn test_folder_stats_leave_selected_folder_alone() {
+        let mock = MockImap::new().on("STATUS", "* STATUS Archive (MESSAGES 12 UNSEEN 4)\r\n");
+        let client = mock.client("acct");
+
+        client.list_messages("INBOX", 10, 0).await.unwrap();
+        assert_eq!(client.get_folder_stats("Archive").await.unwrap(), (12, 4));
+
+        let commands = mock.commands();
+        assert!(commands.contains(&"STATUS \"Archive\" (MESSAGES UNSEEN)".to_string()));
+        // INBOX is still the selected folder: nothing selected, examined or closed since
+        let switches: Vec<_> = commands
+            .iter()
+            .filter(|c| ["SELECT", "EXAMINE", "CLOSE", "UNSELECT"].iter().any(|p| c.starts_with(p)))
+            .collect();
+        assert_eq!(switches, ["SELECT \"INBOX\""]);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_flags_use_one_compacted_uid_store() {
+        let mock = MockImap::new();
+        let client = mock.client("acct");
+
+        client
+            .set_flags_bulk(
+                "INBOX",
+                &[25, 12, 20, 21, 22, 15, 23, 24, 20],
+                &[ImapFlag::Seen],
+                true,
+            )
+            .await
+            .unwrap();
+
+        let stores: Vec<_> = mock
+            .commands()
+            .into_iter()
+            .filter(|c| c.starts_with("UID STORE"))
+            .collect();
+        assert_eq!(stores, ["UID STORE 12,15,20:25 +FLAGS (\\Seen)"]);
+    }
+
+    #[tokio::test]
+    async fn test_gmail_archive_drops_inbox_label() {
+        let mock = MockImap::new();
+        let client = mock.client("acct");
+
+        client.remove_inbox_label("INBOX", 42).await.unwrap();
+
+        let commands = mock.commands();
+        assert!(commands.iter().any(|c| c == "UID STORE 42 -X-GM-LABELS (\\Inbox)"));
+        assert!(!commands.iter().any(|c| c.starts_with("UID MOVE")));
+    }
+
+    /// Accepts the connection but never sends a greeting
+    struct SilentTransport;
+
+    #[async_trait::async_trait]
+    impl ImapTransport for SilentTransport {
+        async fn open(&self) -> Result<Box<dyn ImapStream>> {
+            use tokio_util::compat::TokioAsyncReadCompatExt;
+
+            let (client, server) = tokio::io::duplex(1024);
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                drop(server);
+            });
+            Ok(Box::new(client.compat()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unresponsive_server_times_out_as_network_error() {
+        let client = ImapClient::with_transport(
+            "acct".to_string(),
+            "me@example.com".to_string(),
+            ProviderType::Custom,
+            ServerConfig {
+                imap_host: "imap.example.com".to_string(),
+                imap_port: 993,
+                smtp_host: "smtp.example.com".to_string(),
+                smtp_port: 465,
+                tls_mode: TlsMode::Implicit,
+                smtp_tls_mode: None,
+            },
+            ImapCredentials::Password {
+                user: "me@example.com".to_string(),
+                password: "secret".to_string(),
+            },
+            Arc::new(SilentTransport),
+        )
+        .with_timeouts(ImapTimeouts {
+            connect: std::time::Duration::from_millis(100),
+            ..Default::default()
+        });
+
+        let err = client.list_folders().await.unwrap_err();
+        assert!(crate::email::transport::is_timeout(&err));
+        assert!(matches!(
+            crate::email::error::EmailError::from(err),
+            crate::email::error::EmailError::Network(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_oauth_falls_back_to_oauthbearer_and_remembers_it() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let mock = MockImap::new().on_no(
+            "AUTHENTICATE XOAUTH2",
+            "[AUTHENTICATIONFAILED] Invalid credentials",
+        );
+        let mut client = mock.client("acct");
+        client.update_credentials(ImapCredentials::OAuth2 {
+            user: "me@example.com".to_string(),
+            access_token: "token".to_string(),
+        });
+
+        client.reconnect().await.unwrap();
+        let commands = mock.commands();
+        assert_eq!(commands[0], "AUTHENTICATE XOAUTH2");
+        assert_eq!(commands[2], "AUTHENTICATE OAUTHBEARER");
+        let response = String::from_utf8(STANDARD.decode(&commands[3]).unwrap()).unwrap();
+        assert_eq!(
+            response,
+            "n,a=me@example.com,\x01host=imap.example.com\x01port=993\x01auth=Bearer token\x01\x01"
+        );
+
+        // Later connections for the account start with the mechanism that worked
+        let other = client.new_connection();
+        other.reconnect().await.unwrap();
+        let commands = mock.commands();
+        let auths: Vec<_> = commands
+            .iter()
+            .filter(|c| c.starts_with("AUTHENTICATE"))
+            .collect();
+        assert_eq!(auths.last().unwrap().as_str(), "AUTHENTICATE OAUTHBEARER");
+        assert_eq!(auths.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_uid_commands_refuse_a_renumbered_folder() {
+        let mock = MockImap::new().on("SELECT", "* OK [UIDVALIDITY 5] UIDs valid\r\n");
+        let client = mock.client("acct");
+        // The UIDs on screen were listed before the server renumbered INBOX
+        client.note_uid_validity("INBOX", Some(4));
+
+        let err = client
+            .set_flags_bulk("INBOX", &[7], &[ImapFlag::Seen], true)
+            .await
+            .unwrap_err();
+        assert!(err.is::<UidValidityChanged>());
+        assert!(matches!(
+            crate::email::error::EmailError::from(err),
+            crate::email::error::EmailError::UidValidityChanged(_)
+        ));
+        assert!(!mock.commands().iter().any(|c| c.starts_with("UID STORE")));
+
+        // Listing the folder again hands out UIDs under the new validity
+        client.list_messages("INBOX", 50, 0).await.unwrap();
+        client
+            .set_flags_bulk("INBOX", &[7], &[ImapFlag::Seen], true)
+            .await
+            .unwrap();
+        assert!(mock.commands().iter().any(|c| c.starts_with("UID STORE 7")));
+    }
+
+    #[tokio::test]
+    async fn test_uid_commands_check_the_recorded_validity_after_a_restart() {
+        let mock = MockImap::new().on("SELECT", "* OK [UIDVALIDITY 5] UIDs valid\r\n");
+        // INBOX was last synced under validity 4; Archive was never recorded
+        let recorded: UidValidityLookup = Arc::new(|folder: &str| (folder == "INBOX").then_some(4));
+        let client = mock.client("acct").with_uid_validity_lookup(recorded);
+
+        let err = client
+            .set_flags_bulk("INBOX", &[7], &[ImapFlag::Seen], true)
+            .await
+            .unwrap_err();
+        let changed = err.downcast_ref::<UidValidityChanged>().unwrap();
+        assert_eq!((changed.expected, changed.found), (Some(4), 5));
+        let err = client
+            .set_flags_bulk("Archive", &[7], &[ImapFlag::Seen], true)
+            .await
+            .unwrap_err();
+        let unknown = err.downcast_ref::<UidValidityChanged>().unwrap();
+        assert_eq!(unknown.expected, None);
+        assert!(!mock.commands().iter().any(|c| c.starts_with("UID STORE")));
+
+        // Still refused on a second try; only listing the folder again clears it
+        assert!(client
+            .set_flags_bulk("INBOX", &[7], &[ImapFlag::Seen], true)
+            .await
+            .is_err());
+        client.list_messages("INBOX", 50, 0).await.unwrap();
+        client
+            .set_flags_bulk("INBOX", &[7], &[ImapFlag::Seen], true)
+            .await
+            .unwrap();
+        assert!(mock.commands().iter().any(|c| c.starts_with("UID STORE 7")));
+    }
+
+    #[tokio::test]
+    async fn test_folder_management_nests_and_protects_special_folders() {
+        let mock = MockImap::new()
+            .on("LIST \"\" \"\"", "* LIST (\\Noselect) \"/\" \"\"\r\n")
+            .on(
+                "LIST \"\" \"Trash\"",
+                "* LIST (\\HasNoChildren \\Trash) \"/\" \"Trash\"\r\n",
+            )
+            .on(
+                "LIST \"\" \"Old\"",
+                "* LIST (\\HasNoChildren) \"/\" \"Old\"\r\n",
+            );
+        let client = mock.client("acct");
+
+        let folder = client
+            .create_folder("Entwürfe", Some("Work"))
+            .await
+            .unwrap();
+        assert_eq!(folder, "Work/Entwürfe");
+        let commands = mock.commands();
+        assert!(commands.contains(&"CREATE \"Work/Entw&APw-rfe\"".to_string()));
+        assert!(commands.contains(&"SUBSCRIBE \"Work/Entw&APw-rfe\"".to_string()));
+        assert!(client.create_folder("a/b", None).await.is_err());
+
+        let err = client.delete_folder("Trash").await.unwrap_err();
+        assert!(err.to_string().contains("special folder"));
+        client.delete_folder("Old").await.unwrap();
+        let deletes: Vec<_> = mock
+            .commands()
+            .into_iter()
+            .filter(|c| c.starts_with("DELETE"))
+            .collect();
+        assert_eq!(deletes, ["DELETE \"Old\""]);
+
+        assert!(nested_folder_name(Some("Work"), "Q3", None).is_err());
+        assert_eq!(nested_folder_name(Some(""), " Q3 ", None).unwrap(), "Q3");
+    }
+
+    #[tokio::test]
+    async fn test_mark_folder_read_stores_unseen_in_one_command() {
+        let mock = MockImap::new()
+            .on("SELECT", "* 9 EXISTS\r\n")
+            .on("UID SEARCH", "* SEARCH 3 4 5 9\r\n");
+        let client = mock.client("acct");
+
+        assert_eq!(client.mark_folder_read("Lists").await.unwrap(), vec![3, 4, 5, 9]);
+        let stores: Vec<_> = mock
+            .commands()
+            .into_iter()
+            .filter(|c| c.starts_with("UID STORE"))
+            .collect();
+        assert_eq!(stores, ["UID STORE 3:5,9 +FLAGS.SILENT (\\Seen)"]);
+
+        // An empty folder is not an error and sends no STORE
+        let empty = MockImap::new().on("SELECT", "* 0 EXISTS\r\n");
+        assert!(empty.client("acct").mark_folder_read("Lists").await.unwrap().is_empty());
+        assert!(!empty.commands().iter().any(|c| c.starts_with("UID STORE")));
+    }
+
+    #[tokio::test]
+    async fn test_copy_message_returns_copyuid() {
+        let mock = MockImap::new().on_ok("UID COPY", "[COPYUID 38505 7 3956] Done");
+        let client = mock.client("acct");
+
+        let copied = client.copy_message("INBOX", 7, "Projects/Q3").await.unwrap();
+        assert_eq!(copied, Some(3956));
+        assert!(mock
+            .commands()
+            .iter()
+            .any(|c| c == "UID COPY 7 \"Projects/Q3\""));
+
+        // Without UIDPLUS the copy still succeeds, just without a UID
+        let plain = MockImap::new().client("acct");
+        assert_eq!(plain.copy_message("INBOX", 7, "Archive").await.unwrap(), None);
+
+        assert!(client.copy_message("INBOX", 7, "INBOX").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_raw_message_is_fetched_with_peek() {
+        let raw = "From: ana@example.com\r\nSubject: Hi\r\n\r\nBody\r\n";
+        let mock = MockImap::new()
+            .on("EXAMINE", "* 1 EXISTS\r\n")
+            .on(
+                "UID FETCH 5 BODY.PEEK[]<6.",
+                "* 1 FETCH (UID 5 BODY[]<6> {6}\r\nana@ex)\r\n",
+            )
+            .on(
+                "UID FETCH 5 BODY.PEEK[]",
+                &format!("* 1 FETCH (UID 5 BODY[] {{{}}}\r\n{})\r\n", raw.len(), raw),
+            )
+            .on(
+                "UID FETCH 5 RFC822.SIZE",
+                &format!("* 1 FETCH (UID 5 RFC822.SIZE {})\r\n", raw.len()),
+            );
+        let client = mock.client("acct");
+
+        let source = client.get_raw_message("INBOX", 5).await.unwrap();
+        assert_eq!(source, raw.as_bytes());
+        let size = client.get_raw_size("INBOX", 5).await.unwrap();
+        assert_eq!(size, raw.len() as u32);
+        let range = client.fetch_raw_range("INBOX", 5, 6, 6).await.unwrap();
+        assert_eq!(range, b"ana@ex");
+
+        // Only read-only access: no SELECT and nothing that could set \Seen
+        let commands = mock.commands();
+        assert!(commands.iter().any(|c| c == "UID FETCH 5 BODY.PEEK[]"));
+        assert!(!commands
+            .iter()
+            .any(|c| c.starts_with("SELECT") || c.contains("BODY[")));
+    }
+
+    #[tokio::test]
+    async fn test_move_message_to_returns_copyuid() {
+        let mock = MockImap::new().on(
+            "UID MOVE",
+            "* OK [COPYUID 38505 7 3956] Moved\r\n* 1 EXPUNGE\r\n",
+        );
+        let client = mock.client("acct");
+
+        let moved = client
+            .move_message_to("INBOX", 7, "Projects/Q3")
+            .await
+            .unwrap();
+        assert_eq!(moved, Some(3956));
+        let commands = mock.commands();
+        assert!(commands.iter().any(|c| c == "UID MOVE 7 \"Projects/Q3\""));
+        assert!(!commands.iter().any(|c| c.starts_with("UID COPY")));
+
+        assert!(client.move_message_to("INBOX", 7, "INBOX").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_attachment_decodes_nested_part() {
+        let mock = MockImap::new()
+            .on("SELECT", "* 1 EXISTS\r\n")
+            .on("EXAMINE", "* 1 EXISTS\r\n")
+            .on(
+                "UID FETCH 5 BODYSTRUCTURE",
+                "* 1 FETCH (UID 5 BODYSTRUCTURE (((\"text\" \"plain\" NIL NIL NIL \"7bit\" 5 1 NIL NIL NIL NIL)\
+                 (\"application\" \"pdf\" (\"name\" \"=?UTF-8?Q?r=C3=A9sum=C3=A9.pdf?=\") NIL NIL \"base64\" 16 NIL NIL NIL NIL)\
+                 (\"image\" \"png\" NIL \"<logo@acme>\" NIL \"base64\" 8 NIL (\"inline\" NIL) NIL NIL) \
+                 \"related\" NIL NIL NIL NIL) \"mixed\" NIL NIL NIL NIL))\r\n",
+            )
+            .on(
+                "UID FETCH 5 BODY.PEEK[1.2]",
+                "* 1 FETCH (UID 5 BODY[1.2] {16}\r\naGVsbG8gd29ybGQ=)\r\n",
+            )
+            .on(
+                "UID FETCH 5 BODY.PEEK[1.3]",
+                "* 1 FETCH (UID 5 BODY[1.3] {8}\r\niVBORw==)\r\n",
+            );
+        let client = mock.client("acct");
+
+        let attachment = client.get_attachment("INBOX", 5, "1.2").await.unwrap();
+        assert_eq!(attachment.filename, "résumé.pdf");
+        assert_eq!(attachment.content_type, "application/pdf");
+        assert_eq!(attachment.data, b"hello world");
+
+        assert!(client.get_attachment("INBOX", 5, "1.1").await.is_err());
+
+        // The inline image is fetched by Content-ID, not listed as an attachment
+        assert!(client.get_attachment("INBOX", 5, "1.3").await.is_err());
+        let logo = client
+            .get_inline_part("INBOX", 5, "<logo@acme>")
+            .await
+            .unwrap();
+        assert_eq!(logo.part_id, "1.3");
+        assert_eq!(logo.content_type, "image/png");
+        assert_eq!(logo.data, b"\x89PNG\r\n");
+    }
+
+    #[test]
+    fn test_html_message_is_alternative_with_generated_plain_part() {
+        let html = format!("<p>Hello <b>team</b>,</p><p>{}</p>", "word ".repeat(60));
+        let build = || {
+            build_message(
+                "me@example.com",
+                &["ana@example.com".to_string()],
+                &[],
+                &[],
+                "Update",
+                &ReplyHeaders::default(),
+                &html,
+                "",
+                &[],
+            )
+            .unwrap()
+        };
+        let first = String::from_utf8(build().formatted()).unwrap();
+        let second = String::from_utf8(build().formatted()).unwrap();
+
+        let boundary = |raw: &str| {
+            let start = raw.find("boundary=\"").unwrap() + "boundary=\"".len();
+            raw[start..].split('"').next().unwrap().to_string()
+        };
+        assert!(first.contains("multipart/alternative"));
+        assert_ne!(boundary(&first), boundary(&second));
+
+        // The plain part is derived from the HTML, not left empty
+        let parsed = mail_parser::MessageParser::default()
+            .parse(first.as_bytes())
+            .unwrap();
+        assert!(parsed.body_text(0).unwrap().starts_with("Hello team,"));
+        assert!(first.lines().all(|line| line.len() <= 78));
+    }
+
+    #[test]
+    fn test_attachments_make_mixed_message_with_base64_parts() {
+        use mail_parser::MimeHeaders;
+
+        let attachment = OutgoingAttachment {
+            filename: "Q3 report.pdf".to_string(),
+            content_type: "application/pdf".to_string(),
+            data: b"%PDF-1.4 \x00\xff".to_vec(),
+        };
+        let message = build_message(
+            "me@example.com",
+            &["ana@example.com".to_string()],
+            &[],
+            &[],
+            "Report",
+            &ReplyHeaders::default(),
+            "<p>Attached</p>",
+            "Attached",
+            &[attachment.clone()],
+        )
+        .unwrap();
+        let raw = String::from_utf8(message.formatted()).unwrap();
+        assert!(raw.contains("multipart/mixed"));
+        assert!(raw.contains("multipart/alternative"));
+        assert!(raw.contains("Content-Disposition: attachment; filename=\"Q3 report.pdf\""));
+        assert!(raw.contains("Content-Transfer-Encoding: base64"));
+
+        let parsed = mail_parser::MessageParser::default()
+            .parse(raw.as_bytes())
+            .unwrap();
+        assert_eq!(parsed.attachment_count(), 1);
+        let part = parsed.attachment(0).unwrap();
+        assert_eq!(part.attachment_name(), Some("Q3 report.pdf"));
+        assert_eq!(part.contents(), attachment.data.as_slice());
+        assert_eq!(parsed.body_text(0).as_deref(), Some("Attached"));
+    }
+
+    #[tokio::test]
+    async fn test_save_draft_replaces_previous_version() {
+        let mock = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1 UIDPLUS\r\n")
+            .on("UID SEARCH", "* SEARCH 42\r\n");
+        let client = mock.client("acct");
+        let saved_at = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_772_445_600);
+        let draft = build_draft(
+            "me@example.com",
+            &["ana@example.com".to_string()],
+            &[],
+            &["bo@example.com".to_string()],
+            "Plans",
+            "",
+            "Still thinking",
+            saved_at,
+        )
+        .unwrap();
+
+        assert_eq!(client.save_draft("Drafts", &draft, Some(41)).await.unwrap(), 42);
+
+        let commands = mock.commands();
+        let append = commands.iter().find(|c| c.starts_with("APPEND")).unwrap();
+        assert!(append
+            .starts_with("APPEND \"Drafts\" (\\Seen \\Draft) \" 2-Mar-2026 10:00:00 +0000\""));
+        assert!(commands.iter().any(|c| c == "UID STORE 41 +FLAGS (\\Deleted)"));
+        assert!(commands.iter().any(|c| c == "UID EXPUNGE 41"));
+        assert!(!commands.iter().any(|c| c == "EXPUNGE"));
+
+        // The stored copy keeps Bcc and its Date is the save time
+        let raw = &mock.appended()[0];
+        assert!(raw.contains("Bcc: bo@example.com"));
+        let stored = client.parse_raw_email(42, "Drafts", raw.as_bytes(), &[]).unwrap();
+        assert_eq!(stored.date_timestamp, 1_772_445_600);
+
+        // Without UIDPLUS the old version is only flagged
+        let mock = MockImap::new().on("UID SEARCH", "* SEARCH 43\r\n");
+        let client = mock.client("acct");
+        assert_eq!(
+            client.save_draft("Drafts", &draft, Some(42)).await.unwrap(),
+            43
+        );
+        let commands = mock.commands();
+        assert!(commands
+            .iter()
+            .any(|c| c == "UID STORE 42 +FLAGS (\\Deleted)"));
+        assert!(!commands.iter().any(|c| c.contains("EXPUNGE")));
+    }
+
+    #[tokio::test]
+    async fn test_save_sent_appends_the_exact_bytes_sent() {
+        let mock = MockImap::new().on("UID SEARCH", "* SEARCH 7\r\n");
+        let client = mock.client("acct");
+        let message = build_message(
+            "me@example.com",
+            &["ana@example.com".to_string()],
+            &[],
+            &["bo@example.com".to_string()],
+            "Plans",
+            &ReplyHeaders::default(),
+            "",
+            "See you at 10",
+            &[],
+        )
+        .unwrap();
+        let raw = message.formatted();
+
+        assert_eq!(client.save_sent("Sent", &raw).await.unwrap(), 7);
+        assert_eq!(mock.appended(), [String::from_utf8(raw.clone()).unwrap()]);
+        let commands = mock.commands();
+        let append = commands.iter().find(|c| c.starts_with("APPEND")).unwrap();
+        assert!(append.starts_with("APPEND \"Sent\" (\\Seen) \""));
+
+        // The search uses the Message-ID generated when the message was built
+        let parsed = MessageParser::default().parse(&raw).unwrap();
+        let search = commands
+            .iter()
+            .find(|c| c.starts_with("UID SEARCH"))
+            .unwrap();
+        assert!(search.contains(parsed.message_id().unwrap()));
+        assert!(!ProviderType::Custom.saves_sent_mail());
+    }
+
+    #[tokio::test]
+    async fn test_keywords_map_to_gmail_labels() {
+        let mock = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1 X-GM-EXT-1\r\n")
+            .on(
+                "UID FETCH 5",
+                "* 1 FETCH (UID 5 FLAGS (\\Seen \\Recent $Todo) X-GM-LABELS (\\Inbox Work))\r\n",
+            );
+        let client = mock.client("acct");
+        let bad = ImapFlag::Keyword("two words".to_string());
+        assert!(client.set_flags_bulk("INBOX", &[5], &[bad], true).await.is_err());
+
+        let flags = [ImapFlag::Seen, ImapFlag::keyword("Todo").unwrap()];
+        client.set_flags_bulk("INBOX", &[5], &flags, true).await.unwrap();
+        let stores: Vec<_> = mock
+            .commands()
+            .into_iter()
+            .filter(|c| c.starts_with("UID STORE"))
+            .collect();
+        assert_eq!(
+            stores,
+            vec!["UID STORE 5 +FLAGS (\\Seen)", "UID STORE 5 +X-GM-LABELS (Todo)"]
+        );
+
+        assert_eq!(
+            client.get_flags("INBOX", 5).await.unwrap(),
+            vec![
+                ImapFlag::Seen,
+                ImapFlag::Keyword("$Todo".to_string()),
+                ImapFlag::Keyword("\\Inbox".to_string()),
+                ImapFlag::Keyword("Work".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_thread_messages_read_references_and_gmail_thread_id() {
+        let mock = MockImap::new()
+            .on("CAPABILITY", "* CAPABILITY IMAP4rev1 X-GM-EXT-1\r\n")
+            .on("EXAMINE", "* 2 EXISTS\r\n")
+            .on(
+                "FETCH 1:2",
+                "* 1 FETCH (UID 7 FLAGS (\\Seen) ENVELOPE (\"Mon, 7 Feb 2022 21:52:25 -0800\" \
+                 \"Lunch\" ((\"Amy\" NIL \"amy\" \"example.com\")) NIL NIL NIL NIL NIL NIL \
+                 \"<a@x>\") BODY[HEADER.FIELDS (REFERENCES)] {2}\r\n\r\n X-GM-THRID 255)\r\n\
+                 * 2 FETCH (UID 9 FLAGS () ENVELOPE (\"Tue, 8 Feb 2022 09:00:00 +0000\" \
+                 \"Re: Lunch\" ((NIL NIL \"bob\" \"example.com\")) NIL NIL NIL NIL NIL \
+                 \"<a@x>\" \"<b@x>\") BODY[HEADER.FIELDS (REFERENCES)] {21}\r\n\
+                 References: <a@x>\r\n\r\n X-GM-THRID 255)\r\n",
+            );
+        let client = mock.client("acct");
+
+        let messages = client.list_thread_messages("INBOX", 10).await.unwrap();
+        assert!(mock.commands().iter().any(|c| c
+            == "FETCH 1:2 (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] X-GM-THRID)"));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].id, "acct:INBOX:7");
+        assert_eq!(messages[0].message_id, "a@x");
+        assert!(messages[0].is_read);
+        assert_eq!(messages[1].in_reply_to.as_deref(), Some("a@x"));
+        assert_eq!(messages[1].references, ["a@x"]);
+        assert_eq!(messages[1].gmail_thread_id, Some(255));
+
+        let threads = group_threads(messages);
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].thread_id, "ff");
+        assert_eq!(threads[0].participants, ["Amy", "bob@example.com"]);
+        assert_eq!(threads[0].unread_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_snoozed_folder_created_and_message_found_by_id() {
+        let mock = MockImap::new().on("UID SEARCH", "* SEARCH 3 9\r\n");
+        let client = mock.client("acct");
+
+        client.ensure_folder("Snoozed").await.unwrap();
+        let uid = client.find_message_id("Snoozed", "a@x").await.unwrap();
+        assert_eq!(uid, Some(9));
+
+        let commands = mock.commands();
+        assert!(commands.iter().any(|c| c == "CREATE \"Snoozed\""));
+        assert!(commands.iter().any(|c| c == "SUBSCRIBE \"Snoozed\""));
+        assert!(commands
+            .iter()
+            .any(|c| c == "UID SEARCH HEADER Message-ID \"<a@x>\""));
+    }
+
+    #[tokio::test]
+    async fn test_ensure_folder_lists_names_quoted_and_exactly() {
+        let mock = MockImap::new()
+            .on(
+                "LIST \"\" \"Project X\"",
+                "* LIST (\\HasNoChildren) \"/\" \"Project X\"\r\n",
+            )
+            // The wildcard also matches other folders
+            .on(
+                "LIST \"\" \"Deals%\"",
+                "* LIST (\\HasNoChildren) \"/\" \"Deals 2025\"\r\n",
+            );
+        let client = mock.client("acct");
+
+        client.ensure_folder("Project X").await.unwrap();
+        client.ensure_folder("Deals*").await.unwrap();
+
+        let commands = mock.commands();
+        assert!(commands.contains(&"LIST \"\" \"Project X\"".to_string()));
+        assert!(!commands.contains(&"CREATE \"Project X\"".to_string()));
+        assert!(commands.contains(&"CREATE \"Deals*\"".to_string()));
+    }
+}
@@ -0,0 +1,323 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use url::{Host, Url};
+
+/// Body of an RFC 8058 one-click unsubscribe POST
+const ONE_CLICK_BODY: &str = "List-Unsubscribe=One-Click";
+const ONE_CLICK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Mailing-list metadata from the RFC 2369 / RFC 2919 `List-*` headers
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MailingList {
+    /// List identifier from List-Id (e.g. "dev.lists.example.org"),
+    /// falling back to the List-Post address when List-Id is missing
+    pub id: Option<String>,
+    /// Human-readable name from the List-Id phrase
+    pub name: Option<String>,
+    /// Address replies to the list should go to (None for announce-only lists)
+    pub post: Option<String>,
+    pub archive: Option<String>,
+    /// Unsubscribe URLs (mailto: and/or https:), in header order
+    pub unsubscribe: Vec<String>,
+}
+
+impl MailingList {
+    /// Build from raw header values; returns None when the message carries none of them
+    pub fn from_headers(
+        list_id: Option<&str>,
+        list_post: Option<&str>,
+        list_archive: Option<&str>,
+        list_unsubscribe: Option<&str>,
+    ) -> Option<Self> {
+        if list_id.is_none()
+            && list_post.is_none()
+            && list_archive.is_none()
+            && list_unsubscribe.is_none()
+        {
+            return None;
+        }
+
+        let (name, id) = list_id.map(parse_list_id).unwrap_or((None, None));
+        let post = list_post
+            .and_then(|v| bracketed_urls(v).into_iter().next())
+            .map(|url| strip_mailto(&url));
+        let archive = list_archive.and_then(|v| bracketed_urls(v).into_iter().next());
+        let unsubscribe = list_unsubscribe.map(bracketed_urls).unwrap_or_default();
+
+        Some(Self {
+            id: id.or_else(|| post.clone()),
+            name,
+            post,
+            archive,
+            unsubscribe,
+        })
+    }
+}
+
+/// How a message can be unsubscribed from, per List-Unsubscribe (RFC 2369)
+/// and List-Unsubscribe-Post (RFC 8058). Only https: and mailto: targets
+/// that pass validation are kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UnsubscribeInfo {
+    pub mailto: Option<MailtoUnsubscribe>,
+    pub https: Option<String>,
+    /// The https URL accepts a one-click POST, no browser needed
+    pub one_click: bool,
+}
+
+/// Message to send for a mailto: unsubscribe
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MailtoUnsubscribe {
+    pub to: String,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+impl UnsubscribeInfo {
+    /// Build from raw header values; None when nothing usable is offered
+    pub fn from_headers(
+        list_unsubscribe: Option<&str>,
+        list_unsubscribe_post: Option<&str>,
+    ) -> Option<Self> {
+        let urls = bracketed_urls(list_unsubscribe?);
+        let one_click = list_unsubscribe_post
+            .is_some_and(|value| collapse_whitespace(value).eq_ignore_ascii_case(ONE_CLICK_BODY));
+        Self::from_urls(&urls, one_click)
+    }
+
+    /// Build from List-Unsubscribe URLs, e.g. the ones cached in `MailingList`
+    pub fn from_urls(urls: &[String], one_click: bool) -> Option<Self> {
+        let https = urls
+            .iter()
+            .find_map(|url| validate_unsubscribe_url(url).ok())
+            .map(String::from);
+        let mailto = urls.iter().find_map(|url| parse_mailto(url));
+        if https.is_none() && mailto.is_none() {
+            return None;
+        }
+        Some(Self {
+            one_click: one_click && https.is_some(),
+            mailto,
+            https,
+        })
+    }
+}
+
+/// Check an unsubscribe URL is safe to request: https only, no credentials,
+/// and a public host name (no IP literals or local names), so a crafted
+/// header can't point the app at the local network
+pub fn validate_unsubscribe_url(url: &str) -> Result<Url> {
+    let parsed = Url::parse(url).context("Invalid unsubscribe URL")?;
+    if parsed.scheme() != "https" {
+        anyhow::bail!("Unsubscribe URL must use https: {}", url);
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        anyhow::bail!("Unsubscribe URL must not carry credentials");
+    }
+    if parsed.port().is_some_and(|port| port != 443) {
+        anyhow::bail!("Unsubscribe URL must use the default https port");
+    }
+    match parsed.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_lowercase();
+            let local = domain == "localhost"
+                || !domain.contains('.')
+                || [".localhost", ".local", ".internal", ".lan", ".home.arpa"]
+                    .iter()
+                    .any(|suffix| domain.ends_with(suffix));
+            if local {
+                anyhow::bail!("Unsubscribe URL points at a local host: {}", domain);
+            }
+        }
+        _ => anyhow::bail!("Unsubscribe URL must name a host, not an IP address"),
+    }
+    Ok(parsed)
+}
+
+/// Parse `mailto:addr?subject=..&body=..`, rejecting anything that could
+/// inject headers
+fn parse_mailto(url: &str) -> Option<MailtoUnsubscribe> {
+    let parsed = Url::parse(url).ok()?;
+    if parsed.scheme() != "mailto" {
+        return None;
+    }
+    let to = urlencoding::decode(parsed.path()).ok()?.into_owned();
+    if !to.contains('@') || to.contains(['\r', '\n', ',', '<', '>']) {
+        return None;
+    }
+
+    let mut mailto = MailtoUnsubscribe {
+        to,
+        ..Default::default()
+    };
+    for (key, value) in parsed.query_pairs() {
+        match key.to_ascii_lowercase().as_str() {
+            "subject" if !value.contains(['\r', '\n']) => mailto.subject = Some(value.into_owned()),
+            "body" => mailto.body = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+    Some(mailto)
+}
+
+/// Send the RFC 8058 one-click unsubscribe POST. Redirects aren't followed,
+/// so the request can't be bounced to a host that failed validation.
+pub async fn one_click_unsubscribe(url: &str) -> Result<()> {
+    let url = validate_unsubscribe_url(url)?;
+    let http = reqwest::Client::builder()
+        .timeout(ONE_CLICK_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    http.post(url)
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .body(ONE_CLICK_BODY)
+        .send()
+        .await
+        .context("Unsubscribe request failed")?
+        .error_for_status()
+        .context("Unsubscribe was rejected")?;
+    Ok(())
+}
+
+/// Split `"Phrase" <list.id>` into (name, id)
+fn parse_list_id(value: &str) -> (Option<String>, Option<String>) {
+    let value = collapse_whitespace(value);
+    match (value.find('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => {
+            let id = value[start + 1..end].trim().to_lowercase();
+            let name = value[..start].trim().trim_matches('"').trim().to_string();
+            (
+                (!name.is_empty()).then_some(name),
+                (!id.is_empty()).then_some(id),
+            )
+        }
+        // Some senders omit the angle brackets
+        _ => {
+            let id = value.trim().to_lowercase();
+            (None, (!id.is_empty()).then_some(id))
+        }
+    }
+}
+
+/// All `<url>` entries of a List-* header, ignoring comments and "NO"
+fn bracketed_urls(value: &str) -> Vec<String> {
+    let value = collapse_whitespace(value);
+    let mut urls = Vec::new();
+    let mut rest = value.as_str();
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let url = rest[start + 1..start + len].replace(' ', "");
+        if !url.is_empty() {
+            urls.push(url);
+        }
+        rest = &rest[start + len + 1..];
+    }
+    urls
+}
+
+fn strip_mailto(url: &str) -> String {
+    match url.strip_prefix("mailto:") {
+        Some(address) => address.split('?').next().unwrap_or(address).to_string(),
+        None => url.to_string(),
+    }
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_list_headers() {
+        let list = MailingList::from_headers(
+            Some("\"Rust Dev\" <Dev.Lists.Example.org>"),
+            Some("<mailto:dev@lists.example.org>"),
+            Some("<https://lists.example.org/archive/dev>"),
+            Some("<mailto:dev-leave@lists.example.org?subject=unsubscribe>,\r\n <https://lists.example.org/u/dev>"),
+        )
+        .unwrap();
+
+        assert_eq!(list.id.as_deref(), Some("dev.lists.example.org"));
+        assert_eq!(list.name.as_deref(), Some("Rust Dev"));
+        assert_eq!(list.post.as_deref(), Some("dev@lists.example.org"));
+        assert_eq!(list.archive.as_deref(), Some("https://lists.example.org/archive/dev"));
+        assert_eq!(list.unsubscribe.len(), 2);
+    }
+
+    #[test]
+    fn test_partial_list_headers() {
+        assert!(MailingList::from_headers(None, None, None, None).is_none());
+
+        // Announce-only list: no posting allowed, no List-Id
+        let announce = MailingList::from_headers(
+            None,
+            Some("NO (posting not allowed)"),
+            None,
+            Some("<https://example.com/unsub>"),
+        )
+        .unwrap();
+        assert_eq!(announce.id, None);
+        assert_eq!(announce.post, None);
+        assert_eq!(announce.unsubscribe, vec!["https://example.com/unsub"]);
+
+        // List-Post alone identifies the list
+        let posted =
+            MailingList::from_headers(None, Some("<mailto:team@example.com>"), None, None).unwrap();
+        assert_eq!(posted.id.as_deref(), Some("team@example.com"));
+    }
+
+    #[test]
+    fn test_unsubscribe_methods_and_one_click() {
+        let info = UnsubscribeInfo::from_headers(
+            Some("<mailto:leave@example.com?subject=Unsubscribe%20me>, <https://news.example.com/u/42>"),
+            Some("List-Unsubscribe=One-Click"),
+        )
+        .unwrap();
+        assert_eq!(info.https.as_deref(), Some("https://news.example.com/u/42"));
+        assert!(info.one_click);
+        let mailto = info.mailto.unwrap();
+        assert_eq!(mailto.to, "leave@example.com");
+        assert_eq!(mailto.subject.as_deref(), Some("Unsubscribe me"));
+
+        // No List-Unsubscribe-Post: the link needs a browser
+        let browser =
+            UnsubscribeInfo::from_headers(Some("<https://news.example.com/u/42>"), None).unwrap();
+        assert!(!browser.one_click);
+
+        // Nothing safe to use
+        assert!(UnsubscribeInfo::from_headers(
+            Some("<http://news.example.com/u>, <https://127.0.0.1/u>"),
+            Some("List-Unsubscribe=One-Click"),
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_unsubscribe_url_validation() {
+        assert!(validate_unsubscribe_url("https://news.example.com/u?id=1").is_ok());
+        for url in [
+            "http://news.example.com/u",
+            "file:///etc/passwd",
+            "https://localhost/u",
+            "https://intranet/u",
+            "https://printer.local/u",
+            "https://10.0.0.1/u",
+            "https://[::1]/u",
+            "https://user:pw@news.example.com/u",
+            "https://news.example.com:8443/u",
+        ] {
+            assert!(validate_unsubscribe_url(url).is_err(), "{} accepted", url);
+        }
+    }
+}
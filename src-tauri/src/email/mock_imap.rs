@@ -0,0 +1,216 @@
+//! Scriptable in-memory IMAP server for unit tests.
+//!
+//! `ImapClient` talks to it over an in-memory pipe exactly as it would to a real
+//! server, so tests exercise the real command and response handling.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use super::imap_client::{ImapClient, ImapCredentials};
+use super::server_presets::{ProviderType, ServerConfig, TlsMode};
+use super::transport::{ImapStream, ImapTransport};
+
+/// Canned responses keyed by command prefix, plus a log of every command received
+#[derive(Clone, Default)]
+pub struct MockImap {
+    responses: Arc<Mutex<Vec<(String, String)>>>,
+    completions: Arc<Mutex<Vec<(String, String)>>>,
+    rejections: Arc<Mutex<Vec<(String, String)>>>,
+    idle_event: Arc<Mutex<Option<String>>>,
+    commands: Arc<Mutex<Vec<String>>>,
+    appended: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockImap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `untagged` before the tagged OK for commands starting with `prefix`
+    /// (matched case-insensitively against the command without its tag)
+    pub fn on(self, prefix: &str, untagged: &str) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .push((prefix.to_uppercase(), untagged.to_string()));
+        self
+    }
+
+    /// Complete commands starting with `prefix` with `OK <text>` instead of
+    /// the plain completion, e.g. to carry a response code
+    pub fn on_ok(self, prefix: &str, text: &str) -> Self {
+        self.completions
+            .lock()
+            .unwrap()
+            .push((prefix.to_uppercase(), text.to_string()));
+        self
+    }
+
+    /// Fail commands starting with `prefix` with `NO <text>`
+    pub fn on_no(self, prefix: &str, text: &str) -> Self {
+        self.rejections
+            .lock()
+            .unwrap()
+            .push((prefix.to_uppercase(), text.to_string()));
+        self
+    }
+
+    /// Untagged response pushed while the client is IDLEing; without one IDLE times out
+    pub fn on_idle(self, untagged: &str) -> Self {
+        *self.idle_event.lock().unwrap() = Some(untagged.to_string());
+        self
+    }
+
+    /// Commands received so far, without tags
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    /// Messages received by APPEND so far
+    pub fn appended(&self) -> Vec<String> {
+        self.appended.lock().unwrap().clone()
+    }
+
+    /// An `ImapClient` whose connections are served by this mock
+    pub fn client(&self, account_id: &str) -> ImapClient {
+        ImapClient::with_transport(
+            account_id.to_string(),
+            "me@example.com".to_string(),
+            ProviderType::Custom,
+            ServerConfig {
+                imap_host: "imap.example.com".to_string(),
+                imap_port: 993,
+                smtp_host: "smtp.example.com".to_string(),
+                smtp_port: 465,
+                tls_mode: TlsMode::Implicit,
+                smtp_tls_mode: None,
+            },
+            ImapCredentials::Password {
+                user: "me@example.com".to_string(),
+                password: "secret".to_string(),
+            },
+            Arc::new(self.clone()),
+        )
+    }
+
+    fn untagged_for(&self, command: &str) -> String {
+        scripted(&self.responses, command).unwrap_or_default()
+    }
+
+    async fn serve(self, stream: DuplexStream) -> std::io::Result<()> {
+        let (read, mut write) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read).lines();
+        write
+            .write_all(b"* OK [CAPABILITY IMAP4rev1 IDLE MOVE] Mock ready\r\n")
+            .await?;
+
+        while let Some(line) = lines.next_line().await? {
+            let Some((tag, command)) = line.split_once(' ') else {
+                continue;
+            };
+            self.commands.lock().unwrap().push(command.to_string());
+            if let Some(len) = literal_len(command) {
+                write.write_all(b"+ Ready for literal data\r\n").await?;
+                // The literal plus the CRLF ending the command
+                let mut literal = vec![0; len + 2];
+                lines.get_mut().read_exact(&mut literal).await?;
+                literal.truncate(len);
+                let literal = String::from_utf8_lossy(&literal).into_owned();
+                self.appended.lock().unwrap().push(literal);
+            }
+            let verb = command.split(' ').next().unwrap_or("").to_uppercase();
+
+            match verb.as_str() {
+                "LOGOUT" => {
+                    write
+                        .write_all(format!("* BYE\r\n{} OK LOGOUT completed\r\n", tag).as_bytes())
+                        .await?;
+                    break;
+                }
+                "IDLE" => {
+                    write.write_all(b"+ idling\r\n").await?;
+                    let event = self.idle_event.lock().unwrap().clone();
+                    if let Some(event) = event {
+                        write.write_all(event.as_bytes()).await?;
+                    }
+                    // Wait for DONE
+                    if let Some(done) = lines.next_line().await? {
+                        self.commands.lock().unwrap().push(done);
+                    }
+                    write
+                        .write_all(format!("{} OK IDLE terminated\r\n", tag).as_bytes())
+                        .await?;
+                }
+                "AUTHENTICATE" => {
+                    write.write_all(b"+ \r\n").await?;
+                    // The client's base64 response
+                    if let Some(response) = lines.next_line().await? {
+                        self.commands.lock().unwrap().push(response);
+                    }
+                    let status = match scripted(&self.rejections, command) {
+                        Some(text) => format!("NO {}", text),
+                        None => "OK AUTHENTICATE completed".to_string(),
+                    };
+                    write
+                        .write_all(format!("{} {}\r\n", tag, status).as_bytes())
+                        .await?;
+                }
+                _ => {
+                    let untagged = self.untagged_for(command);
+                    if let Some(text) = scripted(&self.rejections, command) {
+                        write
+                            .write_all(format!("{}{} NO {}\r\n", untagged, tag, text).as_bytes())
+                            .await?;
+                        continue;
+                    }
+                    let completion = scripted(&self.completions, command)
+                        .unwrap_or_else(|| format!("{} completed", verb));
+                    write
+                        .write_all(format!("{}{} OK {}\r\n", untagged, tag, completion).as_bytes())
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// First scripted reply whose prefix matches `command` (case-insensitively)
+fn scripted(replies: &Mutex<Vec<(String, String)>>, command: &str) -> Option<String> {
+    let upper = command.to_uppercase();
+    replies
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(prefix, _)| upper.starts_with(prefix))
+        .map(|(_, reply)| reply.clone())
+}
+
+/// Length of a synchronizing literal (`{123}`) ending the command line
+fn literal_len(command: &str) -> Option<usize> {
+    let (_, literal) = command.strip_suffix('}')?.rsplit_once('{')?;
+    literal.parse().ok()
+}
+
+#[async_trait::async_trait]
+impl ImapTransport for MockImap {
+    async fn open(&self) -> Result<Box<dyn ImapStream>> {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(self.clone().serve(server));
+        Ok(Box::new(client.compat()))
+    }
+}
+
+/// Untagged FETCH response carrying a full message as BODY[]
+pub fn fetch_body_response(seq: u32, uid: u32, flags: &str, raw: &str) -> String {
+    format!(
+        "* {} FETCH (UID {} FLAGS ({}) BODY[] {{{}}}\r\n{})\r\n",
+        seq,
+        uid,
+        flags,
+        raw.len(),
+        raw
+    )
+}
@@ -1,8 +1,37 @@
+pub mod attachment_safety;
+pub mod attachments;
+pub mod auth_results;
+pub mod body_cache;
+pub mod calendar;
+pub mod compose;
+pub mod connection_check;
+pub mod discovery;
+pub mod error;
+pub mod headers;
 pub mod idle;
 pub mod imap_client;
+pub mod mailing_list;
+#[cfg(test)]
+pub mod mock_imap;
+pub mod outbox;
+pub mod pool;
 pub mod provider;
+pub mod quoting;
+pub mod rate_limit;
+pub mod remote_content;
+pub mod rules;
+pub mod search;
+pub mod security;
 pub mod server_presets;
+pub mod smtp;
+pub mod snooze;
+pub mod sort;
+pub mod special_folders;
+pub mod threading;
+pub mod transport;
 pub mod types;
+pub mod unified;
+pub mod utf7;
 
 pub use imap_client::ImapClient;
 pub use types::{Email, EmailListItem, Folder, SpecialFolder};
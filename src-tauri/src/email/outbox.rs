@@ -0,0 +1,171 @@
+//! Outgoing messages held back for a grace period so a send can be undone.
+//!
+//! Each queued send is a task that sleeps for the delay and then runs the send.
+//! Cancelling before the delay is up stops it; once the send has started it
+//! can no longer be cancelled. On shutdown the remaining sends go out at once.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use super::error::EmailError;
+
+/// Undo-send window unless configured otherwise
+pub const DEFAULT_UNDO_SEND_SECS: u64 = 10;
+
+/// How long shutdown waits for flushed sends to finish
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+enum Signal {
+    Cancel,
+    SendNow,
+}
+
+struct PendingSend {
+    signal: oneshot::Sender<Signal>,
+    task: JoinHandle<()>,
+}
+
+/// Sends waiting out their undo window, by pending ID
+#[derive(Clone, Default)]
+pub struct Outbox {
+    pending: Arc<Mutex<HashMap<String, PendingSend>>>,
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `send` after `delay` unless cancelled first; returns the pending ID.
+    /// `on_done` gets the ID and the send's result once it has run.
+    pub fn enqueue<T, S, D>(&self, delay: Duration, send: S, on_done: D) -> String
+    where
+        T: Send + 'static,
+        S: Future<Output = Result<T, EmailError>> + Send + 'static,
+        D: FnOnce(&str, Result<T, EmailError>) + Send + 'static,
+    {
+        let pending_id = uuid::Uuid::new_v4().to_string();
+        let (signal, mut signal_rx) = oneshot::channel();
+
+        // Hold the lock until the entry is in, so a send with no delay can't
+        // look for it first
+        let mut pending = self.pending.lock().unwrap();
+        let outbox = self.clone();
+        let id = pending_id.clone();
+        let task = tokio::spawn(async move {
+            let signal = tokio::select! {
+                _ = tokio::time::sleep(delay) => {
+                    // Claim the send; if cancel or flush got there first, the
+                    // signal they sent decides
+                    match outbox.pending.lock().unwrap().remove(&id) {
+                        Some(_) => Signal::SendNow,
+                        None => signal_rx.try_recv().unwrap_or(Signal::Cancel),
+                    }
+                }
+                signal = &mut signal_rx => signal.unwrap_or(Signal::Cancel),
+            };
+            if let Signal::Cancel = signal {
+                println!("[Outbox] Send {} cancelled", id);
+                return;
+            }
+
+            let result = send.await;
+            if let Err(e) = &result {
+                eprintln!("[Outbox] Send {} failed: {}", id, e);
+            }
+            on_done(&id, result);
+        });
+        pending.insert(pending_id.clone(), PendingSend { signal, task });
+
+        pending_id
+    }
+
+    /// Stop a send that hasn't started. False if it already went out (or is
+    /// going out) or the ID is unknown.
+    pub fn cancel(&self, pending_id: &str) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.remove(pending_id) {
+            Some(send) => send.signal.send(Signal::Cancel).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Number of sends still inside their undo window
+    pub fn pending_count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// Send everything still waiting right away and wait up to `timeout` for
+    /// it to finish. Returns how many sends didn't finish in time.
+    pub async fn flush(&self, timeout: Duration) -> usize {
+        let tasks: Vec<JoinHandle<()>> = {
+            let mut pending = self.pending.lock().unwrap();
+            pending
+                .drain()
+                .map(|(_, send)| {
+                    let _ = send.signal.send(Signal::SendNow);
+                    send.task
+                })
+                .collect()
+        };
+        if tasks.is_empty() {
+            return 0;
+        }
+
+        println!("[Outbox] Flushing {} pending send(s)", tasks.len());
+        let total = tasks.len();
+        let mut unfinished = total;
+        let wait_all = async {
+            for task in tasks {
+                let _ = task.await;
+                unfinished -= 1;
+            }
+        };
+        if tokio::time::timeout(timeout, wait_all).await.is_err() {
+            eprintln!("[Outbox] {} of {} send(s) didn't finish before shutdown", unfinished, total);
+        }
+        unfinished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_cancel_and_flush_pending_sends() {
+        let outbox = Outbox::new();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let send = || {
+            let sent = sent.clone();
+            async move {
+                sent.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        };
+
+        let cancelled = outbox.enqueue(Duration::from_secs(60), send(), |_, _| {});
+        let flushed = outbox.enqueue(Duration::from_secs(60), send(), |_, _| {});
+        assert_eq!(outbox.pending_count(), 2);
+        assert!(outbox.cancel(&cancelled));
+        assert!(!outbox.cancel(&cancelled));
+
+        assert_eq!(outbox.flush(Duration::from_secs(5)).await, 0);
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+        assert!(!outbox.cancel(&flushed));
+
+        // Without a delay the send goes out on its own
+        let (done_tx, done_rx) = oneshot::channel();
+        let id = outbox.enqueue(Duration::ZERO, send(), move |id, result| {
+            let _ = done_tx.send((id.to_string(), result.is_ok()));
+        });
+        assert_eq!(done_rx.await.unwrap(), (id, true));
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+        assert_eq!(outbox.pending_count(), 0);
+    }
+}
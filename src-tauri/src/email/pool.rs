@@ -0,0 +1,225 @@
+//! Per-account pool of IMAP connections.
+//!
+//! Every connection is an `ImapClient` with its own session, so commands for
+//! the same account run concurrently up to the pool size instead of queueing
+//! behind one client. Connections are opened lazily by the first command that
+//! uses them and logged out again after sitting unused.
+
+use futures::future::select_all;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OwnedMutexGuard;
+
+use super::imap_client::ImapClient;
+
+/// Connections kept per account unless configured otherwise
+pub const DEFAULT_POOL_SIZE: usize = 3;
+
+/// Connections unused for this long are logged out
+pub const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+struct Connection {
+    client: Arc<tokio::sync::Mutex<ImapClient>>,
+    last_used: Arc<Mutex<Instant>>,
+}
+
+impl Connection {
+    fn new(client: ImapClient) -> Self {
+        Self {
+            client: Arc::new(tokio::sync::Mutex::new(client)),
+            last_used: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    fn try_checkout(&self) -> Option<PooledClient> {
+        let guard = self.client.clone().try_lock_owned().ok()?;
+        Some(PooledClient::new(guard, self.last_used.clone()))
+    }
+}
+
+struct AccountPool {
+    /// Never connected; new connections are created from it
+    template: ImapClient,
+    connections: Vec<Connection>,
+}
+
+/// A connection checked out of the pool. Nobody else can use it until it is
+/// dropped, which hands it back.
+pub struct PooledClient {
+    guard: OwnedMutexGuard<ImapClient>,
+    last_used: Arc<Mutex<Instant>>,
+}
+
+impl PooledClient {
+    fn new(guard: OwnedMutexGuard<ImapClient>, last_used: Arc<Mutex<Instant>>) -> Self {
+        Self { guard, last_used }
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = ImapClient;
+
+    fn deref(&self) -> &ImapClient {
+        &self.guard
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut ImapClient {
+        &mut self.guard
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        *self.last_used.lock().unwrap() = Instant::now();
+    }
+}
+
+/// Up to `size` IMAP connections per account
+#[derive(Clone)]
+pub struct ConnectionPool {
+    size: usize,
+    accounts: Arc<Mutex<HashMap<String, AccountPool>>>,
+}
+
+impl ConnectionPool {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size: size.max(1),
+            accounts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Start a pool for the client's account with `client` as its first
+    /// connection, replacing any existing pool
+    pub fn insert(&self, client: ImapClient) {
+        let pool = AccountPool {
+            template: client.new_connection(),
+            connections: vec![Connection::new(client)],
+        };
+        let mut accounts = self.accounts.lock().unwrap();
+        accounts.insert(pool.template.account_id.clone(), pool);
+    }
+
+    /// Drop every connection of an account. Checked-out connections stay
+    /// usable but aren't handed out again.
+    pub fn remove(&self, account_id: &str) {
+        self.accounts.lock().unwrap().remove(account_id);
+    }
+
+    /// An unused connection, a new one while the pool has room, or else the
+    /// first connection released. None if the account has no pool.
+    pub async fn checkout(&self, account_id: &str) -> Option<PooledClient> {
+        let busy = {
+            let mut accounts = self.accounts.lock().unwrap();
+            let pool = accounts.get_mut(account_id)?;
+
+            if let Some(client) = pool.connections.iter().find_map(Connection::try_checkout) {
+                return Some(client);
+            }
+            if pool.connections.len() < self.size {
+                let connection = Connection::new(pool.template.new_connection());
+                let client = connection.try_checkout();
+                pool.connections.push(connection);
+                return client;
+            }
+
+            pool.connections
+                .iter()
+                .map(|connection| (connection.client.clone(), connection.last_used.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        let waits = busy.into_iter().map(|(client, last_used)| {
+            Box::pin(async move { PooledClient::new(client.lock_owned().await, last_used) })
+        });
+        let (client, _, _) = select_all(waits).await;
+        Some(client)
+    }
+
+    /// Log out connections that haven't been used for `max_idle`. The pool
+    /// itself stays, so the next command connects again.
+    pub async fn close_idle(&self, max_idle: Duration) -> usize {
+        let idle: Vec<PooledClient> = {
+            let mut accounts = self.accounts.lock().unwrap();
+            let mut idle = Vec::new();
+            for pool in accounts.values_mut() {
+                pool.connections.retain(|connection| {
+                    if connection.last_used.lock().unwrap().elapsed() < max_idle {
+                        return true;
+                    }
+                    match connection.try_checkout() {
+                        Some(client) => {
+                            idle.push(client);
+                            false
+                        }
+                        None => true,
+                    }
+                });
+            }
+            idle
+        };
+
+        let closed = idle.len();
+        for client in idle {
+            client.disconnect().await;
+        }
+        closed
+    }
+
+    /// Close idle connections every minute; spawned once at startup
+    pub async fn close_idle_periodically(self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let closed = self.close_idle(POOL_IDLE_TIMEOUT).await;
+            if closed > 0 {
+                println!("[IMAP] Closed {} idle pooled connection(s)", closed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::mock_imap::MockImap;
+
+    #[tokio::test]
+    async fn test_pool_runs_connections_concurrently_up_to_size() {
+        let mock = MockImap::new();
+        let pool = ConnectionPool::new(2);
+        pool.insert(mock.client("acct"));
+        assert!(pool.checkout("other").await.is_none());
+
+        let first = pool.checkout("acct").await.unwrap();
+        let second = pool.checkout("acct").await.unwrap();
+        first.reconnect().await.unwrap();
+        second.reconnect().await.unwrap();
+        assert_eq!(
+            mock.commands()
+                .iter()
+                .filter(|c| c.starts_with("LOGIN"))
+                .count(),
+            2
+        );
+
+        // Full: the third caller waits for a connection to come back
+        let third = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.checkout("acct").await.is_some() })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!third.is_finished());
+        drop(first);
+        assert!(third.await.unwrap());
+
+        drop(second);
+        assert_eq!(pool.close_idle(Duration::ZERO).await, 2);
+        assert!(mock.commands().iter().any(|c| c == "LOGOUT"));
+        assert!(pool.checkout("acct").await.is_some());
+    }
+}
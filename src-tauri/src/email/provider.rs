@@ -1,16 +1,20 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+use super::attachments::OutgoingAttachment;
+use super::threading::ReplyHeaders;
 use super::types::{Email, EmailListItem, Folder};
 
 /// IMAP flag types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ImapFlag {
     Seen,
     Flagged,
     Deleted,
     Answered,
     Draft,
+    /// A user keyword such as `$Important` or `Todo` (a Gmail label on Gmail)
+    Keyword(String),
 }
 
 impl ImapFlag {
@@ -21,8 +25,41 @@ impl ImapFlag {
             ImapFlag::Deleted => "\\Deleted",
             ImapFlag::Answered => "\\Answered",
             ImapFlag::Draft => "\\Draft",
+            ImapFlag::Keyword(keyword) => keyword,
         }
     }
+
+    /// Parse a flag from a FETCH response; None for `\Recent` and other
+    /// system flags that can't be set
+    pub fn from_imap_str(flag: &str) -> Option<Self> {
+        match flag.to_ascii_lowercase().as_str() {
+            "\\seen" => Some(ImapFlag::Seen),
+            "\\flagged" => Some(ImapFlag::Flagged),
+            "\\deleted" => Some(ImapFlag::Deleted),
+            "\\answered" => Some(ImapFlag::Answered),
+            "\\draft" => Some(ImapFlag::Draft),
+            _ if flag.starts_with('\\') => None,
+            _ => Some(ImapFlag::Keyword(flag.to_string())),
+        }
+    }
+
+    /// A keyword flag, if `keyword` is a valid IMAP atom
+    pub fn keyword(keyword: &str) -> Result<Self> {
+        if !is_valid_keyword(keyword) {
+            anyhow::bail!("Invalid IMAP keyword: {:?}", keyword);
+        }
+        Ok(ImapFlag::Keyword(keyword.to_string()))
+    }
+}
+
+/// RFC 3501 `flag-keyword`: a non-empty atom, so printable ASCII without
+/// spaces or any of `( ) { % * " \ ]`
+pub fn is_valid_keyword(keyword: &str) -> bool {
+    !keyword.is_empty()
+        && keyword.bytes().all(|b| {
+            b.is_ascii_graphic()
+                && !matches!(b, b'(' | b')' | b'{' | b'%' | b'*' | b'"' | b'\\' | b']')
+        })
 }
 
 /// Unified email provider trait — abstracts IMAP/SMTP operations
@@ -39,7 +76,9 @@ pub trait EmailProvider: Send + Sync {
     /// Get a single message by UID
     async fn get_message(&self, folder: &str, uid: u32) -> Result<Email>;
 
-    /// Send an email via SMTP
+    /// Send an email via SMTP; `reply` threads it under an earlier message.
+    /// With attachments the message is multipart/mixed. Returns the message
+    /// exactly as transmitted, Message-ID and Date included.
     async fn send_email(
         &self,
         from: &str,
@@ -49,7 +88,12 @@ pub trait EmailProvider: Send + Sync {
         subject: &str,
         body_html: &str,
         body_plain: &str,
-    ) -> Result<()>;
+        reply: &ReplyHeaders,
+        attachments: &[OutgoingAttachment],
+    ) -> Result<Vec<u8>>;
+
+    /// Every flag and keyword set on a message (Gmail labels included)
+    async fn get_flags(&self, folder: &str, uid: u32) -> Result<Vec<ImapFlag>>;
 
     /// Set or remove flags on a message
     async fn set_flags(&self, folder: &str, uid: u32, flags: &[ImapFlag], add: bool)
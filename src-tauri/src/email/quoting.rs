@@ -0,0 +1,185 @@
+/// Markers that start a forwarded/replied-to block in HTML bodies
+const HTML_QUOTE_MARKERS: &[&str] = &[
+    "<div class=\"gmail_quote",
+    "<blockquote",
+    "<div id=\"appendonsend",
+    "<div id=\"divRplyFwdMsg",
+];
+
+/// Return only the newest part of a reply: drops `>`-quoted lines and
+/// everything from the first reply attribution or separator onwards
+/// ("On ... wrote:", "-----Original Message-----", Outlook "From:/Sent:" headers).
+/// Falls back to the full body when stripping would leave nothing.
+pub fn strip_quoted_text(body: &str) -> String {
+    let latest = if looks_like_html(body) {
+        strip_html_quote(body)
+    } else {
+        strip_plain_quote(body)
+    };
+
+    if latest.trim().is_empty() {
+        body.trim().to_string()
+    } else {
+        latest
+    }
+}
+
+fn looks_like_html(body: &str) -> bool {
+    let lower = body.to_lowercase();
+    lower.contains("<html")
+        || lower.contains("<div")
+        || lower.contains("<p>")
+        || lower.contains("<br")
+}
+
+fn strip_html_quote(body: &str) -> String {
+    let cut = html_quote_start(body).unwrap_or(body.len());
+    body[..cut].trim().to_string()
+}
+
+/// Byte offset of the first quoted/forwarded block in an HTML body
+pub fn html_quote_start(body: &str) -> Option<usize> {
+    // ASCII lowercasing keeps byte offsets valid for slicing `body`
+    let lower = body.to_ascii_lowercase();
+    HTML_QUOTE_MARKERS
+        .iter()
+        .filter_map(|marker| lower.find(&marker.to_ascii_lowercase()))
+        .min()
+}
+
+/// Byte offset where the quoted part of a plain text reply starts: the
+/// attribution or separator line, or a `>`-quoted block that runs to the
+/// end. Inline replies between quoted lines don't count.
+pub fn plain_quote_start(body: &str) -> Option<usize> {
+    let lines: Vec<&str> = body.split('\n').collect();
+    let mut offset = 0;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if is_separator(trimmed)
+            || is_attribution(trimmed, lines.get(i + 1).copied())
+            || is_outlook_header(trimmed, &lines[i + 1..])
+        {
+            return Some(offset);
+        }
+        if trimmed.starts_with('>')
+            && lines[i..]
+                .iter()
+                .all(|l| l.trim().is_empty() || l.trim().starts_with('>'))
+        {
+            return Some(offset);
+        }
+        offset += line.len() + 1;
+    }
+    None
+}
+
+fn strip_plain_quote(body: &str) -> String {
+    let lines: Vec<&str> = body.lines().collect();
+    let mut kept = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if is_separator(trimmed) || is_attribution(trimmed, lines.get(i + 1).copied()) {
+            break;
+        }
+        if is_outlook_header(trimmed, &lines[i + 1..]) {
+            break;
+        }
+        if trimmed.starts_with('>') {
+            continue;
+        }
+        kept.push(*line);
+    }
+
+    kept.join("\n").trim().to_string()
+}
+
+fn is_separator(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    (lower.starts_with("-----")
+        && (lower.contains("original message") || lower.contains("forwarded message")))
+        || (line.len() >= 10 && line.chars().all(|c| c == '_'))
+}
+
+/// "On <date>, <name> wrote:", possibly wrapped over two lines
+fn is_attribution(line: &str, next: Option<&str>) -> bool {
+    if !line.starts_with("On ") {
+        return false;
+    }
+    if line.ends_with("wrote:") {
+        return true;
+    }
+    next.map(|n| n.trim().ends_with("wrote:")).unwrap_or(false)
+}
+
+/// Outlook-style "From: ..." header block followed by "Sent:"/"Date:" within a few lines
+fn is_outlook_header(line: &str, rest: &[&str]) -> bool {
+    if !line.starts_with("From:") {
+        return false;
+    }
+    rest.iter()
+        .take(3)
+        .any(|l| l.trim().starts_with("Sent:") || l.trim().starts_with("Date:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_plain_reply() {
+        let body = "Sounds good, ship it.\n\nOn Tue, 3 Mar 2026 at 10:00, Ana <ana@example.com> wrote:\n> Can we release today?\n> Thanks";
+        assert_eq!(strip_quoted_text(body), "Sounds good, ship it.");
+
+        let outlook =
+            "Approved.\n\nFrom: Ben\nSent: Monday\nTo: Team\nSubject: Budget\n\nPlease approve.";
+        assert_eq!(strip_quoted_text(outlook), "Approved.");
+
+        let inline = "See below.\n> old line\nMy answer inline.";
+        assert_eq!(strip_quoted_text(inline), "See below.\nMy answer inline.");
+    }
+
+    #[test]
+    fn test_strip_gmail_outlook_and_plain_quoting() {
+        // Gmail wraps a long attribution line
+        let gmail = "Works for me.\n\nOn Tue, Mar 3, 2026 at 10:00 AM Ana Smith <\nana@example.com> wrote:\n> Friday?";
+        assert_eq!(strip_quoted_text(gmail), "Works for me.");
+        let gmail_html = "<div dir=\"ltr\">Works for me.</div><br><div class=\"gmail_quote\"><div dir=\"ltr\" class=\"gmail_attr\">On Tue wrote:</div><blockquote>Friday?</blockquote></div>";
+        assert_eq!(
+            strip_quoted_text(gmail_html),
+            "<div dir=\"ltr\">Works for me.</div><br>"
+        );
+
+        let outlook =
+            "Done.\n\n-----Original Message-----\nFrom: Ben\nSent: Monday\nCan you file it?";
+        assert_eq!(strip_quoted_text(outlook), "Done.");
+        let outlook_html = "<p>Done.</p><div id=\"divRplyFwdMsg\"><b>From:</b> Ben</div><div>Can you file it?</div>";
+        assert_eq!(strip_quoted_text(outlook_html), "<p>Done.</p>");
+
+        let plain = "Agreed.\n\n> > Ship Friday?\n> Yes, Friday.\n>";
+        assert_eq!(strip_quoted_text(plain), "Agreed.");
+    }
+
+    #[test]
+    fn test_strip_keeps_body_when_everything_is_quoted() {
+        let body = "> only quoted\n> text";
+        assert_eq!(strip_quoted_text(body), body);
+
+        let html = "<div>Yes.</div><div class=\"gmail_quote\">On Mon wrote: old</div>";
+        assert_eq!(strip_quoted_text(html), "<div>Yes.</div>");
+    }
+
+    #[test]
+    fn test_quote_start() {
+        let body = "Thanks!\n\nOn Mon, Ana wrote:\n> Hi";
+        assert_eq!(plain_quote_start(body), body.find("On Mon"));
+
+        let inline = "See below.\n> old line\nMy answer.\n\n> trailing\n> quote\n";
+        assert_eq!(plain_quote_start(inline), inline.find("> trailing"));
+        assert_eq!(plain_quote_start("No quote here"), None);
+
+        let html = "<p>Yes</p><BLOCKQUOTE>old</BLOCKQUOTE>";
+        assert_eq!(html_quote_start(html), Some(10));
+    }
+}
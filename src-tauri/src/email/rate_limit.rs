@@ -0,0 +1,262 @@
+use anyhow::Result;
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::sync::Semaphore;
+use tokio::time::{sleep, Duration, Instant};
+
+use super::server_presets::ProviderType;
+
+/// First wait after the server reports a rate limit; doubles per strike
+const THROTTLE_BASE: Duration = Duration::from_secs(5);
+const THROTTLE_MAX: Duration = Duration::from_secs(5 * 60);
+
+/// Rate-limited attempts before the error is passed on to the caller
+const MAX_THROTTLED_RETRIES: u32 = 5;
+
+/// How hard an `ImapClient` may drive the server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimits {
+    /// Commands per second allowed on average
+    pub requests_per_sec: f64,
+    /// Commands that may go out back to back before the rate applies
+    pub burst: u32,
+    /// Fetches in flight at once across the account's connections
+    pub max_concurrent_fetches: usize,
+}
+
+impl RateLimits {
+    /// Defaults that stay under each provider's published limits
+    pub fn for_provider(provider: &ProviderType) -> Self {
+        let (requests_per_sec, burst, max_concurrent_fetches) = match provider {
+            ProviderType::Gmail => (10.0, 20, 4),
+            ProviderType::Outlook => (5.0, 10, 2),
+            ProviderType::Yahoo => (5.0, 10, 2),
+            ProviderType::Custom => (20.0, 40, 4),
+        };
+        Self {
+            requests_per_sec,
+            burst,
+            max_concurrent_fetches,
+        }
+    }
+
+    /// These limits with any of the given values put in their place
+    pub fn with_overrides(
+        self,
+        requests_per_sec: Option<f64>,
+        burst: Option<u32>,
+        max_concurrent_fetches: Option<usize>,
+    ) -> Self {
+        Self {
+            requests_per_sec: requests_per_sec.unwrap_or(self.requests_per_sec),
+            burst: burst.unwrap_or(self.burst),
+            max_concurrent_fetches: max_concurrent_fetches.unwrap_or(self.max_concurrent_fetches),
+        }
+    }
+}
+
+/// Token bucket refilled at `requests_per_sec`, holding at most `burst`
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// Nothing goes out before this after the server pushed back
+    throttled_until: Option<Instant>,
+    /// Rate-limit responses in a row, for the backoff
+    strikes: u32,
+}
+
+impl Bucket {
+    fn new(limits: &RateLimits, now: Instant) -> Self {
+        Self {
+            tokens: limits.burst.max(1) as f64,
+            updated: now,
+            throttled_until: None,
+            strikes: 0,
+        }
+    }
+
+    /// Take a token, or return how long to wait before trying again
+    fn take(&mut self, limits: &RateLimits, now: Instant) -> Option<Duration> {
+        if let Some(until) = self.throttled_until {
+            if until > now {
+                return Some(until - now);
+            }
+            self.throttled_until = None;
+        }
+
+        let rate = limits.requests_per_sec.max(0.01);
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(limits.burst.max(1) as f64);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+
+    /// Hold everything back after a rate-limit response, longer each time
+    fn throttle(&mut self, now: Instant) -> Duration {
+        self.strikes += 1;
+        let delay = THROTTLE_BASE
+            .saturating_mul(1u32 << (self.strikes - 1).min(16))
+            .min(THROTTLE_MAX);
+        self.throttled_until = Some(now + delay);
+        self.tokens = 0.0;
+        delay
+    }
+}
+
+/// Paces an account's commands and caps its concurrent fetches. Shared by
+/// every connection to the account, so a pool can't multiply the rate.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    bucket: Mutex<Bucket>,
+    fetches: Semaphore,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            bucket: Mutex::new(Bucket::new(&limits, Instant::now())),
+            fetches: Semaphore::new(limits.max_concurrent_fetches.max(1)),
+            limits,
+        }
+    }
+
+    pub fn limits(&self) -> RateLimits {
+        self.limits
+    }
+
+    /// Wait for a token, queueing behind a server-imposed backoff if any
+    async fn wait_turn(&self) {
+        loop {
+            let wait = self
+                .bucket
+                .lock()
+                .unwrap()
+                .take(&self.limits, Instant::now());
+            match wait {
+                Some(wait) => sleep(wait).await,
+                None => return,
+            }
+        }
+    }
+
+    /// Run `op` once it's this caller's turn. A rate-limit response backs
+    /// the whole account off and the operation is retried, so callers see a
+    /// delay instead of an error.
+    pub async fn run<T, F, Fut>(&self, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempts = 0;
+        loop {
+            let _permit = self.fetches.acquire().await;
+            self.wait_turn().await;
+
+            let result = op().await;
+            match result {
+                Err(e) if is_rate_limited(&e) && attempts < MAX_THROTTLED_RETRIES => {
+                    attempts += 1;
+                    let delay = self.bucket.lock().unwrap().throttle(Instant::now());
+                    eprintln!(
+                        "[RateLimit] Server is throttling, backing off {}s: {:#}",
+                        delay.as_secs(),
+                        e
+                    );
+                }
+                Ok(value) => {
+                    self.bucket.lock().unwrap().strikes = 0;
+                    return Ok(value);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Whether the server refused a command for going over its limits: the
+/// RFC 5530 `[LIMIT]` code, or Gmail's bandwidth message. `[OVERQUOTA]` is a
+/// full mailbox, which waiting doesn't fix.
+pub fn is_rate_limited(err: &anyhow::Error) -> bool {
+    use async_imap::error::Error;
+
+    err.chain()
+        .any(|cause| match cause.downcast_ref::<Error>() {
+            Some(Error::No(text) | Error::Bad(text)) => {
+                let text = text.to_ascii_uppercase();
+                text.contains("[LIMIT]")
+                    || text.contains("[THROTTLED]")
+                    || text.contains("EXCEEDED COMMAND OR BANDWIDTH LIMITS")
+            }
+            _ => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_bucket_paces_and_backs_off() {
+        let limits = RateLimits {
+            requests_per_sec: 2.0,
+            burst: 2,
+            max_concurrent_fetches: 1,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket::new(&limits, start);
+
+        // The burst goes straight out, then one token every half second
+        assert_eq!(bucket.take(&limits, start), None);
+        assert_eq!(bucket.take(&limits, start), None);
+        assert_eq!(
+            bucket.take(&limits, start),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            bucket.take(&limits, start + Duration::from_millis(500)),
+            None
+        );
+
+        // A throttle holds everything back, and doubles on the next strike
+        let now = start + Duration::from_secs(1);
+        assert_eq!(bucket.throttle(now), THROTTLE_BASE);
+        assert_eq!(bucket.take(&limits, now), Some(THROTTLE_BASE));
+        assert_eq!(bucket.throttle(now), THROTTLE_BASE * 2);
+        let later = now + THROTTLE_BASE * 2 + Duration::from_secs(1);
+        assert_eq!(bucket.take(&limits, later), None);
+
+        let limited = Err::<(), _>(async_imap::error::Error::No(
+            "[LIMIT] Too many commands".to_string(),
+        ))
+        .context("Failed to fetch messages")
+        .unwrap_err();
+        assert!(is_rate_limited(&limited));
+        let missing = Err::<(), _>(async_imap::error::Error::No(
+            "[NONEXISTENT] No such mailbox".to_string(),
+        ))
+        .context("Failed to select folder")
+        .unwrap_err();
+        assert!(!is_rate_limited(&missing));
+        let full = Err::<(), _>(async_imap::error::Error::No(
+            "[OVERQUOTA] Mailbox is full".to_string(),
+        ))
+        .context("Failed to append message")
+        .unwrap_err();
+        assert!(!is_rate_limited(&full));
+
+        let gmail = RateLimits::for_provider(&ProviderType::Gmail);
+        let tuned = gmail.with_overrides(Some(2.5), None, Some(1));
+        assert_eq!(tuned.requests_per_sec, 2.5);
+        assert_eq!(tuned.burst, gmail.burst);
+        assert_eq!(tuned.max_concurrent_fetches, 1);
+    }
+}
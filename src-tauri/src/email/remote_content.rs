@@ -0,0 +1,490 @@
+/// Shown in place of a blocked remote image: a transparent 1x1 GIF
+pub const BLOCKED_IMAGE_PLACEHOLDER: &str =
+    "data:image/gif;base64,R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7";
+
+/// Attributes the browser loads a URL from without the user clicking
+const URL_ATTRIBUTES: &[&str] = &["src", "srcset", "background", "poster"];
+
+/// Replace remote URLs in `src`/`background`-style attributes, `<link>`
+/// hrefs and CSS (`style` attributes and `<style>` blocks) with a
+/// placeholder, so opening a message doesn't load tracking pixels. `cid:`
+/// and `data:` URLs are kept. Returns the rewritten HTML and whether
+/// anything was blocked.
+pub fn block_remote_content(html: &str) -> (String, bool) {
+    let mut out = String::with_capacity(html.len());
+    let mut blocked = false;
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let tag = &rest[start..];
+
+        if tag.starts_with("<!--") {
+            // Comments (including Outlook's conditional ones) are copied as is
+            let end = tag.find("-->").map(|i| i + 3).unwrap_or(tag.len());
+            out.push_str(&tag[..end]);
+            rest = &tag[end..];
+            continue;
+        }
+        if !tag[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            // A stray '<' in text
+            out.push('<');
+            rest = &tag[1..];
+            continue;
+        }
+
+        let end = tag_end(tag);
+        let (rewritten, changed) = rewrite_tag(&tag[..end]);
+        out.push_str(&rewritten);
+        blocked |= changed;
+        rest = &tag[end..];
+
+        if tag_name(tag).eq_ignore_ascii_case("style") {
+            // The stylesheet is text up to the closing tag, not markup
+            let close = rest
+                .to_ascii_lowercase()
+                .find("</style")
+                .unwrap_or(rest.len());
+            let (css, changed) = block_css(&rest[..close]);
+            out.push_str(&css);
+            blocked |= changed;
+            rest = &rest[close..];
+        }
+    }
+    out.push_str(rest);
+    (out, blocked)
+}
+
+/// Byte offset just past the '>' closing the tag, ignoring '>' in quoted values
+fn tag_end(tag: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    tag.len()
+}
+
+/// The element name of a start tag
+fn tag_name(tag: &str) -> &str {
+    let end = tag[1..]
+        .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        .map_or(tag.len(), |i| i + 1);
+    &tag[1..end]
+}
+
+/// Rewrite the URL attributes and inline styles of one start tag
+fn rewrite_tag(tag: &str) -> (String, bool) {
+    let bytes = tag.as_bytes();
+    let mut out = String::with_capacity(tag.len());
+    let mut blocked = false;
+    let element = tag_name(tag);
+    // Skip '<' and the tag name
+    let mut i = 1 + element.len();
+    let mut copied = 0;
+
+    while i < bytes.len() {
+        if bytes[i].is_ascii_whitespace() || bytes[i] == b'/' {
+            i += 1;
+            continue;
+        }
+        if bytes[i] == b'>' {
+            break;
+        }
+
+        let name_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"=/>".contains(&bytes[i]) {
+            i += 1;
+        }
+        let name = &tag[name_start..i];
+
+        let mut j = i;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+        if j >= bytes.len() || bytes[j] != b'=' {
+            // Attribute without a value
+            continue;
+        }
+        j += 1;
+        while j < bytes.len() && bytes[j].is_ascii_whitespace() {
+            j += 1;
+        }
+
+        let value_start = j;
+        let (text_start, text_end, value_end) = match bytes.get(j) {
+            Some(&q @ (b'"' | b'\'')) => {
+                let close = tag[j + 1..]
+                    .find(q as char)
+                    .map(|k| j + 1 + k)
+                    .unwrap_or(tag.len());
+                (j + 1, close, (close + 1).min(tag.len()))
+            }
+            _ => {
+                while j < bytes.len() && !bytes[j].is_ascii_whitespace() && bytes[j] != b'>' {
+                    j += 1;
+                }
+                (value_start, j, j)
+            }
+        };
+        let value = &tag[text_start..text_end];
+
+        // Any <link> href is fetched (stylesheets, icons, preloads)
+        let is_url_attribute = URL_ATTRIBUTES
+            .iter()
+            .any(|attr| attr.eq_ignore_ascii_case(name))
+            || (element.eq_ignore_ascii_case("link") && name.eq_ignore_ascii_case("href"));
+        if is_url_attribute && is_remote(value) {
+            out.push_str(&tag[copied..value_start]);
+            out.push('"');
+            out.push_str(BLOCKED_IMAGE_PLACEHOLDER);
+            out.push('"');
+            copied = value_end;
+            blocked = true;
+        } else if name.eq_ignore_ascii_case("style") {
+            // The browser decodes character references before parsing the CSS
+            let (css, changed) = block_css(&decode_char_refs(value));
+            if changed {
+                out.push_str(&tag[copied..value_start]);
+                out.push('"');
+                out.push_str(&css.replace('&', "&amp;").replace('"', "&quot;"));
+                out.push('"');
+                copied = value_end;
+                blocked = true;
+            }
+        }
+        i = value_end;
+    }
+
+    out.push_str(&tag[copied..]);
+    (out, blocked)
+}
+
+/// Replace remote `url(...)` references in CSS with the placeholder and drop
+/// `@import` rules that load a remote stylesheet. Function and rule names
+/// are compared with CSS escapes decoded, so `\75 rl(` is caught too.
+fn block_css(css: &str) -> (String, bool) {
+    let bytes = css.as_bytes();
+    let mut out = String::with_capacity(css.len());
+    let mut blocked = false;
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let at_rule = bytes[i] == b'@';
+        let (name, name_end) = css_ident(css, if at_rule { i + 1 } else { i });
+        if name.is_empty() {
+            i += 1;
+            continue;
+        }
+
+        if !at_rule && name.eq_ignore_ascii_case("url") && bytes.get(name_end) == Some(&b'(') {
+            let (url, len) = css_url(&css[name_end + 1..]);
+            let end = name_end + 1 + len;
+            if is_remote(url) {
+                out.push_str(&css[copied..i]);
+                out.push_str("url(");
+                out.push_str(BLOCKED_IMAGE_PLACEHOLDER);
+                out.push(')');
+                copied = end;
+                blocked = true;
+            }
+            i = end;
+        } else if at_rule && name.eq_ignore_ascii_case("import") {
+            let end = css[name_end..]
+                .find(';')
+                .map_or(css.len(), |k| name_end + k + 1);
+            let rule = &css[name_end..end];
+            let target = name_end + (rule.len() - rule.trim_start().len());
+            let url = match bytes.get(target) {
+                Some(b'"' | b'\'') => Some(css_url(&css[target..]).0),
+                _ => {
+                    let (function, function_end) = css_ident(css, target);
+                    (function.eq_ignore_ascii_case("url") && bytes.get(function_end) == Some(&b'('))
+                        .then(|| css_url(&css[function_end + 1..]).0)
+                }
+            };
+            if url.is_none_or(is_remote) {
+                out.push_str(&css[copied..i]);
+                copied = end;
+                blocked = true;
+            }
+            i = end;
+        } else {
+            i = name_end;
+        }
+    }
+
+    out.push_str(&css[copied..]);
+    (out, blocked)
+}
+
+/// The identifier starting at byte `start` with CSS escapes (`\75 `, `\u`)
+/// decoded, and the offset just past it. Empty when none starts there.
+fn css_ident(css: &str, start: usize) -> (String, usize) {
+    let mut name = String::new();
+    let mut chars = css[start..].char_indices().peekable();
+
+    while let Some(&(k, c)) = chars.peek() {
+        if c == '\\' {
+            chars.next();
+            let mut hex = String::new();
+            while let Some(&(_, h)) = chars.peek() {
+                if hex.len() == 6 || !h.is_ascii_hexdigit() {
+                    break;
+                }
+                hex.push(h);
+                chars.next();
+            }
+            if hex.is_empty() {
+                match chars.next() {
+                    Some((_, escaped)) => name.push(escaped),
+                    None => return (name, css.len()),
+                }
+            } else {
+                let code = u32::from_str_radix(&hex, 16).unwrap_or(0);
+                name.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                // One whitespace character ends a hex escape
+                chars.next_if(|&(_, w)| w.is_ascii_whitespace());
+            }
+        } else if c.is_ascii_alphanumeric() || c == '-' || c == '_' || !c.is_ascii() {
+            name.push(c);
+            chars.next();
+        } else {
+            return (name, start + k);
+        }
+    }
+    (name, css.len())
+}
+
+/// Decode the character references of an attribute value (`&#117;`,
+/// `&#x75;`, `&lpar;`); unknown ones are kept as they are
+fn decode_char_refs(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp + 1..];
+
+        if let Some(number) = rest.strip_prefix('#') {
+            let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => (hex, 16),
+                None => (number, 10),
+            };
+            let len = digits
+                .find(|c: char| !c.is_digit(radix))
+                .unwrap_or(digits.len());
+            if len > 0 {
+                let code = u32::from_str_radix(&digits[..len], radix).unwrap_or(0);
+                out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                // The ';' is optional for numeric references
+                let after = &digits[len..];
+                rest = after.strip_prefix(';').unwrap_or(after);
+                continue;
+            }
+        } else if let Some(semi) = rest.find(';').filter(|&semi| semi <= 8) {
+            if let Some(c) = named_char_ref(&rest[..semi]) {
+                out.push(c);
+                rest = &rest[semi + 1..];
+                continue;
+            }
+        }
+        out.push('&');
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Named references for the characters CSS syntax is built from
+fn named_char_ref(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "lpar" => '(',
+        "rpar" => ')',
+        "colon" => ':',
+        "semi" => ';',
+        "sol" => '/',
+        "bsol" => '\\',
+        "commat" => '@',
+        "period" => '.',
+        "comma" => ',',
+        "num" => '#',
+        "equals" => '=',
+        "lowbar" => '_',
+        "Tab" => '\t',
+        "NewLine" => '\n',
+        "nbsp" => '\u{a0}',
+        _ => return None,
+    })
+}
+
+/// The URL at the start of `css` (just past `url(`, or an `@import` string)
+/// and the length up to and including the closing ')'
+fn css_url(css: &str) -> (&str, usize) {
+    let start = css.len() - css.trim_start().len();
+    let rest = &css[start..];
+    let (url, after) = match rest.chars().next() {
+        Some(q @ ('"' | '\'')) => {
+            let close = rest[1..].find(q).map_or(rest.len(), |k| k + 1);
+            (&rest[1..close], (close + 1).min(rest.len()))
+        }
+        _ => {
+            let close = rest.find(')').unwrap_or(rest.len());
+            (&rest[..close], close)
+        }
+    };
+    let end = rest[after..]
+        .find(')')
+        .map_or(rest.len(), |k| after + k + 1);
+    (url, start + end)
+}
+
+/// Anything but an embedded (`cid:`/`data:`) or empty URL counts as remote,
+/// so entity-encoded or scheme-relative URLs are blocked too
+fn is_remote(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    !(url.is_empty() || url.starts_with("cid:") || url.starts_with("data:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_remote_urls_and_keeps_embedded_ones() {
+        let html = "<p>Hi a < b</p><img width=1 SRC=\"https://t.example/p.gif?id=1\">\
+                    <img src='cid:logo@acme' alt=\"x > y\"><td background=//cdn.example/bg.png>\
+                    <!-- <img src=\"http://old\"> --><img src=\"data:image/png;base64,AA==\"/>";
+        let (blocked_html, blocked) = block_remote_content(html);
+
+        assert!(blocked);
+        assert_eq!(
+            blocked_html,
+            format!(
+                "<p>Hi a < b</p><img width=1 SRC=\"{0}\">\
+                 <img src='cid:logo@acme' alt=\"x > y\"><td background=\"{0}\">\
+                 <!-- <img src=\"http://old\"> --><img src=\"data:image/png;base64,AA==\"/>",
+                BLOCKED_IMAGE_PLACEHOLDER
+            )
+        );
+
+        let embedded = "<img src=\"cid:a\"><a href=\"https://example.com\">link</a>";
+        assert_eq!(
+            block_remote_content(embedded),
+            (embedded.to_string(), false)
+        );
+    }
+
+    #[test]
+    fn test_blocks_remote_urls_in_inline_styles() {
+        let html = "<div style=\"color:red; background:URL( 'https://t.example/bg.png' )\">\
+                    <td style='font-family:\"Arial\"; background:url(cid:bg)'>";
+        assert_eq!(
+            block_remote_content(html),
+            (
+                format!(
+                    "<div style=\"color:red; background:url({})\">\
+                     <td style='font-family:\"Arial\"; background:url(cid:bg)'>",
+                    BLOCKED_IMAGE_PLACEHOLDER
+                ),
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn test_blocks_remote_stylesheets() {
+        let (html, blocked) = block_remote_content(
+            "<style type=\"text/css\">.x { background: url(//t.example/x.png) }\
+             .y { background: url(\"data:image/png;base64,AA==\") }</style><p>a < b</p>",
+        );
+        assert!(blocked);
+        assert_eq!(
+            html,
+            format!(
+                "<style type=\"text/css\">.x {{ background: url({}) }}\
+                 .y {{ background: url(\"data:image/png;base64,AA==\") }}</style><p>a < b</p>",
+                BLOCKED_IMAGE_PLACEHOLDER
+            )
+        );
+
+        let (html, blocked) = block_remote_content(
+            "<STYLE>@import url(\"https://t.example/a.css\");\
+             @IMPORT 'http://t.example/b.css' screen;p { margin: 0 }</STYLE>",
+        );
+        assert!(blocked);
+        assert_eq!(html, "<STYLE>p { margin: 0 }</STYLE>");
+
+        let (html, blocked) = block_remote_content(
+            "<link rel=\"stylesheet\" href=\"https://t.example/s.css\">\
+             <a href=\"https://example.com\">link</a>",
+        );
+        assert!(blocked);
+        assert_eq!(
+            html,
+            format!(
+                "<link rel=\"stylesheet\" href=\"{}\"><a href=\"https://example.com\">link</a>",
+                BLOCKED_IMAGE_PLACEHOLDER
+            )
+        );
+    }
+
+    #[test]
+    fn test_blocks_encoded_and_escaped_urls() {
+        // Character references are decoded before the CSS is parsed
+        for style in [
+            "background:&#117;rl(https://t.example/p.gif)",
+            "background:&#x75;rl&lpar;https://t.example/p.gif)",
+            "background:u&#114l(&quot;https://t.example/p.gif&quot;)",
+        ] {
+            let html = format!("<div style=\"{}\">", style);
+            assert_eq!(
+                block_remote_content(&html),
+                (
+                    format!(
+                        "<div style=\"background:url({})\">",
+                        BLOCKED_IMAGE_PLACEHOLDER
+                    ),
+                    true
+                ),
+                "{}",
+                style
+            );
+        }
+        // Rewritten values are re-encoded for the attribute
+        let (html, _) = block_remote_content(
+            "<p style='font-family:\"A&amp;B\";background:url(//t.example/p.gif)'>",
+        );
+        assert_eq!(
+            html,
+            format!(
+                "<p style=\"font-family:&quot;A&amp;B&quot;;background:url({})\">",
+                BLOCKED_IMAGE_PLACEHOLDER
+            )
+        );
+
+        // CSS escapes in function and rule names
+        let (html, blocked) = block_remote_content(
+            "<style>@\\69mport \"https://t.example/a.css\";\
+             .x { background: \\75 rl(https://t.example/x.png) }\
+             .y { background: U\\52L(https://t.example/y.png) }</style>",
+        );
+        assert!(blocked);
+        assert_eq!(
+            html,
+            format!(
+                "<style>.x {{ background: url({0}) }}.y {{ background: url({0}) }}</style>",
+                BLOCKED_IMAGE_PLACEHOLDER
+            )
+        );
+    }
+}
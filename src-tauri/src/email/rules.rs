@@ -0,0 +1,317 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+use super::imap_client::ImapClient;
+use super::provider::{EmailProvider, ImapFlag};
+use crate::commands::email::map_folder_name;
+use crate::db::EmailDatabase;
+
+/// A filter applied to mail arriving in the inbox. A rule matches when all
+/// of its conditions do; rules are tried in the order they were added.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Rule {
+    /// Assigned when the rule is stored
+    #[serde(default)]
+    pub id: i64,
+    pub name: String,
+    /// Only apply to this account; all accounts when None
+    #[serde(default)]
+    pub account_id: Option<String>,
+    pub conditions: Vec<RuleCondition>,
+    pub actions: Vec<RuleAction>,
+    /// Skip the rules after this one once it matches
+    #[serde(default = "default_stop_processing")]
+    pub stop_processing: bool,
+}
+
+fn default_stop_processing() -> bool {
+    true
+}
+
+/// Case-insensitive substring tests on the envelope
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "field", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// Sender name or address
+    From {
+        contains: String,
+    },
+    /// Any To or Cc recipient
+    To {
+        contains: String,
+    },
+    Subject {
+        contains: String,
+    },
+    HasAttachment,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Move to a folder (special folder aliases like "archive" are resolved)
+    Move {
+        folder: String,
+    },
+    /// Star the message
+    Flag,
+    MarkRead,
+    /// Use this category instead of classifying the message
+    Categorize {
+        category: String,
+    },
+}
+
+/// What rules are matched against: one newly arrived message
+#[derive(Debug, Clone, Default)]
+pub struct RuleMessage {
+    pub uid: u32,
+    pub message_id: String,
+    pub from: String,
+    /// To and Cc recipients
+    pub to: Vec<String>,
+    pub subject: String,
+    pub has_attachment: bool,
+}
+
+impl Rule {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("Rule name is required");
+        }
+        if self.conditions.is_empty() || self.actions.is_empty() {
+            bail!("A rule needs at least one condition and one action");
+        }
+        for condition in &self.conditions {
+            match condition {
+                RuleCondition::From { contains }
+                | RuleCondition::To { contains }
+                | RuleCondition::Subject { contains }
+                    if contains.trim().is_empty() =>
+                {
+                    bail!("Rule conditions need text to match");
+                }
+                _ => {}
+            }
+        }
+        let mut moves = 0;
+        for action in &self.actions {
+            match action {
+                RuleAction::Move { folder } if folder.trim().is_empty() => {
+                    bail!("Move needs a folder")
+                }
+                RuleAction::Move { .. } => moves += 1,
+                RuleAction::Categorize { category } if category.trim().is_empty() => {
+                    bail!("Categorize needs a category")
+                }
+                _ => {}
+            }
+        }
+        if moves > 1 {
+            bail!("A rule can move a message to only one folder");
+        }
+        Ok(())
+    }
+
+    /// Whether every condition holds, stopping at the first that doesn't
+    pub fn matches(&self, message: &RuleMessage) -> bool {
+        self.conditions.iter().all(|condition| match condition {
+            RuleCondition::From { contains } => contains_ignore_case(&message.from, contains),
+            RuleCondition::To { contains } => message
+                .to
+                .iter()
+                .any(|to| contains_ignore_case(to, contains)),
+            RuleCondition::Subject { contains } => contains_ignore_case(&message.subject, contains),
+            RuleCondition::HasAttachment => message.has_attachment,
+        })
+    }
+
+    fn moves(&self) -> bool {
+        self.actions
+            .iter()
+            .any(|action| matches!(action, RuleAction::Move { .. }))
+    }
+}
+
+fn contains_ignore_case(value: &str, needle: &str) -> bool {
+    value.to_lowercase().contains(&needle.trim().to_lowercase())
+}
+
+/// The rules that fire for `message`, in order. Evaluation stops after a
+/// matching rule with `stop_processing`, or one that moves the message.
+pub fn matching_rules<'a>(rules: &'a [Rule], message: &RuleMessage) -> Vec<&'a Rule> {
+    let mut fired = Vec::new();
+    for rule in rules {
+        if !rule.matches(message) {
+            continue;
+        }
+        fired.push(rule);
+        if rule.stop_processing || rule.moves() {
+            break;
+        }
+    }
+    fired
+}
+
+/// Run the account's rules over newly arrived messages in `folder` and
+/// return the UIDs that were moved out of it
+pub async fn apply_rules(
+    client: &ImapClient,
+    db: &Mutex<Option<EmailDatabase>>,
+    folder: &str,
+    uids: &[u32],
+) -> Result<Vec<u32>> {
+    let rules = {
+        let db_lock = db.lock().unwrap();
+        match db_lock.as_ref() {
+            Some(database) => database.get_rules(Some(&client.account_id))?,
+            None => return Ok(Vec::new()),
+        }
+    };
+    if rules.is_empty() || uids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut moved = Vec::new();
+    for message in client.get_rule_messages(folder, uids).await? {
+        for rule in matching_rules(&rules, &message) {
+            println!(
+                "[Rules:{}] Rule \"{}\" (#{}) fired for UID {} in {} ({})",
+                client.account_id, rule.name, rule.id, message.uid, folder, message.subject
+            );
+            if let Err(e) = apply_actions(client, db, folder, &message, &rule.actions).await {
+                eprintln!(
+                    "[Rules:{}] Rule \"{}\" failed for UID {}: {}",
+                    client.account_id, rule.name, message.uid, e
+                );
+                continue;
+            }
+            if rule.moves() {
+                moved.push(message.uid);
+            }
+        }
+    }
+    Ok(moved)
+}
+
+/// Flags and categories first, so they travel with the message when it moves
+async fn apply_actions(
+    client: &ImapClient,
+    db: &Mutex<Option<EmailDatabase>>,
+    folder: &str,
+    message: &RuleMessage,
+    actions: &[RuleAction],
+) -> Result<()> {
+    let mut flags = Vec::new();
+    let mut target = None;
+    for action in actions {
+        match action {
+            RuleAction::MarkRead => flags.push(ImapFlag::Seen),
+            RuleAction::Flag => flags.push(ImapFlag::Flagged),
+            RuleAction::Categorize { category } => {
+                if message.message_id.is_empty() {
+                    continue;
+                }
+                let db_lock = db.lock().unwrap();
+                if let Some(database) = db_lock.as_ref() {
+                    database.set_rule_category(
+                        &client.account_id,
+                        &message.message_id,
+                        category,
+                    )?;
+                }
+            }
+            RuleAction::Move { folder } => target = Some(folder.as_str()),
+        }
+    }
+
+    if !flags.is_empty() {
+        client
+            .set_flags_bulk(folder, &[message.uid], &flags, true)
+            .await?;
+    }
+    if let Some(target) = target {
+        let target = map_folder_name(&client.provider, target);
+        if target != folder {
+            client.move_message(folder, message.uid, &target).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i64, conditions: Vec<RuleCondition>, actions: Vec<RuleAction>) -> Rule {
+        Rule {
+            id,
+            name: format!("rule {}", id),
+            account_id: None,
+            conditions,
+            actions,
+            stop_processing: false,
+        }
+    }
+
+    #[test]
+    fn test_rules_fire_in_order_and_stop() {
+        let github = RuleCondition::From {
+            contains: "notifications@GitHub.com".to_string(),
+        };
+        let mut rules = vec![
+            rule(1, vec![github.clone()], vec![RuleAction::MarkRead]),
+            rule(
+                2,
+                vec![
+                    RuleCondition::Subject {
+                        contains: "invoice".to_string(),
+                    },
+                    RuleCondition::HasAttachment,
+                ],
+                vec![RuleAction::Flag],
+            ),
+            rule(
+                3,
+                vec![github],
+                vec![RuleAction::Move {
+                    folder: "Dev".to_string(),
+                }],
+            ),
+            rule(
+                4,
+                vec![RuleCondition::To {
+                    contains: "me".to_string(),
+                }],
+                vec![RuleAction::Flag],
+            ),
+        ];
+        let message = RuleMessage {
+            from: "GitHub <notifications@github.com>".to_string(),
+            to: vec!["me@example.com".to_string()],
+            subject: "Invoice for March".to_string(),
+            ..Default::default()
+        };
+        let fired = |rules: &[Rule]| -> Vec<i64> {
+            matching_rules(rules, &message)
+                .iter()
+                .map(|r| r.id)
+                .collect()
+        };
+
+        // No attachment, so rule 2 doesn't match; the move in rule 3 ends it
+        assert_eq!(fired(&rules), [1, 3]);
+
+        rules[0].stop_processing = true;
+        assert_eq!(fired(&rules), [1]);
+
+        assert!(rules[2].validate().is_ok());
+        let mut two_moves = rules[2].clone();
+        two_moves.actions.push(RuleAction::Move {
+            folder: "Other".to_string(),
+        });
+        assert!(two_moves.validate().is_err());
+        assert!(rule(5, vec![], vec![RuleAction::Flag]).validate().is_err());
+    }
+}
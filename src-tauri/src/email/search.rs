@@ -0,0 +1,206 @@
+//! Search box syntax mapped to IMAP SEARCH keys (RFC 3501 section 6.4.4).
+//!
+//! `from:alice subject:"q3 report" since:2026-03-01 is:unread invoice` becomes
+//! `FROM "alice" SUBJECT "q3 report" SINCE 1-Mar-2026 UNSEEN TEXT "invoice"`.
+//! Bare words and unknown keys search the whole message with TEXT.
+
+use chrono::NaiveDate;
+
+/// One search key; a query matches messages that satisfy all of them
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchTerm {
+    From(String),
+    To(String),
+    Cc(String),
+    Subject(String),
+    Body(String),
+    Text(String),
+    Since(NaiveDate),
+    Before(NaiveDate),
+    Seen(bool),
+    Flagged(bool),
+}
+
+/// A parsed search box query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub terms: Vec<SearchTerm>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Self {
+        let terms = tokenize(query)
+            .into_iter()
+            .filter_map(|token| parse_term(&token))
+            .collect();
+        Self { terms }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Whether any string argument needs `CHARSET UTF-8`
+    pub fn is_ascii(&self) -> bool {
+        self.terms.iter().all(|term| match term {
+            SearchTerm::From(value)
+            | SearchTerm::To(value)
+            | SearchTerm::Cc(value)
+            | SearchTerm::Subject(value)
+            | SearchTerm::Body(value)
+            | SearchTerm::Text(value) => value.is_ascii(),
+            _ => true,
+        })
+    }
+
+    /// SEARCH criteria, e.g. `FROM "alice" TEXT "invoice"`; `ALL` when empty
+    pub fn to_imap_criteria(&self) -> String {
+        if self.terms.is_empty() {
+            return "ALL".to_string();
+        }
+        self.terms
+            .iter()
+            .map(|term| match term {
+                SearchTerm::From(value) => format!("FROM {}", quote(value)),
+                SearchTerm::To(value) => format!("TO {}", quote(value)),
+                SearchTerm::Cc(value) => format!("CC {}", quote(value)),
+                SearchTerm::Subject(value) => format!("SUBJECT {}", quote(value)),
+                SearchTerm::Body(value) => format!("BODY {}", quote(value)),
+                SearchTerm::Text(value) => format!("TEXT {}", quote(value)),
+                SearchTerm::Since(date) => format!("SINCE {}", imap_date(*date)),
+                SearchTerm::Before(date) => format!("BEFORE {}", imap_date(*date)),
+                SearchTerm::Seen(true) => "SEEN".to_string(),
+                SearchTerm::Seen(false) => "UNSEEN".to_string(),
+                SearchTerm::Flagged(true) => "FLAGGED".to_string(),
+                SearchTerm::Flagged(false) => "UNFLAGGED".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Split on whitespace outside double quotes; quotes stay in the token
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for ch in query.chars() {
+        match ch {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(ch);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_term(token: &str) -> Option<SearchTerm> {
+    let keyed = token
+        .split_once(':')
+        .filter(|(key, value)| !key.starts_with('"') && !value.is_empty());
+
+    let term = keyed.and_then(|(key, value)| {
+        let value = unquote(value);
+        match key.to_ascii_lowercase().as_str() {
+            "from" => Some(SearchTerm::From(value)),
+            "to" => Some(SearchTerm::To(value)),
+            "cc" => Some(SearchTerm::Cc(value)),
+            "subject" => Some(SearchTerm::Subject(value)),
+            "body" => Some(SearchTerm::Body(value)),
+            "since" | "after" => parse_date(&value).map(SearchTerm::Since),
+            "before" => parse_date(&value).map(SearchTerm::Before),
+            "is" => match value.to_ascii_lowercase().as_str() {
+                "read" => Some(SearchTerm::Seen(true)),
+                "unread" => Some(SearchTerm::Seen(false)),
+                "starred" | "flagged" => Some(SearchTerm::Flagged(true)),
+                "unstarred" | "unflagged" => Some(SearchTerm::Flagged(false)),
+                _ => None,
+            },
+            _ => None,
+        }
+    });
+
+    term.or_else(|| {
+        let text = unquote(token);
+        (!text.is_empty()).then_some(SearchTerm::Text(text))
+    })
+}
+
+fn unquote(value: &str) -> String {
+    value.replace('"', "")
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+/// RFC 3501 date: `1-Mar-2026`
+fn imap_date(date: NaiveDate) -> String {
+    date.format("%-d-%b-%Y").to_string()
+}
+
+/// IMAP quoted string. A quoted string can't hold CR, LF or NUL, and a line
+/// break would end the command and start a new one, so control characters
+/// become spaces.
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' | '"' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c if c.is_control() => quoted.push(' '),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_maps_to_search_keys() {
+        let query = SearchQuery::parse(
+            "from:alice subject:\"q3 report\" since:2026-03-01 is:unread invoice",
+        );
+        assert_eq!(
+            query.to_imap_criteria(),
+            "FROM \"alice\" SUBJECT \"q3 report\" SINCE 1-Mar-2026 UNSEEN TEXT \"invoice\""
+        );
+
+        // Unknown keys and bad dates fall back to a full-text search
+        let query = SearchQuery::parse("since:yesterday \"two words\" size:big");
+        assert_eq!(
+            query.to_imap_criteria(),
+            "TEXT \"since:yesterday\" TEXT \"two words\" TEXT \"size:big\""
+        );
+
+        assert_eq!(SearchQuery::parse("   ").to_imap_criteria(), "ALL");
+        assert!(!SearchQuery::parse("from:zoë").is_ascii());
+    }
+
+    #[test]
+    fn test_line_breaks_cannot_end_the_command() {
+        let query = SearchQuery::parse("from:\"x\r\nA1 DELETE INBOX\" \"a\\b\"");
+        let criteria = query.to_imap_criteria();
+        assert!(!criteria.contains(['\r', '\n']));
+        assert_eq!(criteria, "FROM \"x  A1 DELETE INBOX\" TEXT \"a\\\\b\"");
+        assert_eq!(quote("<id@host>\0"), "\"<id@host> \"");
+    }
+}
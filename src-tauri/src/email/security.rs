@@ -0,0 +1,192 @@
+use mail_parser::{Message, MessagePart, MimeHeaders, PartType};
+use serde::{Deserialize, Serialize};
+
+/// Which standard a message was encrypted or signed with
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityScheme {
+    /// RFC 3156 multipart/encrypted or multipart/signed with PGP parts
+    PgpMime,
+    /// ASCII-armored PGP block inside a text part
+    PgpInline,
+    /// RFC 8551 application/pkcs7-mime or multipart/signed with a PKCS#7 signature
+    Smime,
+}
+
+/// Outcome of checking a signature. Only detection exists so far.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStatus {
+    Unverified,
+}
+
+/// Encryption/signing detected while parsing a message
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MessageSecurity {
+    pub encrypted: bool,
+    pub signed: bool,
+    pub scheme: Option<SecurityScheme>,
+    /// Set for signed messages
+    pub signature: Option<SignatureStatus>,
+}
+
+impl MessageSecurity {
+    /// Inspect every MIME part of a parsed message
+    pub fn from_message(message: &Message<'_>) -> Self {
+        let mut security = Self::default();
+        for part in &message.parts {
+            security.inspect(part);
+        }
+        if security.signed {
+            security.signature = Some(SignatureStatus::Unverified);
+        }
+        security
+    }
+
+    fn inspect(&mut self, part: &MessagePart<'_>) {
+        let Some(content_type) = part.content_type() else {
+            return;
+        };
+        let ty = content_type.ctype().to_ascii_lowercase();
+        let subtype = content_type.subtype().unwrap_or("").to_ascii_lowercase();
+        let param = |name: &str| {
+            content_type
+                .attributes()
+                .unwrap_or_default()
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.to_ascii_lowercase())
+                .unwrap_or_default()
+        };
+
+        match (ty.as_str(), subtype.as_str()) {
+            ("multipart", "encrypted") => {
+                self.encrypted = true;
+                if param("protocol") == "application/pgp-encrypted" {
+                    self.mark(SecurityScheme::PgpMime);
+                }
+            }
+            ("multipart", "signed") => {
+                self.signed = true;
+                match param("protocol").as_str() {
+                    "application/pgp-signature" => self.mark(SecurityScheme::PgpMime),
+                    "application/pkcs7-signature" | "application/x-pkcs7-signature" => {
+                        self.mark(SecurityScheme::Smime)
+                    }
+                    _ => {}
+                }
+            }
+            ("application", "pkcs7-mime") | ("application", "x-pkcs7-mime") => {
+                // smime-type is optional; without it the part is enveloped (encrypted) data
+                match param("smime-type").as_str() {
+                    "signed-data" => self.signed = true,
+                    "certs-only" => {}
+                    _ => self.encrypted = true,
+                }
+                self.mark(SecurityScheme::Smime);
+            }
+            ("text", _) => {
+                if let PartType::Text(text) = &part.body {
+                    if text.contains("-----BEGIN PGP MESSAGE-----") {
+                        self.encrypted = true;
+                        self.mark(SecurityScheme::PgpInline);
+                    }
+                    if text.contains("-----BEGIN PGP SIGNED MESSAGE-----") {
+                        self.signed = true;
+                        self.mark(SecurityScheme::PgpInline);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The outermost scheme wins; parts are visited outside-in
+    fn mark(&mut self, scheme: SecurityScheme) {
+        self.scheme.get_or_insert(scheme);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mail_parser::MessageParser;
+
+    const PGP_MIME: &str = "From: Ana <ana@example.com>\r\n\
+        To: me@example.com\r\n\
+        Subject: Secret\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/encrypted; protocol=\"application/pgp-encrypted\"; boundary=\"b1\"\r\n\
+        \r\n\
+        --b1\r\n\
+        Content-Type: application/pgp-encrypted\r\n\
+        \r\n\
+        Version: 1\r\n\
+        --b1\r\n\
+        Content-Type: application/octet-stream; name=\"encrypted.asc\"\r\n\
+        \r\n\
+        -----BEGIN PGP MESSAGE-----\r\n\
+        hQEMA1Jy8dvlR0pbAQf/Xt2BsQ0Zm3cQ==\r\n\
+        -----END PGP MESSAGE-----\r\n\
+        --b1--\r\n";
+
+    const PGP_SIGNED: &str = "From: Ana <ana@example.com>\r\n\
+        Subject: Signed\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/signed; micalg=pgp-sha256; protocol=\"application/pgp-signature\"; boundary=\"s1\"\r\n\
+        \r\n\
+        --s1\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        Meet at noon.\r\n\
+        --s1\r\n\
+        Content-Type: application/pgp-signature; name=\"signature.asc\"\r\n\
+        \r\n\
+        -----BEGIN PGP SIGNATURE-----\r\n\
+        iQEzBAEBCAAdFiEE\r\n\
+        -----END PGP SIGNATURE-----\r\n\
+        --s1--\r\n";
+
+    #[test]
+    fn test_detects_pgp_mime_encrypted_and_keeps_payload_as_part() {
+        let message = MessageParser::default().parse(PGP_MIME.as_bytes()).unwrap();
+        let security = MessageSecurity::from_message(&message);
+
+        assert!(security.encrypted);
+        assert!(!security.signed);
+        assert_eq!(security.scheme, Some(SecurityScheme::PgpMime));
+        // The ciphertext is an attachment, not a text body
+        assert!(message.body_text(0).is_none());
+        let payload = message
+            .attachments()
+            .find(|part| part.attachment_name() == Some("encrypted.asc"))
+            .unwrap();
+        assert!(payload
+            .contents()
+            .starts_with(b"-----BEGIN PGP MESSAGE-----"));
+    }
+
+    #[test]
+    fn test_detects_signed_as_unverified() {
+        let message = MessageParser::default()
+            .parse(PGP_SIGNED.as_bytes())
+            .unwrap();
+        let security = MessageSecurity::from_message(&message);
+
+        assert!(security.signed);
+        assert!(!security.encrypted);
+        assert_eq!(security.scheme, Some(SecurityScheme::PgpMime));
+        assert_eq!(security.signature, Some(SignatureStatus::Unverified));
+        assert_eq!(message.body_text(0).as_deref(), Some("Meet at noon."));
+
+        let smime = MessageParser::default()
+            .parse(
+                b"Subject: x\r\nContent-Type: application/pkcs7-mime; smime-type=enveloped-data; name=smime.p7m\r\n\r\nMIAGCSqGSIb3DQEHA6CAMIACAQ\r\n"
+                    .as_slice(),
+            )
+            .unwrap();
+        let security = MessageSecurity::from_message(&smime);
+        assert!(security.encrypted);
+        assert_eq!(security.scheme, Some(SecurityScheme::Smime));
+    }
+}
@@ -3,12 +3,60 @@ use serde::{Deserialize, Serialize};
 /// Authentication type for an email account
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum AuthType {
-    /// OAuth2 XOAUTH2 SASL mechanism
+    /// OAuth2 access token, over XOAUTH2 or OAUTHBEARER
     OAuth2,
     /// Plain password / app password
     Password,
 }
 
+/// SASL mechanism an OAuth2 access token is presented with
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OAuthMechanism {
+    /// Google's original mechanism, also accepted by Microsoft
+    #[default]
+    XOAuth2,
+    /// The standardized mechanism (RFC 7628)
+    OAuthBearer,
+}
+
+impl OAuthMechanism {
+    /// Name as sent in AUTHENTICATE
+    pub fn as_str(&self) -> &str {
+        match self {
+            OAuthMechanism::XOAuth2 => "XOAUTH2",
+            OAuthMechanism::OAuthBearer => "OAUTHBEARER",
+        }
+    }
+
+    /// The mechanism to fall back to when this one is rejected
+    pub fn other(self) -> Self {
+        match self {
+            OAuthMechanism::XOAuth2 => OAuthMechanism::OAuthBearer,
+            OAuthMechanism::OAuthBearer => OAuthMechanism::XOAuth2,
+        }
+    }
+
+    /// Initial client response carrying the token
+    pub fn initial_response(&self, user: &str, token: &str, host: &str, port: u16) -> String {
+        match self {
+            OAuthMechanism::XOAuth2 => format!("user={}\x01auth=Bearer {}\x01\x01", user, token),
+            OAuthMechanism::OAuthBearer => format!(
+                "n,a={},\x01host={}\x01port={}\x01auth=Bearer {}\x01\x01",
+                user, host, port, token
+            ),
+        }
+    }
+
+    /// Reply to the error challenge a server sends before failing the
+    /// exchange, so it can send its tagged NO
+    pub fn error_ack(&self) -> &'static str {
+        match self {
+            OAuthMechanism::XOAuth2 => "",
+            OAuthMechanism::OAuthBearer => "\x01",
+        }
+    }
+}
+
 /// Email provider type
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ProviderType {
@@ -36,6 +84,82 @@ impl ProviderType {
             _ => ProviderType::Custom,
         }
     }
+
+    /// Whether the provider files mail sent over SMTP in Sent by itself, so
+    /// appending a copy would store it twice
+    pub fn saves_sent_mail(&self) -> bool {
+        matches!(self, ProviderType::Gmail | ProviderType::Outlook)
+    }
+}
+
+/// How a connection is secured
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMode {
+    /// TLS from the first byte (IMAP 993, SMTP 465)
+    #[default]
+    Implicit,
+    /// Plain connection upgraded with STARTTLS (IMAP 143, SMTP 587)
+    StartTls,
+    /// No encryption at all
+    None,
+}
+
+impl TlsMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TlsMode::Implicit => "implicit",
+            TlsMode::StartTls => "start_tls",
+            TlsMode::None => "none",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "implicit" | "tls" | "ssl" => Some(TlsMode::Implicit),
+            "start_tls" | "starttls" => Some(TlsMode::StartTls),
+            "none" | "plain" => Some(TlsMode::None),
+            _ => None,
+        }
+    }
+
+    /// Likely mode for an IMAP port: STARTTLS on 143, implicit TLS otherwise
+    pub fn for_imap_port(port: u16) -> Self {
+        if port == 143 {
+            TlsMode::StartTls
+        } else {
+            TlsMode::Implicit
+        }
+    }
+
+    /// Mode SMTP used before it could be configured: implicit TLS on 465,
+    /// STARTTLS on any other port
+    pub fn for_smtp_port(port: u16) -> Self {
+        if port == 465 {
+            TlsMode::Implicit
+        } else {
+            TlsMode::StartTls
+        }
+    }
+}
+
+/// Also accepts the old `use_tls` boolean (true is implicit TLS)
+impl<'de> Deserialize<'de> for TlsMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Legacy(bool),
+            Name(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Legacy(true) => Ok(TlsMode::Implicit),
+            Repr::Legacy(false) => Ok(TlsMode::None),
+            Repr::Name(name) => TlsMode::from_str(&name)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown TLS mode: {}", name))),
+        }
+    }
 }
 
 /// Server configuration for IMAP and SMTP
@@ -45,59 +169,237 @@ pub struct ServerConfig {
     pub imap_port: u16,
     pub smtp_host: String,
     pub smtp_port: u16,
-    pub use_tls: bool,
+    /// Security of the IMAP connection (formerly the `use_tls` flag)
+    #[serde(alias = "use_tls")]
+    pub tls_mode: TlsMode,
+    /// Security of the SMTP connection; chosen from the port when unset
+    #[serde(default)]
+    pub smtp_tls_mode: Option<TlsMode>,
 }
 
-/// Well-known server presets
-pub fn get_server_preset(provider: &ProviderType) -> Option<ServerConfig> {
-    match provider {
-        ProviderType::Gmail => Some(ServerConfig {
-            imap_host: "imap.gmail.com".to_string(),
-            imap_port: 993,
-            smtp_host: "smtp.gmail.com".to_string(),
-            smtp_port: 465,
-            use_tls: true,
-        }),
-        ProviderType::Outlook => Some(ServerConfig {
-            imap_host: "outlook.office365.com".to_string(),
-            imap_port: 993,
-            smtp_host: "smtp.office365.com".to_string(),
-            smtp_port: 587,
-            use_tls: true,
-        }),
-        ProviderType::Yahoo => Some(ServerConfig {
-            imap_host: "imap.mail.yahoo.com".to_string(),
-            imap_port: 993,
-            smtp_host: "smtp.mail.yahoo.com".to_string(),
-            smtp_port: 465,
-            use_tls: true,
-        }),
-        ProviderType::Custom => None,
+impl ServerConfig {
+    pub fn smtp_tls(&self) -> TlsMode {
+        self.smtp_tls_mode
+            .unwrap_or_else(|| TlsMode::for_smtp_port(self.smtp_port))
     }
 }
 
-/// Detect provider from email domain
-pub fn detect_provider(email: &str) -> ProviderType {
-    let domain = email
-        .split('@')
-        .nth(1)
-        .unwrap_or("")
-        .to_lowercase();
+/// How a provider lets a mail app sign in
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PresetAuth {
+    /// Sign in through the provider's page in the browser
+    OAuth,
+    /// The account password works as is
+    Password,
+    /// A password generated for the app in the provider's account settings
+    AppPassword,
+    /// The password shown by the provider's bridge app running locally
+    Bridge,
+}
+
+/// Settings for a well-known provider. Adding one is a single entry in
+/// `PRESETS`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerPreset {
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Gmail, Outlook and Yahoo get provider-specific handling; the rest are
+    /// `Custom` servers with known settings
+    pub provider: ProviderType,
+    /// Address domains served by this provider
+    pub domains: &'static [&'static str],
+    pub imap_host: &'static str,
+    pub imap_port: u16,
+    pub imap_tls: TlsMode,
+    pub smtp_host: &'static str,
+    pub smtp_port: u16,
+    pub smtp_tls: TlsMode,
+    /// Ways to sign in, preferred first
+    pub auth: &'static [PresetAuth],
+    /// What to tell the user before they sign in, if anything
+    pub auth_hint: Option<&'static str>,
+}
 
-    match domain.as_str() {
-        "gmail.com" | "googlemail.com" => ProviderType::Gmail,
-        "outlook.com" | "hotmail.com" | "live.com" | "msn.com" => ProviderType::Outlook,
-        "yahoo.com" | "ymail.com" | "rocketmail.com" => ProviderType::Yahoo,
-        _ => ProviderType::Custom,
+impl ServerPreset {
+    pub fn server_config(&self) -> ServerConfig {
+        ServerConfig {
+            imap_host: self.imap_host.to_string(),
+            imap_port: self.imap_port,
+            smtp_host: self.smtp_host.to_string(),
+            smtp_port: self.smtp_port,
+            tls_mode: self.imap_tls,
+            smtp_tls_mode: Some(self.smtp_tls),
+        }
+    }
+
+    /// How the account is stored: OAuth when that's the preferred way in,
+    /// a password of some kind otherwise
+    pub fn auth_type(&self) -> AuthType {
+        match self.auth.first() {
+            Some(PresetAuth::OAuth) => AuthType::OAuth2,
+            _ => AuthType::Password,
+        }
     }
 }
 
-/// Get default auth type for a provider
-pub fn default_auth_type(provider: &ProviderType) -> AuthType {
-    match provider {
-        ProviderType::Gmail | ProviderType::Outlook => AuthType::OAuth2,
-        ProviderType::Yahoo | ProviderType::Custom => AuthType::Password,
+pub const PRESETS: &[ServerPreset] = &[
+    ServerPreset {
+        id: "gmail",
+        name: "Gmail",
+        provider: ProviderType::Gmail,
+        domains: &["gmail.com", "googlemail.com"],
+        imap_host: "imap.gmail.com",
+        imap_port: 993,
+        imap_tls: TlsMode::Implicit,
+        smtp_host: "smtp.gmail.com",
+        smtp_port: 465,
+        smtp_tls: TlsMode::Implicit,
+        auth: &[PresetAuth::OAuth, PresetAuth::AppPassword],
+        auth_hint: None,
+    },
+    ServerPreset {
+        id: "outlook",
+        name: "Outlook.com",
+        provider: ProviderType::Outlook,
+        domains: &["outlook.com", "hotmail.com", "live.com", "msn.com"],
+        imap_host: "outlook.office365.com",
+        imap_port: 993,
+        imap_tls: TlsMode::Implicit,
+        smtp_host: "smtp.office365.com",
+        smtp_port: 587,
+        smtp_tls: TlsMode::StartTls,
+        auth: &[PresetAuth::OAuth],
+        auth_hint: Some("Microsoft no longer accepts passwords from mail apps; sign in with Microsoft"),
+    },
+    ServerPreset {
+        id: "yahoo",
+        name: "Yahoo Mail",
+        provider: ProviderType::Yahoo,
+        domains: &["yahoo.com", "ymail.com", "rocketmail.com"],
+        imap_host: "imap.mail.yahoo.com",
+        imap_port: 993,
+        imap_tls: TlsMode::Implicit,
+        smtp_host: "smtp.mail.yahoo.com",
+        smtp_port: 465,
+        smtp_tls: TlsMode::Implicit,
+        auth: &[PresetAuth::AppPassword],
+        auth_hint: Some("Yahoo needs an app password, created under Account security"),
+    },
+    ServerPreset {
+        id: "icloud",
+        name: "iCloud Mail",
+        provider: ProviderType::Custom,
+        domains: &["icloud.com", "me.com", "mac.com"],
+        imap_host: "imap.mail.me.com",
+        imap_port: 993,
+        imap_tls: TlsMode::Implicit,
+        smtp_host: "smtp.mail.me.com",
+        smtp_port: 587,
+        smtp_tls: TlsMode::StartTls,
+        auth: &[PresetAuth::AppPassword],
+        auth_hint: Some("iCloud needs an app-specific password, created at account.apple.com"),
+    },
+    ServerPreset {
+        id: "fastmail",
+        name: "Fastmail",
+        provider: ProviderType::Custom,
+        domains: &["fastmail.com", "fastmail.fm", "fastmail.net", "messagingengine.com"],
+        imap_host: "imap.fastmail.com",
+        imap_port: 993,
+        imap_tls: TlsMode::Implicit,
+        smtp_host: "smtp.fastmail.com",
+        smtp_port: 465,
+        smtp_tls: TlsMode::Implicit,
+        auth: &[PresetAuth::AppPassword],
+        auth_hint: Some("Fastmail needs an app password, created under Settings > Privacy & Security"),
+    },
+    ServerPreset {
+        id: "proton",
+        name: "Proton Mail (Bridge)",
+        provider: ProviderType::Custom,
+        domains: &["proton.me", "protonmail.com", "protonmail.ch", "pm.me"],
+        imap_host: "127.0.0.1",
+        imap_port: 1143,
+        imap_tls: TlsMode::StartTls,
+        smtp_host: "127.0.0.1",
+        smtp_port: 1025,
+        smtp_tls: TlsMode::StartTls,
+        auth: &[PresetAuth::Bridge],
+        auth_hint: Some(
+            "Proton Mail works through Proton Mail Bridge; keep it running and use the password it shows, not your Proton password",
+        ),
+    },
+    ServerPreset {
+        id: "gmx",
+        name: "GMX",
+        provider: ProviderType::Custom,
+        domains: &["gmx.com", "gmx.us", "gmx.co.uk", "gmx.fr", "gmx.es"],
+        imap_host: "imap.gmx.com",
+        imap_port: 993,
+        imap_tls: TlsMode::Implicit,
+        smtp_host: "mail.gmx.com",
+        smtp_port: 587,
+        smtp_tls: TlsMode::StartTls,
+        auth: &[PresetAuth::Password],
+        auth_hint: Some("Turn on IMAP access in the GMX web mail settings first"),
+    },
+    ServerPreset {
+        id: "gmx_de",
+        name: "GMX (Germany, Austria, Switzerland)",
+        provider: ProviderType::Custom,
+        domains: &["gmx.net", "gmx.de", "gmx.at", "gmx.ch"],
+        imap_host: "imap.gmx.net",
+        imap_port: 993,
+        imap_tls: TlsMode::Implicit,
+        smtp_host: "mail.gmx.net",
+        smtp_port: 587,
+        smtp_tls: TlsMode::StartTls,
+        auth: &[PresetAuth::Password],
+        auth_hint: Some("Turn on IMAP access in the GMX web mail settings first"),
+    },
+    ServerPreset {
+        id: "zoho",
+        name: "Zoho Mail",
+        provider: ProviderType::Custom,
+        domains: &["zoho.com", "zohomail.com"],
+        imap_host: "imap.zoho.com",
+        imap_port: 993,
+        imap_tls: TlsMode::Implicit,
+        smtp_host: "smtp.zoho.com",
+        smtp_port: 465,
+        smtp_tls: TlsMode::Implicit,
+        auth: &[PresetAuth::Password, PresetAuth::AppPassword],
+        auth_hint: Some(
+            "Turn on IMAP access in Zoho Mail settings; with two-factor sign-in use an app-specific password",
+        ),
+    },
+];
+
+/// The preset serving the address's domain, if it's a known provider
+pub fn preset_for_email(email: &str) -> Option<&'static ServerPreset> {
+    let domain = email.rsplit_once('@')?.1.trim().to_lowercase();
+    PRESETS
+        .iter()
+        .find(|preset| preset.domains.contains(&domain.as_str()))
+}
+
+/// Server settings for the providers with their own `ProviderType`
+pub fn get_server_preset(provider: &ProviderType) -> Option<ServerConfig> {
+    if *provider == ProviderType::Custom {
+        return None;
     }
+    PRESETS
+        .iter()
+        .find(|preset| preset.provider == *provider)
+        .map(ServerPreset::server_config)
+}
+
+/// Detect provider from email domain
+pub fn detect_provider(email: &str) -> ProviderType {
+    preset_for_email(email)
+        .map(|preset| preset.provider.clone())
+        .unwrap_or(ProviderType::Custom)
 }
 
 /// Special folder names vary across providers
@@ -134,3 +436,62 @@ pub fn get_special_folders(provider: &ProviderType) -> SpecialFolders {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_config_accepts_legacy_use_tls() {
+        let legacy: ServerConfig = serde_json::from_str(
+            r#"{"imap_host":"mail.example.com","imap_port":993,
+                "smtp_host":"mail.example.com","smtp_port":587,"use_tls":true}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.tls_mode, TlsMode::Implicit);
+        assert_eq!(legacy.smtp_tls(), TlsMode::StartTls);
+
+        let explicit: ServerConfig = serde_json::from_str(
+            r#"{"imap_host":"mail.example.com","imap_port":143,
+                "smtp_host":"mail.example.com","smtp_port":25,
+                "tls_mode":"start_tls","smtp_tls_mode":"none"}"#,
+        )
+        .unwrap();
+        assert_eq!(explicit.tls_mode, TlsMode::StartTls);
+        assert_eq!(explicit.smtp_tls(), TlsMode::None);
+        assert_eq!(
+            serde_json::to_value(explicit.tls_mode).unwrap(),
+            serde_json::json!("start_tls")
+        );
+    }
+
+    #[test]
+    fn test_presets_match_address_domains() {
+        let icloud = preset_for_email("Someone@Me.com").unwrap();
+        assert_eq!(icloud.id, "icloud");
+        assert_eq!(icloud.auth, [PresetAuth::AppPassword]);
+        assert_eq!(icloud.server_config().smtp_tls(), TlsMode::StartTls);
+        let host = |email| preset_for_email(email).unwrap().imap_host;
+        assert_eq!(host("a@gmx.de"), "imap.gmx.net");
+        assert_eq!(host("a@gmx.com"), "imap.gmx.com");
+        assert!(preset_for_email("a@example.com").is_none());
+        assert!(preset_for_email("not an address").is_none());
+
+        assert_eq!(detect_provider("a@hotmail.com"), ProviderType::Outlook);
+        assert_eq!(detect_provider("a@fastmail.com"), ProviderType::Custom);
+        let gmail = preset_for_email("a@gmail.com").unwrap();
+        assert_eq!(gmail.auth_type(), AuthType::OAuth2);
+        assert!(get_server_preset(&ProviderType::Custom).is_none());
+        assert_eq!(
+            get_server_preset(&ProviderType::Yahoo).unwrap().imap_host,
+            "imap.mail.yahoo.com"
+        );
+
+        // Every domain belongs to one preset only
+        let mut domains: Vec<_> = PRESETS.iter().flat_map(|p| p.domains).collect();
+        let total = domains.len();
+        domains.sort();
+        domains.dedup();
+        assert_eq!(domains.len(), total);
+    }
+}
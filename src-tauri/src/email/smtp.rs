@@ -0,0 +1,150 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Timeouts and retry policy for SMTP submission
+#[derive(Debug, Clone)]
+pub struct SmtpSendOptions {
+    /// TCP connect + TLS handshake
+    pub connect_timeout: Duration,
+    /// EHLO + AUTH after the connection is up
+    pub auth_timeout: Duration,
+    /// MAIL FROM / RCPT TO / DATA for the message itself
+    pub data_timeout: Duration,
+    /// Total attempts for transient failures (1 = no retry)
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for each further retry
+    pub initial_backoff: Duration,
+}
+
+impl Default for SmtpSendOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(15),
+            auth_timeout: Duration::from_secs(15),
+            data_timeout: Duration::from_secs(60),
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Which step of the SMTP exchange failed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmtpPhase {
+    Connect,
+    Data,
+}
+
+/// A failed SMTP send, carrying the server's reply code when there was one
+#[derive(Debug, Clone)]
+pub struct SmtpSendError {
+    pub phase: SmtpPhase,
+    /// SMTP reply code (e.g. 550, 421)
+    pub code: Option<u16>,
+    /// Permanent failures (5xx, rejected recipient, bad credentials) are not retried
+    pub permanent: bool,
+    /// The connection timed out or dropped while sending without a reply, so the
+    /// server may already have accepted the message; resending could duplicate it
+    pub delivery_unknown: bool,
+    pub message: String,
+}
+
+impl SmtpSendError {
+    pub fn new(phase: SmtpPhase, code: Option<u16>, permanent: bool, message: String) -> Self {
+        // Before the message is handed over, any failure means nothing was sent.
+        // Once MAIL/RCPT/DATA are underway only an explicit reply says what happened;
+        // a timeout or dropped connection may come after the server took the message.
+        let replied = code.is_some_and(|c| (400..600).contains(&c));
+        let delivery_unknown = phase == SmtpPhase::Data && !permanent && !replied;
+        Self {
+            phase,
+            code,
+            permanent,
+            delivery_unknown,
+            message,
+        }
+    }
+
+    pub fn from_lettre(phase: SmtpPhase, err: &lettre::transport::smtp::Error) -> Self {
+        let code = err.status().and_then(|c| c.to_string().parse().ok());
+        // 5xx replies, client-side errors and TLS failures won't go away by retrying
+        let permanent = err.is_permanent() || err.is_client() || err.is_tls();
+        Self::new(phase, code, permanent, err.to_string())
+    }
+
+    pub fn timeout(phase: SmtpPhase, after: Duration) -> Self {
+        let message = format!("timed out after {}s", after.as_secs());
+        Self::new(phase, None, false, message)
+    }
+
+    /// Worth retrying: a 4xx reply, or a failure before the message was sent
+    pub fn is_transient(&self) -> bool {
+        !self.permanent && !self.delivery_unknown
+    }
+}
+
+impl fmt::Display for SmtpSendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let step = match self.phase {
+            SmtpPhase::Connect => "connect/authenticate",
+            SmtpPhase::Data => "send",
+        };
+        let kind = if self.permanent {
+            "rejected"
+        } else if self.delivery_unknown {
+            "delivery unknown, the message may have been sent"
+        } else {
+            "temporary failure, try again later"
+        };
+        match self.code {
+            Some(code) => write!(f, "SMTP {} {} ({}): {}", code, kind, step, self.message),
+            None => write!(f, "SMTP {} ({}): {}", kind, step, self.message),
+        }
+    }
+}
+
+impl std::error::Error for SmtpSendError {}
+
+/// Backoff before retry number `retry` (1-based)
+pub fn retry_backoff(options: &SmtpSendOptions, retry: u32) -> Duration {
+    options.initial_backoff * 2u32.saturating_pow(retry.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_4xx_replies_are_retried() {
+        let err = SmtpSendError::new(SmtpPhase::Data, Some(451), false, "try later".into());
+        assert!(err.is_transient());
+        assert!(!err.delivery_unknown);
+    }
+
+    #[test]
+    fn test_5xx_replies_are_not_retried() {
+        let err = SmtpSendError::new(SmtpPhase::Data, Some(550), true, "no such user".into());
+        assert!(!err.is_transient());
+        assert!(!err.delivery_unknown);
+        assert!(err.to_string().contains("rejected"));
+    }
+
+    #[test]
+    fn test_data_timeout_is_delivery_unknown() {
+        let err = SmtpSendError::timeout(SmtpPhase::Data, Duration::from_secs(60));
+        assert!(err.delivery_unknown);
+        assert!(!err.is_transient());
+        assert!(err.to_string().contains("delivery unknown"));
+
+        let dropped = SmtpSendError::new(SmtpPhase::Data, None, false, "connection reset".into());
+        assert!(dropped.delivery_unknown);
+        assert!(!dropped.is_transient());
+    }
+
+    #[test]
+    fn test_connect_timeout_is_retried() {
+        let err = SmtpSendError::timeout(SmtpPhase::Connect, Duration::from_secs(30));
+        assert!(err.is_transient());
+        assert!(!err.delivery_unknown);
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::time::{sleep, Duration};
+
+use super::imap_client::ImapClient;
+use super::provider::EmailProvider;
+use crate::commands::account::AccountManager;
+use crate::commands::email::get_client_for_account;
+use crate::db::EmailDatabase;
+
+/// Folder snoozed messages wait in until they are due
+pub const SNOOZED_FOLDER: &str = "Snoozed";
+
+/// Folder a due message is moved back to
+pub const RESURFACE_FOLDER: &str = "INBOX";
+
+/// How often the background task looks for due snoozes
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A message waiting in the Snoozed folder. It's found again by Message-ID,
+/// since its UID there can change (e.g. after UIDVALIDITY resets).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snooze {
+    pub account_id: String,
+    pub message_id: String,
+    pub subject: String,
+    /// Folder the message was snoozed from
+    pub folder: String,
+    /// Unix timestamp the message comes back at
+    pub until: i64,
+}
+
+/// Event payload emitted when a snoozed message is back in the inbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnoozeDueEvent {
+    pub account_id: String,
+    pub message_id: String,
+    pub subject: String,
+    pub folder: String,
+}
+
+/// Move a snoozed message back to the inbox. Returns false when it is no
+/// longer in the Snoozed folder (the user moved or deleted it meanwhile).
+pub async fn resurface(client: &ImapClient, snooze: &Snooze) -> Result<bool> {
+    let Some(uid) = client
+        .find_message_id(SNOOZED_FOLDER, &snooze.message_id)
+        .await?
+    else {
+        return Ok(false);
+    };
+    client
+        .move_message(SNOOZED_FOLDER, uid, RESURFACE_FOLDER)
+        .await?;
+    Ok(true)
+}
+
+/// Check for due snoozes now and then every minute. The first check runs
+/// straight away, so snoozes that fell due while the app was closed come
+/// back on startup.
+pub fn spawn_snooze_worker(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            process_due_snoozes(&app).await;
+            sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+async fn process_due_snoozes(app: &AppHandle) {
+    let db = app.state::<Arc<Mutex<Option<EmailDatabase>>>>();
+    let account_manager = app.state::<AccountManager>();
+
+    let due = {
+        let db_lock = db.lock().unwrap();
+        let Some(database) = db_lock.as_ref() else {
+            return;
+        };
+        match database.get_due_snoozes(Utc::now().timestamp()) {
+            Ok(due) => due,
+            Err(e) => {
+                eprintln!("[Snooze] Failed to load due snoozes: {}", e);
+                return;
+            }
+        }
+    };
+
+    for snooze in due {
+        let account = {
+            let db_lock = db.lock().unwrap();
+            db_lock
+                .as_ref()
+                .and_then(|database| database.get_account(&snooze.account_id).ok().flatten())
+        };
+        let Some(account) = account else {
+            // The account was removed; its snoozes go with it
+            forget(&db, &snooze);
+            continue;
+        };
+        if account.sync_paused {
+            continue;
+        }
+
+        let client = match get_client_for_account(&account_manager, &account).await {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("[Snooze] No connection for {}: {}", account.email, e);
+                continue;
+            }
+        };
+        match resurface(&client, &snooze).await {
+            Ok(found) => {
+                forget(&db, &snooze);
+                if !found {
+                    println!(
+                        "[Snooze] {} is no longer in {}, dropping its snooze",
+                        snooze.message_id, SNOOZED_FOLDER
+                    );
+                    continue;
+                }
+                account_manager.invalidate_unread(&snooze.account_id, RESURFACE_FOLDER);
+                let _ = app.emit(
+                    "snooze:due",
+                    SnoozeDueEvent {
+                        account_id: snooze.account_id.clone(),
+                        message_id: snooze.message_id.clone(),
+                        subject: snooze.subject.clone(),
+                        folder: RESURFACE_FOLDER.to_string(),
+                    },
+                );
+            }
+            // Left in place, so the next check retries
+            Err(e) => eprintln!("[Snooze] Failed to resurface {}: {}", snooze.message_id, e),
+        }
+    }
+}
+
+fn forget(db: &Mutex<Option<EmailDatabase>>, snooze: &Snooze) {
+    let db_lock = db.lock().unwrap();
+    if let Some(database) = db_lock.as_ref() {
+        if let Err(e) = database.remove_snooze(&snooze.account_id, &snooze.message_id) {
+            eprintln!("[Snooze] Failed to remove snooze: {}", e);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+use super::threading::normalize_subject;
+use super::types::EmailListItem;
+
+/// Order of a folder's message list, remembered per folder
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageSort {
+    /// Newest first
+    #[default]
+    Date,
+    /// Sender address A-Z
+    Sender,
+    /// Subject A-Z, ignoring "Re:"/"Fwd:" prefixes
+    Subject,
+    /// Largest first
+    Size,
+}
+
+impl MessageSort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MessageSort::Date => "date",
+            MessageSort::Sender => "sender",
+            MessageSort::Subject => "subject",
+            MessageSort::Size => "size",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "date" => Some(MessageSort::Date),
+            "sender" => Some(MessageSort::Sender),
+            "subject" => Some(MessageSort::Subject),
+            "size" => Some(MessageSort::Size),
+            _ => None,
+        }
+    }
+
+    /// Sort criteria for the server-side `UID SORT` command (RFC 5256)
+    pub fn sort_criteria(self) -> &'static str {
+        match self {
+            MessageSort::Date => "REVERSE DATE",
+            MessageSort::Sender => "FROM",
+            MessageSort::Subject => "SUBJECT",
+            MessageSort::Size => "REVERSE SIZE",
+        }
+    }
+
+    fn compare(self, a: &EmailListItem, b: &EmailListItem) -> Ordering {
+        match self {
+            // Items arrive newest first, and the sort is stable
+            MessageSort::Date => Ordering::Equal,
+            MessageSort::Sender => a
+                .from_email
+                .to_lowercase()
+                .cmp(&b.from_email.to_lowercase()),
+            MessageSort::Subject => base_subject(&a.subject).cmp(&base_subject(&b.subject)),
+            MessageSort::Size => b.size.cmp(&a.size),
+        }
+    }
+}
+
+/// Reorder newest-first items client-side; ties stay newest first
+pub fn sort_items(items: &mut [EmailListItem], sort: MessageSort) {
+    items.sort_by(|a, b| sort.compare(a, b));
+}
+
+/// Lowercased subject without reply/forward prefixes, roughly the RFC 5256 base subject
+fn base_subject(subject: &str) -> String {
+    normalize_subject(subject).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::types::SyncState;
+
+    fn item(id: &str, from_email: &str, subject: &str, size: u32) -> EmailListItem {
+        EmailListItem {
+            id: id.to_string(),
+            thread_id: String::new(),
+            subject: subject.to_string(),
+            from: String::new(),
+            from_email: from_email.to_string(),
+            date: String::new(),
+            snippet: String::new(),
+            is_read: false,
+            is_starred: false,
+            has_attachments: false,
+            is_first_contact: false,
+            sync_state: SyncState::LiveFetched,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_client_side_sort_orders() {
+        let newest_first = vec![
+            item("3", "zed@example.com", "Re: Budget", 10),
+            item("2", "Amy@example.com", "agenda", 300),
+            item("1", "amy@example.com", "Fwd: re: Contract", 20),
+        ];
+        let ids = |items: &[EmailListItem]| items.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+
+        let mut items = newest_first.clone();
+        sort_items(&mut items, MessageSort::Sender);
+        assert_eq!(ids(&items), ["2", "1", "3"]);
+
+        let mut items = newest_first.clone();
+        sort_items(&mut items, MessageSort::Subject);
+        assert_eq!(ids(&items), ["2", "3", "1"]);
+
+        let mut items = newest_first.clone();
+        sort_items(&mut items, MessageSort::Size);
+        assert_eq!(ids(&items), ["2", "1", "3"]);
+
+        let mut items = newest_first;
+        sort_items(&mut items, MessageSort::Date);
+        assert_eq!(ids(&items), ["3", "2", "1"]);
+    }
+}
@@ -0,0 +1,187 @@
+use async_imap::imap_proto::types::NameAttribute;
+use serde::{Deserialize, Serialize};
+
+use super::server_presets::{get_special_folders, ProviderType};
+use super::types::SpecialFolder;
+
+/// An account's special folders as advertised by LIST (RFC 6154 SPECIAL-USE).
+/// Roles the server doesn't advertise are None and resolve to the provider's
+/// default names.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SpecialFolderMap {
+    pub sent: Option<String>,
+    pub drafts: Option<String>,
+    pub trash: Option<String>,
+    pub archive: Option<String>,
+    /// The \Junk mailbox
+    pub spam: Option<String>,
+}
+
+impl SpecialFolderMap {
+    /// Build from LIST responses, using only the SPECIAL-USE attributes
+    pub fn from_list<'a>(
+        entries: impl IntoIterator<Item = (&'a str, &'a [NameAttribute<'a>])>,
+    ) -> Self {
+        let mut map = Self::default();
+        // \All only stands in for \Archive when no dedicated archive exists
+        let mut all_mail = None;
+
+        for (name, attributes) in entries {
+            for attribute in attributes {
+                let slot = match attribute {
+                    NameAttribute::Sent => &mut map.sent,
+                    NameAttribute::Drafts => &mut map.drafts,
+                    NameAttribute::Trash => &mut map.trash,
+                    NameAttribute::Archive => &mut map.archive,
+                    NameAttribute::Junk => &mut map.spam,
+                    NameAttribute::All => &mut all_mail,
+                    _ => continue,
+                };
+                slot.get_or_insert_with(|| name.to_string());
+            }
+        }
+
+        if map.archive.is_none() {
+            map.archive = all_mail;
+        }
+        map
+    }
+
+    /// Whether the server advertised any special-use folder
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Folder name for a role, falling back to the provider's default name
+    pub fn folder(&self, provider: &ProviderType, kind: SpecialFolder) -> &str {
+        let resolved = match kind {
+            SpecialFolder::Sent => &self.sent,
+            SpecialFolder::Drafts => &self.drafts,
+            SpecialFolder::Trash => &self.trash,
+            SpecialFolder::Archive => &self.archive,
+            SpecialFolder::Spam => &self.spam,
+            SpecialFolder::Inbox | SpecialFolder::Starred => &None,
+        };
+        resolved
+            .as_deref()
+            .unwrap_or_else(|| resolve_special_folder(provider, kind))
+    }
+}
+
+/// RFC 6154 name of a SPECIAL-USE attribute; None for other LIST attributes
+pub fn special_use_name(attribute: &NameAttribute<'_>) -> Option<&'static str> {
+    match attribute {
+        NameAttribute::All => Some("\\All"),
+        NameAttribute::Archive => Some("\\Archive"),
+        NameAttribute::Drafts => Some("\\Drafts"),
+        NameAttribute::Flagged => Some("\\Flagged"),
+        NameAttribute::Junk => Some("\\Junk"),
+        NameAttribute::Sent => Some("\\Sent"),
+        NameAttribute::Trash => Some("\\Trash"),
+        _ => None,
+    }
+}
+
+/// Names used when the server doesn't advertise SPECIAL-USE
+pub fn default_folder_name(kind: SpecialFolder) -> &'static str {
+    match kind {
+        SpecialFolder::Inbox => "INBOX",
+        SpecialFolder::Sent => "Sent",
+        SpecialFolder::Drafts => "Drafts",
+        SpecialFolder::Trash => "Trash",
+        SpecialFolder::Archive => "Archive",
+        SpecialFolder::Spam => "Spam",
+        SpecialFolder::Starred => "Starred",
+    }
+}
+
+/// Folder name for a role on a provider when the server doesn't say, from
+/// the provider presets. Gmail keeps its system folders under `[Gmail]/`.
+pub fn resolve_special_folder(provider: &ProviderType, kind: SpecialFolder) -> &'static str {
+    let presets = get_special_folders(provider);
+    match kind {
+        SpecialFolder::Inbox => "INBOX",
+        SpecialFolder::Sent => presets.sent,
+        SpecialFolder::Drafts => presets.drafts,
+        SpecialFolder::Trash => presets.trash,
+        SpecialFolder::Archive => presets.archive,
+        SpecialFolder::Spam => presets.spam,
+        SpecialFolder::Starred if *provider == ProviderType::Gmail => "[Gmail]/Starred",
+        SpecialFolder::Starred => default_folder_name(kind),
+    }
+}
+
+/// Role named by a frontend folder name ("inbox", "trash", ...)
+pub fn special_folder_for_alias(folder: &str) -> Option<SpecialFolder> {
+    match folder.to_lowercase().as_str() {
+        "inbox" => Some(SpecialFolder::Inbox),
+        "sent" => Some(SpecialFolder::Sent),
+        "drafts" => Some(SpecialFolder::Drafts),
+        "trash" => Some(SpecialFolder::Trash),
+        "spam" => Some(SpecialFolder::Spam),
+        "archive" => Some(SpecialFolder::Archive),
+        "starred" => Some(SpecialFolder::Starred),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_from_special_use_attributes() {
+        let gmail = [
+            ("INBOX", vec![NameAttribute::Marked]),
+            ("[Gmail]/All Mail", vec![NameAttribute::All]),
+            ("[Gmail]/Sent Mail", vec![NameAttribute::Sent]),
+            ("[Gmail]/Spam", vec![NameAttribute::Junk]),
+            ("[Gmail]/Bin", vec![NameAttribute::Trash]),
+        ];
+        let map = SpecialFolderMap::from_list(gmail.iter().map(|(n, a)| (*n, a.as_slice())));
+        let custom = ProviderType::Custom;
+
+        assert_eq!(
+            map.folder(&custom, SpecialFolder::Sent),
+            "[Gmail]/Sent Mail"
+        );
+        assert_eq!(map.folder(&custom, SpecialFolder::Trash), "[Gmail]/Bin");
+        assert_eq!(
+            map.folder(&custom, SpecialFolder::Archive),
+            "[Gmail]/All Mail"
+        );
+        assert_eq!(map.folder(&custom, SpecialFolder::Spam), "[Gmail]/Spam");
+        // Not advertised: default name
+        assert_eq!(map.folder(&custom, SpecialFolder::Drafts), "Drafts");
+
+        let plain = SpecialFolderMap::from_list([("INBOX", [].as_slice())]);
+        assert!(plain.is_empty());
+        assert_eq!(plain.folder(&custom, SpecialFolder::Trash), "Trash");
+    }
+
+    #[test]
+    fn test_gmail_defaults_live_under_gmail_prefix() {
+        let gmail = ProviderType::Gmail;
+        let empty = SpecialFolderMap::default();
+
+        assert_eq!(empty.folder(&gmail, SpecialFolder::Trash), "[Gmail]/Trash");
+        assert_eq!(
+            empty.folder(&gmail, SpecialFolder::Drafts),
+            "[Gmail]/Drafts"
+        );
+        assert_eq!(
+            resolve_special_folder(&gmail, SpecialFolder::Inbox),
+            "INBOX"
+        );
+        assert_eq!(
+            resolve_special_folder(&ProviderType::Outlook, SpecialFolder::Trash),
+            "Deleted"
+        );
+
+        assert_eq!(
+            special_folder_for_alias("Archive"),
+            Some(SpecialFolder::Archive)
+        );
+        assert_eq!(special_folder_for_alias("Receipts"), None);
+    }
+}
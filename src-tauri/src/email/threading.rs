@@ -0,0 +1,474 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::compose::parse_address_list;
+
+/// References kept on an outgoing reply. RFC 5322 sets no limit, so like most
+/// clients we keep the thread root and the most recent ancestors, which keeps
+/// the header well under the 998-character line limit.
+pub const MAX_REFERENCES: usize = 20;
+
+/// In-Reply-To/References of an outgoing reply, as bracketed message IDs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplyHeaders {
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+}
+
+impl ReplyHeaders {
+    /// Validate the IDs the frontend passed back (with or without angle
+    /// brackets). The replied-to message ends the References chain, which is
+    /// then truncated to `MAX_REFERENCES`.
+    pub fn new(in_reply_to: Option<&str>, references: &[String]) -> Result<Self> {
+        let in_reply_to = in_reply_to
+            .filter(|id| !id.trim().is_empty())
+            .map(bracket_message_id)
+            .transpose()?;
+
+        let mut chain = Vec::new();
+        for id in references.iter().flat_map(|r| r.split_whitespace()) {
+            let id = bracket_message_id(id)?;
+            if !chain.contains(&id) {
+                chain.push(id);
+            }
+        }
+        if let Some(parent) = &in_reply_to {
+            chain.retain(|id| id != parent);
+            chain.push(parent.clone());
+        }
+
+        Ok(Self {
+            in_reply_to,
+            references: truncate_references(chain),
+        })
+    }
+
+    /// Value of the References header, if any
+    pub fn references_header(&self) -> Option<String> {
+        (!self.references.is_empty()).then(|| self.references.join(" "))
+    }
+}
+
+/// Wrap a message ID in angle brackets, rejecting values that can't be one
+pub fn bracket_message_id(id: &str) -> Result<String> {
+    let trimmed = id.trim();
+    let bare = trimmed
+        .strip_prefix('<')
+        .and_then(|rest| rest.strip_suffix('>'))
+        .unwrap_or(trimmed);
+    if bare.is_empty()
+        || bare
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == '<' || c == '>')
+    {
+        bail!("Invalid message ID: {}", id);
+    }
+    Ok(format!("<{}>", bare))
+}
+
+/// Keep the first (thread root) and the latest references
+fn truncate_references(mut chain: Vec<String>) -> Vec<String> {
+    if chain.len() > MAX_REFERENCES {
+        chain.drain(1..chain.len() - (MAX_REFERENCES - 1));
+    }
+    chain
+}
+
+/// Reply/forward prefixes, lowercase, in the languages mail clients commonly
+/// localize them to (e.g. German "AW:"/"WG:", Swedish "SV:", Dutch "Antw:",
+/// Chinese "回复:")
+const SUBJECT_PREFIXES: &[&str] = &[
+    "re",
+    "fw",
+    "fwd",
+    "aw",
+    "wg",
+    "sv",
+    "vs",
+    "vb",
+    "antw",
+    "doorst",
+    "tr",
+    "rif",
+    "r",
+    "res",
+    "enc",
+    "rv",
+    "ref",
+    "odp",
+    "pd",
+    "ynt",
+    "ilt",
+    "vá",
+    "továbbítás",
+    "vl",
+    "απ",
+    "σχετ",
+    "πρθ",
+    "отв",
+    "пересл",
+    "回复",
+    "回覆",
+    "答复",
+    "转发",
+    "轉寄",
+    "返信",
+    "転送",
+    "답장",
+    "전달",
+];
+
+/// Subject without reply/forward prefixes such as "Re:", "Fwd:", "AW:",
+/// "Re[2]:" or "回复：", repeated or mixed
+pub fn normalize_subject(subject: &str) -> String {
+    strip_subject_prefixes(subject).0.to_string()
+}
+
+/// The base subject and whether any prefix was removed
+fn strip_subject_prefixes(subject: &str) -> (&str, bool) {
+    let mut rest = subject.trim();
+    let mut stripped = false;
+    while let Some(after) = subject_prefix_len(rest).map(|len| &rest[len..]) {
+        rest = after.trim_start();
+        stripped = true;
+    }
+    (rest, stripped)
+}
+
+/// Byte length of a leading `Re:`-style prefix, including an optional
+/// counter like `[2]` or `(2)` and the colon
+fn subject_prefix_len(text: &str) -> Option<usize> {
+    let word_end = text
+        .char_indices()
+        .find(|(_, c)| !c.is_alphabetic())
+        .map_or(text.len(), |(i, _)| i);
+    if word_end == 0 || !SUBJECT_PREFIXES.contains(&text[..word_end].to_lowercase().as_str()) {
+        return None;
+    }
+
+    let mut rest = &text[word_end..];
+    for (open, close) in [('[', ']'), ('(', ')')] {
+        if let Some(inner) = rest.strip_prefix(open) {
+            let end = inner.find(close)?;
+            if end == 0 || !inner[..end].chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            rest = &inner[end + 1..];
+        }
+    }
+    let rest = rest.trim_start_matches(' ');
+    let after = rest.strip_prefix(':').or_else(|| rest.strip_prefix('：'))?;
+    Some(text.len() - after.len())
+}
+
+/// Message IDs in a References/In-Reply-To value, without angle brackets
+pub fn message_ids_in(value: &str) -> Vec<String> {
+    let ids: Vec<String> = value
+        .split('<')
+        .skip(1)
+        .filter_map(|part| part.split_once('>'))
+        .map(|(id, _)| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    if ids.is_empty() {
+        // Some senders leave the brackets out
+        value.split_whitespace().map(str::to_string).collect()
+    } else {
+        ids
+    }
+}
+
+/// What threading needs to know about one message
+#[derive(Debug, Clone, Default)]
+pub struct ThreadMessage {
+    /// `account:folder:uid`, as in `EmailListItem::id`
+    pub id: String,
+    pub message_id: String,
+    pub in_reply_to: Option<String>,
+    pub references: Vec<String>,
+    /// Gmail's X-GM-THRID, the same in every folder the conversation is in
+    pub gmail_thread_id: Option<u64>,
+    pub subject: String,
+    pub from: String,
+    pub from_email: String,
+    pub date: String,
+    pub timestamp: i64,
+    pub snippet: String,
+    pub is_read: bool,
+}
+
+/// A conversation in the message list
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Thread {
+    pub thread_id: String,
+    /// Subject of the first message
+    pub subject: String,
+    /// Senders, in the order they joined the conversation
+    pub participants: Vec<String>,
+    pub message_count: usize,
+    pub unread_count: usize,
+    pub latest_date: String,
+    pub latest_snippet: String,
+    /// Message IDs (`account:folder:uid`), newest first
+    pub email_ids: Vec<String>,
+}
+
+/// Group messages into conversations, newest conversation first.
+///
+/// Messages with a Gmail thread ID are grouped by it. Others are linked
+/// JWZ-style: two messages are in the same conversation when their
+/// Message-ID/In-Reply-To/References chains share an ID, even if the shared
+/// ancestor isn't in `messages`. A reply that lost its headers ("Re: ..."
+/// with no In-Reply-To or References) joins the conversation with the same
+/// normalized subject.
+pub fn group_threads(messages: Vec<ThreadMessage>) -> Vec<Thread> {
+    let mut sets = DisjointSets::new(messages.len());
+
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+    for (i, message) in messages.iter().enumerate() {
+        let keys: Vec<String> = match message.gmail_thread_id {
+            Some(thread_id) => vec![format!("gm:{}", thread_id)],
+            None => message
+                .references
+                .iter()
+                .chain(&message.in_reply_to)
+                .chain(Some(&message.message_id))
+                .filter(|id| !id.is_empty())
+                .map(|id| format!("id:{}", id))
+                .collect(),
+        };
+        for key in keys {
+            let first = *by_id.entry(key).or_insert(i);
+            sets.union(first, i);
+        }
+    }
+
+    // Headerless replies go last so they join an existing conversation
+    let mut by_subject: HashMap<String, usize> = HashMap::new();
+    let mut headerless = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        if message.gmail_thread_id.is_some() {
+            continue;
+        }
+        let (base, is_reply) = strip_subject_prefixes(&message.subject);
+        if base.is_empty() {
+            continue;
+        }
+        let key = base.to_lowercase();
+        if is_reply && message.in_reply_to.is_none() && message.references.is_empty() {
+            headerless.push((i, key));
+        } else {
+            by_subject.entry(key).or_insert(i);
+        }
+    }
+    for (i, key) in headerless {
+        let first = *by_subject.entry(key).or_insert(i);
+        sets.union(first, i);
+    }
+
+    let mut groups: HashMap<usize, Vec<&ThreadMessage>> = HashMap::new();
+    for (i, message) in messages.iter().enumerate() {
+        groups.entry(sets.find(i)).or_default().push(message);
+    }
+
+    let mut threads: Vec<(i64, Thread)> = groups
+        .into_values()
+        .map(|mut members| {
+            members.sort_by_key(|m| m.timestamp);
+            let latest = members[members.len() - 1].timestamp;
+            (latest, build_thread(&members))
+        })
+        .collect();
+    threads.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.thread_id.cmp(&b.1.thread_id))
+    });
+    threads.into_iter().map(|(_, thread)| thread).collect()
+}
+
+/// Summarize a conversation whose members are sorted oldest first
+fn build_thread(members: &[&ThreadMessage]) -> Thread {
+    let first = members[0];
+    let latest = members[members.len() - 1];
+
+    let thread_id = match first.gmail_thread_id {
+        Some(thread_id) => format!("{:x}", thread_id),
+        None => {
+            // Hash of the conversation root, whichever member we start from
+            let root = first
+                .references
+                .first()
+                .or(first.in_reply_to.as_ref())
+                .filter(|id| !id.is_empty())
+                .unwrap_or(&first.message_id);
+            if root.is_empty() {
+                first.id.clone()
+            } else {
+                format!("{:x}", md5::compute(root.as_bytes()))
+            }
+        }
+    };
+
+    let mut participants: Vec<String> = Vec::new();
+    let mut seen = Vec::new();
+    for member in members {
+        let address = member.from_email.to_lowercase();
+        if seen.contains(&address) {
+            continue;
+        }
+        seen.push(address);
+        let name = parse_address_list(&member.from)
+            .into_iter()
+            .next()
+            .and_then(|addr| addr.name)
+            .unwrap_or_else(|| member.from.clone());
+        participants.push(name);
+    }
+
+    Thread {
+        thread_id,
+        subject: first.subject.clone(),
+        participants,
+        message_count: members.len(),
+        unread_count: members.iter().filter(|m| !m.is_read).count(),
+        latest_date: latest.date.clone(),
+        latest_snippet: latest.snippet.clone(),
+        email_ids: members.iter().rev().map(|m| m.id.clone()).collect(),
+    }
+}
+
+/// Union-find over message indices
+struct DisjointSets {
+    parent: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            // The earlier message's set stays the root
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_headers_bracket_and_truncate() {
+        let references: Vec<String> = (0..30).map(|i| format!("id{}@example.com", i)).collect();
+        let headers = ReplyHeaders::new(Some("parent@example.com"), &references).unwrap();
+
+        assert_eq!(headers.in_reply_to.as_deref(), Some("<parent@example.com>"));
+        assert_eq!(headers.references.len(), MAX_REFERENCES);
+        assert_eq!(headers.references[0], "<id0@example.com>");
+        assert_eq!(headers.references[1], "<id12@example.com>");
+        assert_eq!(
+            headers.references.last().map(String::as_str),
+            Some("<parent@example.com>")
+        );
+
+        assert_eq!(bracket_message_id(" <a@b> ").unwrap(), "<a@b>");
+        assert!(bracket_message_id("a b@c").is_err());
+        assert!(bracket_message_id("<>").is_err());
+    }
+
+    #[test]
+    fn test_normalize_subject_multilingual_prefixes() {
+        assert_eq!(normalize_subject("Re: Fwd: RE[2]: Budget"), "Budget");
+        assert_eq!(normalize_subject("AW: WG: Angebot"), "Angebot");
+        assert_eq!(normalize_subject("SV:Möte"), "Möte");
+        assert_eq!(normalize_subject("回复：会议"), "会议");
+        assert_eq!(normalize_subject("Antw: Re(3): Offerte"), "Offerte");
+        // Only known prefixes, and only when followed by a colon
+        assert_eq!(normalize_subject("Reminder: call"), "Reminder: call");
+        assert_eq!(normalize_subject("Re your note"), "Re your note");
+        assert_eq!(message_ids_in(" <a@x>\r\n <b@x>"), ["a@x", "b@x"]);
+    }
+
+    fn message(id: &str, message_id: &str, refs: &[&str], subject: &str, ts: i64) -> ThreadMessage {
+        ThreadMessage {
+            id: id.to_string(),
+            message_id: message_id.to_string(),
+            in_reply_to: refs.last().map(|r| r.to_string()),
+            references: refs.iter().map(|r| r.to_string()).collect(),
+            subject: subject.to_string(),
+            from: format!("User {} <{}@example.com>", id, id),
+            from_email: format!("{}@example.com", id),
+            timestamp: ts,
+            is_read: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_group_threads_by_references_subject_and_gmail_id() {
+        let mut gmail_a = message("g1", "g1@x", &[], "Lunch", 50);
+        gmail_a.gmail_thread_id = Some(255);
+        let mut gmail_b = message("g2", "g2@x", &[], "Re: Lunch?", 60);
+        gmail_b.gmail_thread_id = Some(255);
+
+        let threads = group_threads(vec![
+            message("a", "root@x", &[], "Plans", 10),
+            // Both reply to a message that isn't in the folder
+            message("b", "b@x", &["root@x", "missing@x"], "Re: Plans", 20),
+            message("c", "c@x", &["root@x", "missing@x"], "AW: Plans", 30),
+            // Lost its headers
+            ThreadMessage {
+                is_read: false,
+                ..message("d", "d@x", &[], "RE: plans", 40)
+            },
+            // Same subject, but a new conversation
+            message("e", "e@x", &[], "Plans", 5),
+            gmail_a,
+            gmail_b,
+        ]);
+
+        let summary: Vec<(&str, Vec<&str>)> = threads
+            .iter()
+            .map(|t| {
+                let ids = t.email_ids.iter().map(String::as_str).collect();
+                (t.thread_id.as_str(), ids)
+            })
+            .collect();
+        let root_hash = format!("{:x}", md5::compute("root@x"));
+        let e_hash = format!("{:x}", md5::compute("e@x"));
+        assert_eq!(
+            summary,
+            [
+                ("ff", vec!["g2", "g1"]),
+                (root_hash.as_str(), vec!["d", "c", "b", "a"]),
+                (e_hash.as_str(), vec!["e"]),
+            ]
+        );
+
+        let plans = &threads[1];
+        assert_eq!(plans.subject, "Plans");
+        assert_eq!(plans.message_count, 4);
+        assert_eq!(plans.unread_count, 1);
+        assert_eq!(plans.participants, ["User a", "User b", "User c", "User d"]);
+    }
+}
@@ -0,0 +1,375 @@
+use anyhow::{Context, Result};
+use async_native_tls::TlsConnector;
+use futures::io::{AsyncRead, AsyncWrite};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead as TokioRead, AsyncWrite as TokioWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, Sleep};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use super::server_presets::TlsMode;
+
+/// Byte stream an IMAP session runs over
+pub trait ImapStream: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug> ImapStream for T {}
+
+/// Opens connections for an `ImapClient`: a TLS socket in production,
+/// an in-memory scripted server in tests
+#[async_trait::async_trait]
+pub trait ImapTransport: Send + Sync {
+    async fn open(&self) -> Result<Box<dyn ImapStream>>;
+}
+
+/// TCP connection to the account's IMAP server, secured according to `mode`
+pub struct TlsTransport {
+    pub host: String,
+    pub port: u16,
+    pub mode: TlsMode,
+}
+
+#[async_trait::async_trait]
+impl ImapTransport for TlsTransport {
+    async fn open(&self) -> Result<Box<dyn ImapStream>> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .context("Failed to connect to IMAP server")?;
+
+        let tcp = match self.mode {
+            TlsMode::None => return Ok(Box::new(tcp.compat())),
+            TlsMode::StartTls => start_tls(tcp).await?,
+            TlsMode::Implicit => tcp,
+        };
+
+        // Convert tokio TcpStream to futures_io compatible stream
+        let tls_stream = TlsConnector::new()
+            .connect(&self.host, tcp.compat())
+            .await
+            .context("TLS handshake failed")?;
+
+        Ok(Box::new(tls_stream))
+    }
+}
+
+/// Timeouts for an `ImapClient`'s connections
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImapTimeouts {
+    /// TCP connect, TLS handshake and login
+    pub connect: Duration,
+    /// Longest a read may wait for data (IDLE extends this while idling)
+    pub read: Duration,
+    /// Longest a write or flush may stall
+    pub write: Duration,
+}
+
+impl Default for ImapTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(30),
+            read: Duration::from_secs(60),
+            write: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Shared by a client and its current stream. Once a read or write has timed
+/// out the session is unusable, and the client reconnects on next use.
+#[derive(Debug, Default)]
+pub struct StreamWatchdog {
+    expired: AtomicBool,
+    /// Read timeout in ms while IDLEing; 0 uses the configured one
+    idle_read_ms: AtomicU64,
+}
+
+impl StreamWatchdog {
+    pub fn expired(&self) -> bool {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    /// Clear the state of the previous stream before opening a new one
+    pub fn reset(&self) {
+        self.expired.store(false, Ordering::Relaxed);
+        self.idle_read_ms.store(0, Ordering::Relaxed);
+    }
+
+    /// Let reads wait up to `timeout` while IDLEing; None restores the
+    /// configured read timeout
+    pub fn set_idle_read_timeout(&self, timeout: Option<Duration>) {
+        let ms = timeout.map_or(0, |t| t.as_millis().max(1) as u64);
+        self.idle_read_ms.store(ms, Ordering::Relaxed);
+    }
+
+    fn read_timeout(&self, configured: Duration) -> Duration {
+        match self.idle_read_ms.load(Ordering::Relaxed) {
+            0 => configured,
+            ms => Duration::from_millis(ms),
+        }
+    }
+}
+
+/// Wraps a connection so a read or write that makes no progress for its
+/// timeout fails with `TimedOut`, instead of a half-open socket hanging the
+/// command (and the client's session lock) forever
+#[derive(Debug)]
+pub struct TimeoutStream {
+    inner: Box<dyn ImapStream>,
+    timeouts: ImapTimeouts,
+    watchdog: Arc<StreamWatchdog>,
+    read_deadline: Option<Pin<Box<Sleep>>>,
+    write_deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl TimeoutStream {
+    pub fn new(
+        inner: Box<dyn ImapStream>,
+        timeouts: ImapTimeouts,
+        watchdog: Arc<StreamWatchdog>,
+    ) -> Self {
+        Self {
+            inner,
+            timeouts,
+            watchdog,
+            read_deadline: None,
+            write_deadline: None,
+        }
+    }
+}
+
+/// Pass a ready result through; while pending, run a timer of `limit` and
+/// fail with `TimedOut` (expiring the watchdog) once it fires
+fn watch<T>(
+    result: Poll<io::Result<T>>,
+    deadline: &mut Option<Pin<Box<Sleep>>>,
+    limit: Duration,
+    watchdog: &StreamWatchdog,
+    cx: &mut TaskContext<'_>,
+    operation: &str,
+) -> Poll<io::Result<T>> {
+    if result.is_ready() {
+        *deadline = None;
+        return result;
+    }
+    let timer = deadline.get_or_insert_with(|| Box::pin(sleep(limit)));
+    if timer.as_mut().poll(cx).is_pending() {
+        return Poll::Pending;
+    }
+    *deadline = None;
+    watchdog.expired.store(true, Ordering::Relaxed);
+    Poll::Ready(Err(timed_out(operation, limit)))
+}
+
+fn timed_out(operation: &str, limit: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("IMAP {} timed out after {}s", operation, limit.as_secs()),
+    )
+}
+
+impl AsyncRead for TimeoutStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let limit = this.watchdog.read_timeout(this.timeouts.read);
+        if this.watchdog.expired() {
+            return Poll::Ready(Err(timed_out("read", limit)));
+        }
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        watch(
+            result,
+            &mut this.read_deadline,
+            limit,
+            &this.watchdog,
+            cx,
+            "read",
+        )
+    }
+}
+
+impl AsyncWrite for TimeoutStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let limit = this.timeouts.write;
+        if this.watchdog.expired() {
+            return Poll::Ready(Err(timed_out("write", limit)));
+        }
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        watch(
+            result,
+            &mut this.write_deadline,
+            limit,
+            &this.watchdog,
+            cx,
+            "write",
+        )
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let limit = this.timeouts.write;
+        let result = Pin::new(&mut this.inner).poll_flush(cx);
+        watch(
+            result,
+            &mut this.write_deadline,
+            limit,
+            &this.watchdog,
+            cx,
+            "write",
+        )
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+/// Whether an error was caused by a connect, read or write timeout
+pub fn is_timeout(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.is::<tokio::time::error::Elapsed>()
+            || cause
+                .downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::TimedOut)
+    })
+}
+
+/// Read the greeting, check the server offers STARTTLS and issue it, leaving
+/// the stream ready for the TLS handshake. The session proper (login,
+/// CAPABILITY) starts again over TLS, as RFC 3501 requires.
+async fn start_tls<S: TokioRead + TokioWrite + Unpin>(stream: S) -> Result<S> {
+    let mut reader = BufReader::new(stream);
+
+    let greeting = read_line(&mut reader).await?;
+    if !greeting.starts_with("* OK") {
+        anyhow::bail!("Unexpected IMAP greeting: {}", greeting.trim_end());
+    }
+
+    let capabilities = command(&mut reader, "S1", "CAPABILITY").await?;
+    let offered = capabilities
+        .iter()
+        .filter(|line| line.starts_with("* CAPABILITY"))
+        .flat_map(|line| line.split_whitespace())
+        .any(|capability| capability.eq_ignore_ascii_case("STARTTLS"));
+    if !offered {
+        anyhow::bail!("STARTTLS was requested but the IMAP server doesn't offer it");
+    }
+
+    command(&mut reader, "S2", "STARTTLS").await?;
+    if !reader.buffer().is_empty() {
+        // Anything sent before the handshake could be injected plaintext
+        anyhow::bail!("IMAP server sent data before the TLS handshake");
+    }
+    Ok(reader.into_inner())
+}
+
+/// Send a tagged command and return its untagged responses; fails unless the
+/// tagged response is OK
+async fn command<S: TokioRead + TokioWrite + Unpin>(
+    reader: &mut BufReader<S>,
+    tag: &str,
+    command: &str,
+) -> Result<Vec<String>> {
+    reader
+        .get_mut()
+        .write_all(format!("{} {}\r\n", tag, command).as_bytes())
+        .await
+        .context("Failed to write to IMAP server")?;
+
+    let mut untagged = Vec::new();
+    loop {
+        let line = read_line(reader).await?;
+        if let Some(status) = line.strip_prefix(tag).map(str::trim) {
+            if status.len() >= 2 && status[..2].eq_ignore_ascii_case("OK") {
+                return Ok(untagged);
+            }
+            anyhow::bail!("{} failed: {}", command, status);
+        }
+        untagged.push(line);
+    }
+}
+
+async fn read_line<S: TokioRead + Unpin>(reader: &mut BufReader<S>) -> Result<String> {
+    let mut line = String::new();
+    let read = reader
+        .read_line(&mut line)
+        .await
+        .context("Failed to read from IMAP server")?;
+    if read == 0 {
+        anyhow::bail!("IMAP server closed the connection");
+    }
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer the client's commands from a script of (expected command, reply)
+    async fn serve(stream: tokio::io::DuplexStream, script: &[(&str, &str)]) {
+        let mut reader = BufReader::new(stream);
+        reader.get_mut().write_all(b"* OK ready\r\n").await.unwrap();
+        for (expected, reply) in script {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            assert_eq!(line.trim_end(), *expected);
+            reader.get_mut().write_all(reply.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_read_times_out_and_expires_watchdog() {
+        use futures::io::AsyncReadExt;
+
+        let (client, _server) = tokio::io::duplex(1024);
+        let watchdog = Arc::new(StreamWatchdog::default());
+        let timeouts = ImapTimeouts {
+            read: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let mut stream = TimeoutStream::new(Box::new(client.compat()), timeouts, watchdog.clone());
+
+        let mut buf = [0u8; 16];
+        let err = stream.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(watchdog.expired());
+        assert!(is_timeout(&anyhow::Error::new(err)));
+
+        watchdog.reset();
+        assert!(!watchdog.expired());
+    }
+
+    #[tokio::test]
+    async fn test_start_tls_requires_capability() {
+        let (client, server) = tokio::io::duplex(1024);
+        let script = [
+            (
+                "S1 CAPABILITY",
+                "* CAPABILITY IMAP4rev1 STARTTLS\r\nS1 OK done\r\n",
+            ),
+            ("S2 STARTTLS", "S2 OK Begin TLS negotiation now\r\n"),
+        ];
+        let server = tokio::spawn(async move { serve(server, &script).await });
+        assert!(start_tls(client).await.is_ok());
+        server.await.unwrap();
+
+        let (client, server) = tokio::io::duplex(1024);
+        let script = [("S1 CAPABILITY", "* CAPABILITY IMAP4rev1\r\nS1 OK done\r\n")];
+        tokio::spawn(async move { serve(server, &script).await });
+        let err = start_tls(client).await.unwrap_err();
+        assert!(err.to_string().contains("doesn't offer it"));
+    }
+}
@@ -1,5 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+use super::attachment_safety::AttachmentSafety;
+use super::attachments::{AttachmentMeta, InlinePartMeta};
+use super::auth_results::{AuthenticationResults, AuthenticationSummary};
+use super::calendar::Invite;
+use super::headers::RawHeader;
+use super::mailing_list::{MailingList, UnsubscribeInfo};
+use super::security::MessageSecurity;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Email {
     pub id: String,
@@ -8,6 +16,11 @@ pub struct Email {
     pub from: String,
     pub from_email: String,
     pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    /// Reply-To addresses; replies go here instead of `from` when present
+    #[serde(default)]
+    pub reply_to: Vec<String>,
     pub date: String,
     pub date_timestamp: i64,
     pub snippet: String,
@@ -22,6 +35,45 @@ pub struct Email {
     pub uid: u32,
     pub folder: String,
     pub message_id: String,
+    /// First message ever received from this sender on this account
+    #[serde(default)]
+    pub is_first_contact: bool,
+    /// Present when the message came through a mailing list
+    #[serde(default)]
+    pub mailing_list: Option<MailingList>,
+    /// How to unsubscribe, from List-Unsubscribe(-Post)
+    #[serde(default)]
+    pub unsubscribe: Option<UnsubscribeInfo>,
+    #[serde(default)]
+    pub sync_state: SyncState,
+    /// S/MIME or PGP encryption/signing found in the MIME structure
+    #[serde(default)]
+    pub security: MessageSecurity,
+    /// RFC822 size in bytes
+    #[serde(default)]
+    pub size: u32,
+    /// Attachments found in BODYSTRUCTURE, including ones in nested multiparts
+    #[serde(default)]
+    pub attachments: Vec<AttachmentMeta>,
+    /// Parts the HTML body references as `cid:` URLs; not in `attachments`
+    #[serde(default)]
+    pub inline_parts: Vec<InlinePartMeta>,
+    /// Remote images in `body_html` were replaced by a placeholder
+    #[serde(default)]
+    pub remote_content_blocked: bool,
+    /// Message-ID this message replies to, without angle brackets like `message_id`
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    /// Message-IDs of the thread ancestors, oldest first
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// Meeting invite or cancellation from a text/calendar part
+    #[serde(default)]
+    pub invite: Option<Invite>,
+    /// SPF/DKIM/DMARC verdicts from the receiving server's
+    /// Authentication-Results header; None when it didn't add one
+    #[serde(default)]
+    pub authentication: Option<AuthenticationSummary>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +88,215 @@ pub struct EmailListItem {
     pub is_read: bool,
     pub is_starred: bool,
     pub has_attachments: bool,
+    /// First message ever received from this sender on this account
+    #[serde(default)]
+    pub is_first_contact: bool,
+    #[serde(default)]
+    pub sync_state: SyncState,
+    /// RFC822 size in bytes
+    #[serde(default)]
+    pub size: u32,
+}
+
+/// Cached copies not refreshed from the server for this long are reported as stale
+pub const CACHE_STALE_AFTER_SECS: i64 = 24 * 60 * 60;
+
+/// How complete and how fresh the data behind an email or list item is
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SyncState {
+    /// Envelope and snippet only; the body must be fetched before display
+    #[default]
+    CachedHeadersOnly,
+    /// Full message (headers and body) is in the local cache
+    CachedFull,
+    /// Cached copy is older than `CACHE_STALE_AFTER_SECS`; flags may be out of date
+    Stale,
+    /// Fetched from the server by this request
+    LiveFetched,
+}
+
+impl SyncState {
+    /// State of a cached row given whether its body was stored and when it was last written
+    pub fn from_cache(has_body: bool, updated_at: i64, now: i64) -> Self {
+        if now - updated_at > CACHE_STALE_AFTER_SECS {
+            SyncState::Stale
+        } else if has_body {
+            SyncState::CachedFull
+        } else {
+            SyncState::CachedHeadersOnly
+        }
+    }
+}
+
+/// Unparsed message source for a "Show original" view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OriginalMessage {
+    pub email_id: String,
+    /// Header block exactly as stored on the server
+    pub raw_headers: String,
+    /// Header fields in their original order
+    pub headers: Vec<RawHeader>,
+    /// Raw message text (everything after the header block), possibly truncated
+    pub raw_body: String,
+    /// True when `raw_body` was cut off at the size cap
+    pub body_truncated: bool,
+    /// Full RFC822 size of the message in bytes
+    pub total_size: u32,
+    pub authentication_results: Vec<AuthenticationResults>,
+}
+
+/// Result of saving an attachment to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentDownload {
+    pub email_id: String,
+    pub index: u32,
+    pub filename: String,
+    pub content_type: String,
+    /// Decoded size in bytes
+    pub size: u64,
+    /// Encoded size declared by the server, verified after download
+    pub encoded_size: u64,
+    /// Offset an interrupted download was resumed from (0 for a fresh download)
+    pub resumed_from: u64,
+    pub path: String,
+    pub sha256: String,
+    pub safety: AttachmentSafety,
+}
+
+/// Decoded content of one attachment, returned by `get_attachment`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentContent {
+    pub part_id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Payload of the "attachment:progress" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentProgress {
+    pub email_id: String,
+    pub index: u32,
+    pub received: u64,
+    pub total: u64,
+}
+
+/// One page of a folder listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailPage {
+    pub items: Vec<EmailListItem>,
+    /// Pass back as `before_uid` for the next page. None when this page is
+    /// empty, which is the end of the folder.
+    pub next_cursor: Option<u32>,
+}
+
+/// UIDs that appeared in or disappeared from a folder during one IDLE
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FolderChanges {
+    /// Ascending
+    pub new_uids: Vec<u32>,
+    /// Ascending
+    pub expunged_uids: Vec<u32>,
+}
+
+/// Where the last incremental sync of a folder left off
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FolderSyncState {
+    pub uid_validity: u32,
+    /// UIDs at or above this arrived after the sync
+    pub uid_next: u32,
+    /// HIGHESTMODSEQ (RFC 7162); None when the server lacks CONDSTORE
+    pub highest_modseq: Option<u64>,
+}
+
+/// What an IDLE monitor last saw of a folder, so a reconnect can tell what
+/// arrived or was expunged while it was disconnected
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IdleFolderState {
+    pub uid_validity: u32,
+    /// UIDs at or above this arrived after the state was saved
+    pub uid_next: u32,
+    /// Ascending
+    pub uids: Vec<u32>,
+}
+
+impl IdleFolderState {
+    /// Fold in the changes an IDLE wakeup reported
+    pub fn apply(&mut self, changes: &FolderChanges) {
+        self.uids.retain(|uid| !changes.expunged_uids.contains(uid));
+        self.uids.extend(&changes.new_uids);
+        self.uids.sort_unstable();
+        self.uids.dedup();
+        if let Some(last) = changes.new_uids.last() {
+            self.uid_next = self.uid_next.max(last + 1);
+        }
+    }
+}
+
+/// Read/starred state of a message as reported by the server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MessageFlags {
+    pub uid: u32,
+    pub is_read: bool,
+    pub is_starred: bool,
+}
+
+/// Changes in a folder since a `FolderSyncState`
+#[derive(Debug, Clone, Default)]
+pub struct FolderDelta {
+    /// State to resume from next time
+    pub state: FolderSyncState,
+    /// Messages whose flags changed, including new ones
+    pub messages: Vec<MessageFlags>,
+    /// Every UID in the folder; cached messages missing from it were expunged
+    pub uids: Vec<u32>,
+    /// `messages` covers every message in the folder (no CONDSTORE, no usable
+    /// previous state) rather than only the changed ones
+    pub complete: bool,
+}
+
+/// Stage of a `sync_folder` run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncPhase {
+    Connecting,
+    Searching,
+    Fetching,
+    Embedding,
+}
+
+/// Totals reported when a `sync_folder` run ends
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FolderSyncSummary {
+    pub folder: String,
+    /// Messages on the listed page
+    pub listed: u32,
+    /// Messages downloaded and cached
+    pub fetched: u32,
+    pub failed: u32,
+    /// Raw size of the downloaded messages
+    pub bytes: u64,
+    pub classified: u32,
+    pub embedded: u32,
+    pub cancelled: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Message sent over the `sync_folder` channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum FolderSyncEvent {
+    Progress {
+        request_id: String,
+        phase: SyncPhase,
+        current: u32,
+        total: u32,
+        bytes: u64,
+    },
+    Completed {
+        request_id: String,
+        summary: FolderSyncSummary,
+    },
 }
 
 /// Represents an IMAP folder/mailbox
@@ -43,16 +304,23 @@ pub struct EmailListItem {
 pub struct Folder {
     /// Full folder path (e.g., "[Gmail]/Sent Mail")
     pub name: String,
+    /// Name exactly as listed by the server (modified UTF-7 unless UTF8=ACCEPT is on)
+    pub raw_name: String,
     /// Display name (e.g., "Sent Mail")
     pub display_name: String,
     /// Special folder type, if detected
     pub special: Option<SpecialFolder>,
     /// Hierarchy delimiter (e.g., "/")
     pub delimiter: Option<String>,
+    /// SPECIAL-USE attributes advertised in LIST (e.g., `\Sent`); empty when unsupported
+    #[serde(default)]
+    pub special_use: Vec<String>,
+    /// False for `\Noselect` entries that only exist to hold child folders
+    pub selectable: bool,
 }
 
 /// Well-known special folder types (RFC 6154)
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum SpecialFolder {
     Inbox,
     Sent,
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::types::EmailListItem;
+
+/// How the per-account lists are interleaved in the unified inbox
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeOrder {
+    /// Newest first
+    #[default]
+    Date,
+    /// Highest AI priority score first, newest first among equal scores
+    Importance,
+}
+
+/// Controls for fetching and merging the unified inbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnifiedInboxOptions {
+    /// Accounts fetched in parallel
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Most messages taken from any single account (defaults to `max_results`)
+    #[serde(default)]
+    pub per_account_limit: Option<u32>,
+    #[serde(default)]
+    pub order: MergeOrder,
+    /// Accounts that haven't answered by then are left out of this merge
+    #[serde(default = "default_account_timeout_ms")]
+    pub account_timeout_ms: u64,
+}
+
+fn default_max_concurrency() -> usize {
+    4
+}
+
+fn default_account_timeout_ms() -> u64 {
+    15_000
+}
+
+impl Default for UnifiedInboxOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrency: default_max_concurrency(),
+            per_account_limit: None,
+            order: MergeOrder::default(),
+            account_timeout_ms: default_account_timeout_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnifiedInbox {
+    pub emails: Vec<EmailListItem>,
+    /// True when at least one account was dropped (timed out or failed)
+    pub incomplete: bool,
+    pub incomplete_accounts: Vec<String>,
+}
+
+/// Unix timestamp of a list item's RFC 2822 date (0 if unparseable)
+pub fn list_item_timestamp(item: &EmailListItem) -> i64 {
+    chrono::DateTime::parse_from_rfc2822(item.date.trim())
+        .map(|dt| dt.timestamp())
+        .unwrap_or(0)
+}
+
+/// K-way merge of streams that are each sorted by descending `key`.
+/// Only the head of each stream is compared, and merging stops after `limit` items.
+pub fn kway_merge<T, K: Ord>(
+    streams: Vec<Vec<T>>,
+    key: impl Fn(&T) -> K,
+    limit: usize,
+) -> Vec<T> {
+    let mut iters: Vec<_> = streams.into_iter().map(|s| s.into_iter()).collect();
+    let mut heads: Vec<Option<T>> = iters.iter_mut().map(|it| it.next()).collect();
+
+    // Ties go to the lower stream index so the output is deterministic
+    let mut heap = BinaryHeap::new();
+    for (i, head) in heads.iter().enumerate() {
+        if let Some(item) = head {
+            heap.push((key(item), Reverse(i)));
+        }
+    }
+
+    let mut merged = Vec::with_capacity(limit);
+    while merged.len() < limit {
+        let Some((_, Reverse(i))) = heap.pop() else {
+            break;
+        };
+        if let Some(item) = heads[i].take() {
+            merged.push(item);
+        }
+        heads[i] = iters[i].next();
+        if let Some(next) = &heads[i] {
+            heap.push((key(next), Reverse(i)));
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kway_merge_interleaves_and_stops_at_limit() {
+        let streams = vec![vec![9, 5, 1], vec![8, 7, 2], vec![], vec![6]];
+        assert_eq!(kway_merge(streams.clone(), |v| *v, 4), vec![9, 8, 7, 6]);
+        assert_eq!(kway_merge(streams, |v| *v, 100), vec![9, 8, 7, 6, 5, 2, 1]);
+    }
+}
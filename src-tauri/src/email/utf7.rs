@@ -0,0 +1,122 @@
+//! Modified UTF-7 mailbox names (RFC 3501 section 5.1.3), used on the wire
+//! unless the server accepted `ENABLE UTF8=ACCEPT`.
+
+use base64::alphabet::IMAP_MUTF7;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+
+const MUTF7: GeneralPurpose = GeneralPurpose::new(
+    &IMAP_MUTF7,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::RequireNone),
+);
+
+/// Encode a UTF-8 mailbox name as modified UTF-7
+pub fn encode_imap_utf7(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut pending: Vec<u16> = Vec::new();
+
+    for ch in name.chars() {
+        if (' '..='~').contains(&ch) {
+            flush_utf16(&mut pending, &mut out);
+            if ch == '&' {
+                out.push_str("&-");
+            } else {
+                out.push(ch);
+            }
+        } else {
+            let mut buf = [0u16; 2];
+            pending.extend_from_slice(ch.encode_utf16(&mut buf));
+        }
+    }
+    flush_utf16(&mut pending, &mut out);
+    out
+}
+
+fn flush_utf16(pending: &mut Vec<u16>, out: &mut String) {
+    if pending.is_empty() {
+        return;
+    }
+    let bytes: Vec<u8> = pending.iter().flat_map(|unit| unit.to_be_bytes()).collect();
+    out.push('&');
+    out.push_str(&MUTF7.encode(bytes));
+    out.push('-');
+    pending.clear();
+}
+
+/// Decode a modified UTF-7 mailbox name. Malformed shifted sections are kept verbatim.
+pub fn decode_imap_utf7(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let shifted = &rest[start + 1..];
+        let Some(end) = shifted.find('-') else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+
+        let encoded = &shifted[..end];
+        if encoded.is_empty() {
+            out.push('&');
+        } else {
+            match decode_utf16_section(encoded) {
+                Some(decoded) => out.push_str(&decoded),
+                None => out.push_str(&rest[start..start + end + 2]),
+            }
+        }
+        rest = &shifted[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_utf16_section(encoded: &str) -> Option<String> {
+    let bytes = MUTF7.decode(encoded).ok()?;
+    if bytes.len() % 2 != 0 {
+        return None;
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf7_round_trip() {
+        let cases = [
+            ("INBOX", "INBOX"),
+            ("Entwürfe", "Entw&APw-rfe"),
+            ("Tom & Jerry", "Tom &- Jerry"),
+            ("日本語", "&ZeVnLIqe-"),
+            ("[Gmail]/Корзина", "[Gmail]/&BBoEPgRABDcEOAQ9BDA-"),
+        ];
+        for (utf8, wire) in cases {
+            assert_eq!(encode_imap_utf7(utf8), wire);
+            assert_eq!(decode_imap_utf7(wire), utf8);
+        }
+
+        // Examples from RFC 3501 section 5.1.3 and RFC 2152
+        assert_eq!(
+            decode_imap_utf7("~peter/mail/&U,BTFw-/&ZeVnLIqe-"),
+            "~peter/mail/台北/日本語"
+        );
+        assert_eq!(
+            encode_imap_utf7("~peter/mail/台北/日本語"),
+            "~peter/mail/&U,BTFw-/&ZeVnLIqe-"
+        );
+        assert_eq!(decode_imap_utf7("&Jjo-"), "\u{263A}");
+        assert_eq!(decode_imap_utf7("Hi Mom -&Jjo--!"), "Hi Mom -\u{263A}-!");
+
+        // Broken sections are passed through rather than dropped
+        assert_eq!(decode_imap_utf7("Bad&AP"), "Bad&AP");
+    }
+}
@@ -7,6 +7,7 @@ mod llm;
 use commands::account::AccountManager;
 use directories::ProjectDirs;
 use email::idle::IdleManager;
+use email::outbox::{Outbox, FLUSH_TIMEOUT};
 use std::sync::{Arc, Mutex};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -21,11 +22,31 @@ pub fn run() {
     std::fs::create_dir_all(data_dir).expect("Failed to create data directory");
     let db_path = data_dir.join("emails.db");
     let database = db::EmailDatabase::new(db_path).expect("Failed to initialize database");
+    let cache_settings = commands::load_cache_settings().unwrap_or_default();
+    let retention_days = cache_settings.max_cache_age_days;
+    let cutoff = chrono::Utc::now().timestamp() - i64::from(retention_days) * 24 * 60 * 60;
+    match database.prune_cache(cutoff) {
+        Ok(0) => {}
+        Ok(removed) => println!(
+            "[Cache] Pruned {} emails older than {} days",
+            removed, retention_days
+        ),
+        Err(e) => eprintln!("[Cache] Failed to prune cache: {}", e),
+    }
     let db_state = Arc::new(Mutex::new(Some(database)));
 
     // Initialize account manager and IDLE manager
-    let account_manager = AccountManager::new();
+    let account_manager = match commands::app_settings().imap_pool_size {
+        Some(size) => AccountManager::with_pool_size(size),
+        None => AccountManager::new(),
+    }
+    .with_database(db_state.clone());
+    account_manager
+        .body_cache()
+        .set_capacity(cache_settings.body_cache_size);
+    tauri::async_runtime::spawn(account_manager.close_idle_connections());
     let idle_manager = IdleManager::new();
+    let outbox = Outbox::new();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
@@ -33,6 +54,12 @@ pub fn run() {
         .manage(db_state)
         .manage(account_manager)
         .manage(idle_manager)
+        .manage(outbox.clone())
+        .setup(|app| {
+            // Also brings back snoozes that fell due while the app was closed
+            email::snooze::spawn_snooze_worker(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // Auth commands
             commands::check_auth_status,
@@ -42,21 +69,77 @@ pub fn run() {
             commands::sign_out,
             commands::get_access_token,
             // Account commands
+            commands::discover_server_config,
+            commands::get_provider_preset,
+            commands::test_connection,
             commands::add_account,
             commands::remove_account,
             commands::list_accounts,
             commands::set_active_account,
             commands::connect_account,
+            commands::find_duplicate_accounts,
+            commands::merge_duplicate_accounts,
+            commands::get_account_summary,
+            commands::pause_account,
+            commands::resume_account,
+            commands::get_signature,
+            commands::set_signature,
+            commands::add_alias,
+            commands::list_aliases,
             // Email commands
             commands::fetch_emails,
+            commands::list_threads,
+            commands::get_folder_sort,
+            commands::set_folder_sort,
+            commands::sync_folder,
+            commands::cancel_folder_sync,
+            commands::fetch_unified_inbox,
             commands::get_email,
+            commands::load_remote_content,
+            commands::get_readable_text,
+            commands::respond_to_invite,
+            commands::allow_remote_content,
+            commands::get_unread_ids,
+            commands::get_original,
+            commands::get_raw_message,
+            commands::save_raw_message,
+            commands::get_message_security,
+            commands::get_attachment,
+            commands::get_inline_part,
+            commands::download_attachment,
+            commands::open_attachment,
             commands::send_email,
+            commands::get_reply_recipients,
+            commands::cancel_send,
+            commands::save_draft,
             commands::mark_email_read,
+            commands::mark_emails_read,
+            commands::mark_folder_read,
             commands::star_email,
+            commands::get_email_flags,
+            commands::set_email_keywords,
             commands::trash_email,
             commands::archive_email,
+            commands::copy_email,
+            commands::move_email,
+            commands::unsubscribe_email,
+            commands::snooze_email,
+            commands::unsnooze_email,
+            commands::list_snoozed,
+            commands::add_rule,
+            commands::list_rules,
+            commands::delete_rule,
+            commands::mark_as_spam,
+            commands::get_special_folders,
             commands::start_idle_monitoring,
             commands::stop_idle_monitoring,
+            commands::suspend_idle_monitoring,
+            commands::resume_idle_monitoring,
+            commands::get_idle_status,
+            commands::list_folders,
+            commands::create_folder,
+            commands::rename_folder,
+            commands::delete_folder,
             commands::get_folder_stats,
             // AI commands
             commands::check_model_status,
@@ -69,6 +152,11 @@ pub fn run() {
             commands::summarize_email_stream,
             commands::get_email_insights,
             commands::classify_priority,
+            commands::explain_email,
+            commands::summarize_thread,
+            commands::suggest_replies,
+            commands::chat_stream,
+            commands::cancel_llm_stream,
             commands::get_model_info,
             commands::get_available_ai_models,
             commands::get_current_model_id,
@@ -80,9 +168,12 @@ pub fn run() {
             commands::init_database,
             commands::get_smart_inbox,
             commands::get_emails_by_category,
+            commands::get_category_counts,
+            commands::list_mailing_lists,
             commands::get_indexing_status,
             commands::reset_indexing_status,
             commands::start_email_indexing,
+            commands::reprocess_email,
             commands::search_smart_emails,
             commands::get_emails_by_account_and_category,
             commands::chat_query,
@@ -91,12 +182,14 @@ pub fn run() {
             commands::get_cache_settings,
             commands::save_cache_settings,
             commands::clear_email_cache,
+            commands::clear_message_cache,
             commands::clear_media_cache,
             commands::clear_all_caches,
             commands::cache_media_asset,
             commands::get_cached_media_asset,
             commands::get_cached_emails_count,
             commands::has_cached_emails,
+            commands::search_cached,
             commands::clear_all_app_data,
             commands::clear_ai_models,
             // RAG commands
@@ -106,12 +199,44 @@ pub fn run() {
             commands::get_embedding_status,
             commands::embed_email,
             commands::embed_all_emails,
+            commands::needs_reindex,
+            commands::reindex_all,
             commands::search_emails_semantic,
+            commands::search_emails_hybrid,
             commands::find_similar_emails,
+            commands::related_emails,
+            commands::compute_thread_embeddings,
+            commands::search_similar_threads,
             commands::get_embedded_count,
             commands::clear_embeddings,
             commands::chat_with_context,
+            commands::chat_with_context_stream,
+            commands::list_categories,
+            commands::set_category,
+            commands::remove_category,
+            commands::reset_categories,
+            // Settings commands
+            commands::get_app_settings,
+            commands::set_local_only,
+            commands::set_attachment_blocklist,
+            commands::set_undo_send_delay,
+            commands::set_embedding_chunking,
+            commands::set_keep_quoted_text,
+            commands::set_embedding_quantization,
+            commands::set_category_threshold,
+            commands::set_vector_search_probes,
+            commands::set_poll_interval,
+            commands::set_attachment_limit,
+            commands::system_health,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |_app, event| {
+            // Sends still inside their undo window go out now rather than being lost
+            if let tauri::RunEvent::Exit = event {
+                if outbox.pending_count() > 0 {
+                    tauri::async_runtime::block_on(outbox.flush(FLUSH_TIMEOUT));
+                }
+            }
+        });
 }
@@ -7,6 +7,7 @@ use llama_cpp_2::model::LlamaModel;
 use llama_cpp_2::sampling::LlamaSampler;
 use std::num::NonZeroU32;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, Once};
 
 /// Default generation parameters
@@ -97,6 +98,21 @@ impl LlmEngine {
         &self,
         prompt: &str,
         params: &GenerationParams,
+        on_token: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        self.generate_stream_cancellable(prompt, params, &AtomicBool::new(false), on_token)
+    }
+
+    /// Generate text with streaming callback, stopping early (and returning
+    /// the text so far) once `cancel` is set
+    pub fn generate_stream_cancellable<F>(
+        &self,
+        prompt: &str,
+        params: &GenerationParams,
+        cancel: &AtomicBool,
         mut on_token: F,
     ) -> Result<String>
     where
@@ -178,6 +194,11 @@ impl LlmEngine {
                 break;
             }
 
+            if cancel.load(Ordering::Relaxed) {
+                println!("[AI] Generation cancelled after {} tokens", n_cur - tokens.len());
+                break;
+            }
+
             // Sample the next token from the last logit position
             let new_token = sampler.sample(&ctx, -1);
 
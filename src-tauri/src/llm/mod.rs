@@ -1,6 +1,7 @@
 pub mod embeddings;
 pub mod engine;
 pub mod model_manager;
+pub mod prompts;
 pub mod rag;
 pub mod summarizer;
 
@@ -11,4 +12,6 @@ pub use model_manager::{
     DEFAULT_MODEL_REPO,
 };
 pub use rag::RagEngine;
-pub use summarizer::Summarizer;
+pub use summarizer::{
+    EmailExplanation, ExplainLevel, ReplySuggestion, Summarizer, ThreadEntry, ThreadSummary,
+};
@@ -0,0 +1,375 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// File next to the downloaded models that overrides the built-in prompts
+pub const PROMPT_OVERRIDES_FILE: &str = "prompts.json";
+
+/// Overrides under this key apply to every model family
+const ANY_MODEL: &str = "default";
+
+/// System and user text of a prompt. `{name}` is a placeholder, `{{`/`}}` are literal braces.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptTemplate {
+    pub system: String,
+    pub user: String,
+}
+
+/// A prompt shipped with the app and the placeholders its caller fills in
+struct BuiltinPrompt {
+    name: &'static str,
+    system: &'static str,
+    user: &'static str,
+    placeholders: &'static [&'static str],
+    /// Placeholders an override must keep, or the model never sees the email/question
+    required: &'static [&'static str],
+}
+
+const BUILTIN_PROMPTS: &[BuiltinPrompt] = &[
+    BuiltinPrompt {
+        name: "summarize",
+        system: "You are a helpful email assistant. {instruction} Do not miss any important information.",
+        user: "Summarize this email:\n\nFrom: {from}\nSubject: {subject}\n\n{body}",
+        placeholders: &["instruction", "from", "subject", "body"],
+        required: &["body"],
+    },
+    BuiltinPrompt {
+        name: "insights",
+        system: "You are an email analysis assistant. List 1-3 key insights about emails. Each insight should be one short sentence. Format: one insight per line starting with an emoji.",
+        user: "Analyze this email:\n\nSubject: {subject}\n\n{body}",
+        placeholders: &["subject", "body"],
+        required: &["body"],
+    },
+    BuiltinPrompt {
+        name: "priority",
+        system: "You are an email priority classifier. Respond with exactly one word: HIGH, MEDIUM, or LOW.\n\n\
+            HIGH: Direct personal email requiring action or reply. Urgent, time-sensitive, from a real person.\n\
+            MEDIUM: Relevant but not urgent. Meeting invites, project updates, questions, team discussions.\n\
+            LOW: Automated, mass-sent, no action needed. Newsletters, promotions, notifications, service emails from noreply addresses.\n\n\
+            Examples:\n\
+            - Subject: \"Can you review this PR by tomorrow?\" From: john@company.com → HIGH\n\
+            - Subject: \"Your weekly digest\" From: noreply@service.com → LOW\n\
+            - Subject: \"Q3 planning meeting notes\" From: sarah@company.com → MEDIUM\n\
+            - Subject: \"Action required: approve expense report\" From: manager@company.com → HIGH\n\
+            - Subject: \"50% off summer sale!\" From: deals@store.com → LOW",
+        user: "Classify this email's priority:\n\nFrom: {from}\nSubject: {subject}\n\n{body}",
+        placeholders: &["from", "subject", "body"],
+        required: &["body"],
+    },
+    BuiltinPrompt {
+        name: "explain",
+        system: "You are a helpful email assistant who explains complex emails in plain language. {instruction} \
+            Do not add facts that are not in the email. \
+            After the explanation write a line \"TERMS:\" followed by up to 5 lines of the form \
+            \"- term: short meaning\" for the jargon you clarified.",
+        user: "Explain this email:\n\nFrom: {from}\nSubject: {subject}\n\n{body}",
+        placeholders: &["instruction", "from", "subject", "body"],
+        required: &["body"],
+    },
+    BuiltinPrompt {
+        name: "explain_with_context",
+        system: "You are a helpful email assistant who explains complex emails in plain language. {instruction} \
+            Do not add facts that are not in the email. \
+            After the explanation write a line \"TERMS:\" followed by up to 5 lines of the form \
+            \"- term: short meaning\" for the jargon you clarified.",
+        user: "Explain this email:\n\nFrom: {from}\nSubject: {subject}\n\n{body}\n\nRelated past emails (background only):\n{context}",
+        placeholders: &["instruction", "from", "subject", "body", "context"],
+        required: &["body"],
+    },
+    BuiltinPrompt {
+        name: "summarize_thread",
+        system: "You are a helpful email assistant. Summarize the email conversation in 2-4 sentences, covering what was discussed and decided. \
+            Then write a line \"ACTION ITEMS:\" followed by one \"- \" line per task someone still has to do (say who, if known), \
+            and a line \"OPEN QUESTIONS:\" followed by one \"- \" line per question nobody has answered yet. \
+            Write \"- None\" under a heading with nothing to list. Do not add facts that are not in the conversation.",
+        user: "Summarize this conversation:\n\nSubject: {subject}\n\n{conversation}",
+        placeholders: &["subject", "conversation"],
+        required: &["conversation"],
+    },
+    BuiltinPrompt {
+        name: "summarize_thread_part",
+        system: "You are a helpful email assistant. Write concise notes on one part of a longer email conversation: \
+            what was said and decided, tasks someone took on or was asked to do, and questions asked and whether they were answered. \
+            Keep names, figures and dates.",
+        user: "Part {part} of a conversation about \"{subject}\":\n\n{conversation}",
+        placeholders: &["part", "subject", "conversation"],
+        required: &["conversation"],
+    },
+    BuiltinPrompt {
+        name: "suggest_replies",
+        system: "You are a helpful email assistant drafting replies for the user. Write {count} different short replies (1-3 sentences each) \
+            to the last message of the conversation, each taking a different stance. \
+            Only mention dates, times, amounts and commitments that appear in the conversation; never make them up. \
+            Start each reply with a line \"REPLY: intent\", where intent is a 1-3 word label such as accept, decline or ask for time, \
+            followed by the reply text.",
+        user: "Conversation about \"{subject}\":\n\n{conversation}",
+        placeholders: &["count", "subject", "conversation"],
+        required: &["conversation"],
+    },
+    BuiltinPrompt {
+        name: "suggest_replies_with_context",
+        system: "You are a helpful email assistant drafting replies for the user. Write {count} different short replies (1-3 sentences each) \
+            to the last message of the conversation, each taking a different stance, in the tone of the user's past emails. \
+            Only mention dates, times, amounts and commitments that appear in the conversation; never make them up. \
+            Start each reply with a line \"REPLY: intent\", where intent is a 1-3 word label such as accept, decline or ask for time, \
+            followed by the reply text.",
+        user: "Conversation about \"{subject}\":\n\n{conversation}\n\nSimilar past emails (for tone only, not facts):\n{context}",
+        placeholders: &["count", "subject", "conversation", "context"],
+        required: &["conversation"],
+    },
+    BuiltinPrompt {
+        name: "chat",
+        system: "You are an intelligent email assistant for Inboxed. Be helpful and concise.",
+        user: "{message}",
+        placeholders: &["message"],
+        required: &["message"],
+    },
+    BuiltinPrompt {
+        name: "chat_with_context",
+        system: "You are an intelligent email assistant for Inboxed. Help users understand their emails. Be concise and conversational. Only reference information from the provided context.",
+        user: "Email context:\n{context}\n\nUser: {message}",
+        placeholders: &["context", "message"],
+        required: &["context", "message"],
+    },
+    BuiltinPrompt {
+        name: "rag_answer",
+        system: "You are an intelligent email assistant for Inboxed. Help users understand their emails. Be concise and conversational. Only reference information from the provided context.",
+        user: "Email context:\n{context}\n\nUser: {query}",
+        placeholders: &["context", "query"],
+        required: &["context", "query"],
+    },
+];
+
+fn builtin(name: &str) -> Option<&'static BuiltinPrompt> {
+    BUILTIN_PROMPTS.iter().find(|prompt| prompt.name == name)
+}
+
+/// Named prompt templates used by the Summarizer. Built-in defaults can be
+/// overridden for all models or for one model family without code changes.
+#[derive(Debug, Clone, Default)]
+pub struct PromptRegistry {
+    /// Model family ("default", "lfm", "qwen", "generic") -> template name -> template
+    overrides: HashMap<String, HashMap<String, PromptTemplate>>,
+}
+
+impl PromptRegistry {
+    /// Load overrides from a JSON file of the form
+    /// `{ "<model family>": { "<template name>": { "system": ..., "user": ... } } }`.
+    /// A missing file means built-in prompts only; an invalid one is an error.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let overrides: HashMap<String, HashMap<String, PromptTemplate>> =
+            serde_json::from_str(&json)
+                .with_context(|| format!("Invalid prompt file {}", path.display()))?;
+
+        let mut registry = Self::default();
+        for (family, templates) in overrides {
+            for (name, template) in templates {
+                registry
+                    .set_override(&family, &name, template)
+                    .with_context(|| format!("In {}", path.display()))?;
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Validate and install an override for one model family (or "default")
+    pub fn set_override(
+        &mut self,
+        family: &str,
+        name: &str,
+        template: PromptTemplate,
+    ) -> Result<()> {
+        let prompt = builtin(name).ok_or_else(|| anyhow!("Unknown prompt template '{}'", name))?;
+        for text in [&template.system, &template.user] {
+            for placeholder in placeholders(text)? {
+                if !prompt.placeholders.contains(&placeholder.as_str()) {
+                    bail!(
+                        "Prompt template '{}' uses unknown placeholder {{{}}} (available: {})",
+                        name,
+                        placeholder,
+                        prompt.placeholders.join(", ")
+                    );
+                }
+            }
+        }
+        let used = placeholders(&format!("{}\n{}", template.system, template.user))?;
+        if let Some(missing) = prompt
+            .required
+            .iter()
+            .find(|r| !used.iter().any(|u| u == *r))
+        {
+            bail!("Prompt template '{}' must use {{{}}}", name, missing);
+        }
+
+        self.overrides
+            .entry(family.to_string())
+            .or_default()
+            .insert(name.to_string(), template);
+        Ok(())
+    }
+
+    /// Render a template for a model family: returns (system, user)
+    pub fn render(
+        &self,
+        family: &str,
+        name: &str,
+        vars: &[(&str, &str)],
+    ) -> Result<(String, String)> {
+        let prompt = builtin(name).ok_or_else(|| anyhow!("Unknown prompt template '{}'", name))?;
+        let template = [family, ANY_MODEL]
+            .iter()
+            .find_map(|key| self.overrides.get(*key).and_then(|t| t.get(name)))
+            .cloned()
+            .unwrap_or_else(|| PromptTemplate {
+                system: prompt.system.to_string(),
+                user: prompt.user.to_string(),
+            });
+
+        let system = fill(&template.system, vars)
+            .with_context(|| format!("Prompt template '{}' (system)", name))?;
+        let user = fill(&template.user, vars)
+            .with_context(|| format!("Prompt template '{}' (user)", name))?;
+        Ok((system, user))
+    }
+}
+
+/// A piece of a parsed template
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Split a template into literal text and placeholders, rejecting stray braces
+fn parse(template: &str) -> Result<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+
+    while let Some(pos) = rest.find(['{', '}']) {
+        if pos > 0 {
+            segments.push(Segment::Text(&rest[..pos]));
+        }
+        let tail = &rest[pos..];
+        if let Some(after) = tail.strip_prefix("{{") {
+            segments.push(Segment::Text("{"));
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("}}") {
+            segments.push(Segment::Text("}"));
+            rest = after;
+        } else if tail.starts_with('}') {
+            bail!("Unmatched '}}' in prompt template");
+        } else {
+            let end = tail
+                .find('}')
+                .ok_or_else(|| anyhow!("Unclosed '{{' in prompt template"))?;
+            let name = &tail[1..end];
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+                bail!("Malformed placeholder {{{}}} in prompt template", name);
+            }
+            segments.push(Segment::Placeholder(name));
+            rest = &tail[end + 1..];
+        }
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    Ok(segments)
+}
+
+fn placeholders(template: &str) -> Result<Vec<String>> {
+    Ok(parse(template)?
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Placeholder(name) => Some(name.to_string()),
+            Segment::Text(_) => None,
+        })
+        .collect())
+}
+
+/// Substitute every placeholder; a placeholder without a value is an error
+fn fill(template: &str, vars: &[(&str, &str)]) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    for segment in parse(template)? {
+        match segment {
+            Segment::Text(text) => out.push_str(text),
+            Segment::Placeholder(name) => {
+                let value = vars
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| *value)
+                    .ok_or_else(|| anyhow!("No value for placeholder {{{}}}", name))?;
+                out.push_str(value);
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_defaults_and_overrides() {
+        let mut registry = PromptRegistry::default();
+        let vars = [("message", "Hi {there}")];
+
+        let (system, user) = registry.render("qwen", "chat", &vars).unwrap();
+        assert!(system.starts_with("You are an intelligent email assistant"));
+        // Values are inserted verbatim, never re-parsed
+        assert_eq!(user, "Hi {there}");
+
+        registry
+            .set_override(
+                "qwen",
+                "chat",
+                PromptTemplate {
+                    system: "Be brief {{really}}.".to_string(),
+                    user: "Q: {message}".to_string(),
+                },
+            )
+            .unwrap();
+        let (system, user) = registry.render("qwen", "chat", &vars).unwrap();
+        assert_eq!(system, "Be brief {really}.");
+        assert_eq!(user, "Q: Hi {there}");
+
+        // Other model families keep the default
+        let (_, user) = registry.render("lfm", "chat", &vars).unwrap();
+        assert_eq!(user, "Hi {there}");
+    }
+
+    #[test]
+    fn test_malformed_templates_are_rejected() {
+        let mut registry = PromptRegistry::default();
+        let template = |user: &str| PromptTemplate {
+            system: String::new(),
+            user: user.to_string(),
+        };
+
+        assert!(registry
+            .set_override("default", "chat", template("{message"))
+            .is_err());
+        assert!(registry
+            .set_override("default", "chat", template("{message} }"))
+            .is_err());
+        assert!(registry
+            .set_override("default", "chat", template("{mesage}"))
+            .is_err());
+        assert!(registry
+            .set_override("default", "chat", template("no question"))
+            .is_err());
+        assert!(registry
+            .set_override("default", "nope", template("{message}"))
+            .is_err());
+
+        // A caller that forgets a value fails instead of sending "{body}" to the model
+        assert!(registry
+            .render("lfm", "insights", &[("subject", "Hi")])
+            .is_err());
+    }
+}
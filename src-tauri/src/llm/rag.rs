@@ -3,23 +3,55 @@
 //! Combines embedding-based retrieval with LLM generation for contextual responses.
 
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use super::embeddings::EmbeddingEngine;
 use super::summarizer::Summarizer;
+use crate::commands::settings::quoted_text_kept;
 use crate::db::vector_db::{EmailEmbedding, SimilarEmail, VectorDatabase};
+use crate::email::quoting::strip_quoted_text;
 
 /// Context retrieved for RAG
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetrievedContext {
     pub email_id: String,
     pub subject: String,
     pub from: String,
     pub snippet: String,
     pub similarity: f32,
+    pub folder: String,
+    pub date: String,
 }
 
-/// Category descriptions for zero-shot classification via embeddings
+/// Body characters per embedded chunk; also where the single-embedding path truncates
+pub const CHUNK_CHARS: usize = 1000;
+
+/// Characters shared by consecutive chunks, so a sentence cut at a chunk
+/// boundary is still whole in one of them
+const CHUNK_OVERLAP: usize = 200;
+
+/// Chunks embedded per email at most; the rest of a very long body is dropped
+const MAX_CHUNKS: usize = 16;
+
+/// Texts embedded per forward pass unless configured otherwise
+pub const DEFAULT_EMBED_BATCH_SIZE: usize = 32;
+
+/// Characters of retrieved email context given to the LLM with a question
+pub const CONTEXT_MAX_CHARS: usize = 2000;
+
+/// Vector matches fetched per requested result when reranking
+pub const RERANK_CANDIDATE_FACTOR: usize = 3;
+
+/// Share of the reranked score that comes from query terms found in the email;
+/// the rest is the vector similarity
+const KEYWORD_WEIGHT: f32 = 0.3;
+
+/// Reciprocal rank fusion constant; larger values flatten the advantage of
+/// the very top ranks
+const RRF_K: f32 = 60.0;
+
+/// Default category descriptions for zero-shot classification via embeddings
 const CATEGORY_DESCRIPTIONS: &[(&str, &str)] = &[
     ("promotions", "Marketing email with sales promotions, discount offers, coupon codes, limited time deals, shopping advertisements, commercial offers"),
     ("newsletters", "Newsletter digest with editorial content, weekly updates, curated news roundup, blog posts, industry insights, recurring content publication"),
@@ -27,6 +59,42 @@ const CATEGORY_DESCRIPTIONS: &[(&str, &str)] = &[
     ("general", "Personal or work email conversation, direct message, meeting discussion, project collaboration, question from a colleague, professional correspondence"),
 ];
 
+/// Category stored when no category matches confidently
+pub const UNCERTAIN_CATEGORY: &str = "uncertain";
+
+/// How far the best category's similarity must be ahead of the runner-up
+/// before it is trusted, unless configured otherwise
+pub const DEFAULT_MIN_CATEGORY_MARGIN: f32 = 0.02;
+
+/// Outcome of zero-shot classification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoryMatch {
+    /// Best category, or `UNCERTAIN_CATEGORY` when its lead was below the threshold
+    pub category: String,
+    /// Similarity of the best-matching category
+    pub similarity: f32,
+    /// Lead of the best category's similarity over the runner-up's
+    pub margin: f32,
+}
+
+/// Names of the built-in classification categories
+pub fn builtin_categories() -> Vec<&'static str> {
+    CATEGORY_DESCRIPTIONS.iter().map(|(name, _)| *name).collect()
+}
+
+/// The built-in (name, description) pairs a new category set starts with
+pub fn default_categories() -> &'static [(&'static str, &'static str)] {
+    CATEGORY_DESCRIPTIONS
+}
+
+/// An email's texts to embed (one per chunk) and the hash of its content
+#[derive(Debug, Clone)]
+pub struct EmbeddingInput {
+    pub email_id: String,
+    pub texts: Vec<String>,
+    pub text_hash: String,
+}
+
 /// RAG Engine combining retrieval and generation
 pub struct RagEngine {
     embedding_engine: Option<Arc<EmbeddingEngine>>,
@@ -64,31 +132,91 @@ impl RagEngine {
         engine.embed(text)
     }
 
-    /// Store embedding for an email
-    pub fn store_email_embedding(&self, email_id: &str, text: &str, text_hash: &str) -> Result<()> {
-        let engine = self
-            .embedding_engine
+    /// Store embeddings for an email, one per text (chunk), replacing its old ones
+    pub fn store_email_embedding(
+        &self,
+        email_id: &str,
+        texts: &[String],
+        text_hash: &str,
+    ) -> Result<()> {
+        let input = EmbeddingInput {
+            email_id: email_id.to_string(),
+            texts: texts.to_vec(),
+            text_hash: text_hash.to_string(),
+        };
+        let vector_db = self
+            .vector_db
             .as_ref()
-            .ok_or_else(|| anyhow!("Embedding engine not initialized"))?;
+            .ok_or_else(|| anyhow!("Vector database not initialized"))?;
+
+        vector_db.store_embeddings(&self.embed_inputs(&[input], DEFAULT_EMBED_BATCH_SIZE)?)?;
+        Ok(())
+    }
+
+    /// Embed many emails with `batch_size` texts per model call and store them
+    /// in one transaction. Emails already embedded with the same text hash are
+    /// skipped. Returns the IDs of the emails that were embedded.
+    pub fn store_email_embeddings_batch(
+        &self,
+        inputs: &[EmbeddingInput],
+        batch_size: usize,
+    ) -> Result<Vec<String>> {
         let vector_db = self
             .vector_db
             .as_ref()
             .ok_or_else(|| anyhow!("Vector database not initialized"))?;
 
-        // Generate embedding
-        let embedding = engine.embed(text)?;
+        let mut changed = Vec::new();
+        for input in inputs {
+            if !vector_db.has_embedding(&input.email_id, &input.text_hash)? {
+                changed.push(input.clone());
+            }
+        }
 
-        // Store in database
-        let email_embedding = EmailEmbedding {
-            email_id: email_id.to_string(),
-            embedding,
-            embedding_model: engine.model_id().to_string(),
-            text_hash: text_hash.to_string(),
-            created_at: chrono::Utc::now().timestamp(),
-        };
+        vector_db.store_embeddings(&self.embed_inputs(&changed, batch_size)?)?;
+        Ok(changed.into_iter().map(|input| input.email_id).collect())
+    }
 
-        vector_db.store_embedding(&email_embedding)?;
-        Ok(())
+    /// One embedding row per text of every input, computed `batch_size` texts at a time
+    fn embed_inputs(
+        &self,
+        inputs: &[EmbeddingInput],
+        batch_size: usize,
+    ) -> Result<Vec<EmailEmbedding>> {
+        let engine = self
+            .embedding_engine
+            .as_ref()
+            .ok_or_else(|| anyhow!("Embedding engine not initialized"))?;
+
+        let texts: Vec<&str> = inputs
+            .iter()
+            .flat_map(|input| input.texts.iter().map(String::as_str))
+            .collect();
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(batch_size.max(1)) {
+            embeddings.extend(engine.embed_batch(batch)?);
+        }
+
+        let created_at = chrono::Utc::now().timestamp();
+        let mut embeddings = embeddings.into_iter();
+        let mut rows = Vec::with_capacity(texts.len());
+        for input in inputs {
+            for chunk_index in 0..input.texts.len() {
+                let embedding = embeddings
+                    .next()
+                    .ok_or_else(|| anyhow!("Missing embedding for {}", input.email_id))?;
+                rows.push(EmailEmbedding {
+                    email_id: input.email_id.clone(),
+                    chunk_index: chunk_index as i64,
+                    embedding,
+                    embedding_model: engine.model_id().to_string(),
+                    text_hash: input.text_hash.clone(),
+                    created_at,
+                    quantization_scale: None,
+                });
+            }
+        }
+        Ok(rows)
     }
 
     /// Search for similar emails
@@ -116,6 +244,69 @@ impl RagEngine {
         Ok(similar)
     }
 
+    /// Search for similar emails, then rerank the top `RERANK_CANDIDATE_FACTOR * top_k`
+    /// vector matches by how many query terms their subject and body contain.
+    /// `text_for` looks up an email's (subject, body); emails it can't find
+    /// keep only their similarity.
+    pub fn search_reranked<F>(
+        &self,
+        query: &str,
+        top_k: usize,
+        exclude_email_id: Option<&str>,
+        text_for: F,
+    ) -> Result<Vec<SimilarEmail>>
+    where
+        F: FnMut(&str) -> Option<(String, String)>,
+    {
+        let candidates = self.search_similar(
+            query,
+            top_k.saturating_mul(RERANK_CANDIDATE_FACTOR),
+            exclude_email_id,
+        )?;
+        Ok(rerank_by_keywords(query, candidates, top_k, text_for))
+    }
+
+    /// Search by keywords and vector similarity, fusing both rankings with
+    /// reciprocal rank fusion (see `fuse_rankings`). `keyword_search` returns
+    /// up to the given number of email IDs, best BM25 match first. Keyword-only
+    /// hits get the similarity of their first embedded chunk, or 0 if unembedded.
+    pub fn hybrid_search<F>(
+        &self,
+        query: &str,
+        top_k: usize,
+        keyword_search: F,
+    ) -> Result<Vec<SimilarEmail>>
+    where
+        F: FnOnce(&str, usize) -> Result<Vec<String>>,
+    {
+        let engine = self
+            .embedding_engine
+            .as_ref()
+            .ok_or_else(|| anyhow!("Embedding engine not initialized"))?;
+        let vector_db = self
+            .vector_db
+            .as_ref()
+            .ok_or_else(|| anyhow!("Vector database not initialized"))?;
+
+        let candidates = top_k.saturating_mul(RERANK_CANDIDATE_FACTOR);
+        let query_embedding = engine.embed(query)?;
+        let vector_matches = vector_db.search_similar(&query_embedding, candidates, None)?;
+        let keyword_matches = keyword_search(query, candidates)?;
+
+        let mut fused = fuse_rankings(
+            vector_matches,
+            &keyword_matches,
+            hybrid_keyword_weight(query),
+            top_k,
+        );
+        for result in fused.iter_mut().filter(|r| r.similarity == 0.0) {
+            if let Some(embedding) = vector_db.get_embedding(&result.email_id)? {
+                result.similarity = cosine_similarity_vec(&query_embedding, &embedding.embedding);
+            }
+        }
+        Ok(fused)
+    }
+
     /// Build context string from similar emails for LLM
     pub fn build_context(&self, contexts: &[RetrievedContext], max_chars: usize) -> String {
         let mut context = String::new();
@@ -152,35 +343,58 @@ impl RagEngine {
             return summarizer.chat(query, None);
         }
 
-        let context_str = self.build_context(contexts, 2000);
-
-        let prompt = format!(
-            "Based on the following emails:\n{}\n\nAnswer the question: {}",
-            context_str, query
-        );
-
-        summarizer.chat(&prompt, Some(&context_str))
+        let context_str = self.build_context(contexts, CONTEXT_MAX_CHARS);
+        summarizer.answer_with_context(query, &context_str)
     }
 
-    /// Compute and cache reference embeddings for category classification
+    /// Load the category set (seeding it with the built-ins the first time)
+    /// and embed any category whose description has no cached embedding
     pub fn init_category_embeddings(&mut self) -> Result<()> {
         let engine = self
             .embedding_engine
             .as_ref()
             .ok_or_else(|| anyhow!("Embedding engine not initialized"))?;
+        let vector_db = self
+            .vector_db
+            .as_ref()
+            .ok_or_else(|| anyhow!("Vector database not initialized"))?;
+
+        vector_db.seed_categories(CATEGORY_DESCRIPTIONS)?;
 
         let mut embeddings = Vec::new();
-        for (category, description) in CATEGORY_DESCRIPTIONS {
-            let embedding = engine.embed(description)?;
-            embeddings.push((category.to_string(), embedding));
+        for category in vector_db.get_categories()? {
+            let cached = category
+                .embedding
+                .filter(|_| category.embedding_model.as_deref() == Some(engine.model_id()));
+            let embedding = match cached {
+                Some(embedding) => embedding,
+                None => {
+                    let embedding = engine.embed(&category.description)?;
+                    vector_db.store_category_embedding(
+                        &category.name,
+                        &embedding,
+                        engine.model_id(),
+                    )?;
+                    embedding
+                }
+            };
+            embeddings.push((category.name, embedding));
         }
 
         self.category_embeddings = Some(embeddings);
         Ok(())
     }
 
-    /// Zero-shot classify an email into a category using embedding similarity
-    pub fn classify_category(&self, subject: &str, from: &str, body: &str) -> Result<String> {
+    /// Zero-shot classify an email into a category using embedding similarity.
+    /// A best match leading the runner-up by less than `min_margin` gives
+    /// `UNCERTAIN_CATEGORY`.
+    pub fn classify_category(
+        &self,
+        subject: &str,
+        from: &str,
+        body: &str,
+        min_margin: f32,
+    ) -> Result<CategoryMatch> {
         let category_embeddings = self
             .category_embeddings
             .as_ref()
@@ -195,19 +409,27 @@ impl RagEngine {
         let email_text = prepare_email_text(subject, from, body);
         let email_embedding = engine.embed(&email_text)?;
 
-        // Find the category with highest cosine similarity
-        let mut best_category = "general";
-        let mut best_similarity = f32::NEG_INFINITY;
+        Ok(nearest_category(&email_embedding, category_embeddings, min_margin))
+    }
 
-        for (category, ref_embedding) in category_embeddings {
-            let similarity = cosine_similarity_vec(&email_embedding, ref_embedding);
-            if similarity > best_similarity {
-                best_similarity = similarity;
-                best_category = category;
-            }
-        }
+    /// Classify many prepared email texts (see `prepare_email_text`) with a
+    /// single batched embedding call. Results are in input order.
+    pub fn classify_texts(&self, texts: &[&str], min_margin: f32) -> Result<Vec<CategoryMatch>> {
+        let category_embeddings = self
+            .category_embeddings
+            .as_ref()
+            .ok_or_else(|| anyhow!("Category embeddings not initialized"))?;
+
+        let engine = self
+            .embedding_engine
+            .as_ref()
+            .ok_or_else(|| anyhow!("Embedding engine not initialized"))?;
 
-        Ok(best_category.to_string())
+        Ok(engine
+            .embed_batch(texts)?
+            .iter()
+            .map(|embedding| nearest_category(embedding, category_embeddings, min_margin))
+            .collect())
     }
 
     /// Get the embedding engine
@@ -227,11 +449,171 @@ impl Default for RagEngine {
     }
 }
 
+/// The category whose reference embedding is most similar
+fn nearest_category(
+    embedding: &[f32],
+    category_embeddings: &[(String, Vec<f32>)],
+    min_margin: f32,
+) -> CategoryMatch {
+    let mut best_category = "general";
+    let mut best_similarity = f32::NEG_INFINITY;
+    let mut runner_up = f32::NEG_INFINITY;
+
+    for (category, ref_embedding) in category_embeddings {
+        let similarity = cosine_similarity_vec(embedding, ref_embedding);
+        if similarity > best_similarity {
+            runner_up = best_similarity;
+            best_similarity = similarity;
+            best_category = category;
+        } else if similarity > runner_up {
+            runner_up = similarity;
+        }
+    }
+
+    // With a single category there is nothing to be ambiguous with
+    let margin = if runner_up.is_finite() {
+        best_similarity - runner_up
+    } else {
+        f32::MAX
+    };
+    let category = if margin < min_margin {
+        UNCERTAIN_CATEGORY
+    } else {
+        best_category
+    };
+    CategoryMatch {
+        category: category.to_string(),
+        similarity: best_similarity,
+        margin,
+    }
+}
+
+/// Rescore `candidates` as a blend of vector similarity and query term
+/// overlap, keeping the best `top_k`. Subject matches count more than body ones.
+pub fn rerank_by_keywords<F>(
+    query: &str,
+    candidates: Vec<SimilarEmail>,
+    top_k: usize,
+    mut text_for: F,
+) -> Vec<SimilarEmail>
+where
+    F: FnMut(&str) -> Option<(String, String)>,
+{
+    let mut terms = tokenize(query);
+    terms.sort_unstable();
+    terms.dedup();
+
+    let mut reranked: Vec<SimilarEmail> = candidates
+        .into_iter()
+        .map(|mut candidate| {
+            let overlap = match text_for(&candidate.email_id) {
+                Some((subject, body)) if !terms.is_empty() => {
+                    let subject = tokenize(&subject);
+                    let body = tokenize(&strip_html(&body));
+                    let matched: f32 = terms
+                        .iter()
+                        .map(|term| {
+                            if subject.contains(term) {
+                                1.0
+                            } else if body.contains(term) {
+                                0.7
+                            } else {
+                                0.0
+                            }
+                        })
+                        .sum();
+                    matched / terms.len() as f32
+                }
+                _ => 0.0,
+            };
+            candidate.score =
+                (1.0 - KEYWORD_WEIGHT) * candidate.similarity + KEYWORD_WEIGHT * overlap;
+            candidate
+        })
+        .collect();
+
+    reranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    reranked.truncate(top_k);
+    reranked
+}
+
+/// Share of the hybrid score given to keyword (BM25) ranks; the rest goes to
+/// vector ranks. Identifier-like queries (order numbers, addresses) lean on
+/// exact matches, longer natural-language questions on meaning.
+pub fn hybrid_keyword_weight(query: &str) -> f32 {
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    if terms.is_empty() {
+        return 0.5;
+    }
+
+    let identifiers = terms
+        .iter()
+        .filter(|term| term.chars().any(|c| c.is_ascii_digit() || c == '@' || c == '#'))
+        .count();
+    let quoted = query.trim().starts_with('"') && query.trim().ends_with('"');
+
+    if quoted || identifiers == terms.len() || (terms.len() <= 2 && identifiers > 0) {
+        0.8
+    } else if identifiers > 0 {
+        0.5
+    } else if terms.len() >= 4 || query.trim_end().ends_with('?') {
+        0.3
+    } else {
+        0.5
+    }
+}
+
+/// Merge a vector ranking and a keyword ranking (email IDs, best first) with
+/// weighted reciprocal rank fusion, keeping the best `top_k`. The fused score
+/// is scaled so an email ranked first by both lists scores 1.0; emails only
+/// the keyword list found have a similarity of 0.
+pub fn fuse_rankings(
+    vector_matches: Vec<SimilarEmail>,
+    keyword_matches: &[String],
+    keyword_weight: f32,
+    top_k: usize,
+) -> Vec<SimilarEmail> {
+    let rrf = |rank: usize| (RRF_K + 1.0) / (RRF_K + rank as f32 + 1.0);
+
+    let mut fused: Vec<SimilarEmail> = vector_matches
+        .into_iter()
+        .enumerate()
+        .map(|(rank, mut result)| {
+            result.score = (1.0 - keyword_weight) * rrf(rank);
+            result
+        })
+        .collect();
+
+    for (rank, email_id) in keyword_matches.iter().enumerate() {
+        let score = keyword_weight * rrf(rank);
+        match fused.iter_mut().find(|r| r.email_id == *email_id) {
+            Some(result) => result.score += score,
+            None => fused.push(SimilarEmail {
+                email_id: email_id.clone(),
+                similarity: 0.0,
+                score,
+            }),
+        }
+    }
+
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+    fused.truncate(top_k);
+    fused
+}
+
+/// Lowercased words of two or more letters or digits
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
 /// Prepare email text for embedding (combine subject + body)
 pub fn prepare_email_text(subject: &str, from: &str, body: &str) -> String {
     // Strip HTML and limit length
-    let clean_body = strip_html(body);
-    let truncated_body = truncate_text(&clean_body, 1000);
+    let clean_body = email_body_text(body);
+    let truncated_body = truncate_text(&clean_body, CHUNK_CHARS);
 
     format!(
         "From: {} Subject: {} Content: {}",
@@ -239,47 +621,388 @@ pub fn prepare_email_text(subject: &str, from: &str, body: &str) -> String {
     )
 }
 
+/// Texts to embed for a long email: the cleaned body split into overlapping
+/// windows of `CHUNK_CHARS`, each prefixed with the sender and subject. A
+/// body that fits in one window gives a single text.
+pub fn prepare_email_chunks(subject: &str, from: &str, body: &str) -> Vec<String> {
+    let clean_body = email_body_text(body);
+    let chars: Vec<char> = clean_body.chars().collect();
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while chunks.len() < MAX_CHUNKS {
+        let mut end = (start + CHUNK_CHARS).min(chars.len());
+        // End on a word boundary unless that would shrink the chunk a lot
+        if end < chars.len() {
+            if let Some(space) = chars[start..end].iter().rposition(|c| c.is_whitespace()) {
+                if space > CHUNK_CHARS / 2 {
+                    end = start + space;
+                }
+            }
+        }
+
+        let text: String = chars[start..end].iter().collect();
+        chunks.push(format!(
+            "From: {} Subject: {} Content: {}",
+            from,
+            subject,
+            text.trim()
+        ));
+
+        if end >= chars.len() {
+            break;
+        }
+        start = end.saturating_sub(CHUNK_OVERLAP).max(start + 1);
+    }
+    chunks
+}
+
 /// Calculate text hash for change detection
 pub fn calculate_text_hash(text: &str) -> String {
     format!("{:x}", md5::compute(text))
 }
 
-/// Strip HTML tags from text
-fn strip_html(html: &str) -> String {
-    let mut result = String::with_capacity(html.len());
-    let mut in_tag = false;
-    let mut in_style = false;
-    let mut in_script = false;
-
-    for c in html.chars() {
-        match c {
-            '<' => {
-                in_tag = true;
-                if html.contains("<style") {
-                    in_style = true;
-                }
-                if html.contains("<script") {
-                    in_script = true;
+/// Plain text of an email body for embedding and summarizing. Quoted reply
+/// history is dropped unless the `keep_quoted_text` setting is on, so a long
+/// thread's messages aren't dominated by the same repeated quotes.
+pub fn email_body_text(body: &str) -> String {
+    if quoted_text_kept() {
+        strip_html(body)
+    } else {
+        strip_html(&strip_quoted_text(body))
+    }
+}
+
+/// Convert HTML to plain text. Script, style and title contents are dropped,
+/// block elements and `<br>` become line breaks, link text is kept and
+/// entities are decoded. Runs of spaces collapse as a browser would.
+pub fn strip_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    // Closing tag whose contents are being skipped (script/style/title)
+    let mut skipping: Option<String> = None;
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        if skipping.is_none() {
+            push_text(&mut out, &rest[..lt]);
+        }
+        let markup = &rest[lt..];
+
+        if let Some(comment) = markup.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        // A '<' that doesn't open a tag ("a < b") is text
+        let opens_tag = markup[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!' || c == '?');
+        let Some(end) = opens_tag.then(|| tag_end(markup)).flatten() else {
+            if skipping.is_none() {
+                push_text(&mut out, "<");
+            }
+            rest = &markup[1..];
+            continue;
+        };
+        rest = &markup[end + 1..];
+
+        let tag = &markup[1..end];
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        if let Some(skipped) = &skipping {
+            if closing && name == *skipped {
+                skipping = None;
+            }
+            continue;
+        }
+
+        match name.as_str() {
+            "script" | "style" | "title" if !closing && !tag.ends_with('/') => {
+                skipping = Some(name);
+            }
+            "br" => out.push('\n'),
+            "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote" | "table" | "ul"
+            | "ol" => out.push_str("\n\n"),
+            "div" | "li" | "tr" | "hr" | "section" | "article" | "header" | "footer" => {
+                out.push('\n')
+            }
+            "td" | "th" if !closing => out.push(' '),
+            _ => {}
+        }
+    }
+    if skipping.is_none() {
+        push_text(&mut out, rest);
+    }
+
+    // Trim every line and keep at most one blank line between blocks
+    let mut text = String::with_capacity(out.len());
+    let mut blank_lines = 0;
+    for line in out.lines().map(str::trim) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !text.is_empty() {
+            text.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        text.push_str(line);
+        blank_lines = 0;
+    }
+    text
+}
+
+/// Convert HTML to plain text that keeps the document's structure, for
+/// reading aloud or a plain-text view. Unlike `strip_html`, headings get
+/// `#` markers by level, list items get bullets or numbers indented by
+/// nesting depth, and table cells are joined with " | " one row per line.
+pub fn html_to_structured_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut skipping: Option<String> = None;
+    // Open lists, innermost last: None for <ul>, the next number for <ol>
+    let mut lists: Vec<Option<u32>> = Vec::new();
+    // A table cell was opened after text on the current line
+    let mut cell_pending = false;
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        if skipping.is_none() {
+            push_cell_text(&mut out, &rest[..lt], &mut cell_pending);
+        }
+        let markup = &rest[lt..];
+
+        if let Some(comment) = markup.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        let opens_tag = markup[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!' || c == '?');
+        let Some(end) = opens_tag.then(|| tag_end(markup)).flatten() else {
+            if skipping.is_none() {
+                push_cell_text(&mut out, "<", &mut cell_pending);
+            }
+            rest = &markup[1..];
+            continue;
+        };
+        rest = &markup[end + 1..];
+
+        let tag = &markup[1..end];
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        if let Some(skipped) = &skipping {
+            if closing && name == *skipped {
+                skipping = None;
+            }
+            continue;
+        }
+
+        match name.as_str() {
+            "script" | "style" | "title" if !closing && !tag.ends_with('/') => {
+                skipping = Some(name);
+            }
+            "br" => {
+                end_line(&mut out);
+                out.push('\n');
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                break_paragraph(&mut out);
+                if !closing {
+                    let level = usize::from(name.as_bytes()[1] - b'0');
+                    out.push_str(&"#".repeat(level));
+                    out.push(' ');
                 }
             }
-            '>' => {
-                in_tag = false;
-                if in_style && html.contains("</style>") {
-                    in_style = false;
+            "ul" | "ol" => {
+                if closing {
+                    lists.pop();
+                } else {
+                    lists.push((name == "ol").then_some(1));
                 }
-                if in_script && html.contains("</script>") {
-                    in_script = false;
+                if lists.is_empty() {
+                    break_paragraph(&mut out);
+                } else {
+                    break_line(&mut out);
+                }
+            }
+            "li" if !closing => {
+                break_line(&mut out);
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        out.push_str(&format!("{}. ", number));
+                        *number += 1;
+                    }
+                    _ => out.push_str("\u{2022} "),
                 }
             }
-            _ if !in_tag && !in_style && !in_script => {
-                result.push(c);
+            "li" | "div" | "section" | "article" | "header" | "footer" => break_line(&mut out),
+            "tr" => {
+                break_line(&mut out);
+                cell_pending = false;
+            }
+            "td" | "th" if !closing => {
+                cell_pending = !out.ends_with('\n') && !out.is_empty();
+            }
+            "p" | "blockquote" | "table" | "hr" => {
+                break_paragraph(&mut out);
+                cell_pending = false;
             }
             _ => {}
         }
     }
+    if skipping.is_none() {
+        push_cell_text(&mut out, rest, &mut cell_pending);
+    }
 
-    // Clean up whitespace
-    result.split_whitespace().collect::<Vec<_>>().join(" ")
+    // Keep indentation, and at most one blank line between blocks
+    let mut text = String::with_capacity(out.len());
+    let mut blank_lines = 0;
+    for line in out.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !text.is_empty() {
+            text.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        text.push_str(line);
+        blank_lines = 0;
+    }
+    text
+}
+
+/// Text inside a table row, with " | " before it if it opens a later cell.
+/// Empty cells (spacers in layout tables) add no separator.
+fn push_cell_text(out: &mut String, text: &str, cell_pending: &mut bool) {
+    if *cell_pending && !decode_entities(text).trim().is_empty() {
+        end_line(out);
+        out.push_str(" | ");
+        *cell_pending = false;
+    }
+    push_text(out, text);
+}
+
+/// Drop the space `push_text` may have left at the end of the line
+fn end_line(out: &mut String) {
+    let trimmed = out.trim_end_matches(' ').len();
+    out.truncate(trimmed);
+}
+
+/// Start a new line unless already at the start of one
+fn break_line(out: &mut String) {
+    end_line(out);
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Leave a blank line before what follows
+fn break_paragraph(out: &mut String) {
+    break_line(out);
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+}
+
+/// Index of the '>' closing the tag at the start of `markup`, skipping quoted attributes
+fn tag_end(markup: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in markup.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Append text content with entities decoded and whitespace collapsed
+fn push_text(out: &mut String, text: &str) {
+    for c in decode_entities(text).chars() {
+        if c.is_whitespace() {
+            if !out.is_empty() && !out.ends_with(char::is_whitespace) {
+                out.push(' ');
+            }
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+/// Decode named and numeric character references; unknown ones are kept as-is
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let candidate = &rest[amp + 1..];
+        let decoded = candidate
+            .find(';')
+            .filter(|&semi| semi <= 10)
+            .and_then(|semi| decode_entity(&candidate[..semi]).map(|c| (c, semi)));
+        match decoded {
+            Some((c, semi)) => {
+                out.push(c);
+                rest = &candidate[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = candidate;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201C}',
+        "rdquo" => '\u{201D}',
+        "hellip" => '\u{2026}',
+        "bull" => '\u{2022}',
+        "copy" => '\u{00A9}',
+        "reg" => '\u{00AE}',
+        "trade" => '\u{2122}',
+        "euro" => '\u{20AC}',
+        _ => return None,
+    };
+    Some(c)
 }
 
 /// Truncate text to max characters
@@ -329,6 +1052,47 @@ mod tests {
         assert!(text.contains("Meeting Tomorrow"));
         assert!(text.contains("John Doe"));
         assert!(text.contains("meet at 3pm"));
+
+        let reply = prepare_email_text(
+            "Re: Meeting",
+            "Ana",
+            "3pm works.\n\nOn Mon, 2 Mar 2026, John Doe <john@example.com> wrote:\n> Let's meet at 3pm",
+        );
+        assert_eq!(reply, "From: Ana Subject: Re: Meeting Content: 3pm works.");
+    }
+
+    #[test]
+    fn test_prepare_email_chunks_overlap_and_cover_body() {
+        let short = prepare_email_chunks("Hi", "Ana", "Just a note");
+        assert_eq!(short, ["From: Ana Subject: Hi Content: Just a note"]);
+
+        let body: String = (0..600).map(|i| format!("w{} ", i)).collect();
+        let chunks = prepare_email_chunks("Long", "Ana", &body);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.starts_with("From: Ana Subject: Long Content: ")));
+        // The tail of the body is embedded, and neighbours share text
+        assert!(chunks.last().unwrap().ends_with("w599"));
+        let content = |c: &str| c.split("Content: ").nth(1).unwrap().to_string();
+        let first_tail: String = content(&chunks[0]).rsplit(' ').next().unwrap().into();
+        assert!(content(&chunks[1]).contains(&format!(" {} ", first_tail)));
+    }
+
+    #[test]
+    fn test_nearest_category_is_uncertain_on_close_calls() {
+        let categories = vec![
+            ("promotions".to_string(), vec![1.0, 0.0]),
+            ("general".to_string(), vec![0.0, 1.0]),
+        ];
+
+        let clear = nearest_category(&[1.0, 0.1], &categories, DEFAULT_MIN_CATEGORY_MARGIN);
+        assert_eq!(clear.category, "promotions");
+        assert!(clear.similarity > 0.99 && clear.margin > 0.8);
+
+        // Almost halfway between the two: the best match only just leads
+        let close = nearest_category(&[1.0, 0.98], &categories, DEFAULT_MIN_CATEGORY_MARGIN);
+        assert_eq!(close.category, UNCERTAIN_CATEGORY);
+        assert!(close.margin > 0.0 && close.margin < DEFAULT_MIN_CATEGORY_MARGIN);
+        assert_eq!(nearest_category(&[1.0, 0.98], &categories, 0.0).category, "promotions");
     }
 
     #[test]
@@ -338,6 +1102,102 @@ mod tests {
         assert_eq!(text, "Hello World");
     }
 
+    #[test]
+    fn test_strip_html_keeps_text_around_style_block() {
+        let html = "<html><head><title>Receipt</title><style>p { color: red; }</style></head>\
+                    <body><p>Thanks &amp; welcome,<br>Ana</p>\
+                    <p>See <a href=\"https://example.com?a=1&amp;b=2\" title=\"x > y\">your order</a> \
+                    &lt;#42&gt;</p><script>var x = 1 < 2;</script><div>Total: 3 &#8364;</div></body></html>";
+        assert_eq!(
+            strip_html(html),
+            "Thanks & welcome,\nAna\n\nSee your order <#42>\n\nTotal: 3 \u{20AC}"
+        );
+        assert_eq!(strip_html("a < b &unknown; c"), "a < b &unknown; c");
+    }
+
+    #[test]
+    fn test_structured_text_keeps_lists_headings_and_tables() {
+        let html = "<h1>Release notes</h1><p>What&#39;s new:</p>\
+                    <ul><li>Faster sync<ul><li>IDLE on all folders</li>\
+                    <li>Smaller <b>fetches</b></li></ul></li>\
+                    <li>Steps:<ol><li>Open settings</li><li>Pick a theme</li></ol></li></ul>\
+                    <h3>Pricing</h3><table><tr><th>Plan</th><td></td><th>Price</th></tr>\
+                    <tr><td>Pro</td><td>&nbsp;</td><td>5 &#8364;</td></tr></table>\
+                    <div>Thanks,<br>The team</div>";
+        assert_eq!(
+            html_to_structured_text(html),
+            "# Release notes\n\nWhat's new:\n\n\
+             \u{2022} Faster sync\n  \u{2022} IDLE on all folders\n  \u{2022} Smaller fetches\n\
+             \u{2022} Steps:\n  1. Open settings\n  2. Pick a theme\n\n\
+             ### Pricing\n\nPlan | Price\nPro | 5 \u{20AC}\n\nThanks,\nThe team"
+        );
+    }
+
+    #[test]
+    fn test_rerank_prefers_exact_term_matches() {
+        let candidate = |id: &str, similarity: f32| SimilarEmail {
+            email_id: id.to_string(),
+            similarity,
+            score: similarity,
+        };
+        let candidates = vec![
+            candidate("vague", 0.80),
+            candidate("invoice", 0.72),
+            candidate("body-only", 0.70),
+        ];
+        let reranked = rerank_by_keywords("Invoice March", candidates, 2, |id| match id {
+            "invoice" => Some(("Your March invoice".into(), String::new())),
+            "body-only" => Some(("Hello".into(), "<p>The invoice is attached</p>".into())),
+            _ => Some(("Quarterly planning".into(), "Agenda".into())),
+        });
+
+        let ids: Vec<_> = reranked.iter().map(|s| s.email_id.as_str()).collect();
+        assert_eq!(ids, ["invoice", "body-only"]);
+        // The vector similarity is kept alongside the final score
+        assert_eq!(reranked[0].similarity, 0.72);
+        assert!(reranked[0].score > reranked[1].score);
+    }
+
+    #[test]
+    fn test_hybrid_search_favours_keywords_for_identifiers() {
+        let result = |id: &str| SimilarEmail {
+            email_id: id.to_string(),
+            similarity: 0.5,
+            score: 0.5,
+        };
+        let vector = || vec![result("shipping-faq"), result("returns"), result("order")];
+
+        // A bare order number: the only exact match wins despite its vector rank
+        let query = "ORD-48213";
+        assert_eq!(hybrid_keyword_weight(query), 0.8);
+        let keyword = vec!["order".to_string()];
+        let fused = fuse_rankings(vector(), &keyword, hybrid_keyword_weight(query), 2);
+        assert_eq!(fused[0].email_id, "order");
+
+        // A conceptual question: the vector ranking leads over a stray word match
+        let query = "what did we agree about returning damaged items?";
+        assert_eq!(hybrid_keyword_weight(query), 0.3);
+        let keyword = vec!["newsletter".to_string()];
+        let fused = fuse_rankings(vector(), &keyword, hybrid_keyword_weight(query), 2);
+        assert_eq!(fused[0].email_id, "shipping-faq");
+        assert!(fused[0].score <= 1.0);
+    }
+
+    #[test]
+    fn test_fuse_rankings_keeps_keyword_only_hits() {
+        let vector = vec![SimilarEmail {
+            email_id: "a".to_string(),
+            similarity: 0.9,
+            score: 0.9,
+        }];
+        let fused = fuse_rankings(vector, &["a".to_string(), "b".to_string()], 0.5, 5);
+
+        assert_eq!(fused.len(), 2);
+        assert_eq!(fused[0].email_id, "a");
+        assert!((fused[0].score - 1.0).abs() < 1e-6);
+        assert_eq!(fused[1].similarity, 0.0);
+    }
+
     #[test]
     fn test_calculate_text_hash() {
         let hash1 = calculate_text_hash("hello");
@@ -1,13 +1,34 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 use super::engine::{GenerationParams, LlmEngine};
+use super::prompts::{PromptRegistry, PROMPT_OVERRIDES_FILE};
+use crate::email::quoting::strip_quoted_text;
+
+/// Longest conversation text given to the model in one prompt; longer
+/// conversations are summarized in parts of this size
+const THREAD_CHUNK_CHARS: usize = 8000;
+/// Rounds of summarizing part notes before whatever is left gets truncated
+const THREAD_MAX_ROUNDS: usize = 3;
+/// Separates messages (or part notes) in a conversation prompt
+const THREAD_SEPARATOR: &str = "\n\n---\n\n";
+const ACTION_ITEMS_HEADING: &str = "ACTION ITEMS:";
+const OPEN_QUESTIONS_HEADING: &str = "OPEN QUESTIONS:";
+/// Newest conversation text given to the model when drafting replies
+const REPLY_CONVERSATION_CHARS: usize = 5000;
+/// Words naming a day or month; a reply may only use the ones the
+/// conversation does ("may" is left out, it's mostly a verb)
+const DATE_WORDS: &str = "monday tuesday wednesday thursday friday saturday sunday today tonight \
+    tomorrow january february march april june july august september october november december";
 
 /// AI-powered email summarizer using local LLM
 pub struct Summarizer {
     engine: Option<Arc<LlmEngine>>,
     model_type: ModelType,
+    prompts: PromptRegistry,
 }
 
 /// Different model types require different prompt formats
@@ -19,6 +40,98 @@ pub enum ModelType {
     Unknown,    // Generic ChatML
 }
 
+impl ModelType {
+    /// Key for per-model prompt overrides
+    fn family(self) -> &'static str {
+        match self {
+            ModelType::LFM25 => "lfm",
+            ModelType::Qwen25 => "qwen",
+            ModelType::Unknown => "generic",
+        }
+    }
+}
+
+/// How deep an email explanation should go
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplainLevel {
+    /// A few plain sentences
+    #[default]
+    Brief,
+    /// Walks through obligations, figures and deadlines
+    Detailed,
+    /// Explain it like I'm five
+    Eli5,
+}
+
+impl ExplainLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "brief" => Some(Self::Brief),
+            "detailed" => Some(Self::Detailed),
+            "eli5" => Some(Self::Eli5),
+            _ => None,
+        }
+    }
+
+    /// Returns (max_tokens, instruction)
+    fn params(self) -> (u32, &'static str) {
+        match self {
+            Self::Brief => (200, "Explain this email in 2-3 plain-language sentences."),
+            Self::Detailed => (500, "Explain this email thoroughly in plain language. Cover what is being asked or agreed, any figures, obligations and deadlines, and what the reader should do."),
+            Self::Eli5 => (300, "Explain this email as if to a ten-year-old, using short everyday words and a simple comparison if it helps."),
+        }
+    }
+}
+
+/// A piece of jargon the explanation clarified
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyTerm {
+    pub term: String,
+    pub meaning: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailExplanation {
+    pub level: ExplainLevel,
+    pub explanation: String,
+    /// The explanation with each key term wrapped in `**` (Markdown bold)
+    pub highlighted: String,
+    pub key_terms: Vec<KeyTerm>,
+    /// Past emails used as background, if any
+    pub context_email_ids: Vec<String>,
+}
+
+/// One message of a conversation to summarize
+#[derive(Debug, Clone)]
+pub struct ThreadEntry {
+    pub from: String,
+    pub date: String,
+    /// Unix timestamp the messages are ordered by
+    pub timestamp: i64,
+    /// Plain text or HTML, quoted history included
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ThreadSummary {
+    pub summary: String,
+    pub action_items: Vec<String>,
+    pub open_questions: Vec<String>,
+    pub message_count: usize,
+    /// Parts the conversation was split into to fit the model's context;
+    /// 1 when it was summarized in one go
+    pub parts: usize,
+}
+
+/// A candidate reply the user can load into compose
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplySuggestion {
+    /// Short label such as "accept", "decline" or "ask for time"
+    pub intent: String,
+    pub text: String,
+}
+
 impl Summarizer {
     /// Create a new Summarizer without a loaded model
     /// Call `load_model` to initialize the LLM
@@ -26,11 +139,14 @@ impl Summarizer {
         Ok(Self {
             engine: None,
             model_type: ModelType::default(),
+            prompts: PromptRegistry::default(),
         })
     }
 
-    /// Load an LLM model from the given path
+    /// Load an LLM model from the given path, along with any prompt
+    /// overrides stored next to it
     pub fn load_model(&mut self, model_path: &Path) -> Result<()> {
+        let prompts = PromptRegistry::load(&model_path.with_file_name(PROMPT_OVERRIDES_FILE))?;
         let engine = LlmEngine::new(model_path)?;
         self.engine = Some(Arc::new(engine));
 
@@ -48,6 +164,7 @@ impl Summarizer {
         } else {
             ModelType::Unknown
         };
+        self.prompts = prompts;
 
         Ok(())
     }
@@ -86,6 +203,12 @@ impl Summarizer {
         }
     }
 
+    /// Render a named prompt template and wrap it in the model's chat format
+    fn build_prompt(&self, name: &str, vars: &[(&str, &str)]) -> Result<String> {
+        let (system, user) = self.prompts.render(self.model_type.family(), name, vars)?;
+        Ok(self.format_prompt(&system, &user))
+    }
+
     /// Get stop sequences for the model
     fn get_stop_sequences(&self) -> Vec<String> {
         vec![
@@ -114,7 +237,7 @@ impl Summarizer {
         from: &str,
         body: &str,
     ) -> Result<String> {
-        let body_text = Self::strip_html(body);
+        let body_text = Self::body_text(body);
         let word_count = body_text.split_whitespace().count();
 
         // Adjust context size based on email length
@@ -124,15 +247,15 @@ impl Summarizer {
         if let Some(engine) = &self.engine {
             let (max_tokens, instruction) = Self::get_summary_params(word_count);
 
-            let system = format!(
-                "You are a helpful email assistant. {} Do not miss any important information.",
-                instruction
-            );
-            let user = format!(
-                "Summarize this email:\n\nFrom: {from}\nSubject: {subject}\n\n{body_preview}"
-            );
-
-            let prompt = self.format_prompt(&system, &user);
+            let prompt = self.build_prompt(
+                "summarize",
+                &[
+                    ("instruction", instruction),
+                    ("from", from),
+                    ("subject", subject),
+                    ("body", &body_preview),
+                ],
+            )?;
 
             let params = GenerationParams {
                 max_tokens,
@@ -159,7 +282,7 @@ impl Summarizer {
     where
         F: FnMut(&str),
     {
-        let body_text = Self::strip_html(body);
+        let body_text = Self::body_text(body);
         let word_count = body_text.split_whitespace().count();
 
         // Adjust context size based on email length
@@ -169,15 +292,15 @@ impl Summarizer {
         if let Some(engine) = &self.engine {
             let (max_tokens, instruction) = Self::get_summary_params(word_count);
 
-            let system = format!(
-                "You are a helpful email assistant. {} Do not miss any important information.",
-                instruction
-            );
-            let user = format!(
-                "Summarize this email:\n\nFrom: {from}\nSubject: {subject}\n\n{body_preview}"
-            );
-
-            let prompt = self.format_prompt(&system, &user);
+            let prompt = self.build_prompt(
+                "summarize",
+                &[
+                    ("instruction", instruction),
+                    ("from", from),
+                    ("subject", subject),
+                    ("body", &body_preview),
+                ],
+            )?;
 
             let params = GenerationParams {
                 max_tokens,
@@ -196,14 +319,14 @@ impl Summarizer {
 
     /// Generate AI insights about the email
     pub fn generate_insights(&self, subject: &str, body: &str) -> Result<Vec<String>> {
-        let body_text = Self::strip_html(body);
+        let body_text = Self::body_text(body);
         let body_preview = Self::truncate_text(&body_text, 1500);
 
         if let Some(engine) = &self.engine {
-            let system = "You are an email analysis assistant. List 1-3 key insights about emails. Each insight should be one short sentence. Format: one insight per line starting with an emoji.";
-            let user = format!("Analyze this email:\n\nSubject: {subject}\n\n{body_preview}");
-
-            let prompt = self.format_prompt(system, &user);
+            let prompt = self.build_prompt(
+                "insights",
+                &[("subject", subject), ("body", &body_preview)],
+            )?;
 
             let params = GenerationParams {
                 max_tokens: 150,
@@ -239,19 +362,10 @@ impl Summarizer {
         let body_preview = Self::truncate_text(&body_text, 1000);
 
         if let Some(engine) = &self.engine {
-            let system = "You are an email priority classifier. Respond with exactly one word: HIGH, MEDIUM, or LOW.\n\n\
-                HIGH: Direct personal email requiring action or reply. Urgent, time-sensitive, from a real person.\n\
-                MEDIUM: Relevant but not urgent. Meeting invites, project updates, questions, team discussions.\n\
-                LOW: Automated, mass-sent, no action needed. Newsletters, promotions, notifications, service emails from noreply addresses.\n\n\
-                Examples:\n\
-                - Subject: \"Can you review this PR by tomorrow?\" From: john@company.com → HIGH\n\
-                - Subject: \"Your weekly digest\" From: noreply@service.com → LOW\n\
-                - Subject: \"Q3 planning meeting notes\" From: sarah@company.com → MEDIUM\n\
-                - Subject: \"Action required: approve expense report\" From: manager@company.com → HIGH\n\
-                - Subject: \"50% off summer sale!\" From: deals@store.com → LOW";
-            let user = format!("Classify this email's priority:\n\nFrom: {from}\nSubject: {subject}\n\n{body_preview}");
-
-            let prompt = self.format_prompt(system, &user);
+            let prompt = self.build_prompt(
+                "priority",
+                &[("from", from), ("subject", subject), ("body", &body_preview)],
+            )?;
 
             let params = GenerationParams {
                 max_tokens: 10,
@@ -283,36 +397,227 @@ impl Summarizer {
         }
     }
 
-    /// Strip HTML tags from content
-    fn strip_html(html: &str) -> String {
-        let result = html
-            .replace("<br>", "\n")
-            .replace("<br/>", "\n")
-            .replace("<br />", "\n")
-            .replace("</p>", "\n\n")
-            .replace("</div>", "\n");
-
-        // Remove all HTML tags
-        let mut in_tag = false;
-        let mut cleaned = String::new();
-
-        for ch in result.chars() {
-            if ch == '<' {
-                in_tag = true;
-            } else if ch == '>' {
-                in_tag = false;
-            } else if !in_tag {
-                cleaned.push(ch);
+    /// Explain a dense (legal, technical, financial) email in plain language.
+    /// `context` holds related past emails used only as background.
+    pub fn explain_email(
+        &self,
+        subject: &str,
+        from: &str,
+        body: &str,
+        level: ExplainLevel,
+        context: Option<&str>,
+    ) -> Result<EmailExplanation> {
+        let Some(engine) = &self.engine else {
+            bail!("AI model not loaded");
+        };
+
+        let body_text = Self::strip_html(body);
+        let body_preview = Self::truncate_text(&body_text, 3000);
+        let (max_tokens, instruction) = level.params();
+
+        let mut vars = vec![
+            ("instruction", instruction),
+            ("from", from),
+            ("subject", subject),
+            ("body", body_preview.as_str()),
+        ];
+        let template = match context {
+            Some(ctx) => {
+                vars.push(("context", ctx));
+                "explain_with_context"
             }
+            None => "explain",
+        };
+        let prompt = self.build_prompt(template, &vars)?;
+        let params = GenerationParams {
+            max_tokens,
+            temperature: 0.3,
+            stop_sequences: self.get_stop_sequences(),
+            ..Default::default()
+        };
+
+        let response = engine.generate(&prompt, &params)?;
+        let (explanation, key_terms) = parse_explanation(&response);
+        if explanation.is_empty() {
+            bail!("Model returned an empty explanation");
+        }
+
+        Ok(EmailExplanation {
+            level,
+            highlighted: highlight_terms(&explanation, &key_terms),
+            explanation,
+            key_terms,
+            context_email_ids: Vec::new(),
+        })
+    }
+
+    /// Summarize a whole conversation. Quoted history is stripped from each
+    /// message and the messages ordered oldest first. A conversation too long
+    /// for the context window is summarized in parts, and the parts' notes
+    /// are combined (in parts again if they are still too long).
+    pub fn summarize_thread(
+        &self,
+        subject: &str,
+        messages: &[ThreadEntry],
+    ) -> Result<ThreadSummary> {
+        let Some(engine) = &self.engine else {
+            bail!("AI model not loaded");
+        };
+        if messages.is_empty() {
+            bail!("The conversation has no messages");
+        }
+
+        let texts = Self::thread_texts(messages);
+        let mut chunks = chunk_texts(&texts, THREAD_CHUNK_CHARS);
+        let parts = chunks.len();
+        for _ in 0..THREAD_MAX_ROUNDS {
+            if chunks.len() <= 1 {
+                break;
+            }
+            let notes = chunks
+                .iter()
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let part = format!("{} of {}", i + 1, chunks.len());
+                    let prompt = self.build_prompt(
+                        "summarize_thread_part",
+                        &[
+                            ("part", &part),
+                            ("subject", subject),
+                            ("conversation", chunk),
+                        ],
+                    )?;
+                    let notes = engine.generate(&prompt, &self.thread_params(250))?;
+                    Ok(format!("Notes on part {}:\n{}", part, notes.trim()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            chunks = chunk_texts(&notes, THREAD_CHUNK_CHARS);
+        }
+        let conversation = Self::truncate_text(&chunks.join(THREAD_SEPARATOR), THREAD_CHUNK_CHARS);
+
+        let prompt = self.build_prompt(
+            "summarize_thread",
+            &[("subject", subject), ("conversation", &conversation)],
+        )?;
+        let response = engine.generate(&prompt, &self.thread_params(400))?;
+        let (summary, action_items, open_questions) = parse_thread_summary(&response);
+        if summary.is_empty() {
+            bail!("Model returned an empty summary");
+        }
+
+        Ok(ThreadSummary {
+            summary,
+            action_items,
+            open_questions,
+            message_count: messages.len(),
+            parts,
+        })
+    }
+
+    /// Draft `count` short candidate replies to the newest message of a
+    /// conversation, each labelled with its intent. `context` holds similar
+    /// past emails, used for tone only. Replies mentioning a figure, day or
+    /// month the conversation doesn't are dropped rather than returned.
+    pub fn suggest_replies(
+        &self,
+        subject: &str,
+        messages: &[ThreadEntry],
+        count: usize,
+        context: Option<&str>,
+        temperature: f32,
+    ) -> Result<Vec<ReplySuggestion>> {
+        let Some(engine) = &self.engine else {
+            bail!("AI model not loaded");
+        };
+        if messages.is_empty() {
+            bail!("The conversation has no messages");
+        }
+
+        // The newest messages matter most, so older ones are dropped first
+        let texts = Self::thread_texts(messages);
+        let mut newest = Vec::new();
+        let mut length = 0;
+        for text in texts.iter().rev() {
+            if !newest.is_empty() && length + text.len() > REPLY_CONVERSATION_CHARS {
+                break;
+            }
+            length += text.len() + THREAD_SEPARATOR.len();
+            newest.push(text.as_str());
         }
+        newest.reverse();
+        let conversation =
+            Self::truncate_text(&newest.join(THREAD_SEPARATOR), REPLY_CONVERSATION_CHARS);
+
+        let count_text = count.to_string();
+        let mut vars = vec![
+            ("count", count_text.as_str()),
+            ("subject", subject),
+            ("conversation", conversation.as_str()),
+        ];
+        let template = match context {
+            Some(ctx) => {
+                vars.push(("context", ctx));
+                "suggest_replies_with_context"
+            }
+            None => "suggest_replies",
+        };
+        let prompt = self.build_prompt(template, &vars)?;
+        let params = GenerationParams {
+            max_tokens: 120 * count as u32,
+            temperature,
+            stop_sequences: self.get_stop_sequences(),
+            ..Default::default()
+        };
+
+        let response = engine.generate(&prompt, &params)?;
+        let source = format!("{}\n{}", subject, texts.join("\n"));
+        let suggestions: Vec<ReplySuggestion> = parse_reply_suggestions(&response)
+            .into_iter()
+            .filter(|suggestion| !invents_details(&suggestion.text, &source))
+            .take(count)
+            .collect();
+        if suggestions.is_empty() {
+            bail!("Model returned no usable replies");
+        }
+        Ok(suggestions)
+    }
+
+    /// Each message as "From/Date" headers and its text without quoted
+    /// history, oldest first
+    fn thread_texts(messages: &[ThreadEntry]) -> Vec<String> {
+        let mut ordered: Vec<&ThreadEntry> = messages.iter().collect();
+        ordered.sort_by_key(|message| message.timestamp);
+        ordered
+            .iter()
+            .map(|message| {
+                let latest = Self::strip_html(&strip_quoted_text(&message.body));
+                format!(
+                    "From: {}\nDate: {}\n\n{}",
+                    message.from,
+                    message.date,
+                    latest.trim()
+                )
+            })
+            .collect()
+    }
+
+    fn thread_params(&self, max_tokens: u32) -> GenerationParams {
+        GenerationParams {
+            max_tokens,
+            temperature: 0.3,
+            stop_sequences: self.get_stop_sequences(),
+            ..Default::default()
+        }
+    }
+
+    /// Strip HTML tags from content
+    fn strip_html(html: &str) -> String {
+        super::rag::strip_html(html)
+    }
 
-        // Clean up whitespace
-        cleaned
-            .split_whitespace()
-            .collect::<Vec<&str>>()
-            .join(" ")
-            .trim()
-            .to_string()
+    /// Plain text of an email body, without quoted history unless configured
+    fn body_text(body: &str) -> String {
+        super::rag::email_body_text(body)
     }
 
     /// Truncate text to a maximum number of characters
@@ -403,30 +708,73 @@ impl Summarizer {
         user_message: &str,
         email_context: Option<&str>,
     ) -> Result<String> {
+        self.chat_stream(user_message, email_context, &AtomicBool::new(false), |_| {})
+    }
+
+    /// Chat with streaming callback; stops early once `cancel` is set
+    pub fn chat_stream<F>(
+        &self,
+        user_message: &str,
+        email_context: Option<&str>,
+        cancel: &AtomicBool,
+        mut on_token: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
         if let Some(engine) = &self.engine {
-            let system = if email_context.is_some() {
-                "You are an intelligent email assistant for Inboxed. Help users understand their emails. Be concise and conversational. Only reference information from the provided context."
-            } else {
-                "You are an intelligent email assistant for Inboxed. Be helpful and concise."
+            let prompt = match email_context {
+                Some(ctx) => self.build_prompt(
+                    "chat_with_context",
+                    &[("context", ctx), ("message", user_message)],
+                )?,
+                None => self.build_prompt("chat", &[("message", user_message)])?,
             };
 
-            let user = match email_context {
-                Some(ctx) => format!("Email context:\n{}\n\nUser: {}", ctx, user_message),
-                None => user_message.to_string(),
-            };
+            engine.generate_stream_cancellable(&prompt, &self.chat_params(), cancel, on_token)
+        } else {
+            // Fallback when no model loaded
+            let response = Self::fallback_chat_response(email_context);
+            on_token(&response);
+            Ok(response)
+        }
+    }
 
-            let prompt = self.format_prompt(system, &user);
-            let params = GenerationParams {
-                max_tokens: 300,
-                temperature: 0.7,
-                stop_sequences: self.get_stop_sequences(),
-                ..Default::default()
-            };
+    /// Answer a question about the given retrieved emails
+    pub fn answer_with_context(&self, query: &str, context: &str) -> Result<String> {
+        self.answer_with_context_stream(query, context, &AtomicBool::new(false), |_| {})
+    }
 
-            engine.generate(&prompt, &params)
+    /// Answer with streaming callback; stops early once `cancel` is set
+    pub fn answer_with_context_stream<F>(
+        &self,
+        query: &str,
+        context: &str,
+        cancel: &AtomicBool,
+        mut on_token: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        if let Some(engine) = &self.engine {
+            let prompt =
+                self.build_prompt("rag_answer", &[("context", context), ("query", query)])?;
+
+            engine.generate_stream_cancellable(&prompt, &self.chat_params(), cancel, on_token)
         } else {
-            // Fallback when no model loaded
-            Ok(Self::fallback_chat_response(email_context))
+            let response = Self::fallback_chat_response(Some(context));
+            on_token(&response);
+            Ok(response)
+        }
+    }
+
+    /// Generation parameters shared by chat and RAG answers
+    fn chat_params(&self) -> GenerationParams {
+        GenerationParams {
+            max_tokens: 300,
+            temperature: 0.7,
+            stop_sequences: self.get_stop_sequences(),
+            ..Default::default()
         }
     }
 
@@ -445,3 +793,246 @@ impl Default for Summarizer {
         Self::new().expect("Failed to create Summarizer")
     }
 }
+
+/// Split a model response into the explanation and its "TERMS:" list
+fn parse_explanation(response: &str) -> (String, Vec<KeyTerm>) {
+    let (text, terms) = match response.find("TERMS:") {
+        Some(pos) => (&response[..pos], &response[pos + "TERMS:".len()..]),
+        None => (response, ""),
+    };
+
+    let key_terms = terms
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().trim_start_matches(['-', '*', '•']).trim();
+            let (term, meaning) = line.split_once(':')?;
+            let term = term.trim().trim_matches('*').trim();
+            let meaning = meaning.trim();
+            (!term.is_empty() && !meaning.is_empty()).then(|| KeyTerm {
+                term: term.to_string(),
+                meaning: meaning.to_string(),
+            })
+        })
+        .take(5)
+        .collect();
+
+    (text.trim().to_string(), key_terms)
+}
+
+/// Pack texts into chunks of at most `max_chars`, keeping each text whole
+/// when it fits. A longer text is split at whitespace.
+fn chunk_texts(texts: &[String], max_chars: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    for text in texts {
+        if text.len() <= max_chars {
+            pieces.push(text.clone());
+            continue;
+        }
+        let mut piece = String::new();
+        for word in text.split_whitespace() {
+            if !piece.is_empty() && piece.len() + 1 + word.len() > max_chars {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            if !piece.is_empty() {
+                piece.push(' ');
+            }
+            piece.push_str(word);
+        }
+        if !piece.is_empty() {
+            pieces.push(piece);
+        }
+    }
+
+    let mut chunks: Vec<String> = Vec::new();
+    for piece in pieces {
+        match chunks.last_mut() {
+            Some(chunk) if chunk.len() + THREAD_SEPARATOR.len() + piece.len() <= max_chars => {
+                chunk.push_str(THREAD_SEPARATOR);
+                chunk.push_str(&piece);
+            }
+            _ => chunks.push(piece),
+        }
+    }
+    chunks
+}
+
+/// Split a thread summary response into the summary, its action items and
+/// its open questions. The headings may come in either order.
+fn parse_thread_summary(response: &str) -> (String, Vec<String>, Vec<String>) {
+    // ASCII uppercasing keeps byte offsets valid for slicing `response`
+    let upper = response.to_ascii_uppercase();
+    let headings = [
+        upper
+            .find(ACTION_ITEMS_HEADING)
+            .map(|pos| (pos, ACTION_ITEMS_HEADING)),
+        upper
+            .find(OPEN_QUESTIONS_HEADING)
+            .map(|pos| (pos, OPEN_QUESTIONS_HEADING)),
+    ];
+    let section_end = |start: usize| {
+        headings
+            .iter()
+            .flatten()
+            .map(|(pos, _)| *pos)
+            .filter(|&pos| pos > start)
+            .min()
+            .unwrap_or(response.len())
+    };
+    let items = |heading: Option<(usize, &str)>| -> Vec<String> {
+        let Some((pos, name)) = heading else {
+            return Vec::new();
+        };
+        response[pos + name.len()..section_end(pos)]
+            .lines()
+            .map(|line| line.trim().trim_start_matches(['-', '*', '•']).trim())
+            .filter(|item| !item.is_empty() && !item.eq_ignore_ascii_case("none"))
+            .map(str::to_string)
+            .collect()
+    };
+
+    let first_heading = headings
+        .iter()
+        .flatten()
+        .map(|(pos, _)| *pos)
+        .min()
+        .unwrap_or(response.len());
+    let summary = response[..first_heading].trim();
+    let summary = match summary.get(..8) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("summary:") => summary[8..].trim(),
+        _ => summary,
+    };
+    (summary.to_string(), items(headings[0]), items(headings[1]))
+}
+
+/// Split a reply suggestions response at its "REPLY: intent" lines
+fn parse_reply_suggestions(response: &str) -> Vec<ReplySuggestion> {
+    let mut suggestions: Vec<ReplySuggestion> = Vec::new();
+    for line in response.lines() {
+        let heading = line.trim().trim_matches(['*', '#']).trim();
+        let label = heading
+            .get(..5)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("reply"))
+            .and_then(|_| heading.split_once(':'))
+            .map(|(_, intent)| intent.trim().trim_matches(['*', '(', ')', '[', ']']).trim());
+        match (label, suggestions.last_mut()) {
+            (Some(intent), _) => suggestions.push(ReplySuggestion {
+                intent: intent.to_lowercase(),
+                text: String::new(),
+            }),
+            (None, Some(current)) => {
+                current.text.push_str(line);
+                current.text.push('\n');
+            }
+            // Preamble before the first reply
+            (None, None) => {}
+        }
+    }
+
+    for suggestion in &mut suggestions {
+        suggestion.text = suggestion.text.trim().to_string();
+    }
+    suggestions.retain(|suggestion| !suggestion.text.is_empty());
+    suggestions
+}
+
+/// Whether `reply` mentions a number, day or month that `source` doesn't,
+/// i.e. a date, amount or commitment the model made up
+fn invents_details(reply: &str, source: &str) -> bool {
+    let source = source.to_lowercase();
+    reply
+        .split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|word| {
+            word.chars().any(|c| c.is_ascii_digit())
+                || DATE_WORDS
+                    .split_whitespace()
+                    .any(|date_word| date_word == word)
+        })
+        .any(|word| !source.contains(&word))
+}
+
+/// Wrap the first unhighlighted occurrence of each term in `**`, longest terms first
+fn highlight_terms(text: &str, terms: &[KeyTerm]) -> String {
+    let mut sorted: Vec<&str> = terms.iter().map(|t| t.term.as_str()).collect();
+    sorted.sort_by_key(|t| std::cmp::Reverse(t.len()));
+
+    let mut result = text.to_string();
+    for term in sorted {
+        // ASCII lowercasing keeps byte offsets valid for slicing `result`
+        let lower = result.to_ascii_lowercase();
+        let needle = term.to_ascii_lowercase();
+        let found = lower.match_indices(&needle).map(|(i, _)| i).find(|&i| {
+            // Skip matches inside an earlier (longer) highlight
+            result[..i].matches("**").count().is_multiple_of(2)
+        });
+        if let Some(start) = found {
+            let end = start + term.len();
+            result = format!("{}**{}**{}", &result[..start], &result[start..end], &result[end..]);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_highlight_explanation() {
+        let response = "The lender may charge an early repayment fee if you pay off the loan before the fixed rate period ends.\n\nTERMS:\n- Early repayment fee: a charge for paying back early\n- Fixed rate: the interest rate that cannot change\n- nonsense line";
+        let (explanation, terms) = parse_explanation(response);
+
+        assert!(explanation.ends_with("period ends."));
+        assert_eq!(terms.len(), 2);
+        assert_eq!(terms[1].term, "Fixed rate");
+
+        let highlighted = highlight_terms(&explanation, &terms);
+        assert!(highlighted.contains("an **early repayment fee** if"));
+        assert!(highlighted.contains("the **fixed rate** period"));
+    }
+
+    #[test]
+    fn test_parse_thread_summary_and_chunking() {
+        let response = "Summary: The team agreed to ship on Friday.\n\nOpen questions:\n- None\n\nACTION ITEMS:\n- Ana: update the changelog\n* Ben: tag the release";
+        let (summary, actions, questions) = parse_thread_summary(response);
+        assert_eq!(summary, "The team agreed to ship on Friday.");
+        assert_eq!(
+            actions,
+            ["Ana: update the changelog", "Ben: tag the release"]
+        );
+        assert!(questions.is_empty());
+
+        let texts = vec!["a".repeat(30), "b".repeat(30), "word ".repeat(20)];
+        let chunks = chunk_texts(&texts, 70);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 70));
+        // Messages that fit together share a chunk; the long one is split
+        assert_eq!(
+            chunks[0],
+            format!("{}{}{}", "a".repeat(30), THREAD_SEPARATOR, "b".repeat(30))
+        );
+        assert_eq!(chunks.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_reply_suggestions_and_drop_invented_details() {
+        let response = "Here are some options:\n\n**REPLY: Accept**\nSounds good, see you on Friday.\n\nReply 2: (ask for time)\nCan I get back to you by Monday?\nThanks!\nREPLY: decline\n";
+        let suggestions = parse_reply_suggestions(response);
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].intent, "accept");
+        assert_eq!(suggestions[1].intent, "ask for time");
+        assert_eq!(
+            suggestions[1].text,
+            "Can I get back to you by Monday?\nThanks!"
+        );
+
+        let source = "Can we meet on Friday at 3pm? The budget is $1,200.";
+        assert!(!invents_details(&suggestions[0].text, source));
+        assert!(invents_details(&suggestions[1].text, source));
+        let grounded = "Yes, 3pm works and $1,200 is fine. I may be late.";
+        assert!(!invents_details(grounded, source));
+        assert!(invents_details("Sure, I can pay $1,500.", source));
+    }
+}
@@ -1,8 +1,37 @@
-import { useState } from 'react'
+import { useEffect, useState } from 'react'
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+import { errorMessage } from '../../stores/emailStore'
 // Account store available for multi-account "From" dropdown
 // import { useAccountStore } from '../../stores/accountStore'
 
+interface SendComplete {
+  pending_id: string
+  error: { code: string; message: string } | null
+  /** Copy saved to the Sent folder by the app; null when the provider files it */
+  sent_email_id: string | null
+}
+
+/** Matches the backend's AttachmentInput; files picked here are sent as base64 */
+interface AttachmentInput {
+  filename: string
+  data: string
+  content_type?: string
+}
+
+/** Base64 contents of a picked file, without the data: URL prefix */
+function readAsBase64(file: File): Promise<string> {
+  return new Promise((resolve, reject) => {
+    const reader = new FileReader()
+    reader.onload = () => {
+      const url = reader.result as string
+      resolve(url.slice(url.indexOf(',') + 1))
+    }
+    reader.onerror = () => reject(reader.error)
+    reader.readAsDataURL(file)
+  })
+}
+
 interface ComposeModalProps {
   isOpen: boolean
   onClose: () => void
@@ -10,6 +39,9 @@ interface ComposeModalProps {
     to: string
     subject: string
     messageId: string
+    references?: string[]
+    /** Prefilled reply text, e.g. a suggested reply */
+    body?: string
   }
 }
 
@@ -32,9 +64,59 @@ export default function ComposeModal({
   const [sending, setSending] = useState(false)
   const [error, setError] = useState<string | null>(null)
   const [showCc, setShowCc] = useState(false)
+  const [draftUid, setDraftUid] = useState<number | null>(null)
+  // Set while a sent message waits out its undo window
+  const [pendingId, setPendingId] = useState<string | null>(null)
+  const [attachments, setAttachments] = useState<AttachmentInput[]>([])
+  // Send-as aliases of the active account; empty sends from its own address
+  const [aliases, setAliases] = useState<string[]>([])
+  const [from, setFrom] = useState('')
+
+  useEffect(() => {
+    if (isOpen && replyTo?.body) setBody(replyTo.body)
+  }, [isOpen, replyTo?.body])
+
+  useEffect(() => {
+    if (!isOpen) return
+    invoke<string[]>('list_aliases')
+      .then(setAliases)
+      .catch((err) => console.warn('[Compose] Failed to load aliases:', err))
+  }, [isOpen])
+
+  useEffect(() => {
+    if (!pendingId) return
+    const unlisten = listen<SendComplete>('send-complete', (event) => {
+      if (event.payload.pending_id !== pendingId) return
+      setPendingId(null)
+      if (event.payload.error) {
+        setError(event.payload.error.message)
+      } else {
+        onClose()
+      }
+    })
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [pendingId, onClose])
 
   if (!isOpen) return null
 
+  const handleAttach = async (files: FileList | null) => {
+    if (!files) return
+    try {
+      const added = await Promise.all(
+        Array.from(files).map(async (file) => ({
+          filename: file.name,
+          data: await readAsBase64(file),
+          content_type: file.type || undefined,
+        }))
+      )
+      setAttachments((current) => [...current, ...added])
+    } catch (err) {
+      setError(errorMessage(err))
+    }
+  }
+
   const handleSend = async () => {
     if (!to || !subject) {
       setError('Please fill in recipient and subject')
@@ -49,17 +131,53 @@ export default function ComposeModal({
       const ccEmails = cc ? cc.split(',').map((e) => e.trim()) : undefined
       const bccEmails = bcc ? bcc.split(',').map((e) => e.trim()) : undefined
 
-      await invoke('send_email', {
+      const id = await invoke<string>('send_email', {
         to: toEmails,
         subject,
-        body: body.replace(/\n/g, '<br>'),
+        body,
+        htmlBody: body.replace(/\n/g, '<br>'),
         cc: ccEmails,
         bcc: bccEmails,
+        inReplyTo: replyTo?.messageId || undefined,
+        references: replyTo?.references,
+        attachments: attachments.length > 0 ? attachments : undefined,
+        from: from || undefined,
       })
+      setPendingId(id)
+    } catch (err) {
+      setError(errorMessage(err))
+    } finally {
+      setSending(false)
+    }
+  }
+
+  const handleUndo = async () => {
+    if (!pendingId) return
+    try {
+      await invoke('cancel_send', { pendingId })
+      setPendingId(null)
+    } catch (err) {
+      setError(errorMessage(err))
+    }
+  }
+
+  const handleSaveDraft = async () => {
+    setSending(true)
+    setError(null)
 
-      onClose()
+    try {
+      const uid = await invoke<number>('save_draft', {
+        to: to ? to.split(',').map((e) => e.trim()) : [],
+        subject,
+        body,
+        htmlBody: body.replace(/\n/g, '<br>'),
+        cc: cc ? cc.split(',').map((e) => e.trim()) : undefined,
+        bcc: bcc ? bcc.split(',').map((e) => e.trim()) : undefined,
+        draftUid: draftUid ?? undefined,
+      })
+      setDraftUid(uid)
     } catch (err) {
-      setError((err as Error).toString())
+      setError(errorMessage(err))
     } finally {
       setSending(false)
     }
@@ -84,6 +202,27 @@ export default function ComposeModal({
         {/* Form */}
         <div className="flex-1 flex flex-col overflow-hidden">
           <div className="px-8 py-6 space-y-4 border-b-[2px] border-foreground">
+            {/* From, when the account has aliases */}
+            {aliases.length > 0 && (
+              <div className="flex items-center gap-4">
+                <label className="font-mono text-xs uppercase tracking-widest w-16">
+                  From
+                </label>
+                <select
+                  value={from}
+                  onChange={(e) => setFrom(e.target.value)}
+                  className="flex-1 bg-transparent border-b-[2px] border-borderLight focus:border-foreground py-2 font-serif outline-none transition-all"
+                >
+                  <option value="">Account address</option>
+                  {aliases.map((alias) => (
+                    <option key={alias} value={alias}>
+                      {alias}
+                    </option>
+                  ))}
+                </select>
+              </div>
+            )}
+
             {/* To */}
             <div className="flex items-center gap-4">
               <label className="font-mono text-xs uppercase tracking-widest w-16">
@@ -160,6 +299,29 @@ export default function ComposeModal({
               className="w-full h-full bg-transparent font-serif text-lg leading-relaxed resize-none outline-none"
             />
           </div>
+
+          {/* Attachments */}
+          {attachments.length > 0 && (
+            <div className="px-8 py-3 border-t-[2px] border-borderLight flex flex-wrap gap-2">
+              {attachments.map((attachment, index) => (
+                <span
+                  key={`${attachment.filename}-${index}`}
+                  className="flex items-center gap-2 px-3 py-1 border-[2px] border-foreground font-mono text-xs"
+                >
+                  {attachment.filename}
+                  <button
+                    onClick={() =>
+                      setAttachments((current) => current.filter((_, i) => i !== index))
+                    }
+                    aria-label={`Remove ${attachment.filename}`}
+                    className="hover:text-mutedForeground"
+                  >
+                    ×
+                  </button>
+                </span>
+              ))}
+            </div>
+          )}
         </div>
 
         {/* Footer */}
@@ -169,6 +331,18 @@ export default function ComposeModal({
           )}
           <div className="flex-1" />
           <div className="flex gap-4">
+            <label className="px-8 py-3 border-[2px] border-foreground font-mono text-xs uppercase tracking-widest hover:bg-muted transition-all duration-100 cursor-pointer focus-within:outline focus-within:outline-3 focus-within:outline-foreground focus-within:outline-offset-3">
+              Attach
+              <input
+                type="file"
+                multiple
+                className="sr-only"
+                onChange={(e) => {
+                  handleAttach(e.target.files)
+                  e.target.value = ''
+                }}
+              />
+            </label>
             <button
               onClick={onClose}
               disabled={sending}
@@ -177,12 +351,27 @@ export default function ComposeModal({
               Cancel
             </button>
             <button
-              onClick={handleSend}
+              onClick={handleSaveDraft}
               disabled={sending}
+              className="px-8 py-3 border-[2px] border-foreground font-mono text-xs uppercase tracking-widest hover:bg-muted transition-all duration-100 disabled:opacity-50 focus-visible:outline focus-visible:outline-3 focus-visible:outline-foreground focus-visible:outline-offset-3"
+            >
+              {draftUid === null ? 'Save Draft' : 'Update Draft'}
+            </button>
+            <button
+              onClick={handleSend}
+              disabled={sending || pendingId !== null}
               className="px-8 py-3 bg-foreground text-background font-mono text-xs uppercase tracking-widest hover:bg-background hover:text-foreground border-[2px] border-transparent hover:border-foreground transition-all duration-100 disabled:opacity-50 focus-visible:outline focus-visible:outline-3 focus-visible:outline-foreground focus-visible:outline-offset-3"
             >
               {sending ? 'Sending...' : 'Send'}
             </button>
+            {pendingId && (
+              <button
+                onClick={handleUndo}
+                className="px-8 py-3 border-[2px] border-foreground font-mono text-xs uppercase tracking-widest hover:bg-muted transition-all duration-100 focus-visible:outline focus-visible:outline-3 focus-visible:outline-foreground focus-visible:outline-offset-3"
+              >
+                Undo
+              </button>
+            )}
           </div>
         </div>
       </div>
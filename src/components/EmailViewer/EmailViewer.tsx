@@ -2,7 +2,7 @@ import { useState, useEffect, useRef } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { listen, UnlistenFn } from '@tauri-apps/api/event'
 import DOMPurify from 'dompurify'
-import { useEmailStore } from '../../stores/emailStore'
+import { useEmailStore, InlinePartMeta, AuthenticationSummary } from '../../stores/emailStore'
 import { useAiStore } from '../../stores/aiStore'
 import { ComposeModal } from '../Compose'
 
@@ -12,8 +12,33 @@ interface EmailSummary {
   priority: string
 }
 
+interface ReplySuggestion {
+  intent: string
+  text: string
+}
+
+/** Point `cid:` image sources at the embedded inline parts */
+function resolveInlineImages(html: string, parts: InlinePartMeta[] = []): string {
+  return parts.reduce((resolved, part) => {
+    if (!part.data_url) return resolved
+    return resolved.split(`cid:${part.content_id}`).join(part.data_url)
+  }, html)
+}
+
+/** Why the sender can't be trusted, if the receiving server's checks say so */
+function authenticationWarning(auth: AuthenticationSummary | null): string | null {
+  if (!auth) return null
+  if (auth.dmarc.status === 'fail') {
+    return `Failed DMARC: this message may not really be from ${auth.dmarc.domain ?? 'the sender shown'}`
+  }
+  if (auth.dmarc.status === 'none' && auth.spf.status === 'fail' && auth.dkim.status !== 'pass') {
+    return 'Sender not verified: SPF failed and no valid DKIM signature'
+  }
+  return null
+}
+
 export default function EmailViewer() {
-  const { selectedEmail, fetchEmails } = useEmailStore()
+  const { selectedEmail, fetchEmails, loadRemoteContent } = useEmailStore()
   const { isModelLoaded, isAiReady, modelStatus, downloadProgress } = useAiStore()
   const [showCompose, setShowCompose] = useState(false)
   const [actionLoading, setActionLoading] = useState<string | null>(null)
@@ -22,6 +47,9 @@ export default function EmailViewer() {
   const [loadingSummary, setLoadingSummary] = useState(false)
   const [showSummary, setShowSummary] = useState(false)
   const [isStreaming, setIsStreaming] = useState(false)
+  const [replySuggestions, setReplySuggestions] = useState<ReplySuggestion[]>([])
+  const [loadingReplies, setLoadingReplies] = useState(false)
+  const [replyBody, setReplyBody] = useState<string | undefined>(undefined)
   const unlistenRef = useRef<UnlistenFn | null>(null)
 
 
@@ -53,6 +81,7 @@ export default function EmailViewer() {
     setSummary(null)
     setStreamingSummary('')
     setIsStreaming(false)
+    setReplySuggestions([])
   }, [selectedEmail?.id])
 
   // Load summary when showSummary is toggled on
@@ -115,6 +144,26 @@ export default function EmailViewer() {
   }
 
   const handleReply = () => {
+    setReplyBody(undefined)
+    setShowCompose(true)
+  }
+
+  const handleSuggestReplies = async () => {
+    if (!selectedEmail) return
+    setLoadingReplies(true)
+    try {
+      setReplySuggestions(
+        await invoke<ReplySuggestion[]>('suggest_replies', { emailId: selectedEmail.id })
+      )
+    } catch (error) {
+      console.error('Failed to suggest replies:', error)
+    } finally {
+      setLoadingReplies(false)
+    }
+  }
+
+  const handleUseSuggestion = (suggestion: ReplySuggestion) => {
+    setReplyBody(suggestion.text)
     setShowCompose(true)
   }
 
@@ -202,6 +251,13 @@ export default function EmailViewer() {
           >
             Reply
           </button>
+          <button
+            onClick={handleSuggestReplies}
+            disabled={!!actionLoading || loadingReplies || !isAiReady}
+            className="px-6 py-2 border-[2px] border-foreground font-mono text-xs uppercase tracking-widest hover:bg-foreground hover:text-background transition-all duration-100 disabled:opacity-50 focus-visible:outline focus-visible:outline-3 focus-visible:outline-foreground focus-visible:outline-offset-3"
+          >
+            {loadingReplies ? 'Drafting...' : 'Suggest Replies'}
+          </button>
           <button
             onClick={handleArchive}
             disabled={!!actionLoading}
@@ -373,18 +429,66 @@ export default function EmailViewer() {
         </div>
       )}
 
+      {/* Suggested Replies */}
+      {replySuggestions.length > 0 && (
+        <div className="border-b-[2px] border-foreground px-6 lg:px-12 py-6 space-y-3">
+          <h3 className="font-mono text-xs uppercase tracking-widest">Suggested Replies</h3>
+          {replySuggestions.map((suggestion, index) => (
+            <button
+              key={index}
+              onClick={() => handleUseSuggestion(suggestion)}
+              className="block w-full text-left px-4 py-3 border-[2px] border-borderLight hover:border-foreground transition-all duration-100"
+            >
+              <span className="font-mono text-xs uppercase tracking-widest text-mutedForeground">
+                {suggestion.intent}
+              </span>
+              <p className="font-serif whitespace-pre-line mt-1">{suggestion.text}</p>
+            </button>
+          ))}
+        </div>
+      )}
+
       {/* Body */}
       <div className="flex-1 overflow-y-auto">
         <article className="max-w-3xl mx-auto px-6 lg:px-12 py-12">
+          {authenticationWarning(selectedEmail.authentication) && (
+            <div className="mb-8 px-4 py-3 border-[2px] border-red-500">
+              <p className="font-mono text-xs uppercase tracking-widest text-red-600">
+                {authenticationWarning(selectedEmail.authentication)}
+              </p>
+            </div>
+          )}
+          {selectedEmail.remote_content_blocked && (
+            <div className="mb-8 px-4 py-3 border-[2px] border-borderLight flex items-center gap-4 flex-wrap">
+              <p className="font-mono text-xs uppercase tracking-widest text-mutedForeground flex-1">
+                Remote images blocked
+              </p>
+              <button
+                onClick={() => loadRemoteContent(false)}
+                className="font-mono text-xs uppercase tracking-widest underline hover:no-underline"
+              >
+                Load images
+              </button>
+              <button
+                onClick={() => loadRemoteContent(true)}
+                className="font-mono text-xs uppercase tracking-widest underline hover:no-underline"
+              >
+                Always from sender
+              </button>
+            </div>
+          )}
           {selectedEmail.body_html ? (
             <div
               className="font-serif text-lg leading-relaxed email-content"
               dangerouslySetInnerHTML={{
-                __html: DOMPurify.sanitize(selectedEmail.body_html, {
-                  USE_PROFILES: { html: true },
-                  FORBID_TAGS: ['script', 'iframe', 'object', 'embed', 'form'],
-                  FORBID_ATTR: ['onmouseover', 'onclick', 'onerror', 'onload'],
-                }),
+                __html: DOMPurify.sanitize(
+                  resolveInlineImages(selectedEmail.body_html, selectedEmail.inline_parts),
+                  {
+                    USE_PROFILES: { html: true },
+                    FORBID_TAGS: ['script', 'iframe', 'object', 'embed', 'form'],
+                    FORBID_ATTR: ['onmouseover', 'onclick', 'onerror', 'onload'],
+                  }
+                ),
               }}
               style={{
                 color: 'var(--foreground)',
@@ -416,7 +520,9 @@ export default function EmailViewer() {
       replyTo={{
         to: selectedEmail.from_email,
         subject: selectedEmail.subject,
-        messageId: selectedEmail.id,
+        messageId: selectedEmail.message_id,
+        references: selectedEmail.references,
+        body: replyBody,
       }}
     />
   </>
@@ -14,6 +14,9 @@ interface CacheSettings {
     auto_sync_on_start: boolean
     cache_media_assets: boolean
     max_cache_age_days: number
+    max_cache_age_secs: number
+    body_cache_size: number
+    prefetch_bodies: boolean
 }
 
 interface StorageSettingsProps {
@@ -369,6 +372,22 @@ export default function StorageSettings({ onClose }: StorageSettingsProps) {
                             />
                         </label>
 
+                        {/* Prefetch Message Bodies */}
+                        <label className="flex items-center justify-between p-4 border border-borderLight cursor-pointer hover:bg-muted transition-colors">
+                            <div>
+                                <p className="font-mono text-sm font-medium">Download Full Messages</p>
+                                <p className="font-serif text-sm text-mutedForeground">
+                                    Fetch message bodies while syncing instead of when a message is opened
+                                </p>
+                            </div>
+                            <input
+                                type="checkbox"
+                                checked={cacheSettings?.prefetch_bodies ?? false}
+                                onChange={(e) => handleSettingChange('prefetch_bodies', e.target.checked)}
+                                className="w-5 h-5 accent-foreground"
+                            />
+                        </label>
+
                         {/* Max Cache Age */}
                         <div className="flex items-center justify-between p-4 border border-borderLight">
                             <div>
@@ -390,6 +409,27 @@ export default function StorageSettings({ onClose }: StorageSettingsProps) {
                                 <option value={365}>1 year</option>
                             </select>
                         </div>
+
+                        {/* Opened Messages Kept in Memory */}
+                        <div className="flex items-center justify-between p-4 border border-borderLight">
+                            <div>
+                                <p className="font-mono text-sm font-medium">Recently Opened Messages</p>
+                                <p className="font-serif text-sm text-mutedForeground">
+                                    How many opened messages to keep in memory so they reopen instantly
+                                </p>
+                            </div>
+                            <select
+                                value={cacheSettings?.body_cache_size ?? 200}
+                                onChange={(e) => handleSettingChange('body_cache_size', parseInt(e.target.value))}
+                                className="px-4 py-2 border-[2px] border-foreground bg-background font-mono text-sm focus:outline-none"
+                            >
+                                <option value={0}>Off</option>
+                                <option value={50}>50</option>
+                                <option value={200}>200</option>
+                                <option value={500}>500</option>
+                                <option value={1000}>1000</option>
+                            </select>
+                        </div>
                     </div>
                 </div>
 
@@ -2,6 +2,8 @@ import { create } from 'zustand'
 import { invoke } from '@tauri-apps/api/core'
 import { useEmailStore } from './emailStore'
 
+export type TlsMode = 'implicit' | 'start_tls' | 'none'
+
 export interface Account {
   id: string
   email: string
@@ -11,12 +13,76 @@ export interface Account {
   imap_port: number
   smtp_host: string
   smtp_port: number
+  tls_mode: TlsMode
+  smtp_tls_mode: TlsMode | null
   auth_type: string
   is_active: boolean
   created_at: number
   last_synced_at: number | null
 }
 
+export interface ServerConfig {
+  imap_host: string
+  imap_port: number
+  smtp_host: string
+  smtp_port: number
+  tls_mode: TlsMode
+  smtp_tls_mode: TlsMode | null
+}
+
+export interface ServerCandidate {
+  config: ServerConfig
+  auth_type: 'OAuth2' | 'Password'
+  source: 'preset' | 'autoconfig' | 'ispdb' | 'srv' | 'guess'
+  confidence: number
+}
+
+export const discoverServerConfig = (email: string) =>
+  invoke<ServerCandidate[]>('discover_server_config', { email })
+
+export type PresetAuth = 'o_auth' | 'password' | 'app_password' | 'bridge'
+
+/** A well-known provider's settings and how it lets mail apps sign in */
+export interface ServerPreset {
+  id: string
+  name: string
+  provider: 'Gmail' | 'Outlook' | 'Yahoo' | 'Custom'
+  domains: string[]
+  imap_host: string
+  imap_port: number
+  imap_tls: TlsMode
+  smtp_host: string
+  smtp_port: number
+  smtp_tls: TlsMode
+  /** Preferred first */
+  auth: PresetAuth[]
+  auth_hint: string | null
+}
+
+export const getProviderPreset = (email: string) =>
+  invoke<ServerPreset | null>('get_provider_preset', { email })
+
+export type ConnectionCredentials =
+  | { type: 'oauth2'; user: string; access_token: string }
+  | { type: 'password'; user: string; password: string }
+
+export interface ProtocolCheck {
+  ok: boolean
+  capabilities: string[]
+  error: {
+    code: 'bad_credentials' | 'certificate' | 'host_unreachable' | 'timeout' | 'other'
+    message: string
+  } | null
+}
+
+export interface ConnectionCheck {
+  imap: ProtocolCheck
+  smtp: ProtocolCheck
+}
+
+export const testConnection = (serverConfig: ServerConfig, credentials: ConnectionCredentials) =>
+  invoke<ConnectionCheck>('test_connection', { serverConfig, credentials })
+
 interface AccountStore {
   accounts: Account[]
   activeAccountId: string | null
@@ -33,6 +99,8 @@ interface AccountStore {
     imapPort?: number
     smtpHost?: string
     smtpPort?: number
+    tlsMode?: TlsMode
+    smtpTlsMode?: TlsMode
   }) => Promise<Account>
   removeAccount: (accountId: string) => Promise<void>
   setActiveAccount: (accountId: string) => Promise<void>
@@ -72,6 +140,8 @@ export const useAccountStore = create<AccountStore>((set, get) => ({
         imapPort: params.imapPort,
         smtpHost: params.smtpHost,
         smtpPort: params.smtpPort,
+        tlsMode: params.tlsMode,
+        smtpTlsMode: params.smtpTlsMode,
       })
 
       await get().fetchAccounts()
@@ -15,42 +15,290 @@ export interface EmailListItem {
   is_read: boolean
   is_starred: boolean
   has_attachments: boolean
+  is_first_contact: boolean
+  sync_state: SyncState
+  size: number
+}
+
+/** A folder listing page; pass `next_cursor` as `beforeUid` to load the next one */
+export interface EmailPage {
+  items: EmailListItem[]
+  next_cursor: number | null
+}
+
+export type MessageSort = 'date' | 'sender' | 'subject' | 'size'
+
+export type SyncState = 'CachedHeadersOnly' | 'CachedFull' | 'Stale' | 'LiveFetched'
+
+export interface MailingList {
+  id: string | null
+  name: string | null
+  post: string | null
+  archive: string | null
+  unsubscribe: string[]
+}
+
+export interface UnsubscribeInfo {
+  mailto: { to: string; subject: string | null; body: string | null } | null
+  https: string | null
+  /** The sender supports RFC 8058 one-click unsubscribe */
+  one_click: boolean
+}
+
+export interface InviteParticipant {
+  email: string
+  name: string | null
+  /** PARTSTAT as sent, e.g. "NEEDS-ACTION" */
+  status: string | null
+}
+
+export interface Invite {
+  method: 'request' | 'cancel' | 'reply' | 'publish' | 'other'
+  uid: string
+  sequence: number
+  summary: string
+  organizer: InviteParticipant | null
+  attendees: InviteParticipant[]
+  /** Unix seconds; all-day events start at midnight UTC */
+  start: number | null
+  end: number | null
+  all_day: boolean
+  timezone: string | null
+  location: string | null
+  recurrence: {
+    freq: string
+    interval: number
+    count: number | null
+    until: number | null
+    by_day: string[]
+    rule: string
+  } | null
+  recurrence_id: string | null
+}
+
+export type InviteResponse = 'accept' | 'decline' | 'tentative'
+
+export type AuthStatus = 'pass' | 'fail' | 'none'
+
+export interface AuthCheck {
+  status: AuthStatus
+  domain: string | null
+}
+
+/** SPF/DKIM/DMARC verdicts from the receiving server's Authentication-Results */
+export interface AuthenticationSummary {
+  authserv_id: string
+  spf: AuthCheck
+  dkim: AuthCheck
+  dmarc: AuthCheck
 }
 
 export interface Email extends EmailListItem {
   to: string[]
+  cc: string[]
+  /** Replies go here instead of `from` when present */
+  reply_to: string[]
   body_html: string | null
   body_plain: string | null
   labels: string[]
+  mailing_list: MailingList | null
+  unsubscribe: UnsubscribeInfo | null
+  security: MessageSecurity
+  attachments: AttachmentMeta[]
+  /** Parts the HTML body shows via `cid:` URLs */
+  inline_parts: InlinePartMeta[]
+  /** Remote images were replaced by a placeholder; offer "load images" */
+  remote_content_blocked: boolean
+  message_id: string
+  in_reply_to: string | null
+  references: string[]
+  /** Meeting invite or cancellation from a text/calendar part */
+  invite: Invite | null
+  /** Null when the receiving server didn't record any checks */
+  authentication: AuthenticationSummary | null
+}
+
+/** A conversation, as returned by `list_threads` */
+export interface Thread {
+  thread_id: string
+  subject: string
+  participants: string[]
+  message_count: number
+  unread_count: number
+  latest_date: string
+  latest_snippet: string
+  /** Newest first */
+  email_ids: string[]
+}
+
+/** A message waiting in the Snoozed folder (see `snooze_email`) */
+export interface Snooze {
+  account_id: string
+  message_id: string
+  subject: string
+  folder: string
+  /** Unix timestamp it comes back to the inbox at */
+  until: number
+}
+
+/** A filter rule run on new inbox mail (see `add_rule`) */
+export interface Rule {
+  id: number
+  name: string
+  /** null applies the rule to every account */
+  account_id: string | null
+  conditions: Array<
+    | { field: 'from' | 'to' | 'subject'; contains: string }
+    | { field: 'has_attachment' }
+  >
+  actions: Array<
+    | { type: 'move'; folder: string }
+    | { type: 'flag' }
+    | { type: 'mark_read' }
+    | { type: 'categorize'; category: string }
+  >
+  /** Skip later rules once this one matches */
+  stop_processing: boolean
+}
+
+export interface AttachmentMeta {
+  /** IMAP part number, e.g. "2" or "1.3" */
+  part_id: string
+  filename: string
+  content_type: string
+  size: number
+}
+
+/** Inline part of an HTML message; fetch larger ones with `get_inline_part` */
+export interface InlinePartMeta {
+  /** Content-ID without angle brackets */
+  content_id: string
+  part_id: string
+  content_type: string
+  size: number
+  /** Set for parts small enough to embed */
+  data_url: string | null
+}
+
+export interface MessageSecurity {
+  encrypted: boolean
+  signed: boolean
+  scheme: 'pgp_mime' | 'pgp_inline' | 'smime' | null
+  signature: 'unverified' | null
 }
 
 interface NewMailEvent {
   account_id: string
   folder: string
+  new_uids: number[]
+  count: number
+  expunged_uids?: number[]
+}
+
+export type SpecialFolder = 'Inbox' | 'Sent' | 'Trash' | 'Drafts' | 'Spam' | 'Archive' | 'Starred'
+
+export interface Folder {
+  name: string
+  raw_name: string
+  display_name: string
+  special: SpecialFolder | null
+  delimiter: string | null
+  special_use: string[]
+  selectable: boolean
 }
 
 export interface FolderStats {
   folder_name: string
   total_count: number
   unread_count: number
+  ok: boolean
+  error: string | null
+}
+
+export type EmailErrorCode =
+  | 'auth_expired'
+  | 'not_found'
+  | 'network'
+  | 'server_rejected'
+  | 'invalid_id'
+  | 'uid_validity_changed'
+  | 'other'
+
+/** Rejection value of the email commands */
+export interface EmailError {
+  code: EmailErrorCode
+  message: string
+}
+
+export function isEmailError(error: unknown): error is EmailError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error
+}
+
+/** Message to show for a rejected invoke, whatever shape it has */
+export function errorMessage(error: unknown): string {
+  if (isEmailError(error)) return error.message
+  return error instanceof Error ? error.message : String(error)
+}
+
+export interface FolderFlagResult {
+  account_id: string
+  folder: string
+  count: number
+  ok: boolean
+  error: EmailError | null
+}
+
+export type SyncPhase = 'connecting' | 'searching' | 'fetching' | 'embedding'
+
+export interface FolderSyncSummary {
+  folder: string
+  listed: number
+  fetched: number
+  failed: number
+  bytes: number
+  classified: number
+  embedded: number
+  cancelled: boolean
+  elapsed_ms: number
+}
+
+/** Messages sent over the `sync_folder` channel */
+export type FolderSyncEvent =
+  | { event: 'progress'; request_id: string; phase: SyncPhase; current: number; total: number; bytes: number }
+  | { event: 'completed'; request_id: string; summary: FolderSyncSummary }
+
+function commandError(error: unknown): { error: string; errorCode: EmailErrorCode | null } {
+  return { error: errorMessage(error), errorCode: isEmailError(error) ? error.code : null }
 }
 
 const POLLING_INTERVAL_MS = 10 * 60 * 1000 // 10 minutes
 
 interface EmailStore {
   emails: EmailListItem[]
+  nextCursor: number | null
   selectedEmail: Email | null
   currentFolder: string
+  folders: Folder[]
   folderStats: FolderStats[]
   loading: boolean
   refreshing: boolean
   error: string | null
+  /** Set with `error` when it came from an email command; 'auth_expired' means re-authenticate */
+  errorCode: EmailErrorCode | null
   pollingInterval: ReturnType<typeof setInterval> | null
   unlistenNewMail: UnlistenFn | null
   fetchEmails: (maxResults?: number, query?: string, forceRefresh?: boolean, folder?: string) => Promise<void>
+  fetchMoreEmails: (maxResults?: number) => Promise<void>
   syncOtherFolders: () => Promise<void>
+  fetchFolders: () => Promise<void>
   fetchFolderStats: () => Promise<void>
   selectEmail: (emailId: string) => Promise<void>
+  loadRemoteContent: (alwaysForSender: boolean) => Promise<void>
+  /** Structured plain text of the open email, for read-aloud or a text view */
+  loadReadableText: () => Promise<string | null>
+  /** Email the organizer of the open invite our answer */
+  respondToInvite: (response: InviteResponse) => Promise<boolean>
+  markEmailsRead: (emailIds: string[], read: boolean) => Promise<void>
   clearSelection: () => void
   setFolder: (folder: string) => Promise<void>
   setupNewMailListener: () => Promise<UnlistenFn>
@@ -61,12 +309,15 @@ interface EmailStore {
 
 export const useEmailStore = create<EmailStore>((set, get) => ({
   emails: [],
+  nextCursor: null,
   selectedEmail: null,
   currentFolder: 'INBOX',
+  folders: [],
   folderStats: [],
   loading: false,
   refreshing: false,
   error: null,
+  errorCode: null,
   pollingInterval: null,
   unlistenNewMail: null,
 
@@ -75,20 +326,20 @@ export const useEmailStore = create<EmailStore>((set, get) => ({
       const state = get()
       // If we already have emails and this is a refresh, don't show full loader
       if (state.emails.length > 0 && forceRefresh) {
-        set({ refreshing: true, error: null })
+        set({ refreshing: true, error: null, errorCode: null })
       } else {
-        set({ loading: true, error: null })
+        set({ loading: true, error: null, errorCode: null })
       }
 
       const currentFolder = folder || state.currentFolder
-      const emails = await invoke<EmailListItem[]>('fetch_emails', {
+      const page = await invoke<EmailPage>('fetch_emails', {
         maxResults,
         query,
         forceRefresh,
         folder: currentFolder,
       })
       
-      set({ emails, loading: false, refreshing: false })
+      set({ emails: page.items, nextCursor: page.next_cursor, loading: false, refreshing: false })
 
       // After a force-refresh, re-index new emails in the background
       if (forceRefresh) {
@@ -109,7 +360,28 @@ export const useEmailStore = create<EmailStore>((set, get) => ({
         }
       }
     } catch (error) {
-      set({ error: (error as Error).toString(), loading: false, refreshing: false })
+      set({ ...commandError(error), loading: false, refreshing: false })
+    }
+  },
+
+  fetchMoreEmails: async (maxResults = 50) => {
+    const state = get()
+    if (state.nextCursor === null || state.loading) return
+    try {
+      set({ loading: true, error: null, errorCode: null })
+      const page = await invoke<EmailPage>('fetch_emails', {
+        maxResults,
+        folder: state.currentFolder,
+        beforeUid: state.nextCursor,
+      })
+      // An empty page is the end of the folder
+      set({
+        emails: [...get().emails, ...page.items],
+        nextCursor: page.next_cursor,
+        loading: false,
+      })
+    } catch (error) {
+      set({ ...commandError(error), loading: false })
     }
   },
 
@@ -132,11 +404,51 @@ export const useEmailStore = create<EmailStore>((set, get) => ({
 
   selectEmail: async (emailId: string) => {
     try {
-      set({ loading: true, error: null })
+      set({ loading: true, error: null, errorCode: null })
       const email = await invoke<Email>('get_email', { emailId })
       set({ selectedEmail: email, loading: false })
     } catch (error) {
-      set({ error: (error as Error).toString(), loading: false })
+      set({ ...commandError(error), loading: false })
+    }
+  },
+
+  loadRemoteContent: async (alwaysForSender: boolean) => {
+    const selected = get().selectedEmail
+    if (!selected) return
+    try {
+      if (alwaysForSender) {
+        await invoke('allow_remote_content', { sender: selected.from_email })
+      }
+      const email = await invoke<Email>('load_remote_content', { emailId: selected.id })
+      // Ignore the result if another email was opened meanwhile
+      if (get().selectedEmail?.id === email.id) {
+        set({ selectedEmail: email })
+      }
+    } catch (error) {
+      set(commandError(error))
+    }
+  },
+
+  loadReadableText: async () => {
+    const selected = get().selectedEmail
+    if (!selected) return null
+    try {
+      return await invoke<string>('get_readable_text', { emailId: selected.id })
+    } catch (error) {
+      set(commandError(error))
+      return null
+    }
+  },
+
+  respondToInvite: async (response: InviteResponse) => {
+    const selected = get().selectedEmail
+    if (!selected?.invite) return false
+    try {
+      await invoke('respond_to_invite', { emailId: selected.id, response })
+      return true
+    } catch (error) {
+      set(commandError(error))
+      return false
     }
   },
 
@@ -146,7 +458,7 @@ export const useEmailStore = create<EmailStore>((set, get) => ({
 
   setFolder: async (folder: string) => {
     // Clear current emails to avoid showing stale data
-    set({ emails: [], selectedEmail: null, currentFolder: folder })
+    set({ emails: [], nextCursor: null, selectedEmail: null, currentFolder: folder })
     const state = get()
     // 1. Fetch from DB first (fast)
     await state.fetchEmails(50, undefined, false, folder)
@@ -154,6 +466,15 @@ export const useEmailStore = create<EmailStore>((set, get) => ({
     state.fetchEmails(50, undefined, true, folder)
   },
 
+  fetchFolders: async () => {
+    try {
+      const folders = await invoke<Folder[]>('list_folders')
+      set({ folders })
+    } catch (error) {
+      console.warn('[EmailStore] Failed to list folders:', error)
+    }
+  },
+
   fetchFolderStats: async () => {
     try {
       const stats = await invoke<FolderStats[]>('get_folder_stats')
@@ -166,9 +487,16 @@ export const useEmailStore = create<EmailStore>((set, get) => ({
   setupNewMailListener: async () => {
     const unlisten = await listen<NewMailEvent>('email:new_mail', (event) => {
       console.log('[EmailStore] New mail detected:', event.payload)
-      // Auto-refresh email list and folder stats when new mail arrives
+      const { account_id, folder, count, expunged_uids } = event.payload
       const { fetchEmails, fetchFolderStats } = useEmailStore.getState()
-      fetchEmails(50, undefined, true)
+
+      // Removals only: drop the rows instead of re-fetching the list
+      if (count === 0 && expunged_uids?.length) {
+        const removed = new Set(expunged_uids.map((uid) => `${account_id}:${folder}:${uid}`))
+        set((state) => ({ emails: state.emails.filter((e) => !removed.has(e.id)) }))
+      } else {
+        fetchEmails(50, undefined, true)
+      }
       fetchFolderStats()
     })
     return unlisten
@@ -198,7 +526,7 @@ export const useEmailStore = create<EmailStore>((set, get) => ({
 
     // 6. Start IDLE monitoring
     try {
-      await invoke('start_idle_monitoring')
+      await invoke<string[]>('start_idle_monitoring')
     } catch (e) {
       console.warn('[EmailStore] IDLE monitoring failed to start:', e)
     }
@@ -233,6 +561,18 @@ export const useEmailStore = create<EmailStore>((set, get) => ({
     }
   },
 
+  markEmailsRead: async (emailIds: string[], read: boolean) => {
+    const results = await invoke<FolderFlagResult[]>('mark_emails_read', { emailIds, read })
+    const failed = results.filter((r) => !r.ok)
+    failed.forEach((r) => console.warn(`[EmailStore] Failed to update flags in ${r.folder}:`, r.error?.message))
+    // Only reflect the change for messages whose folder succeeded
+    const failedPrefixes = failed.map((r) => `${r.account_id}:${r.folder}:`)
+    const updated = new Set(emailIds.filter((id) => !failedPrefixes.some((p) => id.startsWith(p))))
+    set((state) => ({
+      emails: state.emails.map((e) => (updated.has(e.id) ? { ...e, is_read: read } : e)),
+    }))
+  },
+
   refreshEmails: async () => {
     await get().fetchEmails(50, undefined, true)
   },
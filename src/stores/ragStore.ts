@@ -11,9 +11,25 @@ export interface EmbeddingStatus {
     error_message: string | null
 }
 
+export interface CategoryDefinition {
+    name: string
+    description: string
+}
+
+export const listCategories = () => invoke<CategoryDefinition[]>('list_categories')
+
+export const setCategory = (name: string, description: string) =>
+    invoke<CategoryDefinition[]>('set_category', { name, description })
+
+export const removeCategory = (name: string) =>
+    invoke<CategoryDefinition[]>('remove_category', { name })
+
+export const resetCategories = () => invoke<CategoryDefinition[]>('reset_categories')
+
 export interface SearchResult {
     email_id: string
     similarity: number
+    score: number
     subject: string | null
     from: string | null
     snippet: string | null
@@ -42,9 +58,9 @@ interface RagStore {
     checkModelDownloaded: () => Promise<boolean>
     downloadAndInitRag: () => Promise<boolean>
     getEmbeddingStatus: () => Promise<void>
-    embedAllEmails: () => Promise<number>
+    embedAllEmails: (batchSize?: number) => Promise<number>
     embedEmail: (emailId: string, subject: string, from: string, body: string) => Promise<void>
-    searchSemantic: (query: string, limit?: number) => Promise<SearchResult[]>
+    searchSemantic: (query: string, limit?: number, rerank?: boolean) => Promise<SearchResult[]>
     findSimilarEmails: (emailId: string, limit?: number) => Promise<SearchResult[]>
     getEmbeddedCount: () => Promise<number>
     clearEmbeddings: () => Promise<void>
@@ -132,7 +148,7 @@ export const useRagStore = create<RagStore>((set, get) => ({
         }
     },
 
-    embedAllEmails: async () => {
+    embedAllEmails: async (batchSize?: number) => {
         let progressUnlisten: UnlistenFn | null = null
         let completeUnlisten: UnlistenFn | null = null
 
@@ -152,7 +168,7 @@ export const useRagStore = create<RagStore>((set, get) => ({
                 })
             })
 
-            const count = await invoke<number>('embed_all_emails')
+            const count = await invoke<number>('embed_all_emails', { batchSize })
 
             // Refresh status
             await get().getEmbeddingStatus()
@@ -178,10 +194,14 @@ export const useRagStore = create<RagStore>((set, get) => ({
         }
     },
 
-    searchSemantic: async (query: string, limit = 10) => {
+    searchSemantic: async (query: string, limit = 10, rerank = false) => {
         try {
             set({ error: null })
-            const results = await invoke<SearchResult[]>('search_emails_semantic', { query, limit })
+            const results = await invoke<SearchResult[]>('search_emails_semantic', {
+                query,
+                limit,
+                rerank,
+            })
             set({ searchResults: results })
             return results
         } catch (error) {